@@ -0,0 +1,101 @@
+use std::borrow::Cow;
+
+use candid::CandidType;
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+use serde::{Deserialize, Serialize};
+
+use crate::public_key::PublicKey;
+use crate::user_canister::OwnerKey;
+use crate::utils::trap;
+
+/// Identifier of a device of a user, unique among the devices of the user
+pub type DeviceId = u32;
+
+/// Maximum size of the name of a device, in bytes
+pub const MAX_DEVICE_NAME_SIZE: usize = 64;
+
+/// Public key of a device of a user.
+///
+/// Each device, such as a browser, holds its own key pair, so that private keys never have to
+/// be copied between devices.
+///
+/// ## Encoding
+///
+/// - 4 bytes: device ID.
+/// - 1 byte: length of the name.
+/// - N bytes: name.
+/// - N bytes: public key.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeviceKey {
+    /// The ID of the device
+    pub device_id: DeviceId,
+    /// The name given to the device by the user
+    pub name: String,
+    /// The public key of the device
+    pub public_key: PublicKey,
+}
+
+impl Storable for DeviceKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 4 + 1 + MAX_DEVICE_NAME_SIZE as u32 + PublicKey::BOUND.max_size(),
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes =
+            Vec::with_capacity(4 + 1 + self.name.len() + self.public_key.encoding_size());
+        bytes.extend_from_slice(&self.device_id.to_le_bytes());
+        bytes.push(self.name.len() as u8);
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(&self.public_key.to_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        if bytes.len() < 5 {
+            trap("Failed to decode DeviceKey: not enough bytes");
+        }
+        let device_id = DeviceId::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let name_len = bytes[4] as usize;
+        let offset = 5 + name_len;
+        if bytes.len() < offset + PublicKey::KEY_LEN_SIZE {
+            trap("Failed to decode DeviceKey: not enough bytes for name and public key");
+        }
+        let name = String::from_utf8_lossy(&bytes[5..offset]).to_string();
+        let public_key = PublicKey::from_bytes(bytes[offset..].to_vec().into());
+
+        Self {
+            device_id,
+            name,
+            public_key,
+        }
+    }
+}
+
+/// The document key of a file, encrypted with the public key of a device
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceOwnerKey {
+    /// The ID of the device the key is encrypted for
+    pub device_id: DeviceId,
+    /// The encrypted document key
+    pub key: OwnerKey,
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_device_key_roundtrip() {
+        let device_key = DeviceKey {
+            device_id: 3,
+            name: "Laptop".to_string(),
+            public_key: PublicKey::try_from(vec![1, 2, 3, 4, 5]).unwrap(),
+        };
+        let decoded = DeviceKey::from_bytes(device_key.to_bytes());
+        assert_eq!(device_key, decoded);
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt::Write as _;
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+/// Path under which the canisters serve their metrics
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Request for the http_request method, as sent by the HTTP gateway
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// The HTTP method
+    pub method: String,
+    /// The requested URL, including the query string
+    pub url: String,
+    /// The request headers
+    pub headers: Vec<(String, String)>,
+    /// The request body
+    pub body: ByteBuf,
+}
+
+impl HttpRequest {
+    /// Get the path of the requested URL, without the query string.
+    pub fn path(&self) -> &str {
+        self.url.split(['?', '#']).next().unwrap_or_default()
+    }
+}
+
+/// Response for the http_request method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The HTTP status code
+    pub status_code: u16,
+    /// The response headers
+    pub headers: Vec<(String, String)>,
+    /// The response body
+    pub body: ByteBuf,
+}
+
+impl HttpResponse {
+    /// A `200 OK` response with metrics in the Prometheus text format.
+    pub fn metrics(metrics: String) -> Self {
+        Self {
+            status_code: 200,
+            headers: vec![
+                (
+                    "Content-Type".to_string(),
+                    "text/plain; version=0.0.4".to_string(),
+                ),
+                ("Cache-Control".to_string(), "no-store".to_string()),
+            ],
+            body: ByteBuf::from(metrics.into_bytes()),
+        }
+    }
+
+    /// A `404 Not Found` response.
+    pub fn not_found() -> Self {
+        Self::text(404, "Not found")
+    }
+
+    /// A `405 Method Not Allowed` response.
+    pub fn method_not_allowed() -> Self {
+        Self::text(405, "Method not allowed")
+    }
+
+    fn text(status_code: u16, body: &str) -> Self {
+        Self {
+            status_code,
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            body: ByteBuf::from(body.as_bytes()),
+        }
+    }
+}
+
+/// Encoder of metrics in the Prometheus text format.
+///
+/// See <https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format>.
+#[derive(Debug, Default)]
+pub struct MetricsEncoder {
+    buf: String,
+}
+
+impl MetricsEncoder {
+    /// Encode a gauge, which is a value that can go up and down.
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) -> &mut Self {
+        self.header(name, help);
+        self.sample(name, &[], value);
+        self
+    }
+
+    /// Encode a gauge with one sample for each value of a label.
+    pub fn gauge_vec<'a>(
+        &mut self,
+        name: &str,
+        help: &str,
+        label: &str,
+        values: impl IntoIterator<Item = (&'a str, f64)>,
+    ) -> &mut Self {
+        self.header(name, help);
+        for (label_value, value) in values {
+            self.sample(name, &[(label, label_value)], value);
+        }
+        self
+    }
+
+    /// Get the encoded metrics.
+    pub fn finish(self) -> String {
+        self.buf
+    }
+
+    fn header(&mut self, name: &str, help: &str) {
+        let _ = writeln!(self.buf, "# HELP {name} {help}");
+        let _ = writeln!(self.buf, "# TYPE {name} gauge");
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.buf.push_str(name);
+        if !labels.is_empty() {
+            let labels = labels
+                .iter()
+                .map(|(label, value)| format!("{label}=\"{}\"", escape_label_value(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = write!(self.buf, "{{{labels}}}");
+        }
+        let _ = writeln!(self.buf, " {value}");
+    }
+}
+
+/// Escape a label value, as required by the Prometheus text format.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_encode_metrics() {
+        let mut encoder = MetricsEncoder::default();
+        encoder
+            .gauge("users", "Number of users", 3.0)
+            .gauge_vec(
+                "canisters",
+                "Canisters by state",
+                "state",
+                [("created", 2.0), ("fail\"ed", 1.0)],
+            )
+            .gauge("cycles", "Cycle balance", 1_500_000_000_000.0);
+
+        assert_eq!(
+            encoder.finish(),
+            "# HELP users Number of users\n\
+             # TYPE users gauge\n\
+             users 3\n\
+             # HELP canisters Canisters by state\n\
+             # TYPE canisters gauge\n\
+             canisters{state=\"created\"} 2\n\
+             canisters{state=\"fail\\\"ed\"} 1\n\
+             # HELP cycles Cycle balance\n\
+             # TYPE cycles gauge\n\
+             cycles 1500000000000\n"
+        );
+    }
+
+    #[test]
+    fn test_should_get_request_path() {
+        let request = |url: &str| HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: ByteBuf::new(),
+        };
+        assert_eq!(request("/metrics").path(), "/metrics");
+        assert_eq!(request("/metrics?time=1").path(), "/metrics");
+        assert_eq!(request("/").path(), "/");
+    }
+}
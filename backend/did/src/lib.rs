@@ -3,6 +3,8 @@
 // use ic_stable_structures::Storable;
 // use ic_stable_structures::storable::Bound;
 
+mod device_key;
+pub mod http;
 #[rustfmt::skip]
 #[allow(clippy::all)]
 #[allow(deprecated)]
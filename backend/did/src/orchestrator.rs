@@ -1,23 +1,67 @@
+mod admin;
+mod block_list;
+mod contact;
+mod device_key;
+mod invite;
+mod linked_principal;
 mod pagination;
 mod public_file_metadata;
+mod share_log;
 mod shared_files;
 mod user;
 mod user_canister;
+mod username;
 mod whoami;
 
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 
-pub use self::pagination::Pagination;
+pub use self::admin::{
+    AdminFailedUserCanistersResponse, AdminReleaseUsernameResponse, AdminReserveUsernameResponse,
+    AdminReservedUsernamesResponse, AdminRetryUserCanisterCreationResponse, AdminStatsResponse,
+    AdminSuspendUserResponse, AdminUnsuspendUserResponse, AdminUser, AdminUserCanisterState,
+    AdminUsernameCollisionsResponse, AdminUsers, AdminUsersResponse, FailedUserCanister,
+    FailedUserCanisters, OrchestratorStats,
+};
+pub use self::block_list::{BlockResponse, GetBlockedResponse, UnblockResponse};
+pub use self::contact::{
+    AddContactRequest, AddContactResponse, Contact, Contacts, ContactsResponse,
+    MAX_CONTACT_NICKNAME_SIZE, RemoveContactResponse,
+};
+pub use self::device_key::{
+    AddDeviceKeyRequest, AddDeviceKeyResponse, MAX_DEVICES_PER_USER, RemoveDeviceKeyResponse,
+};
+pub use self::invite::{
+    CreateInviteRequest, CreateInviteResponse, Invite, InviteState, InvitesResponse,
+    MAX_INVITES_PER_USER, RevokeInviteResponse,
+};
+pub use self::linked_principal::{
+    ApprovePrincipalLinkResponse, LinkedPrincipals, LinkedPrincipalsResponse,
+    MAX_LINK_REQUESTS_PER_ACCOUNT, RequestPrincipalLinkResponse, UnlinkPrincipalResponse,
+};
+pub use self::pagination::{CursorPagination, FileCursorPagination, Pagination};
 pub use self::public_file_metadata::PublicFileMetadata;
+pub use self::share_log::{
+    BlockHash, DataCertificate, GetBlocksRequest, GetBlocksResponse, ShareBlock, ShareBlockWithId,
+    ShareOperation,
+};
 pub use self::shared_files::{
-    FileId, RevokeShareFileResponse, ShareFileMetadata, ShareFileResponse, SharedFilesResponse,
+    DeclineShareResponse, FileId, FileShares, RevokeShareFileResponse, ShareFileMetadata,
+    ShareFileResponse, ShareState, ShareStateResponse, SharedByMeFile, SharedByMeFiles,
+    SharedByMeResponse, SharedFilesResponse, UserCanisterShares, UserCanisterSharesResponse,
 };
 pub use self::user::{
-    GetUsersResponse, GetUsersResponseUsers, MAX_USERNAME_SIZE, PublicUser, SetUserResponse, User,
+    DeleteAccountRequest, DeleteAccountResponse, GetUsersResponse, GetUsersResponseUsers,
+    MAX_DISPLAY_NAME_SIZE, MAX_USERNAME_SIZE, PublicUser, SearchUsersResponse,
+    SearchUsersResponseUsers, SetUserResponse, UpdateProfileRequest, UpdateProfileResponse,
+    UpdatePublicKeyResponse, UpdateUserPublicKeyResponse, User, UserProfile, UsernameCollision,
+};
+pub use self::user_canister::{
+    GetUserCanisterResponse, RetryUserCanisterCreationResponse, UserCanisterResponse,
 };
-pub use self::user_canister::{RetryUserCanisterCreationResponse, UserCanisterResponse};
+pub use self::username::{normalize_username, username_key};
 pub use self::whoami::WhoamiResponse;
+pub use crate::device_key::{DeviceId, DeviceKey, MAX_DEVICE_NAME_SIZE};
 pub use crate::public_key::PublicKey;
 
 /// Orchestrator canister install arguments
@@ -26,7 +70,7 @@ pub enum OrchestratorInstallArgs {
     /// Arguments for the `init` method
     Init(OrchestratorInitArgs),
     /// Arguments for the `post_upgrade` method
-    Upgrade,
+    Upgrade(OrchestratorUpgradeArgs),
 }
 
 /// Orchestrator canister `init` arguments
@@ -36,4 +80,13 @@ pub struct OrchestratorInitArgs {
     pub orbit_station_admin: String,
     /// Principal of the Orbit Station canister
     pub orbit_station: Principal,
+    /// Principals allowed to call the admin methods
+    pub admins: Vec<Principal>,
+}
+
+/// Orchestrator canister `post_upgrade` arguments
+#[derive(Debug, CandidType, Serialize, Deserialize)]
+pub struct OrchestratorUpgradeArgs {
+    /// Principals allowed to call the admin methods; `None` keeps the current admins
+    pub admins: Option<Vec<Principal>>,
 }
@@ -0,0 +1,186 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use super::{PublicUser, UsernameCollision};
+
+/// State of the user canister of a user, as seen by the admins
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminUserCanisterState {
+    /// The user canister is created and ready to use
+    Created(Principal),
+    /// The user canister is being created
+    CreationPending,
+    /// The user canister creation failed; returns the reason
+    CreationFailed { reason: String },
+    /// The creation is not started yet
+    Uninitialized,
+}
+
+/// A user along with their user canister, as seen by the admins
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminUser {
+    /// The public information of the user
+    pub user: PublicUser,
+    /// The state of the user canister of the user
+    pub user_canister: AdminUserCanisterState,
+    /// When the user was suspended, in nanoseconds since the epoch; `None` if not suspended
+    pub suspended_at: Option<u64>,
+}
+
+/// Page of users returned by the `admin_users` method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminUsers {
+    /// Returned users, ordered by principal
+    pub users: Vec<AdminUser>,
+    /// The cursor of the next page. If None, there are no more users to fetch
+    pub next: Option<Principal>,
+    /// Total number of users
+    pub total: u64,
+}
+
+/// Response for the admin_users method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminUsersResponse {
+    /// The users
+    Users(AdminUsers),
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// A user whose user canister creation failed
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FailedUserCanister {
+    /// The user
+    pub user: Principal,
+    /// The reason of the failure
+    pub reason: String,
+}
+
+/// Page of failed user canister creations returned by the `admin_failed_user_canisters` method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FailedUserCanisters {
+    /// Returned failed creations, ordered by user principal
+    pub failed: Vec<FailedUserCanister>,
+    /// The cursor of the next page. If None, there are no more failed creations to fetch
+    pub next: Option<Principal>,
+}
+
+/// Response for the admin_failed_user_canisters method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminFailedUserCanistersResponse {
+    /// The failed user canister creations
+    Failed(FailedUserCanisters),
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Response for the admin_retry_user_canister_creation method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminRetryUserCanisterCreationResponse {
+    /// The user canister creation is retried
+    Ok,
+    /// The user canister exists
+    Created(Principal),
+    /// Creation is already in progress
+    CreationPending,
+    /// The user doesn't exist
+    NoSuchUser,
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Aggregate statistics of the orchestrator
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OrchestratorStats {
+    /// Number of users
+    pub users: u64,
+    /// Number of created user canisters
+    pub user_canisters: u64,
+    /// Number of user canisters being created
+    pub pending_user_canisters: u64,
+    /// Number of failed user canister creations
+    pub failed_user_canisters: u64,
+    /// Number of accounts being deleted
+    pub pending_account_deletions: u64,
+    /// Number of failed account deletions
+    pub failed_account_deletions: u64,
+    /// Number of suspended users
+    pub suspended_users: u64,
+    /// Number of blocks in the share log
+    pub share_log_length: u64,
+}
+
+/// Response for the admin_stats method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminStatsResponse {
+    /// The statistics of the orchestrator
+    Stats(OrchestratorStats),
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Response for the admin_suspend_user method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminSuspendUserResponse {
+    /// The user is suspended
+    Ok,
+    /// The user doesn't exist
+    NoSuchUser,
+    /// Failed to suspend the user on their user canister; nothing was changed
+    FailedToUpdateUserCanister(String),
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Response for the admin_unsuspend_user method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminUnsuspendUserResponse {
+    /// The user is not suspended anymore
+    Ok,
+    /// The user is not suspended
+    NotSuspended,
+    /// Failed to reinstate the user on their user canister; nothing was changed
+    FailedToUpdateUserCanister(String),
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Response for the admin_username_collisions method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminUsernameCollisionsResponse {
+    /// The usernames shared by several users
+    Collisions(Vec<UsernameCollision>),
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Response for the admin_reserve_username method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminReserveUsernameResponse {
+    /// The username is reserved
+    Ok,
+    /// The username is too long, or contains characters which are not allowed
+    InvalidUsername,
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Response for the admin_release_username method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminReleaseUsernameResponse {
+    /// The username is not reserved anymore
+    Ok,
+    /// The username is not reserved
+    NotReserved,
+    /// The caller is not an admin
+    Unauthorized,
+}
+
+/// Response for the admin_reserved_usernames method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminReservedUsernamesResponse {
+    /// The reserved usernames, ordered by their canonical key
+    Usernames(Vec<String>),
+    /// The caller is not an admin
+    Unauthorized,
+}
@@ -0,0 +1,37 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+/// Response for the block method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum BlockResponse {
+    /// The principal was blocked
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The principal is neither a user nor a user canister
+    UnknownPrincipal,
+    /// The caller can't block themselves
+    CannotBlockSelf,
+}
+
+/// Response for the unblock method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UnblockResponse {
+    /// The principal was unblocked
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The principal is not blocked by the caller
+    NotBlocked,
+}
+
+/// Response for the get_blocked method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum GetBlockedResponse {
+    /// The users and user canisters blocked by the caller
+    Blocked(Vec<Principal>),
+    /// The caller is anonymous
+    AnonymousCaller,
+}
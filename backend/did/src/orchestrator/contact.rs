@@ -0,0 +1,78 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use super::PublicUser;
+
+/// Maximum size of the nickname of a contact, in bytes
+pub const MAX_CONTACT_NICKNAME_SIZE: usize = 64;
+
+/// A user in the contacts of another user
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Contact {
+    /// The public information of the contact
+    pub user: PublicUser,
+    /// The nickname given to the contact
+    pub nickname: Option<String>,
+    /// When a file was last shared with the contact, in nanoseconds since the epoch; `None` if
+    /// no file was shared with them
+    pub last_shared_at: Option<u64>,
+}
+
+/// Request for the add_contact method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AddContactRequest {
+    /// The user to add to the contacts
+    pub user: Principal,
+    /// The nickname to give to the contact; `None` or an empty nickname removes it
+    pub nickname: Option<String>,
+}
+
+/// Response for the add_contact method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AddContactResponse {
+    /// The contact was added, or its nickname was updated
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The user to add doesn't exist
+    NoSuchContact,
+    /// The caller can't add themselves to their contacts
+    CannotAddSelf,
+    /// The nickname is too long
+    NicknameTooLong,
+}
+
+/// Response for the remove_contact method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RemoveContactResponse {
+    /// The contact was removed
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The user is not in the contacts of the caller
+    NoSuchContact,
+}
+
+/// Page of contacts returned by the `contacts` method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Contacts {
+    /// Returned contacts, the most recently shared with first
+    pub contacts: Vec<Contact>,
+    /// The next page offset. If None, there are no more contacts to fetch
+    pub next: Option<u64>,
+    /// Total number of contacts
+    pub total: u64,
+}
+
+/// Response for the contacts method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ContactsResponse {
+    /// The contacts of the caller
+    Contacts(Contacts),
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+}
@@ -0,0 +1,50 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use super::{DeviceId, PublicKey};
+
+/// Maximum number of devices of a user
+pub const MAX_DEVICES_PER_USER: usize = 16;
+
+/// Request for the add_device_key method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AddDeviceKeyRequest {
+    /// The name of the device
+    pub name: String,
+    /// The public key of the device
+    pub public_key: PublicKey,
+}
+
+/// Response for the add_device_key method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AddDeviceKeyResponse {
+    /// The device key was added, with the ID of the device
+    Ok(DeviceId),
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The name of the device is too long
+    NameTooLong,
+    /// The caller has already [`MAX_DEVICES_PER_USER`] devices
+    TooManyDevices,
+    /// The user canister of the caller is not created yet
+    UserCanisterNotReady,
+    /// Failed to add the device key on the user canister; nothing was changed
+    FailedToUpdateUserCanister(String),
+}
+
+/// Response for the remove_device_key method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RemoveDeviceKeyResponse {
+    /// The device key was removed
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The caller has no such device
+    NoSuchDevice,
+    /// Failed to remove the device key on the user canister; nothing was changed
+    FailedToUpdateUserCanister(String),
+}
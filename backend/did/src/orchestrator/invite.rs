@@ -0,0 +1,81 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use super::{FileId, ShareFileMetadata};
+
+/// Maximum number of invites a user can have at once
+pub const MAX_INVITES_PER_USER: usize = 64;
+
+/// Request for the create_invite method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CreateInviteRequest {
+    /// The file to share with the invitee once they sign up
+    pub file_id: FileId,
+    /// Metadata of the file to share
+    pub metadata: ShareFileMetadata,
+}
+
+/// Response for the create_invite method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum CreateInviteResponse {
+    /// The invite was created; returns the invite code to give to the invitee
+    Ok(String),
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The user canister of the caller is not created yet
+    UserCanisterNotReady,
+    /// The caller is suspended
+    Suspended,
+    /// The caller has already [`MAX_INVITES_PER_USER`] invites
+    TooManyInvites,
+}
+
+/// State of an invite
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum InviteState {
+    /// The invitee didn't sign up yet; the invite expires at the given time, in nanoseconds
+    /// since the epoch
+    Pending { expires_at: u64 },
+    /// The invitee signed up with the invite code; the owner must now share the file with them,
+    /// wrapping the document key for their public key
+    Redeemed { user: Principal },
+}
+
+/// An invite to share a file with someone who has not signed up yet
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Invite {
+    /// The invite code
+    pub code: String,
+    /// The file to share with the invitee
+    pub file_id: FileId,
+    /// Metadata of the file to share
+    pub metadata: ShareFileMetadata,
+    /// When the invite was created, in nanoseconds since the epoch
+    pub created_at: u64,
+    /// The state of the invite
+    pub state: InviteState,
+}
+
+/// Response for the invites method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum InvitesResponse {
+    /// The invites of the caller, the oldest first
+    Invites(Vec<Invite>),
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+}
+
+/// Response for the revoke_invite method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RevokeInviteResponse {
+    /// The invite was revoked
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no such invite
+    NoSuchInvite,
+}
@@ -0,0 +1,79 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of pending requests to link a principal to an account
+pub const MAX_LINK_REQUESTS_PER_ACCOUNT: usize = 16;
+
+/// Response for the request_principal_link method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RequestPrincipalLinkResponse {
+    /// The link was requested, and must be approved from the account
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The account doesn't exist
+    NoSuchAccount,
+    /// The caller already has a user, or is already linked to an account
+    CallerHasAlreadyAUser,
+    /// The account has already [`MAX_LINK_REQUESTS_PER_ACCOUNT`] pending link requests
+    TooManyLinkRequests,
+}
+
+/// Response for the approve_principal_link method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ApprovePrincipalLinkResponse {
+    /// The principal was linked to the account of the caller
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The principal didn't request to be linked to the account of the caller, or the request expired
+    NoSuchLinkRequest,
+    /// The principal has a user, or is linked to an account, since it requested the link
+    PrincipalHasAlreadyAUser,
+    /// The user canister of the caller is not created yet
+    UserCanisterNotReady,
+    /// Failed to link the principal on the user canister; nothing was changed
+    FailedToUpdateUserCanister(String),
+}
+
+/// Response for the unlink_principal method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UnlinkPrincipalResponse {
+    /// The principal was unlinked from the account of the caller
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The principal is not linked to the account of the caller
+    NotLinked,
+    /// The caller is a linked principal, and the principal is not the caller
+    Unauthorized,
+    /// Failed to unlink the principal on a user canister; the principal stays linked, and the
+    /// unlink can be retried
+    FailedToUpdateUserCanister(String),
+}
+
+/// The principals of an account
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LinkedPrincipals {
+    /// The principal the account was created with
+    pub account: Principal,
+    /// The principals linked to the account, ordered by principal
+    pub linked: Vec<Principal>,
+    /// The principals waiting for their link to be approved, ordered by principal
+    pub requests: Vec<Principal>,
+}
+
+/// Response for the linked_principals method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LinkedPrincipalsResponse {
+    /// The principals of the account of the caller
+    LinkedPrincipals(LinkedPrincipals),
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+}
@@ -1,6 +1,8 @@
-use candid::CandidType;
+use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 
+use super::FileId;
+
 /// Pagination struct for paginated responses
 #[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
 pub struct Pagination {
@@ -9,3 +11,23 @@ pub struct Pagination {
     /// The number of items to return
     pub limit: u64,
 }
+
+/// Pagination with a cursor, for paginated responses ordered by principal
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct CursorPagination {
+    /// The cursor of the page to return, as returned with the previous page; `None` for the
+    /// first page
+    pub cursor: Option<Principal>,
+    /// The number of items to return
+    pub limit: u64,
+}
+
+/// Pagination with a cursor, for paginated responses ordered by file ID
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct FileCursorPagination {
+    /// The cursor of the page to return, as returned with the previous page; `None` for the
+    /// first page
+    pub cursor: Option<FileId>,
+    /// The number of items to return
+    pub limit: u64,
+}
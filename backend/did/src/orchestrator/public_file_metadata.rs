@@ -1,7 +1,7 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
 
-use super::{FileId, PublicUser};
+use super::{FileId, PublicUser, ShareState};
 
 /// Public file metadata which is stored for the shared files info
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -10,4 +10,6 @@ pub struct PublicFileMetadata {
     pub file_name: String,
     /// Users the file is shared with
     pub shared_with: Vec<PublicUser>,
+    /// State of the share for the caller
+    pub share_state: ShareState,
 }
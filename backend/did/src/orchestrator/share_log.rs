@@ -0,0 +1,336 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+use super::FileId;
+use crate::utils::trap;
+
+/// Max size of a principal in bytes
+const MAX_PRINCIPAL_SIZE: usize = 29;
+/// Size of a block hash in bytes
+const HASH_SIZE: usize = 32;
+
+const OP_SHARE: u8 = 0;
+const OP_REVOKE: u8 = 1;
+const OP_DECLINE: u8 = 2;
+
+/// Hash of a [`ShareBlock`].
+pub type BlockHash = [u8; HASH_SIZE];
+
+/// Operation recorded by a [`ShareBlock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ShareOperation {
+    /// The file was shared with the user
+    Share,
+    /// The owner revoked the share of the file with the user
+    Revoke,
+    /// The user declined the file shared with them
+    Decline,
+}
+
+impl ShareOperation {
+    /// The `btype` of the block in its ICRC-3 representation.
+    pub fn btype(&self) -> &'static str {
+        match self {
+            Self::Share => "share",
+            Self::Revoke => "revoke",
+            Self::Decline => "decline",
+        }
+    }
+}
+
+/// A block of the share log.
+///
+/// Each block holds the hash of the previous one, so that the log can't be rewritten without
+/// changing the hash of the tip, which is certified by the orchestrator.
+///
+/// ## Encoding
+///
+/// - 1 byte: operation.
+/// - 1 byte: whether the block has a parent hash.
+/// - 32 bytes: parent hash, if any.
+/// - 8 bytes: timestamp.
+/// - 1 byte: length of the user canister principal.
+/// - N bytes: user canister principal.
+/// - 8 bytes: file id.
+/// - 1 byte: length of the user principal.
+/// - N bytes: user principal.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ShareBlock {
+    /// Hash of the previous block; `None` for the first block
+    pub parent_hash: Option<ByteBuf>,
+    /// Recorded operation
+    pub operation: ShareOperation,
+    /// User canister owning the file
+    pub user_canister: Principal,
+    /// Shared file
+    pub file_id: FileId,
+    /// User the file is shared with
+    pub user: Principal,
+    /// Time at which the operation was recorded, in nanoseconds
+    pub timestamp: u64,
+}
+
+impl ShareBlock {
+    /// Compute the hash of the block.
+    ///
+    /// The hash is the ICRC-3 representation-independent hash of the block as the map:
+    ///
+    /// ```txt
+    /// record {
+    ///   "phash": blob; // omitted for the first block
+    ///   "btype": text;
+    ///   "ts": nat;
+    ///   "tx": record { "canister": blob; "file_id": nat; "user": blob };
+    /// }
+    /// ```
+    pub fn hash(&self) -> BlockHash {
+        let tx = hash_map(vec![
+            ("canister", hash_bytes(self.user_canister.as_slice())),
+            ("file_id", hash_nat(self.file_id)),
+            ("user", hash_bytes(self.user.as_slice())),
+        ]);
+
+        let mut fields = vec![
+            ("btype", hash_bytes(self.operation.btype().as_bytes())),
+            ("ts", hash_nat(self.timestamp)),
+            ("tx", tx),
+        ];
+        if let Some(parent_hash) = &self.parent_hash {
+            fields.push(("phash", hash_bytes(parent_hash)));
+        }
+
+        hash_map(fields)
+    }
+}
+
+impl Storable for ShareBlock {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: (1 + 1 + HASH_SIZE + 8 + (1 + MAX_PRINCIPAL_SIZE) * 2 + 8) as u32,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(Self::BOUND.max_size() as usize);
+        bytes.push(match self.operation {
+            ShareOperation::Share => OP_SHARE,
+            ShareOperation::Revoke => OP_REVOKE,
+            ShareOperation::Decline => OP_DECLINE,
+        });
+        match &self.parent_hash {
+            Some(parent_hash) => {
+                if parent_hash.len() != HASH_SIZE {
+                    trap("Failed to encode ShareBlock: invalid parent hash");
+                }
+                bytes.push(1);
+                bytes.extend_from_slice(parent_hash);
+            }
+            None => bytes.push(0),
+        }
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        encode_principal(&mut bytes, &self.user_canister);
+        bytes.extend_from_slice(&self.file_id.to_le_bytes());
+        encode_principal(&mut bytes, &self.user);
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        if bytes.len() < 2 {
+            trap("Failed to decode ShareBlock: not enough bytes");
+        }
+        let operation = match bytes[0] {
+            OP_SHARE => ShareOperation::Share,
+            OP_REVOKE => ShareOperation::Revoke,
+            OP_DECLINE => ShareOperation::Decline,
+            _ => trap("Failed to decode ShareBlock: invalid operation"),
+        };
+        let mut offset = 2;
+        let parent_hash = match bytes[1] {
+            0 => None,
+            1 => {
+                let parent_hash = read_slice(&bytes, &mut offset, HASH_SIZE);
+                Some(ByteBuf::from(parent_hash))
+            }
+            _ => trap("Failed to decode ShareBlock: invalid parent hash flag"),
+        };
+        let timestamp = u64::from_le_bytes(
+            read_slice(&bytes, &mut offset, 8)
+                .try_into()
+                .expect("Invalid timestamp"),
+        );
+        let user_canister = decode_principal(&bytes, &mut offset);
+        let file_id = u64::from_le_bytes(
+            read_slice(&bytes, &mut offset, 8)
+                .try_into()
+                .expect("Invalid file id"),
+        );
+        let user = decode_principal(&bytes, &mut offset);
+
+        Self {
+            parent_hash,
+            operation,
+            user_canister,
+            file_id,
+            user,
+            timestamp,
+        }
+    }
+}
+
+/// A [`ShareBlock`] with its index in the log.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ShareBlockWithId {
+    /// Index of the block in the log
+    pub id: u64,
+    /// The block
+    pub block: ShareBlock,
+}
+
+/// Request for the `get_blocks` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct GetBlocksRequest {
+    /// Index of the first block to return
+    pub start: u64,
+    /// Number of blocks to return
+    pub length: u64,
+}
+
+/// Response for the `get_blocks` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct GetBlocksResponse {
+    /// Number of blocks in the log
+    pub log_length: u64,
+    /// Requested blocks, from the oldest to the newest
+    pub blocks: Vec<ShareBlockWithId>,
+}
+
+/// Certificate of the tip of the share log, as returned by `get_tip_certificate`.
+///
+/// The hash tree has the labels `last_block_index`, holding the LEB128-encoded index of the
+/// last block, and `last_block_hash`, holding its hash. Its root hash is the certified data of
+/// the orchestrator.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct DataCertificate {
+    /// Certificate issued by the IC for the certified data of the orchestrator
+    pub certificate: ByteBuf,
+    /// CBOR-encoded hash tree
+    pub hash_tree: ByteBuf,
+}
+
+/// Encode a principal prefixed by its length.
+fn encode_principal(bytes: &mut Vec<u8>, principal: &Principal) {
+    let principal = principal.as_slice();
+    bytes.push(principal.len() as u8);
+    bytes.extend_from_slice(principal);
+}
+
+/// Read a principal prefixed by its length, advancing the offset.
+fn decode_principal(bytes: &[u8], offset: &mut usize) -> Principal {
+    let len = read_slice(bytes, offset, 1)[0] as usize;
+    Principal::from_slice(read_slice(bytes, offset, len))
+}
+
+/// Read `len` bytes, advancing the offset.
+fn read_slice<'a>(bytes: &'a [u8], offset: &mut usize, len: usize) -> &'a [u8] {
+    if bytes.len() < *offset + len {
+        trap("Failed to decode ShareBlock: not enough bytes");
+    }
+    let slice = &bytes[*offset..*offset + len];
+    *offset += len;
+
+    slice
+}
+
+fn hash_bytes(bytes: &[u8]) -> BlockHash {
+    Sha256::digest(bytes).into()
+}
+
+fn hash_nat(mut value: u64) -> BlockHash {
+    let mut leb128 = Vec::with_capacity(10);
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            leb128.push(byte);
+            break;
+        }
+        leb128.push(byte | 0x80);
+    }
+
+    hash_bytes(&leb128)
+}
+
+fn hash_map(fields: Vec<(&str, BlockHash)>) -> BlockHash {
+    let mut fields = fields
+        .into_iter()
+        .map(|(key, value)| {
+            let mut field = hash_bytes(key.as_bytes()).to_vec();
+            field.extend_from_slice(&value);
+            field
+        })
+        .collect::<Vec<_>>();
+    fields.sort();
+
+    hash_bytes(&fields.concat())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_share_block_roundtrip() {
+        let block = ShareBlock {
+            parent_hash: None,
+            operation: ShareOperation::Share,
+            user_canister: Principal::from_slice(&[1; 29]),
+            file_id: 42,
+            user: Principal::from_slice(&[2; 29]),
+            timestamp: 1_000,
+        };
+        let decoded = ShareBlock::from_bytes(block.to_bytes());
+        assert_eq!(block, decoded);
+
+        let block = ShareBlock {
+            parent_hash: Some(ByteBuf::from(block.hash().to_vec())),
+            operation: ShareOperation::Decline,
+            user_canister: Principal::anonymous(),
+            file_id: u64::MAX,
+            user: Principal::from_slice(&[3; 10]),
+            timestamp: u64::MAX,
+        };
+        let decoded = ShareBlock::from_bytes(block.to_bytes());
+        assert_eq!(block, decoded);
+    }
+
+    #[test]
+    fn test_should_hash_nat_as_leb128() {
+        assert_eq!(hash_nat(0), hash_bytes(&[0x00]));
+        assert_eq!(hash_nat(624485), hash_bytes(&[0xe5, 0x8e, 0x26]));
+    }
+
+    #[test]
+    fn test_should_change_hash_with_parent() {
+        let block = ShareBlock {
+            parent_hash: None,
+            operation: ShareOperation::Revoke,
+            user_canister: Principal::from_slice(&[1; 29]),
+            file_id: 1,
+            user: Principal::from_slice(&[2; 29]),
+            timestamp: 1,
+        };
+        let child = ShareBlock {
+            parent_hash: Some(ByteBuf::from(vec![0; 32])),
+            ..block.clone()
+        };
+        assert_ne!(block.hash(), child.hash());
+        assert_eq!(block.hash(), block.clone().hash());
+    }
+}
@@ -5,6 +5,7 @@ use ic_stable_structures::Storable;
 use ic_stable_structures::storable::Bound;
 use serde::{Deserialize, Serialize};
 
+use super::PublicUser;
 use super::public_file_metadata::PublicFileMetadata;
 
 /// File ID type
@@ -17,6 +18,10 @@ pub enum ShareFileResponse {
     Ok,
     /// There is no user with the given principal
     NoSuchUser(Principal),
+    /// The user with the given principal blocked the user canister or its owner
+    Blocked(Principal),
+    /// The owner of the user canister is suspended
+    Suspended,
     /// Endpoint was not called by a user canister
     Unauthorized,
 }
@@ -32,6 +37,46 @@ pub enum RevokeShareFileResponse {
     Unauthorized,
 }
 
+/// Result for `accept_share` and `hide_share` methods
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ShareStateResponse {
+    /// The share state was updated successfully
+    Ok,
+    /// The file is not shared with the caller, or the share was declined
+    NoSuchShare,
+    /// Anonymous user
+    AnonymousUser,
+}
+
+/// Result for `decline_share` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum DeclineShareResponse {
+    /// The share was declined and the owner has been notified
+    Ok,
+    /// The file is not shared with the caller
+    NoSuchShare,
+    /// Anonymous user
+    AnonymousUser,
+    /// The share was declined, but the owner's user canister could not be notified.
+    ///
+    /// The call can be retried to notify the owner again.
+    FailedToNotifyOwner(String),
+}
+
+/// State of a file share from the recipient's point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, CandidType, Serialize, Deserialize)]
+pub enum ShareState {
+    /// The file has been shared, but the recipient hasn't accepted it yet
+    #[default]
+    Pending,
+    /// The recipient accepted the share
+    Accepted,
+    /// The recipient declined the share; the owner drops the recipient's key
+    Declined,
+    /// The recipient hid the share from their shared files
+    Hidden,
+}
+
 /// Result for `shared_files` method
 #[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
 pub enum SharedFilesResponse {
@@ -41,6 +86,66 @@ pub enum SharedFilesResponse {
     NoSuchUser,
     /// Anonymous user
     AnonymousUser,
+    /// The caller is suspended
+    Suspended,
+}
+
+/// File shared by the caller, with the users it is shared with
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct SharedByMeFile {
+    pub file_id: FileId,
+    pub file_name: String,
+    /// Users the file is shared with, ordered by principal; declined shares are not included
+    pub shared_with: Vec<PublicUser>,
+}
+
+/// Page of files returned by the `shared_by_me` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct SharedByMeFiles {
+    /// Returned files, ordered by file ID
+    pub files: Vec<SharedByMeFile>,
+    /// The next page offset. If None, there are no more files to fetch
+    pub next: Option<u64>,
+    /// Total number of shared files
+    pub total: u64,
+}
+
+/// Result for `shared_by_me` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SharedByMeResponse {
+    /// Files shared by the caller
+    Files(SharedByMeFiles),
+    /// The caller has no user canister
+    NoUserCanister,
+    /// Anonymous user
+    AnonymousUser,
+}
+
+/// Users a file of a user canister is shared with
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct FileShares {
+    /// The shared file
+    pub file_id: FileId,
+    /// Users the file is shared with; declined shares are not included
+    pub users: Vec<Principal>,
+}
+
+/// Page of shares returned by the `get_user_canister_shares` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct UserCanisterShares {
+    /// Returned files with the users they're shared with, ordered by file ID
+    pub files: Vec<FileShares>,
+    /// The cursor of the next page. If None, there are no more shares to fetch
+    pub next: Option<FileId>,
+}
+
+/// Result for `get_user_canister_shares` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum UserCanisterSharesResponse {
+    /// The page of shares held for the calling user canister
+    Shares(UserCanisterShares),
+    /// Endpoint was not called by a user canister
+    Unauthorized,
 }
 
 /// File metadata of a shared file
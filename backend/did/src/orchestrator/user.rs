@@ -4,10 +4,14 @@ use ic_stable_structures::storable::Bound;
 use serde::{Deserialize, Serialize};
 
 use super::PublicKey;
+use crate::utils::trap;
 
 /// Maximum username size
 pub const MAX_USERNAME_SIZE: usize = 255;
 
+/// Maximum display name size
+pub const MAX_DISPLAY_NAME_SIZE: usize = 64;
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct User {
     pub public_key: PublicKey,
@@ -50,12 +54,88 @@ impl Storable for User {
     }
 }
 
+/// Profile of a user, kept apart from the [`User`].
+///
+/// ## Encoding
+///
+/// - 1 byte: length of the display name; 0 if there is no display name.
+/// - N bytes: display name.
+/// - 1 byte: number of previous usernames.
+/// - For each previous username, from the oldest to the newest:
+///   - 1 byte: length of the username.
+///   - N bytes: username.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserProfile {
+    /// Name displayed instead of the username, if any
+    pub display_name: Option<String>,
+    /// Usernames the user had before, from the oldest to the newest
+    pub previous_usernames: Vec<String>,
+}
+
+impl Storable for UserProfile {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        let display_name_len = profile_bytes(&bytes, 0, 1)[0] as usize;
+        let mut offset = 1;
+        let display_name = (display_name_len > 0).then(|| {
+            String::from_utf8_lossy(profile_bytes(&bytes, offset, display_name_len)).to_string()
+        });
+        offset += display_name_len;
+
+        let count = profile_bytes(&bytes, offset, 1)[0];
+        offset += 1;
+        let mut previous_usernames = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = profile_bytes(&bytes, offset, 1)[0] as usize;
+            offset += 1;
+            previous_usernames
+                .push(String::from_utf8_lossy(profile_bytes(&bytes, offset, len)).to_string());
+            offset += len;
+        }
+
+        UserProfile {
+            display_name,
+            previous_usernames,
+        }
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        let mut bytes = vec![];
+        let display_name = self.display_name.as_deref().unwrap_or_default();
+        bytes.push(display_name.len() as u8);
+        bytes.extend_from_slice(display_name.as_bytes());
+
+        bytes.push(self.previous_usernames.len() as u8);
+        for username in &self.previous_usernames {
+            bytes.push(username.len() as u8);
+            bytes.extend_from_slice(username.as_bytes());
+        }
+
+        bytes.into()
+    }
+}
+
+/// Get `len` bytes of an encoded [`UserProfile`] at `offset`, trapping if there are not enough.
+fn profile_bytes(bytes: &[u8], offset: usize, len: usize) -> &[u8] {
+    match bytes.get(offset..offset + len) {
+        Some(bytes) => bytes,
+        None => trap("Failed to decode UserProfile: not enough bytes"),
+    }
+}
+
 /// Public user information
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct PublicUser {
     pub username: String,
     pub public_key: PublicKey,
     pub ic_principal: Principal,
+    /// Name displayed instead of the username, if any
+    pub display_name: Option<String>,
+    /// Version of the public key, increased every time it changes.
+    ///
+    /// Sharers can check it before wrapping document keys, to make sure the key is still current.
+    pub key_version: u64,
 }
 
 impl PublicUser {
@@ -65,8 +145,78 @@ impl PublicUser {
             username: user.username,
             public_key: user.public_key,
             ic_principal,
+            display_name: None,
+            key_version: 0,
         }
     }
+
+    /// Set the display fields from the profile of the user
+    pub fn with_profile(mut self, profile: UserProfile) -> Self {
+        self.display_name = profile.display_name;
+        self
+    }
+}
+
+/// Request for the update_profile method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UpdateProfileRequest {
+    /// The new username; it can be the current one
+    pub username: String,
+    /// The new display name; `None` or an empty name removes it
+    pub display_name: Option<String>,
+}
+
+/// Response for the update_profile method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UpdateProfileResponse {
+    /// The profile was updated
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The username is too long
+    UsernameTooLong,
+    /// The username is taken by another user, or was used by another user before
+    UsernameExists,
+    /// The username contains characters which are not allowed
+    InvalidUsername,
+    /// The username is reserved by the admins
+    UsernameReserved,
+    /// The display name is too long
+    DisplayNameTooLong,
+}
+
+/// Users whose usernames have the same canonical key, found when migrating the usernames
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UsernameCollision {
+    /// The canonical key of the usernames
+    pub username: String,
+    /// The users having a username with this key
+    pub users: Vec<Principal>,
+}
+
+/// Request for the delete_account method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeleteAccountRequest {
+    /// Whether to keep the user canister and its data, detached from the Orbit Station, instead
+    /// of deleting it
+    pub archive: bool,
+}
+
+/// Response for the delete_account method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum DeleteAccountResponse {
+    /// The deletion of the account has started
+    Ok,
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The account is already being deleted
+    DeletionPending,
+    /// The user canister is being created; the account can be deleted once it is created
+    UserCanisterCreationPending,
 }
 
 /// Response for the set_user method
@@ -86,6 +236,47 @@ pub enum SetUserResponse {
     /// The caller already has a user
     #[serde(rename = "caller_has_already_a_user")]
     CallerHasAlreadyAUser,
+    /// The username contains characters which are not allowed
+    #[serde(rename = "invalid_username")]
+    InvalidUsername,
+    /// The username is reserved by the admins
+    #[serde(rename = "username_reserved")]
+    UsernameReserved,
+    /// The caller is suspended
+    #[serde(rename = "suspended")]
+    Suspended,
+    /// The invite code doesn't exist, expired or was already used
+    #[serde(rename = "invalid_invite")]
+    InvalidInvite,
+}
+
+/// Response for the update_user_public_key method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UpdateUserPublicKeyResponse {
+    /// The public key was updated; returns its version
+    Ok(u64),
+    /// There is no user with the given principal
+    NoSuchUser,
+    /// Endpoint was not called by the user canister of the user
+    Unauthorized,
+}
+
+/// Response for the update_public_key method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UpdatePublicKeyResponse {
+    /// The public key was updated on the orchestrator and on the user canister; returns its
+    /// version
+    Ok(u64),
+    /// The caller is anonymous
+    AnonymousCaller,
+    /// The caller has no user
+    NoSuchUser,
+    /// The public key was changed in the meantime; returns its current version
+    StaleVersion(u64),
+    /// A rotation of the owner public key is in progress on the user canister
+    KeyRotationInProgress,
+    /// The user canister could not be updated; nothing was changed
+    FailedToUpdateUserCanister(String),
 }
 
 /// Response for the get_users method
@@ -106,7 +297,29 @@ pub struct GetUsersResponseUsers {
     pub users: Vec<PublicUser>,
     /// The next page offset. If None, there are no more users to fetch
     pub next: Option<u64>,
-    /// Total number of users
+    /// Total number of users matching the query
+    pub total: u64,
+}
+
+/// Response for the search_users method
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SearchUsersResponse {
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "invalid_query")]
+    InvalidQuery,
+    #[serde(rename = "users")]
+    Users(SearchUsersResponseUsers),
+}
+
+/// Response for the search_users method with pagination
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchUsersResponseUsers {
+    /// Returned users
+    pub users: Vec<PublicUser>,
+    /// The cursor of the next page. If None, there are no more users to fetch
+    pub next: Option<Principal>,
+    /// Total number of users matching the query
     pub total: u64,
 }
 
@@ -128,6 +341,30 @@ mod test {
         assert_eq!(user, decoded_user);
     }
 
+    #[test]
+    fn test_storable_user_profile_roundtrip() {
+        let profile = UserProfile {
+            display_name: Some("Test User".to_string()),
+            previous_usernames: vec!["old_user".to_string(), "older_user".to_string()],
+        };
+        assert_eq!(UserProfile::from_bytes(profile.to_bytes()), profile);
+
+        let profile = UserProfile::default();
+        assert_eq!(UserProfile::from_bytes(profile.to_bytes()), profile);
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to decode UserProfile")]
+    fn test_should_trap_on_truncated_user_profile() {
+        let profile = UserProfile {
+            display_name: Some("Test User".to_string()),
+            previous_usernames: vec!["old_user".to_string()],
+        };
+        let bytes = profile.to_bytes();
+
+        UserProfile::from_bytes(bytes[..bytes.len() - 1].to_vec().into());
+    }
+
     #[test]
     fn test_should_create_public_user_from_user() {
         let user = User {
@@ -141,5 +378,12 @@ mod test {
         assert_eq!(public_user.username, user.username);
         assert_eq!(public_user.public_key, user.public_key);
         assert_eq!(public_user.ic_principal, ic_principal);
+        assert_eq!(public_user.display_name, None);
+
+        let public_user = public_user.with_profile(UserProfile {
+            display_name: Some("Test User".to_string()),
+            previous_usernames: vec![],
+        });
+        assert_eq!(public_user.display_name.as_deref(), Some("Test User"));
     }
 }
@@ -30,3 +30,14 @@ pub enum RetryUserCanisterCreationResponse {
     /// User not found - use `set_user` first to create a user
     UserNotFound,
 }
+
+/// Response for `get_user_canister` query
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum GetUserCanisterResponse {
+    /// The user canister of the user
+    Ok(Principal),
+    /// The user has no user canister
+    NoSuchUser,
+    /// The caller is not a user canister
+    Unauthorized,
+}
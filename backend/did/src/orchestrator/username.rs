@@ -0,0 +1,70 @@
+/// Offset between the fullwidth forms (U+FF01 to U+FF5E) and the ASCII characters they stand for.
+const FULLWIDTH_OFFSET: u32 = 0xFEE0;
+
+/// Normalize a username, checking that it follows the username charset policy.
+///
+/// Fullwidth forms (U+FF01 to U+FF5E, and the ideographic space) are folded to the ASCII
+/// characters they stand for; no other Unicode normalization is applied. The folded username must
+/// then be made of ASCII letters, digits, `_`, `-` and `.`, and start with a letter or a digit;
+/// any other character, such as zero-width or confusable characters, makes it invalid.
+///
+/// Returns the normalized username, keeping its case, or `None` if it is invalid.
+pub fn normalize_username(username: &str) -> Option<String> {
+    let username = fold_fullwidth(username);
+    let valid = username
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+    valid.then_some(username)
+}
+
+/// Get the canonical key of a username, which is unique among users.
+///
+/// Usernames which only differ by their case, or by fullwidth forms, have the same key.
+pub fn username_key(username: &str) -> String {
+    fold_fullwidth(username).to_lowercase()
+}
+
+/// Fold the fullwidth forms of a string to the ASCII characters they stand for.
+fn fold_fullwidth(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - FULLWIDTH_OFFSET).unwrap_or(c),
+            '\u{3000}' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_normalize_username() {
+        assert_eq!(
+            normalize_username("Alice_1.b-c").as_deref(),
+            Some("Alice_1.b-c")
+        );
+        assert_eq!(normalize_username("Ａｌｉｃｅ").as_deref(), Some("Alice"));
+        assert_eq!(normalize_username(""), None);
+        assert_eq!(normalize_username("_alice"), None);
+        assert_eq!(normalize_username("ali ce"), None);
+        // zero-width space
+        assert_eq!(normalize_username("ali\u{200B}ce"), None);
+        // cyrillic "а"
+        assert_eq!(normalize_username("\u{0430}lice"), None);
+    }
+
+    #[test]
+    fn test_should_get_username_key() {
+        assert_eq!(username_key("Alice"), "alice");
+        assert_eq!(username_key("ＡＬＩＣＥ"), "alice");
+        assert_eq!(username_key("alice"), username_key("ALICE"));
+    }
+}
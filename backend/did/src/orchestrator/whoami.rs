@@ -31,6 +31,8 @@ mod test {
             username: "test_user".to_string(),
             public_key: vec![1; PublicKey::MAX_KEY_SIZE].try_into().unwrap(),
             ic_principal: Principal::from_slice(&[2; 29]),
+            display_name: None,
+            key_version: 0,
         };
 
         let response = WhoamiResponse::from(user.clone());
@@ -1,23 +1,63 @@
+mod access_log;
+mod audit_log;
 mod delete_file;
+mod device_key;
 mod file;
+mod key_rotation;
+mod linked_principal;
+mod outbox;
 mod owner_key;
 mod path;
+mod reconcile_shares;
+mod reencrypt_file;
 mod request_file;
+mod share_file;
+mod suspension;
+mod transfer_file;
 mod upload_file_atomic;
 
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 
+pub use self::access_log::{
+    ConfirmDownloadResponse, FileAccessLog, FileAccessLogEntry, GetFileAccessLogResponse,
+};
+pub use self::audit_log::{AuditAction, AuditLog, AuditLogEntry, AuditLogFilter};
 pub use self::delete_file::DeleteFileResponse;
+pub use self::device_key::{SetDeviceKeysResponse, SyncDeviceKeyResponse};
 pub use self::file::{
     AliasInfo, FileData, FileDownloadResponse, FileSharingResponse, FileStatus, GetAliasInfoError,
     PublicFileMetadata, UploadFileAtomicRequest, UploadFileContinueRequest,
     UploadFileContinueResponse, UploadFileError, UploadFileRequest,
 };
+pub use self::key_rotation::{
+    CompleteKeyRotationResponse, KeyRotation, KeyRotationStatus, SetPublicKeyResponse,
+    StartKeyRotationResponse, SubmitKeyRotationBatchResponse, SyncPublicKeyResponse,
+    WrappedFileKey,
+};
+pub use self::linked_principal::LinkPrincipalResponse;
+pub use self::outbox::{OutboxEntry, OutboxEntryWithId, OutboxOperation};
 pub use self::owner_key::OwnerKey;
 pub use self::path::Path;
+pub use self::reconcile_shares::{FileShare, ReconcileSharesResponse, ShareReconciliationReport};
+pub use self::reencrypt_file::{
+    ReencryptFileContinueResponse, ReencryptFileRequest, ReencryptFileResponse,
+};
 pub use self::request_file::RequestFileResponse;
+pub use self::share_file::{
+    RevokeAllForUserReport, RevokeOutcome, RevokeResult, RevokeShareForUsersResponse,
+    RevokeShareResponse, RevokedShare, ShareFileWithUsersResponse, ShareMode, ShareOutcome,
+    ShareResult,
+};
+pub use self::suspension::SetSuspendedResponse;
+pub use self::transfer_file::{
+    AcceptFileTransferResponse, CompleteFileTransferResponse, IncomingFileTransfer,
+    MAX_INCOMING_TRANSFERS_PER_SENDER, MAX_TRANSFER_CHUNK_SIZE, MAX_TRANSFER_NUM_CHUNKS,
+    ReceiveFileTransferChunkResponse, ReceiveFileTransferRequest, ReceiveFileTransferResponse,
+    ReleaseFileTransferResponse, TransferFileRequest, TransferFileResponse,
+};
 pub use self::upload_file_atomic::UploadFileAtomicResponse;
+pub use crate::device_key::{DeviceId, DeviceKey, DeviceOwnerKey};
 pub use crate::public_key::PublicKey;
 
 /// User Canister canister install arguments.
@@ -0,0 +1,130 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::trap;
+
+/// Max size of a principal in bytes
+const MAX_PRINCIPAL_SIZE: usize = 29;
+
+/// An entry of the access log of a file, recorded when a user confirms the download of the file.
+///
+/// ## Encoding
+///
+/// - 1 byte: length of the principal.
+/// - N bytes: principal.
+/// - 8 bytes: timestamp.
+/// - 8 bytes: version.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct FileAccessLogEntry {
+    /// The user who downloaded the file
+    pub user: Principal,
+    /// Time at which the download was confirmed, in nanoseconds
+    pub timestamp: u64,
+    /// Version of the downloaded file, which is the time at which its content was uploaded
+    pub version: u64,
+}
+
+impl Storable for FileAccessLogEntry {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: (1 + MAX_PRINCIPAL_SIZE + 8 + 8) as u32,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let principal = self.user.as_slice();
+        let mut bytes = Vec::with_capacity(1 + principal.len() + 16);
+        bytes.push(principal.len() as u8);
+        bytes.extend_from_slice(principal);
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        if bytes.is_empty() {
+            trap("Failed to decode FileAccessLogEntry: empty bytes");
+        }
+        let principal_len = bytes[0] as usize;
+        if bytes.len() != 1 + principal_len + 16 {
+            trap("Failed to decode FileAccessLogEntry: invalid length");
+        }
+        let mut offset = 1;
+        let user = Principal::from_slice(&bytes[offset..offset + principal_len]);
+        offset += principal_len;
+        let timestamp = u64::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("Invalid timestamp"),
+        );
+        offset += 8;
+        let version = u64::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("Invalid version"),
+        );
+
+        Self {
+            user,
+            timestamp,
+            version,
+        }
+    }
+}
+
+/// Response for the `confirm_download` method.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ConfirmDownloadResponse {
+    /// The download was recorded in the file access log.
+    Ok,
+    /// File was not found.
+    FileNotFound,
+    /// File is not uploaded yet.
+    NotUploadedFile,
+    /// The caller has no access to the file.
+    PermissionError,
+}
+
+/// Response for the `get_file_access_log` method.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GetFileAccessLogResponse {
+    /// Page of the access log of the file
+    Ok(FileAccessLog),
+    /// File was not found.
+    FileNotFound,
+}
+
+/// Page of the access log of a file.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileAccessLog {
+    /// Entries of the access log, from the oldest to the newest
+    pub entries: Vec<FileAccessLogEntry>,
+    /// Offset of the next page, if any
+    pub next: Option<u64>,
+    /// Total number of entries in the access log
+    pub total: u64,
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_file_access_log_entry_roundtrip() {
+        let entry = FileAccessLogEntry {
+            user: Principal::from_slice(&[1; 29]),
+            timestamp: 1_700_000_000_000_000_000,
+            version: 1_600_000_000_000_000_000,
+        };
+
+        let bytes = entry.to_bytes();
+        let decoded = FileAccessLogEntry::from_bytes(bytes);
+
+        assert_eq!(entry, decoded);
+    }
+}
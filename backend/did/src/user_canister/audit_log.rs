@@ -0,0 +1,359 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+use serde::{Deserialize, Serialize};
+
+use super::Path;
+use crate::FileId;
+use crate::utils::trap;
+
+const OP_REQUEST_FILE: u8 = 0;
+const OP_UPLOAD_FILE: u8 = 1;
+const OP_SHARE_FILE: u8 = 2;
+const OP_REVOKE_SHARE: u8 = 3;
+const OP_DECLINE_SHARE: u8 = 4;
+const OP_DELETE_FILE: u8 = 5;
+const OP_REENCRYPT_FILE: u8 = 6;
+const OP_TRANSFER_FILE: u8 = 7;
+const OP_RECEIVE_FILE: u8 = 8;
+
+/// Action recorded into the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum AuditAction {
+    /// A file was requested.
+    RequestFile,
+    /// A file was uploaded.
+    UploadFile,
+    /// A file was shared with a user.
+    ShareFile {
+        /// The user the file was shared with
+        user: Principal,
+    },
+    /// The share of a file with a user was revoked.
+    RevokeShare {
+        /// The user the share was revoked from
+        user: Principal,
+    },
+    /// A user declined the share of a file.
+    DeclineShare {
+        /// The user who declined the share
+        user: Principal,
+    },
+    /// A file was deleted.
+    DeleteFile,
+    /// A file was re-encrypted with a new document key.
+    ReencryptFile,
+    /// A file was transferred to another user, who became its owner.
+    TransferFile {
+        /// The user the file was transferred to
+        user: Principal,
+    },
+    /// A file was received from another user, who was its owner.
+    ReceiveFile {
+        /// The user the file was received from
+        user: Principal,
+    },
+}
+
+/// An entry of the audit log of the user canister.
+///
+/// ## Encoding
+///
+/// - 1 byte: length of the actor principal.
+/// - N bytes: actor principal.
+/// - 8 bytes: timestamp.
+/// - 8 bytes: file ID.
+/// - 1 byte: action op code.
+/// - For actions with a user:
+///   - 1 byte: length of the user principal.
+///   - N bytes: user principal.
+/// - Remaining bytes: path.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    /// The principal who performed the action
+    pub actor: Principal,
+    /// The action performed
+    pub action: AuditAction,
+    /// The file the action was performed on
+    pub file_id: FileId,
+    /// Path of the file at the time of the action
+    pub path: Path,
+    /// Time of the action, in nanoseconds
+    pub timestamp: u64,
+}
+
+impl Storable for AuditLogEntry {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = vec![];
+        encode_principal(&mut bytes, &self.actor);
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.file_id.to_le_bytes());
+        match &self.action {
+            AuditAction::RequestFile => bytes.push(OP_REQUEST_FILE),
+            AuditAction::UploadFile => bytes.push(OP_UPLOAD_FILE),
+            AuditAction::ShareFile { user } => {
+                bytes.push(OP_SHARE_FILE);
+                encode_principal(&mut bytes, user);
+            }
+            AuditAction::RevokeShare { user } => {
+                bytes.push(OP_REVOKE_SHARE);
+                encode_principal(&mut bytes, user);
+            }
+            AuditAction::DeclineShare { user } => {
+                bytes.push(OP_DECLINE_SHARE);
+                encode_principal(&mut bytes, user);
+            }
+            AuditAction::DeleteFile => bytes.push(OP_DELETE_FILE),
+            AuditAction::ReencryptFile => bytes.push(OP_REENCRYPT_FILE),
+            AuditAction::TransferFile { user } => {
+                bytes.push(OP_TRANSFER_FILE);
+                encode_principal(&mut bytes, user);
+            }
+            AuditAction::ReceiveFile { user } => {
+                bytes.push(OP_RECEIVE_FILE);
+                encode_principal(&mut bytes, user);
+            }
+        }
+        bytes.extend_from_slice(&self.path.to_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut offset = 0;
+        let actor = decode_principal(&bytes, &mut offset);
+        if bytes.len() < offset + 17 {
+            trap("Failed to decode AuditLogEntry: not enough bytes");
+        }
+        let timestamp = u64::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("Invalid timestamp"),
+        );
+        offset += 8;
+        let file_id = FileId::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("Invalid file ID"),
+        );
+        offset += 8;
+        let op_code = bytes[offset];
+        offset += 1;
+        let action = match op_code {
+            OP_REQUEST_FILE => AuditAction::RequestFile,
+            OP_UPLOAD_FILE => AuditAction::UploadFile,
+            OP_SHARE_FILE => AuditAction::ShareFile {
+                user: decode_principal(&bytes, &mut offset),
+            },
+            OP_REVOKE_SHARE => AuditAction::RevokeShare {
+                user: decode_principal(&bytes, &mut offset),
+            },
+            OP_DECLINE_SHARE => AuditAction::DeclineShare {
+                user: decode_principal(&bytes, &mut offset),
+            },
+            OP_DELETE_FILE => AuditAction::DeleteFile,
+            OP_REENCRYPT_FILE => AuditAction::ReencryptFile,
+            OP_TRANSFER_FILE => AuditAction::TransferFile {
+                user: decode_principal(&bytes, &mut offset),
+            },
+            OP_RECEIVE_FILE => AuditAction::ReceiveFile {
+                user: decode_principal(&bytes, &mut offset),
+            },
+            _ => trap("Failed to decode AuditLogEntry: invalid action op code"),
+        };
+        let path = Path::from_bytes(Cow::Borrowed(&bytes[offset..]));
+
+        Self {
+            actor,
+            action,
+            file_id,
+            path,
+            timestamp,
+        }
+    }
+}
+
+/// Write a principal prefixed by its length.
+fn encode_principal(bytes: &mut Vec<u8>, principal: &Principal) {
+    let principal = principal.as_slice();
+    bytes.push(principal.len() as u8);
+    bytes.extend_from_slice(principal);
+}
+
+/// Read a principal prefixed by its length, advancing the offset.
+fn decode_principal(bytes: &[u8], offset: &mut usize) -> Principal {
+    if bytes.len() <= *offset {
+        trap("Failed to decode AuditLogEntry: not enough bytes for principal");
+    }
+    let len = bytes[*offset] as usize;
+    *offset += 1;
+    if bytes.len() < *offset + len {
+        trap("Failed to decode AuditLogEntry: not enough bytes for principal");
+    }
+    let principal = Principal::from_slice(&bytes[*offset..*offset + len]);
+    *offset += len;
+
+    principal
+}
+
+/// Filter for the `get_audit_log` method.
+///
+/// Entries must match all the provided fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct AuditLogFilter {
+    /// The principal who performed the action
+    pub actor: Option<Principal>,
+    /// The user the file was shared with, revoked from, declined by, transferred to or received
+    /// from
+    pub user: Option<Principal>,
+    /// The file the action was performed on
+    pub file_id: Option<FileId>,
+    /// Path of the file at the time of the action
+    pub path: Option<Path>,
+    /// Only entries recorded at or after this time, in nanoseconds
+    pub since: Option<u64>,
+    /// Only entries recorded at or before this time, in nanoseconds
+    pub until: Option<u64>,
+}
+
+impl AuditLogFilter {
+    /// Returns whether the entry matches the filter.
+    pub fn matches(&self, entry: &AuditLogEntry) -> bool {
+        let user = match &entry.action {
+            AuditAction::ShareFile { user }
+            | AuditAction::RevokeShare { user }
+            | AuditAction::DeclineShare { user }
+            | AuditAction::TransferFile { user }
+            | AuditAction::ReceiveFile { user } => Some(*user),
+            _ => None,
+        };
+
+        self.actor.is_none_or(|actor| actor == entry.actor)
+            && self.user.is_none_or(|filter| Some(filter) == user)
+            && self.file_id.is_none_or(|file_id| file_id == entry.file_id)
+            && self.path.as_ref().is_none_or(|path| path == &entry.path)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp <= until)
+    }
+}
+
+/// Page of the audit log.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditLog {
+    /// Entries of the audit log, from the oldest to the newest
+    pub entries: Vec<AuditLogEntry>,
+    /// Offset of the next page, if any
+    pub next: Option<u64>,
+    /// Total number of entries matching the filter
+    pub total: u64,
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_audit_log_entry_roundtrip() {
+        let actions = [
+            AuditAction::RequestFile,
+            AuditAction::UploadFile,
+            AuditAction::ShareFile {
+                user: Principal::from_slice(&[2; 29]),
+            },
+            AuditAction::RevokeShare {
+                user: Principal::from_slice(&[3; 10]),
+            },
+            AuditAction::DeclineShare {
+                user: Principal::anonymous(),
+            },
+            AuditAction::DeleteFile,
+            AuditAction::ReencryptFile,
+            AuditAction::TransferFile {
+                user: Principal::from_slice(&[4; 29]),
+            },
+            AuditAction::ReceiveFile {
+                user: Principal::from_slice(&[5; 29]),
+            },
+        ];
+
+        for action in actions {
+            let entry = AuditLogEntry {
+                actor: Principal::from_slice(&[1; 29]),
+                action,
+                file_id: 42,
+                path: Path::new("/contracts/x.pdf").unwrap(),
+                timestamp: 1_700_000_000_000_000_000,
+            };
+
+            let bytes = entry.to_bytes();
+            let decoded = AuditLogEntry::from_bytes(bytes);
+            assert_eq!(entry, decoded);
+        }
+    }
+
+    #[test]
+    fn test_should_filter_audit_log_entries() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let entry = AuditLogEntry {
+            actor: alice,
+            action: AuditAction::ShareFile { user: bob },
+            file_id: 1,
+            path: Path::new("/contracts/x.pdf").unwrap(),
+            timestamp: 100,
+        };
+
+        assert!(AuditLogFilter::default().matches(&entry));
+        assert!(
+            AuditLogFilter {
+                actor: Some(alice),
+                user: Some(bob),
+                file_id: Some(1),
+                path: Some(Path::new("/contracts/x.pdf").unwrap()),
+                since: Some(100),
+                until: Some(100),
+            }
+            .matches(&entry)
+        );
+        assert!(
+            !AuditLogFilter {
+                actor: Some(bob),
+                ..Default::default()
+            }
+            .matches(&entry)
+        );
+        assert!(
+            !AuditLogFilter {
+                user: Some(alice),
+                ..Default::default()
+            }
+            .matches(&entry)
+        );
+        assert!(
+            !AuditLogFilter {
+                path: Some(Path::new("/contracts/y.pdf").unwrap()),
+                ..Default::default()
+            }
+            .matches(&entry)
+        );
+        assert!(
+            !AuditLogFilter {
+                since: Some(101),
+                ..Default::default()
+            }
+            .matches(&entry)
+        );
+        assert!(
+            !AuditLogFilter {
+                until: Some(99),
+                ..Default::default()
+            }
+            .matches(&entry)
+        );
+    }
+}
@@ -8,8 +8,6 @@ pub enum DeleteFileResponse {
     Ok,
     /// File was not found.
     FileNotFound,
-    /// Failed to unindex the file on the orchestrator.
-    FailedToRevokeShare(String),
 }
 
 impl DeleteFileResponse {
@@ -18,9 +16,6 @@ impl DeleteFileResponse {
         match self {
             DeleteFileResponse::Ok => self,
             DeleteFileResponse::FileNotFound => panic!("File not found: {}", s),
-            DeleteFileResponse::FailedToRevokeShare(err) => {
-                panic!("Failed to revoke share: {}: {}", s, err)
-            }
         }
     }
 }
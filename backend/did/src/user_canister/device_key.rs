@@ -0,0 +1,27 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Response for the `add_device_key`, `remove_device_key` and `remove_recipient_device_key`
+/// methods.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SyncDeviceKeyResponse {
+    /// The device key was added or removed.
+    Ok,
+    /// The caller is not the orchestrator.
+    Unauthorized,
+    /// The device to remove is not registered.
+    NoSuchDevice,
+}
+
+/// Response for the `set_device_keys` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SetDeviceKeysResponse {
+    /// The device keys were set.
+    Ok,
+    /// The file doesn't exist.
+    FileNotFound,
+    /// The file has not been uploaded yet.
+    PendingError,
+    /// The user is neither the owner nor a user the file is shared with.
+    NotShared,
+}
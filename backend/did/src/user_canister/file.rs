@@ -1,7 +1,7 @@
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 
-use super::{OwnerKey, Path, PublicKey};
+use super::{DeviceKey, DeviceOwnerKey, OwnerKey, Path, PublicKey};
 
 /// Public file metadata
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -46,6 +46,8 @@ pub struct AliasInfo {
     pub file_name: String,
     pub file_path: Path,
     pub public_key: PublicKey,
+    /// The public keys of the devices of the owner, to encrypt the document key for each device
+    pub device_keys: Vec<DeviceKey>,
 }
 
 /// File data
@@ -67,6 +69,9 @@ pub enum FileDownloadResponse {
     NotUploadedFile,
     #[serde(rename = "permission_error")]
     PermissionError,
+    /// No document key was stored for the requested device
+    #[serde(rename = "device_key_not_found")]
+    DeviceKeyNotFound,
     #[serde(rename = "found_file")]
     FoundFile(FileData),
 }
@@ -74,27 +79,40 @@ pub enum FileDownloadResponse {
 /// File upload error
 /// - `not_requested`: The file is not requested.
 /// - `already_uploaded`: The file is already uploaded.
+/// - `suspended`: The owner is suspended.
 #[derive(CandidType, Serialize, Deserialize)]
 pub enum UploadFileError {
     #[serde(rename = "not_requested")]
     NotRequested,
     #[serde(rename = "already_uploaded")]
     AlreadyUploaded,
+    #[serde(rename = "suspended")]
+    Suspended,
 }
 
 /// File upload response
 /// - `pending_error`: The file is pending upload.
 /// - `permission_error`: The file is not shared with the user.
 /// - `file_not_found`: The file is not found.
+/// - `pending`: The share is recorded, but the orchestrator could not be reached yet; it is
+///   applied once the orchestrator confirms it.
+/// - `rejected`: The orchestrator rejected the share.
+/// - `suspended`: The owner is suspended.
 /// - `ok`: The file is uploaded successfully.
 #[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
 pub enum FileSharingResponse {
     #[serde(rename = "pending_error")]
     PendingError,
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "rejected")]
+    Rejected(String),
     #[serde(rename = "permission_error")]
     PermissionError,
     #[serde(rename = "file_not_found")]
     FileNotFound,
+    #[serde(rename = "suspended")]
+    Suspended,
     #[serde(rename = "ok")]
     Ok,
 }
@@ -107,6 +125,8 @@ pub struct UploadFileRequest {
     pub file_type: String,
     pub owner_key: OwnerKey,
     pub num_chunks: u64,
+    /// The document key encrypted for each device of the owner; `None` if there is none
+    pub device_keys: Option<Vec<DeviceOwnerKey>>,
 }
 
 /// File upload atomic request
@@ -117,6 +137,8 @@ pub struct UploadFileAtomicRequest {
     pub owner_key: OwnerKey,
     pub file_type: String,
     pub num_chunks: u64,
+    /// The document key encrypted for each device of the owner; `None` if there is none
+    pub device_keys: Option<Vec<DeviceOwnerKey>>,
 }
 
 /// File upload continue request
@@ -133,6 +155,7 @@ pub struct UploadFileContinueRequest {
 /// - `chunk_already_uploaded`: The file is not shared with the user.
 /// - `chunk_out_of_bounds`: The chunk is out of bounds (chunk_id >= num_chunks).
 /// - `file_not_found`: The file is not found.
+/// - `suspended`: The owner is suspended.
 /// - `ok`: The chunk is uploaded successfully.
 #[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
 pub enum UploadFileContinueResponse {
@@ -144,6 +167,8 @@ pub enum UploadFileContinueResponse {
     ChunkOutOfBounds,
     #[serde(rename = "file_not_found")]
     FileNotFound,
+    #[serde(rename = "suspended")]
+    Suspended,
     #[serde(rename = "ok")]
     Ok,
 }
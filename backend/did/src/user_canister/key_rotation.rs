@@ -0,0 +1,161 @@
+use std::borrow::Cow;
+
+use candid::CandidType;
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+use serde::{Deserialize, Serialize};
+
+use super::OwnerKey;
+use crate::FileId;
+use crate::public_key::PublicKey;
+use crate::utils::trap;
+
+/// A rotation of the owner public key in progress.
+///
+/// ## Encoding
+///
+/// - N bytes: new public key.
+/// - 8 bytes: timestamp of the start of the rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct KeyRotation {
+    /// The public key the document keys are re-wrapped for
+    pub new_public_key: PublicKey,
+    /// Time at which the rotation was started, in nanoseconds
+    pub started_at: u64,
+}
+
+impl Storable for KeyRotation {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: PublicKey::BOUND.max_size() + 8,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = self.new_public_key.to_bytes().into_owned();
+        bytes.extend_from_slice(&self.started_at.to_le_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        if bytes.len() < PublicKey::KEY_LEN_SIZE {
+            trap("Failed to decode KeyRotation: not enough bytes for public key");
+        }
+        let new_public_key = PublicKey::from_bytes(bytes[..].into());
+        let offset = new_public_key.encoding_size();
+        if bytes.len() != offset + 8 {
+            trap("Failed to decode KeyRotation: invalid length");
+        }
+        let started_at = u64::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("Invalid started_at"),
+        );
+
+        Self {
+            new_public_key,
+            started_at,
+        }
+    }
+}
+
+/// Progress of a key rotation.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct KeyRotationStatus {
+    /// The rotation in progress
+    pub rotation: KeyRotation,
+    /// Number of files whose key is already wrapped for the new public key
+    pub migrated: u64,
+    /// Number of files whose key must still be re-wrapped
+    pub remaining: u64,
+}
+
+/// The document key of a file, wrapped for the owner public key.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct WrappedFileKey {
+    /// The file ID
+    pub file_id: FileId,
+    /// The document key, encrypted with the owner public key
+    pub owner_key: OwnerKey,
+}
+
+/// Response for the `start_key_rotation` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum StartKeyRotationResponse {
+    /// The rotation was started.
+    Ok,
+    /// Another rotation is already in progress.
+    RotationInProgress,
+    /// The new public key is the current owner public key.
+    SameKey,
+    /// Files are being re-encrypted; the rotation can be started once they are completed or
+    /// aborted.
+    ReencryptionInProgress,
+}
+
+/// Response for the `submit_key_rotation_batch` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SubmitKeyRotationBatchResponse {
+    /// The keys were updated; returns the number of files which must still be migrated.
+    Ok(u64),
+    /// There is no rotation in progress.
+    NoRotationInProgress,
+    /// The file was not found; no key of the batch was updated.
+    FileNotFound(FileId),
+    /// The file has no document key yet; no key of the batch was updated.
+    NotUploadedFile(FileId),
+}
+
+/// Response for the `complete_key_rotation` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum CompleteKeyRotationResponse {
+    /// The owner public key was replaced on the canister and on the orchestrator.
+    Ok,
+    /// There is no rotation in progress.
+    NoRotationInProgress,
+    /// Some files must still be migrated.
+    FilesNotMigrated(u64),
+    /// Failed to update the public key on the orchestrator; the rotation can be completed again.
+    FailedToUpdateOrchestrator(String),
+}
+
+/// Response for the `set_public_key` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SetPublicKeyResponse {
+    /// The owner public key was replaced on the canister and on the orchestrator; returns its
+    /// version.
+    Ok(u64),
+    /// A rotation of the owner public key is in progress.
+    KeyRotationInProgress,
+    /// Failed to update the public key on the orchestrator; nothing was changed.
+    FailedToUpdateOrchestrator(String),
+}
+
+/// Response for the `sync_public_key` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SyncPublicKeyResponse {
+    /// The owner public key was replaced.
+    Ok,
+    /// The caller is not the orchestrator.
+    Unauthorized,
+    /// The canister already holds this version or a newer one; returns the current version.
+    StaleVersion(u64),
+    /// A rotation of the owner public key is in progress.
+    KeyRotationInProgress,
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_key_rotation_roundtrip() {
+        let rotation = KeyRotation {
+            new_public_key: PublicKey::try_from(vec![4; 32]).unwrap(),
+            started_at: 1_000,
+        };
+        let decoded = KeyRotation::from_bytes(rotation.to_bytes());
+        assert_eq!(rotation, decoded);
+    }
+}
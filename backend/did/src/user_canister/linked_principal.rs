@@ -0,0 +1,12 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Response for the `link_principal`, `unlink_principal`, `link_recipient_principal` and
+/// `unlink_recipient_principal` methods.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum LinkPrincipalResponse {
+    /// The principal was linked or unlinked.
+    Ok,
+    /// The caller is not the orchestrator.
+    Unauthorized,
+}
@@ -0,0 +1,334 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+use serde::{Deserialize, Serialize};
+
+use super::OwnerKey;
+use crate::FileId;
+use crate::utils::trap;
+
+const OP_SHARE_FILE: u8 = 0;
+const OP_REVOKE_SHARE: u8 = 1;
+const OP_REVOKE_USER_SHARES: u8 = 2;
+
+/// Operation on the orchestrator, recorded into the outbox of the user canister until the
+/// orchestrator confirms it.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum OutboxOperation {
+    /// Index the share of a file with users.
+    ///
+    /// The shares are applied on the user canister once the orchestrator confirms them.
+    ShareFile {
+        file_id: FileId,
+        file_name: String,
+        /// The users to share the file with, with the document key encrypted for them
+        users: Vec<(Principal, OwnerKey)>,
+        /// Whether the users unknown to the orchestrator are dropped from the share, rather than
+        /// rejecting it
+        partial: bool,
+    },
+    /// Remove the shares of a file from the index.
+    ///
+    /// The shares are already removed from the user canister.
+    RevokeShare {
+        file_id: FileId,
+        users: Vec<Principal>,
+    },
+    /// Remove the shares of files with a user from the index.
+    ///
+    /// The shares are already removed from the user canister.
+    RevokeUserShares {
+        user: Principal,
+        file_ids: Vec<FileId>,
+    },
+}
+
+impl OutboxOperation {
+    /// The files the operation is about.
+    pub fn file_ids(&self) -> Vec<FileId> {
+        match self {
+            Self::ShareFile { file_id, .. } | Self::RevokeShare { file_id, .. } => vec![*file_id],
+            Self::RevokeUserShares { file_ids, .. } => file_ids.clone(),
+        }
+    }
+}
+
+/// An entry of the outbox of the user canister.
+///
+/// ## Encoding
+///
+/// - 8 bytes: creation timestamp.
+/// - 4 bytes: number of attempts.
+/// - 1 byte: whether there is a last error.
+/// - For entries with a last error:
+///   - 4 bytes: length of the error.
+///   - N bytes: error.
+/// - 1 byte: operation op code.
+/// - For [`OutboxOperation::ShareFile`]:
+///   - 8 bytes: file ID.
+///   - 2 bytes: length of the file name.
+///   - N bytes: file name.
+///   - 4 bytes: number of users.
+///   - For each user: 1 byte length of the principal, N bytes principal, key.
+///   - 1 byte: whether the share is partial.
+/// - For [`OutboxOperation::RevokeShare`]:
+///   - 8 bytes: file ID.
+///   - 4 bytes: number of users.
+///   - For each user: 1 byte length of the principal, N bytes principal.
+/// - For [`OutboxOperation::RevokeUserShares`]:
+///   - 1 byte: length of the user principal.
+///   - N bytes: user principal.
+///   - 4 bytes: number of files.
+///   - For each file: 8 bytes file ID.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// The operation to deliver to the orchestrator
+    pub operation: OutboxOperation,
+    /// Time at which the operation was recorded, in nanoseconds
+    pub created_at: u64,
+    /// Number of failed delivery attempts
+    pub attempts: u32,
+    /// Error of the last failed delivery attempt, if any
+    pub last_error: Option<String>,
+}
+
+impl Storable for OutboxEntry {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.created_at.to_le_bytes());
+        bytes.extend_from_slice(&self.attempts.to_le_bytes());
+        match &self.last_error {
+            Some(error) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(error.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(error.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+        match &self.operation {
+            OutboxOperation::ShareFile {
+                file_id,
+                file_name,
+                users,
+                partial,
+            } => {
+                bytes.push(OP_SHARE_FILE);
+                bytes.extend_from_slice(&file_id.to_le_bytes());
+                bytes.extend_from_slice(&(file_name.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(file_name.as_bytes());
+                bytes.extend_from_slice(&(users.len() as u32).to_le_bytes());
+                for (user, key) in users {
+                    encode_principal(&mut bytes, user);
+                    bytes.extend_from_slice(key.as_bytes());
+                }
+                bytes.push(*partial as u8);
+            }
+            OutboxOperation::RevokeShare { file_id, users } => {
+                bytes.push(OP_REVOKE_SHARE);
+                bytes.extend_from_slice(&file_id.to_le_bytes());
+                bytes.extend_from_slice(&(users.len() as u32).to_le_bytes());
+                for user in users {
+                    encode_principal(&mut bytes, user);
+                }
+            }
+            OutboxOperation::RevokeUserShares { user, file_ids } => {
+                bytes.push(OP_REVOKE_USER_SHARES);
+                encode_principal(&mut bytes, user);
+                bytes.extend_from_slice(&(file_ids.len() as u32).to_le_bytes());
+                for file_id in file_ids {
+                    bytes.extend_from_slice(&file_id.to_le_bytes());
+                }
+            }
+        }
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut offset = 0;
+        let created_at = u64::from_le_bytes(
+            read_slice(&bytes, &mut offset, 8)
+                .try_into()
+                .expect("Invalid created_at"),
+        );
+        let attempts = u32::from_le_bytes(
+            read_slice(&bytes, &mut offset, 4)
+                .try_into()
+                .expect("Invalid attempts"),
+        );
+        let last_error = match read_slice(&bytes, &mut offset, 1)[0] {
+            0 => None,
+            1 => {
+                let len = read_len(&bytes, &mut offset);
+                let error = read_slice(&bytes, &mut offset, len);
+                Some(String::from_utf8_lossy(error).into_owned())
+            }
+            _ => trap("Failed to decode OutboxEntry: invalid last error flag"),
+        };
+        let op_code = read_slice(&bytes, &mut offset, 1)[0];
+        let operation = match op_code {
+            OP_SHARE_FILE => {
+                let file_id = read_file_id(&bytes, &mut offset);
+                let name_len = u16::from_le_bytes(
+                    read_slice(&bytes, &mut offset, 2)
+                        .try_into()
+                        .expect("Invalid file name length"),
+                ) as usize;
+                let file_name =
+                    String::from_utf8(read_slice(&bytes, &mut offset, name_len).to_vec())
+                        .unwrap_or_else(|_| {
+                            trap("Failed to decode OutboxEntry: invalid file name")
+                        });
+                let users_len = read_len(&bytes, &mut offset);
+                let users = (0..users_len)
+                    .map(|_| {
+                        let user = decode_principal(&bytes, &mut offset);
+                        let key: [u8; OwnerKey::KEY_SIZE] =
+                            read_slice(&bytes, &mut offset, OwnerKey::KEY_SIZE)
+                                .try_into()
+                                .expect("Invalid key");
+                        (user, OwnerKey::from(key))
+                    })
+                    .collect();
+                let partial = read_slice(&bytes, &mut offset, 1)[0] == 1;
+                OutboxOperation::ShareFile {
+                    file_id,
+                    file_name,
+                    users,
+                    partial,
+                }
+            }
+            OP_REVOKE_SHARE => {
+                let file_id = read_file_id(&bytes, &mut offset);
+                let users_len = read_len(&bytes, &mut offset);
+                let users = (0..users_len)
+                    .map(|_| decode_principal(&bytes, &mut offset))
+                    .collect();
+                OutboxOperation::RevokeShare { file_id, users }
+            }
+            OP_REVOKE_USER_SHARES => {
+                let user = decode_principal(&bytes, &mut offset);
+                let files_len = read_len(&bytes, &mut offset);
+                let file_ids = (0..files_len)
+                    .map(|_| read_file_id(&bytes, &mut offset))
+                    .collect();
+                OutboxOperation::RevokeUserShares { user, file_ids }
+            }
+            _ => trap("Failed to decode OutboxEntry: invalid operation op code"),
+        };
+
+        Self {
+            operation,
+            created_at,
+            attempts,
+            last_error,
+        }
+    }
+}
+
+/// An [`OutboxEntry`] with its ID.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct OutboxEntryWithId {
+    /// ID of the entry; entries are delivered in ascending order
+    pub id: u64,
+    /// The entry
+    pub entry: OutboxEntry,
+}
+
+/// Encode a principal prefixed by its length.
+fn encode_principal(bytes: &mut Vec<u8>, principal: &Principal) {
+    let principal = principal.as_slice();
+    bytes.push(principal.len() as u8);
+    bytes.extend_from_slice(principal);
+}
+
+/// Read a principal prefixed by its length, advancing the offset.
+fn decode_principal(bytes: &[u8], offset: &mut usize) -> Principal {
+    let len = read_slice(bytes, offset, 1)[0] as usize;
+    Principal::from_slice(read_slice(bytes, offset, len))
+}
+
+/// Read a 4 bytes length, advancing the offset.
+fn read_len(bytes: &[u8], offset: &mut usize) -> usize {
+    u32::from_le_bytes(
+        read_slice(bytes, offset, 4)
+            .try_into()
+            .expect("Invalid length"),
+    ) as usize
+}
+
+/// Read a file ID, advancing the offset.
+fn read_file_id(bytes: &[u8], offset: &mut usize) -> FileId {
+    FileId::from_le_bytes(
+        read_slice(bytes, offset, 8)
+            .try_into()
+            .expect("Invalid file ID"),
+    )
+}
+
+/// Read `len` bytes, advancing the offset.
+fn read_slice<'a>(bytes: &'a [u8], offset: &mut usize, len: usize) -> &'a [u8] {
+    if bytes.len() < *offset + len {
+        trap("Failed to decode OutboxEntry: not enough bytes");
+    }
+    let slice = &bytes[*offset..*offset + len];
+    *offset += len;
+
+    slice
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_outbox_entry_roundtrip() {
+        let entries = [
+            OutboxEntry {
+                operation: OutboxOperation::ShareFile {
+                    file_id: 42,
+                    file_name: "contract.pdf".to_string(),
+                    users: vec![
+                        (
+                            Principal::from_slice(&[1; 29]),
+                            [1; OwnerKey::KEY_SIZE].into(),
+                        ),
+                        (Principal::anonymous(), [2; OwnerKey::KEY_SIZE].into()),
+                    ],
+                    partial: true,
+                },
+                created_at: 1_000,
+                attempts: 0,
+                last_error: None,
+            },
+            OutboxEntry {
+                operation: OutboxOperation::RevokeShare {
+                    file_id: u64::MAX,
+                    users: vec![Principal::from_slice(&[3; 10])],
+                },
+                created_at: u64::MAX,
+                attempts: 3,
+                last_error: Some("canister is stopped".to_string()),
+            },
+            OutboxEntry {
+                operation: OutboxOperation::RevokeUserShares {
+                    user: Principal::from_slice(&[4; 29]),
+                    file_ids: vec![1, 2, u64::MAX],
+                },
+                created_at: 2_000,
+                attempts: 1,
+                last_error: None,
+            },
+        ];
+
+        for entry in entries {
+            let decoded = OutboxEntry::from_bytes(entry.to_bytes());
+            assert_eq!(entry, decoded);
+        }
+    }
+}
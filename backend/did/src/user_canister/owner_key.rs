@@ -1,7 +1,15 @@
+use std::borrow::Cow;
+
 use candid::CandidType;
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
 use serde::{Deserialize, Serialize};
 
 /// User decryption key
+///
+/// ## Encoding
+///
+/// - 512 bytes: the key.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType)]
 pub struct OwnerKey([u8; Self::KEY_SIZE]);
 
@@ -30,6 +38,23 @@ impl From<[u8; Self::KEY_SIZE]> for OwnerKey {
     }
 }
 
+impl Storable for OwnerKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: Self::KEY_SIZE as u32,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let mut key = [0; Self::KEY_SIZE];
+        key.copy_from_slice(&bytes[..Self::KEY_SIZE]);
+        OwnerKey(key)
+    }
+}
+
 impl Serialize for OwnerKey {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
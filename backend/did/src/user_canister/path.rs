@@ -1,3 +1,4 @@
+use std::fmt;
 use std::rc::Rc;
 use std::str::FromStr;
 
@@ -80,6 +81,12 @@ impl Storable for Path {
     }
 }
 
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 impl FromStr for Path {
     type Err = &'static str;
 
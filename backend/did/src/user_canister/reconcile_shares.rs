@@ -0,0 +1,35 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::FileId;
+
+/// The share of a file with a user.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, CandidType, Serialize, Deserialize,
+)]
+pub struct FileShare {
+    pub file_id: FileId,
+    pub user: Principal,
+}
+
+/// Report of the reconciliation of the shares of the user canister with the orchestrator.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ShareReconciliationReport {
+    /// Time at which the reconciliation completed, in nanoseconds
+    pub reconciled_at: u64,
+    /// Shares held by the orchestrator only, whose revocation was recorded into the outbox
+    pub revoked_on_orchestrator: Vec<FileShare>,
+    /// Shares held by the user canister only, which were dropped
+    pub dropped: Vec<FileShare>,
+}
+
+/// Result for `reconcile_shares` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ReconcileSharesResponse {
+    /// The shares were reconciled
+    Ok(ShareReconciliationReport),
+    /// A reconciliation is already in progress
+    InProgress,
+    /// The shares held by the orchestrator could not be retrieved
+    FailedToGetShares(String),
+}
@@ -0,0 +1,62 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use super::OwnerKey;
+use crate::FileId;
+
+/// Request for the `reencrypt_file` method.
+///
+/// The chunks encrypted with the new document key are then uploaded with
+/// `reencrypt_file_continue`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReencryptFileRequest {
+    /// The file to re-encrypt
+    pub file_id: FileId,
+    /// Users whose access to the file is revoked
+    pub revoke: Vec<Principal>,
+    /// Number of chunks of the re-encrypted file
+    pub num_chunks: u64,
+    /// The new document key, encrypted with the owner public key
+    pub owner_key: OwnerKey,
+    /// The new document key, encrypted for each remaining recipient
+    pub shared_keys: Vec<(Principal, OwnerKey)>,
+}
+
+/// Response for the `reencrypt_file` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ReencryptFileResponse {
+    /// The users were revoked and the re-encrypted chunks can be uploaded.
+    Ok,
+    /// The file was not found.
+    FileNotFound,
+    /// The file is not fully uploaded yet.
+    NotUploadedFile,
+    /// The re-encrypted file must have at least one chunk.
+    InvalidNumChunks,
+    /// The owner public key is being rotated.
+    KeyRotationInProgress,
+    /// No key was given for a recipient who keeps access to the file.
+    MissingSharedKey(Principal),
+    /// A key was given for a user the file is not shared with, or who is revoked.
+    UnexpectedSharedKey(Principal),
+}
+
+/// Response for the `reencrypt_file_continue` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ReencryptFileContinueResponse {
+    /// The chunk was staged; more chunks are expected.
+    Ok,
+    /// All the chunks were uploaded and the file now uses the new document key.
+    Completed,
+    /// There is no re-encryption in progress for the file.
+    NoReencryptionInProgress,
+    /// The chunk was already uploaded.
+    ChunkAlreadyUploaded,
+    /// The chunk is out of bounds (chunk_id >= num_chunks).
+    ChunkOutOfBounds,
+    /// The file was shared or revoked in the meantime; the re-encryption was aborted.
+    RecipientsChanged,
+    /// The owner public key is being rotated, or was rotated since the re-encryption was started;
+    /// the re-encryption was aborted.
+    OwnerKeyChanged,
+}
@@ -0,0 +1,113 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use super::Path;
+use crate::FileId;
+
+/// How `share_file_with_users` handles the recipients the file can't be shared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ShareMode {
+    /// The file is shared with nobody if it can't be shared with every recipient
+    AllOrNothing,
+    /// The file is shared with the recipients it can be shared with
+    Partial,
+}
+
+/// Outcome of the share of a file with a recipient.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ShareOutcome {
+    /// The file was shared with the recipient
+    Ok,
+    /// The orchestrator could not be reached; the share will be retried
+    Pending,
+    /// The recipient is not a registered user
+    NoSuchUser,
+    /// The recipient blocked the owner of the file
+    Blocked,
+    /// The file is already shared with the recipient
+    AlreadyShared,
+    /// No key was provided for the recipient
+    KeyLengthMismatch,
+    /// The file was not shared with the recipient, since it could not be shared with another
+    /// recipient in [`ShareMode::AllOrNothing`] mode
+    NotShared,
+    /// The orchestrator rejected the share
+    Rejected(String),
+}
+
+/// Outcome of the share of a file with a recipient of `share_file_with_users`.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ShareResult {
+    pub user: Principal,
+    pub outcome: ShareOutcome,
+}
+
+/// Result for `share_file_with_users` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ShareFileWithUsersResponse {
+    /// The outcome for each recipient, in the order of the request
+    Ok(Vec<ShareResult>),
+    /// The file doesn't exist
+    FileNotFound,
+    /// The file has not been uploaded yet
+    PendingError,
+    /// The owner is suspended; the file can't be shared with new users
+    Suspended,
+}
+
+/// Outcome of the revocation of the share of a file for a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum RevokeOutcome {
+    /// The share was revoked
+    Ok,
+    /// The file is not shared with the user
+    NotShared,
+}
+
+/// Outcome of the revocation of a share for a user of `revoke_share_for_users`.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct RevokeResult {
+    pub user: Principal,
+    pub outcome: RevokeOutcome,
+}
+
+/// Result for `revoke_share` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum RevokeShareResponse {
+    /// The share was revoked
+    Ok,
+    /// The file is not shared with the user
+    NotShared,
+    /// The file doesn't exist
+    FileNotFound,
+}
+
+/// Result for `revoke_share_for_users` method
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum RevokeShareForUsersResponse {
+    /// The outcome for each user, in the order of the request
+    Ok(Vec<RevokeResult>),
+    /// The file doesn't exist
+    FileNotFound,
+}
+
+/// A file whose share with a user was revoked by `revoke_all_for_user`.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct RevokedShare {
+    pub file_id: FileId,
+    pub path: Path,
+}
+
+/// Report of the `revoke_all_for_user` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct RevokeAllForUserReport {
+    /// The user whose shares were revoked
+    pub user: Principal,
+    /// The files which were shared with the user
+    pub revoked: Vec<RevokedShare>,
+    /// The files whose share with the user was waiting for the orchestrator, and was cancelled
+    pub cancelled: Vec<RevokedShare>,
+    /// Whether the revocation is waiting in the outbox, since the orchestrator could not be
+    /// reached; it will be retried
+    pub pending: bool,
+}
@@ -0,0 +1,13 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Response for the `set_suspended` method.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum SetSuspendedResponse {
+    /// The owner was suspended or reinstated.
+    Ok,
+    /// The owner was already suspended, or not suspended.
+    Unchanged,
+    /// The caller is not the orchestrator.
+    Unauthorized,
+}
@@ -0,0 +1,173 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use super::{OwnerKey, Path};
+use crate::FileId;
+
+/// Maximum number of chunks of a file being transferred
+pub const MAX_TRANSFER_NUM_CHUNKS: u64 = 1024;
+
+/// Maximum size of a chunk of a file being transferred, in bytes
+pub const MAX_TRANSFER_CHUNK_SIZE: usize = 2 * 1024 * 1024;
+
+/// Maximum number of transfers a user canister can stage at once from the same user canister
+pub const MAX_INCOMING_TRANSFERS_PER_SENDER: usize = 8;
+
+/// Request for the `transfer_file` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TransferFileRequest {
+    /// The file to transfer
+    pub file_id: FileId,
+    /// The user who becomes the owner of the file
+    pub recipient: Principal,
+    /// The document key, encrypted with the public key of the recipient
+    pub owner_key: OwnerKey,
+    /// Whether the file stays shared with its current recipients
+    pub keep_shares: bool,
+}
+
+/// Response for the `transfer_file` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TransferFileResponse {
+    /// The file was sent to the user canister of the recipient; it is deleted once the recipient
+    /// accepts the transfer.
+    Ok,
+    /// The file was not found.
+    FileNotFound,
+    /// The file is not fully uploaded yet.
+    NotUploadedFile,
+    /// The file has more chunks than can be transferred.
+    TooManyChunks,
+    /// The owner is suspended; files can't be transferred.
+    Suspended,
+    /// The owner public key is being rotated.
+    KeyRotationInProgress,
+    /// The file is already being transferred.
+    TransferInProgress,
+    /// The recipient is the owner of the file.
+    RecipientIsOwner,
+    /// The recipient has no user canister.
+    NoSuchUser,
+    /// The recipient already has a file at the same path.
+    FileAlreadyExists,
+    /// The file was changed while being transferred; the transfer was aborted.
+    FileChanged,
+    /// The transfer failed and can be started again; returns the reason.
+    Failed(String),
+}
+
+/// Request for the `receive_file_transfer` method, called by the user canister of the current
+/// owner of the file.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReceiveFileTransferRequest {
+    /// The current owner of the file
+    pub owner: Principal,
+    /// The ID of the file in the user canister of the current owner
+    pub file_id: FileId,
+    /// The path of the file
+    pub path: Path,
+    /// The MIME type of the file
+    pub file_type: String,
+    /// Number of chunks of the file
+    pub num_chunks: u64,
+    /// The document key, encrypted with the public key of the recipient
+    pub owner_key: OwnerKey,
+    /// The document key, encrypted for each user the file is shared with again
+    pub shared_keys: Vec<(Principal, OwnerKey)>,
+}
+
+/// Response for the `receive_file_transfer` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ReceiveFileTransferResponse {
+    /// The transfer was started and the chunks can be sent.
+    Ok,
+    /// The caller is not the user canister of the owner of the file.
+    Unauthorized,
+    /// A file already exists at the same path.
+    FileAlreadyExists,
+    /// The file must have at least one chunk, and at most [`MAX_TRANSFER_NUM_CHUNKS`].
+    InvalidNumChunks,
+    /// Too many transfers from the user canister are staged already.
+    TooManyTransfers,
+    /// The recipient is accepting a previous transfer of the file.
+    AcceptInProgress,
+    /// The owner is suspended; files can't be received.
+    Suspended,
+    /// The user canister of the owner could not be checked; returns the reason.
+    FailedToCheckSender(String),
+}
+
+/// Response for the `receive_file_transfer_chunk` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ReceiveFileTransferChunkResponse {
+    /// The chunk was staged.
+    Ok,
+    /// There is no transfer in progress for the file, or it was completed already.
+    NoTransferInProgress,
+    /// The chunk is out of bounds (chunk_id >= num_chunks).
+    ChunkOutOfBounds,
+    /// The chunk is larger than [`MAX_TRANSFER_CHUNK_SIZE`].
+    ChunkTooLarge,
+}
+
+/// Response for the `complete_file_transfer` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum CompleteFileTransferResponse {
+    /// All the chunks were received; the transfer awaits the acceptance of the recipient.
+    Ok,
+    /// There is no transfer in progress for the file.
+    NoTransferInProgress,
+    /// Not all the chunks were received; returns the number of missing chunks.
+    MissingChunks(u64),
+    /// A file already exists at the same path.
+    FileAlreadyExists,
+}
+
+/// A transfer of a file to the owner of the canister, awaiting their acceptance.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IncomingFileTransfer {
+    /// The user canister sending the file
+    pub sender: Principal,
+    /// The ID of the file in the user canister sending it
+    pub file_id: FileId,
+    /// The current owner of the file
+    pub owner: Principal,
+    /// The path of the file
+    pub path: Path,
+    /// The MIME type of the file
+    pub file_type: String,
+    /// Number of chunks of the file
+    pub num_chunks: u64,
+    /// When the transfer was started
+    pub started_at: u64,
+}
+
+/// Response for the `accept_file_transfer` method.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AcceptFileTransferResponse {
+    /// The file was created, and deleted from the user canister sending it; returns its ID.
+    Ok(FileId),
+    /// There is no transfer awaiting acceptance for the file.
+    NoTransferInProgress,
+    /// The transfer is already being accepted.
+    AcceptInProgress,
+    /// A file already exists at the same path.
+    FileAlreadyExists,
+    /// The sender withdrew the file, or changed it since it was sent; the transfer was dropped.
+    Withdrawn,
+    /// The user canister sending the file could not be called; the transfer can be accepted
+    /// again. Returns the reason.
+    Failed(String),
+}
+
+/// Response for the `release_file_transfer` method, called by the user canister of the recipient
+/// of a file once they accept it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ReleaseFileTransferResponse {
+    /// The file was deleted; the recipient owns it now.
+    Ok,
+    /// The file is not being transferred to the caller.
+    NoTransferInProgress,
+    /// The file was deleted or changed since it was sent; the transfer was dropped.
+    FileChanged,
+}
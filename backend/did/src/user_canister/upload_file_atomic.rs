@@ -10,6 +10,8 @@ pub enum UploadFileAtomicResponse {
     Ok(FileId),
     /// File already exists.
     FileAlreadyExists,
+    /// The owner is suspended.
+    Suspended,
 }
 
 impl UploadFileAtomicResponse {
@@ -37,3 +37,33 @@ pub fn time() -> u64 {
         time.as_nanos() as u64
     }
 }
+
+/// Returns the size of the stable memory, in WebAssembly pages of 64 KiB
+pub fn stable_memory_pages() -> u64 {
+    if cfg!(target_family = "wasm") {
+        ic_cdk::stable::stable_size()
+    } else {
+        0
+    }
+}
+
+/// Returns the size of the heap memory, in bytes
+pub fn heap_memory_size() -> u64 {
+    #[cfg(target_family = "wasm")]
+    {
+        core::arch::wasm32::memory_size(0) as u64 * 65536
+    }
+    #[cfg(not(target_family = "wasm"))]
+    {
+        0
+    }
+}
+
+/// Returns the cycle balance of the canister
+pub fn cycle_balance() -> u128 {
+    if cfg!(target_family = "wasm") {
+        ic_cdk::api::canister_cycle_balance()
+    } else {
+        0
+    }
+}
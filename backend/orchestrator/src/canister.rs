@@ -1,25 +1,90 @@
 mod create_user;
+mod delete_account;
+mod revocations;
+mod share_log;
+
+use std::collections::BTreeSet;
 
 use candid::Principal;
 use create_user::CreateUserStateMachine;
+use delete_account::DeleteAccountStateMachine;
+use did::http::{HttpRequest, HttpResponse, METRICS_PATH, MetricsEncoder};
 use did::orchestrator::{
-    FileId, GetUsersResponse, GetUsersResponseUsers, MAX_USERNAME_SIZE, OrchestratorInstallArgs,
-    Pagination, PublicFileMetadata, PublicKey, PublicUser, RetryUserCanisterCreationResponse,
-    RevokeShareFileResponse, SetUserResponse, ShareFileMetadata, ShareFileResponse,
-    SharedFilesResponse, User, UserCanisterResponse, WhoamiResponse,
+    AddContactRequest, AddContactResponse, AddDeviceKeyRequest, AddDeviceKeyResponse,
+    AdminFailedUserCanistersResponse, AdminReleaseUsernameResponse, AdminReserveUsernameResponse,
+    AdminReservedUsernamesResponse, AdminRetryUserCanisterCreationResponse, AdminStatsResponse,
+    AdminSuspendUserResponse, AdminUnsuspendUserResponse, AdminUser, AdminUserCanisterState,
+    AdminUsernameCollisionsResponse, AdminUsers, AdminUsersResponse, ApprovePrincipalLinkResponse,
+    BlockResponse, Contact, Contacts, ContactsResponse, CreateInviteRequest, CreateInviteResponse,
+    CursorPagination, DataCertificate, DeclineShareResponse, DeleteAccountRequest,
+    DeleteAccountResponse, DeviceId, DeviceKey, FailedUserCanister, FailedUserCanisters,
+    FileCursorPagination, FileId, FileShares, GetBlockedResponse, GetBlocksRequest,
+    GetBlocksResponse, GetUserCanisterResponse, GetUsersResponse, GetUsersResponseUsers, Invite,
+    InviteState, InvitesResponse, LinkedPrincipals, LinkedPrincipalsResponse,
+    MAX_CONTACT_NICKNAME_SIZE, MAX_DEVICE_NAME_SIZE, MAX_DEVICES_PER_USER, MAX_DISPLAY_NAME_SIZE,
+    MAX_INVITES_PER_USER, MAX_LINK_REQUESTS_PER_ACCOUNT, MAX_USERNAME_SIZE,
+    OrchestratorInstallArgs, OrchestratorStats, Pagination, PublicFileMetadata, PublicKey,
+    PublicUser, RemoveContactResponse, RemoveDeviceKeyResponse, RequestPrincipalLinkResponse,
+    RetryUserCanisterCreationResponse, RevokeInviteResponse, RevokeShareFileResponse,
+    SearchUsersResponse, SearchUsersResponseUsers, SetUserResponse, ShareFileMetadata,
+    ShareFileResponse, ShareOperation, ShareState, ShareStateResponse, SharedByMeFile,
+    SharedByMeFiles, SharedByMeResponse, SharedFilesResponse, UnblockResponse,
+    UnlinkPrincipalResponse, UpdateProfileRequest, UpdateProfileResponse, UpdatePublicKeyResponse,
+    UpdateUserPublicKeyResponse, User, UserCanisterResponse, UserCanisterShares,
+    UserCanisterSharesResponse, WhoamiResponse, normalize_username, username_key,
+};
+use did::user_canister::{
+    FileSharingResponse, LinkPrincipalResponse, SetSuspendedResponse, SyncDeviceKeyResponse,
+    SyncPublicKeyResponse,
 };
+use did::utils::{cycle_balance, heap_memory_size, stable_memory_pages};
+use revocations::PendingRevocations;
+use share_log::ShareLog;
 
+use crate::client::UserCanisterClient;
 use crate::debug;
+use crate::storage::account_deletion::AccountDeletionStorage;
+use crate::storage::block_list::BlockListStorage;
 use crate::storage::config::Config;
+use crate::storage::contacts::ContactsStorage;
+use crate::storage::device_keys::DeviceKeysStorage;
+use crate::storage::invites::{
+    InviteCode, InviteEntry, InvitesStorage, format_invite_code, parse_invite_code,
+};
+use crate::storage::linked_principals::LinkedPrincipalsStorage;
+use crate::storage::moderation::ModerationStorage;
+use crate::storage::share_log::ShareLogStorage;
 use crate::storage::shared_files::SharedFilesStorage;
 use crate::storage::user_canister::{UserCanisterCreateState, UserCanisterStorage};
 use crate::storage::users::UserStorage;
-use crate::utils::{msg_caller, trap};
+use crate::utils::{msg_caller, random_bytes, time, trap};
 
 /// Maximum number of users to retrieve at once.
 const MAX_GET_USERS_LIMIT: u64 = 128;
 /// Minimum length of the query string for getting users.
 const GET_USERS_QUERY_MIN_LENGTH: usize = 4;
+/// Maximum number of files that can be retrieved at once with `shared_by_me`.
+const MAX_SHARED_BY_ME_LIMIT: u64 = 128;
+/// Maximum number of blocks of the share log to retrieve at once.
+const MAX_GET_BLOCKS_LIMIT: u64 = 128;
+/// Maximum number of contacts that can be retrieved at once.
+const MAX_CONTACTS_LIMIT: u64 = 128;
+/// Maximum number of shared files to retrieve at once for a user canister.
+const MAX_USER_CANISTER_SHARES_LIMIT: u64 = 128;
+/// Time after which a request to link a principal to an account expires, in nanoseconds.
+const LINK_REQUEST_EXPIRY: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Time after which an invite which was not redeemed expires, in nanoseconds.
+const INVITE_EXPIRY: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+/// Version of the stable memory in which the usernames are keyed by their canonical key, and
+/// mapped to the user who has them.
+const USERNAMES_SCHEMA_VERSION: u64 = 1;
+/// Version of the stable memory in which the user canisters are indexed by owner.
+const OWNERS_INDEX_SCHEMA_VERSION: u64 = 2;
+/// Version of the stable memory in which the users files are shared with are in the contacts of
+/// the owners.
+const CONTACTS_SCHEMA_VERSION: u64 = 3;
+/// Current version of the stable memory.
+const SCHEMA_VERSION: u64 = CONTACTS_SCHEMA_VERSION;
 
 /// API for Business Logic
 pub struct Canister;
@@ -35,429 +100,3918 @@ impl Canister {
 
         Config::set_orbit_station(args.orbit_station);
         Config::set_orbit_station_admin(args.orbit_station_admin);
+        Config::set_admins(args.admins);
+        // a new canister has nothing to migrate
+        Config::set_schema_version(SCHEMA_VERSION);
+
+        PendingRevocations::schedule_retries();
     }
 
-    /// Get the users from the storage as [`GetUsersResponse`].
-    ///
-    /// If the caller is anonymous, it returns [`GetUsersResponse::PermissionError`].
-    ///
-    /// Up to 128 users can be retrieved at once.
-    ///
-    /// FIXME: this function should be protected.
-    ///
-    /// # Arguments
-    ///
-    /// - `Pagination`: The pagination parameters, including `offset` and `limit`.
-    /// - `query`: An optional query string to filter users by username. It has a minimum length of [`GET_USERS_QUERY_MIN_LENGTH`].
-    pub fn get_users(
-        Pagination { offset, limit }: Pagination,
-        query: Option<&str>,
-    ) -> GetUsersResponse {
-        let limit = limit.min(MAX_GET_USERS_LIMIT);
-        debug!("Getting users with offset: {offset}, limit: {limit}, query: {query:?}",);
+    /// Restore the canister state after an upgrade.
+    pub fn post_upgrade(args: OrchestratorInstallArgs) {
+        let OrchestratorInstallArgs::Upgrade(args) = args else {
+            trap("Invalid arguments");
+        };
 
-        let caller = msg_caller();
-        if caller == Principal::anonymous() {
-            return GetUsersResponse::PermissionError;
-        }
+        debug!("Upgrading canister with args: {:?}", args);
 
-        // Validate the query length.
-        if query.is_some_and(|q| q.len() < GET_USERS_QUERY_MIN_LENGTH) {
-            debug!("Query is too short: {query:?}",);
-            return GetUsersResponse::InvalidQuery;
+        if let Some(admins) = args.admins {
+            Config::set_admins(admins);
         }
 
-        let filter = |user: &User| {
-            if let Some(query) = query {
-                user.username.contains(query)
-            } else {
-                true
-            }
-        };
+        // certified data is not kept across upgrades
+        ShareLog::certify_tip();
 
-        let users = UserStorage::get_users_in_range(offset, limit, filter)
-            .into_iter()
-            .map(|(principal, user)| PublicUser::new(user, principal))
-            .collect::<Vec<_>>();
+        Self::migrate();
 
-        let max_users = UserStorage::count(filter);
-        let next = if offset + limit < max_users {
-            Some(offset + limit)
-        } else {
-            None
-        };
+        // timers are not kept across upgrades
+        if cfg!(target_family = "wasm") {
+            DeleteAccountStateMachine::resume_all(Config::get_orbit_station());
+        }
+        PendingRevocations::schedule_retries();
+    }
 
-        GetUsersResponse::Users(GetUsersResponseUsers {
-            users,
-            total: max_users,
-            next,
-        })
+    /// Run the migrations of the stable memory not run yet, up to [`SCHEMA_VERSION`].
+    fn migrate() {
+        let version = Config::get_schema_version();
+        if version < USERNAMES_SCHEMA_VERSION {
+            let collisions = UserStorage::migrate_usernames();
+            if !collisions.is_empty() {
+                debug!("Found usernames colliding on their canonical key: {collisions:?}");
+            }
+        }
+        if version < OWNERS_INDEX_SCHEMA_VERSION {
+            UserCanisterStorage::build_owners_index();
+        }
+        if version < CONTACTS_SCHEMA_VERSION {
+            Self::import_contacts_from_shares();
+        }
+
+        if version != SCHEMA_VERSION {
+            debug!("Migrated stable memory from version {version} to {SCHEMA_VERSION}");
+            Config::set_schema_version(SCHEMA_VERSION);
+        }
     }
 
-    /// Get a user from the storage as [`PublicUser`].
-    pub fn get_user(principal: Principal) -> Option<PublicUser> {
-        debug!("Getting user with principal: {principal}",);
-        UserStorage::get_user(&principal).map(|user| PublicUser::new(user, principal))
+    /// Accept a file shared with the caller by the given user canister.
+    ///
+    /// # Returns
+    ///
+    /// - [`ShareStateResponse::Ok`] if the share was accepted.
+    /// - [`ShareStateResponse::AnonymousUser`] if the caller is anonymous.
+    /// - [`ShareStateResponse::NoSuchShare`] if the file is not shared with the caller or the share was declined.
+    pub fn accept_share(user_canister: Principal, file_id: FileId) -> ShareStateResponse {
+        debug!("Accepting share for user_canister: {user_canister}, file_id: {file_id}");
+        Self::update_share_state(user_canister, file_id, ShareState::Accepted)
     }
 
-    /// Retry the user canister creation for the current caller.
+    /// Add a user to the contacts of the caller, or update the nickname of the contact.
     ///
     /// # Returns
     ///
-    /// - [`RetryUserCanisterCreationResponse::Ok`] if the user canister creation is retried.
-    /// - [`RetryUserCanisterCreationResponse::Created`] if the user canister already exists.
-    /// - [`RetryUserCanisterCreationResponse::AnonymousCaller`]: The caller is anonymous.
-    /// - [`RetryUserCanisterCreationResponse::CreationPending`]: The user canister creation is already in progress.
-    /// - [`RetryUserCanisterCreationResponse::UserNotFound`]: The user doesn't exist. In that case, the caller should call `set_user` first.
-    pub fn retry_user_canister_creation() -> RetryUserCanisterCreationResponse {
-        debug!(
-            "Retrying user canister creation for caller: {}",
-            msg_caller()
-        );
-        let caller = msg_caller();
+    /// - [`AddContactResponse::Ok`] if the contact was added or updated.
+    /// - [`AddContactResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`AddContactResponse::NoSuchUser`] if the caller has no user.
+    /// - [`AddContactResponse::NoSuchContact`] if the user to add doesn't exist or is being deleted.
+    /// - [`AddContactResponse::CannotAddSelf`] if the caller tries to add themselves.
+    /// - [`AddContactResponse::NicknameTooLong`] if the nickname is too long.
+    pub fn add_contact(
+        AddContactRequest { user, nickname }: AddContactRequest,
+    ) -> AddContactResponse {
+        debug!("Adding contact: {user}, nickname: {nickname:?}");
+        let caller = Self::account_caller();
         if caller == Principal::anonymous() {
-            return RetryUserCanisterCreationResponse::AnonymousCaller;
+            return AddContactResponse::AnonymousCaller;
         }
-
-        // check if the user exists
         if UserStorage::get_user(&caller).is_none() {
-            return RetryUserCanisterCreationResponse::UserNotFound;
+            return AddContactResponse::NoSuchUser;
+        }
+        if user == caller {
+            return AddContactResponse::CannotAddSelf;
+        }
+        if nickname
+            .as_ref()
+            .is_some_and(|nickname| nickname.len() > MAX_CONTACT_NICKNAME_SIZE)
+        {
+            return AddContactResponse::NicknameTooLong;
+        }
+        if UserStorage::get_user(&user).is_none()
+            || AccountDeletionStorage::is_deletion_pending(user)
+        {
+            return AddContactResponse::NoSuchContact;
         }
 
-        // check if the user canister already exists
-        if let Some(canister) = UserCanisterStorage::get_user_canister(caller) {
-            return RetryUserCanisterCreationResponse::Created(canister);
+        let nickname = nickname.filter(|nickname| !nickname.is_empty());
+        ContactsStorage::set_contact(caller, user, nickname);
+
+        AddContactResponse::Ok
+    }
+
+    /// Register the public key of a device of the caller.
+    ///
+    /// The key is first added on the user canister of the caller, then on the orchestrator. The
+    /// document keys of the files can then be encrypted for each device of the caller, so that
+    /// the private key never has to be copied between devices.
+    ///
+    /// # Returns
+    ///
+    /// - [`AddDeviceKeyResponse::Ok`] with the ID of the device.
+    /// - [`AddDeviceKeyResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`AddDeviceKeyResponse::NoSuchUser`] if the caller has no user.
+    /// - [`AddDeviceKeyResponse::NameTooLong`] if the name of the device is too long.
+    /// - [`AddDeviceKeyResponse::TooManyDevices`] if the caller has already [`MAX_DEVICES_PER_USER`] devices.
+    /// - [`AddDeviceKeyResponse::UserCanisterNotReady`] if the user canister of the caller is not created yet.
+    /// - [`AddDeviceKeyResponse::FailedToUpdateUserCanister`] if the user canister could not be updated.
+    pub async fn add_device_key(
+        AddDeviceKeyRequest { name, public_key }: AddDeviceKeyRequest,
+    ) -> AddDeviceKeyResponse {
+        debug!("Adding device key: {name}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return AddDeviceKeyResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return AddDeviceKeyResponse::NoSuchUser;
         }
+        if name.len() > MAX_DEVICE_NAME_SIZE {
+            return AddDeviceKeyResponse::NameTooLong;
+        }
+        if DeviceKeysStorage::get_device_keys(caller).len() >= MAX_DEVICES_PER_USER {
+            return AddDeviceKeyResponse::TooManyDevices;
+        }
+        let Some(user_canister) = UserCanisterStorage::get_user_canister(caller) else {
+            return AddDeviceKeyResponse::UserCanisterNotReady;
+        };
 
-        // check the current state of the user canister creation
-        match UserCanisterStorage::get_create_state(caller) {
-            Some(UserCanisterCreateState::Ok { user_canister }) => {
-                RetryUserCanisterCreationResponse::Created(user_canister)
+        // the ID is reserved before calling the user canister, so that concurrent calls get
+        // different IDs
+        let device_key = DeviceKey {
+            device_id: DeviceKeysStorage::reserve_device_id(caller),
+            name,
+            public_key,
+        };
+        if cfg!(target_family = "wasm") {
+            match UserCanisterClient::from(user_canister)
+                .add_device_key(&device_key)
+                .await
+            {
+                Ok(SyncDeviceKeyResponse::Ok) => {}
+                Ok(err) => {
+                    return AddDeviceKeyResponse::FailedToUpdateUserCanister(format!("{err:?}"));
+                }
+                Err(err) => {
+                    return AddDeviceKeyResponse::FailedToUpdateUserCanister(err.to_string());
+                }
             }
-            Some(UserCanisterCreateState::Failed { .. }) | None => {
-                if cfg!(target_family = "wasm") {
-                    CreateUserStateMachine::start(Config::get_orbit_station(), caller);
+        }
+
+        // other devices may have been added while waiting for the user canister
+        if DeviceKeysStorage::get_device_keys(caller).len() >= MAX_DEVICES_PER_USER {
+            if cfg!(target_family = "wasm") {
+                if let Err(err) = UserCanisterClient::from(user_canister)
+                    .remove_device_key(device_key.device_id)
+                    .await
+                {
+                    debug!(
+                        "Failed to remove device key {} from user canister: {err}",
+                        device_key.device_id
+                    );
                 }
-                RetryUserCanisterCreationResponse::Ok
             }
-            Some(_) => RetryUserCanisterCreationResponse::CreationPending,
+            return AddDeviceKeyResponse::TooManyDevices;
         }
+
+        let device_id = device_key.device_id;
+        DeviceKeysStorage::insert_device_key(caller, device_key);
+
+        AddDeviceKeyResponse::Ok(device_id)
     }
 
-    /// Revoke the share of a file for a user.
+    /// Get the users whose user canister creation failed, along with the reason of the failure.
+    ///
+    /// Up to 128 failed creations can be retrieved at once. They are ordered by user principal;
+    /// the next page is retrieved by passing the `next` cursor of the response.
     ///
     /// # Returns
     ///
-    /// - [`RevokeShareFileResponse::Ok`] if the file was unshared successfully.
-    /// - [`RevokeShareFileResponse::NoSuchUser`] if the user doesn't exist.
-    /// - [`RevokeShareFileResponse::Unauthorized`] if the caller is not a user canister.
-    pub fn revoke_share_file(user: Principal, file_id: FileId) -> RevokeShareFileResponse {
-        debug!("Revoking share for user: {user}, file_id: {file_id}",);
-        let user_canister = msg_caller();
-        // check if the caller is a user canister
-        if !UserCanisterStorage::is_user_canister(user_canister) {
-            return RevokeShareFileResponse::Unauthorized;
+    /// - [`AdminFailedUserCanistersResponse::Failed`] with the page of failed creations.
+    /// - [`AdminFailedUserCanistersResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_failed_user_canisters(
+        CursorPagination { cursor, limit }: CursorPagination,
+    ) -> AdminFailedUserCanistersResponse {
+        if !Config::is_admin(msg_caller()) {
+            return AdminFailedUserCanistersResponse::Unauthorized;
         }
 
-        // Revoke share for the user
-        SharedFilesStorage::revoke_share(user, user_canister, file_id);
+        let limit = limit.min(MAX_GET_USERS_LIMIT);
+        let (failed, next) = UserCanisterStorage::get_failed_create_states(cursor, limit);
+        let failed = failed
+            .into_iter()
+            .map(|(user, reason)| FailedUserCanister { user, reason })
+            .collect();
 
-        RevokeShareFileResponse::Ok
+        AdminFailedUserCanistersResponse::Failed(FailedUserCanisters { failed, next })
     }
 
-    /// Revoke the share of a file for a list of users.
+    /// Release a username reserved with [`Self::admin_reserve_username`].
     ///
     /// # Returns
     ///
-    /// - [`RevokeShareFileResponse::Ok`] if the file was unshared successfully.
-    /// - [`RevokeShareFileResponse::NoSuchUser`] if the user doesn't exist.
-    /// - [`RevokeShareFileResponse::Unauthorized`] if the caller is not a user canister.
-    pub fn revoke_share_file_for_users(
-        users: Vec<Principal>,
-        file_id: FileId,
-    ) -> RevokeShareFileResponse {
-        debug!(
-            "Revoking share for users: {:?}, file_id: {}",
-            users, file_id
-        );
-        let user_canister = msg_caller();
-        // check if the caller is a user canister
-        if !UserCanisterStorage::is_user_canister(user_canister) {
-            return RevokeShareFileResponse::Unauthorized;
+    /// - [`AdminReleaseUsernameResponse::Ok`] if the username is not reserved anymore.
+    /// - [`AdminReleaseUsernameResponse::NotReserved`] if the username is not reserved.
+    /// - [`AdminReleaseUsernameResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_release_username(username: String) -> AdminReleaseUsernameResponse {
+        debug!("Releasing username: {username}");
+        if !Config::is_admin(msg_caller()) {
+            return AdminReleaseUsernameResponse::Unauthorized;
         }
 
-        // Revoke share for the user
-        for user in users {
-            SharedFilesStorage::revoke_share(user, user_canister, file_id);
+        if ModerationStorage::release_username(&username) {
+            AdminReleaseUsernameResponse::Ok
+        } else {
+            AdminReleaseUsernameResponse::NotReserved
         }
-
-        RevokeShareFileResponse::Ok
     }
 
-    /// Set a new user in the storage.
+    /// Reserve a username, so that no user can register with it or take it, regardless of its
+    /// case.
+    ///
+    /// A user who already has the username keeps it.
     ///
     /// # Returns
     ///
-    /// - [`SetUserResponse::Ok`] if the user was set successfully.
-    /// - [`SetUserResponse::AnonymousCaller`] if the caller is anonymous.
-    /// - [`SetUserResponse::UsernameTooLong`] if the username is too long.
-    /// - [`SetUserResponse::UsernameExists`] if the username already exists.
-    /// - [`SetUserResponse::CallerHasAlreadyAUser`] if the caller already has a user.
-    pub fn set_user(username: String, public_key: PublicKey) -> SetUserResponse {
-        debug!("Setting user with username: {username}, public_key: {public_key:?}",);
-        // Check if the caller is anonymous.
-        let caller = msg_caller();
-        if caller == Principal::anonymous() {
-            return SetUserResponse::AnonymousCaller;
+    /// - [`AdminReserveUsernameResponse::Ok`] if the username is reserved.
+    /// - [`AdminReserveUsernameResponse::InvalidUsername`] if the username is too long or has characters which are not allowed.
+    /// - [`AdminReserveUsernameResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_reserve_username(username: String) -> AdminReserveUsernameResponse {
+        debug!("Reserving username: {username}");
+        if !Config::is_admin(msg_caller()) {
+            return AdminReserveUsernameResponse::Unauthorized;
         }
-
-        // Check if the username is too long.
         if username.len() > MAX_USERNAME_SIZE {
-            return SetUserResponse::UsernameTooLong;
-        }
-
-        // check if username already exists
-        if UserStorage::username_exists(&username) {
-            return SetUserResponse::UsernameExists;
+            return AdminReserveUsernameResponse::InvalidUsername;
         }
+        let Some(username) = normalize_username(&username) else {
+            return AdminReserveUsernameResponse::InvalidUsername;
+        };
 
-        // check if the caller already has a user
-        if UserStorage::get_user(&caller).is_some() {
-            return SetUserResponse::CallerHasAlreadyAUser;
-        }
+        ModerationStorage::reserve_username(username);
 
-        // Add the user to the storage and return Ok.
-        UserStorage::add_user(
-            caller,
-            User {
-                username,
-                public_key,
-            },
-        );
+        AdminReserveUsernameResponse::Ok
+    }
 
-        // start state machine to create user canister
-        if cfg!(target_family = "wasm") {
-            CreateUserStateMachine::start(Config::get_orbit_station(), caller);
+    /// Get the usernames reserved by the admins.
+    ///
+    /// # Returns
+    ///
+    /// - [`AdminReservedUsernamesResponse::Usernames`] with the reserved usernames.
+    /// - [`AdminReservedUsernamesResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_reserved_usernames() -> AdminReservedUsernamesResponse {
+        if !Config::is_admin(msg_caller()) {
+            return AdminReservedUsernamesResponse::Unauthorized;
         }
 
-        SetUserResponse::Ok
+        AdminReservedUsernamesResponse::Usernames(ModerationStorage::reserved_usernames())
     }
 
-    /// Share a file with a user.
+    /// Retry the user canister creation on behalf of a user.
+    ///
+    /// Like [`Self::retry_user_canister_creation`], the creation is only retried if it failed or
+    /// was never started.
     ///
     /// # Returns
     ///
-    /// - [`ShareFileResponse::Ok`] if the file was shared successfully.
-    /// - [`ShareFileResponse::NoSuchUser`] if the user doesn't exist.
-    /// - [`ShareFileResponse::Unauthorized`] if the caller is not a user canister.
-    pub fn share_file(
+    /// - [`AdminRetryUserCanisterCreationResponse::Ok`] if the user canister creation is retried.
+    /// - [`AdminRetryUserCanisterCreationResponse::Created`] if the user canister already exists.
+    /// - [`AdminRetryUserCanisterCreationResponse::CreationPending`] if the user canister creation is already in progress.
+    /// - [`AdminRetryUserCanisterCreationResponse::NoSuchUser`] if the user doesn't exist.
+    /// - [`AdminRetryUserCanisterCreationResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_retry_user_canister_creation(
         user: Principal,
-        file_id: FileId,
-        metadata: ShareFileMetadata,
-    ) -> ShareFileResponse {
-        debug!("Sharing file with user: {user}, file_id: {file_id}, metadata: {metadata:?}",);
-        Self::share_file_with_users(vec![user], file_id, metadata)
+    ) -> AdminRetryUserCanisterCreationResponse {
+        debug!("Admin retrying user canister creation for user: {user}");
+        if !Config::is_admin(msg_caller()) {
+            return AdminRetryUserCanisterCreationResponse::Unauthorized;
+        }
+        if UserStorage::get_user(&user).is_none() {
+            return AdminRetryUserCanisterCreationResponse::NoSuchUser;
+        }
+
+        match Self::user_canister_state(user) {
+            AdminUserCanisterState::Created(user_canister) => {
+                AdminRetryUserCanisterCreationResponse::Created(user_canister)
+            }
+            AdminUserCanisterState::CreationPending => {
+                AdminRetryUserCanisterCreationResponse::CreationPending
+            }
+            AdminUserCanisterState::CreationFailed { .. }
+            | AdminUserCanisterState::Uninitialized => {
+                if cfg!(target_family = "wasm") {
+                    CreateUserStateMachine::start(Config::get_orbit_station(), user);
+                }
+                AdminRetryUserCanisterCreationResponse::Ok
+            }
+        }
     }
 
-    /// Share a file with many users.
+    /// Get aggregate statistics of the orchestrator.
     ///
     /// # Returns
     ///
-    /// - [`ShareFileResponse::Ok`] if the file was shared successfully.
-    /// - [`ShareFileResponse::NoSuchUser`] if the user doesn't exist.
-    /// - [`ShareFileResponse::Unauthorized`] if the caller is not a user canister.
-    pub fn share_file_with_users(
-        users: Vec<Principal>,
-        file_id: FileId,
-        metadata: ShareFileMetadata,
-    ) -> ShareFileResponse {
-        debug!(
-            "Sharing file with users: {:?}, file_id: {}, metadata: {:?}",
-            users, file_id, metadata
-        );
-        let user_canister = msg_caller();
-        // check if the caller is a user canister
-        if !UserCanisterStorage::is_user_canister(user_canister) {
-            return ShareFileResponse::Unauthorized;
+    /// - [`AdminStatsResponse::Stats`] with the statistics.
+    /// - [`AdminStatsResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_stats() -> AdminStatsResponse {
+        if !Config::is_admin(msg_caller()) {
+            return AdminStatsResponse::Unauthorized;
         }
 
-        // check if all the users exist
-        if let Some(no_such_user) = users
-            .iter()
-            .find(|user| UserStorage::get_user(user).is_none())
-        {
-            return ShareFileResponse::NoSuchUser(*no_such_user);
+        let (pending_user_canisters, failed_user_canisters) =
+            UserCanisterStorage::count_create_states();
+        let (pending_account_deletions, failed_account_deletions) =
+            AccountDeletionStorage::count_delete_states();
+
+        AdminStatsResponse::Stats(OrchestratorStats {
+            users: UserStorage::count(),
+            user_canisters: UserCanisterStorage::count_user_canisters(),
+            pending_user_canisters,
+            failed_user_canisters,
+            pending_account_deletions,
+            failed_account_deletions,
+            suspended_users: ModerationStorage::suspended_users().len() as u64,
+            share_log_length: ShareLogStorage::len(),
+        })
+    }
+
+    /// Suspend a user.
+    ///
+    /// The user canister of the user, if created, is told first to reject uploads and new shares.
+    /// A suspended user can't register again, nor share files or list the files shared with
+    /// them, and is hidden from [`Self::get_users`].
+    ///
+    /// # Returns
+    ///
+    /// - [`AdminSuspendUserResponse::Ok`] if the user is suspended.
+    /// - [`AdminSuspendUserResponse::NoSuchUser`] if the user doesn't exist.
+    /// - [`AdminSuspendUserResponse::FailedToUpdateUserCanister`] if the user canister could not be updated.
+    /// - [`AdminSuspendUserResponse::Unauthorized`] if the caller is not an admin.
+    pub async fn admin_suspend_user(user: Principal) -> AdminSuspendUserResponse {
+        debug!("Suspending user: {user}");
+        if !Config::is_admin(msg_caller()) {
+            return AdminSuspendUserResponse::Unauthorized;
+        }
+        if UserStorage::get_user(&user).is_none() {
+            return AdminSuspendUserResponse::NoSuchUser;
         }
 
-        // share the file with all the users
-        for user in users {
-            SharedFilesStorage::share_file(user, user_canister, file_id, metadata.clone());
+        if let Some(user_canister) = UserCanisterStorage::get_user_canister(user) {
+            if cfg!(target_family = "wasm") {
+                match UserCanisterClient::from(user_canister)
+                    .set_suspended(true)
+                    .await
+                {
+                    Ok(SetSuspendedResponse::Ok | SetSuspendedResponse::Unchanged) => {}
+                    Ok(err) => {
+                        return AdminSuspendUserResponse::FailedToUpdateUserCanister(format!(
+                            "{err:?}"
+                        ));
+                    }
+                    Err(err) => {
+                        return AdminSuspendUserResponse::FailedToUpdateUserCanister(
+                            err.to_string(),
+                        );
+                    }
+                }
+            }
         }
 
-        ShareFileResponse::Ok
+        ModerationStorage::suspend(user, time());
+
+        AdminSuspendUserResponse::Ok
     }
 
-    /// Returns the list of shared files for the caller.
+    /// Lift the suspension of a user.
+    ///
+    /// The user canister of the user, if any, is told first to accept uploads and new shares again.
     ///
     /// # Returns
     ///
-    /// - [`SharedFilesResponse::AnonymousUser`] if the caller is anonymous.
-    /// - [`SharedFilesResponse::NoSuchUser`] if the user doesn't exist.
-    /// - [`SharedFilesResponse::SharedFiles`] if the user exists and has shared files.
-    pub fn shared_files() -> SharedFilesResponse {
-        debug!("Getting shared files for caller: {}", msg_caller());
-        let caller = msg_caller();
-        if caller == Principal::anonymous() {
-            return SharedFilesResponse::AnonymousUser;
+    /// - [`AdminUnsuspendUserResponse::Ok`] if the user is not suspended anymore.
+    /// - [`AdminUnsuspendUserResponse::NotSuspended`] if the user is not suspended.
+    /// - [`AdminUnsuspendUserResponse::FailedToUpdateUserCanister`] if the user canister could not be updated.
+    /// - [`AdminUnsuspendUserResponse::Unauthorized`] if the caller is not an admin.
+    pub async fn admin_unsuspend_user(user: Principal) -> AdminUnsuspendUserResponse {
+        debug!("Unsuspending user: {user}");
+        if !Config::is_admin(msg_caller()) {
+            return AdminUnsuspendUserResponse::Unauthorized;
+        }
+        if !ModerationStorage::is_suspended(user) {
+            return AdminUnsuspendUserResponse::NotSuspended;
         }
 
-        // check if the user exists
-        if UserStorage::get_user(&caller).is_none() {
-            return SharedFilesResponse::NoSuchUser;
+        if let Some(user_canister) = UserCanisterStorage::get_user_canister(user) {
+            if cfg!(target_family = "wasm") {
+                match UserCanisterClient::from(user_canister)
+                    .set_suspended(false)
+                    .await
+                {
+                    Ok(SetSuspendedResponse::Ok | SetSuspendedResponse::Unchanged) => {}
+                    Ok(err) => {
+                        return AdminUnsuspendUserResponse::FailedToUpdateUserCanister(format!(
+                            "{err:?}"
+                        ));
+                    }
+                    Err(err) => {
+                        return AdminUnsuspendUserResponse::FailedToUpdateUserCanister(
+                            err.to_string(),
+                        );
+                    }
+                }
+            }
         }
 
-        SharedFilesResponse::SharedFiles(
-            SharedFilesStorage::get_shared_files(caller)
-                .into_iter()
-                .map(|(user_canister, files)| {
-                    (
-                        user_canister,
-                        files
-                            .into_iter()
-                            .filter_map(|file_id| {
-                                SharedFilesStorage::get_file_metadata(user_canister, file_id).map(
-                                    |file_metadata| PublicFileMetadata {
-                                        file_id,
-                                        file_name: file_metadata.file_name,
-                                        shared_with: SharedFilesStorage::shared_with(
-                                            user_canister,
-                                            file_id,
-                                        )
-                                        .into_iter()
-                                        .filter_map(|principal| {
-                                            UserStorage::get_user(&principal)
-                                                .map(|user| PublicUser::new(user, principal))
-                                        })
-                                        .collect(),
-                                    },
-                                )
-                            })
-                            .collect::<Vec<_>>(),
-                    )
+        ModerationStorage::unsuspend(user);
+
+        AdminUnsuspendUserResponse::Ok
+    }
+
+    /// Get the users whose usernames have the same canonical key, i.e. are the same once lowercased
+    /// and with their fullwidth forms folded.
+    ///
+    /// Such users registered before usernames were compared by their canonical key; the username
+    /// stays taken until all of them change it.
+    ///
+    /// # Returns
+    ///
+    /// - [`AdminUsernameCollisionsResponse::Collisions`] with the colliding usernames.
+    /// - [`AdminUsernameCollisionsResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_username_collisions() -> AdminUsernameCollisionsResponse {
+        if !Config::is_admin(msg_caller()) {
+            return AdminUsernameCollisionsResponse::Unauthorized;
+        }
+
+        AdminUsernameCollisionsResponse::Collisions(UserStorage::username_collisions())
+    }
+
+    /// Get the users along with the state of their user canister.
+    ///
+    /// Up to 128 users can be retrieved at once. Users are ordered by principal; the next page is
+    /// retrieved by passing the `next` cursor of the response.
+    ///
+    /// # Returns
+    ///
+    /// - [`AdminUsersResponse::Users`] with the page of users.
+    /// - [`AdminUsersResponse::Unauthorized`] if the caller is not an admin.
+    pub fn admin_users(CursorPagination { cursor, limit }: CursorPagination) -> AdminUsersResponse {
+        if !Config::is_admin(msg_caller()) {
+            return AdminUsersResponse::Unauthorized;
+        }
+
+        let limit = limit.min(MAX_GET_USERS_LIMIT);
+        let (users, total, next) = UserStorage::search_users(None, cursor, limit, &BTreeSet::new());
+        let users = users
+            .into_iter()
+            .map(|(principal, user)| AdminUser {
+                user: UserStorage::to_public_user(principal, user),
+                user_canister: Self::user_canister_state(principal),
+                suspended_at: ModerationStorage::suspended_at(principal),
+            })
+            .collect();
+
+        AdminUsersResponse::Users(AdminUsers { users, next, total })
+    }
+
+    /// Approve the request of a principal to be linked to the account of the caller.
+    ///
+    /// The principal is first linked on the user canister of the caller, then on the
+    /// orchestrator. Once linked, the principal acts as the account of the caller, on the
+    /// orchestrator and on the user canister. The user canisters sharing files with the caller are
+    /// then notified, so that the principal can download the files shared with the caller.
+    ///
+    /// # Returns
+    ///
+    /// - [`ApprovePrincipalLinkResponse::Ok`] if the principal was linked.
+    /// - [`ApprovePrincipalLinkResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`ApprovePrincipalLinkResponse::NoSuchUser`] if the caller has no user.
+    /// - [`ApprovePrincipalLinkResponse::NoSuchLinkRequest`] if the principal didn't request the
+    ///   link, or the request expired.
+    /// - [`ApprovePrincipalLinkResponse::PrincipalHasAlreadyAUser`] if the principal has a user, or
+    ///   is linked to an account.
+    /// - [`ApprovePrincipalLinkResponse::UserCanisterNotReady`] if the user canister of the caller
+    ///   is not created yet.
+    /// - [`ApprovePrincipalLinkResponse::FailedToUpdateUserCanister`] if the user canister could
+    ///   not be updated.
+    pub async fn approve_principal_link(principal: Principal) -> ApprovePrincipalLinkResponse {
+        debug!("Approving link of principal: {principal}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return ApprovePrincipalLinkResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return ApprovePrincipalLinkResponse::NoSuchUser;
+        }
+        if !Self::is_link_requested(caller, principal) {
+            return ApprovePrincipalLinkResponse::NoSuchLinkRequest;
+        }
+        if Self::has_account(principal) {
+            return ApprovePrincipalLinkResponse::PrincipalHasAlreadyAUser;
+        }
+        let Some(user_canister) = UserCanisterStorage::get_user_canister(caller) else {
+            return ApprovePrincipalLinkResponse::UserCanisterNotReady;
+        };
+
+        // link the principal on the user canister first, so that it can use it once linked
+        if cfg!(target_family = "wasm") {
+            match UserCanisterClient::from(user_canister)
+                .link_principal(principal)
+                .await
+            {
+                Ok(LinkPrincipalResponse::Ok) => {}
+                Ok(err) => {
+                    return ApprovePrincipalLinkResponse::FailedToUpdateUserCanister(format!(
+                        "{err:?}"
+                    ));
+                }
+                Err(err) => {
+                    return ApprovePrincipalLinkResponse::FailedToUpdateUserCanister(
+                        err.to_string(),
+                    );
+                }
+            }
+        }
+
+        // the principal may have registered a user in the meantime
+        if Self::has_account(principal) {
+            return ApprovePrincipalLinkResponse::PrincipalHasAlreadyAUser;
+        }
+
+        LinkedPrincipalsStorage::link(caller, principal);
+
+        // let the user canisters sharing files with the account know the principal, so that it
+        // can download the files
+        if cfg!(target_family = "wasm") {
+            let user_canisters = SharedFilesStorage::get_shared_files(caller).into_keys();
+            ic_cdk::futures::spawn(Self::link_recipient_principals(
+                user_canisters.collect(),
+                caller,
+                vec![principal],
+            ));
+        }
+
+        ApprovePrincipalLinkResponse::Ok
+    }
+
+    /// Block a user or a user canister, so that it can't share files with the caller anymore.
+    ///
+    /// Blocking a user blocks their user canister too. The files already shared with the caller
+    /// by the blocked user or user canister are hidden from [`Self::shared_files`].
+    ///
+    /// # Returns
+    ///
+    /// - [`BlockResponse::Ok`] if the principal was blocked.
+    /// - [`BlockResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`BlockResponse::NoSuchUser`] if the caller has no user.
+    /// - [`BlockResponse::UnknownPrincipal`] if the principal is neither a user nor a user canister.
+    /// - [`BlockResponse::CannotBlockSelf`] if the caller tries to block themselves.
+    pub fn block(principal: Principal) -> BlockResponse {
+        debug!("Blocking principal: {principal}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return BlockResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return BlockResponse::NoSuchUser;
+        }
+        if principal == caller || UserCanisterStorage::get_owner(principal) == Some(caller) {
+            return BlockResponse::CannotBlockSelf;
+        }
+        if UserStorage::get_user(&principal).is_none()
+            && UserCanisterStorage::get_owner(principal).is_none()
+        {
+            return BlockResponse::UnknownPrincipal;
+        }
+
+        BlockListStorage::block(caller, principal);
+
+        BlockResponse::Ok
+    }
+
+    /// Get the contacts of the caller, the most recently shared with first.
+    ///
+    /// Contacts no file was shared with, or whose share time is unknown, follow, ordered by
+    /// principal. Deleted users are not returned.
+    ///
+    /// # Returns
+    ///
+    /// - [`ContactsResponse::Contacts`] with the requested page of contacts.
+    /// - [`ContactsResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`ContactsResponse::NoSuchUser`] if the caller has no user.
+    pub fn contacts(Pagination { offset, limit }: Pagination) -> ContactsResponse {
+        let limit = limit.min(MAX_CONTACTS_LIMIT);
+        debug!(
+            "Getting contacts of caller: {}, offset: {offset}, limit: {limit}",
+            msg_caller()
+        );
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return ContactsResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return ContactsResponse::NoSuchUser;
+        }
+
+        let mut contacts = ContactsStorage::get_contacts(caller)
+            .into_iter()
+            .filter_map(|(principal, entry)| {
+                UserStorage::get_public_user(&principal).map(|user| Contact {
+                    user,
+                    nickname: entry.nickname,
+                    last_shared_at: entry.last_shared_at,
                 })
-                .collect(),
-        )
+            })
+            .collect::<Vec<_>>();
+        // the sort is stable: contacts shared with at the same time stay ordered by principal
+        contacts.sort_by_key(|contact| std::cmp::Reverse(contact.last_shared_at));
+
+        let total = contacts.len() as u64;
+        let contacts = contacts
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+        let next = if offset.saturating_add(limit) < total {
+            Some(offset + limit)
+        } else {
+            None
+        };
+
+        ContactsResponse::Contacts(Contacts {
+            contacts,
+            next,
+            total,
+        })
+    }
+
+    /// Create an invite to share a file with someone who has not signed up yet.
+    ///
+    /// The invitee signs up with [`Self::set_user`] passing the returned invite code. The owner
+    /// then finds the invite redeemed in [`Self::invites`] and shares the file with the invitee,
+    /// wrapping the document key for their public key; the invite is removed once the file is
+    /// shared. Invites which are not redeemed expire after [`INVITE_EXPIRY`].
+    ///
+    /// # Returns
+    ///
+    /// - [`CreateInviteResponse::Ok`] with the invite code.
+    /// - [`CreateInviteResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`CreateInviteResponse::NoSuchUser`] if the caller has no user.
+    /// - [`CreateInviteResponse::UserCanisterNotReady`] if the user canister of the caller is not created yet.
+    /// - [`CreateInviteResponse::Suspended`] if the caller is suspended.
+    /// - [`CreateInviteResponse::TooManyInvites`] if the caller has already [`MAX_INVITES_PER_USER`] invites.
+    pub async fn create_invite(
+        CreateInviteRequest { file_id, metadata }: CreateInviteRequest,
+    ) -> CreateInviteResponse {
+        debug!(
+            "Creating invite for caller: {}, file_id: {file_id}, metadata: {metadata:?}",
+            msg_caller()
+        );
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return CreateInviteResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return CreateInviteResponse::NoSuchUser;
+        }
+        if ModerationStorage::is_suspended(caller) {
+            return CreateInviteResponse::Suspended;
+        }
+        if UserCanisterStorage::get_user_canister(caller).is_none() {
+            return CreateInviteResponse::UserCanisterNotReady;
+        }
+
+        if Self::count_invites(caller) >= MAX_INVITES_PER_USER {
+            return CreateInviteResponse::TooManyInvites;
+        }
+
+        let code = random_bytes().await;
+        // other invites may have been created while waiting for the random bytes
+        if Self::count_invites(caller) >= MAX_INVITES_PER_USER {
+            return CreateInviteResponse::TooManyInvites;
+        }
+        InvitesStorage::insert_invite(
+            code,
+            InviteEntry {
+                owner: caller,
+                file_id,
+                file_name: metadata.file_name,
+                created_at: time(),
+                invitee: None,
+            },
+        );
+
+        CreateInviteResponse::Ok(format_invite_code(&code))
+    }
+
+    /// Decline a file shared with the caller by the given user canister.
+    ///
+    /// The share is marked as declined and the owner's user canister is notified, so that it
+    /// drops the key shared with the caller.
+    /// Declining an already declined share notifies the owner again.
+    ///
+    /// # Returns
+    ///
+    /// - [`DeclineShareResponse::Ok`] if the share was declined.
+    /// - [`DeclineShareResponse::AnonymousUser`] if the caller is anonymous.
+    /// - [`DeclineShareResponse::NoSuchShare`] if the file is not shared with the caller.
+    /// - [`DeclineShareResponse::FailedToNotifyOwner`] if the user canister could not be notified.
+    pub async fn decline_share(user_canister: Principal, file_id: FileId) -> DeclineShareResponse {
+        debug!("Declining share for user_canister: {user_canister}, file_id: {file_id}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return DeclineShareResponse::AnonymousUser;
+        }
+
+        if !SharedFilesStorage::decline_share(caller, user_canister, file_id) {
+            return DeclineShareResponse::NoSuchShare;
+        }
+        ShareLog::record(ShareOperation::Decline, user_canister, file_id, caller);
+
+        // notify the owner
+        if cfg!(target_family = "wasm") {
+            match UserCanisterClient::from(user_canister)
+                .decline_share(caller, file_id)
+                .await
+            {
+                Err(err) => {
+                    return DeclineShareResponse::FailedToNotifyOwner(err.to_string());
+                }
+                // the file may have been deleted in the meantime
+                Ok(FileSharingResponse::Ok | FileSharingResponse::FileNotFound) => {}
+                Ok(err) => {
+                    return DeclineShareResponse::FailedToNotifyOwner(format!("{err:?}"));
+                }
+            }
+        }
+
+        DeclineShareResponse::Ok
+    }
+
+    /// Delete the account of the caller.
+    ///
+    /// Every share of a file shared with the caller, or by the caller, is revoked; then the user
+    /// canister of the caller is deleted, or archived if requested, and the user is removed. The
+    /// deletion goes on in the background; a failed deletion can be retried by calling this
+    /// method again.
+    ///
+    /// # Returns
+    ///
+    /// - [`DeleteAccountResponse::Ok`] if the deletion has started.
+    /// - [`DeleteAccountResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`DeleteAccountResponse::NoSuchUser`] if the caller has no user.
+    /// - [`DeleteAccountResponse::DeletionPending`] if the account is already being deleted.
+    /// - [`DeleteAccountResponse::UserCanisterCreationPending`] if the user canister is being created.
+    pub fn delete_account(
+        DeleteAccountRequest { archive }: DeleteAccountRequest,
+    ) -> DeleteAccountResponse {
+        debug!(
+            "Deleting account for caller: {}, archive: {archive}",
+            msg_caller()
+        );
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return DeleteAccountResponse::AnonymousCaller;
+        }
+
+        if UserStorage::get_user(&caller).is_none() {
+            return DeleteAccountResponse::NoSuchUser;
+        }
+
+        if AccountDeletionStorage::is_deletion_pending(caller) {
+            return DeleteAccountResponse::DeletionPending;
+        }
+
+        // the creation state machine must not run along with the deletion
+        if let Some(state) = UserCanisterStorage::get_create_state(caller) {
+            if !matches!(state, UserCanisterCreateState::Failed { .. }) {
+                return DeleteAccountResponse::UserCanisterCreationPending;
+            }
+        }
+
+        // start state machine to delete the account
+        AccountDeletionStorage::init_delete_state(caller, archive);
+        if cfg!(target_family = "wasm") {
+            DeleteAccountStateMachine::start(Config::get_orbit_station(), caller);
+        }
+
+        DeleteAccountResponse::Ok
+    }
+
+    /// Get up to 128 blocks of the share log, starting from the block at index `start`.
+    ///
+    /// Blocks are returned with their index and can be verified against the certified tip
+    /// returned by [`Canister::get_tip_certificate`].
+    pub fn get_blocks(request: GetBlocksRequest) -> GetBlocksResponse {
+        let length = request.length.min(MAX_GET_BLOCKS_LIMIT);
+
+        GetBlocksResponse {
+            log_length: ShareLogStorage::len(),
+            blocks: ShareLogStorage::get_blocks(request.start, length),
+        }
+    }
+
+    /// Get the certificate of the tip of the share log.
+    ///
+    /// Returns `None` if the log is empty or if not called as a query.
+    pub fn get_tip_certificate() -> Option<DataCertificate> {
+        ShareLog::tip_certificate()
+    }
+
+    /// Get the users from the storage as [`GetUsersResponse`], paginated by offset.
+    ///
+    /// If the caller is anonymous, it returns [`GetUsersResponse::PermissionError`]. Without a
+    /// query, the admins get every user, while the other users get their contacts. Suspended
+    /// users are left out.
+    ///
+    /// Up to 128 users can be retrieved at once. Kept for the clients paginating by offset; see
+    /// [`Self::search_users`].
+    ///
+    /// # Arguments
+    ///
+    /// - `Pagination`: The pagination parameters, including `offset` and `limit`.
+    /// - `query`: An optional query string to filter users by username. It has a minimum length of [`GET_USERS_QUERY_MIN_LENGTH`].
+    pub fn get_users(
+        Pagination { offset, limit }: Pagination,
+        query: Option<&str>,
+    ) -> GetUsersResponse {
+        let limit = limit.min(MAX_GET_USERS_LIMIT);
+        debug!("Getting users with offset: {offset}, limit: {limit}, query: {query:?}",);
+
+        let caller = msg_caller();
+        if caller == Principal::anonymous() {
+            return GetUsersResponse::PermissionError;
+        }
+
+        // Validate the query length.
+        if query.is_some_and(|q| q.len() < GET_USERS_QUERY_MIN_LENGTH) {
+            debug!("Query is too short: {query:?}",);
+            return GetUsersResponse::InvalidQuery;
+        }
+
+        let suspended = ModerationStorage::suspended_users();
+        let (users, total) = if query.is_none() && !Config::is_admin(caller) {
+            let contacts = ContactsStorage::get_contacts(Self::account_caller())
+                .into_iter()
+                .filter(|(contact, _)| !suspended.contains(contact))
+                .filter_map(|(contact, _)| {
+                    UserStorage::get_user(&contact).map(|user| (contact, user))
+                })
+                .collect::<Vec<_>>();
+            let total = contacts.len() as u64;
+            (contacts, total)
+        } else {
+            let (users, total, _) =
+                UserStorage::search_users(query, None, offset.saturating_add(limit), &suspended);
+            (users, total)
+        };
+        let users = users
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|(principal, user)| UserStorage::to_public_user(principal, user))
+            .collect::<Vec<_>>();
+        let next = Some(offset.saturating_add(limit)).filter(|next| *next < total);
+
+        GetUsersResponse::Users(GetUsersResponseUsers { users, total, next })
+    }
+
+    /// Search the users as [`SearchUsersResponse`].
+    ///
+    /// If the caller is anonymous, it returns [`SearchUsersResponse::PermissionError`]. Listing
+    /// the users without a query is reserved to the admins. Suspended users are left out.
+    ///
+    /// Up to 128 users can be retrieved at once. Users are ordered by principal; the next page is
+    /// retrieved by passing the `next` cursor of the response.
+    ///
+    /// # Arguments
+    ///
+    /// - `CursorPagination`: The pagination parameters, including `cursor` and `limit`.
+    /// - `query`: An optional query string to filter users by username. It has a minimum length of [`GET_USERS_QUERY_MIN_LENGTH`].
+    pub fn search_users(
+        CursorPagination { cursor, limit }: CursorPagination,
+        query: Option<&str>,
+    ) -> SearchUsersResponse {
+        let limit = limit.min(MAX_GET_USERS_LIMIT);
+        debug!("Searching users with cursor: {cursor:?}, limit: {limit}, query: {query:?}",);
+
+        let caller = msg_caller();
+        if caller == Principal::anonymous() || (query.is_none() && !Config::is_admin(caller)) {
+            return SearchUsersResponse::PermissionError;
+        }
+
+        // Validate the query length.
+        if query.is_some_and(|q| q.len() < GET_USERS_QUERY_MIN_LENGTH) {
+            debug!("Query is too short: {query:?}",);
+            return SearchUsersResponse::InvalidQuery;
+        }
+
+        let (users, total, next) =
+            UserStorage::search_users(query, cursor, limit, &ModerationStorage::suspended_users());
+        let users = users
+            .into_iter()
+            .map(|(principal, user)| UserStorage::to_public_user(principal, user))
+            .collect::<Vec<_>>();
+
+        SearchUsersResponse::Users(SearchUsersResponseUsers { users, total, next })
+    }
+
+    /// Get the users and user canisters blocked by the caller.
+    pub fn get_blocked() -> GetBlockedResponse {
+        debug!("Getting principals blocked by: {}", msg_caller());
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return GetBlockedResponse::AnonymousCaller;
+        }
+
+        GetBlockedResponse::Blocked(BlockListStorage::get_blocked(caller))
+    }
+
+    /// Get the public keys of the devices of a user, ordered by device ID.
+    ///
+    /// They are used to encrypt the document key of a file for each device of the user.
+    pub fn get_device_keys(user: Principal) -> Vec<DeviceKey> {
+        DeviceKeysStorage::get_device_keys(user)
+    }
+
+    /// Get a user from the storage as [`PublicUser`].
+    pub fn get_user(principal: Principal) -> Option<PublicUser> {
+        debug!("Getting user with principal: {principal}",);
+        UserStorage::get_public_user(&principal)
+    }
+
+    /// Get the user canister of a user, for the calling user canister.
+    ///
+    /// # Returns
+    ///
+    /// - [`GetUserCanisterResponse::Ok`] with the user canister of the user.
+    /// - [`GetUserCanisterResponse::NoSuchUser`] if the user has no user canister.
+    /// - [`GetUserCanisterResponse::Unauthorized`] if the caller is not a user canister.
+    pub fn get_user_canister(user: Principal) -> GetUserCanisterResponse {
+        debug!("Getting user canister of {user} for {}", msg_caller());
+        if !UserCanisterStorage::is_user_canister(msg_caller()) {
+            return GetUserCanisterResponse::Unauthorized;
+        }
+
+        match UserCanisterStorage::get_user_canister(user) {
+            Some(user_canister) => GetUserCanisterResponse::Ok(user_canister),
+            None => GetUserCanisterResponse::NoSuchUser,
+        }
+    }
+
+    /// Returns a page of the shares held for the calling user canister, ordered by file ID, so
+    /// that it can reconcile them with its own shares.
+    ///
+    /// Up to [`MAX_USER_CANISTER_SHARES_LIMIT`] files can be retrieved at once.
+    /// Declined shares are not returned.
+    ///
+    /// # Returns
+    ///
+    /// - [`UserCanisterSharesResponse::Shares`] with the users each file is shared with.
+    /// - [`UserCanisterSharesResponse::Unauthorized`] if the caller is not a user canister.
+    pub fn get_user_canister_shares(
+        FileCursorPagination { cursor, limit }: FileCursorPagination,
+    ) -> UserCanisterSharesResponse {
+        debug!("Getting shares for user canister: {}", msg_caller());
+        let user_canister = msg_caller();
+        if !UserCanisterStorage::is_user_canister(user_canister) {
+            return UserCanisterSharesResponse::Unauthorized;
+        }
+
+        let limit = limit.min(MAX_USER_CANISTER_SHARES_LIMIT);
+        let (files, next) =
+            SharedFilesStorage::user_canister_shares_after(user_canister, cursor, limit);
+
+        UserCanisterSharesResponse::Shares(UserCanisterShares {
+            files: files
+                .into_iter()
+                .map(|(file_id, users)| {
+                    let mut users = users.into_iter().collect::<Vec<_>>();
+                    users.sort();
+                    FileShares { file_id, users }
+                })
+                .collect(),
+            next,
+        })
+    }
+
+    /// Hide a file shared with the caller by the given user canister from [`Self::shared_files`].
+    ///
+    /// # Returns
+    ///
+    /// - [`ShareStateResponse::Ok`] if the share was hidden.
+    /// - [`ShareStateResponse::AnonymousUser`] if the caller is anonymous.
+    /// - [`ShareStateResponse::NoSuchShare`] if the file is not shared with the caller or the share was declined.
+    pub fn hide_share(user_canister: Principal, file_id: FileId) -> ShareStateResponse {
+        debug!("Hiding share for user_canister: {user_canister}, file_id: {file_id}");
+        Self::update_share_state(user_canister, file_id, ShareState::Hidden)
+    }
+
+    /// Serve HTTP requests through the HTTP gateway.
+    ///
+    /// Only the metrics of the orchestrator are served, under [`METRICS_PATH`], in the
+    /// Prometheus text format.
+    ///
+    /// # Returns
+    ///
+    /// - `200 OK` with the metrics for a `GET` request to [`METRICS_PATH`].
+    /// - `405 Method Not Allowed` for any other method on [`METRICS_PATH`].
+    /// - `404 Not Found` for any other path.
+    pub fn http_request(request: HttpRequest) -> HttpResponse {
+        if request.path() != METRICS_PATH {
+            return HttpResponse::not_found();
+        }
+        if request.method != "GET" {
+            return HttpResponse::method_not_allowed();
+        }
+
+        HttpResponse::metrics(Self::encode_metrics())
+    }
+
+    /// Get the invites created by the caller, the oldest first.
+    ///
+    /// Redeemed invites tell the caller to share the file with the invitee. Expired invites are
+    /// not returned.
+    ///
+    /// # Returns
+    ///
+    /// - [`InvitesResponse::Invites`] with the invites of the caller.
+    /// - [`InvitesResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`InvitesResponse::NoSuchUser`] if the caller has no user.
+    pub fn invites() -> InvitesResponse {
+        debug!("Getting invites for caller: {}", msg_caller());
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return InvitesResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return InvitesResponse::NoSuchUser;
+        }
+
+        let invites = InvitesStorage::get_invites(caller)
+            .into_iter()
+            .filter_map(|(code, invite)| {
+                let state = Self::invite_state(&invite)?;
+                Some(Invite {
+                    code: format_invite_code(&code),
+                    file_id: invite.file_id,
+                    metadata: ShareFileMetadata {
+                        file_name: invite.file_name,
+                    },
+                    created_at: invite.created_at,
+                    state,
+                })
+            })
+            .collect();
+
+        InvitesResponse::Invites(invites)
+    }
+
+    /// Get the principals of the account of the caller, and the principals waiting for their link
+    /// to it to be approved.
+    ///
+    /// # Returns
+    ///
+    /// - [`LinkedPrincipalsResponse::LinkedPrincipals`] with the principals of the account.
+    /// - [`LinkedPrincipalsResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`LinkedPrincipalsResponse::NoSuchUser`] if the caller has no user.
+    pub fn linked_principals() -> LinkedPrincipalsResponse {
+        debug!("Getting linked principals for caller: {}", msg_caller());
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return LinkedPrincipalsResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return LinkedPrincipalsResponse::NoSuchUser;
+        }
+
+        LinkedPrincipalsResponse::LinkedPrincipals(LinkedPrincipals {
+            account: caller,
+            linked: LinkedPrincipalsStorage::get_linked_principals(caller),
+            requests: LinkedPrincipalsStorage::get_link_requests(caller)
+                .into_iter()
+                .filter(|(principal, _)| Self::is_link_requested(caller, *principal))
+                .map(|(principal, _)| principal)
+                .collect(),
+        })
+    }
+
+    /// Retry the user canister creation for the current caller.
+    ///
+    /// # Returns
+    ///
+    /// - [`RetryUserCanisterCreationResponse::Ok`] if the user canister creation is retried.
+    /// - [`RetryUserCanisterCreationResponse::Created`] if the user canister already exists.
+    /// - [`RetryUserCanisterCreationResponse::AnonymousCaller`]: The caller is anonymous.
+    /// - [`RetryUserCanisterCreationResponse::CreationPending`]: The user canister creation is already in progress.
+    /// - [`RetryUserCanisterCreationResponse::UserNotFound`]: The user doesn't exist. In that case, the caller should call `set_user` first.
+    pub fn retry_user_canister_creation() -> RetryUserCanisterCreationResponse {
+        debug!(
+            "Retrying user canister creation for caller: {}",
+            msg_caller()
+        );
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return RetryUserCanisterCreationResponse::AnonymousCaller;
+        }
+
+        // check if the user exists
+        if UserStorage::get_user(&caller).is_none() {
+            return RetryUserCanisterCreationResponse::UserNotFound;
+        }
+
+        // check if the user canister already exists
+        if let Some(canister) = UserCanisterStorage::get_user_canister(caller) {
+            return RetryUserCanisterCreationResponse::Created(canister);
+        }
+
+        // check the current state of the user canister creation
+        match UserCanisterStorage::get_create_state(caller) {
+            Some(UserCanisterCreateState::Ok { user_canister }) => {
+                RetryUserCanisterCreationResponse::Created(user_canister)
+            }
+            Some(UserCanisterCreateState::Failed { .. }) | None => {
+                if cfg!(target_family = "wasm") {
+                    CreateUserStateMachine::start(Config::get_orbit_station(), caller);
+                }
+                RetryUserCanisterCreationResponse::Ok
+            }
+            Some(_) => RetryUserCanisterCreationResponse::CreationPending,
+        }
+    }
+
+    /// Remove a user from the contacts of the caller.
+    ///
+    /// The user is added back if the caller shares a file with them again.
+    ///
+    /// # Returns
+    ///
+    /// - [`RemoveContactResponse::Ok`] if the contact was removed.
+    /// - [`RemoveContactResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`RemoveContactResponse::NoSuchContact`] if the user is not a contact of the caller.
+    pub fn remove_contact(user: Principal) -> RemoveContactResponse {
+        debug!("Removing contact: {user}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return RemoveContactResponse::AnonymousCaller;
+        }
+
+        if ContactsStorage::remove_contact(caller, user) {
+            RemoveContactResponse::Ok
+        } else {
+            RemoveContactResponse::NoSuchContact
+        }
+    }
+
+    /// Remove the public key of a device of the caller, e.g. when the device is lost.
+    ///
+    /// The key is first removed from the user canister of the caller, and the keys stored for the
+    /// device by the user canisters sharing files with the caller are dropped, so that the device
+    /// can't download files anymore; it is then removed from the orchestrator. The ID of the
+    /// device is never reused.
+    ///
+    /// # Returns
+    ///
+    /// - [`RemoveDeviceKeyResponse::Ok`] if the device key was removed.
+    /// - [`RemoveDeviceKeyResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`RemoveDeviceKeyResponse::NoSuchUser`] if the caller has no user.
+    /// - [`RemoveDeviceKeyResponse::NoSuchDevice`] if the caller has no such device.
+    /// - [`RemoveDeviceKeyResponse::FailedToUpdateUserCanister`] if a user canister could not be updated.
+    pub async fn remove_device_key(device_id: DeviceId) -> RemoveDeviceKeyResponse {
+        debug!("Removing device key: {device_id}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return RemoveDeviceKeyResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return RemoveDeviceKeyResponse::NoSuchUser;
+        }
+        if !DeviceKeysStorage::get_device_keys(caller)
+            .iter()
+            .any(|device_key| device_key.device_id == device_id)
+        {
+            return RemoveDeviceKeyResponse::NoSuchDevice;
+        }
+
+        if let Some(user_canister) = UserCanisterStorage::get_user_canister(caller) {
+            if cfg!(target_family = "wasm") {
+                match UserCanisterClient::from(user_canister)
+                    .remove_device_key(device_id)
+                    .await
+                {
+                    // the device may already have been removed from the user canister
+                    Ok(SyncDeviceKeyResponse::Ok | SyncDeviceKeyResponse::NoSuchDevice) => {}
+                    Ok(err) => {
+                        return RemoveDeviceKeyResponse::FailedToUpdateUserCanister(format!(
+                            "{err:?}"
+                        ));
+                    }
+                    Err(err) => {
+                        return RemoveDeviceKeyResponse::FailedToUpdateUserCanister(
+                            err.to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        if cfg!(target_family = "wasm") {
+            for user_canister in SharedFilesStorage::get_shared_files(caller).into_keys() {
+                match UserCanisterClient::from(user_canister)
+                    .remove_recipient_device_key(caller, device_id)
+                    .await
+                {
+                    Ok(SyncDeviceKeyResponse::Ok) => {}
+                    Ok(err) => {
+                        return RemoveDeviceKeyResponse::FailedToUpdateUserCanister(format!(
+                            "{err:?}"
+                        ));
+                    }
+                    Err(err) => {
+                        return RemoveDeviceKeyResponse::FailedToUpdateUserCanister(
+                            err.to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        DeviceKeysStorage::remove_device_key(caller, device_id);
+
+        RemoveDeviceKeyResponse::Ok
+    }
+
+    /// Get the user who has the given username, or who had it before.
+    ///
+    /// Searches for a previous username are redirected to the user who had it, as long as it is
+    /// reserved to them.
+    pub fn resolve_username(username: String) -> Option<PublicUser> {
+        debug!("Resolving username: {username}");
+        if msg_caller() == Principal::anonymous() {
+            return None;
+        }
+
+        UserStorage::get_user_by_username(&username)
+            .map(|(principal, user)| UserStorage::to_public_user(principal, user))
+            .or_else(|| {
+                UserStorage::get_previous_username_owner(&username)
+                    .and_then(|principal| UserStorage::get_public_user(&principal))
+            })
+    }
+
+    /// Request to link the caller to an account, such as the account of the user on another
+    /// device.
+    ///
+    /// The link must then be approved from the account with [`Self::approve_principal_link`],
+    /// within [`LINK_REQUEST_EXPIRY`]. Requesting again renews the request. The expired requests
+    /// to the account are removed.
+    ///
+    /// # Returns
+    ///
+    /// - [`RequestPrincipalLinkResponse::Ok`] if the link was requested.
+    /// - [`RequestPrincipalLinkResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`RequestPrincipalLinkResponse::NoSuchAccount`] if the account doesn't exist or is being
+    ///   deleted.
+    /// - [`RequestPrincipalLinkResponse::CallerHasAlreadyAUser`] if the caller has a user, or is
+    ///   linked to an account.
+    /// - [`RequestPrincipalLinkResponse::TooManyLinkRequests`] if the account has already
+    ///   [`MAX_LINK_REQUESTS_PER_ACCOUNT`] pending requests.
+    pub fn request_principal_link(account: Principal) -> RequestPrincipalLinkResponse {
+        debug!("Requesting link to account: {account}");
+        let caller = msg_caller();
+        if caller == Principal::anonymous() {
+            return RequestPrincipalLinkResponse::AnonymousCaller;
+        }
+        if Self::has_account(caller) {
+            return RequestPrincipalLinkResponse::CallerHasAlreadyAUser;
+        }
+        // the account may be given by any of its principals
+        let account = LinkedPrincipalsStorage::get_account(account).unwrap_or(account);
+        if UserStorage::get_user(&account).is_none()
+            || AccountDeletionStorage::is_deletion_pending(account)
+        {
+            return RequestPrincipalLinkResponse::NoSuchAccount;
+        }
+
+        let now = time();
+        LinkedPrincipalsStorage::remove_link_requests_until(
+            account,
+            now.saturating_sub(LINK_REQUEST_EXPIRY),
+        );
+        let requests = LinkedPrincipalsStorage::get_link_requests(account);
+        if requests.len() >= MAX_LINK_REQUESTS_PER_ACCOUNT
+            && !requests.iter().any(|(principal, _)| *principal == caller)
+        {
+            return RequestPrincipalLinkResponse::TooManyLinkRequests;
+        }
+
+        LinkedPrincipalsStorage::request_link(account, caller, now);
+
+        RequestPrincipalLinkResponse::Ok
+    }
+
+    /// Revoke an invite created by the caller, whether it was redeemed or not.
+    ///
+    /// # Returns
+    ///
+    /// - [`RevokeInviteResponse::Ok`] if the invite was revoked.
+    /// - [`RevokeInviteResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`RevokeInviteResponse::NoSuchInvite`] if the caller has no invite with this code.
+    pub fn revoke_invite(code: String) -> RevokeInviteResponse {
+        debug!("Revoking invite");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return RevokeInviteResponse::AnonymousCaller;
+        }
+        let Some(code) = parse_invite_code(&code) else {
+            return RevokeInviteResponse::NoSuchInvite;
+        };
+        if InvitesStorage::get_invite(code).is_none_or(|invite| invite.owner != caller) {
+            return RevokeInviteResponse::NoSuchInvite;
+        }
+
+        InvitesStorage::remove_invite(code);
+
+        RevokeInviteResponse::Ok
+    }
+
+    /// Revoke the share of a file for a user.
+    ///
+    /// # Returns
+    ///
+    /// - [`RevokeShareFileResponse::Ok`] if the file was unshared successfully.
+    /// - [`RevokeShareFileResponse::NoSuchUser`] if the user doesn't exist.
+    /// - [`RevokeShareFileResponse::Unauthorized`] if the caller is not a user canister.
+    pub fn revoke_share_file(user: Principal, file_id: FileId) -> RevokeShareFileResponse {
+        debug!("Revoking share for user: {user}, file_id: {file_id}",);
+        let user_canister = msg_caller();
+        // check if the caller is a user canister
+        if !UserCanisterStorage::is_user_canister(user_canister) {
+            return RevokeShareFileResponse::Unauthorized;
+        }
+
+        // Revoke share for the user
+        Self::revoke_share(user, user_canister, file_id);
+
+        RevokeShareFileResponse::Ok
+    }
+
+    /// Revoke the share of a file for a list of users.
+    ///
+    /// # Returns
+    ///
+    /// - [`RevokeShareFileResponse::Ok`] if the file was unshared successfully.
+    /// - [`RevokeShareFileResponse::NoSuchUser`] if the user doesn't exist.
+    /// - [`RevokeShareFileResponse::Unauthorized`] if the caller is not a user canister.
+    pub fn revoke_share_file_for_users(
+        users: Vec<Principal>,
+        file_id: FileId,
+    ) -> RevokeShareFileResponse {
+        debug!(
+            "Revoking share for users: {:?}, file_id: {}",
+            users, file_id
+        );
+        let user_canister = msg_caller();
+        // check if the caller is a user canister
+        if !UserCanisterStorage::is_user_canister(user_canister) {
+            return RevokeShareFileResponse::Unauthorized;
+        }
+
+        // Revoke share for the user
+        for user in users {
+            Self::revoke_share(user, user_canister, file_id);
+        }
+
+        RevokeShareFileResponse::Ok
+    }
+
+    /// Revoke the shares of a list of files for a user.
+    ///
+    /// # Returns
+    ///
+    /// - [`RevokeShareFileResponse::Ok`] if the files were unshared successfully.
+    /// - [`RevokeShareFileResponse::Unauthorized`] if the caller is not a user canister.
+    pub fn revoke_user_shares(user: Principal, file_ids: Vec<FileId>) -> RevokeShareFileResponse {
+        debug!("Revoking shares for user: {user}, file_ids: {file_ids:?}");
+        let user_canister = msg_caller();
+        // check if the caller is a user canister
+        if !UserCanisterStorage::is_user_canister(user_canister) {
+            return RevokeShareFileResponse::Unauthorized;
+        }
+
+        for file_id in file_ids {
+            Self::revoke_share(user, user_canister, file_id);
+        }
+
+        RevokeShareFileResponse::Ok
+    }
+
+    /// Set a new user in the storage.
+    ///
+    /// The username is normalized with [`normalize_username`], and must be unique regardless of
+    /// its case.
+    ///
+    /// # Returns
+    ///
+    /// - [`SetUserResponse::Ok`] if the user was set successfully.
+    /// - [`SetUserResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`SetUserResponse::UsernameTooLong`] if the username is too long.
+    /// - [`SetUserResponse::InvalidUsername`] if the username has characters which are not allowed.
+    /// - [`SetUserResponse::UsernameExists`] if the username already exists.
+    /// - [`SetUserResponse::CallerHasAlreadyAUser`] if the caller already has a user.
+    pub fn set_user(
+        username: String,
+        public_key: PublicKey,
+        invite: Option<String>,
+    ) -> SetUserResponse {
+        // the invite code is a secret, so it is not logged
+        debug!(
+            "Setting user with username: {username}, public_key: {public_key:?}, with invite: {}",
+            invite.is_some()
+        );
+        // Check if the caller is anonymous.
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return SetUserResponse::AnonymousCaller;
+        }
+        if ModerationStorage::is_suspended(caller) {
+            return SetUserResponse::Suspended;
+        }
+
+        // Check if the username is too long.
+        if username.len() > MAX_USERNAME_SIZE {
+            return SetUserResponse::UsernameTooLong;
+        }
+        let Some(username) = normalize_username(&username) else {
+            return SetUserResponse::InvalidUsername;
+        };
+
+        // check if username already exists, or is reserved to a user who had it before
+        if !UserStorage::is_username_available(&username, caller) {
+            return SetUserResponse::UsernameExists;
+        }
+        if ModerationStorage::is_username_reserved(&username) {
+            return SetUserResponse::UsernameReserved;
+        }
+
+        // check if the caller already has a user
+        if UserStorage::get_user(&caller).is_some() {
+            return SetUserResponse::CallerHasAlreadyAUser;
+        }
+
+        // check the invite the caller signs up with, if any
+        let invite = match invite.as_deref().map(parse_invite_code) {
+            None => None,
+            Some(Some(code)) if Self::is_invite_pending(code) => Some(code),
+            Some(_) => return SetUserResponse::InvalidInvite,
+        };
+
+        // Add the user to the storage and return Ok.
+        UserStorage::add_user(
+            caller,
+            User {
+                username,
+                public_key,
+            },
+        );
+        // the owner of the invite can now share the file with the caller
+        if let Some(code) = invite {
+            InvitesStorage::redeem_invite(code, caller);
+        }
+
+        // start state machine to create user canister
+        if cfg!(target_family = "wasm") {
+            CreateUserStateMachine::start(Config::get_orbit_station(), caller);
+        }
+
+        SetUserResponse::Ok
+    }
+
+    /// Share a file with a user.
+    ///
+    /// # Returns
+    ///
+    /// - [`ShareFileResponse::Ok`] if the file was shared successfully.
+    /// - [`ShareFileResponse::NoSuchUser`] if the user doesn't exist.
+    /// - [`ShareFileResponse::Unauthorized`] if the caller is not a user canister.
+    pub fn share_file(
+        user: Principal,
+        file_id: FileId,
+        metadata: ShareFileMetadata,
+    ) -> ShareFileResponse {
+        debug!("Sharing file with user: {user}, file_id: {file_id}, metadata: {metadata:?}",);
+        Self::share_file_with_users(vec![user], file_id, metadata)
+    }
+
+    /// Share a file with many users.
+    ///
+    /// The users are added to the contacts of the owner of the user canister, and the invites they
+    /// redeemed for the file are completed.
+    ///
+    /// # Returns
+    ///
+    /// - [`ShareFileResponse::Ok`] if the file was shared successfully.
+    /// - [`ShareFileResponse::NoSuchUser`] if the user doesn't exist or is being deleted.
+    /// - [`ShareFileResponse::Blocked`] if the user blocked the user canister or its owner.
+    /// - [`ShareFileResponse::Suspended`] if the owner of the user canister is suspended.
+    /// - [`ShareFileResponse::Unauthorized`] if the caller is not a user canister.
+    pub fn share_file_with_users(
+        users: Vec<Principal>,
+        file_id: FileId,
+        metadata: ShareFileMetadata,
+    ) -> ShareFileResponse {
+        debug!(
+            "Sharing file with users: {:?}, file_id: {}, metadata: {:?}",
+            users, file_id, metadata
+        );
+        let user_canister = msg_caller();
+        // check if the caller is a user canister
+        if !UserCanisterStorage::is_user_canister(user_canister) {
+            return ShareFileResponse::Unauthorized;
+        }
+        let owner = UserCanisterStorage::get_owner(user_canister);
+        if owner.is_some_and(ModerationStorage::is_suspended) {
+            return ShareFileResponse::Suspended;
+        }
+
+        // check if all the users exist, and are not being deleted
+        if let Some(no_such_user) = users.iter().find(|user| {
+            UserStorage::get_user(user).is_none()
+                || AccountDeletionStorage::is_deletion_pending(**user)
+        }) {
+            return ShareFileResponse::NoSuchUser(*no_such_user);
+        }
+
+        // check if a user blocked the user canister or its owner
+        if let Some(blocked_by) = users
+            .iter()
+            .find(|user| Self::is_sender_blocked(**user, user_canister, owner))
+        {
+            return ShareFileResponse::Blocked(*blocked_by);
+        }
+
+        // share the file with all the users; sharing again is a no-op, so that user canisters
+        // can retry
+        for user in users {
+            // the principals linked to the user must be known by the user canister, for them to
+            // download the files
+            let linked = LinkedPrincipalsStorage::get_linked_principals(user);
+            if cfg!(target_family = "wasm")
+                && !linked.is_empty()
+                && !SharedFilesStorage::get_shared_files(user).contains_key(&user_canister)
+            {
+                ic_cdk::futures::spawn(Self::link_recipient_principals(
+                    vec![user_canister],
+                    user,
+                    linked,
+                ));
+            }
+            let was_shared =
+                SharedFilesStorage::shared_with(user_canister, file_id).contains(&user);
+            SharedFilesStorage::share_file(user, user_canister, file_id, metadata.clone());
+            if !was_shared {
+                ShareLog::record(ShareOperation::Share, user_canister, file_id, user);
+                if let Some(owner) = owner {
+                    ContactsStorage::record_share(owner, user, Some(time()));
+                }
+            }
+            // the share completes the invites redeemed by the user for this file
+            if let Some(owner) = owner {
+                InvitesStorage::complete_invites(owner, file_id, user);
+            }
+        }
+
+        ShareFileResponse::Ok
+    }
+
+    /// Unblock a user or a user canister blocked by the caller.
+    ///
+    /// The files shared with the caller before are shown again by [`Self::shared_files`].
+    ///
+    /// # Returns
+    ///
+    /// - [`UnblockResponse::Ok`] if the principal was unblocked.
+    /// - [`UnblockResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`UnblockResponse::NotBlocked`] if the principal is not blocked by the caller.
+    pub fn unblock(principal: Principal) -> UnblockResponse {
+        debug!("Unblocking principal: {principal}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return UnblockResponse::AnonymousCaller;
+        }
+
+        if BlockListStorage::unblock(caller, principal) {
+            UnblockResponse::Ok
+        } else {
+            UnblockResponse::NotBlocked
+        }
+    }
+
+    /// Unlink a principal from the account of the caller.
+    ///
+    /// The principal is first unlinked on the user canisters sharing files with the caller and on
+    /// the user canister of the caller, then on the orchestrator.
+    ///
+    /// The principal the account was created with can't be unlinked, but may unlink any linked
+    /// principal; a linked principal may only unlink itself.
+    ///
+    /// # Returns
+    ///
+    /// - [`UnlinkPrincipalResponse::Ok`] if the principal was unlinked.
+    /// - [`UnlinkPrincipalResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`UnlinkPrincipalResponse::NoSuchUser`] if the caller has no user.
+    /// - [`UnlinkPrincipalResponse::NotLinked`] if the principal is not linked to the account of
+    ///   the caller.
+    /// - [`UnlinkPrincipalResponse::Unauthorized`] if the caller is a linked principal other than
+    ///   the principal.
+    /// - [`UnlinkPrincipalResponse::FailedToUpdateUserCanister`] if a user canister could not be
+    ///   updated.
+    pub async fn unlink_principal(principal: Principal) -> UnlinkPrincipalResponse {
+        debug!("Unlinking principal: {principal}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return UnlinkPrincipalResponse::AnonymousCaller;
+        }
+        if UserStorage::get_user(&caller).is_none() {
+            return UnlinkPrincipalResponse::NoSuchUser;
+        }
+        if LinkedPrincipalsStorage::get_account(principal) != Some(caller) {
+            return UnlinkPrincipalResponse::NotLinked;
+        }
+        if msg_caller() != caller && msg_caller() != principal {
+            return UnlinkPrincipalResponse::Unauthorized;
+        }
+
+        // unlink the principal on the user canisters first, so that it never keeps its access
+        if cfg!(target_family = "wasm") {
+            for user_canister in SharedFilesStorage::get_shared_files(caller).into_keys() {
+                match UserCanisterClient::from(user_canister)
+                    .unlink_recipient_principal(principal)
+                    .await
+                {
+                    Ok(LinkPrincipalResponse::Ok) => {}
+                    Ok(err) => {
+                        return UnlinkPrincipalResponse::FailedToUpdateUserCanister(format!(
+                            "{err:?}"
+                        ));
+                    }
+                    Err(err) => {
+                        return UnlinkPrincipalResponse::FailedToUpdateUserCanister(
+                            err.to_string(),
+                        );
+                    }
+                }
+            }
+        }
+        if let Some(user_canister) = UserCanisterStorage::get_user_canister(caller) {
+            if cfg!(target_family = "wasm") {
+                match UserCanisterClient::from(user_canister)
+                    .unlink_principal(principal)
+                    .await
+                {
+                    Ok(LinkPrincipalResponse::Ok) => {}
+                    Ok(err) => {
+                        return UnlinkPrincipalResponse::FailedToUpdateUserCanister(format!(
+                            "{err:?}"
+                        ));
+                    }
+                    Err(err) => {
+                        return UnlinkPrincipalResponse::FailedToUpdateUserCanister(
+                            err.to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        LinkedPrincipalsStorage::unlink(caller, principal);
+
+        UnlinkPrincipalResponse::Ok
+    }
+
+    /// Update the username and the display name of the caller.
+    ///
+    /// The username is normalized with [`normalize_username`]. The previous username stays
+    /// reserved to the caller, so that searches for it can be redirected with
+    /// [`Self::resolve_username`].
+    ///
+    /// # Returns
+    ///
+    /// - [`UpdateProfileResponse::Ok`] if the profile was updated.
+    /// - [`UpdateProfileResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`UpdateProfileResponse::NoSuchUser`] if the caller has no user.
+    /// - [`UpdateProfileResponse::UsernameTooLong`] if the username is too long.
+    /// - [`UpdateProfileResponse::InvalidUsername`] if the username has characters which are not allowed.
+    /// - [`UpdateProfileResponse::UsernameExists`] if the username is taken or reserved to another user.
+    /// - [`UpdateProfileResponse::UsernameReserved`] if the username is reserved by the admins.
+    /// - [`UpdateProfileResponse::DisplayNameTooLong`] if the display name is too long.
+    pub fn update_profile(
+        UpdateProfileRequest {
+            username,
+            display_name,
+        }: UpdateProfileRequest,
+    ) -> UpdateProfileResponse {
+        debug!("Updating profile with username: {username}, display_name: {display_name:?}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return UpdateProfileResponse::AnonymousCaller;
+        }
+
+        let Some(user) = UserStorage::get_user(&caller) else {
+            return UpdateProfileResponse::NoSuchUser;
+        };
+
+        if username.len() > MAX_USERNAME_SIZE {
+            return UpdateProfileResponse::UsernameTooLong;
+        }
+        let Some(username) = normalize_username(&username) else {
+            return UpdateProfileResponse::InvalidUsername;
+        };
+        if display_name
+            .as_ref()
+            .is_some_and(|name| name.len() > MAX_DISPLAY_NAME_SIZE)
+        {
+            return UpdateProfileResponse::DisplayNameTooLong;
+        }
+        // the caller may change the case of their username
+        if username_key(&user.username) != username_key(&username) {
+            if !UserStorage::is_username_available(&username, caller) {
+                return UpdateProfileResponse::UsernameExists;
+            }
+            if ModerationStorage::is_username_reserved(&username) {
+                return UpdateProfileResponse::UsernameReserved;
+            }
+        }
+
+        UserStorage::update_profile(caller, username, display_name);
+
+        UpdateProfileResponse::Ok
+    }
+
+    /// Update the public key of the caller.
+    ///
+    /// `version` is the version of the public key the caller is replacing; the update is rejected
+    /// if the key was changed in the meantime. The key and its new version are recorded on the
+    /// orchestrator first, then set on the user canister of the caller, if any, which rejects it
+    /// if it already holds a newer key. If the user canister rejects the key, the previous key is
+    /// restored; if the key was changed in the meantime, the user canister is synced back to the
+    /// key held by the orchestrator.
+    ///
+    /// # Returns
+    ///
+    /// - [`UpdatePublicKeyResponse::Ok`] with the new version of the public key.
+    /// - [`UpdatePublicKeyResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`UpdatePublicKeyResponse::NoSuchUser`] if the caller has no user.
+    /// - [`UpdatePublicKeyResponse::StaleVersion`] if the public key was changed in the meantime.
+    /// - [`UpdatePublicKeyResponse::KeyRotationInProgress`] if the owner public key is being
+    ///   rotated on the user canister.
+    /// - [`UpdatePublicKeyResponse::FailedToUpdateUserCanister`] if the user canister could not be
+    ///   updated.
+    pub async fn update_public_key(public_key: PublicKey, version: u64) -> UpdatePublicKeyResponse {
+        debug!("Updating public key from version {version}, public_key: {public_key:?}");
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return UpdatePublicKeyResponse::AnonymousCaller;
+        }
+        let Some(user) = UserStorage::get_user(&caller) else {
+            return UpdatePublicKeyResponse::NoSuchUser;
+        };
+
+        let current_version = UserStorage::get_public_key_version(&caller);
+        if version != current_version {
+            return UpdatePublicKeyResponse::StaleVersion(current_version);
+        }
+        if user.public_key == public_key {
+            return UpdatePublicKeyResponse::Ok(current_version);
+        }
+
+        // record the key first, so that a concurrent update sees the new version
+        let Some(new_version) = UserStorage::set_public_key(caller, public_key) else {
+            return UpdatePublicKeyResponse::NoSuchUser;
+        };
+
+        // then propagate it to the user canister
+        if let Some(user_canister) = UserCanisterStorage::get_user_canister(caller) {
+            if cfg!(target_family = "wasm") {
+                let result = UserCanisterClient::from(user_canister)
+                    .sync_public_key(public_key, new_version)
+                    .await;
+                if !matches!(result, Ok(SyncPublicKeyResponse::Ok)) {
+                    // restore the previous key, unless the key was changed in the meantime
+                    if UserStorage::get_public_key_version(&caller) == new_version {
+                        UserStorage::set_public_key(caller, user.public_key);
+                    }
+                    Self::resync_public_key(caller, user_canister);
+
+                    return match result {
+                        Ok(SyncPublicKeyResponse::StaleVersion(_)) => {
+                            UpdatePublicKeyResponse::StaleVersion(
+                                UserStorage::get_public_key_version(&caller),
+                            )
+                        }
+                        Ok(SyncPublicKeyResponse::KeyRotationInProgress) => {
+                            UpdatePublicKeyResponse::KeyRotationInProgress
+                        }
+                        Ok(err) => {
+                            UpdatePublicKeyResponse::FailedToUpdateUserCanister(format!("{err:?}"))
+                        }
+                        Err(err) => {
+                            UpdatePublicKeyResponse::FailedToUpdateUserCanister(err.to_string())
+                        }
+                    };
+                }
+            }
+        }
+
+        // the key may have been changed by the user canister in the meantime
+        let current_version = UserStorage::get_public_key_version(&caller);
+        if current_version != new_version {
+            if let Some(user_canister) = UserCanisterStorage::get_user_canister(caller) {
+                Self::resync_public_key(caller, user_canister);
+            }
+            return UpdatePublicKeyResponse::StaleVersion(current_version);
+        }
+
+        UpdatePublicKeyResponse::Ok(new_version)
+    }
+
+    /// Set the public key held by the orchestrator for a user, along with its version, on their
+    /// user canister, in the background.
+    ///
+    /// The user canister ignores it if it already holds a newer key.
+    fn resync_public_key(user: Principal, user_canister: Principal) {
+        let Some(current) = UserStorage::get_user(&user) else {
+            return;
+        };
+        let version = UserStorage::get_public_key_version(&user);
+        if cfg!(target_family = "wasm") {
+            ic_cdk::futures::spawn(async move {
+                if let Err(err) = UserCanisterClient::from(user_canister)
+                    .sync_public_key(current.public_key, version)
+                    .await
+                {
+                    debug!(
+                        "Failed to sync the public key of {user} back to {user_canister}: {err}"
+                    );
+                }
+            });
+        }
+    }
+
+    /// Update the public key of a user.
+    ///
+    /// Only the user canister of the user can update it, once the owner public key has been
+    /// replaced or rotated on the user canister.
+    ///
+    /// # Returns
+    ///
+    /// - [`UpdateUserPublicKeyResponse::Ok`] with the new version of the public key.
+    /// - [`UpdateUserPublicKeyResponse::NoSuchUser`] if the user doesn't exist.
+    /// - [`UpdateUserPublicKeyResponse::Unauthorized`] if the caller is not the user canister of
+    ///   the user.
+    pub fn update_user_public_key(
+        user: Principal,
+        public_key: PublicKey,
+    ) -> UpdateUserPublicKeyResponse {
+        debug!("Updating public key for user: {user}, public_key: {public_key:?}");
+        let caller = msg_caller();
+        if UserCanisterStorage::get_user_canister(user) != Some(caller) {
+            return UpdateUserPublicKeyResponse::Unauthorized;
+        }
+
+        match UserStorage::set_public_key(user, public_key) {
+            Some(version) => UpdateUserPublicKeyResponse::Ok(version),
+            None => UpdateUserPublicKeyResponse::NoSuchUser,
+        }
+    }
+
+    /// Returns the files shared by the caller, with the users they're shared with.
+    ///
+    /// The caller is either the owner of a user canister, or the user canister itself.
+    ///
+    /// Declined shares are not returned. Up to 128 files can be retrieved at once.
+    ///
+    /// # Returns
+    ///
+    /// - [`SharedByMeResponse::Files`] with a page of the shared files, ordered by file ID.
+    /// - [`SharedByMeResponse::AnonymousUser`] if the caller is anonymous.
+    /// - [`SharedByMeResponse::NoUserCanister`] if the caller has no user canister.
+    pub fn shared_by_me(Pagination { offset, limit }: Pagination) -> SharedByMeResponse {
+        let limit = limit.min(MAX_SHARED_BY_ME_LIMIT);
+        debug!(
+            "Getting files shared by caller: {}, offset: {offset}, limit: {limit}",
+            msg_caller()
+        );
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return SharedByMeResponse::AnonymousUser;
+        }
+
+        let user_canister = if UserCanisterStorage::is_user_canister(caller) {
+            caller
+        } else if let Some(user_canister) = UserCanisterStorage::get_user_canister(caller) {
+            user_canister
+        } else {
+            return SharedByMeResponse::NoUserCanister;
+        };
+
+        let (shares, total) =
+            SharedFilesStorage::user_canister_shares_in_range(user_canister, offset, limit);
+        let files = shares
+            .into_iter()
+            .map(|(file_id, users)| SharedByMeFile {
+                file_id,
+                file_name: SharedFilesStorage::get_file_metadata(user_canister, file_id)
+                    .map(|metadata| metadata.file_name)
+                    .unwrap_or_default(),
+                // sorted, since the users are stored in a hash set
+                shared_with: users
+                    .into_iter()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .filter_map(|principal| UserStorage::get_public_user(&principal))
+                    .collect(),
+            })
+            .collect();
+        let next = if offset + limit < total {
+            Some(offset + limit)
+        } else {
+            None
+        };
+
+        SharedByMeResponse::Files(SharedByMeFiles { files, next, total })
+    }
+
+    /// Returns the list of shared files for the caller.
+    ///
+    /// Declined and hidden shares are not returned, nor the shares of the user canisters blocked
+    /// by the caller.
+    ///
+    /// # Returns
+    ///
+    /// - [`SharedFilesResponse::AnonymousUser`] if the caller is anonymous.
+    /// - [`SharedFilesResponse::NoSuchUser`] if the user doesn't exist.
+    /// - [`SharedFilesResponse::SharedFiles`] if the user exists and has shared files.
+    pub fn shared_files() -> SharedFilesResponse {
+        debug!("Getting shared files for caller: {}", msg_caller());
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return SharedFilesResponse::AnonymousUser;
+        }
+
+        // check if the user exists
+        if UserStorage::get_user(&caller).is_none() {
+            return SharedFilesResponse::NoSuchUser;
+        }
+        if ModerationStorage::is_suspended(caller) {
+            return SharedFilesResponse::Suspended;
+        }
+
+        SharedFilesResponse::SharedFiles(
+            SharedFilesStorage::get_shared_files(caller)
+                .into_iter()
+                .filter(|(user_canister, _)| {
+                    let owner = UserCanisterStorage::get_owner(*user_canister);
+                    !Self::is_sender_blocked(caller, *user_canister, owner)
+                })
+                .map(|(user_canister, files)| {
+                    (
+                        user_canister,
+                        files
+                            .into_iter()
+                            .filter(|(_, share_state)| {
+                                !matches!(share_state, ShareState::Declined | ShareState::Hidden)
+                            })
+                            .filter_map(|(file_id, share_state)| {
+                                SharedFilesStorage::get_file_metadata(user_canister, file_id).map(
+                                    |file_metadata| PublicFileMetadata {
+                                        file_id,
+                                        file_name: file_metadata.file_name,
+                                        shared_with: SharedFilesStorage::shared_with(
+                                            user_canister,
+                                            file_id,
+                                        )
+                                        .into_iter()
+                                        .filter_map(|principal| {
+                                            UserStorage::get_public_user(&principal)
+                                        })
+                                        .collect(),
+                                        share_state,
+                                    },
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Checks whether a given username exists in the storage, or is reserved to a user who had
+    /// it before, regardless of its case.
+    pub fn username_exists(username: String) -> bool {
+        debug!("Checking if username exists: {username}",);
+        UserStorage::username_exists(&username)
+            || UserStorage::get_previous_username_owner(&username).is_some()
+    }
+
+    /// Get user canister information for the current caller.
+    ///
+    /// # Returns
+    ///
+    /// - [`UserCanisterResponse::AnonymousCaller`] if the caller is anonymous.
+    /// - [`UserCanisterResponse::Ok`] if the user canister is created and ready to use.
+    /// - [`UserCanisterResponse::CreationPending`] if the user canister is being created.
+    /// - [`UserCanisterResponse::CreationFailed`] if the user canister creation failed.
+    pub fn user_canister() -> UserCanisterResponse {
+        debug!("Getting user canister for caller: {}", msg_caller());
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return UserCanisterResponse::AnonymousCaller;
+        }
+
+        if let Some(canister) = UserCanisterStorage::get_user_canister(caller) {
+            return UserCanisterResponse::Ok(canister);
+        }
+
+        // otherwise check if it failed or it is pending
+        UserCanisterStorage::get_create_state(caller)
+            .map(|state| match state {
+                UserCanisterCreateState::Failed { reason } => {
+                    UserCanisterResponse::CreationFailed { reason }
+                }
+                _ => UserCanisterResponse::CreationPending,
+            })
+            .unwrap_or(UserCanisterResponse::Uninitialized)
+    }
+
+    /// Get [`WhoamiResponse`] for the current caller.
+    ///
+    /// # Returns
+    ///
+    /// - [`WhoamiResponse::UnknownUser`] if the caller is anonymous or doesn't exist.
+    /// - [`WhoamiResponse::KnownUser`] if the caller exists.
+    pub fn whoami() -> WhoamiResponse {
+        debug!("Getting whoami for caller: {}", msg_caller());
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return WhoamiResponse::UnknownUser;
+        }
+
+        UserStorage::get_public_user(&caller)
+            .map(WhoamiResponse::from)
+            .unwrap_or(WhoamiResponse::UnknownUser)
+    }
+
+    /// Get the account of the caller.
+    ///
+    /// That is the principal the account was created with, if the caller is linked to an account;
+    /// otherwise the caller itself.
+    fn account_caller() -> Principal {
+        let caller = msg_caller();
+        LinkedPrincipalsStorage::get_account(caller).unwrap_or(caller)
+    }
+
+    /// Get the state of the user canister of a user.
+    fn user_canister_state(user: Principal) -> AdminUserCanisterState {
+        if let Some(user_canister) = UserCanisterStorage::get_user_canister(user) {
+            return AdminUserCanisterState::Created(user_canister);
+        }
+
+        match UserCanisterStorage::get_create_state(user) {
+            Some(UserCanisterCreateState::Ok { user_canister }) => {
+                AdminUserCanisterState::Created(user_canister)
+            }
+            Some(UserCanisterCreateState::Failed { reason }) => {
+                AdminUserCanisterState::CreationFailed { reason }
+            }
+            Some(_) => AdminUserCanisterState::CreationPending,
+            None => AdminUserCanisterState::Uninitialized,
+        }
+    }
+
+    /// Add the users files are shared with to the contacts of the owners of the files.
+    ///
+    /// The time of these shares is unknown.
+    fn import_contacts_from_shares() {
+        for (user_canister, users) in SharedFilesStorage::recipients() {
+            let Some(owner) = UserCanisterStorage::get_owner(user_canister) else {
+                continue;
+            };
+            for user in users {
+                ContactsStorage::record_share(owner, user, None);
+            }
+        }
+    }
+
+    /// Get whether a principal has a user, or is linked to an account.
+    fn has_account(principal: Principal) -> bool {
+        UserStorage::get_user(&principal).is_some()
+            || LinkedPrincipalsStorage::get_account(principal).is_some()
+    }
+
+    /// Encode the metrics of the orchestrator in the Prometheus text format.
+    fn encode_metrics() -> String {
+        let (pending_user_canisters, failed_user_canisters) =
+            UserCanisterStorage::count_create_states();
+        let (users_with_shares, shared_files_metadata, files_shares) =
+            SharedFilesStorage::index_sizes();
+
+        let mut encoder = MetricsEncoder::default();
+        encoder
+            .gauge(
+                "orchestrator_users",
+                "Number of users.",
+                UserStorage::count() as f64,
+            )
+            .gauge_vec(
+                "orchestrator_user_canisters",
+                "Number of user canisters by creation state.",
+                "state",
+                [
+                    (
+                        "created",
+                        UserCanisterStorage::count_user_canisters() as f64,
+                    ),
+                    ("pending", pending_user_canisters as f64),
+                    ("failed", failed_user_canisters as f64),
+                ],
+            )
+            .gauge_vec(
+                "orchestrator_share_index_entries",
+                "Number of entries in each share index.",
+                "index",
+                [
+                    ("shared_files", users_with_shares as f64),
+                    ("shared_files_metadata", shared_files_metadata as f64),
+                    ("files_shares", files_shares as f64),
+                ],
+            )
+            .gauge(
+                "orchestrator_share_log_blocks",
+                "Number of blocks in the share log.",
+                ShareLogStorage::len() as f64,
+            )
+            .gauge(
+                "orchestrator_suspended_users",
+                "Number of suspended users.",
+                ModerationStorage::suspended_users().len() as f64,
+            )
+            .gauge(
+                "orchestrator_invites",
+                "Number of invites, including expired ones.",
+                InvitesStorage::count() as f64,
+            )
+            .gauge(
+                "orchestrator_stable_memory_pages",
+                "Size of the stable memory, in pages of 64 KiB.",
+                stable_memory_pages() as f64,
+            )
+            .gauge(
+                "orchestrator_heap_memory_bytes",
+                "Size of the heap memory, in bytes.",
+                heap_memory_size() as f64,
+            )
+            .gauge(
+                "orchestrator_cycle_balance",
+                "Cycle balance of the orchestrator.",
+                cycle_balance() as f64,
+            );
+
+        encoder.finish()
+    }
+
+    /// Count the invites of a user, removing the expired ones, which don't count.
+    fn count_invites(user: Principal) -> usize {
+        let mut invites = 0;
+        for (code, invite) in InvitesStorage::get_invites(user) {
+            if Self::invite_state(&invite).is_some() {
+                invites += 1;
+            } else {
+                InvitesStorage::remove_invite(code);
+            }
+        }
+        invites
+    }
+
+    /// Get the state of an invite, or `None` if it expired before being redeemed.
+    fn invite_state(invite: &InviteEntry) -> Option<InviteState> {
+        match invite.invitee {
+            Some(user) => Some(InviteState::Redeemed { user }),
+            None => {
+                let expires_at = invite.created_at.saturating_add(INVITE_EXPIRY);
+                (expires_at > time()).then_some(InviteState::Pending { expires_at })
+            }
+        }
+    }
+
+    /// Get whether an invite exists, and is neither redeemed nor expired.
+    fn is_invite_pending(code: InviteCode) -> bool {
+        InvitesStorage::get_invite(code).is_some_and(|invite| {
+            matches!(
+                Self::invite_state(&invite),
+                Some(InviteState::Pending { .. })
+            )
+        })
+    }
+
+    /// Get whether a principal requested to be linked to an account, and the request didn't expire.
+    fn is_link_requested(account: Principal, principal: Principal) -> bool {
+        LinkedPrincipalsStorage::get_link_request(account, principal)
+            .is_some_and(|requested_at| requested_at.saturating_add(LINK_REQUEST_EXPIRY) > time())
+    }
+
+    /// Link the principals of an account on the user canisters sharing files with it.
+    ///
+    /// Failures are only logged: the principals can't download the files shared by the user
+    /// canisters which could not be updated, until they are linked again.
+    async fn link_recipient_principals(
+        user_canisters: Vec<Principal>,
+        account: Principal,
+        principals: Vec<Principal>,
+    ) {
+        for user_canister in user_canisters {
+            let client = UserCanisterClient::from(user_canister);
+            for principal in &principals {
+                match client.link_recipient_principal(account, *principal).await {
+                    Ok(LinkPrincipalResponse::Ok) => {}
+                    Ok(err) => {
+                        debug!(
+                            "Failed to link {principal} on user canister {user_canister}: {err:?}"
+                        );
+                    }
+                    Err(err) => {
+                        debug!(
+                            "Failed to link {principal} on user canister {user_canister}: {err}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get whether a user blocked a user canister, or its owner.
+    fn is_sender_blocked(
+        user: Principal,
+        user_canister: Principal,
+        owner: Option<Principal>,
+    ) -> bool {
+        BlockListStorage::is_blocked(user, user_canister)
+            || owner.is_some_and(|owner| BlockListStorage::is_blocked(user, owner))
+    }
+
+    /// Revoke the share of a file for a user, recording it in the share log if the file was
+    /// shared with the user.
+    fn revoke_share(user: Principal, user_canister: Principal, file_id: FileId) {
+        let was_shared = SharedFilesStorage::shared_with(user_canister, file_id).contains(&user);
+        SharedFilesStorage::revoke_share(user, user_canister, file_id);
+        if was_shared {
+            ShareLog::record(ShareOperation::Revoke, user_canister, file_id, user);
+        }
+    }
+
+    /// Set the [`ShareState`] of a file shared with the caller by the given user canister.
+    ///
+    /// Declined shares can't be updated, since the owner has already dropped the caller's key.
+    fn update_share_state(
+        user_canister: Principal,
+        file_id: FileId,
+        state: ShareState,
+    ) -> ShareStateResponse {
+        let caller = Self::account_caller();
+        if caller == Principal::anonymous() {
+            return ShareStateResponse::AnonymousUser;
+        }
+
+        match SharedFilesStorage::get_share_state(caller, user_canister, file_id) {
+            None | Some(ShareState::Declined) => ShareStateResponse::NoSuchShare,
+            Some(_) => {
+                SharedFilesStorage::set_share_state(caller, user_canister, file_id, state);
+                ShareStateResponse::Ok
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::HashMap;
+
+    use did::orchestrator::{OrchestratorInitArgs, OrchestratorUpgradeArgs, User};
+
+    use super::*;
+    use crate::storage::contacts::ContactEntry;
+
+    #[test]
+    fn test_should_init_canister() {
+        let orbit_station = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        Canister::init(OrchestratorInstallArgs::Init(OrchestratorInitArgs {
+            orbit_station,
+            orbit_station_admin: "admin".to_string(),
+            admins: vec![],
+        }));
+
+        assert_eq!(Config::get_orbit_station(), orbit_station);
+        assert!(!Config::is_admin(msg_caller()));
+    }
+
+    #[test]
+    fn test_should_set_admins_on_upgrade() {
+        init_canister();
+        let admin = Principal::from_slice(&[9; 29]);
+
+        Canister::post_upgrade(OrchestratorInstallArgs::Upgrade(OrchestratorUpgradeArgs {
+            admins: Some(vec![admin]),
+        }));
+        assert!(Config::is_admin(admin));
+
+        // admins are kept if not given
+        Canister::post_upgrade(OrchestratorInstallArgs::Upgrade(OrchestratorUpgradeArgs {
+            admins: None,
+        }));
+        assert!(Config::is_admin(admin));
+
+        Canister::post_upgrade(OrchestratorInstallArgs::Upgrade(OrchestratorUpgradeArgs {
+            admins: Some(vec![]),
+        }));
+        assert!(!Config::is_admin(admin));
+    }
+
+    #[test]
+    fn test_should_only_allow_admins_to_call_admin_methods() {
+        init_canister();
+        let pagination = CursorPagination {
+            cursor: None,
+            limit: 10,
+        };
+
+        assert_eq!(
+            Canister::admin_users(pagination.clone()),
+            AdminUsersResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::admin_failed_user_canisters(pagination),
+            AdminFailedUserCanistersResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::admin_retry_user_canister_creation(msg_caller()),
+            AdminRetryUserCanisterCreationResponse::Unauthorized
+        );
+        assert_eq!(Canister::admin_stats(), AdminStatsResponse::Unauthorized);
+        assert_eq!(
+            Canister::admin_reserve_username("admin".to_string()),
+            AdminReserveUsernameResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::admin_release_username("admin".to_string()),
+            AdminReleaseUsernameResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::admin_reserved_usernames(),
+            AdminReservedUsernamesResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::admin_username_collisions(),
+            AdminUsernameCollisionsResponse::Unauthorized
+        );
+    }
+
+    #[test]
+    fn test_should_get_users_and_stats_as_admin() {
+        init_canister();
+        Config::set_admins(vec![msg_caller()]);
+
+        let alice = Principal::from_slice(&[1; 6]);
+        let bob = Principal::from_slice(&[2; 6]);
+        let charlie = Principal::from_slice(&[3; 6]);
+        for (principal, username) in [(alice, "alice"), (bob, "bob"), (charlie, "charlie")] {
+            UserStorage::add_user(
+                principal,
+                User {
+                    username: username.to_string(),
+                    public_key: PublicKey::default(),
+                },
+            );
+        }
+        let alice_canister = Principal::from_slice(&[11; 29]);
+        UserCanisterStorage::set_user_canister(alice, alice_canister);
+        UserCanisterStorage::set_create_state(
+            bob,
+            UserCanisterCreateState::Failed {
+                reason: "no cycles".to_string(),
+            },
+        );
+
+        let AdminUsersResponse::Users(page) = Canister::admin_users(CursorPagination {
+            cursor: None,
+            limit: 10,
+        }) else {
+            panic!("expected users");
+        };
+        assert_eq!(page.total, 3);
+        assert_eq!(
+            page.users
+                .iter()
+                .map(|user| user.user_canister.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                AdminUserCanisterState::Created(alice_canister),
+                AdminUserCanisterState::CreationFailed {
+                    reason: "no cycles".to_string()
+                },
+                AdminUserCanisterState::Uninitialized,
+            ]
+        );
+
+        assert_eq!(
+            Canister::admin_failed_user_canisters(CursorPagination {
+                cursor: None,
+                limit: 10,
+            }),
+            AdminFailedUserCanistersResponse::Failed(FailedUserCanisters {
+                failed: vec![FailedUserCanister {
+                    user: bob,
+                    reason: "no cycles".to_string(),
+                }],
+                next: None,
+            })
+        );
+
+        assert_eq!(
+            Canister::admin_stats(),
+            AdminStatsResponse::Stats(OrchestratorStats {
+                users: 3,
+                user_canisters: 1,
+                pending_user_canisters: 0,
+                failed_user_canisters: 1,
+                pending_account_deletions: 0,
+                failed_account_deletions: 0,
+                suspended_users: 0,
+                share_log_length: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_should_serve_metrics() {
+        init_canister();
+
+        let alice = Principal::from_slice(&[1; 6]);
+        let bob = Principal::from_slice(&[2; 6]);
+        for (principal, username) in [(alice, "alice"), (bob, "bob")] {
+            UserStorage::add_user(
+                principal,
+                User {
+                    username: username.to_string(),
+                    public_key: PublicKey::default(),
+                },
+            );
+        }
+        UserCanisterStorage::set_user_canister(alice, Principal::from_slice(&[11; 29]));
+        UserCanisterStorage::init_create_state(bob);
+
+        let request = |method: &str, url: &str| HttpRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: Default::default(),
+        };
+
+        let response = Canister::http_request(request("GET", "/metrics?time=1"));
+        assert_eq!(response.status_code, 200);
+        let metrics = String::from_utf8(response.body.into_vec()).unwrap();
+        assert!(metrics.contains("\norchestrator_users 2\n"));
+        assert!(metrics.contains("\norchestrator_user_canisters{state=\"created\"} 1\n"));
+        assert!(metrics.contains("\norchestrator_user_canisters{state=\"pending\"} 1\n"));
+        assert!(metrics.contains("\norchestrator_user_canisters{state=\"failed\"} 0\n"));
+        assert!(metrics.contains("\norchestrator_share_index_entries{index=\"files_shares\"} 0\n"));
+
+        assert_eq!(
+            Canister::http_request(request("POST", "/metrics")).status_code,
+            405
+        );
+        assert_eq!(Canister::http_request(request("GET", "/")).status_code, 404);
+    }
+
+    #[test]
+    fn test_should_retry_user_canister_creation_as_admin() {
+        init_canister();
+        Config::set_admins(vec![msg_caller()]);
+
+        let alice = Principal::from_slice(&[1; 29]);
+        assert_eq!(
+            Canister::admin_retry_user_canister_creation(alice),
+            AdminRetryUserCanisterCreationResponse::NoSuchUser
+        );
+
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        UserCanisterStorage::set_create_state(
+            alice,
+            UserCanisterCreateState::Failed {
+                reason: "test".to_string(),
+            },
+        );
+        assert_eq!(
+            Canister::admin_retry_user_canister_creation(alice),
+            AdminRetryUserCanisterCreationResponse::Ok
+        );
+
+        UserCanisterStorage::init_create_state(alice);
+        assert_eq!(
+            Canister::admin_retry_user_canister_creation(alice),
+            AdminRetryUserCanisterCreationResponse::CreationPending
+        );
+
+        let alice_canister = Principal::from_slice(&[11; 29]);
+        UserCanisterStorage::set_user_canister(alice, alice_canister);
+        assert_eq!(
+            Canister::admin_retry_user_canister_creation(alice),
+            AdminRetryUserCanisterCreationResponse::Created(alice_canister)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_suspend_and_unsuspend_user_as_admin() {
+        init_canister();
+        let alice = Principal::from_slice(&[1; 29]);
+        assert_eq!(
+            Canister::admin_suspend_user(alice).await,
+            AdminSuspendUserResponse::Unauthorized
+        );
+
+        Config::set_admins(vec![msg_caller()]);
+        assert_eq!(
+            Canister::admin_suspend_user(alice).await,
+            AdminSuspendUserResponse::NoSuchUser
+        );
+        assert_eq!(
+            Canister::admin_unsuspend_user(alice).await,
+            AdminUnsuspendUserResponse::NotSuspended
+        );
+
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        assert_eq!(
+            Canister::admin_suspend_user(alice).await,
+            AdminSuspendUserResponse::Ok
+        );
+        assert!(ModerationStorage::is_suspended(alice));
+
+        let AdminUsersResponse::Users(page) = Canister::admin_users(CursorPagination {
+            cursor: None,
+            limit: 10,
+        }) else {
+            panic!("expected users");
+        };
+        assert!(page.users[0].suspended_at.is_some());
+        let AdminStatsResponse::Stats(stats) = Canister::admin_stats() else {
+            panic!("expected stats");
+        };
+        assert_eq!(stats.suspended_users, 1);
+
+        // suspended users are hidden from the other users
+        assert_eq!(
+            Canister::search_users(
+                CursorPagination {
+                    cursor: None,
+                    limit: 10,
+                },
+                Some("alice"),
+            ),
+            SearchUsersResponse::Users(SearchUsersResponseUsers {
+                users: vec![],
+                total: 0,
+                next: None,
+            })
+        );
+
+        assert_eq!(
+            Canister::admin_unsuspend_user(alice).await,
+            AdminUnsuspendUserResponse::Ok
+        );
+        assert!(!ModerationStorage::is_suspended(alice));
+        assert_eq!(
+            Canister::admin_unsuspend_user(alice).await,
+            AdminUnsuspendUserResponse::NotSuspended
+        );
+    }
+
+    #[test]
+    fn test_should_not_allow_suspended_user() {
+        init_canister();
+        let caller = msg_caller();
+        UserStorage::add_user(
+            caller,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        ModerationStorage::suspend(caller, 1);
+
+        assert_eq!(
+            Canister::set_user("alice2".to_string(), PublicKey::default(), None),
+            SetUserResponse::Suspended
+        );
+        assert_eq!(Canister::shared_files(), SharedFilesResponse::Suspended);
+
+        // the caller is also the user canister of the suspended user
+        UserCanisterStorage::set_user_canister(caller, caller);
+        let bob = Principal::from_slice(&[2; 29]);
+        UserStorage::add_user(
+            bob,
+            User {
+                username: "bob".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        assert_eq!(
+            Canister::share_file_with_users(
+                vec![bob],
+                1,
+                ShareFileMetadata {
+                    file_name: "foo.txt".to_string(),
+                },
+            ),
+            ShareFileResponse::Suspended
+        );
+        assert!(SharedFilesStorage::get_shared_files(bob).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_should_create_and_revoke_invites() {
+        init_canister();
+        let caller = msg_caller();
+        let request = CreateInviteRequest {
+            file_id: 1,
+            metadata: ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        };
+        assert_eq!(
+            Canister::create_invite(request.clone()).await,
+            CreateInviteResponse::NoSuchUser
+        );
+
+        UserStorage::add_user(
+            caller,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        assert_eq!(
+            Canister::create_invite(request.clone()).await,
+            CreateInviteResponse::UserCanisterNotReady
+        );
+
+        UserCanisterStorage::set_user_canister(caller, Principal::from_slice(&[11; 29]));
+        let CreateInviteResponse::Ok(code) = Canister::create_invite(request.clone()).await else {
+            panic!("expected invite code");
+        };
+        let InvitesResponse::Invites(invites) = Canister::invites() else {
+            panic!("expected invites");
+        };
+        assert_eq!(invites.len(), 1);
+        assert_eq!(invites[0].code, code);
+        assert_eq!(invites[0].metadata, request.metadata);
+        assert!(matches!(invites[0].state, InviteState::Pending { .. }));
+
+        // expired invites are not returned, and don't count
+        InvitesStorage::insert_invite(
+            [0; 16],
+            InviteEntry {
+                owner: caller,
+                file_id: 2,
+                file_name: "bar.txt".to_string(),
+                created_at: 0,
+                invitee: None,
+            },
+        );
+        let InvitesResponse::Invites(invites) = Canister::invites() else {
+            panic!("expected invites");
+        };
+        assert_eq!(invites.len(), 1);
+        for _ in 1..MAX_INVITES_PER_USER {
+            assert!(matches!(
+                Canister::create_invite(request.clone()).await,
+                CreateInviteResponse::Ok(_)
+            ));
+        }
+        assert_eq!(
+            Canister::create_invite(request).await,
+            CreateInviteResponse::TooManyInvites
+        );
+        assert!(InvitesStorage::get_invite([0; 16]).is_none());
+
+        assert_eq!(
+            Canister::revoke_invite("not a code".to_string()),
+            RevokeInviteResponse::NoSuchInvite
+        );
+        assert_eq!(
+            Canister::revoke_invite(code.clone()),
+            RevokeInviteResponse::Ok
+        );
+        assert_eq!(
+            Canister::revoke_invite(code),
+            RevokeInviteResponse::NoSuchInvite
+        );
+    }
+
+    #[test]
+    fn test_should_sign_up_with_invite_and_complete_it_on_share() {
+        init_canister();
+        let caller = msg_caller();
+        let owner = Principal::from_slice(&[2; 29]);
+        let invite = |created_at| InviteEntry {
+            owner,
+            file_id: 1,
+            file_name: "foo.txt".to_string(),
+            created_at,
+            invitee: None,
+        };
+        let (expired, code) = ([1; 16], [2; 16]);
+        InvitesStorage::insert_invite(expired, invite(0));
+        InvitesStorage::insert_invite(code, invite(time()));
+
+        for invalid in ["not a code".to_string(), format_invite_code(&expired)] {
+            assert_eq!(
+                Canister::set_user("bob".to_string(), PublicKey::default(), Some(invalid)),
+                SetUserResponse::InvalidInvite
+            );
+        }
+        assert!(UserStorage::get_user(&caller).is_none());
+
+        assert_eq!(
+            Canister::set_user(
+                "bob".to_string(),
+                PublicKey::default(),
+                Some(format_invite_code(&code).to_uppercase())
+            ),
+            SetUserResponse::Ok
+        );
+        assert_eq!(
+            InvitesStorage::get_invite(code).unwrap().invitee,
+            Some(caller)
+        );
+
+        // the owner shares the file with the invitee from their user canister
+        UserCanisterStorage::set_user_canister(owner, caller);
+        assert_eq!(
+            Canister::share_file(
+                caller,
+                1,
+                ShareFileMetadata {
+                    file_name: "foo.txt".to_string(),
+                },
+            ),
+            ShareFileResponse::Ok
+        );
+        assert!(InvitesStorage::get_invite(code).is_none());
+    }
+
+    #[test]
+    fn test_should_reserve_and_release_usernames_as_admin() {
+        init_canister();
+        Config::set_admins(vec![msg_caller()]);
+        assert_eq!(
+            Canister::admin_username_collisions(),
+            AdminUsernameCollisionsResponse::Collisions(vec![])
+        );
+
+        assert_eq!(
+            Canister::admin_reserve_username("Admin".to_string()),
+            AdminReserveUsernameResponse::Ok
+        );
+        assert_eq!(
+            Canister::admin_reserve_username("not valid!".to_string()),
+            AdminReserveUsernameResponse::InvalidUsername
+        );
+        assert_eq!(
+            Canister::admin_reserved_usernames(),
+            AdminReservedUsernamesResponse::Usernames(vec!["Admin".to_string()])
+        );
+
+        assert_eq!(
+            Canister::set_user("aDmIn".to_string(), PublicKey::default(), None),
+            SetUserResponse::UsernameReserved
+        );
+        assert_eq!(
+            Canister::set_user("alice".to_string(), PublicKey::default(), None),
+            SetUserResponse::Ok
+        );
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "admin".to_string(),
+                display_name: None,
+            }),
+            UpdateProfileResponse::UsernameReserved
+        );
+
+        assert_eq!(
+            Canister::admin_release_username("ADMIN".to_string()),
+            AdminReleaseUsernameResponse::Ok
+        );
+        assert_eq!(
+            Canister::admin_release_username("admin".to_string()),
+            AdminReleaseUsernameResponse::NotReserved
+        );
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "admin".to_string(),
+                display_name: None,
+            }),
+            UpdateProfileResponse::Ok
+        );
+    }
+
+    #[test]
+    fn test_should_get_user() {
+        init_canister();
+
+        // setup user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: vec![1; 32].try_into().unwrap(),
+            },
+        );
+
+        // get user
+        let response = Canister::get_user(principal);
+        assert_eq!(
+            response,
+            Some(PublicUser {
+                username: "test_user".to_string(),
+                public_key: vec![1; 32].try_into().unwrap(),
+                ic_principal: principal,
+                display_name: None,
+                key_version: 0,
+            })
+        );
+
+        // get user with a different principal
+        let principal = Principal::from_slice(&[1; 6]);
+        let response = Canister::get_user(principal);
+        assert!(response.is_none());
+    }
+
+    #[test]
+    fn test_should_search_users() {
+        init_canister();
+        Config::set_admins(vec![msg_caller()]);
+
+        // setup user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // get users
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: None,
+                limit: 10,
+            },
+            None,
+        );
+        assert_eq!(
+            response,
+            SearchUsersResponse::Users(SearchUsersResponseUsers {
+                users: vec![PublicUser {
+                    username: "test_user".to_string(),
+                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                    ic_principal: principal,
+                    display_name: None,
+                    key_version: 0,
+                }],
+                total: 1,
+                next: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_should_get_users_by_offset() {
+        init_canister();
+        Config::set_admins(vec![msg_caller()]);
+
+        // setup users
+        for i in 0..9 {
+            UserStorage::add_user(
+                Principal::from_slice(&[i; 6]),
+                User {
+                    username: format!("test_user_{i}",),
+                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                },
+            );
+        }
+
+        let GetUsersResponse::Users(GetUsersResponseUsers { users, total, next }) =
+            Canister::get_users(
+                Pagination {
+                    offset: 0,
+                    limit: 5,
+                },
+                None,
+            )
+        else {
+            panic!("Expected GetUsersResponse::Users");
+        };
+        assert_eq!(users.len(), 5);
+        assert_eq!(total, 9);
+        assert_eq!(next, Some(5));
+
+        let GetUsersResponse::Users(GetUsersResponseUsers { users, total, next }) =
+            Canister::get_users(
+                Pagination {
+                    offset: 5,
+                    limit: 5,
+                },
+                None,
+            )
+        else {
+            panic!("Expected GetUsersResponse::Users");
+        };
+        assert_eq!(users.len(), 4);
+        assert_eq!(users[0].ic_principal, Principal::from_slice(&[5; 6]));
+        assert_eq!(total, 9);
+        assert!(next.is_none());
+
+        let GetUsersResponse::Users(GetUsersResponseUsers { users, total, next }) =
+            Canister::get_users(
+                Pagination {
+                    offset: 0,
+                    limit: 5,
+                },
+                Some("test_user_3"),
+            )
+        else {
+            panic!("Expected GetUsersResponse::Users");
+        };
+        assert_eq!(users.len(), 1);
+        assert_eq!(total, 1);
+        assert!(next.is_none());
+
+        assert_eq!(
+            Canister::get_users(
+                Pagination {
+                    offset: 0,
+                    limit: 5,
+                },
+                Some("aa"),
+            ),
+            GetUsersResponse::InvalidQuery
+        );
+    }
+
+    #[test]
+    fn test_should_get_contacts_as_users_if_not_admin() {
+        init_canister();
+
+        let bob = Principal::from_slice(&[2; 6]);
+        let charlie = Principal::from_slice(&[3; 6]);
+        for (principal, username) in [(msg_caller(), "alice"), (bob, "bob"), (charlie, "charlie")] {
+            UserStorage::add_user(
+                principal,
+                User {
+                    username: username.to_string(),
+                    public_key: PublicKey::default(),
+                },
+            );
+        }
+        ContactsStorage::set_contact(msg_caller(), bob, None);
+
+        let GetUsersResponse::Users(GetUsersResponseUsers { users, total, next }) =
+            Canister::get_users(
+                Pagination {
+                    offset: 0,
+                    limit: 127,
+                },
+                None,
+            )
+        else {
+            panic!("Expected GetUsersResponse::Users");
+        };
+        assert_eq!(
+            users
+                .into_iter()
+                .map(|user| user.ic_principal)
+                .collect::<Vec<_>>(),
+            vec![bob]
+        );
+        assert_eq!(total, 1);
+        assert!(next.is_none());
+    }
+
+    #[test]
+    fn test_should_not_list_users_if_not_admin() {
+        init_canister();
+
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: None,
+                limit: 10,
+            },
+            None,
+        );
+        assert_eq!(response, SearchUsersResponse::PermissionError);
+    }
+
+    #[test]
+    fn test_should_search_users_with_query() {
+        init_canister();
+
+        // setup users
+        for i in 0..150 {
+            UserStorage::add_user(
+                Principal::from_slice(&[i; 6]),
+                User {
+                    username: format!("test_user_{i}",),
+                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                },
+            );
+        }
+
+        // get users
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: None,
+                limit: 20,
+            },
+            Some("test_user_12"),
+        );
+
+        // there should be eleven users (12, 120, 121, ..., 129)
+        let SearchUsersResponse::Users(SearchUsersResponseUsers { users, total, next }) = response
+        else {
+            panic!("Expected SearchUsersResponse::Users");
+        };
+
+        assert_eq!(users.len(), 11);
+        assert_eq!(total, 11);
+        assert!(next.is_none());
+
+        // with pagination
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: None,
+                limit: 5,
+            },
+            Some("test_user_12"),
+        );
+
+        // there should be eleven users (12, 120, 121, ..., 129)
+        let SearchUsersResponse::Users(SearchUsersResponseUsers { users, total, next }) = response
+        else {
+            panic!("Expected SearchUsersResponse::Users");
+        };
+
+        assert_eq!(users.len(), 5);
+        assert_eq!(total, 11);
+        assert_eq!(next, Some(users[4].ic_principal));
+    }
+
+    #[test]
+    fn test_should_not_search_users_with_invalid_query() {
+        init_canister();
+
+        // get users
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: None,
+                limit: 20,
+            },
+            Some("aa"),
+        );
+        assert_eq!(response, SearchUsersResponse::InvalidQuery);
+    }
+
+    #[test]
+    fn test_should_search_paginated_users() {
+        init_canister();
+        Config::set_admins(vec![msg_caller()]);
+
+        // setup users
+        for i in 0..9 {
+            UserStorage::add_user(
+                Principal::from_slice(&[i; 6]),
+                User {
+                    username: format!("test_user_{i}",),
+                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                },
+            );
+        }
+
+        // get users
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: None,
+                limit: 5,
+            },
+            None,
+        );
+
+        let SearchUsersResponse::Users(SearchUsersResponseUsers { total, users, next }) = response
+        else {
+            panic!("Expected SearchUsersResponse::Users");
+        };
+        assert_eq!(users.len(), 5);
+        assert_eq!(total, 9);
+        assert_eq!(next, Some(users[4].ic_principal));
+
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: next,
+                limit: 8,
+            },
+            None,
+        );
+
+        let SearchUsersResponse::Users(SearchUsersResponseUsers { total, users, next }) = response
+        else {
+            panic!("Expected SearchUsersResponse::Users");
+        };
+        assert_eq!(total, 9);
+        assert_eq!(users.len(), 4);
+        assert!(next.is_none());
+    }
+
+    #[test]
+    fn test_should_search_capped_paginated_users() {
+        init_canister();
+        Config::set_admins(vec![msg_caller()]);
+
+        // setup users
+        for i in 0..150 {
+            UserStorage::add_user(
+                Principal::from_slice(&[i; 6]),
+                User {
+                    username: format!("test_user_{i}",),
+                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                },
+            );
+        }
+
+        // get users
+        let response = Canister::search_users(
+            CursorPagination {
+                cursor: None,
+                limit: 150,
+            },
+            None,
+        );
+
+        let SearchUsersResponse::Users(SearchUsersResponseUsers { total, users, next }) = response
+        else {
+            panic!("Expected SearchUsersResponse::Users");
+        };
+        assert_eq!(users.len() as u64, MAX_GET_USERS_LIMIT);
+        assert_eq!(total, 150);
+        assert_eq!(next, users.last().map(|user| user.ic_principal));
+    }
+
+    #[test]
+    fn test_should_retry_user_canister_creation() {
+        init_canister();
+
+        // let's setup a user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // of course this won't start the state machine on test unit; let's set the state to failed
+        UserCanisterStorage::set_create_state(
+            principal,
+            UserCanisterCreateState::Failed {
+                reason: "test".to_string(),
+            },
+        );
+
+        // we can retry now :D
+        let response = Canister::retry_user_canister_creation();
+        assert_eq!(response, RetryUserCanisterCreationResponse::Ok);
+    }
+
+    #[test]
+    fn test_should_not_retry_user_canister_creation_if_user_does_not_exist() {
+        init_canister();
+
+        // let's setup another user
+        UserStorage::add_user(
+            Principal::management_canister(),
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // user does not exist
+        let response = Canister::retry_user_canister_creation();
+        assert_eq!(response, RetryUserCanisterCreationResponse::UserNotFound);
+    }
+
+    #[test]
+    fn test_should_not_retry_if_user_canister_exists() {
+        init_canister();
+
+        // let's setup a user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // let's set the user canister
+        let user_canister = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        UserCanisterStorage::set_user_canister(principal, user_canister);
+
+        // canister already exists
+        let response = Canister::retry_user_canister_creation();
+        assert_eq!(
+            response,
+            RetryUserCanisterCreationResponse::Created(user_canister)
+        );
+    }
+
+    #[test]
+    fn test_should_not_retry_if_pending() {
+        init_canister();
+
+        // let's setup a user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // let's set the user canister creation state to something pending
+        UserCanisterStorage::set_create_state(principal, UserCanisterCreateState::CreateCanister);
+
+        // canister already exists
+        let response = Canister::retry_user_canister_creation();
+        assert_eq!(response, RetryUserCanisterCreationResponse::CreationPending);
+    }
+
+    #[test]
+    fn test_should_register_user_if_valid() {
+        init_canister();
+
+        // setup user
+        let principal = msg_caller();
+        let username = "test_user".to_string();
+        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+
+        // register user
+        let response = Canister::set_user(username.clone(), public_key, None);
+        assert_eq!(response, SetUserResponse::Ok);
+
+        // check if user exists
+        let user = UserStorage::get_user(&principal).unwrap();
+        assert_eq!(user.username, username);
+        assert_eq!(user.public_key, public_key);
+    }
+
+    #[test]
+    fn test_should_not_add_user_if_username_too_long() {
+        init_canister();
+
+        // setup user
+        let principal = msg_caller();
+        let username = "a".repeat(MAX_USERNAME_SIZE + 1);
+        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+
+        // register user
+        let response = Canister::set_user(username.clone(), public_key, None);
+        assert_eq!(response, SetUserResponse::UsernameTooLong);
+
+        // check if user does not exist
+        let user = UserStorage::get_user(&principal);
+        assert!(user.is_none());
+    }
+
+    #[test]
+    fn test_should_not_add_user_if_username_invalid() {
+        init_canister();
+
+        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+        for username in ["", "_test_user", "test user", "te\u{200B}st_user"] {
+            assert_eq!(
+                Canister::set_user(username.to_string(), public_key, None),
+                SetUserResponse::InvalidUsername
+            );
+        }
+        assert!(UserStorage::get_user(&msg_caller()).is_none());
+    }
+
+    #[test]
+    fn test_should_not_add_user_if_username_exists_in_other_case() {
+        init_canister();
+
+        UserStorage::add_user(
+            Principal::from_slice(&[2; 29]),
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+
+        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+        for username in ["Alice", "ＡＬＩＣＥ"] {
+            assert_eq!(
+                Canister::set_user(username.to_string(), public_key, None),
+                SetUserResponse::UsernameExists
+            );
+        }
+
+        // fullwidth forms are normalized
+        assert_eq!(
+            Canister::set_user("ｂｏｂ".to_string(), public_key, None),
+            SetUserResponse::Ok
+        );
+        assert_eq!(
+            UserStorage::get_user(&msg_caller()).unwrap().username,
+            "bob"
+        );
+    }
+
+    #[test]
+    fn test_should_not_add_user_if_caller_has_already_a_user() {
+        init_canister();
+
+        // setup user
+        let username = "test_user".to_string();
+        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+
+        // register user
+        let response = Canister::set_user(username.clone(), public_key, None);
+        assert_eq!(response, SetUserResponse::Ok);
+
+        // try another username
+        let response = Canister::set_user("foo".to_string(), public_key, None);
+        assert_eq!(response, SetUserResponse::CallerHasAlreadyAUser);
+    }
+
+    #[test]
+    fn test_should_tell_if_username_exists() {
+        init_canister();
+
+        // setup user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // check if username exists
+        let exists = Canister::username_exists("test_user".to_string());
+        assert!(exists);
+
+        // check if non-existing username exists
+        let exists = Canister::username_exists("non_existing_user".to_string());
+        assert!(!exists);
+    }
+
+    #[test]
+    fn test_should_update_profile() {
+        init_canister();
+
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "new_user".to_string(),
+                display_name: None,
+            }),
+            UpdateProfileResponse::NoSuchUser
+        );
+
+        let principal = msg_caller();
+        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+        assert_eq!(
+            Canister::set_user("test_user".to_string(), public_key, None),
+            SetUserResponse::Ok
+        );
+        let bob = Principal::from_slice(&[2; 29]);
+        UserStorage::add_user(
+            bob,
+            User {
+                username: "bob".to_string(),
+                public_key,
+            },
+        );
+
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "a".repeat(MAX_USERNAME_SIZE + 1),
+                display_name: None,
+            }),
+            UpdateProfileResponse::UsernameTooLong
+        );
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "test_user".to_string(),
+                display_name: Some("a".repeat(MAX_DISPLAY_NAME_SIZE + 1)),
+            }),
+            UpdateProfileResponse::DisplayNameTooLong
+        );
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "new user".to_string(),
+                display_name: None,
+            }),
+            UpdateProfileResponse::InvalidUsername
+        );
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "Bob".to_string(),
+                display_name: None,
+            }),
+            UpdateProfileResponse::UsernameExists
+        );
+
+        // changing the case of the username doesn't reserve it
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "Test_User".to_string(),
+                display_name: None,
+            }),
+            UpdateProfileResponse::Ok
+        );
+        assert!(
+            UserStorage::get_profile(&principal)
+                .previous_usernames
+                .is_empty()
+        );
+
+        assert_eq!(
+            Canister::update_profile(UpdateProfileRequest {
+                username: "new_user".to_string(),
+                display_name: Some("Test User".to_string()),
+            }),
+            UpdateProfileResponse::Ok
+        );
+        assert_eq!(
+            Canister::whoami(),
+            WhoamiResponse::KnownUser(PublicUser {
+                username: "new_user".to_string(),
+                public_key,
+                ic_principal: principal,
+                display_name: Some("Test User".to_string()),
+                key_version: 0,
+            })
+        );
+
+        // the previous username is reserved, and redirects to the user
+        assert!(Canister::username_exists("test_user".to_string()));
+        assert_eq!(
+            Canister::resolve_username("test_user".to_string()).map(|user| user.ic_principal),
+            Some(principal)
+        );
+        assert_eq!(
+            Canister::resolve_username("new_user".to_string()).map(|user| user.ic_principal),
+            Some(principal)
+        );
+        assert_eq!(
+            Canister::resolve_username("bob".to_string()).map(|user| user.ic_principal),
+            Some(bob)
+        );
+        assert_eq!(Canister::resolve_username("alice".to_string()), None);
+    }
+
+    #[test]
+    fn test_should_tell_whoami() {
+        init_canister();
+
+        // setup user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // get whoami
+        let whoami = Canister::whoami();
+        assert_eq!(
+            whoami,
+            WhoamiResponse::KnownUser(PublicUser {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                ic_principal: principal,
+                display_name: None,
+                key_version: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_should_return_shared_files() {
+        init_canister();
+
+        // setup user
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // insert shared files
+        let file_id = 1;
+        let user_canister = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+
+        SharedFilesStorage::share_file(
+            principal,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+
+        let public_user = PublicUser {
+            username: "test_user".to_string(),
+            public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            ic_principal: principal,
+            display_name: None,
+            key_version: 0,
+        };
+
+        let mut expected = HashMap::new();
+        expected.insert(
+            user_canister,
+            vec![PublicFileMetadata {
+                file_id,
+                file_name: "foo.txt".to_string(),
+                shared_with: vec![public_user],
+                share_state: ShareState::Pending,
+            }]
+            .into_iter()
+            .collect(),
+        );
+
+        // get shared files
+        let shared_files = Canister::shared_files();
+        assert_eq!(shared_files, SharedFilesResponse::SharedFiles(expected));
+    }
+
+    #[test]
+    fn test_should_return_error_on_shared_files_unexisting_user() {
+        init_canister();
+
+        // get shared files
+        let shared_files = Canister::shared_files();
+        assert_eq!(shared_files, SharedFilesResponse::NoSuchUser);
+    }
+
+    #[test]
+    fn test_should_revoke_shared_file() {
+        init_canister();
+
+        // insert user canister
+        let user_canister = msg_caller();
+        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        UserCanisterStorage::set_user_canister(user, user_canister);
+
+        // revoke share
+        let file_id = 1;
+        SharedFilesStorage::share_file(
+            user,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        let response = Canister::revoke_share_file(user, file_id);
+        assert_eq!(response, RevokeShareFileResponse::Ok);
+
+        // check if the file is revoked
+        let shared_files = SharedFilesStorage::get_shared_files(user);
+        assert_eq!(shared_files.len(), 0);
+    }
+
+    #[test]
+    fn test_should_not_revoke_shared_file_if_caller_is_not_a_user_canister() {
+        init_canister();
+
+        // insert user canister
+        let user_canister = msg_caller();
+        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+
+        // revoke share
+        let file_id = 1;
+        SharedFilesStorage::share_file(
+            user,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        let response = Canister::revoke_share_file(user, file_id);
+        assert_eq!(response, RevokeShareFileResponse::Unauthorized);
+
+        // check if the file is NOT revoked
+        let shared_files = SharedFilesStorage::get_shared_files(user);
+        assert_eq!(shared_files.len(), 1);
+    }
+
+    #[test]
+    fn test_should_revoke_shared_file_with_users() {
+        init_canister();
+
+        // insert user canister
+        let user_canister = msg_caller();
+        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        let user_2 = Principal::from_slice(&[2; 6]);
+        let user_3 = Principal::from_slice(&[3; 6]);
+        UserCanisterStorage::set_user_canister(user, user_canister);
+        UserCanisterStorage::set_user_canister(user_2, user_canister);
+        UserCanisterStorage::set_user_canister(user_3, user_canister);
+
+        // revoke share
+        let file_id = 1;
+        SharedFilesStorage::share_file(
+            user,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        SharedFilesStorage::share_file(
+            user_2,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        SharedFilesStorage::share_file(
+            user_3,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        let response = Canister::revoke_share_file_for_users(vec![user, user_2], file_id);
+        assert_eq!(response, RevokeShareFileResponse::Ok);
+
+        // check if the file is revoked
+        let shared_files = SharedFilesStorage::get_shared_files(user);
+        assert_eq!(shared_files.len(), 0);
+        let shared_files = SharedFilesStorage::get_shared_files(user_2);
+        assert_eq!(shared_files.len(), 0);
+        let shared_files = SharedFilesStorage::get_shared_files(user_3);
+        assert_eq!(shared_files.len(), 1);
+    }
+
+    #[test]
+    fn test_should_revoke_user_shares() {
+        init_canister();
+
+        let user_canister = msg_caller();
+        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        let user_2 = Principal::from_slice(&[2; 6]);
+        assert_eq!(
+            Canister::revoke_user_shares(user, vec![1]),
+            RevokeShareFileResponse::Unauthorized
+        );
+
+        UserCanisterStorage::set_user_canister(user, user_canister);
+        for (shared_with, file_id) in [(user, 1), (user, 2), (user, 3), (user_2, 1)] {
+            SharedFilesStorage::share_file(
+                shared_with,
+                user_canister,
+                file_id,
+                ShareFileMetadata {
+                    file_name: "foo.txt".to_string(),
+                },
+            );
+        }
+        let response = Canister::revoke_user_shares(user, vec![1, 2]);
+        assert_eq!(response, RevokeShareFileResponse::Ok);
+
+        let shared_files = SharedFilesStorage::get_shared_files(user);
+        assert_eq!(shared_files.len(), 1);
+        let shared_files = SharedFilesStorage::get_shared_files(user_2);
+        assert_eq!(shared_files.len(), 1);
+    }
+
+    #[test]
+    fn test_should_share_file() {
+        init_canister();
+
+        // insert user canister
+        let user_canister = msg_caller();
+        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+
+        // set user canister
+        UserCanisterStorage::set_user_canister(user, user_canister);
+
+        // create user
+        let alice = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        // share file
+        let file_id = 1;
+        let response = Canister::share_file(
+            alice,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        assert_eq!(response, ShareFileResponse::Ok);
+
+        // check if the file is shared
+        let shared_files = SharedFilesStorage::get_shared_files(alice);
+        assert_eq!(shared_files.len(), 1);
+    }
+
+    #[test]
+    fn test_should_not_share_if_not_called_by_user_canister() {
+        init_canister();
+
+        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+
+        // share file
+        let file_id = 1;
+        let response = Canister::share_file(
+            user,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        assert_eq!(response, ShareFileResponse::Unauthorized);
+
+        // check if the file is NOT shared
+        let shared_files = SharedFilesStorage::get_shared_files(user);
+        assert_eq!(shared_files.len(), 0);
     }
 
-    /// Checks whether a given username exists in the storage.
-    pub fn username_exists(username: String) -> bool {
-        debug!("Checking if username exists: {username}",);
-        UserStorage::username_exists(&username)
-    }
+    #[test]
+    fn test_should_not_share_if_user_does_not_exist() {
+        init_canister();
 
-    /// Get user canister information for the current caller.
-    ///
-    /// # Returns
-    ///
-    /// - [`UserCanisterResponse::AnonymousCaller`] if the caller is anonymous.
-    /// - [`UserCanisterResponse::Ok`] if the user canister is created and ready to use.
-    /// - [`UserCanisterResponse::CreationPending`] if the user canister is being created.
-    /// - [`UserCanisterResponse::CreationFailed`] if the user canister creation failed.
-    pub fn user_canister() -> UserCanisterResponse {
-        debug!("Getting user canister for caller: {}", msg_caller());
-        let caller = msg_caller();
-        if caller == Principal::anonymous() {
-            return UserCanisterResponse::AnonymousCaller;
-        }
+        // insert user canister
+        let user_canister = msg_caller();
+        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
 
-        if let Some(canister) = UserCanisterStorage::get_user_canister(caller) {
-            return UserCanisterResponse::Ok(canister);
-        }
+        // set user canister
+        UserCanisterStorage::set_user_canister(user, user_canister);
 
-        // otherwise check if it failed or it is pending
-        UserCanisterStorage::get_create_state(caller)
-            .map(|state| match state {
-                UserCanisterCreateState::Failed { reason } => {
-                    UserCanisterResponse::CreationFailed { reason }
-                }
-                _ => UserCanisterResponse::CreationPending,
-            })
-            .unwrap_or(UserCanisterResponse::Uninitialized)
-    }
+        let alice = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
 
-    /// Get [`WhoamiResponse`] for the current caller.
-    ///
-    /// # Returns
-    ///
-    /// - [`WhoamiResponse::UnknownUser`] if the caller is anonymous or doesn't exist.
-    /// - [`WhoamiResponse::KnownUser`] if the caller exists.
-    pub fn whoami() -> WhoamiResponse {
-        debug!("Getting whoami for caller: {}", msg_caller());
-        let caller = msg_caller();
-        if caller == Principal::anonymous() {
-            return WhoamiResponse::UnknownUser;
-        }
+        // share file
+        let file_id = 1;
+        let response = Canister::share_file(
+            alice,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
+        assert_eq!(response, ShareFileResponse::NoSuchUser(alice));
 
-        UserStorage::get_user(&caller)
-            .map(|user| PublicUser::new(user, caller))
-            .map(WhoamiResponse::from)
-            .unwrap_or(WhoamiResponse::UnknownUser)
+        // check if the file is NOT shared
+        let shared_files = SharedFilesStorage::get_shared_files(alice);
+        assert_eq!(shared_files.len(), 0);
     }
-}
-
-#[cfg(test)]
-mod test {
 
-    use std::collections::HashMap;
+    #[test]
+    fn test_should_accept_share() {
+        init_canister();
 
-    use did::orchestrator::{OrchestratorInitArgs, User};
+        let user = msg_caller();
+        let user_canister = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        let file_id = 1;
 
-    use super::*;
+        // not shared yet
+        assert_eq!(
+            Canister::accept_share(user_canister, file_id),
+            ShareStateResponse::NoSuchShare
+        );
 
-    #[test]
-    fn test_should_init_canister() {
-        let orbit_station = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-        Canister::init(OrchestratorInstallArgs::Init(OrchestratorInitArgs {
-            orbit_station,
-            orbit_station_admin: "admin".to_string(),
-        }));
+        SharedFilesStorage::share_file(
+            user,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
+            },
+        );
 
-        assert_eq!(Config::get_orbit_station(), orbit_station);
+        assert_eq!(
+            Canister::accept_share(user_canister, file_id),
+            ShareStateResponse::Ok
+        );
+        assert_eq!(
+            SharedFilesStorage::get_share_state(user, user_canister, file_id),
+            Some(ShareState::Accepted)
+        );
     }
 
-    #[test]
-    fn test_should_get_user() {
+    #[tokio::test]
+    async fn test_should_decline_share() {
         init_canister();
 
-        // setup user
-        let principal = msg_caller();
-        UserStorage::add_user(
-            principal,
-            User {
-                username: "test_user".to_string(),
-                public_key: vec![1; 32].try_into().unwrap(),
+        let user = msg_caller();
+        let user_canister = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        let file_id = 1;
+
+        // not shared yet
+        assert_eq!(
+            Canister::decline_share(user_canister, file_id).await,
+            DeclineShareResponse::NoSuchShare
+        );
+
+        SharedFilesStorage::share_file(
+            user,
+            user_canister,
+            file_id,
+            ShareFileMetadata {
+                file_name: "foo.txt".to_string(),
             },
         );
 
-        // get user
-        let response = Canister::get_user(principal);
         assert_eq!(
-            response,
-            Some(PublicUser {
-                username: "test_user".to_string(),
-                public_key: vec![1; 32].try_into().unwrap(),
-                ic_principal: principal,
-            })
+            Canister::decline_share(user_canister, file_id).await,
+            DeclineShareResponse::Ok
+        );
+        assert_eq!(
+            SharedFilesStorage::get_share_state(user, user_canister, file_id),
+            Some(ShareState::Declined)
         );
+        assert!(SharedFilesStorage::shared_with(user_canister, file_id).is_empty());
+        let blocks = ShareLogStorage::get_blocks(0, 10);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].block.operation, ShareOperation::Decline);
+        assert_eq!(blocks[0].block.user, user);
 
-        // get user with a different principal
-        let principal = Principal::from_slice(&[1; 6]);
-        let response = Canister::get_user(principal);
-        assert!(response.is_none());
+        // a declined share can't be accepted nor hidden
+        assert_eq!(
+            Canister::accept_share(user_canister, file_id),
+            ShareStateResponse::NoSuchShare
+        );
+        assert_eq!(
+            Canister::hide_share(user_canister, file_id),
+            ShareStateResponse::NoSuchShare
+        );
     }
 
     #[test]
-    fn test_should_get_users() {
+    fn test_should_not_return_declined_or_hidden_shared_files() {
         init_canister();
 
         // setup user
@@ -470,619 +4024,978 @@ mod test {
             },
         );
 
-        // get users
-        let response = Canister::get_users(
-            Pagination {
-                offset: 0,
-                limit: 10,
-            },
-            None,
-        );
-        assert_eq!(
-            response,
-            GetUsersResponse::Users(GetUsersResponseUsers {
-                users: vec![PublicUser {
-                    username: "test_user".to_string(),
-                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
-                    ic_principal: principal,
-                }],
-                total: 1,
-                next: None,
-            })
-        );
-    }
-
-    #[test]
-    fn test_should_get_users_with_query() {
-        init_canister();
-
-        // setup users
-        for i in 0..150 {
-            UserStorage::add_user(
-                Principal::from_slice(&[i; 6]),
-                User {
-                    username: format!("test_user_{i}",),
-                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+        let user_canister = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        for file_id in 1..=3 {
+            SharedFilesStorage::share_file(
+                principal,
+                user_canister,
+                file_id,
+                ShareFileMetadata {
+                    file_name: "foo.txt".to_string(),
                 },
             );
         }
 
-        // get users
-        let response = Canister::get_users(
-            Pagination {
-                offset: 0,
-                limit: 20,
-            },
-            Some("test_user_12"),
+        assert_eq!(
+            Canister::hide_share(user_canister, 2),
+            ShareStateResponse::Ok
         );
+        assert!(SharedFilesStorage::decline_share(
+            principal,
+            user_canister,
+            3
+        ));
 
-        // there should be eleven users (12, 120, 121, ..., 129)
-        let GetUsersResponse::Users(GetUsersResponseUsers { users, total, next }) = response else {
-            panic!("Expected GetUsersResponse::Users");
+        let SharedFilesResponse::SharedFiles(shared_files) = Canister::shared_files() else {
+            panic!("expected shared files");
         };
+        let files = &shared_files[&user_canister];
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file_id, 1);
+        assert_eq!(files[0].share_state, ShareState::Pending);
+    }
 
-        assert_eq!(users.len(), 11);
-        assert_eq!(total, 11);
-        assert!(next.is_none());
+    #[test]
+    fn test_should_record_share_events_in_share_log() {
+        init_canister();
 
-        // with pagination
-        let response = Canister::get_users(
-            Pagination {
-                offset: 0,
-                limit: 5,
+        let user_canister = msg_caller();
+        let alice = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        UserCanisterStorage::set_user_canister(alice, user_canister);
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
             },
-            Some("test_user_12"),
         );
 
-        // there should be eleven users (12, 120, 121, ..., 129)
-        let GetUsersResponse::Users(GetUsersResponseUsers { users, total, next }) = response else {
-            panic!("Expected GetUsersResponse::Users");
-        };
+        let file_id = 1;
+        assert_eq!(
+            Canister::share_file(
+                alice,
+                file_id,
+                ShareFileMetadata {
+                    file_name: "foo.txt".to_string(),
+                },
+            ),
+            ShareFileResponse::Ok
+        );
+        // sharing again, as a retry would, is not recorded
+        assert_eq!(
+            Canister::share_file(
+                alice,
+                file_id,
+                ShareFileMetadata {
+                    file_name: "foo.txt".to_string(),
+                },
+            ),
+            ShareFileResponse::Ok
+        );
+        assert_eq!(
+            Canister::revoke_share_file(alice, file_id),
+            RevokeShareFileResponse::Ok
+        );
+        // revoking a file which is not shared is not recorded
+        assert_eq!(
+            Canister::revoke_share_file(alice, file_id),
+            RevokeShareFileResponse::Ok
+        );
 
-        assert_eq!(users.len(), 5);
-        assert_eq!(total, 11);
-        assert_eq!(next, Some(5));
+        let response = Canister::get_blocks(GetBlocksRequest {
+            start: 0,
+            length: 10,
+        });
+        assert_eq!(response.log_length, 2);
+        assert_eq!(response.blocks.len(), 2);
+
+        let share = &response.blocks[0].block;
+        assert_eq!(share.operation, ShareOperation::Share);
+        assert_eq!(share.user_canister, user_canister);
+        assert_eq!(share.file_id, file_id);
+        assert_eq!(share.user, alice);
+        assert!(share.parent_hash.is_none());
+
+        let revoke = &response.blocks[1].block;
+        assert_eq!(revoke.operation, ShareOperation::Revoke);
+        assert_eq!(
+            revoke.parent_hash.as_ref().map(|hash| hash.as_slice()),
+            Some(share.hash().as_slice())
+        );
+        assert_eq!(ShareLogStorage::tip(), Some((1, revoke.hash())));
+
+        // no certificate outside of a query call
+        assert!(Canister::get_tip_certificate().is_none());
     }
 
     #[test]
-    fn test_should_not_get_users_with_invalid_query() {
+    fn test_should_get_capped_blocks() {
         init_canister();
 
-        // get users
-        let response = Canister::get_users(
-            Pagination {
-                offset: 0,
-                limit: 20,
-            },
-            Some("aa"),
-        );
-        assert_eq!(response, GetUsersResponse::InvalidQuery);
+        let user_canister = Principal::from_slice(&[2; 29]);
+        let user = Principal::from_slice(&[3; 29]);
+        for file_id in 0..(MAX_GET_BLOCKS_LIMIT + 2) {
+            ShareLog::record(ShareOperation::Share, user_canister, file_id, user);
+        }
+
+        let response = Canister::get_blocks(GetBlocksRequest {
+            start: 1,
+            length: MAX_GET_BLOCKS_LIMIT + 2,
+        });
+        assert_eq!(response.log_length, MAX_GET_BLOCKS_LIMIT + 2);
+        assert_eq!(response.blocks.len(), MAX_GET_BLOCKS_LIMIT as usize);
+        assert_eq!(response.blocks[0].id, 1);
     }
 
     #[test]
-    fn test_should_get_paginated_users() {
+    fn test_should_delete_account() {
         init_canister();
+        let request = DeleteAccountRequest { archive: false };
+        assert_eq!(
+            Canister::delete_account(request.clone()),
+            DeleteAccountResponse::NoSuchUser
+        );
 
-        // setup users
-        for i in 0..9 {
-            UserStorage::add_user(
-                Principal::from_slice(&[i; 6]),
-                User {
-                    username: format!("test_user_{i}",),
-                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
-                },
-            );
-        }
-
-        // get users
-        let response = Canister::get_users(
-            Pagination {
-                offset: 0,
-                limit: 5,
+        let caller = msg_caller();
+        UserStorage::add_user(
+            caller,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
             },
-            None,
+        );
+        UserCanisterStorage::init_create_state(caller);
+        assert_eq!(
+            Canister::delete_account(request.clone()),
+            DeleteAccountResponse::UserCanisterCreationPending
         );
 
-        let GetUsersResponse::Users(GetUsersResponseUsers { total, users, next }) = response else {
-            panic!("Expected GetUsersResponse::Users");
-        };
-        assert_eq!(users.len(), 5);
-        assert_eq!(total, 9);
-        assert_eq!(next, Some(5));
+        UserCanisterStorage::set_user_canister(caller, Principal::from_slice(&[2; 29]));
+        assert_eq!(
+            Canister::delete_account(request.clone()),
+            DeleteAccountResponse::Ok
+        );
+        assert_eq!(
+            Canister::delete_account(request.clone()),
+            DeleteAccountResponse::DeletionPending
+        );
+
+        // a failed deletion can be retried
+        AccountDeletionStorage::set_delete_state(
+            caller,
+            crate::storage::account_deletion::AccountDeleteState::Failed {
+                reason: "failed".to_string(),
+            },
+        );
+        assert_eq!(Canister::delete_account(request), DeleteAccountResponse::Ok);
+    }
 
-        let response = Canister::get_users(
-            Pagination {
-                offset: 5,
-                limit: 8,
+    #[test]
+    fn test_should_not_share_with_user_being_deleted() {
+        init_canister();
+        // the caller is a user canister
+        UserCanisterStorage::set_user_canister(Principal::from_slice(&[3; 29]), msg_caller());
+        let bob = Principal::from_slice(&[2; 29]);
+        UserStorage::add_user(
+            bob,
+            User {
+                username: "bob".to_string(),
+                public_key: PublicKey::default(),
             },
-            None,
         );
+        AccountDeletionStorage::init_delete_state(bob, false);
 
-        let GetUsersResponse::Users(GetUsersResponseUsers { total, users, next }) = response else {
-            panic!("Expected GetUsersResponse::Users");
-        };
-        assert_eq!(total, 9);
-        assert_eq!(users.len(), 4);
-        assert!(next.is_none());
+        assert_eq!(
+            Canister::share_file(
+                bob,
+                1,
+                ShareFileMetadata {
+                    file_name: "file.txt".to_string(),
+                },
+            ),
+            ShareFileResponse::NoSuchUser(bob)
+        );
     }
 
     #[test]
-    fn test_should_get_capped_paginated_users() {
+    fn test_should_add_and_remove_contacts() {
         init_canister();
 
-        // setup users
-        for i in 0..150 {
+        let bob = Principal::from_slice(&[2; 29]);
+        let request = AddContactRequest {
+            user: bob,
+            nickname: Some("Bobby".to_string()),
+        };
+        assert_eq!(
+            Canister::add_contact(request.clone()),
+            AddContactResponse::NoSuchUser
+        );
+
+        for (principal, username) in [(msg_caller(), "alice"), (bob, "bob")] {
             UserStorage::add_user(
-                Principal::from_slice(&[i; 6]),
+                principal,
                 User {
-                    username: format!("test_user_{i}",),
-                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                    username: username.to_string(),
+                    public_key: PublicKey::default(),
                 },
             );
         }
-
-        // get users
-        let response = Canister::get_users(
-            Pagination {
-                offset: 0,
-                limit: 150,
-            },
-            None,
+        assert_eq!(
+            Canister::add_contact(AddContactRequest {
+                user: msg_caller(),
+                nickname: None,
+            }),
+            AddContactResponse::CannotAddSelf
+        );
+        assert_eq!(
+            Canister::add_contact(AddContactRequest {
+                user: Principal::from_slice(&[3; 29]),
+                nickname: None,
+            }),
+            AddContactResponse::NoSuchContact
+        );
+        assert_eq!(
+            Canister::add_contact(AddContactRequest {
+                user: bob,
+                nickname: Some("a".repeat(MAX_CONTACT_NICKNAME_SIZE + 1)),
+            }),
+            AddContactResponse::NicknameTooLong
         );
+        assert_eq!(Canister::add_contact(request), AddContactResponse::Ok);
 
-        let GetUsersResponse::Users(GetUsersResponseUsers { total, users, next }) = response else {
-            panic!("Expected GetUsersResponse::Users");
+        let ContactsResponse::Contacts(contacts) = Canister::contacts(Pagination {
+            offset: 0,
+            limit: 10,
+        }) else {
+            panic!("expected contacts");
         };
-        assert_eq!(users.len() as u64, MAX_GET_USERS_LIMIT);
-        assert_eq!(total, 150);
-        assert_eq!(next, Some(MAX_GET_USERS_LIMIT));
+        assert_eq!(contacts.total, 1);
+        assert_eq!(contacts.next, None);
+        assert_eq!(contacts.contacts[0].user.ic_principal, bob);
+        assert_eq!(contacts.contacts[0].nickname.as_deref(), Some("Bobby"));
+        assert_eq!(contacts.contacts[0].last_shared_at, None);
+
+        assert_eq!(Canister::remove_contact(bob), RemoveContactResponse::Ok);
+        assert_eq!(
+            Canister::remove_contact(bob),
+            RemoveContactResponse::NoSuchContact
+        );
     }
 
     #[test]
-    fn test_should_retry_user_canister_creation() {
+    fn test_should_add_contacts_when_sharing() {
         init_canister();
-
-        // let's setup a user
-        let principal = msg_caller();
+        // the caller is the user canister of alice
+        let alice = Principal::from_slice(&[3; 29]);
+        UserCanisterStorage::set_user_canister(alice, msg_caller());
+        let bob = Principal::from_slice(&[2; 29]);
         UserStorage::add_user(
-            principal,
+            bob,
             User {
-                username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                username: "bob".to_string(),
+                public_key: PublicKey::default(),
             },
         );
 
-        // of course this won't start the state machine on test unit; let's set the state to failed
-        UserCanisterStorage::set_create_state(
-            principal,
-            UserCanisterCreateState::Failed {
-                reason: "test".to_string(),
-            },
+        assert_eq!(
+            Canister::share_file(
+                bob,
+                1,
+                ShareFileMetadata {
+                    file_name: "file.txt".to_string(),
+                },
+            ),
+            ShareFileResponse::Ok
         );
 
-        // we can retry now :D
-        let response = Canister::retry_user_canister_creation();
-        assert_eq!(response, RetryUserCanisterCreationResponse::Ok);
+        let contacts = ContactsStorage::get_contacts(alice);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].0, bob);
+        assert!(contacts[0].1.last_shared_at.is_some());
     }
 
     #[test]
-    fn test_should_not_retry_user_canister_creation_if_user_does_not_exist() {
+    fn test_should_migrate_stable_memory_on_upgrade() {
         init_canister();
+        assert_eq!(Config::get_schema_version(), SCHEMA_VERSION);
 
-        // let's setup another user
+        // the usernames are migrated from a canister which was never migrated
+        Config::set_schema_version(0);
         UserStorage::add_user(
-            Principal::management_canister(),
+            msg_caller(),
             User {
-                username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                username: "Alice".to_string(),
+                public_key: PublicKey::default(),
             },
         );
-
-        // user does not exist
-        let response = Canister::retry_user_canister_creation();
-        assert_eq!(response, RetryUserCanisterCreationResponse::UserNotFound);
+        Canister::post_upgrade(OrchestratorInstallArgs::Upgrade(OrchestratorUpgradeArgs {
+            admins: None,
+        }));
+        assert_eq!(Config::get_schema_version(), SCHEMA_VERSION);
+        assert!(UserStorage::username_exists("alice"));
     }
 
     #[test]
-    fn test_should_not_retry_if_user_canister_exists() {
+    fn test_should_import_contacts_from_shares() {
         init_canister();
-
-        // let's setup a user
-        let principal = msg_caller();
-        UserStorage::add_user(
-            principal,
-            User {
-                username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+        let alice = Principal::from_slice(&[3; 29]);
+        let alice_canister = Principal::from_slice(&[13; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        UserCanisterStorage::set_user_canister(alice, alice_canister);
+        SharedFilesStorage::share_file(
+            bob,
+            alice_canister,
+            1,
+            ShareFileMetadata {
+                file_name: "file.txt".to_string(),
             },
         );
 
-        // let's set the user canister
-        let user_canister = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-        UserCanisterStorage::set_user_canister(principal, user_canister);
-
-        // canister already exists
-        let response = Canister::retry_user_canister_creation();
+        // the contacts are imported from a canister which has no contacts yet
+        Config::set_schema_version(CONTACTS_SCHEMA_VERSION - 1);
+        Canister::post_upgrade(OrchestratorInstallArgs::Upgrade(OrchestratorUpgradeArgs {
+            admins: None,
+        }));
+        assert_eq!(Config::get_schema_version(), SCHEMA_VERSION);
         assert_eq!(
-            response,
-            RetryUserCanisterCreationResponse::Created(user_canister)
+            ContactsStorage::get_contacts(alice),
+            vec![(bob, ContactEntry::default())]
         );
+
+        // a removed contact is not imported again on the next upgrade
+        assert!(ContactsStorage::remove_contact(alice, bob));
+        Canister::post_upgrade(OrchestratorInstallArgs::Upgrade(OrchestratorUpgradeArgs {
+            admins: None,
+        }));
+        assert!(ContactsStorage::get_contacts(alice).is_empty());
     }
 
     #[test]
-    fn test_should_not_retry_if_pending() {
+    fn test_should_block_and_unblock() {
         init_canister();
 
-        // let's setup a user
-        let principal = msg_caller();
+        let bob = Principal::from_slice(&[2; 29]);
+        let bob_canister = Principal::from_slice(&[12; 29]);
+        assert_eq!(Canister::block(bob), BlockResponse::NoSuchUser);
+
+        for (principal, username) in [(msg_caller(), "alice"), (bob, "bob")] {
+            UserStorage::add_user(
+                principal,
+                User {
+                    username: username.to_string(),
+                    public_key: PublicKey::default(),
+                },
+            );
+        }
+        UserCanisterStorage::set_user_canister(bob, bob_canister);
+        assert_eq!(
+            Canister::block(msg_caller()),
+            BlockResponse::CannotBlockSelf
+        );
+        assert_eq!(
+            Canister::block(Principal::from_slice(&[3; 29])),
+            BlockResponse::UnknownPrincipal
+        );
+        assert_eq!(Canister::block(bob), BlockResponse::Ok);
+        assert_eq!(Canister::block(bob_canister), BlockResponse::Ok);
+        assert_eq!(
+            Canister::get_blocked(),
+            GetBlockedResponse::Blocked(vec![bob, bob_canister])
+        );
+
+        assert_eq!(Canister::unblock(bob), UnblockResponse::Ok);
+        assert_eq!(Canister::unblock(bob), UnblockResponse::NotBlocked);
+        assert_eq!(
+            Canister::get_blocked(),
+            GetBlockedResponse::Blocked(vec![bob_canister])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_add_and_remove_device_keys() {
+        init_canister();
+
+        let alice = msg_caller();
+        let public_key = PublicKey::try_from(vec![1; 32]).unwrap();
+        let request = |name: &str| AddDeviceKeyRequest {
+            name: name.to_string(),
+            public_key,
+        };
+        assert_eq!(
+            Canister::add_device_key(request("Laptop")).await,
+            AddDeviceKeyResponse::NoSuchUser
+        );
         UserStorage::add_user(
-            principal,
+            alice,
             User {
-                username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
             },
         );
+        assert_eq!(
+            Canister::add_device_key(request("Laptop")).await,
+            AddDeviceKeyResponse::UserCanisterNotReady
+        );
+        UserCanisterStorage::set_user_canister(alice, Principal::from_slice(&[3; 29]));
+        assert_eq!(
+            Canister::add_device_key(request(&"a".repeat(MAX_DEVICE_NAME_SIZE + 1))).await,
+            AddDeviceKeyResponse::NameTooLong
+        );
 
-        // let's set the user canister creation state to something pending
-        UserCanisterStorage::set_create_state(principal, UserCanisterCreateState::CreateCanister);
+        let AddDeviceKeyResponse::Ok(laptop) = Canister::add_device_key(request("Laptop")).await
+        else {
+            panic!("failed to add device key");
+        };
+        let AddDeviceKeyResponse::Ok(phone) = Canister::add_device_key(request("Phone")).await
+        else {
+            panic!("failed to add device key");
+        };
+        assert_ne!(laptop, phone);
+        assert_eq!(
+            Canister::get_device_keys(alice),
+            vec![
+                DeviceKey {
+                    device_id: laptop,
+                    name: "Laptop".to_string(),
+                    public_key,
+                },
+                DeviceKey {
+                    device_id: phone,
+                    name: "Phone".to_string(),
+                    public_key,
+                },
+            ]
+        );
 
-        // canister already exists
-        let response = Canister::retry_user_canister_creation();
-        assert_eq!(response, RetryUserCanisterCreationResponse::CreationPending);
+        assert_eq!(
+            Canister::remove_device_key(laptop).await,
+            RemoveDeviceKeyResponse::Ok
+        );
+        assert_eq!(
+            Canister::remove_device_key(laptop).await,
+            RemoveDeviceKeyResponse::NoSuchDevice
+        );
+        assert_eq!(Canister::get_device_keys(alice).len(), 1);
+
+        for name in 1..MAX_DEVICES_PER_USER {
+            Canister::add_device_key(request(&name.to_string())).await;
+        }
+        assert_eq!(
+            Canister::add_device_key(request("Tablet")).await,
+            AddDeviceKeyResponse::TooManyDevices
+        );
     }
 
-    #[test]
-    fn test_should_register_user_if_valid() {
+    #[tokio::test]
+    async fn test_should_link_principals() {
         init_canister();
 
-        // setup user
-        let principal = msg_caller();
-        let username = "test_user".to_string();
-        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+        let alice = msg_caller();
+        let alice_phone = Principal::from_slice(&[11; 29]);
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        assert_eq!(
+            Canister::approve_principal_link(alice_phone).await,
+            ApprovePrincipalLinkResponse::NoSuchLinkRequest
+        );
 
-        // register user
-        let response = Canister::set_user(username.clone(), public_key);
-        assert_eq!(response, SetUserResponse::Ok);
+        // expired requests can't be approved
+        LinkedPrincipalsStorage::request_link(alice, alice_phone, 0);
+        assert_eq!(
+            Canister::approve_principal_link(alice_phone).await,
+            ApprovePrincipalLinkResponse::NoSuchLinkRequest
+        );
 
-        // check if user exists
-        let user = UserStorage::get_user(&principal).unwrap();
-        assert_eq!(user.username, username);
-        assert_eq!(user.public_key, public_key);
+        LinkedPrincipalsStorage::request_link(alice, alice_phone, time());
+        assert_eq!(
+            Canister::approve_principal_link(alice_phone).await,
+            ApprovePrincipalLinkResponse::UserCanisterNotReady
+        );
+        UserCanisterStorage::set_user_canister(alice, Principal::from_slice(&[3; 29]));
+        assert_eq!(
+            Canister::linked_principals(),
+            LinkedPrincipalsResponse::LinkedPrincipals(LinkedPrincipals {
+                account: alice,
+                linked: vec![],
+                requests: vec![alice_phone],
+            })
+        );
+        assert_eq!(
+            Canister::approve_principal_link(alice_phone).await,
+            ApprovePrincipalLinkResponse::Ok
+        );
+        assert_eq!(
+            Canister::linked_principals(),
+            LinkedPrincipalsResponse::LinkedPrincipals(LinkedPrincipals {
+                account: alice,
+                linked: vec![alice_phone],
+                requests: vec![],
+            })
+        );
+
+        assert_eq!(
+            Canister::unlink_principal(alice).await,
+            UnlinkPrincipalResponse::NotLinked
+        );
+        assert_eq!(
+            Canister::unlink_principal(alice_phone).await,
+            UnlinkPrincipalResponse::Ok
+        );
+        assert_eq!(LinkedPrincipalsStorage::get_account(alice_phone), None);
     }
 
-    #[test]
-    fn test_should_not_add_user_if_username_too_long() {
+    #[tokio::test]
+    async fn test_should_only_unlink_itself_from_linked_principal() {
         init_canister();
 
-        // setup user
-        let principal = msg_caller();
-        let username = "a".repeat(MAX_USERNAME_SIZE + 1);
-        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
-
-        // register user
-        let response = Canister::set_user(username.clone(), public_key);
-        assert_eq!(response, SetUserResponse::UsernameTooLong);
+        let alice = Principal::from_slice(&[1; 28]);
+        let alice_laptop = Principal::from_slice(&[21; 29]);
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        // the caller is a device of alice
+        LinkedPrincipalsStorage::link(alice, msg_caller());
+        LinkedPrincipalsStorage::link(alice, alice_laptop);
 
-        // check if user does not exist
-        let user = UserStorage::get_user(&principal);
-        assert!(user.is_none());
+        assert_eq!(
+            Canister::unlink_principal(alice_laptop).await,
+            UnlinkPrincipalResponse::Unauthorized
+        );
+        assert_eq!(
+            LinkedPrincipalsStorage::get_account(alice_laptop),
+            Some(alice)
+        );
+        assert_eq!(
+            Canister::unlink_principal(msg_caller()).await,
+            UnlinkPrincipalResponse::Ok
+        );
+        assert_eq!(LinkedPrincipalsStorage::get_account(msg_caller()), None);
     }
 
     #[test]
-    fn test_should_not_add_user_if_caller_has_already_a_user() {
+    fn test_should_bound_link_requests() {
         init_canister();
 
-        // setup user
-        let username = "test_user".to_string();
-        let public_key = PublicKey::try_from(vec![1; 32]).expect("invalid public key");
+        let bob = Principal::from_slice(&[2; 29]);
+        UserStorage::add_user(
+            bob,
+            User {
+                username: "bob".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        let device = |i: usize| Principal::from_slice(&[10 + i as u8; 29]);
+        for i in 0..MAX_LINK_REQUESTS_PER_ACCOUNT {
+            LinkedPrincipalsStorage::request_link(bob, device(i), time());
+        }
+        assert_eq!(
+            Canister::request_principal_link(bob),
+            RequestPrincipalLinkResponse::TooManyLinkRequests
+        );
 
-        // register user
-        let response = Canister::set_user(username.clone(), public_key);
-        assert_eq!(response, SetUserResponse::Ok);
+        // expired requests are removed on the next request
+        LinkedPrincipalsStorage::request_link(bob, device(0), 0);
+        assert_eq!(
+            Canister::request_principal_link(bob),
+            RequestPrincipalLinkResponse::Ok
+        );
+        assert_eq!(
+            LinkedPrincipalsStorage::get_link_request(bob, device(0)),
+            None
+        );
+        assert_eq!(
+            LinkedPrincipalsStorage::get_link_requests(bob).len(),
+            MAX_LINK_REQUESTS_PER_ACCOUNT
+        );
 
-        // try another username
-        let response = Canister::set_user("foo".to_string(), public_key);
-        assert_eq!(response, SetUserResponse::CallerHasAlreadyAUser);
+        // pending requests can still be renewed
+        assert_eq!(
+            Canister::request_principal_link(bob),
+            RequestPrincipalLinkResponse::Ok
+        );
     }
 
     #[test]
-    fn test_should_tell_if_username_exists() {
+    fn test_should_act_as_linked_account() {
         init_canister();
 
-        // setup user
-        let principal = msg_caller();
+        let bob = Principal::from_slice(&[2; 29]);
+        assert_eq!(
+            Canister::request_principal_link(bob),
+            RequestPrincipalLinkResponse::NoSuchAccount
+        );
         UserStorage::add_user(
-            principal,
+            bob,
             User {
-                username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                username: "bob".to_string(),
+                public_key: PublicKey::default(),
             },
         );
+        assert_eq!(
+            Canister::request_principal_link(bob),
+            RequestPrincipalLinkResponse::Ok
+        );
+        assert!(LinkedPrincipalsStorage::get_link_request(bob, msg_caller()).is_some());
 
-        // check if username exists
-        let exists = Canister::username_exists("test_user".to_string());
-        assert!(exists);
-
-        // check if non-existing username exists
-        let exists = Canister::username_exists("non_existing_user".to_string());
-        assert!(!exists);
+        // the caller is a device of bob
+        LinkedPrincipalsStorage::link(bob, msg_caller());
+        assert_eq!(
+            Canister::request_principal_link(bob),
+            RequestPrincipalLinkResponse::CallerHasAlreadyAUser
+        );
+        assert_eq!(
+            Canister::set_user("alice".to_string(), PublicKey::default(), None),
+            SetUserResponse::CallerHasAlreadyAUser
+        );
+        let WhoamiResponse::KnownUser(user) = Canister::whoami() else {
+            panic!("the caller should be known");
+        };
+        assert_eq!(user.ic_principal, bob);
+        assert_eq!(user.username, "bob");
     }
 
     #[test]
-    fn test_should_tell_whoami() {
+    fn test_should_not_share_with_user_who_blocked_sender() {
         init_canister();
-
-        // setup user
-        let principal = msg_caller();
+        // the caller is the user canister of alice
+        let alice = Principal::from_slice(&[3; 29]);
+        UserCanisterStorage::set_user_canister(alice, msg_caller());
+        let bob = Principal::from_slice(&[2; 29]);
         UserStorage::add_user(
-            principal,
+            bob,
             User {
-                username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                username: "bob".to_string(),
+                public_key: PublicKey::default(),
             },
         );
+        let metadata = ShareFileMetadata {
+            file_name: "file.txt".to_string(),
+        };
+
+        // blocking the owner blocks their user canister
+        BlockListStorage::block(bob, alice);
+        assert_eq!(
+            Canister::share_file(bob, 1, metadata.clone()),
+            ShareFileResponse::Blocked(bob)
+        );
+        BlockListStorage::unblock(bob, alice);
 
-        // get whoami
-        let whoami = Canister::whoami();
+        BlockListStorage::block(bob, msg_caller());
         assert_eq!(
-            whoami,
-            WhoamiResponse::KnownUser(PublicUser {
-                username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
-                ic_principal: principal,
-            })
+            Canister::share_file(bob, 1, metadata),
+            ShareFileResponse::Blocked(bob)
         );
+        assert!(SharedFilesStorage::get_shared_files(bob).is_empty());
     }
 
     #[test]
-    fn test_should_return_shared_files() {
+    fn test_should_hide_shares_of_blocked_senders() {
         init_canister();
 
-        // setup user
         let principal = msg_caller();
         UserStorage::add_user(
             principal,
             User {
                 username: "test_user".to_string(),
-                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                public_key: PublicKey::default(),
             },
         );
-
-        // insert shared files
-        let file_id = 1;
-        let user_canister = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-
+        let bob = Principal::from_slice(&[2; 29]);
+        let bob_canister = Principal::from_slice(&[12; 29]);
+        UserStorage::add_user(
+            bob,
+            User {
+                username: "bob".to_string(),
+                public_key: PublicKey::default(),
+            },
+        );
+        UserCanisterStorage::set_user_canister(bob, bob_canister);
         SharedFilesStorage::share_file(
             principal,
-            user_canister,
-            file_id,
+            bob_canister,
+            1,
             ShareFileMetadata {
                 file_name: "foo.txt".to_string(),
             },
         );
 
-        let public_user = PublicUser {
-            username: "test_user".to_string(),
-            public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
-            ic_principal: principal,
-        };
-
-        let mut expected = HashMap::new();
-        expected.insert(
-            user_canister,
-            vec![PublicFileMetadata {
-                file_id,
-                file_name: "foo.txt".to_string(),
-                shared_with: vec![public_user],
-            }]
-            .into_iter()
-            .collect(),
+        assert_eq!(Canister::block(bob), BlockResponse::Ok);
+        assert_eq!(
+            Canister::shared_files(),
+            SharedFilesResponse::SharedFiles(HashMap::new())
         );
 
-        // get shared files
-        let shared_files = Canister::shared_files();
-        assert_eq!(shared_files, SharedFilesResponse::SharedFiles(expected));
+        // unblocking shows the shares again
+        assert_eq!(Canister::unblock(bob), UnblockResponse::Ok);
+        let SharedFilesResponse::SharedFiles(shared_files) = Canister::shared_files() else {
+            panic!("expected shared files");
+        };
+        assert_eq!(shared_files[&bob_canister].len(), 1);
     }
 
     #[test]
-    fn test_should_return_error_on_shared_files_unexisting_user() {
+    fn test_should_update_user_public_key() {
         init_canister();
 
-        // get shared files
-        let shared_files = Canister::shared_files();
-        assert_eq!(shared_files, SharedFilesResponse::NoSuchUser);
-    }
+        let user = Principal::from_slice(&[2; 29]);
+        let public_key = PublicKey::try_from(vec![2; 32]).expect("invalid public key");
 
-    #[test]
-    fn test_should_revoke_shared_file() {
-        init_canister();
+        // the caller is not the user canister of the user
+        assert_eq!(
+            Canister::update_user_public_key(user, public_key),
+            UpdateUserPublicKeyResponse::Unauthorized
+        );
 
-        // insert user canister
-        let user_canister = msg_caller();
-        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-        UserCanisterStorage::set_user_canister(user, user_canister);
+        UserCanisterStorage::set_user_canister(user, msg_caller());
+        assert_eq!(
+            Canister::update_user_public_key(user, public_key),
+            UpdateUserPublicKeyResponse::NoSuchUser
+        );
 
-        // revoke share
-        let file_id = 1;
-        SharedFilesStorage::share_file(
+        UserStorage::add_user(
             user,
-            user_canister,
-            file_id,
-            ShareFileMetadata {
-                file_name: "foo.txt".to_string(),
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
             },
         );
-        let response = Canister::revoke_share_file(user, file_id);
-        assert_eq!(response, RevokeShareFileResponse::Ok);
-
-        // check if the file is revoked
-        let shared_files = SharedFilesStorage::get_shared_files(user);
-        assert_eq!(shared_files.len(), 0);
+        assert_eq!(
+            Canister::update_user_public_key(user, public_key),
+            UpdateUserPublicKeyResponse::Ok(1)
+        );
+        assert_eq!(UserStorage::get_user(&user).unwrap().public_key, public_key);
+        assert_eq!(Canister::get_user(user).unwrap().key_version, 1);
     }
 
-    #[test]
-    fn test_should_not_revoke_shared_file_if_caller_is_not_a_user_canister() {
+    #[tokio::test]
+    async fn test_should_update_public_key() {
         init_canister();
 
-        // insert user canister
-        let user_canister = msg_caller();
-        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        let public_key = PublicKey::try_from(vec![2; 32]).expect("invalid public key");
+        assert_eq!(
+            Canister::update_public_key(public_key, 0).await,
+            UpdatePublicKeyResponse::NoSuchUser
+        );
 
-        // revoke share
-        let file_id = 1;
-        SharedFilesStorage::share_file(
-            user,
-            user_canister,
-            file_id,
-            ShareFileMetadata {
-                file_name: "foo.txt".to_string(),
+        let principal = msg_caller();
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
             },
         );
-        let response = Canister::revoke_share_file(user, file_id);
-        assert_eq!(response, RevokeShareFileResponse::Unauthorized);
+        assert_eq!(
+            Canister::update_public_key(public_key, 0).await,
+            UpdatePublicKeyResponse::Ok(1)
+        );
+        assert_eq!(
+            UserStorage::get_user(&principal).unwrap().public_key,
+            public_key
+        );
 
-        // check if the file is NOT revoked
-        let shared_files = SharedFilesStorage::get_shared_files(user);
-        assert_eq!(shared_files.len(), 1);
+        // updates from an older version are rejected
+        let new_public_key = PublicKey::try_from(vec![3; 32]).expect("invalid public key");
+        assert_eq!(
+            Canister::update_public_key(new_public_key, 0).await,
+            UpdatePublicKeyResponse::StaleVersion(1)
+        );
+        assert_eq!(
+            UserStorage::get_user(&principal).unwrap().public_key,
+            public_key
+        );
+
+        assert_eq!(
+            Canister::update_public_key(new_public_key, 1).await,
+            UpdatePublicKeyResponse::Ok(2)
+        );
+        let WhoamiResponse::KnownUser(user) = Canister::whoami() else {
+            panic!("expected known user");
+        };
+        assert_eq!(user.key_version, 2);
     }
 
     #[test]
-    fn test_should_revoke_shared_file_with_users() {
+    fn test_should_get_user_canister() {
         init_canister();
+        let alice = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        let alice_canister = Principal::from_slice(&[2; 29]);
 
-        // insert user canister
-        let user_canister = msg_caller();
-        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-        let user_2 = Principal::from_slice(&[2; 6]);
-        let user_3 = Principal::from_slice(&[3; 6]);
-        UserCanisterStorage::set_user_canister(user, user_canister);
-        UserCanisterStorage::set_user_canister(user_2, user_canister);
-        UserCanisterStorage::set_user_canister(user_3, user_canister);
-
-        // revoke share
-        let file_id = 1;
-        SharedFilesStorage::share_file(
-            user,
-            user_canister,
-            file_id,
-            ShareFileMetadata {
-                file_name: "foo.txt".to_string(),
-            },
-        );
-        SharedFilesStorage::share_file(
-            user_2,
-            user_canister,
-            file_id,
-            ShareFileMetadata {
-                file_name: "foo.txt".to_string(),
-            },
+        assert_eq!(
+            Canister::get_user_canister(alice),
+            GetUserCanisterResponse::Unauthorized
         );
-        SharedFilesStorage::share_file(
-            user_3,
-            user_canister,
-            file_id,
-            ShareFileMetadata {
-                file_name: "foo.txt".to_string(),
-            },
+
+        UserCanisterStorage::set_user_canister(Principal::from_slice(&[3; 29]), msg_caller());
+        assert_eq!(
+            Canister::get_user_canister(alice),
+            GetUserCanisterResponse::NoSuchUser
         );
-        let response = Canister::revoke_share_file_for_users(vec![user, user_2], file_id);
-        assert_eq!(response, RevokeShareFileResponse::Ok);
 
-        // check if the file is revoked
-        let shared_files = SharedFilesStorage::get_shared_files(user);
-        assert_eq!(shared_files.len(), 0);
-        let shared_files = SharedFilesStorage::get_shared_files(user_2);
-        assert_eq!(shared_files.len(), 0);
-        let shared_files = SharedFilesStorage::get_shared_files(user_3);
-        assert_eq!(shared_files.len(), 1);
+        UserCanisterStorage::set_user_canister(alice, alice_canister);
+        assert_eq!(
+            Canister::get_user_canister(alice),
+            GetUserCanisterResponse::Ok(alice_canister)
+        );
     }
 
     #[test]
-    fn test_should_share_file() {
+    fn test_should_get_user_canister_shares() {
         init_canister();
 
-        // insert user canister
-        let user_canister = msg_caller();
-        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-
-        // set user canister
-        UserCanisterStorage::set_user_canister(user, user_canister);
+        let pagination = FileCursorPagination {
+            cursor: None,
+            limit: 10,
+        };
+        assert_eq!(
+            Canister::get_user_canister_shares(pagination.clone()),
+            UserCanisterSharesResponse::Unauthorized
+        );
 
-        // create user
+        let user_canister = msg_caller();
         let alice = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
+        UserCanisterStorage::set_user_canister(alice, user_canister);
         UserStorage::add_user(
             alice,
             User {
-                username: "test_user".to_string(),
+                username: "alice".to_string(),
                 public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
             },
         );
-
-        // share file
-        let file_id = 1;
-        let response = Canister::share_file(
-            alice,
-            file_id,
-            ShareFileMetadata {
-                file_name: "foo.txt".to_string(),
-            },
+        assert_eq!(
+            Canister::share_file(
+                alice,
+                1,
+                ShareFileMetadata {
+                    file_name: "foo.txt".to_string(),
+                },
+            ),
+            ShareFileResponse::Ok
         );
-        assert_eq!(response, ShareFileResponse::Ok);
 
-        // check if the file is shared
-        let shared_files = SharedFilesStorage::get_shared_files(alice);
-        assert_eq!(shared_files.len(), 1);
+        assert_eq!(
+            Canister::get_user_canister_shares(pagination),
+            UserCanisterSharesResponse::Shares(UserCanisterShares {
+                files: vec![FileShares {
+                    file_id: 1,
+                    users: vec![alice],
+                }],
+                next: None,
+            })
+        );
     }
 
     #[test]
-    fn test_should_not_share_if_not_called_by_user_canister() {
+    fn test_should_get_files_shared_by_me() {
         init_canister();
 
-        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-
-        // share file
-        let file_id = 1;
-        let response = Canister::share_file(
-            user,
-            file_id,
-            ShareFileMetadata {
-                file_name: "foo.txt".to_string(),
-            },
+        // the caller has no user canister yet
+        let pagination = Pagination {
+            offset: 0,
+            limit: 10,
+        };
+        assert_eq!(
+            Canister::shared_by_me(pagination),
+            SharedByMeResponse::NoUserCanister
         );
-        assert_eq!(response, ShareFileResponse::Unauthorized);
 
-        // check if the file is NOT shared
-        let shared_files = SharedFilesStorage::get_shared_files(user);
-        assert_eq!(shared_files.len(), 0);
+        let user_canister = Principal::from_slice(&[2; 29]);
+        let alice = Principal::from_slice(&[3; 29]);
+        let bob = Principal::from_slice(&[4; 29]);
+        UserCanisterStorage::set_user_canister(msg_caller(), user_canister);
+        for (user, username) in [(alice, "alice"), (bob, "bob")] {
+            UserStorage::add_user(
+                user,
+                User {
+                    username: username.to_string(),
+                    public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+                },
+            );
+        }
+        for (user, file_id) in [(alice, 1), (bob, 1), (alice, 2)] {
+            SharedFilesStorage::share_file(
+                user,
+                user_canister,
+                file_id,
+                ShareFileMetadata {
+                    file_name: format!("{file_id}.txt"),
+                },
+            );
+        }
+
+        let SharedByMeResponse::Files(page) = Canister::shared_by_me(Pagination {
+            offset: 0,
+            limit: 1,
+        }) else {
+            panic!("expected files");
+        };
+        assert_eq!(page.total, 2);
+        assert_eq!(page.next, Some(1));
+        assert_eq!(page.files.len(), 1);
+        assert_eq!(page.files[0].file_id, 1);
+        assert_eq!(page.files[0].file_name, "1.txt");
+        // the users are ordered by principal
+        let shared_with = page.files[0]
+            .shared_with
+            .iter()
+            .map(|user| user.username.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(shared_with, vec!["alice", "bob"]);
+
+        assert_eq!(
+            Canister::shared_by_me(Pagination {
+                offset: 1,
+                limit: 1,
+            }),
+            SharedByMeResponse::Files(SharedByMeFiles {
+                files: vec![SharedByMeFile {
+                    file_id: 2,
+                    file_name: "2.txt".to_string(),
+                    shared_with: vec![PublicUser::new(
+                        UserStorage::get_user(&alice).unwrap(),
+                        alice
+                    )],
+                }],
+                next: None,
+                total: 2,
+            })
+        );
     }
 
     #[test]
-    fn test_should_not_share_if_user_does_not_exist() {
+    fn test_user_canister_should_get_files_shared_by_me() {
         init_canister();
 
-        // insert user canister
-        let user_canister = msg_caller();
-        let user = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-
-        // set user canister
-        UserCanisterStorage::set_user_canister(user, user_canister);
-
-        let alice = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
-
-        // share file
-        let file_id = 1;
-        let response = Canister::share_file(
+        let alice = Principal::from_slice(&[3; 29]);
+        UserCanisterStorage::set_user_canister(alice, msg_caller());
+        SharedFilesStorage::share_file(
             alice,
-            file_id,
+            msg_caller(),
+            1,
             ShareFileMetadata {
                 file_name: "foo.txt".to_string(),
             },
         );
-        assert_eq!(response, ShareFileResponse::NoSuchUser(alice));
 
-        // check if the file is NOT shared
-        let shared_files = SharedFilesStorage::get_shared_files(alice);
-        assert_eq!(shared_files.len(), 0);
+        let SharedByMeResponse::Files(page) = Canister::shared_by_me(Pagination {
+            offset: 0,
+            limit: 10,
+        }) else {
+            panic!("expected files");
+        };
+        assert_eq!(page.total, 1);
+        assert_eq!(page.files[0].file_id, 1);
+        // alice is not a registered user
+        assert!(page.files[0].shared_with.is_empty());
     }
 
     fn init_canister() {
@@ -1090,6 +5003,7 @@ mod test {
         Canister::init(OrchestratorInstallArgs::Init(OrchestratorInitArgs {
             orbit_station,
             orbit_station_admin: "admin".to_string(),
+            admins: vec![],
         }));
     }
 }
@@ -349,7 +349,10 @@ impl CreateUserStateMachine {
     /// Get the time difference between the current time and the scheduled time.
     ///
     /// If the scheduled time is in the past, return [`DEFAULT_INTERVAL`].
-    fn scheduled_at_time_diff(date: OffsetDateTime, scheduled_at: &TimestampRfc3339) -> Duration {
+    pub(super) fn scheduled_at_time_diff(
+        date: OffsetDateTime,
+        scheduled_at: &TimestampRfc3339,
+    ) -> Duration {
         debug!("Calculating time difference for scheduled_at: {scheduled_at} at date: {date}");
         let scheduled_at = match OffsetDateTime::parse(scheduled_at, &Rfc3339) {
             Ok(scheduled_at) => scheduled_at,
@@ -0,0 +1,391 @@
+use std::time::Duration;
+
+use candid::Principal;
+use did::orbit_station::RequestStatus;
+
+use super::Canister;
+use super::create_user::CreateUserStateMachine;
+use super::revocations::PendingRevocations;
+use crate::client::OrbitStationClient;
+use crate::debug;
+use crate::storage::account_deletion::{AccountDeleteState, AccountDeletionStorage};
+use crate::storage::block_list::BlockListStorage;
+use crate::storage::contacts::ContactsStorage;
+use crate::storage::device_keys::DeviceKeysStorage;
+use crate::storage::invites::InvitesStorage;
+use crate::storage::linked_principals::LinkedPrincipalsStorage;
+use crate::storage::moderation::ModerationStorage;
+use crate::storage::shared_files::SharedFilesStorage;
+use crate::storage::user_canister::UserCanisterStorage;
+use crate::storage::users::UserStorage;
+use crate::utils::{datetime, trap};
+
+/// Default interval between each operation.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+/// Interval to wait for the Orbit Station to process the request.
+const ORBIT_STATION_REQUEST_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A service to delete the account of a user, along with their user canister.
+#[derive(Debug, Clone, Copy)]
+pub struct DeleteAccountStateMachine {
+    orbit_station: Principal,
+    user: Principal,
+}
+
+impl DeleteAccountStateMachine {
+    /// Creates a new instance of [`DeleteAccountStateMachine`] and starts it.
+    ///
+    /// The deletion state must be initialized with
+    /// [`AccountDeletionStorage::init_delete_state`] first; the state machine runs from the
+    /// current state, so it can be started again after an upgrade.
+    pub fn start(orbit_station: Principal, user: Principal) {
+        let state_machine = Self {
+            orbit_station,
+            user,
+        };
+
+        debug!("Starting account deletion state machine for user: {user}");
+
+        state_machine.tick(Duration::from_secs(1));
+    }
+
+    /// Start again the state machines of the account deletions in progress.
+    ///
+    /// Timers are not kept across upgrades, so the deletions would be pending forever otherwise.
+    pub fn resume_all(orbit_station: Principal) {
+        for user in AccountDeletionStorage::pending_deletions() {
+            Self::start(orbit_station, user);
+        }
+    }
+
+    /// Set a timer to wait for the specified duration and then run the state machine.
+    fn tick(self, delay: Duration) {
+        debug!(
+            "Scheduling next step for account deletion for user: {} in {:?}",
+            self.user, delay
+        );
+        // run state machine
+        ic_cdk_timers::set_timer(delay, move || {
+            ic_cdk::futures::spawn(async move {
+                self.run().await;
+            });
+        });
+    }
+
+    /// Run a step of the state machine.
+    async fn run(self) {
+        // load state from storage
+        let current_state = AccountDeletionStorage::get_delete_state(self.user)
+            .unwrap_or_else(|| trap("Account deletion state not found"));
+        debug!(
+            "Running account deletion state machine for user: {}. Current state: {:?}",
+            self.user, current_state
+        );
+
+        let current_state_id = std::mem::discriminant(&current_state);
+
+        let new_state = match current_state {
+            AccountDeleteState::RevokeShares { archive } => self.revoke_shares(archive),
+            AccountDeleteState::DeleteCanister {
+                user_canister,
+                archive,
+            } => self.delete_canister(user_canister, archive).await,
+            AccountDeleteState::WaitForDeleteCanisterSchedule {
+                request_id,
+                user_canister,
+                ..
+            } => {
+                self.check_delete_canister_result(request_id, user_canister)
+                    .await
+            }
+            AccountDeleteState::WaitForDeleteCanisterResult {
+                request_id,
+                user_canister,
+            } => {
+                self.check_delete_canister_result(request_id, user_canister)
+                    .await
+            }
+            AccountDeleteState::Ok => {
+                self.complete();
+
+                return; // stop the state machine
+            }
+            AccountDeleteState::Failed { .. } => return, // stop the state machine
+        };
+
+        debug!("New state for account deletion: {:?}", new_state);
+
+        // update state in storage if the variant type has changed
+        if std::mem::discriminant(&new_state) != current_state_id {
+            AccountDeletionStorage::set_delete_state(self.user, new_state.clone());
+        }
+
+        // schedule next step
+        let delay = Self::delay(&new_state);
+        self.tick(delay);
+    }
+
+    /// Revokes every share of a file shared with the user, and of a file shared by the user.
+    ///
+    /// The user canister is removed from the user canisters index first, so that it can't share
+    /// files anymore. The user canisters sharing files with the user are asked to drop the keys
+    /// of the user through [`PendingRevocations`].
+    fn revoke_shares(&self, archive: bool) -> AccountDeleteState {
+        debug!("Revoking shares for user: {}", self.user);
+        UserCanisterStorage::remove_from_index(self.user);
+
+        // files shared with the user
+        for (user_canister, files) in SharedFilesStorage::get_shared_files(self.user) {
+            PendingRevocations::push(user_canister, self.user);
+            for file_id in files.into_keys() {
+                Canister::revoke_share(self.user, user_canister, file_id);
+            }
+        }
+        if cfg!(target_family = "wasm") {
+            ic_cdk::futures::spawn(PendingRevocations::flush());
+        }
+
+        // files shared by the user
+        let Some(user_canister) = UserCanisterStorage::get_user_canister(self.user) else {
+            return AccountDeleteState::Ok;
+        };
+        for (file_id, users) in SharedFilesStorage::user_canister_shares(user_canister) {
+            for user in users {
+                Canister::revoke_share(user, user_canister, file_id);
+            }
+        }
+
+        AccountDeleteState::DeleteCanister {
+            user_canister,
+            archive,
+        }
+    }
+
+    /// Sends a request to the Orbit Station canister to delete or archive the user canister.
+    ///
+    /// If `archive` is set, the user canister is kept with its data, detached from the Orbit
+    /// Station, instead of being deleted.
+    async fn delete_canister(&self, user_canister: Principal, archive: bool) -> AccountDeleteState {
+        debug!(
+            "Deleting user canister: {user_canister} for user: {}",
+            self.user
+        );
+
+        match OrbitStationClient::from(self.orbit_station)
+            .delete_user_canister(user_canister, self.user, archive)
+            .await
+        {
+            Ok(Ok(request)) => AccountDeleteState::WaitForDeleteCanisterResult {
+                request_id: request.request.id,
+                user_canister,
+            },
+            Ok(Err(e)) => AccountDeleteState::Failed {
+                reason: format!("failed to delete canister: {e:?}"),
+            },
+            Err(err) => AccountDeleteState::Failed {
+                reason: format!("failed to delete canister: {err}"),
+            },
+        }
+    }
+
+    /// Checks the result of the delete canister request.
+    async fn check_delete_canister_result(
+        &self,
+        request_id: String,
+        user_canister: Principal,
+    ) -> AccountDeleteState {
+        debug!("Checking delete canister result for request ID: {request_id}");
+        // send request to get the request status
+        let response = match OrbitStationClient::from(self.orbit_station)
+            .get_request_status(request_id.clone())
+            .await
+        {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                return AccountDeleteState::Failed {
+                    reason: format!(
+                        "failed to get request status for {request_id} (delete_canister): {e:?}"
+                    ),
+                };
+            }
+            Err(err) => {
+                return AccountDeleteState::Failed {
+                    reason: format!(
+                        "failed to get request status for {request_id} (delete_canister): {err}"
+                    ),
+                };
+            }
+        };
+
+        let status = response.request.status;
+        debug!("Request status for {request_id}: {:?}", status);
+        match status {
+            RequestStatus::Completed { .. } => {
+                debug!("Delete canister request completed successfully for {request_id}");
+                AccountDeleteState::Ok
+            }
+            RequestStatus::Failed { reason } => AccountDeleteState::Failed {
+                reason: format!("failed to delete canister: {}", reason.unwrap_or_default()),
+            },
+            RequestStatus::Rejected => AccountDeleteState::Failed {
+                reason: "delete_canister request rejected".to_string(),
+            },
+            RequestStatus::Cancelled { reason } => AccountDeleteState::Failed {
+                reason: format!(
+                    "delete_canister request cancelled: {}",
+                    reason.unwrap_or_default()
+                ),
+            },
+            RequestStatus::Scheduled { scheduled_at } => {
+                debug!("Delete canister request is scheduled for {request_id} at {scheduled_at}");
+                // operation is scheduled; update state
+                AccountDeleteState::WaitForDeleteCanisterSchedule {
+                    request_id,
+                    scheduled_at,
+                    user_canister,
+                }
+            }
+            RequestStatus::Approved | RequestStatus::Created | RequestStatus::Processing { .. } => {
+                debug!("Delete canister request is still in progress for {request_id}");
+                // operation is in progress; update state
+                AccountDeleteState::WaitForDeleteCanisterResult {
+                    request_id,
+                    user_canister,
+                }
+            }
+        }
+    }
+
+    /// Complete the account deletion by purging the user, their username, their user canister,
+    /// their contacts, their block list, their linked principals, their device keys and their
+    /// invites from the storage.
+    ///
+    /// The user and their user canister are also removed from the contacts and block lists of the
+    /// other users, and from the suspended users.
+    fn complete(&self) {
+        debug!("Account deletion completed for user: {}", self.user);
+        if let Some(user_canister) = UserCanisterStorage::get_user_canister(self.user) {
+            BlockListStorage::remove_from_all_block_lists(user_canister);
+        }
+        UserStorage::remove_user(self.user);
+        UserCanisterStorage::remove_user_canister(self.user);
+        ContactsStorage::remove_contacts(self.user);
+        ContactsStorage::remove_from_all_contacts(self.user);
+        BlockListStorage::remove_block_list(self.user);
+        BlockListStorage::remove_from_all_block_lists(self.user);
+        ModerationStorage::unsuspend(self.user);
+        LinkedPrincipalsStorage::remove_account(self.user);
+        DeviceKeysStorage::remove_device_keys(self.user);
+        InvitesStorage::remove_invites(self.user);
+        AccountDeletionStorage::remove_delete_state(self.user);
+    }
+
+    /// Get interval to sleep for the next operation [`AccountDeleteState`].
+    /// Returns [`Duration`].
+    fn delay(op: &AccountDeleteState) -> Duration {
+        match op {
+            AccountDeleteState::RevokeShares { .. } => DEFAULT_INTERVAL,
+            AccountDeleteState::DeleteCanister { .. } => DEFAULT_INTERVAL,
+            AccountDeleteState::WaitForDeleteCanisterSchedule { scheduled_at, .. } => {
+                CreateUserStateMachine::scheduled_at_time_diff(datetime(), scheduled_at)
+            }
+            AccountDeleteState::WaitForDeleteCanisterResult { .. } => {
+                ORBIT_STATION_REQUEST_INTERVAL
+            }
+            AccountDeleteState::Ok => Duration::ZERO,
+            AccountDeleteState::Failed { .. } => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::orchestrator::{PublicKey, ShareFileMetadata, User};
+
+    use super::*;
+    use crate::storage::pending_revocations::PendingRevocationsStorage;
+
+    #[test]
+    fn test_should_revoke_shares_and_purge_user() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let alice_canister = Principal::from_slice(&[11; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let bob_canister = Principal::from_slice(&[12; 29]);
+        for (user, username, user_canister) in
+            [(alice, "alice", alice_canister), (bob, "bob", bob_canister)]
+        {
+            UserStorage::add_user(
+                user,
+                User {
+                    username: username.to_string(),
+                    public_key: PublicKey::default(),
+                },
+            );
+            UserCanisterStorage::set_user_canister(user, user_canister);
+        }
+        let metadata = ShareFileMetadata {
+            file_name: "file.txt".to_string(),
+        };
+        SharedFilesStorage::share_file(alice, bob_canister, 1, metadata.clone());
+        SharedFilesStorage::share_file(bob, alice_canister, 2, metadata);
+        let charlie = Principal::from_slice(&[3; 29]);
+        ContactsStorage::set_contact(bob, alice, None);
+        ContactsStorage::set_contact(bob, charlie, None);
+        BlockListStorage::block(bob, alice);
+        BlockListStorage::block(charlie, alice_canister);
+        BlockListStorage::block(charlie, bob);
+        ModerationStorage::suspend(alice, 1);
+
+        let state_machine = DeleteAccountStateMachine {
+            orbit_station: Principal::management_canister(),
+            user: alice,
+        };
+        AccountDeletionStorage::init_delete_state(alice, false);
+        assert_eq!(
+            state_machine.revoke_shares(false),
+            AccountDeleteState::DeleteCanister {
+                user_canister: alice_canister,
+                archive: false
+            }
+        );
+        assert!(SharedFilesStorage::get_shared_files(alice).is_empty());
+        assert!(SharedFilesStorage::get_shared_files(bob).is_empty());
+        assert!(!UserCanisterStorage::is_user_canister(alice_canister));
+        assert!(UserCanisterStorage::is_user_canister(bob_canister));
+        // bob's user canister must drop the keys shared with alice
+        assert_eq!(
+            PendingRevocationsStorage::entries(),
+            vec![(bob_canister, alice)]
+        );
+
+        state_machine.complete();
+        assert!(UserStorage::get_user(&alice).is_none());
+        assert!(!UserStorage::username_exists("alice"));
+        assert_eq!(UserCanisterStorage::get_user_canister(alice), None);
+        assert_eq!(AccountDeletionStorage::get_delete_state(alice), None);
+        assert!(UserStorage::get_user(&bob).is_some());
+        // alice is purged from the other users' contacts, block lists and suspensions
+        assert_eq!(
+            ContactsStorage::get_contacts(bob)
+                .into_iter()
+                .map(|(contact, _)| contact)
+                .collect::<Vec<_>>(),
+            vec![charlie]
+        );
+        assert!(BlockListStorage::get_blocked(bob).is_empty());
+        assert_eq!(BlockListStorage::get_blocked(charlie), vec![bob]);
+        assert!(!ModerationStorage::is_suspended(alice));
+    }
+
+    #[test]
+    fn test_should_skip_canister_deletion_without_user_canister() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let state_machine = DeleteAccountStateMachine {
+            orbit_station: Principal::management_canister(),
+            user: alice,
+        };
+
+        assert_eq!(state_machine.revoke_shares(true), AccountDeleteState::Ok);
+    }
+}
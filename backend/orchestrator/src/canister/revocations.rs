@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use candid::Principal;
+use did::user_canister::FileSharingResponse;
+
+use crate::client::UserCanisterClient;
+use crate::debug;
+use crate::storage::pending_revocations::PendingRevocationsStorage;
+use crate::storage::user_canister::UserCanisterStorage;
+
+/// Interval between two deliveries of the pending revocations.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Durable queue of the revocations to deliver to the user canisters.
+///
+/// When an account is deleted, the shares with the user are removed from the index right away;
+/// the user canisters sharing files with the user must drop their keys too. The revocations are
+/// recorded in the same message, then delivered until the user canister confirms them.
+/// Delivering a revocation twice has no effect.
+pub struct PendingRevocations;
+
+impl PendingRevocations {
+    /// Schedule the delivery of the pending revocations at a regular interval.
+    pub fn schedule_retries() {
+        if cfg!(target_family = "wasm") {
+            ic_cdk_timers::set_timer_interval(RETRY_INTERVAL, || {
+                ic_cdk::futures::spawn(Self::flush())
+            });
+        }
+    }
+
+    /// Record that a user canister must drop its shares with a deleted user.
+    pub fn push(user_canister: Principal, user: Principal) {
+        PendingRevocationsStorage::push(user_canister, user);
+    }
+
+    /// Deliver the pending revocations.
+    ///
+    /// The revocations for user canisters which were deleted in the meantime are dropped.
+    pub async fn flush() {
+        for (user_canister, user) in PendingRevocationsStorage::entries() {
+            if !UserCanisterStorage::is_user_canister(user_canister) {
+                PendingRevocationsStorage::remove(user_canister, user);
+                continue;
+            }
+
+            match UserCanisterClient::from(user_canister)
+                .revoke_deleted_user(user)
+                .await
+            {
+                Ok(FileSharingResponse::Ok) => {
+                    PendingRevocationsStorage::remove(user_canister, user);
+                }
+                Ok(err) => {
+                    debug!("Failed to revoke the shares of {user} on {user_canister}: {err:?}");
+                }
+                Err(err) => {
+                    debug!("Failed to revoke the shares of {user} on {user_canister}: {err}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use candid::Principal;
+use did::orchestrator::{BlockHash, DataCertificate, FileId, ShareOperation};
+use ic_certification::{HashTree, fork, label, leaf};
+use serde::Serialize as _;
+use serde_bytes::ByteBuf;
+
+use crate::storage::share_log::ShareLogStorage;
+use crate::utils::trap;
+
+/// Label of the index of the last block in the certified hash tree.
+const LAST_BLOCK_INDEX_LABEL: &str = "last_block_index";
+/// Label of the hash of the last block in the certified hash tree.
+const LAST_BLOCK_HASH_LABEL: &str = "last_block_hash";
+
+/// Records share events into the share log and certifies its tip.
+pub struct ShareLog;
+
+impl ShareLog {
+    /// Append a block for the given operation and certify the new tip.
+    pub fn record(
+        operation: ShareOperation,
+        user_canister: Principal,
+        file_id: FileId,
+        user: Principal,
+    ) {
+        let (index, hash) = ShareLogStorage::append(operation, user_canister, file_id, user);
+        Self::set_certified_data(index, hash);
+    }
+
+    /// Certify the current tip of the log.
+    ///
+    /// Must be called after an upgrade, since the certified data is not kept.
+    pub fn certify_tip() {
+        if let Some((index, hash)) = ShareLogStorage::tip() {
+            Self::set_certified_data(index, hash);
+        }
+    }
+
+    /// Get the certificate of the tip of the log.
+    ///
+    /// Returns `None` if the log is empty or if the certificate is not available, which is the
+    /// case outside of query calls.
+    pub fn tip_certificate() -> Option<DataCertificate> {
+        let (index, hash) = ShareLogStorage::tip()?;
+        let certificate = if cfg!(target_family = "wasm") {
+            ic_cdk::api::data_certificate()?
+        } else {
+            return None;
+        };
+
+        let mut serializer = serde_cbor::Serializer::new(Vec::new());
+        serializer
+            .self_describe()
+            .unwrap_or_else(|err| trap(format!("Failed to encode hash tree: {err}")));
+        Self::hash_tree(index, hash)
+            .serialize(&mut serializer)
+            .unwrap_or_else(|err| trap(format!("Failed to encode hash tree: {err}")));
+
+        Some(DataCertificate {
+            certificate: ByteBuf::from(certificate),
+            hash_tree: ByteBuf::from(serializer.into_inner()),
+        })
+    }
+
+    fn set_certified_data(index: u64, hash: BlockHash) {
+        if cfg!(target_family = "wasm") {
+            ic_cdk::api::certified_data_set(Self::hash_tree(index, hash).digest());
+        }
+    }
+
+    /// Build the hash tree of the tip, with the labels sorted as required by the IC.
+    fn hash_tree(index: u64, hash: BlockHash) -> HashTree {
+        fork(
+            label(LAST_BLOCK_HASH_LABEL, leaf(hash.to_vec())),
+            label(LAST_BLOCK_INDEX_LABEL, leaf(leb128(index))),
+        )
+    }
+}
+
+/// Encode a number as unsigned LEB128.
+fn leb128(mut value: u64) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(10);
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use ic_certification::LookupResult;
+
+    use super::*;
+
+    #[test]
+    fn test_should_encode_leb128() {
+        assert_eq!(leb128(0), vec![0x00]);
+        assert_eq!(leb128(127), vec![0x7f]);
+        assert_eq!(leb128(624485), vec![0xe5, 0x8e, 0x26]);
+    }
+
+    #[test]
+    fn test_should_build_tip_hash_tree() {
+        let hash = [42; 32];
+        let tree = ShareLog::hash_tree(300, hash);
+
+        assert_eq!(
+            tree.lookup_path([LAST_BLOCK_HASH_LABEL]),
+            LookupResult::Found(&hash)
+        );
+        assert_eq!(
+            tree.lookup_path([LAST_BLOCK_INDEX_LABEL]),
+            LookupResult::Found(&[0xac, 0x02])
+        );
+    }
+}
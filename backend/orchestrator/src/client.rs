@@ -1,3 +1,5 @@
 mod orbit_station;
+mod user_canister;
 
 pub use self::orbit_station::OrbitStationClient;
+pub use self::user_canister::UserCanisterClient;
@@ -1,6 +1,7 @@
 use candid::Principal;
 use did::orbit_station::{
     Allow, AuthScope, CanisterInstallMode, ChangeExternalCanisterOperationInput,
+    ConfigureExternalCanisterOperationInput, ConfigureExternalCanisterOperationKind,
     CreateExternalCanisterOperationInput, CreateExternalCanisterOperationKind,
     CreateExternalCanisterOperationKindCreateNew, CreateRequestInput, CreateRequestResult,
     ExternalCanisterCallPermission, ExternalCanisterMetadata, ExternalCanisterPermissions,
@@ -151,4 +152,44 @@ impl OrbitStationClient {
             .candid()
             .map_err(CallError::from)
     }
+
+    /// Send a request to the Orbit Station canister to delete the user canister.
+    ///
+    /// If `archive` is set, the user canister is only removed from the Orbit Station, keeping
+    /// its data; otherwise it is stopped and deleted.
+    pub async fn delete_user_canister(
+        &self,
+        canister_id: Principal,
+        owner: Principal,
+        archive: bool,
+    ) -> CallResult<CreateRequestResult> {
+        debug!("Deleting user canister {canister_id} for user {owner}, archive: {archive}");
+        let (title, kind) = if archive {
+            (
+                format!("archive user canister for user {owner}"),
+                ConfigureExternalCanisterOperationKind::SoftDelete,
+            )
+        } else {
+            (
+                format!("delete user canister for user {owner}"),
+                ConfigureExternalCanisterOperationKind::Delete,
+            )
+        };
+
+        let request = CreateRequestInput {
+            title: Some(title),
+            summary: None,
+            execution_plan: Some(RequestExecutionSchedule::Immediate),
+            expiration_dt: None,
+            operation: RequestOperationInput::ConfigureExternalCanister(
+                ConfigureExternalCanisterOperationInput { kind, canister_id },
+            ),
+        };
+
+        Call::unbounded_wait(self.principal, "create_request")
+            .with_arg(request)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
 }
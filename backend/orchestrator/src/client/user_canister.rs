@@ -0,0 +1,206 @@
+use candid::Principal;
+use did::orchestrator::FileId;
+use did::user_canister::{
+    DeviceId, DeviceKey, FileSharingResponse, LinkPrincipalResponse, PublicKey,
+    SetSuspendedResponse, SyncDeviceKeyResponse, SyncPublicKeyResponse,
+};
+use ic_cdk::call::{Call, CallResult, Error as CallError};
+
+use crate::debug;
+
+/// Client for a user canister.
+pub struct UserCanisterClient {
+    principal: Principal,
+}
+
+impl From<Principal> for UserCanisterClient {
+    fn from(principal: Principal) -> Self {
+        UserCanisterClient { principal }
+    }
+}
+
+impl UserCanisterClient {
+    /// Add a device key of the owner of the user canister.
+    ///
+    /// If successful, returns [`SyncDeviceKeyResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn add_device_key(
+        &self,
+        device_key: &DeviceKey,
+    ) -> CallResult<SyncDeviceKeyResponse> {
+        debug!("Adding device key {}", device_key.device_id);
+
+        Call::unbounded_wait(self.principal, "add_device_key")
+            .with_arg(device_key)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Notify the user canister that a user declined the share of a file.
+    ///
+    /// If successful, returns [`FileSharingResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn decline_share(
+        &self,
+        user: Principal,
+        file_id: FileId,
+    ) -> CallResult<FileSharingResponse> {
+        debug!("Notifying declined share of file {file_id} for user {user}");
+
+        Call::unbounded_wait(self.principal, "decline_share")
+            .with_args(&(user, file_id))
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Set the owner public key of the user canister, along with its version.
+    ///
+    /// If successful, returns [`SyncPublicKeyResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn sync_public_key(
+        &self,
+        public_key: PublicKey,
+        version: u64,
+    ) -> CallResult<SyncPublicKeyResponse> {
+        debug!("Syncing public key version {version}");
+
+        Call::unbounded_wait(self.principal, "sync_public_key")
+            .with_args(&(public_key, version))
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Link a principal to the account of the owner of the user canister.
+    ///
+    /// If successful, returns [`LinkPrincipalResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn link_principal(&self, principal: Principal) -> CallResult<LinkPrincipalResponse> {
+        debug!("Linking principal {principal}");
+
+        Call::unbounded_wait(self.principal, "link_principal")
+            .with_arg(principal)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Unlink a principal from the account of the owner of the user canister.
+    ///
+    /// If successful, returns [`LinkPrincipalResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn unlink_principal(
+        &self,
+        principal: Principal,
+    ) -> CallResult<LinkPrincipalResponse> {
+        debug!("Unlinking principal {principal}");
+
+        Call::unbounded_wait(self.principal, "unlink_principal")
+            .with_arg(principal)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Link a principal to the account of a user the owner of the user canister shares files with.
+    ///
+    /// If successful, returns [`LinkPrincipalResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn link_recipient_principal(
+        &self,
+        account: Principal,
+        principal: Principal,
+    ) -> CallResult<LinkPrincipalResponse> {
+        debug!("Linking principal {principal} to recipient {account}");
+
+        Call::unbounded_wait(self.principal, "link_recipient_principal")
+            .with_args(&(account, principal))
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Unlink a principal from the account of a user the owner of the user canister shares files
+    /// with.
+    ///
+    /// If successful, returns [`LinkPrincipalResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn unlink_recipient_principal(
+        &self,
+        principal: Principal,
+    ) -> CallResult<LinkPrincipalResponse> {
+        debug!("Unlinking recipient principal {principal}");
+
+        Call::unbounded_wait(self.principal, "unlink_recipient_principal")
+            .with_arg(principal)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Remove a device key of the owner of the user canister.
+    ///
+    /// If successful, returns [`SyncDeviceKeyResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn remove_device_key(
+        &self,
+        device_id: DeviceId,
+    ) -> CallResult<SyncDeviceKeyResponse> {
+        debug!("Removing device key {device_id}");
+
+        Call::unbounded_wait(self.principal, "remove_device_key")
+            .with_arg(device_id)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Remove the keys stored for a device of a user the owner of the user canister shares files
+    /// with.
+    ///
+    /// If successful, returns [`SyncDeviceKeyResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn remove_recipient_device_key(
+        &self,
+        user: Principal,
+        device_id: DeviceId,
+    ) -> CallResult<SyncDeviceKeyResponse> {
+        debug!("Removing device key {device_id} of recipient {user}");
+
+        Call::unbounded_wait(self.principal, "remove_recipient_device_key")
+            .with_args(&(user, device_id))
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Drop every share of the user canister with a user whose account was deleted.
+    ///
+    /// If successful, returns [`FileSharingResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn revoke_deleted_user(&self, user: Principal) -> CallResult<FileSharingResponse> {
+        debug!("Revoking the shares of deleted user {user}");
+
+        Call::unbounded_wait(self.principal, "revoke_deleted_user")
+            .with_arg(user)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+
+    /// Suspend or reinstate the owner of the user canister.
+    ///
+    /// If successful, returns [`SetSuspendedResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn set_suspended(&self, suspended: bool) -> CallResult<SetSuspendedResponse> {
+        debug!("Setting suspended to {suspended}");
+
+        Call::unbounded_wait(self.principal, "set_suspended")
+            .with_arg(suspended)
+            .await?
+            .candid()
+            .map_err(CallError::from)
+    }
+}
@@ -4,12 +4,26 @@ mod storage;
 mod utils;
 
 use candid::Principal;
+use did::http::{HttpRequest, HttpResponse};
 use did::orchestrator::{
-    FileId, GetUsersResponse, OrchestratorInstallArgs, Pagination, PublicKey, PublicUser,
-    RetryUserCanisterCreationResponse, RevokeShareFileResponse, SetUserResponse, ShareFileMetadata,
-    ShareFileResponse, SharedFilesResponse, UserCanisterResponse, WhoamiResponse,
+    AddContactRequest, AddContactResponse, AddDeviceKeyRequest, AddDeviceKeyResponse,
+    AdminFailedUserCanistersResponse, AdminReleaseUsernameResponse, AdminReserveUsernameResponse,
+    AdminReservedUsernamesResponse, AdminRetryUserCanisterCreationResponse, AdminStatsResponse,
+    AdminSuspendUserResponse, AdminUnsuspendUserResponse, AdminUsernameCollisionsResponse,
+    AdminUsersResponse, ApprovePrincipalLinkResponse, BlockResponse, ContactsResponse,
+    CreateInviteRequest, CreateInviteResponse, CursorPagination, DataCertificate,
+    DeclineShareResponse, DeleteAccountRequest, DeleteAccountResponse, DeviceId, DeviceKey,
+    FileCursorPagination, FileId, GetBlockedResponse, GetBlocksRequest, GetBlocksResponse,
+    GetUserCanisterResponse, GetUsersResponse, InvitesResponse, LinkedPrincipalsResponse,
+    OrchestratorInstallArgs, Pagination, PublicKey, PublicUser, RemoveContactResponse,
+    RemoveDeviceKeyResponse, RequestPrincipalLinkResponse, RetryUserCanisterCreationResponse,
+    RevokeInviteResponse, RevokeShareFileResponse, SearchUsersResponse, SetUserResponse,
+    ShareFileMetadata, ShareFileResponse, ShareStateResponse, SharedByMeResponse,
+    SharedFilesResponse, UnblockResponse, UnlinkPrincipalResponse, UpdateProfileRequest,
+    UpdateProfileResponse, UpdatePublicKeyResponse, UpdateUserPublicKeyResponse,
+    UserCanisterResponse, UserCanisterSharesResponse, WhoamiResponse,
 };
-use ic_cdk_macros::{init, query, update};
+use ic_cdk_macros::{init, post_upgrade, query, update};
 
 use self::canister::Canister;
 use self::storage::config::Config;
@@ -19,6 +33,130 @@ pub fn init(args: OrchestratorInstallArgs) {
     Canister::init(args);
 }
 
+#[post_upgrade]
+pub fn post_upgrade(args: OrchestratorInstallArgs) {
+    Canister::post_upgrade(args);
+}
+
+#[update]
+pub fn accept_share(user_canister: Principal, file_id: FileId) -> ShareStateResponse {
+    Canister::accept_share(user_canister, file_id)
+}
+
+#[update]
+pub fn add_contact(request: AddContactRequest) -> AddContactResponse {
+    Canister::add_contact(request)
+}
+
+#[update]
+pub async fn add_device_key(request: AddDeviceKeyRequest) -> AddDeviceKeyResponse {
+    Canister::add_device_key(request).await
+}
+
+#[query]
+pub fn admin_failed_user_canisters(
+    pagination: CursorPagination,
+) -> AdminFailedUserCanistersResponse {
+    Canister::admin_failed_user_canisters(pagination)
+}
+
+#[update]
+pub fn admin_release_username(username: String) -> AdminReleaseUsernameResponse {
+    Canister::admin_release_username(username)
+}
+
+#[update]
+pub fn admin_reserve_username(username: String) -> AdminReserveUsernameResponse {
+    Canister::admin_reserve_username(username)
+}
+
+#[query]
+pub fn admin_reserved_usernames() -> AdminReservedUsernamesResponse {
+    Canister::admin_reserved_usernames()
+}
+
+#[update]
+pub fn admin_retry_user_canister_creation(
+    user: Principal,
+) -> AdminRetryUserCanisterCreationResponse {
+    Canister::admin_retry_user_canister_creation(user)
+}
+
+#[query]
+pub fn admin_stats() -> AdminStatsResponse {
+    Canister::admin_stats()
+}
+
+#[update]
+pub async fn admin_suspend_user(user: Principal) -> AdminSuspendUserResponse {
+    Canister::admin_suspend_user(user).await
+}
+
+#[update]
+pub async fn admin_unsuspend_user(user: Principal) -> AdminUnsuspendUserResponse {
+    Canister::admin_unsuspend_user(user).await
+}
+
+#[query]
+pub fn admin_username_collisions() -> AdminUsernameCollisionsResponse {
+    Canister::admin_username_collisions()
+}
+
+#[query]
+pub fn admin_users(pagination: CursorPagination) -> AdminUsersResponse {
+    Canister::admin_users(pagination)
+}
+
+#[update]
+pub async fn approve_principal_link(principal: Principal) -> ApprovePrincipalLinkResponse {
+    Canister::approve_principal_link(principal).await
+}
+
+#[update]
+pub fn block(principal: Principal) -> BlockResponse {
+    Canister::block(principal)
+}
+
+#[query]
+pub fn contacts(pagination: Pagination) -> ContactsResponse {
+    Canister::contacts(pagination)
+}
+
+#[update]
+pub async fn create_invite(request: CreateInviteRequest) -> CreateInviteResponse {
+    Canister::create_invite(request).await
+}
+
+#[update]
+pub async fn decline_share(user_canister: Principal, file_id: FileId) -> DeclineShareResponse {
+    Canister::decline_share(user_canister, file_id).await
+}
+
+#[update]
+pub fn delete_account(request: DeleteAccountRequest) -> DeleteAccountResponse {
+    Canister::delete_account(request)
+}
+
+#[query]
+pub fn get_blocked() -> GetBlockedResponse {
+    Canister::get_blocked()
+}
+
+#[query]
+pub fn get_blocks(request: GetBlocksRequest) -> GetBlocksResponse {
+    Canister::get_blocks(request)
+}
+
+#[query]
+pub fn get_device_keys(user: Principal) -> Vec<DeviceKey> {
+    Canister::get_device_keys(user)
+}
+
+#[query]
+pub fn get_tip_certificate() -> Option<DataCertificate> {
+    Canister::get_tip_certificate()
+}
+
 #[query]
 pub fn get_users(pagination: Pagination, query: Option<String>) -> GetUsersResponse {
     Canister::get_users(pagination, query.as_deref())
@@ -29,16 +167,71 @@ pub fn get_user(principal: Principal) -> Option<PublicUser> {
     Canister::get_user(principal)
 }
 
+#[query]
+pub fn get_user_canister(user: Principal) -> GetUserCanisterResponse {
+    Canister::get_user_canister(user)
+}
+
+#[query]
+pub fn get_user_canister_shares(pagination: FileCursorPagination) -> UserCanisterSharesResponse {
+    Canister::get_user_canister_shares(pagination)
+}
+
+#[update]
+pub fn hide_share(user_canister: Principal, file_id: FileId) -> ShareStateResponse {
+    Canister::hide_share(user_canister, file_id)
+}
+
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    Canister::http_request(request)
+}
+
+#[query]
+pub fn invites() -> InvitesResponse {
+    Canister::invites()
+}
+
+#[query]
+pub fn linked_principals() -> LinkedPrincipalsResponse {
+    Canister::linked_principals()
+}
+
 #[query]
 pub fn orbit_station() -> Principal {
     Config::get_orbit_station()
 }
 
+#[update]
+pub fn remove_contact(user: Principal) -> RemoveContactResponse {
+    Canister::remove_contact(user)
+}
+
+#[update]
+pub async fn remove_device_key(device_id: DeviceId) -> RemoveDeviceKeyResponse {
+    Canister::remove_device_key(device_id).await
+}
+
+#[update]
+pub fn request_principal_link(account: Principal) -> RequestPrincipalLinkResponse {
+    Canister::request_principal_link(account)
+}
+
+#[query]
+pub fn resolve_username(username: String) -> Option<PublicUser> {
+    Canister::resolve_username(username)
+}
+
 #[update]
 pub fn retry_user_canister_creation() -> RetryUserCanisterCreationResponse {
     Canister::retry_user_canister_creation()
 }
 
+#[update]
+pub fn revoke_invite(code: String) -> RevokeInviteResponse {
+    Canister::revoke_invite(code)
+}
+
 #[update]
 pub fn revoke_share_file(user: Principal, file_id: FileId) -> RevokeShareFileResponse {
     Canister::revoke_share_file(user, file_id)
@@ -53,8 +246,22 @@ pub fn revoke_share_file_for_users(
 }
 
 #[update]
-pub fn set_user(username: String, public_key: PublicKey) -> SetUserResponse {
-    Canister::set_user(username, public_key)
+pub fn revoke_user_shares(user: Principal, file_ids: Vec<FileId>) -> RevokeShareFileResponse {
+    Canister::revoke_user_shares(user, file_ids)
+}
+
+#[query]
+pub fn search_users(pagination: CursorPagination, query: Option<String>) -> SearchUsersResponse {
+    Canister::search_users(pagination, query.as_deref())
+}
+
+#[update]
+pub fn set_user(
+    username: String,
+    public_key: PublicKey,
+    invite: Option<String>,
+) -> SetUserResponse {
+    Canister::set_user(username, public_key, invite)
 }
 
 #[update]
@@ -75,11 +282,44 @@ pub fn share_file_with_users(
     Canister::share_file_with_users(users, file_id, metadata)
 }
 
+#[query]
+pub fn shared_by_me(pagination: Pagination) -> SharedByMeResponse {
+    Canister::shared_by_me(pagination)
+}
+
 #[query]
 pub fn shared_files() -> SharedFilesResponse {
     Canister::shared_files()
 }
 
+#[update]
+pub fn unblock(principal: Principal) -> UnblockResponse {
+    Canister::unblock(principal)
+}
+
+#[update]
+pub async fn unlink_principal(principal: Principal) -> UnlinkPrincipalResponse {
+    Canister::unlink_principal(principal).await
+}
+
+#[update]
+pub fn update_profile(request: UpdateProfileRequest) -> UpdateProfileResponse {
+    Canister::update_profile(request)
+}
+
+#[update]
+pub async fn update_public_key(public_key: PublicKey, version: u64) -> UpdatePublicKeyResponse {
+    Canister::update_public_key(public_key, version).await
+}
+
+#[update]
+pub fn update_user_public_key(
+    user: Principal,
+    public_key: PublicKey,
+) -> UpdateUserPublicKeyResponse {
+    Canister::update_user_public_key(user, public_key)
+}
+
 #[query]
 pub fn username_exists(username: String) -> bool {
     Canister::username_exists(username)
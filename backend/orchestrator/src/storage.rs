@@ -1,4 +1,13 @@
+pub mod account_deletion;
+pub mod block_list;
 pub mod config;
+pub mod contacts;
+pub mod device_keys;
+pub mod invites;
+pub mod linked_principals;
+pub mod moderation;
+pub mod pending_revocations;
+pub mod share_log;
 pub mod shared_files;
 pub mod user_canister;
 pub mod users;
@@ -0,0 +1,122 @@
+mod delete_state;
+
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+pub use self::delete_state::AccountDeleteState;
+use crate::storage::memory::{ACCOUNT_DELETE_STATES_MEMORY_ID, MEMORY_MANAGER};
+
+thread_local! {
+    /// Account deletions, in progress or failed.
+    ///
+    /// A map between the user principal and the state of the deletion of their account.
+    static ACCOUNT_DELETE_STATES: RefCell<StableBTreeMap<StorablePrincipal, AccountDeleteState, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(ACCOUNT_DELETE_STATES_MEMORY_ID)))
+    );
+}
+
+/// Account deletion storage to access the deletion states of the accounts
+pub struct AccountDeletionStorage;
+
+impl AccountDeletionStorage {
+    /// Initialize an account deletion.
+    ///
+    /// If `archive` is set, the user canister is archived rather than deleted.
+    pub fn init_delete_state(principal: Principal, archive: bool) {
+        ACCOUNT_DELETE_STATES.with_borrow_mut(|states| {
+            states.insert(
+                principal.into(),
+                AccountDeleteState::RevokeShares { archive },
+            )
+        });
+    }
+
+    /// Get the [`AccountDeleteState`] for a certain user.
+    pub fn get_delete_state(principal: Principal) -> Option<AccountDeleteState> {
+        ACCOUNT_DELETE_STATES.with_borrow(|states| states.get(&StorablePrincipal::from(principal)))
+    }
+
+    /// Update the [`AccountDeleteState`] for a certain user.
+    pub fn set_delete_state(principal: Principal, state: AccountDeleteState) {
+        ACCOUNT_DELETE_STATES.with_borrow_mut(|states| {
+            states.insert(principal.into(), state);
+        });
+    }
+
+    /// Remove the [`AccountDeleteState`] for a certain user, once the account is deleted.
+    pub fn remove_delete_state(principal: Principal) {
+        ACCOUNT_DELETE_STATES.with_borrow_mut(|states| {
+            states.remove(&StorablePrincipal::from(principal));
+        });
+    }
+
+    /// Get whether the account of a user is being deleted.
+    ///
+    /// Failed deletions are not in progress.
+    pub fn is_deletion_pending(principal: Principal) -> bool {
+        Self::get_delete_state(principal)
+            .is_some_and(|state| !matches!(state, AccountDeleteState::Failed { .. }))
+    }
+
+    /// Get the users whose account is being deleted.
+    ///
+    /// Failed deletions are not in progress.
+    pub fn pending_deletions() -> Vec<Principal> {
+        ACCOUNT_DELETE_STATES.with_borrow(|states| {
+            states
+                .iter()
+                .filter(|(_, state)| !matches!(state, AccountDeleteState::Failed { .. }))
+                .map(|(principal, _)| principal.0)
+                .collect()
+        })
+    }
+
+    /// Count the account deletions.
+    ///
+    /// Returns the number of deletions in progress and the number of failed deletions.
+    pub fn count_delete_states() -> (u64, u64) {
+        ACCOUNT_DELETE_STATES.with_borrow(|states| {
+            let failed = states
+                .iter()
+                .filter(|(_, state)| matches!(state, AccountDeleteState::Failed { .. }))
+                .count() as u64;
+            (states.len() - failed, failed)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_init_and_remove_delete_state() {
+        let principal = Principal::from_slice(&[1; 29]);
+        assert!(!AccountDeletionStorage::is_deletion_pending(principal));
+
+        AccountDeletionStorage::init_delete_state(principal, true);
+        assert_eq!(
+            AccountDeletionStorage::get_delete_state(principal),
+            Some(AccountDeleteState::RevokeShares { archive: true })
+        );
+        assert!(AccountDeletionStorage::is_deletion_pending(principal));
+        assert_eq!(AccountDeletionStorage::pending_deletions(), vec![principal]);
+
+        AccountDeletionStorage::set_delete_state(
+            principal,
+            AccountDeleteState::Failed {
+                reason: "failed".to_string(),
+            },
+        );
+        assert!(!AccountDeletionStorage::is_deletion_pending(principal));
+        assert!(AccountDeletionStorage::pending_deletions().is_empty());
+
+        AccountDeletionStorage::remove_delete_state(principal);
+        assert_eq!(AccountDeletionStorage::get_delete_state(principal), None);
+    }
+}
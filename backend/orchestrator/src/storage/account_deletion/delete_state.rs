@@ -0,0 +1,247 @@
+use candid::Principal;
+use did::orbit_station::TimestampRfc3339;
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+
+use crate::utils::trap;
+
+const OP_REVOKE_SHARES: u8 = 0;
+const OP_DELETE_CANISTER: u8 = 1;
+const OP_WAIT_FOR_DELETE_CANISTER_SCHEDULE: u8 = 2;
+const OP_WAIT_FOR_DELETE_CANISTER_RESULT: u8 = 3;
+const OP_OK: u8 = 254;
+const OP_FAILED: u8 = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountDeleteState {
+    /// Revoke the shares of the files shared with the user and of the files shared by the user.
+    ///
+    /// If `archive` is set, the user canister is archived rather than deleted.
+    RevokeShares { archive: bool },
+    /// Send a request to the orbit station to delete or archive the user canister.
+    DeleteCanister {
+        user_canister: Principal,
+        archive: bool,
+    },
+    /// Wait for the orbit station to start scheduled canister deletion.
+    /// It can mutate to [`AccountDeleteState::WaitForDeleteCanisterResult`] when executing.
+    WaitForDeleteCanisterSchedule {
+        user_canister: Principal,
+        scheduled_at: TimestampRfc3339,
+        request_id: String,
+    },
+    /// Wait for the delete canister operation to finish.
+    /// This state mutates to [`AccountDeleteState::WaitForDeleteCanisterSchedule`] when scheduled.
+    WaitForDeleteCanisterResult {
+        user_canister: Principal,
+        request_id: String,
+    },
+    /// The user canister is deleted; the user records can be purged.
+    Ok,
+    /// The account deletion failed.
+    Failed { reason: String },
+}
+
+impl Storable for AccountDeleteState {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 2048,
+        is_fixed_size: false,
+    };
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        if bytes.is_empty() {
+            trap("Failed to decode AccountDeleteState: empty bytes");
+        }
+        // read op code
+        let op_code = bytes[0];
+        match op_code {
+            // states stored before archiving was supported have no archive flag
+            OP_REVOKE_SHARES => AccountDeleteState::RevokeShares {
+                archive: Self::decode_flag(&bytes[1..]),
+            },
+            OP_DELETE_CANISTER => {
+                let (user_canister, offset) = Self::decode_principal(&bytes[1..]);
+                AccountDeleteState::DeleteCanister {
+                    user_canister,
+                    archive: Self::decode_flag(&bytes[1 + offset..]),
+                }
+            }
+            OP_WAIT_FOR_DELETE_CANISTER_SCHEDULE => {
+                let (user_canister, offset) = Self::decode_principal(&bytes[1..]);
+                let (scheduled_at, len) = Self::decode_string(&bytes[1 + offset..]);
+                let (request_id, _) = Self::decode_string(&bytes[1 + offset + len..]);
+                AccountDeleteState::WaitForDeleteCanisterSchedule {
+                    user_canister,
+                    scheduled_at,
+                    request_id,
+                }
+            }
+            OP_WAIT_FOR_DELETE_CANISTER_RESULT => {
+                let (user_canister, offset) = Self::decode_principal(&bytes[1..]);
+                let (request_id, _) = Self::decode_string(&bytes[1 + offset..]);
+                AccountDeleteState::WaitForDeleteCanisterResult {
+                    user_canister,
+                    request_id,
+                }
+            }
+            OP_OK => AccountDeleteState::Ok,
+            OP_FAILED => AccountDeleteState::Failed {
+                reason: Self::decode_string(&bytes[1..]).0,
+            },
+            _ => trap("Failed to decode AccountDeleteState: invalid operation code"),
+        }
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let mut bytes = vec![];
+        match self {
+            AccountDeleteState::RevokeShares { archive } => {
+                bytes.push(OP_REVOKE_SHARES);
+                bytes.push(*archive as u8);
+            }
+            AccountDeleteState::DeleteCanister {
+                user_canister,
+                archive,
+            } => {
+                bytes.push(OP_DELETE_CANISTER);
+                Self::encode_principal(&mut bytes, *user_canister);
+                bytes.push(*archive as u8);
+            }
+            AccountDeleteState::WaitForDeleteCanisterSchedule {
+                user_canister,
+                scheduled_at,
+                request_id,
+            } => {
+                bytes.push(OP_WAIT_FOR_DELETE_CANISTER_SCHEDULE);
+                Self::encode_principal(&mut bytes, *user_canister);
+                Self::encode_string(&mut bytes, scheduled_at);
+                Self::encode_string(&mut bytes, request_id);
+            }
+            AccountDeleteState::WaitForDeleteCanisterResult {
+                user_canister,
+                request_id,
+            } => {
+                bytes.push(OP_WAIT_FOR_DELETE_CANISTER_RESULT);
+                Self::encode_principal(&mut bytes, *user_canister);
+                Self::encode_string(&mut bytes, request_id);
+            }
+            AccountDeleteState::Ok => bytes.push(OP_OK),
+            AccountDeleteState::Failed { reason } => {
+                bytes.push(OP_FAILED);
+                Self::encode_string(&mut bytes, reason);
+            }
+        }
+
+        bytes.into()
+    }
+}
+
+impl AccountDeleteState {
+    /// Write a principal, prefixed by its length.
+    fn encode_principal(bytes: &mut Vec<u8>, principal: Principal) {
+        bytes.push(principal.as_slice().len() as u8);
+        bytes.extend_from_slice(principal.as_slice());
+    }
+
+    /// Read a principal prefixed by its length, returning it along with the bytes read.
+    fn decode_principal(bytes: &[u8]) -> (Principal, usize) {
+        let len = bytes[0] as usize;
+        (Principal::from_slice(&bytes[1..1 + len]), 1 + len)
+    }
+
+    /// Read a flag, which is unset if missing.
+    fn decode_flag(bytes: &[u8]) -> bool {
+        bytes.first().is_some_and(|flag| *flag != 0)
+    }
+
+    /// Write a string, prefixed by its length; strings longer than 255 bytes are truncated.
+    fn encode_string(bytes: &mut Vec<u8>, value: &str) {
+        let value = &value.as_bytes()[..value.len().min(u8::MAX as usize)];
+        bytes.push(value.len() as u8);
+        bytes.extend_from_slice(value);
+    }
+
+    /// Read a string prefixed by its length, returning it along with the bytes read.
+    fn decode_string(bytes: &[u8]) -> (String, usize) {
+        let len = bytes[0] as usize;
+        (
+            String::from_utf8_lossy(&bytes[1..1 + len]).into_owned(),
+            1 + len,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_storable_delete_states_roundtrip() {
+        let user_canister = Principal::from_slice(&[2; 29]);
+        let states = [
+            AccountDeleteState::RevokeShares { archive: false },
+            AccountDeleteState::RevokeShares { archive: true },
+            AccountDeleteState::DeleteCanister {
+                user_canister,
+                archive: false,
+            },
+            AccountDeleteState::DeleteCanister {
+                user_canister,
+                archive: true,
+            },
+            AccountDeleteState::WaitForDeleteCanisterSchedule {
+                user_canister,
+                scheduled_at: TimestampRfc3339::from("2023-10-01T00:00:00Z"),
+                request_id: "request_id".to_string(),
+            },
+            AccountDeleteState::WaitForDeleteCanisterResult {
+                user_canister,
+                request_id: "request_id".to_string(),
+            },
+            AccountDeleteState::Ok,
+            AccountDeleteState::Failed {
+                reason: "failed".to_string(),
+            },
+        ];
+
+        for state in states {
+            let bytes = state.to_bytes();
+            let decoded_state = AccountDeleteState::from_bytes(bytes);
+            assert_eq!(state, decoded_state);
+        }
+    }
+
+    #[test]
+    fn test_storable_should_decode_states_without_archive_flag() {
+        let user_canister = Principal::from_slice(&[2; 29]);
+        assert_eq!(
+            AccountDeleteState::from_bytes(vec![OP_REVOKE_SHARES].into()),
+            AccountDeleteState::RevokeShares { archive: false }
+        );
+
+        let mut bytes = vec![OP_DELETE_CANISTER, 29];
+        bytes.extend_from_slice(user_canister.as_slice());
+        assert_eq!(
+            AccountDeleteState::from_bytes(bytes.into()),
+            AccountDeleteState::DeleteCanister {
+                user_canister,
+                archive: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_storable_should_truncate_long_reason() {
+        let state = AccountDeleteState::Failed {
+            reason: "a".repeat(300),
+        };
+        let decoded_state = AccountDeleteState::from_bytes(state.to_bytes());
+        assert_eq!(
+            decoded_state,
+            AccountDeleteState::Failed {
+                reason: "a".repeat(255)
+            }
+        );
+    }
+}
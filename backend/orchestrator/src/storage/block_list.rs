@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+use crate::storage::memory::{BLOCK_LIST_MEMORY_ID, MEMORY_MANAGER};
+
+thread_local! {
+    /// Block lists of the users.
+    ///
+    /// A set of (user, blocked) pairs, where the blocked principal is a user or a user canister.
+    static BLOCK_LIST: RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(BLOCK_LIST_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the block lists of the users.
+///
+/// Users don't receive shares from the users and user canisters they blocked.
+pub struct BlockListStorage;
+
+impl BlockListStorage {
+    /// Add a user or a user canister to the block list of a user.
+    pub fn block(user: Principal, blocked: Principal) {
+        BLOCK_LIST.with_borrow_mut(|block_list| {
+            block_list.insert((user.into(), blocked.into()), ());
+        });
+    }
+
+    /// Remove a user or a user canister from the block list of a user.
+    ///
+    /// Returns `false` if it was not blocked.
+    pub fn unblock(user: Principal, blocked: Principal) -> bool {
+        BLOCK_LIST.with_borrow_mut(|block_list| {
+            block_list.remove(&(user.into(), blocked.into())).is_some()
+        })
+    }
+
+    /// Get whether a user blocked the given user or user canister.
+    pub fn is_blocked(user: Principal, blocked: Principal) -> bool {
+        BLOCK_LIST.with_borrow(|block_list| block_list.contains_key(&(user.into(), blocked.into())))
+    }
+
+    /// Get the users and user canisters blocked by a user, ordered by principal.
+    pub fn get_blocked(user: Principal) -> Vec<Principal> {
+        let user = StorablePrincipal::from(user);
+        BLOCK_LIST.with_borrow(|block_list| {
+            block_list
+                .range((user, StorablePrincipal::from(Principal::from_slice(&[])))..)
+                .take_while(|((owner, _), _)| *owner == user)
+                .map(|((_, blocked), _)| blocked.0)
+                .collect()
+        })
+    }
+
+    /// Remove a user or a user canister from the block list of every user.
+    pub fn remove_from_all_block_lists(blocked: Principal) {
+        let blocked = StorablePrincipal::from(blocked);
+        BLOCK_LIST.with_borrow_mut(|block_list| {
+            let keys = block_list
+                .keys()
+                .filter(|(_, entry_blocked)| *entry_blocked == blocked)
+                .collect::<Vec<_>>();
+            for key in keys {
+                block_list.remove(&key);
+            }
+        });
+    }
+
+    /// Remove the block list of a user.
+    pub fn remove_block_list(user: Principal) {
+        let blocked = Self::get_blocked(user);
+        BLOCK_LIST.with_borrow_mut(|block_list| {
+            for principal in blocked {
+                block_list.remove(&(user.into(), principal.into()));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_block_and_unblock() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let bob_canister = Principal::from_slice(&[12; 29]);
+
+        BlockListStorage::block(alice, bob);
+        BlockListStorage::block(alice, bob_canister);
+        BlockListStorage::block(bob, alice);
+        assert!(BlockListStorage::is_blocked(alice, bob));
+        assert!(!BlockListStorage::is_blocked(bob, bob_canister));
+        assert_eq!(
+            BlockListStorage::get_blocked(alice),
+            vec![bob, bob_canister]
+        );
+
+        assert!(BlockListStorage::unblock(alice, bob));
+        assert!(!BlockListStorage::unblock(alice, bob));
+        assert!(!BlockListStorage::is_blocked(alice, bob));
+
+        BlockListStorage::remove_block_list(alice);
+        assert!(BlockListStorage::get_blocked(alice).is_empty());
+        assert!(BlockListStorage::is_blocked(bob, alice));
+    }
+}
@@ -3,9 +3,12 @@ use std::cell::RefCell;
 use candid::Principal;
 use did::StorablePrincipal;
 use ic_stable_structures::memory_manager::VirtualMemory;
-use ic_stable_structures::{DefaultMemoryImpl, StableCell};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 
-use super::memory::{MEMORY_MANAGER, ORBIT_STATION_ADMIN_MEMORY_ID, ORBIT_STATION_MEMORY_ID};
+use super::memory::{
+    ADMINS_MEMORY_ID, MEMORY_MANAGER, ORBIT_STATION_ADMIN_MEMORY_ID, ORBIT_STATION_MEMORY_ID,
+    SCHEMA_VERSION_MEMORY_ID,
+};
 use crate::utils::trap;
 
 thread_local! {
@@ -18,6 +21,16 @@ thread_local! {
     static ORBIT_STATION_ADMIN: RefCell<StableCell<String, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(ORBIT_STATION_ADMIN_MEMORY_ID)), String::default()).unwrap()
     );
+
+    /// Admins of the orchestrator
+    static ADMINS: RefCell<StableBTreeMap<StorablePrincipal, (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(ADMINS_MEMORY_ID)))
+    );
+
+    /// Version of the layout of the stable memory, up to which the migrations were run
+    static SCHEMA_VERSION: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(SCHEMA_VERSION_MEMORY_ID)), 0).unwrap()
+    );
 }
 
 /// Canister configuration
@@ -47,6 +60,33 @@ impl Config {
             trap(format!("Failed to set orbit station admin: {:?}", err));
         }
     }
+
+    /// Replace the admins of the orchestrator
+    pub fn set_admins(admins: Vec<Principal>) {
+        ADMINS.with_borrow_mut(|storage| {
+            storage.clear_new();
+            for admin in admins {
+                storage.insert(admin.into(), ());
+            }
+        });
+    }
+
+    /// Get whether the given principal is an admin of the orchestrator
+    pub fn is_admin(principal: Principal) -> bool {
+        ADMINS.with_borrow(|admins| admins.contains_key(&StorablePrincipal::from(principal)))
+    }
+
+    /// Get the version of the layout of the stable memory; 0 if it was never set
+    pub fn get_schema_version() -> u64 {
+        SCHEMA_VERSION.with_borrow(|cell| *cell.get())
+    }
+
+    /// Set the version of the layout of the stable memory
+    pub fn set_schema_version(version: u64) {
+        if let Err(err) = SCHEMA_VERSION.with_borrow_mut(|cell| cell.set(version)) {
+            trap(format!("Failed to set schema version: {:?}", err));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -66,4 +106,24 @@ mod test {
         Config::set_orbit_station_admin(admin.clone());
         assert_eq!(Config::get_orbit_station_admin(), admin);
     }
+
+    #[test]
+    fn test_admins() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        Config::set_admins(vec![alice, bob]);
+        assert!(Config::is_admin(alice));
+        assert!(Config::is_admin(bob));
+
+        Config::set_admins(vec![bob]);
+        assert!(!Config::is_admin(alice));
+        assert!(Config::is_admin(bob));
+    }
+
+    #[test]
+    fn test_schema_version() {
+        assert_eq!(Config::get_schema_version(), 0);
+        Config::set_schema_version(1);
+        assert_eq!(Config::get_schema_version(), 1);
+    }
 }
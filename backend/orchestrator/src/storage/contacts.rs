@@ -0,0 +1,140 @@
+mod contact_entry;
+
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+pub use self::contact_entry::ContactEntry;
+use crate::storage::memory::{CONTACTS_MEMORY_ID, MEMORY_MANAGER};
+
+thread_local! {
+    /// Contacts of the users.
+    ///
+    /// A map between a (user, contact) pair and the contact entry.
+    static CONTACTS: RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), ContactEntry, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(CONTACTS_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the contacts of the users.
+///
+/// Contacts are private to each user.
+pub struct ContactsStorage;
+
+impl ContactsStorage {
+    /// Add a contact to the contacts of a user, or update the nickname of the contact.
+    pub fn set_contact(user: Principal, contact: Principal, nickname: Option<String>) {
+        Self::update_contact(user, contact, |entry| entry.nickname = nickname);
+    }
+
+    /// Record that a file was shared by a user with a contact, adding the contact to the contacts
+    /// of the user if needed.
+    ///
+    /// `shared_at` is `None` if the time of the share is unknown.
+    pub fn record_share(user: Principal, contact: Principal, shared_at: Option<u64>) {
+        Self::update_contact(user, contact, |entry| {
+            entry.last_shared_at = entry.last_shared_at.max(shared_at);
+        });
+    }
+
+    /// Remove a contact from the contacts of a user.
+    ///
+    /// Returns `false` if the user has no such contact.
+    pub fn remove_contact(user: Principal, contact: Principal) -> bool {
+        CONTACTS
+            .with_borrow_mut(|contacts| contacts.remove(&(user.into(), contact.into())).is_some())
+    }
+
+    /// Get the contacts of a user, ordered by principal.
+    pub fn get_contacts(user: Principal) -> Vec<(Principal, ContactEntry)> {
+        let user = StorablePrincipal::from(user);
+        CONTACTS.with_borrow(|contacts| {
+            contacts
+                .range((user, StorablePrincipal::from(Principal::from_slice(&[])))..)
+                .take_while(|((owner, _), _)| *owner == user)
+                .map(|((_, contact), entry)| (contact.0, entry))
+                .collect()
+        })
+    }
+
+    /// Remove all the contacts of a user.
+    pub fn remove_contacts(user: Principal) {
+        let contacts = Self::get_contacts(user);
+        CONTACTS.with_borrow_mut(|storage| {
+            for (contact, _) in contacts {
+                storage.remove(&(user.into(), contact.into()));
+            }
+        });
+    }
+
+    /// Remove a user from the contacts of every user.
+    pub fn remove_from_all_contacts(contact: Principal) {
+        let contact = StorablePrincipal::from(contact);
+        CONTACTS.with_borrow_mut(|contacts| {
+            let keys = contacts
+                .keys()
+                .filter(|(_, entry_contact)| *entry_contact == contact)
+                .collect::<Vec<_>>();
+            for key in keys {
+                contacts.remove(&key);
+            }
+        });
+    }
+
+    /// Update the entry of a contact of a user, creating it if needed.
+    fn update_contact(user: Principal, contact: Principal, f: impl FnOnce(&mut ContactEntry)) {
+        let key = (
+            StorablePrincipal::from(user),
+            StorablePrincipal::from(contact),
+        );
+        CONTACTS.with_borrow_mut(|contacts| {
+            let mut entry = contacts.get(&key).unwrap_or_default();
+            f(&mut entry);
+            contacts.insert(key, entry);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_set_and_remove_contacts() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let charlie = Principal::from_slice(&[3; 29]);
+
+        ContactsStorage::set_contact(alice, bob, Some("Bobby".to_string()));
+        ContactsStorage::record_share(alice, bob, Some(10));
+        ContactsStorage::record_share(alice, bob, None);
+        ContactsStorage::record_share(alice, charlie, None);
+        ContactsStorage::set_contact(bob, alice, None);
+
+        assert_eq!(
+            ContactsStorage::get_contacts(alice),
+            vec![
+                (
+                    bob,
+                    ContactEntry {
+                        nickname: Some("Bobby".to_string()),
+                        last_shared_at: Some(10),
+                    }
+                ),
+                (charlie, ContactEntry::default()),
+            ]
+        );
+
+        assert!(ContactsStorage::remove_contact(alice, charlie));
+        assert!(!ContactsStorage::remove_contact(alice, charlie));
+        assert_eq!(ContactsStorage::get_contacts(alice).len(), 1);
+
+        ContactsStorage::remove_contacts(alice);
+        assert!(ContactsStorage::get_contacts(alice).is_empty());
+        assert_eq!(ContactsStorage::get_contacts(bob).len(), 1);
+    }
+}
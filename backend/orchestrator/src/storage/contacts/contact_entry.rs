@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+
+use did::orchestrator::MAX_CONTACT_NICKNAME_SIZE;
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+
+/// A contact of a user, as stored in the contacts storage.
+///
+/// ## Encoding
+///
+/// - 8 bytes: time of the last share with the contact, in nanoseconds; 0 if unknown.
+/// - 1 byte: length of the nickname; 0 if there is no nickname.
+/// - N bytes: nickname.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContactEntry {
+    /// The nickname given to the contact
+    pub nickname: Option<String>,
+    /// When a file was last shared with the contact, if known
+    pub last_shared_at: Option<u64>,
+}
+
+impl Storable for ContactEntry {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 8 + 1 + MAX_CONTACT_NICKNAME_SIZE as u32,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let nickname = self.nickname.as_deref().unwrap_or_default();
+        let mut bytes = Vec::with_capacity(8 + 1 + nickname.len());
+        bytes.extend_from_slice(&self.last_shared_at.unwrap_or_default().to_le_bytes());
+        bytes.push(nickname.len() as u8);
+        bytes.extend_from_slice(nickname.as_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let last_shared_at = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let nickname_len = bytes[8] as usize;
+        let nickname = (nickname_len > 0)
+            .then(|| String::from_utf8_lossy(&bytes[9..9 + nickname_len]).to_string());
+
+        Self {
+            nickname,
+            last_shared_at: (last_shared_at > 0).then_some(last_shared_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_storable_contact_entry_roundtrip() {
+        for entry in [
+            ContactEntry::default(),
+            ContactEntry {
+                nickname: Some("Bobby".to_string()),
+                last_shared_at: Some(1_700_000_000_000_000_000),
+            },
+        ] {
+            let decoded = ContactEntry::from_bytes(entry.to_bytes());
+            assert_eq!(entry, decoded);
+        }
+    }
+}
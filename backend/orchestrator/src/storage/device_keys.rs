@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use did::orchestrator::{DeviceId, DeviceKey};
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+use crate::storage::memory::{DEVICE_KEYS_MEMORY_ID, MEMORY_MANAGER, NEXT_DEVICE_IDS_MEMORY_ID};
+
+thread_local! {
+    /// Device keys of the users.
+    ///
+    /// A map between a (user, device ID) pair and the device key.
+    static DEVICE_KEYS: RefCell<StableBTreeMap<(StorablePrincipal, DeviceId), DeviceKey, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(DEVICE_KEYS_MEMORY_ID)))
+    );
+
+    /// Next device ID of each user.
+    ///
+    /// Device IDs are never reused, so that keys wrapped for a removed device can't be mistaken
+    /// for keys of a new device.
+    static NEXT_DEVICE_IDS: RefCell<StableBTreeMap<StorablePrincipal, DeviceId, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(NEXT_DEVICE_IDS_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the device keys of the users.
+pub struct DeviceKeysStorage;
+
+impl DeviceKeysStorage {
+    /// Reserve the ID of a new device of a user.
+    ///
+    /// The ID is reserved even if the device key is never inserted.
+    pub fn reserve_device_id(user: Principal) -> DeviceId {
+        NEXT_DEVICE_IDS.with_borrow_mut(|ids| {
+            let device_id = ids.get(&user.into()).unwrap_or_default();
+            ids.insert(user.into(), device_id + 1);
+            device_id
+        })
+    }
+
+    /// Insert a device key of a user, with an ID given by [`Self::reserve_device_id`].
+    pub fn insert_device_key(user: Principal, device_key: DeviceKey) {
+        DEVICE_KEYS.with_borrow_mut(|keys| {
+            keys.insert((user.into(), device_key.device_id), device_key);
+        });
+    }
+
+    /// Remove a device key of a user.
+    ///
+    /// Returns `false` if the user has no such device.
+    pub fn remove_device_key(user: Principal, device_id: DeviceId) -> bool {
+        DEVICE_KEYS.with_borrow_mut(|keys| keys.remove(&(user.into(), device_id)).is_some())
+    }
+
+    /// Get the device keys of a user, ordered by device ID.
+    pub fn get_device_keys(user: Principal) -> Vec<DeviceKey> {
+        let user = StorablePrincipal::from(user);
+        DEVICE_KEYS.with_borrow(|keys| {
+            keys.range((user, 0)..)
+                .take_while(|((owner, _), _)| *owner == user)
+                .map(|(_, key)| key)
+                .collect()
+        })
+    }
+
+    /// Remove all the device keys of a user.
+    pub fn remove_device_keys(user: Principal) {
+        let devices = Self::get_device_keys(user);
+        DEVICE_KEYS.with_borrow_mut(|keys| {
+            for device in devices {
+                keys.remove(&(user.into(), device.device_id));
+            }
+        });
+        NEXT_DEVICE_IDS.with_borrow_mut(|ids| ids.remove(&user.into()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::orchestrator::PublicKey;
+
+    use super::*;
+
+    #[test]
+    fn test_should_add_and_remove_device_keys() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let device_key = |device_id, name: &str| DeviceKey {
+            device_id,
+            name: name.to_string(),
+            public_key: PublicKey::try_from(vec![1; 32]).unwrap(),
+        };
+
+        assert_eq!(DeviceKeysStorage::reserve_device_id(alice), 0);
+        assert_eq!(DeviceKeysStorage::reserve_device_id(alice), 1);
+        assert_eq!(DeviceKeysStorage::reserve_device_id(bob), 0);
+        DeviceKeysStorage::insert_device_key(alice, device_key(0, "Laptop"));
+        DeviceKeysStorage::insert_device_key(alice, device_key(1, "Phone"));
+        DeviceKeysStorage::insert_device_key(bob, device_key(0, "Laptop"));
+        assert_eq!(
+            DeviceKeysStorage::get_device_keys(alice),
+            vec![device_key(0, "Laptop"), device_key(1, "Phone")]
+        );
+
+        // ids are not reused
+        assert!(DeviceKeysStorage::remove_device_key(alice, 1));
+        assert!(!DeviceKeysStorage::remove_device_key(alice, 1));
+        assert_eq!(DeviceKeysStorage::reserve_device_id(alice), 2);
+
+        DeviceKeysStorage::remove_device_keys(alice);
+        assert!(DeviceKeysStorage::get_device_keys(alice).is_empty());
+        assert_eq!(DeviceKeysStorage::reserve_device_id(alice), 0);
+        assert_eq!(DeviceKeysStorage::get_device_keys(bob).len(), 1);
+    }
+}
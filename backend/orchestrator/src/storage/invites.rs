@@ -0,0 +1,202 @@
+mod invite_entry;
+
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use did::orchestrator::FileId;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+pub use self::invite_entry::InviteEntry;
+use crate::storage::memory::{INVITES_MEMORY_ID, MEMORY_MANAGER, OWNER_INVITES_MEMORY_ID};
+
+thread_local! {
+    /// Invites to share a file with someone who has not signed up yet.
+    ///
+    /// A map between the invite code and the invite.
+    static INVITES: RefCell<StableBTreeMap<InviteCode, InviteEntry, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(INVITES_MEMORY_ID)))
+    );
+
+    /// Index of the invites of each user.
+    ///
+    /// A set of (owner, invite code) pairs.
+    static OWNER_INVITES: RefCell<StableBTreeMap<(StorablePrincipal, InviteCode), (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(OWNER_INVITES_MEMORY_ID)))
+    );
+}
+
+/// Code of an invite, given to the invitee; it is shown as a hexadecimal string.
+pub type InviteCode = [u8; 16];
+
+/// Format an invite code as a hexadecimal string.
+pub fn format_invite_code(code: &InviteCode) -> String {
+    code.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Parse an invite code from a hexadecimal string, regardless of its case.
+///
+/// Returns `None` if the string is not a valid invite code.
+pub fn parse_invite_code(code: &str) -> Option<InviteCode> {
+    if code.len() != 2 * size_of::<InviteCode>() || !code.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut bytes = InviteCode::default();
+    for (byte, hex) in bytes.iter_mut().zip(code.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+    }
+
+    Some(bytes)
+}
+
+/// Accessor for the invites of the users.
+///
+/// An invite holds a pending share until the invitee signs up with its code and the owner shares
+/// the file with them.
+pub struct InvitesStorage;
+
+impl InvitesStorage {
+    /// Insert a new invite.
+    pub fn insert_invite(code: InviteCode, invite: InviteEntry) {
+        OWNER_INVITES.with_borrow_mut(|owner_invites| {
+            owner_invites.insert((invite.owner.into(), code), ());
+        });
+        INVITES.with_borrow_mut(|invites| {
+            invites.insert(code, invite);
+        });
+    }
+
+    /// Get an invite by its code.
+    pub fn get_invite(code: InviteCode) -> Option<InviteEntry> {
+        INVITES.with_borrow(|invites| invites.get(&code))
+    }
+
+    /// Mark an invite as redeemed by the given user.
+    ///
+    /// Returns `false` if there is no such invite.
+    pub fn redeem_invite(code: InviteCode, invitee: Principal) -> bool {
+        INVITES.with_borrow_mut(|invites| {
+            let Some(mut invite) = invites.get(&code) else {
+                return false;
+            };
+            invite.invitee = Some(invitee);
+            invites.insert(code, invite);
+            true
+        })
+    }
+
+    /// Remove an invite.
+    ///
+    /// Returns the removed invite, if any.
+    pub fn remove_invite(code: InviteCode) -> Option<InviteEntry> {
+        let invite = INVITES.with_borrow_mut(|invites| invites.remove(&code))?;
+        OWNER_INVITES.with_borrow_mut(|owner_invites| {
+            owner_invites.remove(&(invite.owner.into(), code));
+        });
+
+        Some(invite)
+    }
+
+    /// Get the invites of a user with their code, the oldest first.
+    pub fn get_invites(owner: Principal) -> Vec<(InviteCode, InviteEntry)> {
+        let owner = StorablePrincipal::from(owner);
+        let codes = OWNER_INVITES.with_borrow(|owner_invites| {
+            owner_invites
+                .range((owner, InviteCode::default())..)
+                .take_while(|((invite_owner, _), _)| *invite_owner == owner)
+                .map(|((_, code), _)| code)
+                .collect::<Vec<_>>()
+        });
+
+        let mut invites = INVITES.with_borrow(|invites| {
+            codes
+                .into_iter()
+                .filter_map(|code| invites.get(&code).map(|invite| (code, invite)))
+                .collect::<Vec<_>>()
+        });
+        invites.sort_by_key(|(_, invite)| invite.created_at);
+
+        invites
+    }
+
+    /// Remove the invites of a user redeemed by the given invitee for the given file, once the
+    /// file is shared with them.
+    pub fn complete_invites(owner: Principal, file_id: FileId, invitee: Principal) {
+        for (code, invite) in Self::get_invites(owner) {
+            if invite.file_id == file_id && invite.invitee == Some(invitee) {
+                Self::remove_invite(code);
+            }
+        }
+    }
+
+    /// Get the number of invites.
+    pub fn count() -> u64 {
+        INVITES.with_borrow(|invites| invites.len())
+    }
+
+    /// Remove all the invites of a user.
+    pub fn remove_invites(owner: Principal) {
+        for (code, _) in Self::get_invites(owner) {
+            Self::remove_invite(code);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_insert_redeem_and_complete_invites() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let invite = |file_id, created_at| InviteEntry {
+            owner: alice,
+            file_id,
+            file_name: "foo.txt".to_string(),
+            created_at,
+            invitee: None,
+        };
+        let (a, b, c, d) = ([1; 16], [2; 16], [3; 16], [4; 16]);
+        InvitesStorage::insert_invite(b, invite(1, 20));
+        InvitesStorage::insert_invite(a, invite(2, 10));
+        InvitesStorage::insert_invite(c, invite(1, 30));
+
+        assert_eq!(
+            InvitesStorage::get_invites(alice)
+                .into_iter()
+                .map(|(code, _)| code)
+                .collect::<Vec<_>>(),
+            vec![a, b, c]
+        );
+        assert!(InvitesStorage::get_invites(bob).is_empty());
+
+        assert!(InvitesStorage::redeem_invite(b, bob));
+        assert!(!InvitesStorage::redeem_invite(d, bob));
+        assert_eq!(InvitesStorage::get_invite(b).unwrap().invitee, Some(bob));
+
+        // only the redeemed invite for the file is completed
+        InvitesStorage::complete_invites(alice, 1, bob);
+        assert!(InvitesStorage::get_invite(b).is_none());
+        assert!(InvitesStorage::get_invite(c).is_some());
+
+        InvitesStorage::remove_invites(alice);
+        assert!(InvitesStorage::get_invites(alice).is_empty());
+        assert!(InvitesStorage::get_invite(a).is_none());
+    }
+
+    #[test]
+    fn test_should_format_and_parse_invite_codes() {
+        let code = [0xab; 16];
+        let formatted = format_invite_code(&code);
+        assert_eq!(formatted, "ab".repeat(16));
+        assert_eq!(parse_invite_code(&formatted), Some(code));
+        assert_eq!(parse_invite_code(&formatted.to_uppercase()), Some(code));
+        assert_eq!(parse_invite_code("abab"), None);
+        assert_eq!(parse_invite_code(&"zz".repeat(16)), None);
+        assert_eq!(parse_invite_code(&"é".repeat(16)), None);
+    }
+}
@@ -0,0 +1,99 @@
+use std::borrow::Cow;
+
+use candid::Principal;
+use did::orchestrator::FileId;
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+
+/// An invite, as stored in the invites storage.
+///
+/// ## Encoding
+///
+/// - 1 byte: length of the owner principal.
+/// - N bytes: owner principal.
+/// - 8 bytes: file ID.
+/// - 8 bytes: creation time, in nanoseconds.
+/// - 1 byte: length of the invitee principal; 0 if the invite was not redeemed.
+/// - N bytes: invitee principal.
+/// - Remaining bytes: file name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InviteEntry {
+    /// The user who created the invite
+    pub owner: Principal,
+    /// The file to share with the invitee
+    pub file_id: FileId,
+    /// The name of the file to share
+    pub file_name: String,
+    /// When the invite was created
+    pub created_at: u64,
+    /// The user who signed up with the invite, if redeemed
+    pub invitee: Option<Principal>,
+}
+
+impl Storable for InviteEntry {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let owner = self.owner.as_slice();
+        let invitee = self
+            .invitee
+            .as_ref()
+            .map(Principal::as_slice)
+            .unwrap_or_default();
+        let mut bytes =
+            Vec::with_capacity(1 + owner.len() + 16 + 1 + invitee.len() + self.file_name.len());
+        bytes.push(owner.len() as u8);
+        bytes.extend_from_slice(owner);
+        bytes.extend_from_slice(&self.file_id.to_le_bytes());
+        bytes.extend_from_slice(&self.created_at.to_le_bytes());
+        bytes.push(invitee.len() as u8);
+        bytes.extend_from_slice(invitee);
+        bytes.extend_from_slice(self.file_name.as_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let owner_len = bytes[0] as usize;
+        let owner = Principal::from_slice(&bytes[1..1 + owner_len]);
+        let mut offset = 1 + owner_len;
+        let file_id = u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let created_at = u64::from_le_bytes(bytes[offset + 8..offset + 16].try_into().unwrap());
+        offset += 16;
+        let invitee_len = bytes[offset] as usize;
+        offset += 1;
+        let invitee =
+            (invitee_len > 0).then(|| Principal::from_slice(&bytes[offset..offset + invitee_len]));
+        offset += invitee_len;
+        let file_name = String::from_utf8_lossy(&bytes[offset..]).to_string();
+
+        Self {
+            owner,
+            file_id,
+            file_name,
+            created_at,
+            invitee,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_storable_invite_entry_roundtrip() {
+        for invitee in [None, Some(Principal::from_slice(&[2; 29]))] {
+            let entry = InviteEntry {
+                owner: Principal::from_slice(&[1; 29]),
+                file_id: 42,
+                file_name: "report.pdf".to_string(),
+                created_at: 1_700_000_000_000_000_000,
+                invitee,
+            };
+            let decoded = InviteEntry::from_bytes(entry.to_bytes());
+            assert_eq!(entry, decoded);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+use crate::storage::memory::{
+    ACCOUNT_PRINCIPALS_MEMORY_ID, LINK_REQUESTS_MEMORY_ID, LINKED_ACCOUNTS_MEMORY_ID,
+    MEMORY_MANAGER,
+};
+
+thread_local! {
+    /// Map between a principal linked to an account and the principal the account was created with.
+    static LINKED_ACCOUNTS: RefCell<StableBTreeMap<StorablePrincipal, StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(LINKED_ACCOUNTS_MEMORY_ID)))
+    );
+
+    /// Index of the principals linked to each account.
+    ///
+    /// A set of (account, linked principal) pairs.
+    static ACCOUNT_PRINCIPALS: RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(ACCOUNT_PRINCIPALS_MEMORY_ID)))
+    );
+
+    /// Requests to link a principal to an account.
+    ///
+    /// A map between an (account, principal) pair and the time of the request.
+    static LINK_REQUESTS: RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(LINK_REQUESTS_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the principals linked to the accounts of the users.
+///
+/// An account is identified by the principal it was created with; other principals, such as the
+/// principals of the other devices of the user, can be linked to it once approved from the
+/// account.
+pub struct LinkedPrincipalsStorage;
+
+impl LinkedPrincipalsStorage {
+    /// Get the account a principal is linked to, if any.
+    pub fn get_account(principal: Principal) -> Option<Principal> {
+        LINKED_ACCOUNTS
+            .with_borrow(|accounts| accounts.get(&principal.into()).map(|account| account.0))
+    }
+
+    /// Link a principal to an account, dropping its request to be linked to it.
+    pub fn link(account: Principal, principal: Principal) {
+        LINKED_ACCOUNTS.with_borrow_mut(|accounts| {
+            accounts.insert(principal.into(), account.into());
+        });
+        ACCOUNT_PRINCIPALS.with_borrow_mut(|principals| {
+            principals.insert((account.into(), principal.into()), ());
+        });
+        LINK_REQUESTS.with_borrow_mut(|requests| {
+            requests.remove(&(account.into(), principal.into()));
+        });
+    }
+
+    /// Unlink a principal from an account.
+    ///
+    /// Returns `false` if the principal is not linked to the account.
+    pub fn unlink(account: Principal, principal: Principal) -> bool {
+        if Self::get_account(principal) != Some(account) {
+            return false;
+        }
+
+        LINKED_ACCOUNTS.with_borrow_mut(|accounts| {
+            accounts.remove(&principal.into());
+        });
+        ACCOUNT_PRINCIPALS.with_borrow_mut(|principals| {
+            principals.remove(&(account.into(), principal.into()));
+        });
+
+        true
+    }
+
+    /// Get the principals linked to an account, ordered by principal.
+    pub fn get_linked_principals(account: Principal) -> Vec<Principal> {
+        let account = StorablePrincipal::from(account);
+        ACCOUNT_PRINCIPALS.with_borrow(|principals| {
+            principals
+                .range((account, StorablePrincipal::from(Principal::from_slice(&[])))..)
+                .take_while(|((owner, _), _)| *owner == account)
+                .map(|((_, principal), _)| principal.0)
+                .collect()
+        })
+    }
+
+    /// Record the request of a principal to be linked to an account, replacing the previous one.
+    pub fn request_link(account: Principal, principal: Principal, requested_at: u64) {
+        LINK_REQUESTS.with_borrow_mut(|requests| {
+            requests.insert((account.into(), principal.into()), requested_at);
+        });
+    }
+
+    /// Get the time at which a principal requested to be linked to an account, if it did.
+    pub fn get_link_request(account: Principal, principal: Principal) -> Option<u64> {
+        LINK_REQUESTS.with_borrow(|requests| requests.get(&(account.into(), principal.into())))
+    }
+
+    /// Get the principals which requested to be linked to an account, with the time of their
+    /// request, ordered by principal.
+    pub fn get_link_requests(account: Principal) -> Vec<(Principal, u64)> {
+        let account = StorablePrincipal::from(account);
+        LINK_REQUESTS.with_borrow(|requests| {
+            requests
+                .range((account, StorablePrincipal::from(Principal::from_slice(&[])))..)
+                .take_while(|((owner, _), _)| *owner == account)
+                .map(|((_, principal), requested_at)| (principal.0, requested_at))
+                .collect()
+        })
+    }
+
+    /// Remove the requests to be linked to an account made at or before the given time.
+    pub fn remove_link_requests_until(account: Principal, until: u64) {
+        let requests = Self::get_link_requests(account);
+        LINK_REQUESTS.with_borrow_mut(|storage| {
+            for (principal, _) in requests
+                .into_iter()
+                .filter(|(_, requested_at)| *requested_at <= until)
+            {
+                storage.remove(&(account.into(), principal.into()));
+            }
+        });
+    }
+
+    /// Remove the principals linked to an account, and the requests to be linked to it.
+    pub fn remove_account(account: Principal) {
+        for principal in Self::get_linked_principals(account) {
+            Self::unlink(account, principal);
+        }
+        let requests = Self::get_link_requests(account);
+        LINK_REQUESTS.with_borrow_mut(|storage| {
+            for (principal, _) in requests {
+                storage.remove(&(account.into(), principal.into()));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_link_and_unlink_principals() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let alice_laptop = Principal::from_slice(&[11; 29]);
+        let alice_phone = Principal::from_slice(&[21; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+
+        LinkedPrincipalsStorage::request_link(alice, alice_laptop, 10);
+        LinkedPrincipalsStorage::request_link(alice, alice_phone, 20);
+        assert_eq!(
+            LinkedPrincipalsStorage::get_link_request(alice, alice_phone),
+            Some(20)
+        );
+        assert_eq!(
+            LinkedPrincipalsStorage::get_link_request(bob, alice_phone),
+            None
+        );
+
+        LinkedPrincipalsStorage::link(alice, alice_laptop);
+        assert_eq!(
+            LinkedPrincipalsStorage::get_account(alice_laptop),
+            Some(alice)
+        );
+        assert_eq!(LinkedPrincipalsStorage::get_account(alice), None);
+        assert_eq!(
+            LinkedPrincipalsStorage::get_linked_principals(alice),
+            vec![alice_laptop]
+        );
+        assert_eq!(
+            LinkedPrincipalsStorage::get_link_requests(alice),
+            vec![(alice_phone, 20)]
+        );
+
+        assert!(!LinkedPrincipalsStorage::unlink(bob, alice_laptop));
+        assert!(LinkedPrincipalsStorage::unlink(alice, alice_laptop));
+        assert_eq!(LinkedPrincipalsStorage::get_account(alice_laptop), None);
+        assert!(LinkedPrincipalsStorage::get_linked_principals(alice).is_empty());
+
+        LinkedPrincipalsStorage::request_link(alice, alice_laptop, 30);
+        LinkedPrincipalsStorage::remove_link_requests_until(alice, 20);
+        assert_eq!(
+            LinkedPrincipalsStorage::get_link_requests(alice),
+            vec![(alice_laptop, 30)]
+        );
+
+        LinkedPrincipalsStorage::link(alice, alice_laptop);
+        LinkedPrincipalsStorage::remove_account(alice);
+        assert_eq!(LinkedPrincipalsStorage::get_account(alice_laptop), None);
+        assert!(LinkedPrincipalsStorage::get_link_requests(alice).is_empty());
+    }
+}
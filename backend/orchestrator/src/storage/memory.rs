@@ -3,18 +3,46 @@ use ic_stable_structures::memory_manager::{MemoryId, MemoryManager as IcMemoryMa
 
 pub const ORBIT_STATION_MEMORY_ID: MemoryId = MemoryId::new(1);
 pub const ORBIT_STATION_ADMIN_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub const ADMINS_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub const SCHEMA_VERSION_MEMORY_ID: MemoryId = MemoryId::new(4);
 
 pub const USER_STORAGE_MEMORY_ID: MemoryId = MemoryId::new(10);
 pub const USERNAMES_MEMORY_ID: MemoryId = MemoryId::new(11);
+pub const USER_PROFILES_MEMORY_ID: MemoryId = MemoryId::new(12);
+pub const USERNAME_HISTORY_MEMORY_ID: MemoryId = MemoryId::new(13);
+pub const PUBLIC_KEY_VERSIONS_MEMORY_ID: MemoryId = MemoryId::new(14);
+pub const USERNAME_TRIGRAMS_MEMORY_ID: MemoryId = MemoryId::new(15);
+pub const USERNAME_COLLISIONS_MEMORY_ID: MemoryId = MemoryId::new(16);
+pub const LINKED_ACCOUNTS_MEMORY_ID: MemoryId = MemoryId::new(17);
+pub const ACCOUNT_PRINCIPALS_MEMORY_ID: MemoryId = MemoryId::new(18);
+pub const LINK_REQUESTS_MEMORY_ID: MemoryId = MemoryId::new(19);
 
 pub const USER_CANISTERS_MEMORY_ID: MemoryId = MemoryId::new(20);
 pub const USER_CANISTERS_INDEX_MEMORY_ID: MemoryId = MemoryId::new(21);
 pub const USER_CANISTER_CREATE_STATES_MEMORY_ID: MemoryId = MemoryId::new(22);
+pub const USER_CANISTER_OWNERS_MEMORY_ID: MemoryId = MemoryId::new(23);
 
 pub const SHARED_FILES_MEMORY_ID: MemoryId = MemoryId::new(30);
 pub const SHARED_FILES_METADATA_MEMORY_ID: MemoryId = MemoryId::new(31);
 pub const FILES_SHARES_MEMORY_ID: MemoryId = MemoryId::new(32);
 
+pub const SHARE_LOG_MEMORY_ID: MemoryId = MemoryId::new(40);
+
+pub const ACCOUNT_DELETE_STATES_MEMORY_ID: MemoryId = MemoryId::new(50);
+pub const PENDING_REVOCATIONS_MEMORY_ID: MemoryId = MemoryId::new(51);
+
+pub const CONTACTS_MEMORY_ID: MemoryId = MemoryId::new(60);
+pub const BLOCK_LIST_MEMORY_ID: MemoryId = MemoryId::new(61);
+
+pub const DEVICE_KEYS_MEMORY_ID: MemoryId = MemoryId::new(70);
+pub const NEXT_DEVICE_IDS_MEMORY_ID: MemoryId = MemoryId::new(71);
+
+pub const SUSPENDED_USERS_MEMORY_ID: MemoryId = MemoryId::new(80);
+pub const RESERVED_USERNAMES_MEMORY_ID: MemoryId = MemoryId::new(81);
+
+pub const INVITES_MEMORY_ID: MemoryId = MemoryId::new(90);
+pub const OWNER_INVITES_MEMORY_ID: MemoryId = MemoryId::new(91);
+
 thread_local! {
     /// Memory manager
     pub static MEMORY_MANAGER: IcMemoryManager<DefaultMemoryImpl> = IcMemoryManager::init(DefaultMemoryImpl::default());
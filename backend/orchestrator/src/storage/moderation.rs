@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use did::orchestrator::username_key;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+use crate::storage::memory::{
+    MEMORY_MANAGER, RESERVED_USERNAMES_MEMORY_ID, SUSPENDED_USERS_MEMORY_ID,
+};
+
+thread_local! {
+    /// Suspended users.
+    ///
+    /// A map between the user principal and the time of the suspension.
+    static SUSPENDED_USERS: RefCell<StableBTreeMap<StorablePrincipal, u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(SUSPENDED_USERS_MEMORY_ID)))
+    );
+
+    /// Usernames reserved by the admins.
+    ///
+    /// A map between the canonical key of the username and the username.
+    static RESERVED_USERNAMES: RefCell<StableBTreeMap<String, String, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(RESERVED_USERNAMES_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the moderation state: suspended users and reserved usernames.
+pub struct ModerationStorage;
+
+impl ModerationStorage {
+    /// Suspend a user, keeping the time of the first suspension.
+    pub fn suspend(user: Principal, suspended_at: u64) {
+        SUSPENDED_USERS.with_borrow_mut(|suspended| {
+            if !suspended.contains_key(&user.into()) {
+                suspended.insert(user.into(), suspended_at);
+            }
+        });
+    }
+
+    /// Lift the suspension of a user.
+    ///
+    /// Returns `false` if the user was not suspended.
+    pub fn unsuspend(user: Principal) -> bool {
+        SUSPENDED_USERS.with_borrow_mut(|suspended| suspended.remove(&user.into()).is_some())
+    }
+
+    /// Get whether a user is suspended.
+    pub fn is_suspended(user: Principal) -> bool {
+        SUSPENDED_USERS.with_borrow(|suspended| suspended.contains_key(&user.into()))
+    }
+
+    /// Get when a user was suspended, if suspended.
+    pub fn suspended_at(user: Principal) -> Option<u64> {
+        SUSPENDED_USERS.with_borrow(|suspended| suspended.get(&user.into()))
+    }
+
+    /// Get the suspended users.
+    pub fn suspended_users() -> BTreeSet<Principal> {
+        SUSPENDED_USERS.with_borrow(|suspended| suspended.keys().map(|user| user.0).collect())
+    }
+
+    /// Reserve a username, so that no user can take it.
+    pub fn reserve_username(username: String) {
+        RESERVED_USERNAMES.with_borrow_mut(|reserved| {
+            reserved.insert(username_key(&username), username);
+        });
+    }
+
+    /// Release a reserved username.
+    ///
+    /// Returns `false` if the username was not reserved.
+    pub fn release_username(username: &str) -> bool {
+        RESERVED_USERNAMES
+            .with_borrow_mut(|reserved| reserved.remove(&username_key(username)).is_some())
+    }
+
+    /// Get whether a username is reserved, regardless of its case.
+    pub fn is_username_reserved(username: &str) -> bool {
+        RESERVED_USERNAMES.with_borrow(|reserved| reserved.contains_key(&username_key(username)))
+    }
+
+    /// Get the reserved usernames, ordered by their canonical key.
+    pub fn reserved_usernames() -> Vec<String> {
+        RESERVED_USERNAMES.with_borrow(|reserved| reserved.values().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_suspend_and_unsuspend_users() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+
+        ModerationStorage::suspend(alice, 1);
+        ModerationStorage::suspend(alice, 2);
+        assert!(ModerationStorage::is_suspended(alice));
+        assert_eq!(ModerationStorage::suspended_at(alice), Some(1));
+        assert!(!ModerationStorage::is_suspended(bob));
+        assert_eq!(
+            ModerationStorage::suspended_users(),
+            BTreeSet::from([alice])
+        );
+
+        assert!(ModerationStorage::unsuspend(alice));
+        assert!(!ModerationStorage::unsuspend(alice));
+        assert!(!ModerationStorage::is_suspended(alice));
+    }
+
+    #[test]
+    fn test_should_reserve_and_release_usernames() {
+        ModerationStorage::reserve_username("Admin".to_string());
+        ModerationStorage::reserve_username("support".to_string());
+        assert!(ModerationStorage::is_username_reserved("ADMIN"));
+        assert!(!ModerationStorage::is_username_reserved("alice"));
+        assert_eq!(
+            ModerationStorage::reserved_usernames(),
+            vec!["Admin".to_string(), "support".to_string()]
+        );
+
+        assert!(ModerationStorage::release_username("admin"));
+        assert!(!ModerationStorage::release_username("admin"));
+        assert!(!ModerationStorage::is_username_reserved("Admin"));
+    }
+}
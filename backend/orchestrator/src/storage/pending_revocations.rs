@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+use crate::storage::memory::{MEMORY_MANAGER, PENDING_REVOCATIONS_MEMORY_ID};
+
+thread_local! {
+    /// Revocations to deliver to the user canisters.
+    ///
+    /// A set of (user canister, user) pairs, where the shares of the user canister with the deleted
+    /// user must be dropped.
+    static PENDING_REVOCATIONS: RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(PENDING_REVOCATIONS_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the revocations to deliver to the user canisters.
+pub struct PendingRevocationsStorage;
+
+impl PendingRevocationsStorage {
+    /// Record that the shares of a user canister with a user must be dropped.
+    pub fn push(user_canister: Principal, user: Principal) {
+        PENDING_REVOCATIONS.with_borrow_mut(|revocations| {
+            revocations.insert((user_canister.into(), user.into()), ());
+        });
+    }
+
+    /// Remove a revocation, once delivered.
+    pub fn remove(user_canister: Principal, user: Principal) {
+        PENDING_REVOCATIONS.with_borrow_mut(|revocations| {
+            revocations.remove(&(user_canister.into(), user.into()));
+        });
+    }
+
+    /// Get the pending revocations, as (user canister, user) pairs.
+    pub fn entries() -> Vec<(Principal, Principal)> {
+        PENDING_REVOCATIONS.with_borrow(|revocations| {
+            revocations
+                .keys()
+                .map(|(user_canister, user)| (user_canister.0, user.0))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_push_and_remove_revocations() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let user_canister = Principal::from_slice(&[11; 29]);
+
+        PendingRevocationsStorage::push(user_canister, alice);
+        PendingRevocationsStorage::push(user_canister, alice);
+        PendingRevocationsStorage::push(user_canister, bob);
+        assert_eq!(
+            PendingRevocationsStorage::entries(),
+            vec![(user_canister, alice), (user_canister, bob)]
+        );
+
+        PendingRevocationsStorage::remove(user_canister, alice);
+        assert_eq!(
+            PendingRevocationsStorage::entries(),
+            vec![(user_canister, bob)]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::orchestrator::{BlockHash, FileId, ShareBlock, ShareBlockWithId, ShareOperation};
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use serde_bytes::ByteBuf;
+
+use super::memory::{MEMORY_MANAGER, SHARE_LOG_MEMORY_ID};
+use crate::utils::time;
+
+thread_local! {
+    /// Share log. Append-only map between the index of a block and the block.
+    static SHARE_LOG: RefCell<StableBTreeMap<u64, ShareBlock, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(SHARE_LOG_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the hash-chained log of share events.
+///
+/// Blocks can only be appended; they are never updated nor removed.
+pub struct ShareLogStorage;
+
+impl ShareLogStorage {
+    /// Append a block for the given operation, chained to the current tip of the log.
+    ///
+    /// Returns the index and the hash of the new tip.
+    pub fn append(
+        operation: ShareOperation,
+        user_canister: Principal,
+        file_id: FileId,
+        user: Principal,
+    ) -> (u64, BlockHash) {
+        SHARE_LOG.with_borrow_mut(|share_log| {
+            let (index, parent_hash) = match share_log.last_key_value() {
+                Some((id, parent)) => (id + 1, Some(ByteBuf::from(parent.hash().to_vec()))),
+                None => (0, None),
+            };
+
+            let block = ShareBlock {
+                parent_hash,
+                operation,
+                user_canister,
+                file_id,
+                user,
+                timestamp: time(),
+            };
+            let hash = block.hash();
+            share_log.insert(index, block);
+
+            (index, hash)
+        })
+    }
+
+    /// Get the index and the hash of the last block of the log, if any.
+    pub fn tip() -> Option<(u64, BlockHash)> {
+        SHARE_LOG.with_borrow(|share_log| {
+            share_log
+                .last_key_value()
+                .map(|(id, block)| (id, block.hash()))
+        })
+    }
+
+    /// Get up to `length` blocks, starting from the block at index `start`.
+    pub fn get_blocks(start: u64, length: u64) -> Vec<ShareBlockWithId> {
+        SHARE_LOG.with_borrow(|share_log| {
+            share_log
+                .range(start..)
+                .take(length as usize)
+                .map(|(id, block)| ShareBlockWithId { id, block })
+                .collect()
+        })
+    }
+
+    /// Number of blocks in the log.
+    pub fn len() -> u64 {
+        SHARE_LOG.with_borrow(|share_log| share_log.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_append_chained_blocks() {
+        assert!(ShareLogStorage::tip().is_none());
+
+        let user_canister = Principal::from_slice(&[1; 29]);
+        let user = Principal::from_slice(&[2; 29]);
+        let (index, first_hash) =
+            ShareLogStorage::append(ShareOperation::Share, user_canister, 1, user);
+        assert_eq!(index, 0);
+        let (index, second_hash) =
+            ShareLogStorage::append(ShareOperation::Revoke, user_canister, 1, user);
+        assert_eq!(index, 1);
+
+        assert_eq!(ShareLogStorage::len(), 2);
+        assert_eq!(ShareLogStorage::tip(), Some((1, second_hash)));
+
+        let blocks = ShareLogStorage::get_blocks(0, 10);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].block.parent_hash, None);
+        assert_eq!(blocks[0].block.hash(), first_hash);
+        assert_eq!(
+            blocks[1]
+                .block
+                .parent_hash
+                .as_ref()
+                .map(|hash| hash.as_slice()),
+            Some(first_hash.as_slice())
+        );
+        assert_eq!(blocks[1].block.operation, ShareOperation::Revoke);
+    }
+
+    #[test]
+    fn test_should_get_blocks_range() {
+        let user_canister = Principal::from_slice(&[1; 29]);
+        let user = Principal::from_slice(&[2; 29]);
+        for file_id in 0..5 {
+            ShareLogStorage::append(ShareOperation::Share, user_canister, file_id, user);
+        }
+
+        let blocks = ShareLogStorage::get_blocks(3, 10);
+        assert_eq!(
+            blocks.iter().map(|block| block.id).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        assert_eq!(blocks[0].block.file_id, 3);
+        assert_eq!(ShareLogStorage::get_blocks(1, 2).len(), 2);
+        assert!(ShareLogStorage::get_blocks(5, 10).is_empty());
+    }
+}
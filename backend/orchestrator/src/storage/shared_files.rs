@@ -2,11 +2,11 @@ mod shared_with_set;
 mod user_shared_files;
 
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use candid::Principal;
 use did::StorablePrincipal;
-use did::orchestrator::{FileId, ShareFileMetadata};
+use did::orchestrator::{FileId, ShareFileMetadata, ShareState};
 use ic_stable_structures::memory_manager::VirtualMemory;
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
 
@@ -110,39 +110,64 @@ impl SharedFilesStorage {
             }
         });
 
-        // remove the user from the file shares; if the hashset is empty, remove the entry
-        let shares = FILES_SHARES.with_borrow_mut(|file_shares| {
-            let key = (user_canister.into(), file_id);
-            if !file_shares.contains_key(&key) {
-                return 0;
-            }
+        Self::remove_from_files_shares(user, user_canister, file_id);
+    }
 
-            // update
-            let mut entry = file_shares
-                .get(&key)
-                .expect("file shares entry must exist at this point");
+    /// Decline a file share for a user for the provided user canister.
+    ///
+    /// The share is kept as [`ShareState::Declined`] for the user, but the user is removed from
+    /// the users the file is shared with.
+    ///
+    /// Returns `false` if the file is not shared with the user.
+    pub fn decline_share(user: Principal, user_canister: Principal, file_id: FileId) -> bool {
+        if !Self::set_share_state(user, user_canister, file_id, ShareState::Declined) {
+            return false;
+        }
 
-            entry.0.remove(&user);
-            if entry.0.is_empty() {
-                file_shares.remove(&key);
-                0
-            } else {
-                let new_len = entry.0.len();
-                file_shares.insert(key, entry);
-                new_len
+        Self::remove_from_files_shares(user, user_canister, file_id);
+
+        true
+    }
+
+    /// Set the [`ShareState`] of a file shared with a user for the provided user canister.
+    ///
+    /// Returns `false` if the file is not shared with the user.
+    pub fn set_share_state(
+        user: Principal,
+        user_canister: Principal,
+        file_id: FileId,
+        state: ShareState,
+    ) -> bool {
+        SHARED_FILES.with_borrow_mut(|shared_files| {
+            let storable_user = StorablePrincipal::from(user);
+            let Some(mut user_shared_files) = shared_files.get(&storable_user) else {
+                return false;
+            };
+
+            if !user_shared_files.set_state(user_canister, file_id, state) {
+                return false;
             }
-        });
 
-        // remove the file metadata if there are no more shares
-        if shares == 0 {
-            SHARED_FILES_METADATA.with_borrow_mut(|shared_files_metadata| {
-                shared_files_metadata.remove(&(user_canister.into(), file_id));
-            });
-        }
+            shared_files.insert(storable_user, user_shared_files);
+            true
+        })
+    }
+
+    /// Get the [`ShareState`] of a file shared with a user for the provided user canister.
+    pub fn get_share_state(
+        user: Principal,
+        user_canister: Principal,
+        file_id: FileId,
+    ) -> Option<ShareState> {
+        SHARED_FILES.with_borrow(|shared_files| {
+            shared_files
+                .get(&StorablePrincipal::from(user))
+                .and_then(|user_shared_files| user_shared_files.get_state(user_canister, file_id))
+        })
     }
 
-    /// For a user, get the list of file IDs shared for each user canister.
-    pub fn get_shared_files(user: Principal) -> HashMap<Principal, HashSet<FileId>> {
+    /// For a user, get the list of file IDs shared for each user canister, along with their state.
+    pub fn get_shared_files(user: Principal) -> HashMap<Principal, HashMap<FileId, ShareState>> {
         SHARED_FILES.with_borrow(|shared_files| {
             let storable_user = StorablePrincipal::from(user);
 
@@ -172,6 +197,138 @@ impl SharedFilesStorage {
                 .unwrap_or_default()
         })
     }
+
+    /// Returns the files of a user canister with the users they're shared with.
+    ///
+    /// Declined shares are not returned.
+    pub fn user_canister_shares(user_canister: Principal) -> Vec<(FileId, HashSet<Principal>)> {
+        let user_canister = StorablePrincipal::from(user_canister);
+        FILES_SHARES.with_borrow(|file_shares| {
+            file_shares
+                .range((user_canister, 0)..=(user_canister, FileId::MAX))
+                .map(|((_, file_id), entry)| (file_id, entry.0))
+                .collect()
+        })
+    }
+
+    /// Returns up to `limit` files of a user canister with the users they're shared with, ordered
+    /// by file ID, starting after the `cursor` file.
+    ///
+    /// Returns the cursor of the next page too, if there are more files.
+    ///
+    /// Declined shares are not returned.
+    pub fn user_canister_shares_after(
+        user_canister: Principal,
+        cursor: Option<FileId>,
+        limit: u64,
+    ) -> (Vec<(FileId, HashSet<Principal>)>, Option<FileId>) {
+        let user_canister = StorablePrincipal::from(user_canister);
+        let start = match cursor {
+            Some(FileId::MAX) => return (vec![], None),
+            Some(cursor) => cursor + 1,
+            None => 0,
+        };
+        let mut files = FILES_SHARES.with_borrow(|file_shares| {
+            file_shares
+                .range((user_canister, start)..=(user_canister, FileId::MAX))
+                .take(limit as usize + 1)
+                .map(|((_, file_id), entry)| (file_id, entry.0))
+                .collect::<Vec<_>>()
+        });
+        if files.len() as u64 <= limit {
+            return (files, None);
+        }
+
+        files.truncate(limit as usize);
+        let next = files.last().map(|(file_id, _)| *file_id);
+        (files, next)
+    }
+
+    /// Returns the users the files of each user canister are shared with.
+    ///
+    /// Declined shares are not returned.
+    pub fn recipients() -> BTreeMap<Principal, HashSet<Principal>> {
+        FILES_SHARES.with_borrow(|file_shares| {
+            let mut recipients = BTreeMap::<Principal, HashSet<Principal>>::new();
+            for ((user_canister, _), entry) in file_shares.iter() {
+                recipients
+                    .entry(user_canister.0)
+                    .or_default()
+                    .extend(entry.0);
+            }
+
+            recipients
+        })
+    }
+
+    /// Returns the number of entries in each share index.
+    ///
+    /// Returns the number of users with shared files, the number of shared files with metadata,
+    /// and the number of shared files with their recipients.
+    pub fn index_sizes() -> (u64, u64, u64) {
+        (
+            SHARED_FILES.with_borrow(|shared_files| shared_files.len()),
+            SHARED_FILES_METADATA.with_borrow(|metadata| metadata.len()),
+            FILES_SHARES.with_borrow(|file_shares| file_shares.len()),
+        )
+    }
+
+    /// Returns a range of the files of a user canister with the users they're shared with,
+    /// ordered by file ID, along with the total number of shared files.
+    ///
+    /// Declined shares are not returned.
+    pub fn user_canister_shares_in_range(
+        user_canister: Principal,
+        offset: u64,
+        limit: u64,
+    ) -> (Vec<(FileId, HashSet<Principal>)>, u64) {
+        let user_canister = StorablePrincipal::from(user_canister);
+        FILES_SHARES.with_borrow(|file_shares| {
+            let range = || file_shares.range((user_canister, 0)..=(user_canister, FileId::MAX));
+            let files = range()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .map(|((_, file_id), entry)| (file_id, entry.0))
+                .collect();
+
+            (files, range().count() as u64)
+        })
+    }
+
+    /// Remove the user from the users a file is shared with.
+    ///
+    /// If the file is not shared with anyone else, its metadata are removed too.
+    fn remove_from_files_shares(user: Principal, user_canister: Principal, file_id: FileId) {
+        // remove the user from the file shares; if the hashset is empty, remove the entry
+        let shares = FILES_SHARES.with_borrow_mut(|file_shares| {
+            let key = (user_canister.into(), file_id);
+            if !file_shares.contains_key(&key) {
+                return 0;
+            }
+
+            // update
+            let mut entry = file_shares
+                .get(&key)
+                .expect("file shares entry must exist at this point");
+
+            entry.0.remove(&user);
+            if entry.0.is_empty() {
+                file_shares.remove(&key);
+                0
+            } else {
+                let new_len = entry.0.len();
+                file_shares.insert(key, entry);
+                new_len
+            }
+        });
+
+        // remove the file metadata if there are no more shares
+        if shares == 0 {
+            SHARED_FILES_METADATA.with_borrow_mut(|shared_files_metadata| {
+                shared_files_metadata.remove(&(user_canister.into(), file_id));
+            });
+        }
+    }
 }
 
 #[cfg(test)]
@@ -218,13 +375,13 @@ mod test {
         assert_eq!(alice_files.len(), 2);
         assert!(alice_files.contains_key(&user_canister_a));
         assert!(alice_files.contains_key(&user_canister_b));
-        assert!(alice_files[&user_canister_a].contains(&1));
-        assert!(alice_files[&user_canister_b].contains(&2));
+        assert!(alice_files[&user_canister_a].contains_key(&1));
+        assert!(alice_files[&user_canister_b].contains_key(&2));
 
         let bob_files = SharedFilesStorage::get_shared_files(bob);
         assert_eq!(bob_files.len(), 1);
         assert!(bob_files.contains_key(&user_canister_a));
-        assert!(bob_files[&user_canister_a].contains(&1));
+        assert!(bob_files[&user_canister_a].contains_key(&1));
     }
 
     #[test]
@@ -257,8 +414,8 @@ mod test {
         let alice_files = SharedFilesStorage::get_shared_files(alice);
         assert_eq!(alice_files.len(), 1);
         assert!(alice_files.contains_key(&user_canister_a));
-        assert!(!alice_files[&user_canister_a].contains(&1));
-        assert!(alice_files[&user_canister_a].contains(&2));
+        assert!(!alice_files[&user_canister_a].contains_key(&1));
+        assert!(alice_files[&user_canister_a].contains_key(&2));
 
         // revoke the last file
         SharedFilesStorage::revoke_share(alice, user_canister_a, 2);
@@ -339,4 +496,151 @@ mod test {
         });
         assert!(metadata.is_none());
     }
+
+    #[test]
+    fn test_should_get_user_canister_shares() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let user_canister_a = Principal::from_slice(&[3; 29]);
+        let user_canister_b = Principal::from_slice(&[4; 29]);
+        let metadata = ShareFileMetadata {
+            file_name: "test.txt".to_string(),
+        };
+
+        SharedFilesStorage::share_file(alice, user_canister_a, 1, metadata.clone());
+        SharedFilesStorage::share_file(bob, user_canister_a, 1, metadata.clone());
+        SharedFilesStorage::share_file(bob, user_canister_a, 2, metadata.clone());
+        SharedFilesStorage::share_file(alice, user_canister_b, 1, metadata);
+        SharedFilesStorage::decline_share(bob, user_canister_a, 2);
+
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares(user_canister_a),
+            vec![(1, HashSet::from([alice, bob]))]
+        );
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares(user_canister_b),
+            vec![(1, HashSet::from([alice]))]
+        );
+        assert_eq!(
+            SharedFilesStorage::recipients(),
+            BTreeMap::from([
+                (user_canister_a, HashSet::from([alice, bob])),
+                (user_canister_b, HashSet::from([alice])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_should_get_user_canister_shares_in_range() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let user_canister_a = Principal::from_slice(&[3; 29]);
+        let user_canister_b = Principal::from_slice(&[4; 29]);
+        let metadata = ShareFileMetadata {
+            file_name: "test.txt".to_string(),
+        };
+
+        for file_id in 1..=3 {
+            SharedFilesStorage::share_file(alice, user_canister_a, file_id, metadata.clone());
+        }
+        SharedFilesStorage::share_file(alice, user_canister_b, 1, metadata);
+
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares_in_range(user_canister_a, 1, 1),
+            (vec![(2, HashSet::from([alice]))], 3)
+        );
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares_in_range(user_canister_a, 2, 10),
+            (vec![(3, HashSet::from([alice]))], 3)
+        );
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares_in_range(user_canister_b, 1, 10),
+            (vec![], 1)
+        );
+    }
+
+    #[test]
+    fn test_should_get_user_canister_shares_after_cursor() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let user_canister = Principal::from_slice(&[3; 29]);
+        let metadata = ShareFileMetadata {
+            file_name: "test.txt".to_string(),
+        };
+        for file_id in 1..=3 {
+            SharedFilesStorage::share_file(alice, user_canister, file_id, metadata.clone());
+        }
+
+        let shares = |file_ids: &[FileId]| {
+            file_ids
+                .iter()
+                .map(|file_id| (*file_id, HashSet::from([alice])))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares_after(user_canister, None, 2),
+            (shares(&[1, 2]), Some(2))
+        );
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares_after(user_canister, Some(2), 2),
+            (shares(&[3]), None)
+        );
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares_after(user_canister, None, 3),
+            (shares(&[1, 2, 3]), None)
+        );
+        assert_eq!(
+            SharedFilesStorage::user_canister_shares_after(user_canister, Some(FileId::MAX), 3),
+            (vec![], None)
+        );
+    }
+
+    #[test]
+    fn test_should_decline_share() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let user_canister_a = Principal::from_slice(&[3; 29]);
+
+        SharedFilesStorage::share_file(
+            alice,
+            user_canister_a,
+            1,
+            ShareFileMetadata {
+                file_name: "test.txt".to_string(),
+            },
+        );
+        SharedFilesStorage::share_file(
+            bob,
+            user_canister_a,
+            1,
+            ShareFileMetadata {
+                file_name: "test.txt".to_string(),
+            },
+        );
+        assert_eq!(
+            SharedFilesStorage::get_share_state(alice, user_canister_a, 1),
+            Some(ShareState::Pending)
+        );
+
+        // cannot decline a file which is not shared
+        assert!(!SharedFilesStorage::decline_share(
+            alice,
+            user_canister_a,
+            2
+        ));
+
+        // decline for alice
+        assert!(SharedFilesStorage::decline_share(alice, user_canister_a, 1));
+        assert_eq!(
+            SharedFilesStorage::get_share_state(alice, user_canister_a, 1),
+            Some(ShareState::Declined)
+        );
+        let shared_with = SharedFilesStorage::shared_with(user_canister_a, 1);
+        assert_eq!(shared_with.len(), 1);
+        assert!(shared_with.contains(&bob));
+        assert!(SharedFilesStorage::get_file_metadata(user_canister_a, 1).is_some());
+
+        // decline for bob; metadata are removed
+        assert!(SharedFilesStorage::decline_share(bob, user_canister_a, 1));
+        assert!(SharedFilesStorage::shared_with(user_canister_a, 1).is_empty());
+        assert!(SharedFilesStorage::get_file_metadata(user_canister_a, 1).is_none());
+    }
 }
@@ -1,26 +1,47 @@
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use candid::Principal;
-use did::orchestrator::FileId;
+use did::orchestrator::{FileId, ShareState};
 use ic_stable_structures::Storable;
 use ic_stable_structures::storable::Bound;
 
+use crate::utils::trap;
+
+const SHARE_STATE_PENDING: u8 = 0;
+const SHARE_STATE_ACCEPTED: u8 = 1;
+const SHARE_STATE_DECLINED: u8 = 2;
+const SHARE_STATE_HIDDEN: u8 = 3;
+
+/// Leading tag of the versioned encoding.
+///
+/// The legacy encoding starts with the length of the map, which never gets this large.
+const VERSIONED_TAG: u64 = u64::MAX;
+/// Version of the encoding with the state of each share.
+const ENCODING_VERSION_SHARE_STATE: u8 = 1;
+
 /// Map of shared files for each user.
 ///
-/// Association between the user canister and the list of file IDs shared for that user.
+/// Association between the user canister and the file IDs shared for that user, along with the
+/// [`ShareState`] of each share.
 ///
 /// ## Encoding
 ///
-/// - The first 8 bytes are the length of the map.
+/// - 8 bytes: [`VERSIONED_TAG`].
+/// - 1 byte: version of the encoding.
+/// - 8 bytes: length of the map.
 /// - For each user canister:
 ///  - 1 byte: length of the user canister ID.
 ///  - N bytes: user canister ID.
 ///  - 8 bytes: length of the file ID list.
 ///  - For each file ID:
 ///    - 8 byte: file ID
+///    - 1 byte: share state
+///
+/// The legacy encoding has no tag nor version, and no share state after each file ID; its shares
+/// are decoded as [`ShareState::Accepted`], since they were all shown to the users.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
-pub struct UserSharedFiles(HashMap<Principal, HashSet<FileId>>);
+pub struct UserSharedFiles(HashMap<Principal, HashMap<FileId, ShareState>>);
 
 impl UserSharedFiles {
     /// Returns whether the map is empty.
@@ -29,8 +50,15 @@ impl UserSharedFiles {
     }
 
     /// Insert a file ID into the list of shared files for a user.
+    ///
+    /// New shares are [`ShareState::Pending`]. Sharing again a file which had been declined
+    /// makes it pending again, while the state of any other existing share is kept.
     pub fn insert_file(&mut self, user: Principal, file_id: FileId) {
-        self.0.entry(user).or_default().insert(file_id);
+        let state = self.0.entry(user).or_default().entry(file_id).or_default();
+
+        if *state == ShareState::Declined {
+            *state = ShareState::Pending;
+        }
     }
 
     /// Get the list of file IDs shared for a user.
@@ -45,14 +73,55 @@ impl UserSharedFiles {
         }
     }
 
-    /// Get the list of file IDs shared for each user canister.
-    pub fn get_files(&self) -> HashMap<Principal, HashSet<FileId>> {
-        let mut files = HashMap::new();
-        for (user, file_ids) in &self.0 {
-            files.insert(*user, file_ids.clone());
+    /// Get the state of a shared file.
+    pub fn get_state(&self, user: Principal, file_id: FileId) -> Option<ShareState> {
+        self.0
+            .get(&user)
+            .and_then(|files| files.get(&file_id))
+            .copied()
+    }
+
+    /// Set the state of a shared file.
+    ///
+    /// Returns `false` if the file is not shared.
+    pub fn set_state(&mut self, user: Principal, file_id: FileId, state: ShareState) -> bool {
+        match self
+            .0
+            .get_mut(&user)
+            .and_then(|files| files.get_mut(&file_id))
+        {
+            Some(current) => {
+                *current = state;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the file IDs shared for each user canister, along with their state.
+    pub fn get_files(&self) -> HashMap<Principal, HashMap<FileId, ShareState>> {
+        self.0.clone()
+    }
+
+    /// Encode a [`ShareState`] into a byte.
+    fn encode_share_state(state: ShareState) -> u8 {
+        match state {
+            ShareState::Pending => SHARE_STATE_PENDING,
+            ShareState::Accepted => SHARE_STATE_ACCEPTED,
+            ShareState::Declined => SHARE_STATE_DECLINED,
+            ShareState::Hidden => SHARE_STATE_HIDDEN,
         }
+    }
 
-        files
+    /// Decode a [`ShareState`] from a byte.
+    fn decode_share_state(byte: u8) -> ShareState {
+        match byte {
+            SHARE_STATE_PENDING => ShareState::Pending,
+            SHARE_STATE_ACCEPTED => ShareState::Accepted,
+            SHARE_STATE_DECLINED => ShareState::Declined,
+            SHARE_STATE_HIDDEN => ShareState::Hidden,
+            _ => trap("Failed to decode UserSharedFiles: invalid share state"),
+        }
     }
 }
 
@@ -61,6 +130,9 @@ impl Storable for UserSharedFiles {
 
     fn to_bytes(&self) -> Cow<[u8]> {
         let mut bytes = vec![];
+        // write the tag and the version of the encoding
+        bytes.extend_from_slice(&VERSIONED_TAG.to_le_bytes());
+        bytes.push(ENCODING_VERSION_SHARE_STATE);
         // write the number of user canisters
         let len = self.0.len() as u64;
         bytes.extend_from_slice(&len.to_le_bytes());
@@ -76,9 +148,10 @@ impl Storable for UserSharedFiles {
             // write the number of file IDs
             let file_ids_len = file_ids.len() as u64;
             bytes.extend_from_slice(&file_ids_len.to_le_bytes());
-            // write the file IDs
-            for file_id in file_ids {
+            // write the file IDs and their state
+            for (file_id, state) in file_ids {
                 bytes.extend_from_slice(&file_id.to_le_bytes());
+                bytes.push(Self::encode_share_state(*state));
             }
         }
 
@@ -86,10 +159,25 @@ impl Storable for UserSharedFiles {
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut offset = 0;
+        // read the version of the encoding, if tagged
+        let tag = u64::from_le_bytes(bytes[0..8].try_into().expect("invalid user map len"));
+        let with_state = if tag == VERSIONED_TAG {
+            if bytes[8] != ENCODING_VERSION_SHARE_STATE {
+                trap("Failed to decode UserSharedFiles: unknown encoding version");
+            }
+            offset += 9;
+            true
+        } else {
+            false
+        };
         // read the number of user canisters
-        let map_len =
-            u64::from_le_bytes(bytes[0..8].try_into().expect("invalid user map len")) as usize;
-        let mut offset = 8;
+        let map_len = u64::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("invalid user map len"),
+        ) as usize;
+        offset += 8;
         // allocate map
         let mut map = HashMap::with_capacity(map_len);
         // iterate over the user canisters
@@ -109,8 +197,8 @@ impl Storable for UserSharedFiles {
             ) as usize;
             offset += 8;
             // allocate file IDs
-            let mut file_ids = HashSet::with_capacity(file_ids_len);
-            // read the file IDs
+            let mut file_ids = HashMap::with_capacity(file_ids_len);
+            // read the file IDs and their state
             for _ in 0..file_ids_len {
                 let file_id = FileId::from_le_bytes(
                     bytes[offset..offset + 8]
@@ -118,7 +206,13 @@ impl Storable for UserSharedFiles {
                         .expect("Invalid file ID length"),
                 );
                 offset += 8;
-                file_ids.insert(file_id);
+                let state = if with_state {
+                    offset += 1;
+                    Self::decode_share_state(bytes[offset - 1])
+                } else {
+                    ShareState::Accepted
+                };
+                file_ids.insert(file_id, state);
             }
 
             // insert the user canister and file IDs into the map
@@ -144,14 +238,14 @@ mod test {
 
         assert_eq!(user_shared_files.0.len(), 1);
         assert!(user_shared_files.0.contains_key(&user));
-        assert!(user_shared_files.0[&user].contains(&file_id));
+        assert!(user_shared_files.0[&user].contains_key(&file_id));
 
         // insert another file ID
         user_shared_files.insert_file(user, 2);
         assert_eq!(user_shared_files.0.len(), 1);
         assert!(user_shared_files.0.contains_key(&user));
-        assert!(user_shared_files.0[&user].contains(&file_id));
-        assert!(user_shared_files.0[&user].contains(&2));
+        assert!(user_shared_files.0[&user].contains_key(&file_id));
+        assert!(user_shared_files.0[&user].contains_key(&2));
     }
 
     #[test]
@@ -175,9 +269,9 @@ mod test {
         // check that user canister is still present
         assert!(user_shared_files.0.contains_key(&user));
         // check that file ID 1 is removed
-        assert!(!user_shared_files.0[&user].contains(&file_id));
+        assert!(!user_shared_files.0[&user].contains_key(&file_id));
         // check that file ID 2 is still present
-        assert!(user_shared_files.0[&user].contains(&2));
+        assert!(user_shared_files.0[&user].contains_key(&2));
     }
 
     #[test]
@@ -196,12 +290,58 @@ mod test {
 
         assert_eq!(user_canisters_shares.len(), 2);
         assert!(user_canisters_shares.contains_key(&user));
-        assert!(user_canisters_shares[&user].contains(&1));
-        assert!(user_canisters_shares[&user].contains(&2));
+        assert!(user_canisters_shares[&user].contains_key(&1));
+        assert!(user_canisters_shares[&user].contains_key(&2));
 
         assert!(user_canisters_shares.contains_key(&user_2));
-        assert!(user_canisters_shares[&user_2].contains(&1));
-        assert!(user_canisters_shares[&user_2].contains(&2));
+        assert!(user_canisters_shares[&user_2].contains_key(&1));
+        assert!(user_canisters_shares[&user_2].contains_key(&2));
+    }
+
+    #[test]
+    fn test_should_set_share_state() {
+        let mut user_shared_files = UserSharedFiles::default();
+        let user = Principal::from_slice(&[1; 29]);
+
+        // new shares are pending
+        user_shared_files.insert_file(user, 1);
+        assert_eq!(
+            user_shared_files.get_state(user, 1),
+            Some(ShareState::Pending)
+        );
+
+        // accept
+        assert!(user_shared_files.set_state(user, 1, ShareState::Accepted));
+        assert_eq!(
+            user_shared_files.get_state(user, 1),
+            Some(ShareState::Accepted)
+        );
+
+        // sharing again keeps the state
+        user_shared_files.insert_file(user, 1);
+        assert_eq!(
+            user_shared_files.get_state(user, 1),
+            Some(ShareState::Accepted)
+        );
+
+        // cannot set state of a file which is not shared
+        assert!(!user_shared_files.set_state(user, 2, ShareState::Accepted));
+        assert_eq!(user_shared_files.get_state(user, 2), None);
+    }
+
+    #[test]
+    fn test_should_make_declined_share_pending_when_shared_again() {
+        let mut user_shared_files = UserSharedFiles::default();
+        let user = Principal::from_slice(&[1; 29]);
+
+        user_shared_files.insert_file(user, 1);
+        assert!(user_shared_files.set_state(user, 1, ShareState::Declined));
+
+        user_shared_files.insert_file(user, 1);
+        assert_eq!(
+            user_shared_files.get_state(user, 1),
+            Some(ShareState::Pending)
+        );
     }
 
     #[test]
@@ -215,9 +355,44 @@ mod test {
 
         user_shared_files.insert_file(user_2, 1);
         user_shared_files.insert_file(user_2, 2);
+        user_shared_files.insert_file(user_2, 3);
+        user_shared_files.insert_file(user_2, 4);
+
+        assert!(user_shared_files.set_state(user, 2, ShareState::Accepted));
+        assert!(user_shared_files.set_state(user_2, 1, ShareState::Declined));
+        assert!(user_shared_files.set_state(user_2, 2, ShareState::Hidden));
 
         let bytes = user_shared_files.to_bytes();
         let decoded = UserSharedFiles::from_bytes(bytes);
         assert_eq!(decoded, user_shared_files);
+
+        let empty = UserSharedFiles::default();
+        assert_eq!(UserSharedFiles::from_bytes(empty.to_bytes()), empty);
+    }
+
+    #[test]
+    fn test_should_decode_legacy_encoding() {
+        let user_canister = Principal::from_slice(&[1; 29]);
+        // one user canister sharing the files 1 and 2, without share states
+        let mut bytes = 1u64.to_le_bytes().to_vec();
+        bytes.push(29);
+        bytes.extend_from_slice(user_canister.as_slice());
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+
+        let decoded = UserSharedFiles::from_bytes(bytes.into());
+        assert_eq!(
+            decoded.get_state(user_canister, 1),
+            Some(ShareState::Accepted)
+        );
+        assert_eq!(
+            decoded.get_state(user_canister, 2),
+            Some(ShareState::Accepted)
+        );
+        assert_eq!(decoded.get_files()[&user_canister].len(), 2);
+
+        // an empty map
+        assert!(UserSharedFiles::from_bytes(0u64.to_le_bytes().to_vec().into()).is_empty());
     }
 }
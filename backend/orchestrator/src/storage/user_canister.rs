@@ -1,6 +1,7 @@
 mod create_state;
 
 use std::cell::RefCell;
+use std::ops::Bound;
 
 use candid::Principal;
 use did::StorablePrincipal;
@@ -9,8 +10,8 @@ use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
 
 pub use self::create_state::UserCanisterCreateState;
 use crate::storage::memory::{
-    MEMORY_MANAGER, USER_CANISTER_CREATE_STATES_MEMORY_ID, USER_CANISTERS_INDEX_MEMORY_ID,
-    USER_CANISTERS_MEMORY_ID,
+    MEMORY_MANAGER, USER_CANISTER_CREATE_STATES_MEMORY_ID, USER_CANISTER_OWNERS_MEMORY_ID,
+    USER_CANISTERS_INDEX_MEMORY_ID, USER_CANISTERS_MEMORY_ID,
 };
 
 thread_local! {
@@ -28,6 +29,13 @@ thread_local! {
         RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USER_CANISTERS_INDEX_MEMORY_ID)))
     );
 
+    /// Owners of the user canisters.
+    ///
+    /// A map between the user canister principal and the owner principal.
+    static USER_CANISTER_OWNERS: RefCell<StableBTreeMap<StorablePrincipal, StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USER_CANISTER_OWNERS_MEMORY_ID)))
+    );
+
     /// Users storage map
     static USER_CANISTER_CREATE_STATES: RefCell<StableBTreeMap<StorablePrincipal, UserCanisterCreateState, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USER_CANISTER_CREATE_STATES_MEMORY_ID)))
@@ -58,6 +66,61 @@ impl UserCanisterStorage {
         });
     }
 
+    /// Get the users whose user canister creation failed, ordered by principal, along with the
+    /// reason of the failure.
+    ///
+    /// The users are returned after the `cursor`, if any, up to `limit`.
+    ///
+    /// Returns the page of failed creations and the cursor of the next page if there are more
+    /// failed creations to fetch.
+    pub fn get_failed_create_states(
+        cursor: Option<Principal>,
+        limit: u64,
+    ) -> (Vec<(Principal, String)>, Option<Principal>) {
+        let start = match cursor {
+            Some(cursor) => Bound::Excluded(StorablePrincipal::from(cursor)),
+            None => Bound::Unbounded,
+        };
+        let mut page = USER_CANISTER_CREATE_STATES.with_borrow(|states| {
+            states
+                .range((start, Bound::Unbounded))
+                .filter_map(|(principal, state)| match state {
+                    UserCanisterCreateState::Failed { reason } => Some((principal.0, reason)),
+                    _ => None,
+                })
+                .take((limit as usize).saturating_add(1))
+                .collect::<Vec<_>>()
+        });
+
+        if page.len() as u64 <= limit {
+            return (page, None);
+        }
+        page.truncate(limit as usize);
+        let next = page.last().map(|(principal, _)| *principal);
+
+        (page, next)
+    }
+
+    /// Count the user canister creations.
+    ///
+    /// Returns the number of creations in progress and the number of failed creations.
+    pub fn count_create_states() -> (u64, u64) {
+        USER_CANISTER_CREATE_STATES.with_borrow(|states| {
+            states
+                .iter()
+                .fold((0, 0), |(pending, failed), (_, state)| match state {
+                    UserCanisterCreateState::Failed { .. } => (pending, failed + 1),
+                    UserCanisterCreateState::Ok { .. } => (pending, failed),
+                    _ => (pending + 1, failed),
+                })
+        })
+    }
+
+    /// Get the number of created user canisters.
+    pub fn count_user_canisters() -> u64 {
+        USER_CANISTERS.with_borrow(|canisters| canisters.len())
+    }
+
     /// Set the user canister for a certain user.
     ///
     /// It also sets the user canister index.
@@ -71,6 +134,43 @@ impl UserCanisterStorage {
             index.insert(user_canister.into(), ());
         });
 
+        USER_CANISTER_OWNERS.with_borrow_mut(|owners| {
+            owners.insert(user_canister.into(), principal.into());
+        });
+
+        USER_CANISTER_CREATE_STATES.with_borrow_mut(|states| {
+            states.remove(&StorablePrincipal::from(principal));
+        });
+    }
+
+    /// Remove the user canister of a certain user from the user canisters index, so that it is no
+    /// longer allowed to act as a user canister.
+    ///
+    /// The user canister of the user is kept until [`Self::remove_user_canister`] is called.
+    pub fn remove_from_index(principal: Principal) {
+        let Some(user_canister) = Self::get_user_canister(principal) else {
+            return;
+        };
+
+        USER_CANISTER_INDEX.with_borrow_mut(|index| {
+            index.remove(&StorablePrincipal::from(user_canister));
+        });
+    }
+
+    /// Remove the user canister and the creation state of a certain user.
+    pub fn remove_user_canister(principal: Principal) {
+        let user_canister = USER_CANISTERS
+            .with_borrow_mut(|canisters| canisters.remove(&StorablePrincipal::from(principal)));
+
+        if let Some(user_canister) = user_canister {
+            USER_CANISTER_INDEX.with_borrow_mut(|index| {
+                index.remove(&user_canister);
+            });
+            USER_CANISTER_OWNERS.with_borrow_mut(|owners| {
+                owners.remove(&user_canister);
+            });
+        }
+
         USER_CANISTER_CREATE_STATES.with_borrow_mut(|states| {
             states.remove(&StorablePrincipal::from(principal));
         });
@@ -83,6 +183,26 @@ impl UserCanisterStorage {
             .map(|p| p.0)
     }
 
+    /// Get the owner of a user canister.
+    pub fn get_owner(user_canister: Principal) -> Option<Principal> {
+        USER_CANISTER_OWNERS
+            .with_borrow(|owners| owners.get(&StorablePrincipal::from(user_canister)))
+            .map(|p| p.0)
+    }
+
+    /// Index the owners of the user canisters.
+    ///
+    /// User canisters created before the owners were indexed are indexed here.
+    pub fn build_owners_index() {
+        USER_CANISTERS.with_borrow(|canisters| {
+            USER_CANISTER_OWNERS.with_borrow_mut(|owners| {
+                for (owner, user_canister) in canisters.iter() {
+                    owners.insert(user_canister, owner);
+                }
+            });
+        });
+    }
+
     /// Get whether the provided principal is a user canister.
     pub fn is_user_canister(principal: Principal) -> bool {
         USER_CANISTER_INDEX
@@ -128,6 +248,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_should_get_failed_create_states() {
+        for i in 1..=4 {
+            UserCanisterStorage::set_create_state(
+                Principal::from_slice(&[i; 29]),
+                UserCanisterCreateState::Failed {
+                    reason: format!("reason {i}"),
+                },
+            );
+        }
+        UserCanisterStorage::init_create_state(Principal::from_slice(&[5; 29]));
+        assert_eq!(UserCanisterStorage::count_create_states(), (1, 4));
+
+        let (page, next) = UserCanisterStorage::get_failed_create_states(None, 3);
+        assert_eq!(page.len(), 3);
+        assert_eq!(
+            page[0],
+            (Principal::from_slice(&[1; 29]), "reason 1".to_string())
+        );
+        assert_eq!(next, Some(Principal::from_slice(&[3; 29])));
+
+        let (page, next) = UserCanisterStorage::get_failed_create_states(next, 3);
+        assert_eq!(
+            page,
+            vec![(Principal::from_slice(&[4; 29]), "reason 4".to_string())]
+        );
+        assert_eq!(next, None);
+    }
+
     #[test]
     fn test_should_set_user_canister() {
         let principal = Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap();
@@ -145,5 +294,46 @@ mod test {
         ));
 
         assert_eq!(UserCanisterStorage::get_create_state(principal), None);
+        assert_eq!(
+            UserCanisterStorage::get_owner(user_canister),
+            Some(principal)
+        );
+    }
+
+    #[test]
+    fn test_should_build_owners_index() {
+        let principal = Principal::from_slice(&[1; 29]);
+        let user_canister = Principal::from_slice(&[2; 29]);
+        // user canister set before the owners were indexed
+        USER_CANISTERS.with_borrow_mut(|canisters| {
+            canisters.insert(principal.into(), user_canister.into());
+        });
+        assert_eq!(UserCanisterStorage::get_owner(user_canister), None);
+
+        UserCanisterStorage::build_owners_index();
+        assert_eq!(
+            UserCanisterStorage::get_owner(user_canister),
+            Some(principal)
+        );
+    }
+
+    #[test]
+    fn test_should_remove_user_canister() {
+        let principal = Principal::from_slice(&[1; 29]);
+        let user_canister = Principal::from_slice(&[2; 29]);
+        UserCanisterStorage::set_user_canister(principal, user_canister);
+
+        UserCanisterStorage::remove_from_index(principal);
+        assert!(!UserCanisterStorage::is_user_canister(user_canister));
+        assert_eq!(
+            UserCanisterStorage::get_user_canister(principal),
+            Some(user_canister)
+        );
+
+        UserCanisterStorage::init_create_state(principal);
+        UserCanisterStorage::remove_user_canister(principal);
+        assert_eq!(UserCanisterStorage::get_user_canister(principal), None);
+        assert_eq!(UserCanisterStorage::get_create_state(principal), None);
+        assert_eq!(UserCanisterStorage::get_owner(user_canister), None);
     }
 }
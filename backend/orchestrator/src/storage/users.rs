@@ -1,13 +1,26 @@
+mod username_trigram;
+
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
 
 use candid::Principal;
 use did::StorablePrincipal;
-use did::orchestrator::User;
+use did::orchestrator::{
+    PublicKey, PublicUser, User, UserProfile, UsernameCollision, username_key,
+};
 use ic_stable_structures::memory_manager::VirtualMemory;
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
 
-use crate::storage::memory::{MEMORY_MANAGER, USER_STORAGE_MEMORY_ID, USERNAMES_MEMORY_ID};
+use self::username_trigram::{TRIGRAM_SIZE, UsernameTrigram};
+use crate::storage::memory::{
+    MEMORY_MANAGER, PUBLIC_KEY_VERSIONS_MEMORY_ID, USER_PROFILES_MEMORY_ID, USER_STORAGE_MEMORY_ID,
+    USERNAME_COLLISIONS_MEMORY_ID, USERNAME_HISTORY_MEMORY_ID, USERNAME_TRIGRAMS_MEMORY_ID,
+    USERNAMES_MEMORY_ID,
+};
+
+/// Number of previous usernames kept for each user; older ones are released.
+const MAX_USERNAME_HISTORY: usize = 5;
 
 thread_local! {
     /// Users storage map
@@ -15,15 +28,49 @@ thread_local! {
         RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USER_STORAGE_MEMORY_ID)))
     );
 
-    /// Usernames storage map.
+    /// Usernames storage map, keyed by the canonical key of the usernames, mapped to the user who
+    /// has the username.
     ///
     /// We use another map to index usernames, because we need to expose an endpoint to check if a username exists.
     /// And checking if a username exists in the users storage is not efficient. O(n), while checking in the
-    /// usernames storage is O(log(n)).
-    static USERNAMES: RefCell<StableBTreeMap<String, (), VirtualMemory<DefaultMemoryImpl>>> =
+    /// usernames storage is O(log(n)). If several users have a username with the same key, it is
+    /// mapped to one of them.
+    static USERNAMES: RefCell<StableBTreeMap<String, StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USERNAMES_MEMORY_ID)))
     );
 
+    /// Profiles of the users.
+    static USER_PROFILES: RefCell<StableBTreeMap<StorablePrincipal, UserProfile, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USER_PROFILES_MEMORY_ID)))
+    );
+
+    /// Previous usernames, keyed by their canonical key, mapped to the user who had them.
+    ///
+    /// They stay reserved to that user, so that searches for them can be redirected.
+    static USERNAME_HISTORY: RefCell<StableBTreeMap<String, StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USERNAME_HISTORY_MEMORY_ID)))
+    );
+
+    /// Versions of the public keys of the users; a user without an entry has version 0.
+    static PUBLIC_KEY_VERSIONS: RefCell<StableBTreeMap<StorablePrincipal, u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(PUBLIC_KEY_VERSIONS_MEMORY_ID)))
+    );
+
+    /// Username search index.
+    ///
+    /// Indexes users by the trigrams of the canonical key of their username, so that searching
+    /// for the users whose username contains a query only goes through the users who have its
+    /// first trigram.
+    static USERNAME_TRIGRAMS: RefCell<StableBTreeMap<UsernameTrigram, (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USERNAME_TRIGRAMS_MEMORY_ID)))
+    );
+
+    /// Users whose username has the same canonical key as another user's, mapped to the key.
+    ///
+    /// Such users were registered before usernames were compared by their canonical key.
+    static USERNAME_COLLISIONS: RefCell<StableBTreeMap<StorablePrincipal, String, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(USERNAME_COLLISIONS_MEMORY_ID)))
+    );
 }
 
 /// Accessor to the users storage
@@ -51,35 +98,97 @@ impl UserStorage {
         with_user(principal, |user| user)
     }
 
-    /// Get all users in a range.
+    /// Search the users, ordered by principal, whose username contains the given query,
+    /// regardless of its case.
     ///
-    /// # Arguments
+    /// Without a query, every user is returned. The `hidden` users are left out. The users are
+    /// returned after the `cursor`, if any, up to `limit`.
     ///
-    /// - `offset`: The offset to start from.
-    /// - `limit`: The maximum number of users to return.
-    /// - `filter`: A closure that takes a reference to a [`User`]
-    ///   and returns a boolean indicating whether the user should be included in the result.
-    pub fn get_users_in_range<F>(offset: u64, limit: u64, filter: F) -> HashMap<Principal, User>
-    where
-        F: Fn(&User) -> bool,
-    {
-        with_users_storage(|users| {
-            users
-                .iter()
-                .filter(|(_, user)| filter(user))
-                .skip(offset as usize)
-                .take(limit as usize)
-                .map(|(principal, user)| (principal.0, user.clone()))
-                .collect()
-        })
+    /// Returns the page of users, the total number of users matching the query, and the cursor
+    /// of the next page if there are more users to fetch.
+    pub fn search_users(
+        query: Option<&str>,
+        cursor: Option<Principal>,
+        limit: u64,
+        hidden: &BTreeSet<Principal>,
+    ) -> (Vec<(Principal, User)>, u64, Option<Principal>) {
+        let after_cursor = |principal: &Principal| cursor.is_none_or(|cursor| *principal > cursor);
+
+        let Some(query) = query else {
+            return with_users_storage(|users| {
+                let start = match cursor {
+                    Some(cursor) => Bound::Excluded(StorablePrincipal::from(cursor)),
+                    None => Bound::Unbounded,
+                };
+                let mut page = users
+                    .range((start, Bound::Unbounded))
+                    .filter(|(principal, _)| !hidden.contains(&principal.0))
+                    .take((limit as usize).saturating_add(1))
+                    .map(|(principal, user)| (principal.0, user))
+                    .collect::<Vec<_>>();
+                let next = Self::next_cursor(&mut page, limit);
+                let hidden_users = hidden
+                    .iter()
+                    .filter(|principal| users.contains_key(&StorablePrincipal::from(**principal)))
+                    .count() as u64;
+
+                (page, users.len() - hidden_users, next)
+            });
+        };
+
+        // usernames containing the query contain its first trigram
+        let query = username_key(query);
+        let Some(trigram) = query.as_bytes().first_chunk::<TRIGRAM_SIZE>().copied() else {
+            return (vec![], 0, None);
+        };
+
+        let mut page = vec![];
+        let mut total = 0;
+        USERNAME_TRIGRAMS.with_borrow(|trigrams| {
+            let candidates = trigrams
+                .range(UsernameTrigram::first(trigram)..)
+                .take_while(|(entry, _)| entry.trigram == trigram);
+            for (UsernameTrigram { principal, .. }, _) in candidates {
+                if hidden.contains(&principal) {
+                    continue;
+                }
+                let Some(user) = Self::get_user(&principal) else {
+                    continue;
+                };
+                if !username_key(&user.username).contains(&query) {
+                    continue;
+                }
+
+                total += 1;
+                if after_cursor(&principal) && page.len() <= limit as usize {
+                    page.push((principal, user));
+                }
+            }
+        });
+        let next = Self::next_cursor(&mut page, limit);
+
+        (page, total, next)
+    }
+
+    /// Truncate a page fetched with one more item than `limit`, returning the cursor of the next
+    /// page if there was such an item.
+    fn next_cursor(page: &mut Vec<(Principal, User)>, limit: u64) -> Option<Principal> {
+        if page.len() as u64 <= limit {
+            return None;
+        }
+
+        page.truncate(limit as usize);
+        page.last().map(|(principal, _)| *principal)
     }
 
-    /// Count the number of users that match a given filter.
-    pub fn count<F>(f: F) -> u64
-    where
-        F: Fn(&User) -> bool,
-    {
-        USERS_STORAGE.with_borrow(|users| users.iter().filter(|(_, user)| f(user)).count() as u64)
+    /// Get the user who has the given username, regardless of its case, along with their
+    /// principal.
+    pub fn get_user_by_username(username: &str) -> Option<(Principal, User)> {
+        let principal = USERNAMES
+            .with_borrow(|usernames| usernames.get(&username_key(username)))?
+            .0;
+
+        Self::get_user(&principal).map(|user| (principal, user))
     }
 
     /// Add a user to the storage.
@@ -95,25 +204,299 @@ impl UserStorage {
         }
 
         USERNAMES.with_borrow_mut(|usernames| {
-            usernames.insert(user.username.clone(), ());
+            usernames.insert(username_key(&user.username), principal.into());
         });
 
+        Self::index_username(principal, &user.username);
         USERS_STORAGE.with_borrow_mut(|users| {
             users.insert(StorablePrincipal::from(principal), user);
         });
     }
 
-    /// Checks whether a username exists in the storage
-    pub fn username_exists(username: &String) -> bool {
-        USERNAMES.with_borrow(|usernames| usernames.contains_key(username))
+    /// Remove a user from the storage, along with their profile and key version.
+    ///
+    /// Their username and previous usernames are released.
+    pub fn remove_user(principal: Principal) {
+        let storable_principal = StorablePrincipal::from(principal);
+        let Some(user) = USERS_STORAGE.with_borrow_mut(|users| users.remove(&storable_principal))
+        else {
+            return;
+        };
+
+        Self::release_username(principal, &username_key(&user.username));
+        Self::unindex_username(principal, &user.username);
+
+        let profile =
+            USER_PROFILES.with_borrow_mut(|profiles| profiles.remove(&storable_principal));
+        USERNAME_HISTORY.with_borrow_mut(|history| {
+            for username in profile.unwrap_or_default().previous_usernames {
+                history.remove(&username_key(&username));
+            }
+        });
+
+        PUBLIC_KEY_VERSIONS.with_borrow_mut(|versions| {
+            versions.remove(&storable_principal);
+        });
+    }
+
+    /// Set the public key of a user, increasing its version if the key changes.
+    ///
+    /// Returns the version of the public key, or `None` if the user doesn't exist.
+    pub fn set_public_key(principal: Principal, public_key: PublicKey) -> Option<u64> {
+        let storable_principal = StorablePrincipal::from(principal);
+        let mut user = Self::get_user(&principal)?;
+        let version = Self::get_public_key_version(&principal);
+        if user.public_key == public_key {
+            return Some(version);
+        }
+
+        user.public_key = public_key;
+        USERS_STORAGE.with_borrow_mut(|users| {
+            users.insert(storable_principal, user);
+        });
+        PUBLIC_KEY_VERSIONS.with_borrow_mut(|versions| {
+            versions.insert(storable_principal, version + 1);
+        });
+
+        Some(version + 1)
+    }
+
+    /// Get the version of the public key of a user.
+    pub fn get_public_key_version(principal: &Principal) -> u64 {
+        PUBLIC_KEY_VERSIONS
+            .with_borrow(|versions| versions.get(&StorablePrincipal::from(*principal)))
+            .unwrap_or_default()
+    }
+
+    /// Get a user by principal as [`PublicUser`], along with their profile and key version.
+    pub fn get_public_user(principal: &Principal) -> Option<PublicUser> {
+        Self::get_user(principal).map(|user| Self::to_public_user(*principal, user))
+    }
+
+    /// Make a [`PublicUser`] out of a user, along with their profile and key version.
+    pub fn to_public_user(principal: Principal, user: User) -> PublicUser {
+        PublicUser {
+            key_version: Self::get_public_key_version(&principal),
+            ..PublicUser::new(user, principal).with_profile(Self::get_profile(&principal))
+        }
+    }
+
+    /// Get the profile of a user; it is empty if it was never set.
+    pub fn get_profile(principal: &Principal) -> UserProfile {
+        USER_PROFILES
+            .with_borrow(|profiles| profiles.get(&StorablePrincipal::from(*principal)))
+            .unwrap_or_default()
+    }
+
+    /// Update the username and the display name of a user.
+    ///
+    /// The previous username is kept in the history of the user, and stays reserved to them; the
+    /// oldest usernames are released once the history is full. Changing only the case of the
+    /// username doesn't add it to the history.
+    ///
+    /// Returns `false` if the user doesn't exist.
+    pub fn update_profile(
+        principal: Principal,
+        username: String,
+        display_name: Option<String>,
+    ) -> bool {
+        let storable_principal = StorablePrincipal::from(principal);
+        let Some(mut user) = Self::get_user(&principal) else {
+            return false;
+        };
+        let mut profile = Self::get_profile(&principal);
+        profile.display_name = display_name.filter(|name| !name.is_empty());
+
+        if user.username != username {
+            let previous_username = std::mem::replace(&mut user.username, username.clone());
+            let (previous_key, key) = (username_key(&previous_username), username_key(&username));
+            if previous_key != key {
+                Self::release_username(principal, &previous_key);
+                USERNAMES.with_borrow_mut(|usernames| {
+                    usernames.insert(key.clone(), storable_principal);
+                });
+            }
+            Self::unindex_username(principal, &previous_username);
+            Self::index_username(principal, &username);
+            USERS_STORAGE.with_borrow_mut(|users| {
+                users.insert(storable_principal, user);
+            });
+
+            if previous_key != key {
+                USERNAME_HISTORY.with_borrow_mut(|history| {
+                    // the user may take back one of their previous usernames
+                    profile
+                        .previous_usernames
+                        .retain(|name| username_key(name) != key);
+                    history.remove(&key);
+
+                    profile.previous_usernames.push(previous_username);
+                    history.insert(previous_key, storable_principal);
+                    while profile.previous_usernames.len() > MAX_USERNAME_HISTORY {
+                        let released = profile.previous_usernames.remove(0);
+                        history.remove(&username_key(&released));
+                    }
+                });
+            }
+        }
+
+        USER_PROFILES.with_borrow_mut(|profiles| {
+            profiles.insert(storable_principal, profile);
+        });
+
+        true
+    }
+
+    /// Get the user who had the given username before, regardless of its case, if it is still
+    /// reserved to them.
+    pub fn get_previous_username_owner(username: &str) -> Option<Principal> {
+        USERNAME_HISTORY.with_borrow(|history| {
+            history
+                .get(&username_key(username))
+                .map(|principal| principal.0)
+        })
+    }
+
+    /// Checks whether a username can be taken by the given user.
+    ///
+    /// A username is available if nobody has it, and it is not reserved to another user who had
+    /// it before.
+    pub fn is_username_available(username: &str, principal: Principal) -> bool {
+        !Self::username_exists(username)
+            && Self::get_previous_username_owner(username).is_none_or(|owner| owner == principal)
+    }
+
+    /// Migrate the usernames to their canonical keys.
+    ///
+    /// The usernames and search indexes are rebuilt from the users, and previous usernames are
+    /// keyed by their canonical key. Users whose usernames have the same key are recorded as
+    /// colliding; they keep their username until they change it, and the key is mapped to the
+    /// first of them.
+    ///
+    /// Returns the collisions found.
+    pub fn migrate_usernames() -> Vec<UsernameCollision> {
+        USERNAMES.with_borrow_mut(|usernames| usernames.clear_new());
+        USERNAME_TRIGRAMS.with_borrow_mut(|trigrams| trigrams.clear_new());
+        USERNAME_COLLISIONS.with_borrow_mut(|collisions| collisions.clear_new());
+
+        let users = with_users_storage(|users| {
+            users
+                .iter()
+                .map(|(principal, user)| (principal.0, user.username))
+                .collect::<Vec<_>>()
+        });
+        let mut owners = HashMap::new();
+        for (principal, username) in users {
+            let key = username_key(&username);
+            if let Some(owner) = owners.get(&key) {
+                USERNAME_COLLISIONS.with_borrow_mut(|collisions| {
+                    collisions.insert(StorablePrincipal::from(*owner), key.clone());
+                    collisions.insert(StorablePrincipal::from(principal), key.clone());
+                });
+            } else {
+                owners.insert(key.clone(), principal);
+                USERNAMES.with_borrow_mut(|usernames| {
+                    usernames.insert(key, principal.into());
+                });
+            }
+            Self::index_username(principal, &username);
+        }
+
+        USERNAME_HISTORY.with_borrow_mut(|history| {
+            let previous_usernames = history.iter().collect::<Vec<_>>();
+            history.clear_new();
+            for (username, principal) in previous_usernames {
+                // current usernames take precedence over previous ones
+                let key = username_key(&username);
+                if !Self::username_exists(&key) && !history.contains_key(&key) {
+                    history.insert(key, principal);
+                }
+            }
+        });
+
+        Self::username_collisions()
+    }
+
+    /// Get the users whose usernames have the same canonical key.
+    pub fn username_collisions() -> Vec<UsernameCollision> {
+        let mut collisions = BTreeMap::<String, Vec<Principal>>::new();
+        USERNAME_COLLISIONS.with_borrow(|users| {
+            for (principal, key) in users.iter() {
+                collisions.entry(key).or_default().push(principal.0);
+            }
+        });
+
+        collisions
+            .into_iter()
+            .map(|(username, users)| UsernameCollision { username, users })
+            .collect()
+    }
+
+    /// Release the canonical key of a username which is no longer used by the given user.
+    ///
+    /// The key stays taken if other users still have a username with the same key, and is mapped
+    /// to one of them.
+    fn release_username(principal: Principal, key: &String) {
+        let colliding_users = USERNAME_COLLISIONS.with_borrow_mut(|collisions| {
+            collisions.remove(&StorablePrincipal::from(principal));
+            collisions
+                .iter()
+                .filter(|(_, user_key)| user_key == key)
+                .map(|(user, _)| user)
+                .collect::<Vec<_>>()
+        });
+
+        let Some(user) = colliding_users.first() else {
+            USERNAMES.with_borrow_mut(|usernames| {
+                usernames.remove(key);
+            });
+            return;
+        };
+        USERNAMES.with_borrow_mut(|usernames| {
+            if usernames.get(key).is_some_and(|owner| owner.0 == principal) {
+                usernames.insert(key.clone(), *user);
+            }
+        });
+        // the last user with the key no longer collides
+        if let [user] = colliding_users.as_slice() {
+            USERNAME_COLLISIONS.with_borrow_mut(|collisions| {
+                collisions.remove(user);
+            });
+        }
+    }
+
+    /// Add the trigrams of the username of a user to the search index.
+    fn index_username(principal: Principal, username: &str) {
+        USERNAME_TRIGRAMS.with_borrow_mut(|trigrams| {
+            for trigram in UsernameTrigram::trigrams(&username_key(username)) {
+                trigrams.insert(UsernameTrigram { trigram, principal }, ());
+            }
+        });
+    }
+
+    /// Remove the trigrams of the username of a user from the search index.
+    fn unindex_username(principal: Principal, username: &str) {
+        USERNAME_TRIGRAMS.with_borrow_mut(|trigrams| {
+            for trigram in UsernameTrigram::trigrams(&username_key(username)) {
+                trigrams.remove(&UsernameTrigram { trigram, principal });
+            }
+        });
+    }
+
+    /// Checks whether a username exists in the storage, regardless of its case
+    pub fn username_exists(username: &str) -> bool {
+        USERNAMES.with_borrow(|usernames| usernames.contains_key(&username_key(username)))
+    }
+
+    /// Get the number of users
+    pub fn count() -> u64 {
+        with_users_storage(|users| users.len())
     }
 }
 
 #[cfg(test)]
 mod test {
 
-    use did::orchestrator::PublicKey;
-
     use super::*;
 
     #[test]
@@ -140,12 +523,14 @@ mod test {
         UserStorage::add_user(principal2, user2.clone());
 
         // Get all users
-        let all_users = UserStorage::get_users_in_range(0, u64::MAX, |_| true);
+        let (all_users, total, next) =
+            UserStorage::search_users(None, None, u64::MAX, &BTreeSet::new());
         // Check if the length of all users is 2
         assert_eq!(all_users.len(), 2);
+        assert_eq!(total, 2);
+        assert_eq!(next, None);
         // Check if the retrieved user is in the list of all users
-        assert_eq!(all_users.get(&principal), Some(&user));
-        assert_eq!(all_users.get(&principal2), Some(&user2));
+        assert_eq!(all_users, vec![(principal, user), (principal2, user2)]);
     }
 
     #[test]
@@ -173,9 +558,313 @@ mod test {
         UserStorage::add_user(principal, user.clone());
 
         // Check if the username exists
-        assert!(UserStorage::username_exists(&"test_user".to_string()));
-        assert!(!UserStorage::username_exists(
-            &"non_existent_user".to_string()
+        assert!(UserStorage::username_exists("test_user"));
+        assert!(!UserStorage::username_exists("non_existent_user"));
+    }
+
+    #[test]
+    fn test_should_set_public_key() {
+        let principal = Principal::from_slice(&[1; 29]);
+        let public_key = PublicKey::try_from(vec![2; 32]).expect("invalid public key");
+        assert_eq!(UserStorage::set_public_key(principal, public_key), None);
+
+        UserStorage::add_user(
+            principal,
+            User {
+                username: "test_user".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+        assert_eq!(UserStorage::get_public_key_version(&principal), 0);
+        assert_eq!(UserStorage::set_public_key(principal, public_key), Some(1));
+        assert_eq!(
+            UserStorage::get_user(&principal).unwrap().public_key,
+            public_key
+        );
+        assert_eq!(UserStorage::get_public_key_version(&principal), 1);
+        assert_eq!(
+            UserStorage::get_public_user(&principal)
+                .unwrap()
+                .key_version,
+            1
+        );
+
+        // setting the same key again keeps the version
+        assert_eq!(UserStorage::set_public_key(principal, public_key), Some(1));
+        assert!(UserStorage::username_exists("test_user"));
+    }
+
+    #[test]
+    fn test_should_update_profile() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        assert!(!UserStorage::update_profile(
+            alice,
+            "alice".to_string(),
+            None
+        ));
+
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+        assert_eq!(UserStorage::get_profile(&alice), UserProfile::default());
+
+        // only the display name changes
+        assert!(UserStorage::update_profile(
+            alice,
+            "alice".to_string(),
+            Some("Alice".to_string())
+        ));
+        assert_eq!(
+            UserStorage::get_public_user(&alice)
+                .unwrap()
+                .display_name
+                .as_deref(),
+            Some("Alice")
+        );
+        assert!(
+            UserStorage::get_profile(&alice)
+                .previous_usernames
+                .is_empty()
+        );
+
+        // the previous username stays reserved to alice
+        assert!(UserStorage::update_profile(
+            alice,
+            "alice2".to_string(),
+            None
+        ));
+        assert_eq!(UserStorage::get_user(&alice).unwrap().username, "alice2");
+        assert!(!UserStorage::username_exists("alice"));
+        assert!(UserStorage::username_exists("alice2"));
+        assert_eq!(
+            UserStorage::get_profile(&alice),
+            UserProfile {
+                display_name: None,
+                previous_usernames: vec!["alice".to_string()],
+            }
+        );
+        assert_eq!(
+            UserStorage::get_previous_username_owner("alice"),
+            Some(alice)
+        );
+        assert!(!UserStorage::is_username_available("alice", bob));
+        assert!(UserStorage::is_username_available("alice", alice));
+
+        // taking it back removes it from the history
+        assert!(UserStorage::update_profile(
+            alice,
+            "alice".to_string(),
+            None
+        ));
+        assert_eq!(
+            UserStorage::get_profile(&alice).previous_usernames,
+            vec!["alice2".to_string()]
+        );
+        assert_eq!(UserStorage::get_previous_username_owner("alice"), None);
+    }
+
+    #[test]
+    fn test_should_release_oldest_previous_usernames() {
+        let alice = Principal::from_slice(&[1; 29]);
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice_0".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+
+        for i in 1..=MAX_USERNAME_HISTORY + 1 {
+            assert!(UserStorage::update_profile(
+                alice,
+                format!("alice_{i}"),
+                None
+            ));
+        }
+
+        let previous_usernames = UserStorage::get_profile(&alice).previous_usernames;
+        assert_eq!(previous_usernames.len(), MAX_USERNAME_HISTORY);
+        assert_eq!(previous_usernames[0], "alice_1");
+        assert_eq!(UserStorage::get_previous_username_owner("alice_0"), None);
+        assert_eq!(
+            UserStorage::get_previous_username_owner("alice_1"),
+            Some(alice)
+        );
+    }
+
+    #[test]
+    fn test_should_remove_user() {
+        let alice = Principal::from_slice(&[1; 29]);
+        UserStorage::add_user(
+            alice,
+            User {
+                username: "alice".to_string(),
+                public_key: PublicKey::try_from(vec![1; 32]).expect("invalid public key"),
+            },
+        );
+        assert!(UserStorage::update_profile(
+            alice,
+            "alice2".to_string(),
+            Some("Alice".to_string())
+        ));
+        UserStorage::set_public_key(
+            alice,
+            PublicKey::try_from(vec![2; 32]).expect("invalid public key"),
+        );
+
+        UserStorage::remove_user(alice);
+        assert_eq!(UserStorage::get_user(&alice), None);
+        assert_eq!(UserStorage::get_profile(&alice), UserProfile::default());
+        assert_eq!(UserStorage::get_public_key_version(&alice), 0);
+        // both the current and the previous usernames are released
+        let bob = Principal::from_slice(&[2; 29]);
+        assert!(UserStorage::is_username_available("alice", bob));
+        assert!(UserStorage::is_username_available("alice2", bob));
+    }
+
+    #[test]
+    fn test_should_search_users() {
+        for i in 0..10u8 {
+            UserStorage::add_user(
+                Principal::from_slice(&[i + 1; 29]),
+                User {
+                    username: format!("user_{}", if i % 2 == 0 { "even" } else { "odd" }),
+                    public_key: PublicKey::default(),
+                },
+            );
+        }
+
+        let (page, total, next) =
+            UserStorage::search_users(Some("_even"), None, 3, &BTreeSet::new());
+        assert_eq!(total, 5);
+        assert_eq!(
+            page.iter()
+                .map(|(principal, _)| *principal)
+                .collect::<Vec<_>>(),
+            vec![
+                Principal::from_slice(&[1; 29]),
+                Principal::from_slice(&[3; 29]),
+                Principal::from_slice(&[5; 29]),
+            ]
+        );
+        assert_eq!(next, Some(Principal::from_slice(&[5; 29])));
+
+        let (page, total, next) =
+            UserStorage::search_users(Some("_even"), next, 3, &BTreeSet::new());
+        assert_eq!(total, 5);
+        assert_eq!(page.len(), 2);
+        assert_eq!(next, None);
+
+        // usernames are indexed again when they change
+        assert!(UserStorage::update_profile(
+            Principal::from_slice(&[1; 29]),
+            "user_renamed".to_string(),
+            None
+        ));
+        assert_eq!(
+            UserStorage::search_users(Some("_even"), None, 10, &BTreeSet::new()).1,
+            4
+        );
+        assert_eq!(
+            UserStorage::search_users(Some("renamed"), None, 10, &BTreeSet::new()).1,
+            1
+        );
+
+        UserStorage::remove_user(Principal::from_slice(&[1; 29]));
+        assert_eq!(
+            UserStorage::search_users(Some("renamed"), None, 10, &BTreeSet::new()).1,
+            0
+        );
+
+        // without query
+        let (page, total, next) = UserStorage::search_users(None, None, 4, &BTreeSet::new());
+        assert_eq!(total, 9);
+        assert_eq!(page.len(), 4);
+        let (page, _, next) = UserStorage::search_users(None, next, 10, &BTreeSet::new());
+        assert_eq!(page.len(), 5);
+        assert_eq!(next, None);
+
+        // hidden users are left out; unknown hidden users are ignored
+        let hidden = BTreeSet::from([
+            Principal::from_slice(&[1; 29]),
+            Principal::from_slice(&[3; 29]),
+            Principal::from_slice(&[5; 29]),
+        ]);
+        let (page, total, _) = UserStorage::search_users(None, None, 10, &hidden);
+        assert_eq!(total, 7);
+        assert_eq!(page.len(), 7);
+        let (page, total, _) = UserStorage::search_users(Some("_even"), None, 10, &hidden);
+        assert_eq!(total, 2);
+        assert_eq!(page.len(), 2);
+    }
+
+    #[test]
+    fn test_should_migrate_usernames() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let alice_upper = Principal::from_slice(&[2; 29]);
+        let bob = Principal::from_slice(&[3; 29]);
+        // users registered before usernames were compared by their canonical key
+        USERS_STORAGE.with_borrow_mut(|users| {
+            for (principal, username) in [(alice, "alice"), (alice_upper, "Alice"), (bob, "Bob")] {
+                users.insert(
+                    principal.into(),
+                    User {
+                        username: username.to_string(),
+                        public_key: PublicKey::default(),
+                    },
+                );
+            }
+        });
+        USERNAME_HISTORY.with_borrow_mut(|history| {
+            history.insert("Robert".to_string(), bob.into());
+        });
+
+        let collisions = UserStorage::migrate_usernames();
+        assert_eq!(
+            collisions,
+            vec![UsernameCollision {
+                username: "alice".to_string(),
+                users: vec![alice, alice_upper],
+            }]
+        );
+        assert!(UserStorage::username_exists("BOB"));
+        assert_eq!(
+            UserStorage::get_previous_username_owner("robert"),
+            Some(bob)
+        );
+        assert_eq!(
+            UserStorage::search_users(Some("alic"), None, 10, &BTreeSet::new()).1,
+            2
+        );
+
+        assert_eq!(
+            UserStorage::get_user_by_username("ALICE").map(|(principal, _)| principal),
+            Some(alice)
+        );
+        assert_eq!(
+            UserStorage::get_user_by_username("bob").map(|(principal, _)| principal),
+            Some(bob)
+        );
+
+        // the key is taken until both colliding users change their username
+        assert!(UserStorage::update_profile(
+            alice,
+            "alice2".to_string(),
+            None
         ));
+        assert!(UserStorage::username_collisions().is_empty());
+        assert!(UserStorage::username_exists("alice"));
+        assert_eq!(
+            UserStorage::get_user_by_username("alice").map(|(principal, _)| principal),
+            Some(alice_upper)
+        );
+        UserStorage::remove_user(alice_upper);
+        assert!(!UserStorage::username_exists("alice"));
+        assert!(UserStorage::get_user_by_username("alice").is_none());
     }
 }
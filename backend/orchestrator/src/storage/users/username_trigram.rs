@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+
+use candid::Principal;
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+
+/// Size of a trigram, in bytes.
+pub const TRIGRAM_SIZE: usize = 3;
+
+/// Entry of the username search index: a trigram of a username, along with the user who has
+/// the username.
+///
+/// Entries are ordered by trigram, then by user, so that the users whose username contains a
+/// trigram can be iterated in the same order as the users storage.
+///
+/// ## Encoding
+///
+/// - 3 bytes: trigram.
+/// - N bytes: principal of the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UsernameTrigram {
+    pub trigram: [u8; TRIGRAM_SIZE],
+    pub principal: Principal,
+}
+
+impl UsernameTrigram {
+    /// Get the distinct trigrams of a username, as bytes.
+    pub fn trigrams(username: &str) -> Vec<[u8; TRIGRAM_SIZE]> {
+        let mut trigrams = username
+            .as_bytes()
+            .windows(TRIGRAM_SIZE)
+            .map(|window| [window[0], window[1], window[2]])
+            .collect::<Vec<_>>();
+        trigrams.sort_unstable();
+        trigrams.dedup();
+
+        trigrams
+    }
+
+    /// Get the smallest entry for a trigram, to iterate over the entries of the trigram.
+    pub fn first(trigram: [u8; TRIGRAM_SIZE]) -> Self {
+        Self {
+            trigram,
+            principal: Principal::from_slice(&[]),
+        }
+    }
+}
+
+impl Storable for UsernameTrigram {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: TRIGRAM_SIZE as u32 + 29,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(TRIGRAM_SIZE + self.principal.as_slice().len());
+        bytes.extend_from_slice(&self.trigram);
+        bytes.extend_from_slice(self.principal.as_slice());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let mut trigram = [0; TRIGRAM_SIZE];
+        trigram.copy_from_slice(&bytes[..TRIGRAM_SIZE]);
+
+        Self {
+            trigram,
+            principal: Principal::from_slice(&bytes[TRIGRAM_SIZE..]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_get_distinct_trigrams() {
+        assert_eq!(UsernameTrigram::trigrams("aaaab"), vec![*b"aaa", *b"aab"]);
+        assert!(UsernameTrigram::trigrams("ab").is_empty());
+    }
+
+    #[test]
+    fn test_storable_username_trigram_roundtrip() {
+        let entry = UsernameTrigram {
+            trigram: *b"abc",
+            principal: Principal::from_slice(&[1; 29]),
+        };
+        let decoded = UsernameTrigram::from_bytes(entry.to_bytes());
+        assert_eq!(entry, decoded);
+    }
+}
@@ -39,6 +39,30 @@ pub fn time() -> u64 {
     }
 }
 
+/// Returns 16 random bytes, from the management canister.
+///
+/// On test units, the bytes are taken from the current time and a counter instead.
+pub async fn random_bytes() -> [u8; 16] {
+    if cfg!(target_family = "wasm") {
+        let Ok(random) = ic_cdk::management_canister::raw_rand().await else {
+            trap("Failed to get randomness from management canister");
+        };
+        let Some(bytes) = random.first_chunk::<16>() else {
+            trap("Failed to get 16 random bytes");
+        };
+
+        *bytes
+    } else {
+        static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let counter = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&time().to_le_bytes());
+        bytes[8..].copy_from_slice(&counter.to_le_bytes());
+        bytes
+    }
+}
+
 /// Returns current datetime
 pub fn datetime() -> OffsetDateTime {
     let time = time();
@@ -1,27 +1,71 @@
+mod key_rotation;
+mod outbox;
+mod reconciliation;
+mod reencryption;
 mod share;
+mod transfer;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use candid::Principal;
-use did::orchestrator::{ShareFileMetadata, ShareFileResponse};
+use did::http::{HttpRequest, HttpResponse, METRICS_PATH, MetricsEncoder};
+use did::orchestrator::{Pagination, PublicKey, UpdateUserPublicKeyResponse};
 use did::user_canister::{
-    AliasInfo, DeleteFileResponse, FileData, FileDownloadResponse, FileSharingResponse, FileStatus,
-    GetAliasInfoError, OwnerKey, Path, PublicFileMetadata, RequestFileResponse,
-    UploadFileAtomicRequest, UploadFileAtomicResponse, UploadFileContinueRequest,
-    UploadFileContinueResponse, UploadFileError, UserCanisterInstallArgs,
+    AcceptFileTransferResponse, AliasInfo, AuditAction, AuditLog, AuditLogEntry, AuditLogFilter,
+    CompleteFileTransferResponse, CompleteKeyRotationResponse, ConfirmDownloadResponse,
+    DeleteFileResponse, DeviceId, DeviceKey, DeviceOwnerKey, FileAccessLog, FileAccessLogEntry,
+    FileData, FileDownloadResponse, FileSharingResponse, FileStatus, GetAliasInfoError,
+    GetFileAccessLogResponse, IncomingFileTransfer, KeyRotationStatus, LinkPrincipalResponse,
+    OutboxEntryWithId, OwnerKey, Path, PublicFileMetadata, ReceiveFileTransferChunkResponse,
+    ReceiveFileTransferRequest, ReceiveFileTransferResponse, ReconcileSharesResponse,
+    ReencryptFileContinueResponse, ReencryptFileRequest, ReencryptFileResponse,
+    ReleaseFileTransferResponse, RequestFileResponse, RevokeAllForUserReport, RevokeOutcome,
+    RevokeResult, RevokeShareForUsersResponse, RevokeShareResponse, RevokedShare,
+    SetDeviceKeysResponse, SetPublicKeyResponse, SetSuspendedResponse, ShareFileWithUsersResponse,
+    ShareMode, ShareOutcome, ShareReconciliationReport, ShareResult, StartKeyRotationResponse,
+    SubmitKeyRotationBatchResponse, SyncDeviceKeyResponse, SyncPublicKeyResponse,
+    TransferFileRequest, TransferFileResponse, UploadFileAtomicRequest, UploadFileAtomicResponse,
+    UploadFileContinueRequest, UploadFileContinueResponse, UploadFileError,
+    UserCanisterInstallArgs, WrappedFileKey,
 };
-use did::utils::trap;
+use did::utils::{cycle_balance, heap_memory_size, stable_memory_pages, trap};
 
+use self::key_rotation::CanisterKeyRotation;
+use self::outbox::Outbox;
+use self::reconciliation::ShareReconciliation;
+use self::reencryption::CanisterReencryption;
+use self::transfer::CanisterFileTransfer;
 use crate::aliases::{AliasGenerator, Randomness};
 use crate::client::OrchestratorClient;
+use crate::storage::audit_log::AuditLogStorage;
 use crate::storage::config::Config;
 use crate::storage::files::{
-    File, FileAliasIndexStorage, FileContent, FileContentsStorage, FileCountStorage,
-    FileDataStorage, FileId, FileMetadata, FileSharesStorage, OwnedFilesStorage, PathStorage,
-    UploadedChunks,
+    File, FileAccessLogStorage, FileAliasIndexStorage, FileContent, FileContentsStorage,
+    FileCountStorage, FileDataStorage, FileDeviceKeysStorage, FileId, FileMetadata,
+    FileSharesStorage, FileStats, FileStatsStorage, OwnedFilesStorage, PathStorage, UploadedChunks,
 };
+use crate::storage::key_rotation::KeyRotationStorage;
+use crate::storage::reencryption::ReencryptionStorage;
 use crate::utils::time;
 
+/// Maximum number of access log entries to retrieve at once.
+const MAX_GET_FILE_ACCESS_LOG_LIMIT: u64 = 128;
+/// Time window during which the downloads of a file confirmed again by the same user, for the same
+/// version, are not recorded again.
+const CONFIRM_DOWNLOAD_DEDUP_WINDOW: u64 = 60 * 60 * 1_000_000_000; // 1 hour
+/// Maximum number of audit log entries to retrieve at once.
+const MAX_GET_AUDIT_LOG_LIMIT: u64 = 128;
+/// Maximum number of document keys to retrieve at once during a key rotation.
+const MAX_GET_KEY_ROTATION_FILES_LIMIT: u64 = 128;
+/// Version of the stable memory in which the files and their contents are counted.
+const FILE_STATS_SCHEMA_VERSION: u64 = 1;
+/// Version of the stable memory in which the files of the key rotation in progress are counted.
+const KEY_ROTATION_PROGRESS_SCHEMA_VERSION: u64 = 2;
+/// Version of the stable memory in which the file shares are counted along with the files.
+const SHARE_STATS_SCHEMA_VERSION: u64 = 3;
+/// Current version of the stable memory.
+const SCHEMA_VERSION: u64 = SHARE_STATS_SCHEMA_VERSION;
+
 /// API for the backend canister
 pub struct Canister;
 
@@ -34,11 +78,50 @@ impl Canister {
 
         Config::set_orchestrator(args.orchestrator);
         Config::set_owner(args.owner);
+        // a new canister has nothing to migrate
+        Config::set_schema_version(SCHEMA_VERSION);
+
+        Outbox::schedule_retries();
+        ShareReconciliation::schedule();
+        CanisterFileTransfer::schedule_expiry();
+    }
+
+    /// Restore the canister state after an upgrade.
+    pub fn post_upgrade(args: UserCanisterInstallArgs) {
+        let UserCanisterInstallArgs::Upgrade = args else {
+            trap("Invalid arguments");
+        };
+
+        Self::migrate();
+
+        // timers are not kept across upgrades
+        Outbox::schedule_retries();
+        ShareReconciliation::schedule();
+        CanisterFileTransfer::schedule_expiry();
+    }
+
+    /// Run the migrations of the stable memory not run yet, up to [`SCHEMA_VERSION`].
+    fn migrate() {
+        let version = Config::get_schema_version();
+        if version < FILE_STATS_SCHEMA_VERSION {
+            FileStatsStorage::recount();
+        }
+        if version < KEY_ROTATION_PROGRESS_SCHEMA_VERSION {
+            KeyRotationStorage::recount();
+        }
+        // the files counted before have their shares counted too
+        if (FILE_STATS_SCHEMA_VERSION..SHARE_STATS_SCHEMA_VERSION).contains(&version) {
+            FileStatsStorage::recount();
+        }
+
+        if version != SCHEMA_VERSION {
+            Config::set_schema_version(SCHEMA_VERSION);
+        }
     }
 
     /// Request a file
     pub async fn request_file(caller: Principal, path: Path) -> RequestFileResponse {
-        if caller != Config::get_owner() {
+        if !Config::is_owner(caller) {
             trap("Only the owner can request a file");
         }
         // check if the file already exists
@@ -73,12 +156,14 @@ impl Canister {
         // add Path
         PathStorage::create(file_id, path);
 
+        Self::audit(caller, AuditAction::RequestFile, file_id);
+
         RequestFileResponse::Ok(alias)
     }
 
     /// Get active requests for the caller
     pub fn get_requests(caller: Principal) -> Vec<PublicFileMetadata> {
-        if caller != Config::get_owner() {
+        if !Config::is_owner(caller) {
             trap("Only the owner can get requests for a file");
         }
         OwnedFilesStorage::get_owned_files()
@@ -98,14 +183,20 @@ impl Canister {
 
     /// upload a file with the given [`FileId`] and file content.
     ///
-    /// to be triggered by requested file uploads
+    /// to be triggered by requested file uploads. `device_keys` holds the document key encrypted
+    /// for the devices of the owner; see [`Self::set_owner_device_keys`].
     pub fn upload_file(
+        caller: Principal,
         file_id: FileId,
         file_content: Vec<u8>,
         file_type: String,
         owner_key: OwnerKey,
         num_chunks: u64,
+        device_keys: Vec<DeviceOwnerKey>,
     ) -> Result<(), UploadFileError> {
+        if Config::is_suspended() {
+            return Err(UploadFileError::Suspended);
+        }
         let file = FileDataStorage::get_file(&file_id);
         if file.is_none() {
             return Err(UploadFileError::NotRequested);
@@ -143,6 +234,7 @@ impl Canister {
 
                 //add file to the storage
                 FileContentsStorage::set_file_contents(&file_id, &chunk_id, file_content);
+                Self::set_owner_device_keys(file_id, device_keys);
                 alias
             }
             FileContent::Uploaded { .. } | FileContent::PartiallyUploaded { .. } => {
@@ -153,6 +245,8 @@ impl Canister {
         // removing alias from the index
         FileAliasIndexStorage::remove_file_id(&alias);
 
+        Self::audit(caller, AuditAction::UploadFile, file_id);
+
         Ok(())
     }
 
@@ -162,9 +256,12 @@ impl Canister {
         caller: Principal,
         request: UploadFileAtomicRequest,
     ) -> UploadFileAtomicResponse {
-        if caller != Config::get_owner() {
+        if !Config::is_owner(caller) {
             trap("Only the owner can upload a file");
         }
+        if Config::is_suspended() {
+            return UploadFileAtomicResponse::Suspended;
+        }
         // check if path exists
         if PathStorage::exists(&request.path) {
             return UploadFileAtomicResponse::FileAlreadyExists;
@@ -206,14 +303,20 @@ impl Canister {
         };
         FileDataStorage::set_file(&file_id, file);
         OwnedFilesStorage::add_owned_file(&file_id);
+        Self::set_owner_device_keys(file_id, request.device_keys.unwrap_or_default());
         // add path
         PathStorage::create(file_id, request.path);
 
+        Self::audit(caller, AuditAction::UploadFile, file_id);
+
         UploadFileAtomicResponse::Ok(file_id)
     }
 
     /// Upload file continue
     pub fn upload_file_continue(request: UploadFileContinueRequest) -> UploadFileContinueResponse {
+        if Config::is_suspended() {
+            return UploadFileContinueResponse::Suspended;
+        }
         let Some(mut file) = FileDataStorage::get_file(&request.file_id) else {
             return UploadFileContinueResponse::FileNotFound;
         };
@@ -274,148 +377,283 @@ impl Canister {
     }
 
     /// Share file with user
+    ///
+    /// The share is applied once the orchestrator indexes it. If the orchestrator can't be
+    /// reached, the share stays in the outbox and [`FileSharingResponse::Pending`] is returned.
+    /// Sharing a file again with a user has no effect.
+    ///
+    /// `device_keys` holds the key encrypted for each device of the user; the user downloads the
+    /// file with the key of the requesting device. The keys of a user the file is already shared
+    /// with can be replaced with [`Self::set_device_keys`].
     pub async fn share_file(
         caller: Principal,
         user_id: Principal,
         file_id: FileId,
         file_key_encrypted_for_user: OwnerKey,
+        device_keys: Vec<DeviceOwnerKey>,
     ) -> FileSharingResponse {
-        if caller != Config::get_owner() {
+        if !Config::is_owner(caller) {
             trap("Only the owner can share a file");
         }
 
-        // check whether we can share the file
-        match share::CanisterShareFile::check_shareable(file_id) {
-            FileSharingResponse::Ok => {}
-            err => {
-                return err;
-            }
-        }
-
-        let Some(path) = PathStorage::read_link(&file_id) else {
-            return FileSharingResponse::FileNotFound;
-        };
-
-        // Index Share file on the orchestrator
-        if cfg!(target_family = "wasm") {
-            // get file name
-            let file_name = path.file_name().unwrap_or_default().to_string();
-
-            match OrchestratorClient::from(Config::get_orchestrator())
-                .share_file(user_id, file_id, ShareFileMetadata { file_name })
-                .await
-            {
-                Err(err) => {
-                    trap(
-                        format!("Error indexing sharing file on orchestrator: {:?}", err).as_str(),
-                    );
-                }
-                Ok(ShareFileResponse::Ok) => {}
-                Ok(share_err) => {
-                    trap(format!("Error sharing file on orchestrator: {:?}", share_err).as_str());
-                }
-            }
+        match Self::share_with(
+            file_id,
+            vec![user_id],
+            vec![file_key_encrypted_for_user],
+            vec![device_keys],
+            ShareMode::AllOrNothing,
+        )
+        .await
+        {
+            ShareFileWithUsersResponse::Ok(results) => match results.into_iter().next() {
+                Some(ShareResult {
+                    outcome: ShareOutcome::Pending,
+                    ..
+                }) => FileSharingResponse::Pending,
+                Some(ShareResult {
+                    outcome: ShareOutcome::Rejected(err),
+                    ..
+                }) => FileSharingResponse::Rejected(err),
+                Some(ShareResult {
+                    outcome:
+                        outcome @ (ShareOutcome::NoSuchUser
+                        | ShareOutcome::Blocked
+                        | ShareOutcome::NotShared),
+                    ..
+                }) => FileSharingResponse::Rejected(format!("{outcome:?}")),
+                _ => FileSharingResponse::Ok,
+            },
+            ShareFileWithUsersResponse::FileNotFound => FileSharingResponse::FileNotFound,
+            ShareFileWithUsersResponse::PendingError => FileSharingResponse::PendingError,
+            ShareFileWithUsersResponse::Suspended => FileSharingResponse::Suspended,
         }
-
-        share::CanisterShareFile::share_file(user_id, file_id, file_key_encrypted_for_user)
     }
 
     /// Share file with users
+    ///
+    /// `file_key_encrypted_for_user` holds the key of each user, in the same order as `users`,
+    /// and `device_keys` the keys for the devices of each user; a user without an entry in
+    /// `device_keys` gets no device key.
+    /// The outcome is returned for each user; in [`ShareMode::AllOrNothing`] mode, the file is
+    /// shared with nobody if it can't be shared with every user. See [`Canister::share_file`].
     pub async fn share_file_with_users(
         caller: Principal,
         users: Vec<Principal>,
         file_id: FileId,
         file_key_encrypted_for_user: Vec<OwnerKey>,
-    ) {
-        if caller != Config::get_owner() {
+        mode: ShareMode,
+        device_keys: Vec<Vec<DeviceOwnerKey>>,
+    ) -> ShareFileWithUsersResponse {
+        if !Config::is_owner(caller) {
             trap("Only the owner can share a file");
         }
 
-        // check whether we can share the file
-        match share::CanisterShareFile::check_shareable(file_id) {
-            FileSharingResponse::Ok => {}
-            err => {
-                trap(format!("Error sharing file: {:?}", err).as_str());
-            }
+        Self::share_with(
+            file_id,
+            users,
+            file_key_encrypted_for_user,
+            device_keys,
+            mode,
+        )
+        .await
+    }
+
+    /// Set the keys of a file for the devices of a user, replacing the previous ones.
+    ///
+    /// The user is the owner, or a user the file is shared with. It is used to add the keys of a
+    /// new device, or to set the device keys again once the file was re-encrypted.
+    ///
+    /// # Returns
+    ///
+    /// - [`SetDeviceKeysResponse::Ok`] if the keys were set.
+    /// - [`SetDeviceKeysResponse::FileNotFound`] if the file doesn't exist.
+    /// - [`SetDeviceKeysResponse::PendingError`] if the file has not been uploaded yet.
+    /// - [`SetDeviceKeysResponse::NotShared`] if the file is not shared with the user.
+    pub fn set_device_keys(
+        caller: Principal,
+        file_id: FileId,
+        user: Principal,
+        device_keys: Vec<DeviceOwnerKey>,
+    ) -> SetDeviceKeysResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can set the device keys");
         }
 
-        let Some(path) = PathStorage::read_link(&file_id) else {
-            trap("File not found");
+        let Some(file) = FileDataStorage::get_file(&file_id) else {
+            return SetDeviceKeysResponse::FileNotFound;
+        };
+        let (FileContent::Uploaded { shared_keys, .. }
+        | FileContent::PartiallyUploaded { shared_keys, .. }) = &file.content
+        else {
+            return SetDeviceKeysResponse::PendingError;
         };
 
-        // Index files on the orchestrator
-        if cfg!(target_family = "wasm") {
-            let file_name = path.file_name().unwrap_or_default().to_string();
-            match OrchestratorClient::from(Config::get_orchestrator())
-                .share_file_with_users(&users, file_id, ShareFileMetadata { file_name })
-                .await
-            {
-                Err(err) => {
-                    trap(
-                        format!("Error indexing sharing file on orchestrator: {:?}", err).as_str(),
-                    );
-                }
-                Ok(ShareFileResponse::Ok) => {}
-                Ok(share_err) => {
-                    trap(format!("Error sharing file on orchestrator: {:?}", share_err).as_str());
-                }
-            }
+        if Config::is_owner(user) {
+            Self::set_owner_device_keys(file_id, device_keys);
+        } else if shared_keys.contains_key(&user) {
+            FileDeviceKeysStorage::set_keys(&file_id, user, device_keys);
+        } else {
+            return SetDeviceKeysResponse::NotShared;
         }
 
-        // commit changes to the canister storage
-        for (user, decryption_key) in users.iter().zip(file_key_encrypted_for_user.iter()) {
-            match share::CanisterShareFile::share_file(*user, file_id, *decryption_key) {
-                FileSharingResponse::Ok => {}
-                err => {
-                    trap(format!("Error sharing file: {:?}", err).as_str());
-                }
-            }
-        }
+        SetDeviceKeysResponse::Ok
     }
 
     /// Revoke file sharing
-    pub async fn revoke_file_sharing(caller: Principal, user_id: Principal, file_id: FileId) {
-        if caller != Config::get_owner() {
+    ///
+    /// The share is removed from the canister right away; the revocation is then delivered to the
+    /// orchestrator through the outbox. A pending share of the file with the user is cancelled.
+    pub async fn revoke_file_sharing(
+        caller: Principal,
+        user_id: Principal,
+        file_id: FileId,
+    ) -> RevokeShareResponse {
+        if !Config::is_owner(caller) {
             trap("Only the owner can revoke file sharing");
         }
 
-        // get file first checking if it exists
-        let Some(mut file) = FileDataStorage::get_file(&file_id) else {
-            trap("File not found");
-        };
+        match Self::revoke_for(file_id, vec![user_id]).await {
+            RevokeShareForUsersResponse::Ok(results) => match results.first() {
+                Some(RevokeResult {
+                    outcome: RevokeOutcome::Ok,
+                    ..
+                }) => RevokeShareResponse::Ok,
+                _ => RevokeShareResponse::NotShared,
+            },
+            RevokeShareForUsersResponse::FileNotFound => RevokeShareResponse::FileNotFound,
+        }
+    }
 
-        // first call the orchestrator to revoke the file, since this can fail
-        if cfg!(target_family = "wasm") {
-            // Revoke files on the orchestrator
-            if let Err(err) = OrchestratorClient::from(Config::get_orchestrator())
-                .revoke_share_file(user_id, file_id)
-                .await
-            {
-                trap(format!("Error revoking shared file on orchestrator: {:?}", err).as_str());
-            }
+    /// Revoke file sharing for users
+    ///
+    /// The outcome is returned for each user. See [`Canister::revoke_file_sharing`].
+    pub async fn revoke_share_for_users(
+        caller: Principal,
+        users: Vec<Principal>,
+        file_id: FileId,
+    ) -> RevokeShareForUsersResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can revoke file sharing");
+        }
+
+        Self::revoke_for(file_id, users).await
+    }
+
+    /// Revoke every share with a user, e.g. when they leave.
+    ///
+    /// The pending shares with the user are cancelled as well. The revocation of all the files is
+    /// recorded into the outbox and delivered to the orchestrator in a single call.
+    pub async fn revoke_all_for_user(caller: Principal, user: Principal) -> RevokeAllForUserReport {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can revoke file sharing");
         }
 
-        // remove user from file shares (cannot fail)
-        match &mut file.content {
-            FileContent::Uploaded { shared_keys, .. }
-            | FileContent::PartiallyUploaded { shared_keys, .. } => {
-                shared_keys.remove(&user_id);
+        let shared = FileSharesStorage::get_file_shares(&user)
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        let pending_shares = Outbox::pending_user_shares(user);
+
+        let mut revoked = vec![];
+        for file_id in &shared {
+            if let Some(path) = PathStorage::read_link(file_id) {
+                Self::audit(caller, AuditAction::RevokeShare { user }, *file_id);
+                revoked.push(RevokedShare {
+                    file_id: *file_id,
+                    path,
+                });
             }
-            _ => {}
+            share::CanisterShareFile::revoke_share(user, *file_id);
         }
+        let cancelled = pending_shares
+            .difference(&shared)
+            .filter_map(|file_id| {
+                PathStorage::read_link(file_id).map(|path| RevokedShare {
+                    file_id: *file_id,
+                    path,
+                })
+            })
+            .collect();
 
-        // persist file (cannot fail)
-        FileDataStorage::set_file(&file_id, file);
+        let file_ids = shared.union(&pending_shares).copied().collect::<Vec<_>>();
+        let pending = if file_ids.is_empty() {
+            false
+        } else {
+            let id = Outbox::revoke_user(user, file_ids);
+            matches!(Outbox::deliver(id).await, outbox::Delivery::Failed(_))
+        };
+
+        RevokeAllForUserReport {
+            user,
+            revoked,
+            cancelled,
+            pending,
+        }
+    }
+
+    /// Drop the share of a file declined by a user.
+    ///
+    /// Can only be called by the orchestrator, once the user has declined the share.
+    ///
+    /// # Returns
+    ///
+    /// - [`FileSharingResponse::Ok`] if the share was removed.
+    /// - [`FileSharingResponse::PermissionError`] if the caller is not the orchestrator.
+    /// - [`FileSharingResponse::FileNotFound`] if the file doesn't exist.
+    pub fn decline_share(
+        caller: Principal,
+        user_id: Principal,
+        file_id: FileId,
+    ) -> FileSharingResponse {
+        if caller != Config::get_orchestrator() {
+            return FileSharingResponse::PermissionError;
+        }
+
+        let response = share::CanisterShareFile::revoke_share(user_id, file_id);
+        if response == FileSharingResponse::Ok {
+            Self::audit(
+                user_id,
+                AuditAction::DeclineShare { user: user_id },
+                file_id,
+            );
+        }
+
+        response
+    }
+
+    /// Drop every share of a file with a user whose account was deleted.
+    ///
+    /// Can only be called by the orchestrator, which already removed the shares from its index;
+    /// the pending shares with the user are cancelled.
+    ///
+    /// # Returns
+    ///
+    /// - [`FileSharingResponse::Ok`] if the shares were removed.
+    /// - [`FileSharingResponse::PermissionError`] if the caller is not the orchestrator.
+    pub fn revoke_deleted_user(caller: Principal, user_id: Principal) -> FileSharingResponse {
+        if caller != Config::get_orchestrator() {
+            return FileSharingResponse::PermissionError;
+        }
+
+        for file_id in FileSharesStorage::get_file_shares(&user_id).unwrap_or_default() {
+            if share::CanisterShareFile::revoke_share(user_id, file_id) == FileSharingResponse::Ok {
+                Self::audit(caller, AuditAction::RevokeShare { user: user_id }, file_id);
+            }
+        }
+        Outbox::cancel_user_shares(user_id);
 
-        // remove file from user shares (cannot fail)
-        FileSharesStorage::revoke(&user_id, &file_id);
+        FileSharingResponse::Ok
     }
 
     /// Download file
+    ///
+    /// If `device_id` is set, the document key encrypted for the device is returned instead of
+    /// the key for the public key of the user. The devices of the owner must still be registered.
     pub fn download_file(
         caller: Principal,
         file_id: FileId,
         chunk_id: u64,
+        device_id: Option<DeviceId>,
     ) -> FileDownloadResponse {
         let file = FileDataStorage::get_file(&file_id);
         if file.is_none() {
@@ -433,17 +671,32 @@ impl Canister {
                 file_type,
                 owner_key,
             } => {
-                if !shared_keys.contains_key(&caller) && caller != file.metadata.requester_principal
-                {
+                // a principal linked to the account of a user downloads as the user
+                let user = if shared_keys.contains_key(&caller) {
+                    caller
+                } else {
+                    Config::get_recipient_account(caller)
+                };
+                if !shared_keys.contains_key(&user) && !Config::is_owner(caller) {
                     return FileDownloadResponse::PermissionError;
                 }
                 let num_chunks = *num_chunks;
                 let file_type = file_type.clone();
                 // if the caller is the owner, use the owner key
                 // else use the shared key
-                let owner_key = match caller == file.metadata.requester_principal {
-                    true => *owner_key,
-                    false => *shared_keys.get(&caller).unwrap(),
+                let owner_key = match (Config::is_owner(caller), device_id) {
+                    (true, None) => *owner_key,
+                    (false, None) => *shared_keys.get(&user).unwrap(),
+                    (true, Some(device_id)) if !Config::is_owner_device(device_id) => {
+                        return FileDownloadResponse::DeviceKeyNotFound;
+                    }
+                    (is_owner, Some(device_id)) => {
+                        let user = if is_owner { Config::get_owner() } else { user };
+                        match FileDeviceKeysStorage::get_key(&file_id, user, device_id) {
+                            Some(key) => key,
+                            None => return FileDownloadResponse::DeviceKeyNotFound,
+                        }
+                    }
                 };
 
                 (num_chunks, file_type, owner_key)
@@ -462,9 +715,94 @@ impl Canister {
         })
     }
 
+    /// Confirm the download of a file, recording it into the file access log.
+    ///
+    /// Since [`Self::download_file`] is a query, the caller must acknowledge the download
+    /// with this update call for the owner to know that the file was retrieved.
+    ///
+    /// A download of the same version of the file confirmed again by the caller within
+    /// [`CONFIRM_DOWNLOAD_DEDUP_WINDOW`] is not recorded again.
+    ///
+    /// # Returns
+    ///
+    /// - [`ConfirmDownloadResponse::Ok`] if the download was recorded.
+    /// - [`ConfirmDownloadResponse::FileNotFound`] if the file doesn't exist.
+    /// - [`ConfirmDownloadResponse::NotUploadedFile`] if the file is not uploaded yet.
+    /// - [`ConfirmDownloadResponse::PermissionError`] if the caller has no access to the file.
+    pub fn confirm_download(caller: Principal, file_id: FileId) -> ConfirmDownloadResponse {
+        let Some(file) = FileDataStorage::get_file(&file_id) else {
+            return ConfirmDownloadResponse::FileNotFound;
+        };
+
+        let FileContent::Uploaded { shared_keys, .. } = &file.content else {
+            return ConfirmDownloadResponse::NotUploadedFile;
+        };
+        let user = if shared_keys.contains_key(&caller) {
+            caller
+        } else {
+            Config::get_recipient_account(caller)
+        };
+        if !shared_keys.contains_key(&user) && !Config::is_owner(caller) {
+            return ConfirmDownloadResponse::PermissionError;
+        }
+
+        let now = time();
+        let version = file.metadata.uploaded_at.unwrap_or_default();
+        let since = now.saturating_sub(CONFIRM_DOWNLOAD_DEDUP_WINDOW);
+        if !FileAccessLogStorage::has_recent_entry(&file_id, user, version, since) {
+            FileAccessLogStorage::record(
+                &file_id,
+                FileAccessLogEntry {
+                    user,
+                    timestamp: now,
+                    version,
+                },
+            );
+        }
+
+        ConfirmDownloadResponse::Ok
+    }
+
+    /// Get the access log of a file by its [`FileId`].
+    ///
+    /// Up to [`MAX_GET_FILE_ACCESS_LOG_LIMIT`] entries can be retrieved at once.
+    ///
+    /// # Returns
+    ///
+    /// - [`GetFileAccessLogResponse::Ok`] with the requested page of the access log.
+    /// - [`GetFileAccessLogResponse::FileNotFound`] if the file doesn't exist.
+    pub fn get_file_access_log(
+        caller: Principal,
+        file_id: FileId,
+        Pagination { offset, limit }: Pagination,
+    ) -> GetFileAccessLogResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can get the file access log");
+        }
+
+        if FileDataStorage::get_file(&file_id).is_none() {
+            return GetFileAccessLogResponse::FileNotFound;
+        }
+
+        let limit = limit.min(MAX_GET_FILE_ACCESS_LOG_LIMIT);
+        let entries = FileAccessLogStorage::get_entries(&file_id, offset, limit);
+        let total = FileAccessLogStorage::count(&file_id);
+        let next = if offset + limit < total {
+            Some(offset + limit)
+        } else {
+            None
+        };
+
+        GetFileAccessLogResponse::Ok(FileAccessLog {
+            entries,
+            next,
+            total,
+        })
+    }
+
     /// Get the list of users that have access to the file by its [`FileId`]
     pub fn get_allowed_users(caller: Principal, file_id: &FileId) -> Vec<Principal> {
-        if caller != Config::get_owner() {
+        if !Config::is_owner(caller) {
             trap("Only the owner can get allowed users");
         }
 
@@ -496,7 +834,7 @@ impl Canister {
 
     /// Get the list of files shared with the user by its [`Principal`]
     pub fn get_shared_files(caller: Principal, user_id: Principal) -> Vec<PublicFileMetadata> {
-        if caller != Config::get_owner() {
+        if !Config::is_owner(caller) {
             trap("Only the owner can get allowed users");
         }
         match FileSharesStorage::get_file_shares(&user_id) {
@@ -538,6 +876,7 @@ impl Canister {
             file_name,
             file_path,
             public_key: file.metadata.user_public_key,
+            device_keys: Config::get_owner_device_keys(),
         })
     }
 
@@ -546,12 +885,13 @@ impl Canister {
     /// The process of deleting a file is as follows:
     ///
     /// 1. Check whether the file exists in the storage.
-    /// 2. Check if the file is shared with any users.
-    /// 3. If the file is shared, remove the sharing information from the storage and revoke the sharing on the orchestrator.
+    /// 2. Check if the file is shared with any users, or if a share is pending in the outbox.
+    /// 3. If the file is shared, remove the sharing information from the storage.
     /// 4. If the file is being uploaded, remove the file request
     /// 5. Remove the file from the storage.
+    /// 6. Revoke the sharing on the orchestrator through the outbox.
     pub async fn delete_file(caller: Principal, file_id: FileId) -> DeleteFileResponse {
-        if caller != Config::get_owner() {
+        if !Config::is_owner(caller) {
             trap("Only the owner can delete files");
         }
 
@@ -560,138 +900,1952 @@ impl Canister {
             return DeleteFileResponse::FileNotFound;
         };
 
-        // 2. Check if the file is shared with any users.
-        let users_with_access = FileSharesStorage::get_users_with_file_shares(&file_id);
-        // revoke share on orchestrator
-        if cfg!(target_family = "wasm") {
-            if let Err(err) = OrchestratorClient::from(Config::get_orchestrator())
-                .revoke_share_file_for_users(&users_with_access, file_id)
-                .await
-            {
-                return DeleteFileResponse::FailedToRevokeShare(err.to_string());
-            }
-        }
+        // record deletion while the file path still exists
+        Self::audit(caller, AuditAction::DeleteFile, file_id);
 
-        // 3. If the file is shared, remove the sharing information from the storage
-        for user_id in users_with_access {
-            // remove file from user shares
-            FileSharesStorage::revoke(&user_id, &file_id);
-        }
-        // remove file
-        FileDataStorage::remove_file(&file_id);
-        OwnedFilesStorage::remove_owned_file(&file_id);
-        // remove file content / alias
-        match file.content {
-            FileContent::PartiallyUploaded { num_chunks, .. }
-            | FileContent::Uploaded { num_chunks, .. } => {
-                for chunk_id in 0..num_chunks {
-                    FileContentsStorage::remove_file_contents(&file_id, &chunk_id);
-                }
-            }
-            FileContent::Pending { alias } => {
-                FileAliasIndexStorage::remove_file_id(&alias);
-            }
+        // 2. - 5. Remove the file and its shares.
+        let users_with_access = Self::remove_file(file_id, file);
+
+        // 6. Revoke the shares on the orchestrator.
+        if !users_with_access.is_empty() {
+            let id = Outbox::revoke(file_id, users_with_access);
+            Outbox::deliver(id).await;
         }
-        // remove file path
-        PathStorage::unlink(file_id);
 
         DeleteFileResponse::Ok
     }
-}
-
-#[cfg(test)]
-mod test {
-    use candid::Principal;
-    use did::user_canister::UserCanisterInitArgs;
 
-    use super::*;
+    /// Transfer the ownership of a file to another user.
+    ///
+    /// The file is copied to the user canister of the recipient, then deleted once the recipient
+    /// accepts it; see [`CanisterFileTransfer`]. If `keep_shares` is set, the file is shared again
+    /// from the user canister of the recipient with the users it is shared with.
+    pub async fn transfer_file(
+        caller: Principal,
+        request: TransferFileRequest,
+    ) -> TransferFileResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can transfer files");
+        }
+        if Config::is_suspended() {
+            return TransferFileResponse::Suspended;
+        }
 
-    #[test]
-    fn test_should_init_canister() {
-        let orchestrator = Principal::from_slice(&[0, 1, 2, 3]);
-        let owner = Principal::from_slice(&[4, 5, 6, 7]);
-        Canister::init(UserCanisterInstallArgs::Init(UserCanisterInitArgs {
-            orchestrator,
-            owner,
-        }));
+        let file = match CanisterFileTransfer::check(&request) {
+            Ok(file) => file,
+            Err(err) => return err,
+        };
+        match CanisterFileTransfer::send(&request, &file).await {
+            Ok(()) => TransferFileResponse::Ok,
+            Err(err) => err,
+        }
+    }
 
-        assert_eq!(Config::get_orchestrator(), orchestrator);
-        assert_eq!(Config::get_owner(), owner);
+    /// Delete a file sent to the calling user canister, once the recipient accepted it.
+    pub async fn release_file_transfer(
+        caller: Principal,
+        file_id: FileId,
+    ) -> ReleaseFileTransferResponse {
+        CanisterFileTransfer::release(caller, file_id).await
     }
 
-    #[tokio::test]
-    async fn test_should_request_file() {
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let caller = init();
-        let alias = Canister::request_file(caller, path).await.unwrap();
-        // NOTE: we expect it to end with 0 because on unit tests the randomness is just zero.
-        assert!(alias.ends_with("7000-8000-000000000000"));
+    /// Start the transfer of a file from the user canister of its owner.
+    pub async fn receive_file_transfer(
+        caller: Principal,
+        request: ReceiveFileTransferRequest,
+    ) -> ReceiveFileTransferResponse {
+        CanisterFileTransfer::receive(caller, request).await
     }
 
-    #[tokio::test]
-    #[should_panic(expected = "Only the owner can request a file")]
-    async fn test_should_not_request_file_if_not_owner() {
-        let path = Path::new("/test_file.txt").expect("valid path");
-        init();
-        Canister::request_file(Principal::anonymous(), path)
-            .await
-            .unwrap();
+    /// Receive a chunk of a file being transferred from the calling user canister.
+    pub fn receive_file_transfer_chunk(
+        caller: Principal,
+        request: UploadFileContinueRequest,
+    ) -> ReceiveFileTransferChunkResponse {
+        CanisterFileTransfer::receive_chunk(caller, request)
     }
 
-    #[tokio::test]
-    async fn test_should_get_requests() {
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let caller = init();
-        Canister::request_file(caller, path.clone()).await;
-        let requests = Canister::get_requests(caller);
-        assert_eq!(requests.len(), 1);
-        assert_eq!(requests[0].file_name, "test_file.txt".to_string());
-        assert_eq!(requests[0].file_path, path);
+    /// Complete the transfer of a file from the calling user canister, which then awaits the
+    /// acceptance of the owner.
+    pub fn complete_file_transfer(
+        caller: Principal,
+        file_id: FileId,
+    ) -> CompleteFileTransferResponse {
+        CanisterFileTransfer::complete(caller, file_id)
     }
 
-    #[tokio::test]
-    #[should_panic(expected = "Only the owner can get requests for a file")]
-    async fn test_should_not_get_requests_if_not_owner() {
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let caller = init();
-        Canister::request_file(caller, path).await;
-        Canister::get_requests(Principal::anonymous());
+    /// Drop the transfer of a file from the calling user canister, if any.
+    pub fn abort_file_transfer(caller: Principal, file_id: FileId) {
+        CanisterFileTransfer::abort(caller, file_id);
     }
 
-    #[tokio::test]
-    async fn test_should_not_create_already_existing_file() {
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let caller = init();
-        Canister::request_file(caller, path.clone()).await.unwrap();
+    /// Get the transfers of files to the owner awaiting their acceptance.
+    pub fn get_incoming_file_transfers(caller: Principal) -> Vec<IncomingFileTransfer> {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can get the incoming file transfers");
+        }
 
-        // create same path
-        let result = Canister::request_file(caller, path).await;
+        CanisterFileTransfer::incoming()
+    }
+
+    /// Accept the transfer of a file from the `sender` user canister.
+    pub async fn accept_file_transfer(
+        caller: Principal,
+        sender: Principal,
+        file_id: FileId,
+    ) -> AcceptFileTransferResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can accept file transfers");
+        }
+
+        CanisterFileTransfer::accept(sender, file_id).await
+    }
+
+    /// Decline the transfer of a file from the `sender` user canister.
+    pub fn decline_file_transfer(caller: Principal, sender: Principal, file_id: FileId) {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can decline file transfers");
+        }
+
+        CanisterFileTransfer::decline(sender, file_id);
+    }
+
+    /// Start the re-encryption of a file under a new document key, revoking the given users.
+    ///
+    /// The revoked users lose access right away; the re-encrypted chunks are then uploaded with
+    /// [`Canister::reencrypt_file_continue`]. The file keeps its current chunks and keys until the
+    /// last chunk is uploaded, so it stays consistent if the upload is interrupted. Starting again
+    /// drops the chunks staged by a previous re-encryption of the file. Once completed, the device
+    /// keys of the file are dropped, and must be set again with [`Canister::set_device_keys`].
+    pub async fn reencrypt_file(
+        caller: Principal,
+        request: ReencryptFileRequest,
+    ) -> ReencryptFileResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can re-encrypt files");
+        }
+
+        let revoked = match CanisterReencryption::check(&request) {
+            Ok(revoked) => revoked,
+            Err(err) => return err,
+        };
+
+        for user in &revoked {
+            if share::CanisterShareFile::revoke_share(*user, request.file_id)
+                == FileSharingResponse::Ok
+            {
+                Self::audit(
+                    caller,
+                    AuditAction::RevokeShare { user: *user },
+                    request.file_id,
+                );
+            }
+        }
+        let file_id = request.file_id;
+        CanisterReencryption::start(request);
+
+        if !revoked.is_empty() {
+            let id = Outbox::revoke(file_id, revoked);
+            Outbox::deliver(id).await;
+        }
+
+        ReencryptFileResponse::Ok
+    }
+
+    /// Upload a chunk of the file being re-encrypted.
+    ///
+    /// Once the last chunk is uploaded, the chunks and the keys of the file are replaced with the
+    /// re-encrypted ones. If the file was shared or revoked in the meantime, the re-encryption is
+    /// dropped and must be started again.
+    pub fn reencrypt_file_continue(
+        caller: Principal,
+        request: UploadFileContinueRequest,
+    ) -> ReencryptFileContinueResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can re-encrypt files");
+        }
+
+        let file_id = request.file_id;
+        let response = CanisterReencryption::upload_chunk(request);
+        if response == ReencryptFileContinueResponse::Completed {
+            Self::audit(caller, AuditAction::ReencryptFile, file_id);
+        }
+
+        response
+    }
+
+    /// Drop the re-encryption in progress of a file, if any, keeping the file as it is.
+    pub fn abort_file_reencryption(caller: Principal, file_id: FileId) {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can re-encrypt files");
+        }
+
+        ReencryptionStorage::remove(&file_id);
+    }
+
+    /// Replace the owner public key, on the canister and on the orchestrator.
+    ///
+    /// The key is first updated on the orchestrator, which returns its new version. It can't be
+    /// replaced while a key rotation is in progress; use [`Canister::start_key_rotation`] to
+    /// re-wrap the document keys for a new public key.
+    pub async fn set_public_key(caller: Principal, public_key: PublicKey) -> SetPublicKeyResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can set the public key");
+        }
+        if KeyRotationStorage::get().is_some() {
+            return SetPublicKeyResponse::KeyRotationInProgress;
+        }
+
+        let version = match Self::update_orchestrator_public_key(public_key).await {
+            Ok(version) => version,
+            Err(err) => return SetPublicKeyResponse::FailedToUpdateOrchestrator(err),
+        };
+        // a newer key may have been synced by the orchestrator in the meantime
+        if version >= Config::get_owner_public_key_version() {
+            Config::set_owner_public_key(caller, public_key);
+            Config::set_owner_public_key_version(version);
+        }
+
+        SetPublicKeyResponse::Ok(version)
+    }
+
+    /// Replace the owner public key with the one set on the orchestrator.
+    ///
+    /// Keys older than the current one are rejected, so that a delayed call can't restore a
+    /// replaced key.
+    pub fn sync_public_key(
+        caller: Principal,
+        public_key: PublicKey,
+        version: u64,
+    ) -> SyncPublicKeyResponse {
+        if caller != Config::get_orchestrator() {
+            return SyncPublicKeyResponse::Unauthorized;
+        }
+        if KeyRotationStorage::get().is_some() {
+            return SyncPublicKeyResponse::KeyRotationInProgress;
+        }
+        let current_version = Config::get_owner_public_key_version();
+        if version <= current_version {
+            return SyncPublicKeyResponse::StaleVersion(current_version);
+        }
+
+        Config::set_owner_public_key(Config::get_owner(), public_key);
+        Config::set_owner_public_key_version(version);
+
+        SyncPublicKeyResponse::Ok
+    }
+
+    /// Link a principal to the account of the owner, so that it can call the canister as the
+    /// owner.
+    ///
+    /// Called by the orchestrator once the owner approved the link.
+    pub fn link_principal(caller: Principal, principal: Principal) -> LinkPrincipalResponse {
+        if caller != Config::get_orchestrator() {
+            return LinkPrincipalResponse::Unauthorized;
+        }
+
+        Config::link_principal(principal);
+
+        LinkPrincipalResponse::Ok
+    }
+
+    /// Unlink a principal from the account of the owner.
+    ///
+    /// Called by the orchestrator.
+    pub fn unlink_principal(caller: Principal, principal: Principal) -> LinkPrincipalResponse {
+        if caller != Config::get_orchestrator() {
+            return LinkPrincipalResponse::Unauthorized;
+        }
+
+        Config::unlink_principal(principal);
+
+        LinkPrincipalResponse::Ok
+    }
+
+    /// Link a principal to the account of a user, so that it can download the files shared with
+    /// the user.
+    ///
+    /// Called by the orchestrator when a principal is linked to the account of a user the owner
+    /// shares files with.
+    pub fn link_recipient_principal(
+        caller: Principal,
+        account: Principal,
+        principal: Principal,
+    ) -> LinkPrincipalResponse {
+        if caller != Config::get_orchestrator() {
+            return LinkPrincipalResponse::Unauthorized;
+        }
+
+        Config::link_recipient_principal(account, principal);
+
+        LinkPrincipalResponse::Ok
+    }
+
+    /// Unlink a principal from the account of a user the owner shares files with.
+    ///
+    /// Called by the orchestrator.
+    pub fn unlink_recipient_principal(
+        caller: Principal,
+        principal: Principal,
+    ) -> LinkPrincipalResponse {
+        if caller != Config::get_orchestrator() {
+            return LinkPrincipalResponse::Unauthorized;
+        }
+
+        Config::unlink_recipient_principal(principal);
+
+        LinkPrincipalResponse::Ok
+    }
+
+    /// Add a device key of the owner, so that document keys can be stored for the device.
+    ///
+    /// Called by the orchestrator when the owner registers a device.
+    pub fn add_device_key(caller: Principal, device_key: DeviceKey) -> SyncDeviceKeyResponse {
+        if caller != Config::get_orchestrator() {
+            return SyncDeviceKeyResponse::Unauthorized;
+        }
+
+        Config::add_owner_device_key(device_key);
+
+        SyncDeviceKeyResponse::Ok
+    }
+
+    /// Remove a device key of the owner; the device can't download files anymore.
+    ///
+    /// Called by the orchestrator.
+    pub fn remove_device_key(caller: Principal, device_id: DeviceId) -> SyncDeviceKeyResponse {
+        if caller != Config::get_orchestrator() {
+            return SyncDeviceKeyResponse::Unauthorized;
+        }
+
+        if Config::remove_owner_device_key(device_id) {
+            SyncDeviceKeyResponse::Ok
+        } else {
+            SyncDeviceKeyResponse::NoSuchDevice
+        }
+    }
+
+    /// Remove the keys stored for a device of a user the owner shares files with, so that the
+    /// device can't download the shared files anymore.
+    ///
+    /// Called by the orchestrator when the user removes the device.
+    pub fn remove_recipient_device_key(
+        caller: Principal,
+        user: Principal,
+        device_id: DeviceId,
+    ) -> SyncDeviceKeyResponse {
+        if caller != Config::get_orchestrator() {
+            return SyncDeviceKeyResponse::Unauthorized;
+        }
+
+        for file_id in FileSharesStorage::get_file_shares(&user).unwrap_or_default() {
+            FileDeviceKeysStorage::remove_key(&file_id, user, device_id);
+        }
+
+        SyncDeviceKeyResponse::Ok
+    }
+
+    /// Suspend or reinstate the owner; while suspended, uploads and new shares are rejected.
+    ///
+    /// Called by the orchestrator when an admin suspends the owner or lifts the suspension.
+    pub fn set_suspended(caller: Principal, suspended: bool) -> SetSuspendedResponse {
+        if caller != Config::get_orchestrator() {
+            return SetSuspendedResponse::Unauthorized;
+        }
+        if Config::is_suspended() == suspended {
+            return SetSuspendedResponse::Unchanged;
+        }
+
+        Config::set_suspended(suspended);
+
+        SetSuspendedResponse::Ok
+    }
+
+    /// Start the rotation of the owner public key.
+    ///
+    /// The owner must then re-wrap the document key of every file for the new public key, using
+    /// [`Canister::get_key_rotation_files`] and [`Canister::submit_key_rotation_batch`], and finally
+    /// call [`Canister::complete_key_rotation`].
+    pub fn start_key_rotation(
+        caller: Principal,
+        new_public_key: PublicKey,
+    ) -> StartKeyRotationResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can rotate the public key");
+        }
+
+        CanisterKeyRotation::start(new_public_key)
+    }
+
+    /// Get the progress of the key rotation in progress, if any.
+    pub fn key_rotation_status(caller: Principal) -> Option<KeyRotationStatus> {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can get the key rotation status");
+        }
+
+        CanisterKeyRotation::status()
+    }
+
+    /// Get the document keys which must still be re-wrapped for the new public key.
+    ///
+    /// Up to [`MAX_GET_KEY_ROTATION_FILES_LIMIT`] keys can be retrieved at once.
+    pub fn get_key_rotation_files(caller: Principal, limit: u64) -> Vec<WrappedFileKey> {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can get the key rotation files");
+        }
+
+        CanisterKeyRotation::pending_keys(limit.min(MAX_GET_KEY_ROTATION_FILES_LIMIT))
+    }
+
+    /// Submit a batch of document keys re-wrapped for the new public key.
+    pub fn submit_key_rotation_batch(
+        caller: Principal,
+        keys: Vec<WrappedFileKey>,
+    ) -> SubmitKeyRotationBatchResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can submit key rotation batches");
+        }
+
+        CanisterKeyRotation::submit_batch(keys)
+    }
+
+    /// Complete the key rotation once all the files are migrated.
+    ///
+    /// The public key is first updated on the orchestrator, then the owner public key of the
+    /// canister is replaced. If the orchestrator can't be updated, the rotation stays in progress
+    /// and can be completed again. If files were uploaded with the old key while the orchestrator
+    /// was being updated, the old key is restored on the orchestrator and the rotation stays in
+    /// progress too.
+    pub async fn complete_key_rotation(caller: Principal) -> CompleteKeyRotationResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can complete the key rotation");
+        }
+
+        let Some(rotation) = CanisterKeyRotation::status().map(|status| status.rotation) else {
+            return CompleteKeyRotationResponse::NoRotationInProgress;
+        };
+        let remaining = CanisterKeyRotation::remaining();
+        if remaining > 0 {
+            return CompleteKeyRotationResponse::FilesNotMigrated(remaining);
+        }
+
+        // update the public key on the orchestrator
+        let version = match Self::update_orchestrator_public_key(rotation.new_public_key).await {
+            Ok(version) => version,
+            Err(err) => return CompleteKeyRotationResponse::FailedToUpdateOrchestrator(err),
+        };
+
+        // files may have been uploaded with the old key in the meantime: restore the old key on
+        // the orchestrator, so that it doesn't diverge from the canister
+        let remaining = CanisterKeyRotation::remaining();
+        if remaining > 0 {
+            let version =
+                match Self::update_orchestrator_public_key(Config::get_owner_public_key()).await {
+                    Ok(version) => version,
+                    Err(err) => {
+                        return CompleteKeyRotationResponse::FailedToUpdateOrchestrator(format!(
+                            "failed to restore the previous public key: {err}"
+                        ));
+                    }
+                };
+            Config::set_owner_public_key_version(
+                version.max(Config::get_owner_public_key_version()),
+            );
+            return CompleteKeyRotationResponse::FilesNotMigrated(remaining);
+        }
+
+        CanisterKeyRotation::finalize(rotation);
+        Config::set_owner_public_key_version(version.max(Config::get_owner_public_key_version()));
+
+        CompleteKeyRotationResponse::Ok
+    }
+
+    /// Set the public key of the owner on the orchestrator, returning its new version.
+    async fn update_orchestrator_public_key(public_key: PublicKey) -> Result<u64, String> {
+        if !cfg!(target_family = "wasm") {
+            return Ok(Config::get_owner_public_key_version() + 1);
+        }
+
+        match OrchestratorClient::from(Config::get_orchestrator())
+            .update_user_public_key(Config::get_owner(), public_key)
+            .await
+        {
+            Ok(UpdateUserPublicKeyResponse::Ok(version)) => Ok(version),
+            Ok(err) => Err(format!("{err:?}")),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Get the operations waiting to be delivered to the orchestrator.
+    pub fn get_outbox(caller: Principal) -> Vec<OutboxEntryWithId> {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can get the outbox");
+        }
+
+        Outbox::entries()
+    }
+
+    /// Reconcile the shares of the canister with the share index of the orchestrator.
+    ///
+    /// The reconciliation also runs daily; see [`ShareReconciliation`].
+    pub async fn reconcile_shares(caller: Principal) -> ReconcileSharesResponse {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can reconcile the shares");
+        }
+
+        ShareReconciliation::run().await
+    }
+
+    /// Get the report of the last reconciliation of the shares, if any.
+    pub fn get_share_reconciliation_report(caller: Principal) -> Option<ShareReconciliationReport> {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can get the share reconciliation report");
+        }
+
+        ShareReconciliation::last_report()
+    }
+
+    /// Get the audit log of the canister, filtered by the given [`AuditLogFilter`].
+    ///
+    /// Up to [`MAX_GET_AUDIT_LOG_LIMIT`] entries can be retrieved at once.
+    pub fn get_audit_log(
+        caller: Principal,
+        filter: AuditLogFilter,
+        Pagination { offset, limit }: Pagination,
+    ) -> AuditLog {
+        if !Config::is_owner(caller) {
+            trap("Only the owner can get the audit log");
+        }
+
+        let limit = limit.min(MAX_GET_AUDIT_LOG_LIMIT);
+        let (entries, total) = AuditLogStorage::get_entries(&filter, offset, limit);
+        let next = if offset + limit < total {
+            Some(offset + limit)
+        } else {
+            None
+        };
+
+        AuditLog {
+            entries,
+            next,
+            total,
+        }
+    }
+
+    /// Serve HTTP requests through the HTTP gateway.
+    ///
+    /// Only the metrics of the canister are served, under [`METRICS_PATH`], in the Prometheus
+    /// text format.
+    pub fn http_request(request: HttpRequest) -> HttpResponse {
+        if request.path() != METRICS_PATH {
+            return HttpResponse::not_found();
+        }
+        if request.method != "GET" {
+            return HttpResponse::method_not_allowed();
+        }
+
+        HttpResponse::metrics(Self::encode_metrics())
+    }
+
+    /// Record the share of a file with the given users into the outbox, and deliver it.
+    ///
+    /// Users without a key, or the file is already shared with, are skipped.
+    /// Store the document key of a file encrypted for the devices of the owner, replacing the
+    /// previous ones.
+    ///
+    /// The keys are stored under the owner principal; keys for devices the owner hasn't
+    /// registered are ignored.
+    fn set_owner_device_keys(file_id: FileId, device_keys: Vec<DeviceOwnerKey>) {
+        let device_keys = device_keys
+            .into_iter()
+            .filter(|key| Config::is_owner_device(key.device_id))
+            .collect();
+        FileDeviceKeysStorage::set_keys(&file_id, Config::get_owner(), device_keys);
+    }
+
+    async fn share_with(
+        file_id: FileId,
+        users: Vec<Principal>,
+        keys: Vec<OwnerKey>,
+        mut device_keys: Vec<Vec<DeviceOwnerKey>>,
+        mode: ShareMode,
+    ) -> ShareFileWithUsersResponse {
+        if Config::is_suspended() {
+            return ShareFileWithUsersResponse::Suspended;
+        }
+
+        // check whether we can share the file
+        match share::CanisterShareFile::check_shareable(file_id) {
+            FileSharingResponse::Ok => {}
+            FileSharingResponse::PendingError => {
+                return ShareFileWithUsersResponse::PendingError;
+            }
+            _ => {
+                return ShareFileWithUsersResponse::FileNotFound;
+            }
+        }
+
+        let Some(path) = PathStorage::read_link(&file_id) else {
+            return ShareFileWithUsersResponse::FileNotFound;
+        };
+
+        // check each user; `None` stands for the users to share the file with
+        let mut shared = FileSharesStorage::get_users_with_file_shares(&file_id)
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        let mut outcomes = Vec::with_capacity(users.len());
+        let mut to_share = vec![];
+        for (index, user) in users.iter().enumerate() {
+            let outcome = match keys.get(index) {
+                None => Some(ShareOutcome::KeyLengthMismatch),
+                Some(_) if !shared.insert(*user) => Some(ShareOutcome::AlreadyShared),
+                Some(key) => {
+                    to_share.push((*user, *key));
+                    None
+                }
+            };
+            outcomes.push(outcome);
+        }
+
+        // in all-or-nothing mode, the file is shared with nobody if a user has no key
+        let failed = outcomes
+            .iter()
+            .any(|outcome| matches!(outcome, Some(ShareOutcome::KeyLengthMismatch)));
+        let delivery = if to_share.is_empty() {
+            None
+        } else if failed && mode == ShareMode::AllOrNothing {
+            return ShareFileWithUsersResponse::Ok(
+                users
+                    .into_iter()
+                    .zip(outcomes)
+                    .map(|(user, outcome)| ShareResult {
+                        user,
+                        outcome: outcome.unwrap_or(ShareOutcome::NotShared),
+                    })
+                    .collect(),
+            );
+        } else {
+            // the device keys are stored right away, since downloads check the share first
+            device_keys.resize(users.len(), vec![]);
+            for (user, _) in &to_share {
+                if let Some(index) = users.iter().position(|share_user| share_user == user) {
+                    FileDeviceKeysStorage::set_keys(
+                        &file_id,
+                        *user,
+                        std::mem::take(&mut device_keys[index]),
+                    );
+                }
+            }
+            let file_name = path.file_name().unwrap_or_default().to_string();
+            let id = Outbox::share(file_id, file_name, to_share, mode == ShareMode::Partial);
+            Some(Outbox::deliver(id).await)
+        };
+
+        let pending = Outbox::pending_shares(file_id);
+        let results = users
+            .into_iter()
+            .zip(outcomes)
+            .map(|(user, outcome)| {
+                let outcome = outcome.unwrap_or_else(|| match &delivery {
+                    Some(outbox::Delivery::Delivered { unknown_users, .. })
+                        if unknown_users.contains(&user) =>
+                    {
+                        ShareOutcome::NoSuchUser
+                    }
+                    Some(outbox::Delivery::Delivered { blocked_by, .. })
+                        if blocked_by.contains(&user) =>
+                    {
+                        ShareOutcome::Blocked
+                    }
+                    Some(outbox::Delivery::NoSuchUser(unknown)) if *unknown == user => {
+                        ShareOutcome::NoSuchUser
+                    }
+                    Some(outbox::Delivery::Blocked(blocked_by)) if *blocked_by == user => {
+                        ShareOutcome::Blocked
+                    }
+                    Some(outbox::Delivery::NoSuchUser(_) | outbox::Delivery::Blocked(_)) => {
+                        ShareOutcome::NotShared
+                    }
+                    Some(outbox::Delivery::Rejected(err)) => ShareOutcome::Rejected(err.clone()),
+                    // users dropped from a partial share before a failed delivery are unknown
+                    Some(outbox::Delivery::Failed(_) | outbox::Delivery::Skipped)
+                        if !pending.contains(&user) =>
+                    {
+                        ShareOutcome::NoSuchUser
+                    }
+                    Some(outbox::Delivery::Failed(_) | outbox::Delivery::Skipped) => {
+                        ShareOutcome::Pending
+                    }
+                    Some(outbox::Delivery::Delivered { .. }) | None => ShareOutcome::Ok,
+                });
+                ShareResult { user, outcome }
+            })
+            .collect::<Vec<_>>();
+
+        // drop the device keys of the users the file was not shared with
+        for ShareResult { user, outcome } in &results {
+            if matches!(
+                outcome,
+                ShareOutcome::NoSuchUser
+                    | ShareOutcome::Blocked
+                    | ShareOutcome::NotShared
+                    | ShareOutcome::Rejected(_)
+            ) {
+                FileDeviceKeysStorage::remove_user_keys(&file_id, *user);
+            }
+        }
+
+        ShareFileWithUsersResponse::Ok(results)
+    }
+
+    /// Revoke the shares of a file for the given users, and deliver the revocation to the
+    /// orchestrator.
+    async fn revoke_for(file_id: FileId, users: Vec<Principal>) -> RevokeShareForUsersResponse {
+        if FileDataStorage::get_file(&file_id).is_none() {
+            return RevokeShareForUsersResponse::FileNotFound;
+        }
+
+        let shared = FileSharesStorage::get_users_with_file_shares(&file_id)
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        let pending = Outbox::pending_shares(file_id);
+        let owner = Config::get_owner();
+        let mut revoked = vec![];
+        let mut results = Vec::with_capacity(users.len());
+        for user in users {
+            let outcome = if revoked.contains(&user) {
+                RevokeOutcome::NotShared
+            } else if shared.contains(&user) {
+                share::CanisterShareFile::revoke_share(user, file_id);
+                Self::audit(owner, AuditAction::RevokeShare { user }, file_id);
+                revoked.push(user);
+                RevokeOutcome::Ok
+            } else if pending.contains(&user) {
+                // the pending share may have reached the orchestrator already
+                revoked.push(user);
+                RevokeOutcome::Ok
+            } else {
+                RevokeOutcome::NotShared
+            };
+            results.push(RevokeResult { user, outcome });
+        }
+
+        if !revoked.is_empty() {
+            let id = Outbox::revoke(file_id, revoked);
+            Outbox::deliver(id).await;
+        }
+
+        RevokeShareForUsersResponse::Ok(results)
+    }
+
+    /// Remove a file, its contents and its shares from the canister, cancelling its pending
+    /// shares.
+    ///
+    /// Returns the users whose share must be revoked on the orchestrator.
+    fn remove_file(file_id: FileId, file: File) -> Vec<Principal> {
+        // Check if the file is shared with any users, or is about to be.
+        let mut users_with_access = FileSharesStorage::get_users_with_file_shares(&file_id);
+        users_with_access.extend(Outbox::cancel_shares(file_id));
+
+        // If the file is shared, remove the sharing information from the storage
+        for user_id in &users_with_access {
+            // remove file from user shares
+            FileSharesStorage::revoke(user_id, &file_id);
+        }
+        // remove file
+        FileDataStorage::remove_file(&file_id);
+        OwnedFilesStorage::remove_owned_file(&file_id);
+        // remove file content / alias
+        match file.content {
+            FileContent::PartiallyUploaded { num_chunks, .. }
+            | FileContent::Uploaded { num_chunks, .. } => {
+                for chunk_id in 0..num_chunks {
+                    FileContentsStorage::remove_file_contents(&file_id, &chunk_id);
+                }
+            }
+            FileContent::Pending { alias } => {
+                FileAliasIndexStorage::remove_file_id(&alias);
+            }
+        }
+        // remove file path
+        PathStorage::unlink(file_id);
+        // remove access log
+        FileAccessLogStorage::remove_file_access_log(&file_id);
+        // remove device keys
+        FileDeviceKeysStorage::remove_file_keys(&file_id);
+        // remove staged re-encryption
+        ReencryptionStorage::remove(&file_id);
+
+        users_with_access
+    }
+
+    /// Encode the metrics of the canister in the Prometheus text format.
+    fn encode_metrics() -> String {
+        let FileStats {
+            stored_bytes,
+            pending,
+            partially_uploaded,
+            uploaded,
+            share_recipients,
+            shares,
+        } = FileStatsStorage::get();
+
+        let mut encoder = MetricsEncoder::default();
+        encoder
+            .gauge_vec(
+                "user_canister_files",
+                "Number of files by state.",
+                "state",
+                [
+                    ("pending", pending as f64),
+                    ("partially_uploaded", partially_uploaded as f64),
+                    ("uploaded", uploaded as f64),
+                ],
+            )
+            .gauge(
+                "user_canister_stored_bytes",
+                "Size of the stored file contents, in bytes.",
+                stored_bytes as f64,
+            )
+            .gauge(
+                "user_canister_shares",
+                "Number of files shared with users, counted once per user.",
+                shares as f64,
+            )
+            .gauge(
+                "user_canister_share_recipients",
+                "Number of users files are shared with.",
+                share_recipients as f64,
+            )
+            .gauge(
+                "user_canister_stable_memory_pages",
+                "Size of the stable memory, in pages of 64 KiB.",
+                stable_memory_pages() as f64,
+            )
+            .gauge(
+                "user_canister_heap_memory_bytes",
+                "Size of the heap memory, in bytes.",
+                heap_memory_size() as f64,
+            )
+            .gauge(
+                "user_canister_cycle_balance",
+                "Cycle balance of the canister.",
+                cycle_balance() as f64,
+            );
+
+        encoder.finish()
+    }
+
+    /// Record an action performed by `actor` on a file into the audit log.
+    ///
+    /// Must be called while the file path exists.
+    fn audit(actor: Principal, action: AuditAction, file_id: FileId) {
+        let Some(path) = PathStorage::read_link(&file_id) else {
+            return;
+        };
+
+        AuditLogStorage::append(AuditLogEntry {
+            actor,
+            action,
+            file_id,
+            path,
+            timestamp: time(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use candid::Principal;
+    use did::user_canister::{KeyRotation, UserCanisterInitArgs};
+
+    use super::*;
+
+    #[test]
+    fn test_should_init_canister() {
+        let orchestrator = Principal::from_slice(&[0, 1, 2, 3]);
+        let owner = Principal::from_slice(&[4, 5, 6, 7]);
+        Canister::init(UserCanisterInstallArgs::Init(UserCanisterInitArgs {
+            orchestrator,
+            owner,
+        }));
+
+        assert_eq!(Config::get_orchestrator(), orchestrator);
+        assert_eq!(Config::get_owner(), owner);
+    }
+
+    #[tokio::test]
+    async fn test_should_request_file() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        let alias = Canister::request_file(caller, path).await.unwrap();
+        // NOTE: we expect it to end with 0 because on unit tests the randomness is just zero.
+        assert!(alias.ends_with("7000-8000-000000000000"));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Only the owner can request a file")]
+    async fn test_should_not_request_file_if_not_owner() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        init();
+        Canister::request_file(Principal::anonymous(), path)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_should_get_requests() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        Canister::request_file(caller, path.clone()).await;
+        let requests = Canister::get_requests(caller);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].file_name, "test_file.txt".to_string());
+        assert_eq!(requests[0].file_path, path);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Only the owner can get requests for a file")]
+    async fn test_should_not_get_requests_if_not_owner() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        Canister::request_file(caller, path).await;
+        Canister::get_requests(Principal::anonymous());
+    }
+
+    #[tokio::test]
+    async fn test_should_request_file_from_linked_principal() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        let device = Principal::from_slice(&[4; 29]);
+        assert_eq!(
+            Canister::link_principal(device, device),
+            LinkPrincipalResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::link_principal(Config::get_orchestrator(), device),
+            LinkPrincipalResponse::Ok
+        );
+
+        Canister::request_file(device, path.clone()).await;
+        assert_eq!(Canister::get_requests(caller).len(), 1);
+        assert_eq!(Canister::get_requests(device).len(), 1);
+
+        assert_eq!(
+            Canister::unlink_principal(device, device),
+            LinkPrincipalResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::unlink_principal(Config::get_orchestrator(), device),
+            LinkPrincipalResponse::Ok
+        );
+        assert!(!Config::is_owner(device));
+    }
+
+    #[tokio::test]
+    async fn test_should_not_create_already_existing_file() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        Canister::request_file(caller, path.clone()).await.unwrap();
+
+        // create same path
+        let result = Canister::request_file(caller, path).await;
         assert_eq!(result, RequestFileResponse::FileAlreadyExists);
     }
 
     #[tokio::test]
-    async fn test_should_upload_file() {
+    async fn test_should_upload_file() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        let alias = Canister::request_file(caller, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        let result = Canister::upload_file(
+            caller,
+            file_id,
+            file_content.clone(),
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
+        );
+        assert!(result.is_ok());
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        assert_eq!(
+            file.content,
+            FileContent::Uploaded {
+                file_type,
+                owner_key,
+                shared_keys: BTreeMap::new(),
+                num_chunks,
+            }
+        );
+    }
+
+    #[test]
+    fn test_should_upload_file_atomic() {
+        let caller = init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        let file_id = Canister::upload_file_atomic(
+            caller,
+            UploadFileAtomicRequest {
+                path,
+                content: file_content.clone(),
+                file_type,
+                owner_key,
+                num_chunks,
+                device_keys: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(file_id, 0);
+
+        // Check if the file was uploaded correctly
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        assert_eq!(
+            file.content,
+            FileContent::Uploaded {
+                file_type: "text/plain".to_string(),
+                owner_key,
+                shared_keys: BTreeMap::new(),
+                num_chunks,
+            }
+        );
+        // Check if the file content was stored correctly
+        let file_content_stored = FileContentsStorage::get_file_contents(&file_id, &0).unwrap();
+        assert_eq!(file_content_stored, file_content);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can upload a file")]
+    fn test_should_not_upload_file_atomic_if_not_owner() {
+        init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        Canister::upload_file_atomic(
+            Principal::anonymous(),
+            UploadFileAtomicRequest {
+                path,
+                content: file_content,
+                file_type,
+                owner_key,
+                num_chunks,
+                device_keys: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_should_not_upload_atomic_if_file_already_exists() {
+        let caller = init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        Canister::upload_file_atomic(
+            caller,
+            UploadFileAtomicRequest {
+                path: path.clone(),
+                content: file_content.clone(),
+                file_type: file_type.clone(),
+                owner_key,
+                num_chunks,
+                device_keys: None,
+            },
+        )
+        .unwrap();
+
+        let res = Canister::upload_file_atomic(
+            caller,
+            UploadFileAtomicRequest {
+                path: path.clone(),
+                content: file_content,
+                file_type,
+                owner_key,
+                num_chunks,
+                device_keys: None,
+            },
+        );
+
+        assert_eq!(res, UploadFileAtomicResponse::FileAlreadyExists);
+    }
+
+    #[test]
+    fn test_should_upload_file_continue() {
+        let caller = init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 2;
+        let file_id = Canister::upload_file_atomic(
+            caller,
+            UploadFileAtomicRequest {
+                path,
+                content: file_content.clone(),
+                file_type,
+                owner_key,
+                num_chunks,
+                device_keys: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(file_id, 0);
+
+        // Check if the file was uploaded correctly
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        let mut uploaded_chunks = UploadedChunks::default();
+        uploaded_chunks.insert(0);
+        assert_eq!(
+            file.content,
+            FileContent::PartiallyUploaded {
+                num_chunks,
+                uploaded_chunks,
+                file_type: "text/plain".to_string(),
+                owner_key,
+                shared_keys: BTreeMap::new(),
+            }
+        );
+
+        // Upload the second chunk
+        let result = Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 1,
+            contents: vec![4, 5, 6],
+        });
+        assert_eq!(result, UploadFileContinueResponse::Ok);
+
+        // Check if the file content was stored correctly
+        let file_content_stored_0 = FileContentsStorage::get_file_contents(&file_id, &0).unwrap();
+        assert_eq!(file_content_stored_0, vec![1, 2, 3]);
+
+        let file_content_stored_1 = FileContentsStorage::get_file_contents(&file_id, &1).unwrap();
+        assert_eq!(file_content_stored_1, vec![4, 5, 6]);
+        // Check if the file content was updated correctly
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        assert_eq!(
+            file.content,
+            FileContent::Uploaded {
+                file_type: "text/plain".to_string(),
+                owner_key,
+                shared_keys: BTreeMap::new(),
+                num_chunks,
+            }
+        );
+    }
+
+    #[test]
+    fn test_should_upload_file_continue_arbitrary_order_and_eval_responses() {
+        let caller = init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 6;
+
+        // Check response for unknown file
+        let result = Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id: 0,
+            chunk_id: 1,
+            contents: vec![4, 5, 6],
+        });
+        assert_eq!(result, UploadFileContinueResponse::FileNotFound);
+
+        let file_id = Canister::upload_file_atomic(
+            caller,
+            UploadFileAtomicRequest {
+                path,
+                content: file_content.clone(),
+                file_type,
+                owner_key,
+                num_chunks,
+                device_keys: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(file_id, 0);
+
+        // Upload chunks in arbitrary order
+        Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 3,
+            contents: vec![10, 11, 12],
+        });
+        Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 1,
+            contents: vec![4, 5, 6],
+        });
+
+        // Upload a duplicate chunk
+        let result = Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 1,
+            contents: vec![4, 5, 6],
+        });
+        assert_eq!(result, UploadFileContinueResponse::ChunkAlreadyUploaded);
+
+        //Check out of bounds chunk
+        let result = Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 6,
+            contents: vec![19, 20, 21],
+        });
+        assert_eq!(result, UploadFileContinueResponse::ChunkOutOfBounds);
+
+        // Upload the remaining chunks
+        Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 5,
+            contents: vec![16, 17, 18],
+        });
+        Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 4,
+            contents: vec![13, 14, 15],
+        });
+        Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 2,
+            contents: vec![7, 8, 9],
+        });
+
+        // Check if the file content was stored correctly
+        for i in 0..num_chunks {
+            let file_content_stored = FileContentsStorage::get_file_contents(&file_id, &i).unwrap();
+            assert_eq!(
+                file_content_stored,
+                vec![i as u8 * 3 + 1, i as u8 * 3 + 2, i as u8 * 3 + 3]
+            );
+        }
+        // Check if the file content was updated correctly
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        assert_eq!(
+            file.content,
+            FileContent::Uploaded {
+                file_type: "text/plain".to_string(),
+                owner_key,
+                shared_keys: BTreeMap::new(),
+                num_chunks,
+            }
+        );
+
+        // Check already uploaded file
+        let result = Canister::upload_file_continue(UploadFileContinueRequest {
+            file_id,
+            chunk_id: 1,
+            contents: vec![4, 5, 6],
+        });
+        assert_eq!(result, UploadFileContinueResponse::FileAlreadyUploaded);
+    }
+
+    #[tokio::test]
+    async fn test_should_download_file() {
+        let caller = init();
+        let owner = init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let alias = Canister::request_file(owner, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        let _ = Canister::upload_file(
+            caller,
+            file_id,
+            file_content.clone(),
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
+        );
+        // Download the file as the owner
+        let result = Canister::download_file(owner, file_id, 0, None);
+        assert_eq!(
+            result,
+            FileDownloadResponse::FoundFile(FileData {
+                contents: file_content.clone(),
+                file_type: file_type.clone(),
+                owner_key,
+                num_chunks
+            })
+        );
+        // Download the file as a shared user
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [6; OwnerKey::KEY_SIZE].into();
+        Canister::share_file(
+            caller,
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            vec![],
+        )
+        .await;
+        let result = Canister::download_file(user_id, file_id, 0, None);
+        assert_eq!(
+            result,
+            FileDownloadResponse::FoundFile(FileData {
+                contents: file_content,
+                file_type,
+                owner_key: [6; OwnerKey::KEY_SIZE].into(),
+                num_chunks
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_download_shared_file_from_linked_principal() {
+        let owner = init();
+        let file_id = upload_test_file(owner, "/test_file.txt").await;
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let user_phone = Principal::from_slice(&[14; 29]);
+        Canister::share_file(
+            owner,
+            user_id,
+            file_id,
+            [6; OwnerKey::KEY_SIZE].into(),
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            Canister::download_file(user_phone, file_id, 0, None),
+            FileDownloadResponse::PermissionError
+        );
+
+        assert_eq!(
+            Canister::link_recipient_principal(user_phone, user_id, user_phone),
+            LinkPrincipalResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::link_recipient_principal(Config::get_orchestrator(), user_id, user_phone),
+            LinkPrincipalResponse::Ok
+        );
+        let FileDownloadResponse::FoundFile(data) =
+            Canister::download_file(user_phone, file_id, 0, None)
+        else {
+            panic!("the linked principal should download the file");
+        };
+        assert_eq!(data.owner_key, [6; OwnerKey::KEY_SIZE].into());
+        assert_eq!(
+            Canister::confirm_download(user_phone, file_id),
+            ConfirmDownloadResponse::Ok
+        );
+
+        assert_eq!(
+            Canister::unlink_recipient_principal(Config::get_orchestrator(), user_phone),
+            LinkPrincipalResponse::Ok
+        );
+        assert_eq!(
+            Canister::download_file(user_phone, file_id, 0, None),
+            FileDownloadResponse::PermissionError
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_download_file_with_device_key() {
+        let owner = init();
+        let device_key = |device_id, byte| DeviceOwnerKey {
+            device_id,
+            key: [byte; OwnerKey::KEY_SIZE].into(),
+        };
+        assert_eq!(
+            Canister::add_device_key(
+                Config::get_orchestrator(),
+                DeviceKey {
+                    device_id: 1,
+                    name: "Laptop".to_string(),
+                    public_key: vec![1; 32].try_into().unwrap(),
+                }
+            ),
+            SyncDeviceKeyResponse::Ok
+        );
+
+        // keys for unknown devices of the owner are ignored
+        let UploadFileAtomicResponse::Ok(file_id) = Canister::upload_file_atomic(
+            owner,
+            UploadFileAtomicRequest {
+                path: Path::new("/test_file.txt").expect("valid path"),
+                content: vec![1, 2, 3],
+                owner_key: [0; OwnerKey::KEY_SIZE].into(),
+                file_type: "text/plain".to_string(),
+                num_chunks: 1,
+                device_keys: Some(vec![device_key(1, 1), device_key(2, 2)]),
+            },
+        ) else {
+            panic!("failed to upload file");
+        };
+        let owner_key_for =
+            |caller, device_id| match Canister::download_file(caller, file_id, 0, device_id) {
+                FileDownloadResponse::FoundFile(data) => Ok(data.owner_key),
+                response => Err(response),
+            };
+        assert_eq!(owner_key_for(owner, Some(1)), Ok(device_key(1, 1).key));
+        assert_eq!(
+            owner_key_for(owner, Some(2)),
+            Err(FileDownloadResponse::DeviceKeyNotFound)
+        );
+
+        // keys of the recipient
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        Canister::share_file(
+            owner,
+            user_id,
+            file_id,
+            [6; OwnerKey::KEY_SIZE].into(),
+            vec![device_key(0, 7)],
+        )
+        .await;
+        assert_eq!(
+            owner_key_for(user_id, None),
+            Ok([6; OwnerKey::KEY_SIZE].into())
+        );
+        assert_eq!(owner_key_for(user_id, Some(0)), Ok(device_key(0, 7).key));
+        assert_eq!(
+            Canister::set_device_keys(owner, file_id, user_id, vec![device_key(1, 8)]),
+            SetDeviceKeysResponse::Ok
+        );
+        assert_eq!(
+            owner_key_for(user_id, Some(0)),
+            Err(FileDownloadResponse::DeviceKeyNotFound)
+        );
+        assert_eq!(owner_key_for(user_id, Some(1)), Ok(device_key(1, 8).key));
+        assert_eq!(
+            Canister::set_device_keys(
+                owner,
+                file_id,
+                Principal::from_slice(&[8; 29]),
+                vec![device_key(1, 8)]
+            ),
+            SetDeviceKeysResponse::NotShared
+        );
+
+        // removed devices of the owner can't download anymore
+        assert_eq!(
+            Canister::remove_device_key(Principal::from_slice(&[8; 29]), 1),
+            SyncDeviceKeyResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::remove_device_key(Config::get_orchestrator(), 1),
+            SyncDeviceKeyResponse::Ok
+        );
+        assert_eq!(
+            Canister::remove_device_key(Config::get_orchestrator(), 1),
+            SyncDeviceKeyResponse::NoSuchDevice
+        );
+        assert_eq!(
+            owner_key_for(owner, Some(1)),
+            Err(FileDownloadResponse::DeviceKeyNotFound)
+        );
+
+        // removed devices of the recipient can't download anymore
+        assert_eq!(
+            Canister::remove_recipient_device_key(user_id, user_id, 1),
+            SyncDeviceKeyResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::remove_recipient_device_key(Config::get_orchestrator(), user_id, 1),
+            SyncDeviceKeyResponse::Ok
+        );
+        assert_eq!(
+            owner_key_for(user_id, Some(1)),
+            Err(FileDownloadResponse::DeviceKeyNotFound)
+        );
+        assert_eq!(
+            owner_key_for(user_id, None),
+            Ok([6; OwnerKey::KEY_SIZE].into())
+        );
+
+        // revoking the share drops the keys of the recipient
+        Canister::set_device_keys(owner, file_id, user_id, vec![device_key(2, 9)]);
+        Canister::revoke_file_sharing(owner, user_id, file_id).await;
+        assert_eq!(FileDeviceKeysStorage::get_key(&file_id, user_id, 2), None);
+        assert_eq!(FileDeviceKeysStorage::get_key(&file_id, user_id, 1), None);
+    }
+
+    #[tokio::test]
+    async fn test_should_not_download_file_if_not_uploaded() {
+        let caller = init();
+        let owner = init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let alias = Canister::request_file(owner, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        // Attempt to download the file on pending state
+        let result = Canister::download_file(caller, file_id, 0, None);
+        assert_eq!(result, FileDownloadResponse::NotUploadedFile);
+
+        // Attempt to download the file on partially uploaded state
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 2;
+        let _ = Canister::upload_file(
+            caller,
+            file_id,
+            file_content.clone(),
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
+        );
+        let result = Canister::download_file(caller, file_id, 0, None);
+        assert_eq!(result, FileDownloadResponse::NotUploadedFile);
+    }
+
+    #[tokio::test]
+    async fn test_should_not_download_file_if_not_owner() {
+        let caller = init();
+        let owner = init();
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let alias = Canister::request_file(owner, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        let _ = Canister::upload_file(
+            caller,
+            file_id,
+            file_content.clone(),
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
+        );
+
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [6; OwnerKey::KEY_SIZE].into();
+        Canister::share_file(
+            caller,
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            vec![],
+        )
+        .await;
+
+        let res = Canister::download_file(Principal::anonymous(), file_id, 0, None);
+        assert_eq!(res, FileDownloadResponse::PermissionError);
+    }
+
+    #[tokio::test]
+    async fn test_should_share_a_file() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        let alias = Canister::request_file(caller, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+        let result = Canister::share_file(
+            caller,
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            vec![],
+        )
+        .await;
+        assert_eq!(result, FileSharingResponse::PendingError);
+        // Upload the file first
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        let res = Canister::upload_file(
+            caller,
+            file_id,
+            file_content,
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
+        );
+        assert!(res.is_ok());
+        // Now share the file
+        let result = Canister::share_file(
+            caller,
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            vec![],
+        )
+        .await;
+        assert_eq!(result, FileSharingResponse::Ok);
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+
+        assert_eq!(
+            file.content,
+            FileContent::Uploaded {
+                file_type: file_type.clone(),
+                owner_key,
+                shared_keys: BTreeMap::from([(user_id, file_key_encrypted_for_user)]),
+                num_chunks,
+            }
+        );
+        // Check if the file is shared with the user
+        let shared_files = Canister::get_shared_files(caller, user_id);
+        assert_eq!(shared_files.len(), 1);
+        assert_eq!(shared_files[0].file_id, file_id);
+    }
+
+    #[tokio::test]
+    async fn test_should_share_more_than_a_file() {
+        let caller = init();
+
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let mut file_ids = vec![];
+        for i in 0..3 {
+            let path = Path::new(format!("/test_file_{i}.txt")).expect("valid path");
+            let alias = Canister::request_file(caller, path).await.unwrap();
+            let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+            let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+            let result = Canister::share_file(
+                caller,
+                user_id,
+                file_id,
+                file_key_encrypted_for_user,
+                vec![],
+            )
+            .await;
+            assert_eq!(result, FileSharingResponse::PendingError);
+            // Upload the file first
+            let file_content = vec![1, 2, 3];
+            let file_type = "text/plain".to_string();
+            let owner_key = [0; OwnerKey::KEY_SIZE].into();
+            let num_chunks = 1;
+            let res = Canister::upload_file(
+                caller,
+                file_id,
+                file_content,
+                file_type.clone(),
+                owner_key,
+                num_chunks,
+                vec![],
+            );
+            assert!(res.is_ok());
+            // Now share the file
+            let result = Canister::share_file(
+                caller,
+                user_id,
+                file_id,
+                file_key_encrypted_for_user,
+                vec![],
+            )
+            .await;
+            assert_eq!(result, FileSharingResponse::Ok);
+            let file = FileDataStorage::get_file(&file_id).unwrap();
+
+            assert_eq!(
+                file.content,
+                FileContent::Uploaded {
+                    file_type: file_type.clone(),
+                    owner_key,
+                    shared_keys: BTreeMap::from([(user_id, file_key_encrypted_for_user)]),
+                    num_chunks,
+                }
+            );
+
+            file_ids.push(file_id);
+        }
+
+        // Check if the file is shared with the user
+        let shared_files = Canister::get_shared_files(caller, user_id);
+        assert_eq!(shared_files.len(), 3);
+        assert!(file_ids.contains(&shared_files[0].file_id));
+        assert!(file_ids.contains(&shared_files[1].file_id));
+        assert!(file_ids.contains(&shared_files[2].file_id));
+    }
+
+    #[tokio::test]
+    async fn should_share_file_with_users() {
         let path = Path::new("/test_file.txt").expect("valid path");
         let caller = init();
         let alias = Canister::request_file(caller, path).await.unwrap();
         let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        //upload the file first
         let file_content = vec![1, 2, 3];
         let file_type = "text/plain".to_string();
         let owner_key = [0; OwnerKey::KEY_SIZE].into();
         let num_chunks = 1;
-        let result = Canister::upload_file(
+        let res = Canister::upload_file(
+            caller,
             file_id,
-            file_content.clone(),
+            file_content,
             file_type.clone(),
             owner_key,
             num_chunks,
+            vec![],
         );
-        assert!(result.is_ok());
+        assert!(res.is_ok());
+        // Now share the file with multiple users
+
+        let user_ids = vec![
+            Principal::from_slice(&[4, 5, 6, 7]),
+            Principal::from_slice(&[8, 9, 10, 11]),
+        ];
+        let file_key_encrypted_for_user = vec![
+            [2; OwnerKey::KEY_SIZE].into(),
+            [1; OwnerKey::KEY_SIZE].into(),
+        ];
+        let response = Canister::share_file_with_users(
+            caller,
+            user_ids.clone(),
+            file_id,
+            file_key_encrypted_for_user,
+            ShareMode::AllOrNothing,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            response,
+            ShareFileWithUsersResponse::Ok(
+                user_ids
+                    .iter()
+                    .map(|user| ShareResult {
+                        user: *user,
+                        outcome: ShareOutcome::Ok,
+                    })
+                    .collect()
+            )
+        );
+        for user_id in user_ids {
+            let shared_files = Canister::get_shared_files(caller, user_id);
+            assert_eq!(shared_files.len(), 1);
+            assert_eq!(shared_files[0].file_id, file_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_should_report_share_outcome_per_user() {
+        let caller = init();
+        let file_id = upload_test_file(caller, "/test_file.txt").await;
+        let alice = Principal::from_slice(&[4, 5, 6, 7]);
+        let bob = Principal::from_slice(&[8, 9, 10, 11]);
+        let charlie = Principal::from_slice(&[12, 13, 14, 15]);
+        let key: OwnerKey = [1; OwnerKey::KEY_SIZE].into();
+        assert_eq!(
+            Canister::share_file(caller, alice, file_id, key, vec![]).await,
+            FileSharingResponse::Ok
+        );
+
+        // charlie has no key, so nobody is shared with in all-or-nothing mode
+        let response = Canister::share_file_with_users(
+            caller,
+            vec![alice, bob, charlie],
+            file_id,
+            vec![key, key],
+            ShareMode::AllOrNothing,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            response,
+            ShareFileWithUsersResponse::Ok(vec![
+                ShareResult {
+                    user: alice,
+                    outcome: ShareOutcome::AlreadyShared,
+                },
+                ShareResult {
+                    user: bob,
+                    outcome: ShareOutcome::NotShared,
+                },
+                ShareResult {
+                    user: charlie,
+                    outcome: ShareOutcome::KeyLengthMismatch,
+                },
+            ])
+        );
+        assert!(Canister::get_shared_files(caller, bob).is_empty());
+
+        // in partial mode, bob is shared with
+        let response = Canister::share_file_with_users(
+            caller,
+            vec![alice, bob, charlie],
+            file_id,
+            vec![key, key],
+            ShareMode::Partial,
+            vec![],
+        )
+        .await;
+        let ShareFileWithUsersResponse::Ok(results) = response else {
+            panic!("unexpected response: {response:?}");
+        };
+        assert_eq!(
+            results
+                .into_iter()
+                .map(|result| result.outcome)
+                .collect::<Vec<_>>(),
+            vec![
+                ShareOutcome::AlreadyShared,
+                ShareOutcome::Ok,
+                ShareOutcome::KeyLengthMismatch,
+            ]
+        );
+        assert_eq!(Canister::get_shared_files(caller, bob).len(), 1);
+
+        // revoke
+        assert_eq!(
+            Canister::revoke_share_for_users(caller, vec![alice, charlie, alice], file_id).await,
+            RevokeShareForUsersResponse::Ok(vec![
+                RevokeResult {
+                    user: alice,
+                    outcome: RevokeOutcome::Ok,
+                },
+                RevokeResult {
+                    user: charlie,
+                    outcome: RevokeOutcome::NotShared,
+                },
+                RevokeResult {
+                    user: alice,
+                    outcome: RevokeOutcome::NotShared,
+                },
+            ])
+        );
+        assert_eq!(
+            Canister::revoke_file_sharing(caller, alice, file_id).await,
+            RevokeShareResponse::NotShared
+        );
+        assert_eq!(
+            Canister::revoke_file_sharing(caller, bob, 42).await,
+            RevokeShareResponse::FileNotFound
+        );
+        assert_eq!(
+            FileSharesStorage::get_users_with_file_shares(&file_id),
+            vec![bob]
+        );
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Only the owner can share a file")]
+    async fn test_only_owner_should_share_file() {
+        init();
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_id = 1;
+        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+        Canister::share_file(
+            Principal::anonymous(),
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            vec![],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_should_revoke_file_sharing() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        let alias = Canister::request_file(caller, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        //upload the file first
+        let file_content = vec![1, 2, 3];
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+        let res = Canister::upload_file(
+            caller,
+            file_id,
+            file_content,
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
+        );
+        assert!(res.is_ok());
+        // Now share the file with  user
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+        Canister::share_file(
+            caller,
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            vec![],
+        )
+        .await;
+        // Revoke sharing
+        assert_eq!(
+            Canister::revoke_file_sharing(caller, user_id, file_id).await,
+            RevokeShareResponse::Ok
+        );
+        // Check if the user can still access the shared files
+        let shared_files = Canister::get_shared_files(caller, user_id);
+        assert_eq!(shared_files.len(), 0);
+        // check if file has its sharing revoked
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        assert_eq!(
+            file.content,
+            FileContent::Uploaded {
+                file_type: file_type.clone(),
+                owner_key,
+                shared_keys: BTreeMap::new(),
+                num_chunks,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_revoke_file_sharing_when_more_files_are_shared() {
+        let caller = init();
+        let mut file_ids = vec![];
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+        let file_type = "text/plain".to_string();
+        let owner_key = [0; OwnerKey::KEY_SIZE].into();
+        let num_chunks = 1;
+
+        for i in 0..5 {
+            let path = Path::new(format!("/test_file_{i}.txt")).expect("valid path");
+            let alias = Canister::request_file(caller, path).await.unwrap();
+            let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+            //upload the file first
+            let file_content = vec![1, 2, 3];
+
+            let res = Canister::upload_file(
+                caller,
+                file_id,
+                file_content,
+                file_type.clone(),
+                owner_key,
+                num_chunks,
+                vec![],
+            );
+            assert!(res.is_ok());
+            // Now share the file with  user
+            Canister::share_file(
+                caller,
+                user_id,
+                file_id,
+                file_key_encrypted_for_user,
+                vec![],
+            )
+            .await;
+
+            file_ids.push(file_id);
+        }
+
+        let file_id = file_ids[1];
+        // Revoke sharing
+        Canister::revoke_file_sharing(caller, user_id, file_id).await;
+        // Check if the user can still access the shared files
+        let shared_files = Canister::get_shared_files(caller, user_id);
+        assert_eq!(shared_files.len(), file_ids.len() - 1);
+        // check if file has its sharing revoked
         let file = FileDataStorage::get_file(&file_id).unwrap();
         assert_eq!(
             file.content,
             FileContent::Uploaded {
-                file_type,
+                file_type: file_type.clone(),
                 owner_key,
                 shared_keys: BTreeMap::new(),
                 num_chunks,
@@ -699,739 +2853,1323 @@ mod test {
         );
     }
 
-    #[test]
-    fn test_should_upload_file_atomic() {
+    #[tokio::test]
+    #[should_panic(expected = "Only the owner can revoke file sharing")]
+    async fn test_only_owner_should_revoke_file_sharing() {
+        init();
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_id = 1;
+        Canister::revoke_file_sharing(Principal::anonymous(), user_id, file_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_should_revoke_all_for_user() {
         let caller = init();
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        let file_id = Canister::upload_file_atomic(
-            caller,
-            UploadFileAtomicRequest {
-                path,
-                content: file_content.clone(),
-                file_type,
-                owner_key,
-                num_chunks,
-            },
-        )
-        .unwrap();
-        assert_eq!(file_id, 0);
+        let alice = Principal::from_slice(&[4; 29]);
+        let bob = Principal::from_slice(&[5; 29]);
+        let key = OwnerKey::from([1; OwnerKey::KEY_SIZE]);
+        let file_a = upload_test_file(caller, "/a.txt").await;
+        let file_b = upload_test_file(caller, "/b.txt").await;
+        let file_c = upload_test_file(caller, "/c.txt").await;
+        for file_id in [file_a, file_b] {
+            Canister::share_file(caller, alice, file_id, key, vec![]).await;
+        }
+        Canister::share_file(caller, bob, file_c, key, vec![]).await;
+        // alice's share of the last file is waiting for the orchestrator
+        Outbox::share(file_c, "c.txt".to_string(), vec![(alice, key)], false);
 
-        // Check if the file was uploaded correctly
-        let file = FileDataStorage::get_file(&file_id).unwrap();
+        let report = Canister::revoke_all_for_user(caller, alice).await;
         assert_eq!(
-            file.content,
-            FileContent::Uploaded {
-                file_type: "text/plain".to_string(),
-                owner_key,
-                shared_keys: BTreeMap::new(),
-                num_chunks,
+            report,
+            RevokeAllForUserReport {
+                user: alice,
+                revoked: vec![
+                    RevokedShare {
+                        file_id: file_a,
+                        path: Path::new("/a.txt").unwrap(),
+                    },
+                    RevokedShare {
+                        file_id: file_b,
+                        path: Path::new("/b.txt").unwrap(),
+                    },
+                ],
+                cancelled: vec![RevokedShare {
+                    file_id: file_c,
+                    path: Path::new("/c.txt").unwrap(),
+                }],
+                pending: false,
             }
         );
-        // Check if the file content was stored correctly
-        let file_content_stored = FileContentsStorage::get_file_contents(&file_id, &0).unwrap();
-        assert_eq!(file_content_stored, file_content);
+        assert!(FileSharesStorage::get_file_shares(&alice).is_none());
+        assert_eq!(
+            FileSharesStorage::get_users_with_file_shares(&file_c),
+            vec![bob]
+        );
+        assert!(Outbox::entries().is_empty());
+
+        let report = Canister::revoke_all_for_user(caller, alice).await;
+        assert!(report.revoked.is_empty());
+        assert!(report.cancelled.is_empty());
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_only_owner_should_revoke_all_for_user() {
+        init();
+        Canister::revoke_all_for_user(Principal::anonymous(), Principal::from_slice(&[4; 29]))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_should_get_alias_info() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        let alias = Canister::request_file(caller, path.clone()).await.unwrap();
+        let alias_info = Canister::get_alias_info(alias.clone());
+        assert!(alias_info.is_ok());
+        let alias_info = alias_info.unwrap();
+        assert_eq!(alias_info.file_name, "test_file.txt".to_string());
+        assert_eq!(alias_info.file_path, path);
     }
 
     #[test]
-    #[should_panic(expected = "Only the owner can upload a file")]
-    fn test_should_not_upload_file_atomic_if_not_owner() {
+    fn test_should_get_alias_info_not_found() {
         init();
+        let alias = "non_existent_alias".to_string();
+        let alias_info = Canister::get_alias_info(alias);
+        assert!(alias_info.is_err());
+        assert_eq!(alias_info.unwrap_err(), GetAliasInfoError::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_should_delete_file() {
+        let user = init();
+
         let path = Path::new("/test_file.txt").expect("valid path");
         let file_content = vec![1, 2, 3];
         let file_type = "text/plain".to_string();
         let owner_key = [0; OwnerKey::KEY_SIZE].into();
         let num_chunks = 1;
-        Canister::upload_file_atomic(
-            Principal::anonymous(),
+        let file_id = Canister::upload_file_atomic(
+            user,
             UploadFileAtomicRequest {
                 path,
                 content: file_content,
                 file_type,
                 owner_key,
                 num_chunks,
+                device_keys: None,
             },
-        );
+        )
+        .unwrap();
+
+        // share file with alice
+        let alice = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+        let result =
+            Canister::share_file(user, alice, file_id, file_key_encrypted_for_user, vec![]).await;
+        assert_eq!(result, FileSharingResponse::Ok);
+
+        // delete file
+        let resp = Canister::delete_file(user, file_id).await;
+        assert_eq!(resp, DeleteFileResponse::Ok);
+
+        // check if the file is deleted
+        let file = FileDataStorage::get_file(&file_id);
+        assert!(file.is_none());
+        // check if the file is deleted from the content storage
+        let file_content = FileContentsStorage::get_file_contents(&file_id, &0);
+        assert!(file_content.is_none());
+        // check if the file is deleted from the shares storage
+        let shares = FileSharesStorage::get_file_shares(&alice);
+        assert!(shares.is_none());
     }
 
-    #[test]
-    fn test_should_not_upload_atomic_if_file_already_exists() {
-        let caller = init();
+    #[tokio::test]
+    async fn test_should_delete_file_when_pending() {
+        let user = init();
+
         let path = Path::new("/test_file.txt").expect("valid path");
         let file_content = vec![1, 2, 3];
         let file_type = "text/plain".to_string();
         let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        Canister::upload_file_atomic(
-            caller,
-            UploadFileAtomicRequest {
-                path: path.clone(),
-                content: file_content.clone(),
-                file_type: file_type.clone(),
-                owner_key,
-                num_chunks,
-            },
-        )
-        .unwrap();
-
-        let res = Canister::upload_file_atomic(
-            caller,
-            UploadFileAtomicRequest {
-                path: path.clone(),
-                content: file_content,
-                file_type,
-                owner_key,
-                num_chunks,
-            },
+        let num_chunks = 4;
+        let request_id = Canister::request_file(user, path.clone()).await.unwrap();
+        // upload the file first
+        let file_id = FileAliasIndexStorage::get_file_id(&request_id).unwrap();
+        let res = Canister::upload_file(
+            user,
+            file_id,
+            file_content,
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
         );
+        assert!(res.is_ok());
 
-        assert_eq!(res, UploadFileAtomicResponse::FileAlreadyExists);
+        // share file with alice
+        let alice = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+        let result =
+            Canister::share_file(user, alice, file_id, file_key_encrypted_for_user, vec![]).await;
+        assert_eq!(result, FileSharingResponse::Ok);
+
+        // delete file
+        let resp = Canister::delete_file(user, file_id).await;
+        assert_eq!(resp, DeleteFileResponse::Ok);
+
+        // check if the file is deleted
+        let file = FileDataStorage::get_file(&file_id);
+        assert!(file.is_none());
+        // check if the file is deleted from the alias index
+        let resp_file_id = FileAliasIndexStorage::get_file_id(&request_id);
+        assert!(resp_file_id.is_none());
+        // check if the file is deleted from the content storage
+        let file_content = FileContentsStorage::get_file_contents(&file_id, &0);
+        assert!(file_content.is_none());
+        // check if the file is deleted from the shares storage
+        let shares = FileSharesStorage::get_file_shares(&alice);
+        assert!(shares.is_none());
     }
 
-    #[test]
-    fn test_should_upload_file_continue() {
-        let caller = init();
+    #[tokio::test]
+    #[should_panic(expected = "Only the owner can delete files")]
+    async fn test_only_owner_should_delete_file() {
+        init();
+        let file_id = 1;
+        Canister::delete_file(Principal::anonymous(), file_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_should_decline_share() {
         let path = Path::new("/test_file.txt").expect("valid path");
-        let file_content = vec![1, 2, 3];
+        let caller = init();
+        let orchestrator = Config::get_orchestrator();
+        let alias = Canister::request_file(caller, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        //upload the file first
         let file_type = "text/plain".to_string();
         let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 2;
-        let file_id = Canister::upload_file_atomic(
+        let num_chunks = 1;
+        let res = Canister::upload_file(
             caller,
-            UploadFileAtomicRequest {
-                path,
-                content: file_content.clone(),
-                file_type,
-                owner_key,
-                num_chunks,
-            },
-        )
-        .unwrap();
-        assert_eq!(file_id, 0);
-
-        // Check if the file was uploaded correctly
-        let file = FileDataStorage::get_file(&file_id).unwrap();
-        let mut uploaded_chunks = UploadedChunks::default();
-        uploaded_chunks.insert(0);
-        assert_eq!(
-            file.content,
-            FileContent::PartiallyUploaded {
-                num_chunks,
-                uploaded_chunks,
-                file_type: "text/plain".to_string(),
-                owner_key,
-                shared_keys: BTreeMap::new(),
-            }
+            file_id,
+            vec![1, 2, 3],
+            file_type.clone(),
+            owner_key,
+            num_chunks,
+            vec![],
         );
-
-        // Upload the second chunk
-        let result = Canister::upload_file_continue(UploadFileContinueRequest {
+        assert!(res.is_ok());
+        // Now share the file with  user
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
+        Canister::share_file(
+            caller,
+            user_id,
             file_id,
-            chunk_id: 1,
-            contents: vec![4, 5, 6],
-        });
-        assert_eq!(result, UploadFileContinueResponse::Ok);
-
-        // Check if the file content was stored correctly
-        let file_content_stored_0 = FileContentsStorage::get_file_contents(&file_id, &0).unwrap();
-        assert_eq!(file_content_stored_0, vec![1, 2, 3]);
+            file_key_encrypted_for_user,
+            vec![],
+        )
+        .await;
 
-        let file_content_stored_1 = FileContentsStorage::get_file_contents(&file_id, &1).unwrap();
-        assert_eq!(file_content_stored_1, vec![4, 5, 6]);
-        // Check if the file content was updated correctly
+        // decline
+        assert_eq!(
+            Canister::decline_share(orchestrator, user_id, file_id),
+            FileSharingResponse::Ok
+        );
+        assert!(Canister::get_shared_files(caller, user_id).is_empty());
         let file = FileDataStorage::get_file(&file_id).unwrap();
         assert_eq!(
             file.content,
             FileContent::Uploaded {
-                file_type: "text/plain".to_string(),
+                file_type,
                 owner_key,
                 shared_keys: BTreeMap::new(),
                 num_chunks,
             }
         );
+
+        // file not found
+        assert_eq!(
+            Canister::decline_share(orchestrator, user_id, file_id + 1),
+            FileSharingResponse::FileNotFound
+        );
     }
 
-    #[test]
-    fn test_should_upload_file_continue_arbitrary_order_and_eval_responses() {
+    #[tokio::test]
+    async fn test_should_not_decline_share_if_not_orchestrator() {
+        init();
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+
+        assert_eq!(
+            Canister::decline_share(user_id, user_id, 0),
+            FileSharingResponse::PermissionError
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_revoke_deleted_user() {
         let caller = init();
+        let orchestrator = Config::get_orchestrator();
+        let file_id = upload_test_file(caller, "/test_file.txt").await;
+        let other_file_id = upload_test_file(caller, "/other_file.txt").await;
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        let bob = Principal::from_slice(&[5, 6, 7, 8]);
+        for (user, file_id) in [(user_id, file_id), (user_id, other_file_id), (bob, file_id)] {
+            Canister::share_file(
+                caller,
+                user,
+                file_id,
+                [0; OwnerKey::KEY_SIZE].into(),
+                vec![],
+            )
+            .await;
+        }
+
+        assert_eq!(
+            Canister::revoke_deleted_user(user_id, user_id),
+            FileSharingResponse::PermissionError
+        );
+        assert_eq!(Canister::get_shared_files(caller, user_id).len(), 2);
+
+        assert_eq!(
+            Canister::revoke_deleted_user(orchestrator, user_id),
+            FileSharingResponse::Ok
+        );
+        assert!(Canister::get_shared_files(caller, user_id).is_empty());
+        assert_eq!(Canister::get_shared_files(caller, bob).len(), 1);
+        let Some(File {
+            content: FileContent::Uploaded { shared_keys, .. },
+            ..
+        }) = FileDataStorage::get_file(&file_id)
+        else {
+            panic!("file not uploaded");
+        };
+        assert!(!shared_keys.contains_key(&user_id));
+        assert!(shared_keys.contains_key(&bob));
+        assert!(Outbox::pending_user_shares(user_id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_should_confirm_download() {
         let path = Path::new("/test_file.txt").expect("valid path");
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 6;
+        let caller = init();
+        let alias = Canister::request_file(caller, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
 
-        // Check response for unknown file
-        let result = Canister::upload_file_continue(UploadFileContinueRequest {
-            file_id: 0,
-            chunk_id: 1,
-            contents: vec![4, 5, 6],
-        });
-        assert_eq!(result, UploadFileContinueResponse::FileNotFound);
+        // cannot confirm download of a pending file
+        assert_eq!(
+            Canister::confirm_download(caller, file_id),
+            ConfirmDownloadResponse::NotUploadedFile
+        );
 
-        let file_id = Canister::upload_file_atomic(
+        let res = Canister::upload_file(
             caller,
-            UploadFileAtomicRequest {
-                path,
-                content: file_content.clone(),
-                file_type,
-                owner_key,
-                num_chunks,
-            },
-        )
-        .unwrap();
-        assert_eq!(file_id, 0);
-
-        // Upload chunks in arbitrary order
-        Canister::upload_file_continue(UploadFileContinueRequest {
-            file_id,
-            chunk_id: 3,
-            contents: vec![10, 11, 12],
-        });
-        Canister::upload_file_continue(UploadFileContinueRequest {
             file_id,
-            chunk_id: 1,
-            contents: vec![4, 5, 6],
-        });
+            vec![1, 2, 3],
+            "text/plain".to_string(),
+            [0; OwnerKey::KEY_SIZE].into(),
+            1,
+            vec![],
+        );
+        assert!(res.is_ok());
+        let version = FileDataStorage::get_file(&file_id)
+            .unwrap()
+            .metadata
+            .uploaded_at
+            .unwrap();
 
-        // Upload a duplicate chunk
-        let result = Canister::upload_file_continue(UploadFileContinueRequest {
+        // share the file with user
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        Canister::share_file(
+            caller,
+            user_id,
             file_id,
-            chunk_id: 1,
-            contents: vec![4, 5, 6],
-        });
-        assert_eq!(result, UploadFileContinueResponse::ChunkAlreadyUploaded);
+            [0; OwnerKey::KEY_SIZE].into(),
+            vec![],
+        )
+        .await;
 
-        //Check out of bounds chunk
-        let result = Canister::upload_file_continue(UploadFileContinueRequest {
-            file_id,
-            chunk_id: 6,
-            contents: vec![19, 20, 21],
-        });
-        assert_eq!(result, UploadFileContinueResponse::ChunkOutOfBounds);
+        assert_eq!(
+            Canister::confirm_download(user_id, file_id),
+            ConfirmDownloadResponse::Ok
+        );
+        // confirming again is recorded once
+        assert_eq!(
+            Canister::confirm_download(user_id, file_id),
+            ConfirmDownloadResponse::Ok
+        );
 
-        // Upload the remaining chunks
-        Canister::upload_file_continue(UploadFileContinueRequest {
-            file_id,
-            chunk_id: 5,
-            contents: vec![16, 17, 18],
-        });
-        Canister::upload_file_continue(UploadFileContinueRequest {
+        // a user without access cannot confirm download
+        assert_eq!(
+            Canister::confirm_download(Principal::from_slice(&[8, 9, 10, 11]), file_id),
+            ConfirmDownloadResponse::PermissionError
+        );
+        // nor for an unexisting file
+        assert_eq!(
+            Canister::confirm_download(user_id, file_id + 1),
+            ConfirmDownloadResponse::FileNotFound
+        );
+
+        // check access log
+        let GetFileAccessLogResponse::Ok(access_log) = Canister::get_file_access_log(
+            caller,
             file_id,
-            chunk_id: 4,
-            contents: vec![13, 14, 15],
-        });
-        Canister::upload_file_continue(UploadFileContinueRequest {
+            Pagination {
+                offset: 0,
+                limit: 10,
+            },
+        ) else {
+            panic!("expected access log");
+        };
+        assert_eq!(access_log.total, 1);
+        assert_eq!(access_log.next, None);
+        assert_eq!(access_log.entries.len(), 1);
+        assert_eq!(access_log.entries[0].user, user_id);
+        assert_eq!(access_log.entries[0].version, version);
+    }
+
+    #[tokio::test]
+    async fn test_should_get_paginated_file_access_log() {
+        let path = Path::new("/test_file.txt").expect("valid path");
+        let caller = init();
+        let alias = Canister::request_file(caller, path).await.unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        let res = Canister::upload_file(
+            caller,
             file_id,
-            chunk_id: 2,
-            contents: vec![7, 8, 9],
-        });
+            vec![1, 2, 3],
+            "text/plain".to_string(),
+            [0; OwnerKey::KEY_SIZE].into(),
+            1,
+            vec![],
+        );
+        assert!(res.is_ok());
 
-        // Check if the file content was stored correctly
-        for i in 0..num_chunks {
-            let file_content_stored = FileContentsStorage::get_file_contents(&file_id, &i).unwrap();
-            assert_eq!(
-                file_content_stored,
-                vec![i as u8 * 3 + 1, i as u8 * 3 + 2, i as u8 * 3 + 3]
+        for timestamp in 0..5 {
+            FileAccessLogStorage::record(
+                &file_id,
+                FileAccessLogEntry {
+                    user: caller,
+                    timestamp,
+                    version: 1,
+                },
             );
         }
-        // Check if the file content was updated correctly
-        let file = FileDataStorage::get_file(&file_id).unwrap();
+
+        let GetFileAccessLogResponse::Ok(access_log) = Canister::get_file_access_log(
+            caller,
+            file_id,
+            Pagination {
+                offset: 1,
+                limit: 2,
+            },
+        ) else {
+            panic!("expected access log");
+        };
+        assert_eq!(access_log.total, 5);
+        assert_eq!(access_log.next, Some(3));
+        assert_eq!(access_log.entries.len(), 2);
+
         assert_eq!(
-            file.content,
-            FileContent::Uploaded {
-                file_type: "text/plain".to_string(),
-                owner_key,
-                shared_keys: BTreeMap::new(),
-                num_chunks,
-            }
+            Canister::get_file_access_log(
+                caller,
+                file_id + 1,
+                Pagination {
+                    offset: 0,
+                    limit: 2,
+                },
+            ),
+            GetFileAccessLogResponse::FileNotFound
         );
 
-        // Check already uploaded file
-        let result = Canister::upload_file_continue(UploadFileContinueRequest {
-            file_id,
-            chunk_id: 1,
-            contents: vec![4, 5, 6],
-        });
-        assert_eq!(result, UploadFileContinueResponse::FileAlreadyUploaded);
+        // access log is removed with the file
+        Canister::delete_file(caller, file_id).await;
+        assert_eq!(FileAccessLogStorage::count(&file_id), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can get the file access log")]
+    fn test_should_not_get_file_access_log_if_not_owner() {
+        init();
+        Canister::get_file_access_log(
+            Principal::anonymous(),
+            0,
+            Pagination {
+                offset: 0,
+                limit: 10,
+            },
+        );
     }
 
     #[tokio::test]
-    async fn test_should_download_file() {
+    async fn test_should_record_audit_log() {
         let caller = init();
-        let owner = init();
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let alias = Canister::request_file(owner, path).await.unwrap();
+        let path = Path::new("/contracts/x.pdf").expect("valid path");
+        let alias = Canister::request_file(caller, path.clone()).await.unwrap();
         let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        let _ = Canister::upload_file(
+        let uploader = Principal::from_slice(&[8, 9, 10, 11]);
+        let res = Canister::upload_file(
+            uploader,
             file_id,
-            file_content.clone(),
-            file_type.clone(),
-            owner_key,
-            num_chunks,
+            vec![1, 2, 3],
+            "application/pdf".to_string(),
+            [0; OwnerKey::KEY_SIZE].into(),
+            1,
+            vec![],
         );
-        // Download the file as the owner
-        let result = Canister::download_file(owner, file_id, 0);
+        assert!(res.is_ok());
+
+        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
+        Canister::share_file(
+            caller,
+            user_id,
+            file_id,
+            [0; OwnerKey::KEY_SIZE].into(),
+            vec![],
+        )
+        .await;
+        Canister::revoke_file_sharing(caller, user_id, file_id).await;
+        Canister::delete_file(caller, file_id).await;
+
+        // another file
+        Canister::request_file(caller, Path::new("/other.txt").expect("valid path"))
+            .await
+            .unwrap();
+
+        let audit_log = Canister::get_audit_log(
+            caller,
+            AuditLogFilter {
+                path: Some(path.clone()),
+                ..Default::default()
+            },
+            Pagination {
+                offset: 0,
+                limit: 10,
+            },
+        );
+        assert_eq!(audit_log.total, 5);
+        assert_eq!(audit_log.next, None);
         assert_eq!(
-            result,
-            FileDownloadResponse::FoundFile(FileData {
-                contents: file_content.clone(),
-                file_type: file_type.clone(),
-                owner_key,
-                num_chunks
-            })
+            audit_log
+                .entries
+                .iter()
+                .map(|entry| (entry.actor, entry.action.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (caller, AuditAction::RequestFile),
+                (uploader, AuditAction::UploadFile),
+                (caller, AuditAction::ShareFile { user: user_id }),
+                (caller, AuditAction::RevokeShare { user: user_id }),
+                (caller, AuditAction::DeleteFile),
+            ]
         );
-        // Download the file as a shared user
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_key_encrypted_for_user = [6; OwnerKey::KEY_SIZE].into();
-        Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
-        let result = Canister::download_file(user_id, file_id, 0);
+        assert!(audit_log.entries.iter().all(|entry| entry.path == path));
+
+        // who had access to the file
+        let audit_log = Canister::get_audit_log(
+            caller,
+            AuditLogFilter {
+                user: Some(user_id),
+                ..Default::default()
+            },
+            Pagination {
+                offset: 1,
+                limit: 10,
+            },
+        );
+        assert_eq!(audit_log.total, 2);
+        assert_eq!(
+            audit_log.entries[0].action,
+            AuditAction::RevokeShare { user: user_id }
+        );
+
+        // paginated
+        let audit_log = Canister::get_audit_log(
+            caller,
+            AuditLogFilter::default(),
+            Pagination {
+                offset: 0,
+                limit: 2,
+            },
+        );
+        assert_eq!(audit_log.total, 6);
+        assert_eq!(audit_log.next, Some(2));
+        assert_eq!(audit_log.entries.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can get the audit log")]
+    fn test_should_not_get_audit_log_if_not_owner() {
+        init();
+        Canister::get_audit_log(
+            Principal::anonymous(),
+            AuditLogFilter::default(),
+            Pagination {
+                offset: 0,
+                limit: 10,
+            },
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_rotate_owner_key() {
+        let caller = init();
+        let old_key = PublicKey::try_from(vec![1; 32]).unwrap();
+        let new_key = PublicKey::try_from(vec![2; 32]).unwrap();
+        Config::set_owner_public_key(caller, old_key);
+
+        // two uploaded files and a pending request
+        for file_id in 0..2 {
+            Canister::upload_file_atomic(
+                caller,
+                UploadFileAtomicRequest {
+                    path: Path::new(format!("/file_{file_id}.txt")).unwrap(),
+                    content: vec![1, 2, 3],
+                    file_type: "text/plain".to_string(),
+                    owner_key: [file_id as u8; OwnerKey::KEY_SIZE].into(),
+                    num_chunks: 1,
+                    device_keys: None,
+                },
+            )
+            .unwrap();
+        }
+        let alias = Canister::request_file(caller, Path::new("/request.txt").unwrap())
+            .await
+            .unwrap();
+        let request_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+
+        assert_eq!(
+            Canister::submit_key_rotation_batch(caller, vec![]),
+            SubmitKeyRotationBatchResponse::NoRotationInProgress
+        );
+        assert_eq!(
+            Canister::start_key_rotation(caller, old_key),
+            StartKeyRotationResponse::SameKey
+        );
+        assert_eq!(
+            Canister::start_key_rotation(caller, new_key),
+            StartKeyRotationResponse::Ok
+        );
+        assert_eq!(
+            Canister::start_key_rotation(caller, new_key),
+            StartKeyRotationResponse::RotationInProgress
+        );
+
+        let status = Canister::key_rotation_status(caller).unwrap();
+        assert_eq!(status.rotation.new_public_key, new_key);
+        assert_eq!(status.migrated, 0);
+        assert_eq!(status.remaining, 2);
+
+        // can't complete before all the files are migrated
+        assert_eq!(
+            Canister::complete_key_rotation(caller).await,
+            CompleteKeyRotationResponse::FilesNotMigrated(2)
+        );
+
+        // an invalid batch is rejected as a whole
+        let rewrapped_key: OwnerKey = [9; OwnerKey::KEY_SIZE].into();
+        assert_eq!(
+            Canister::submit_key_rotation_batch(
+                caller,
+                vec![
+                    WrappedFileKey {
+                        file_id: 0,
+                        owner_key: rewrapped_key,
+                    },
+                    WrappedFileKey {
+                        file_id: request_id,
+                        owner_key: rewrapped_key,
+                    },
+                ],
+            ),
+            SubmitKeyRotationBatchResponse::NotUploadedFile(request_id)
+        );
+        assert_eq!(Canister::key_rotation_status(caller).unwrap().remaining, 2);
+
+        // migrate in batches of one
+        loop {
+            let keys = Canister::get_key_rotation_files(caller, 1);
+            let Some(key) = keys.first() else {
+                break;
+            };
+            assert_eq!(keys.len(), 1);
+            let response = Canister::submit_key_rotation_batch(
+                caller,
+                vec![WrappedFileKey {
+                    file_id: key.file_id,
+                    owner_key: rewrapped_key,
+                }],
+            );
+            assert!(matches!(response, SubmitKeyRotationBatchResponse::Ok(_)));
+        }
+
+        let status = Canister::key_rotation_status(caller).unwrap();
+        assert_eq!(status.migrated, 2);
+        assert_eq!(status.remaining, 0);
+
         assert_eq!(
-            result,
-            FileDownloadResponse::FoundFile(FileData {
-                contents: file_content,
-                file_type,
-                owner_key: [6; OwnerKey::KEY_SIZE].into(),
-                num_chunks
-            })
+            Canister::complete_key_rotation(caller).await,
+            CompleteKeyRotationResponse::Ok
+        );
+        assert_eq!(Config::get_owner_public_key(), new_key);
+        assert_eq!(Config::get_owner_public_key_version(), 1);
+        assert!(Canister::key_rotation_status(caller).is_none());
+        for file_id in 0..2 {
+            let file = FileDataStorage::get_file(&file_id).unwrap();
+            assert_eq!(file.metadata.user_public_key, new_key);
+            assert_eq!(file.content.owner_key(), Some(&rewrapped_key));
+        }
+        let request = FileDataStorage::get_file(&request_id).unwrap();
+        assert_eq!(request.metadata.user_public_key, new_key);
+        assert_eq!(
+            Canister::complete_key_rotation(caller).await,
+            CompleteKeyRotationResponse::NoRotationInProgress
         );
     }
 
-    #[tokio::test]
-    async fn test_should_not_download_file_if_not_uploaded() {
+    #[test]
+    #[should_panic(expected = "Cannot set the public key while a key rotation is in progress")]
+    fn test_should_not_set_public_key_during_key_rotation() {
         let caller = init();
-        let owner = init();
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let alias = Canister::request_file(owner, path).await.unwrap();
-        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-        // Attempt to download the file on pending state
-        let result = Canister::download_file(caller, file_id, 0);
-        assert_eq!(result, FileDownloadResponse::NotUploadedFile);
-
-        // Attempt to download the file on partially uploaded state
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 2;
-        let _ = Canister::upload_file(
-            file_id,
-            file_content.clone(),
-            file_type.clone(),
-            owner_key,
-            num_chunks,
+        let new_key = PublicKey::try_from(vec![2; 32]).unwrap();
+        assert_eq!(
+            Canister::start_key_rotation(caller, new_key),
+            StartKeyRotationResponse::Ok
         );
-        let result = Canister::download_file(caller, file_id, 0);
-        assert_eq!(result, FileDownloadResponse::NotUploadedFile);
+        Config::set_owner_public_key(caller, new_key);
     }
 
     #[tokio::test]
-    async fn test_should_not_download_file_if_not_owner() {
+    async fn test_should_set_and_sync_public_key() {
         let caller = init();
-        let owner = init();
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let alias = Canister::request_file(owner, path).await.unwrap();
-        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        let _ = Canister::upload_file(
-            file_id,
-            file_content.clone(),
-            file_type.clone(),
-            owner_key,
-            num_chunks,
+        let key = PublicKey::try_from(vec![1; 32]).unwrap();
+        assert_eq!(
+            Canister::set_public_key(caller, key).await,
+            SetPublicKeyResponse::Ok(1)
         );
+        assert_eq!(Config::get_owner_public_key(), key);
+        assert_eq!(Config::get_owner_public_key_version(), 1);
 
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_key_encrypted_for_user = [6; OwnerKey::KEY_SIZE].into();
-        Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
+        // only newer keys are synced from the orchestrator
+        let new_key = PublicKey::try_from(vec![2; 32]).unwrap();
+        assert_eq!(
+            Canister::sync_public_key(Principal::anonymous(), new_key, 2),
+            SyncPublicKeyResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::sync_public_key(Config::get_orchestrator(), new_key, 1),
+            SyncPublicKeyResponse::StaleVersion(1)
+        );
+        assert_eq!(Config::get_owner_public_key(), key);
+        assert_eq!(
+            Canister::sync_public_key(Config::get_orchestrator(), new_key, 2),
+            SyncPublicKeyResponse::Ok
+        );
+        assert_eq!(Config::get_owner_public_key(), new_key);
+        assert_eq!(Config::get_owner_public_key_version(), 2);
 
-        let res = Canister::download_file(Principal::anonymous(), file_id, 0);
-        assert_eq!(res, FileDownloadResponse::PermissionError);
+        // not while a key rotation is in progress
+        assert_eq!(
+            Canister::start_key_rotation(caller, key),
+            StartKeyRotationResponse::Ok
+        );
+        assert_eq!(
+            Canister::set_public_key(caller, key).await,
+            SetPublicKeyResponse::KeyRotationInProgress
+        );
+        assert_eq!(
+            Canister::sync_public_key(Config::get_orchestrator(), key, 3),
+            SyncPublicKeyResponse::KeyRotationInProgress
+        );
     }
 
     #[tokio::test]
-    async fn test_should_share_a_file() {
-        let path = Path::new("/test_file.txt").expect("valid path");
+    #[should_panic(expected = "Only the owner can set the public key")]
+    async fn test_should_not_set_public_key_if_not_owner() {
+        init();
+        Canister::set_public_key(
+            Principal::anonymous(),
+            PublicKey::try_from(vec![2; 32]).unwrap(),
+        )
+        .await;
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can rotate the public key")]
+    fn test_should_not_start_key_rotation_if_not_owner() {
+        init();
+        Canister::start_key_rotation(
+            Principal::anonymous(),
+            PublicKey::try_from(vec![2; 32]).unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_reencrypt_file() {
         let caller = init();
-        let alias = Canister::request_file(caller, path).await.unwrap();
-        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
-        let result =
-            Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
-        assert_eq!(result, FileSharingResponse::PendingError);
-        // Upload the file first
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        let res = Canister::upload_file(
-            file_id,
-            file_content,
-            file_type.clone(),
-            owner_key,
-            num_chunks,
+        let file_id = upload_test_file(caller, "/reencrypt.txt").await;
+        let alice = Principal::from_slice(&[4; 29]);
+        let bob = Principal::from_slice(&[5; 29]);
+        for user in [alice, bob] {
+            Canister::share_file(
+                caller,
+                user,
+                file_id,
+                [0; OwnerKey::KEY_SIZE].into(),
+                vec![],
+            )
+            .await;
+        }
+
+        let owner_key = OwnerKey::from([1; OwnerKey::KEY_SIZE]);
+        let alice_key = OwnerKey::from([2; OwnerKey::KEY_SIZE]);
+        let response = Canister::reencrypt_file(
+            caller,
+            ReencryptFileRequest {
+                file_id,
+                revoke: vec![bob],
+                num_chunks: 2,
+                owner_key,
+                shared_keys: vec![(alice, alice_key)],
+            },
+        )
+        .await;
+        assert_eq!(response, ReencryptFileResponse::Ok);
+
+        // bob is revoked right away, while the file keeps its content
+        assert!(Canister::get_shared_files(caller, bob).is_empty());
+        assert_eq!(
+            Canister::reencrypt_file_continue(
+                caller,
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id: 1,
+                    contents: vec![5, 6],
+                },
+            ),
+            ReencryptFileContinueResponse::Ok
+        );
+        assert_eq!(
+            Canister::reencrypt_file_continue(
+                caller,
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id: 1,
+                    contents: vec![5, 6],
+                },
+            ),
+            ReencryptFileContinueResponse::ChunkAlreadyUploaded
+        );
+        assert_eq!(
+            FileContentsStorage::get_file_contents(&file_id, &0),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            FileDataStorage::get_file(&file_id)
+                .unwrap()
+                .content
+                .owner_key(),
+            Some(&[0; OwnerKey::KEY_SIZE].into())
         );
-        assert!(res.is_ok());
-        // Now share the file
-        let result =
-            Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
-        assert_eq!(result, FileSharingResponse::Ok);
-        let file = FileDataStorage::get_file(&file_id).unwrap();
 
+        // the last chunk swaps the content
         assert_eq!(
-            file.content,
+            Canister::reencrypt_file_continue(
+                caller,
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id: 0,
+                    contents: vec![3, 4],
+                },
+            ),
+            ReencryptFileContinueResponse::Completed
+        );
+        assert_eq!(
+            FileDataStorage::get_file(&file_id).unwrap().content,
             FileContent::Uploaded {
-                file_type: file_type.clone(),
+                num_chunks: 2,
+                file_type: "text/plain".to_string(),
                 owner_key,
-                shared_keys: BTreeMap::from([(user_id, file_key_encrypted_for_user)]),
-                num_chunks,
+                shared_keys: BTreeMap::from([(alice, alice_key)]),
             }
         );
-        // Check if the file is shared with the user
-        let shared_files = Canister::get_shared_files(caller, user_id);
-        assert_eq!(shared_files.len(), 1);
-        assert_eq!(shared_files[0].file_id, file_id);
+        assert_eq!(
+            FileContentsStorage::get_file_contents(&file_id, &0),
+            Some(vec![3, 4])
+        );
+        assert_eq!(
+            FileContentsStorage::get_file_contents(&file_id, &1),
+            Some(vec![5, 6])
+        );
+        assert!(ReencryptionStorage::get(&file_id).is_none());
+        assert_eq!(
+            Canister::reencrypt_file_continue(
+                caller,
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id: 0,
+                    contents: vec![3, 4],
+                },
+            ),
+            ReencryptFileContinueResponse::NoReencryptionInProgress
+        );
     }
 
     #[tokio::test]
-    async fn test_should_share_more_than_a_file() {
+    async fn test_should_not_reencrypt_file_with_wrong_keys() {
         let caller = init();
+        let file_id = upload_test_file(caller, "/reencrypt.txt").await;
+        let alice = Principal::from_slice(&[4; 29]);
+        let bob = Principal::from_slice(&[5; 29]);
+        Canister::share_file(
+            caller,
+            alice,
+            file_id,
+            [0; OwnerKey::KEY_SIZE].into(),
+            vec![],
+        )
+        .await;
 
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let mut file_ids = vec![];
-        for i in 0..3 {
-            let path = Path::new(format!("/test_file_{i}.txt")).expect("valid path");
-            let alias = Canister::request_file(caller, path).await.unwrap();
-            let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-            let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
-            let result =
-                Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
-            assert_eq!(result, FileSharingResponse::PendingError);
-            // Upload the file first
-            let file_content = vec![1, 2, 3];
-            let file_type = "text/plain".to_string();
-            let owner_key = [0; OwnerKey::KEY_SIZE].into();
-            let num_chunks = 1;
-            let res = Canister::upload_file(
-                file_id,
-                file_content,
-                file_type.clone(),
-                owner_key,
-                num_chunks,
-            );
-            assert!(res.is_ok());
-            // Now share the file
-            let result =
-                Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
-            assert_eq!(result, FileSharingResponse::Ok);
-            let file = FileDataStorage::get_file(&file_id).unwrap();
+        let request = ReencryptFileRequest {
+            file_id,
+            revoke: vec![],
+            num_chunks: 1,
+            owner_key: [1; OwnerKey::KEY_SIZE].into(),
+            shared_keys: vec![],
+        };
+        assert_eq!(
+            Canister::reencrypt_file(caller, request.clone()).await,
+            ReencryptFileResponse::MissingSharedKey(alice)
+        );
+        assert_eq!(
+            Canister::reencrypt_file(
+                caller,
+                ReencryptFileRequest {
+                    shared_keys: vec![
+                        (alice, [2; OwnerKey::KEY_SIZE].into()),
+                        (bob, [2; OwnerKey::KEY_SIZE].into()),
+                    ],
+                    ..request.clone()
+                },
+            )
+            .await,
+            ReencryptFileResponse::UnexpectedSharedKey(bob)
+        );
+        assert_eq!(
+            Canister::reencrypt_file(
+                caller,
+                ReencryptFileRequest {
+                    num_chunks: 0,
+                    revoke: vec![alice],
+                    ..request.clone()
+                },
+            )
+            .await,
+            ReencryptFileResponse::InvalidNumChunks
+        );
+        assert_eq!(
+            Canister::reencrypt_file(
+                caller,
+                ReencryptFileRequest {
+                    file_id: 42,
+                    ..request
+                },
+            )
+            .await,
+            ReencryptFileResponse::FileNotFound
+        );
+        // nothing was revoked
+        assert_eq!(Canister::get_shared_files(caller, alice).len(), 1);
+    }
 
-            assert_eq!(
-                file.content,
-                FileContent::Uploaded {
-                    file_type: file_type.clone(),
-                    owner_key,
-                    shared_keys: BTreeMap::from([(user_id, file_key_encrypted_for_user)]),
-                    num_chunks,
-                }
-            );
+    #[tokio::test]
+    async fn test_should_drop_reencryption_if_recipients_changed() {
+        let caller = init();
+        let file_id = upload_test_file(caller, "/reencrypt.txt").await;
+        let response = Canister::reencrypt_file(
+            caller,
+            ReencryptFileRequest {
+                file_id,
+                revoke: vec![],
+                num_chunks: 1,
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                shared_keys: vec![],
+            },
+        )
+        .await;
+        assert_eq!(response, ReencryptFileResponse::Ok);
 
-            file_ids.push(file_id);
-        }
+        // the file is shared with the old key while the chunks are uploaded
+        let alice = Principal::from_slice(&[4; 29]);
+        Canister::share_file(
+            caller,
+            alice,
+            file_id,
+            [0; OwnerKey::KEY_SIZE].into(),
+            vec![],
+        )
+        .await;
 
-        // Check if the file is shared with the user
-        let shared_files = Canister::get_shared_files(caller, user_id);
-        assert_eq!(shared_files.len(), 3);
-        assert!(file_ids.contains(&shared_files[0].file_id));
-        assert!(file_ids.contains(&shared_files[1].file_id));
-        assert!(file_ids.contains(&shared_files[2].file_id));
+        assert_eq!(
+            Canister::reencrypt_file_continue(
+                caller,
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id: 0,
+                    contents: vec![9],
+                },
+            ),
+            ReencryptFileContinueResponse::RecipientsChanged
+        );
+        assert!(ReencryptionStorage::get(&file_id).is_none());
+        assert_eq!(
+            FileContentsStorage::get_file_contents(&file_id, &0),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            FileDataStorage::get_file(&file_id)
+                .unwrap()
+                .content
+                .owner_key(),
+            Some(&[0; OwnerKey::KEY_SIZE].into())
+        );
     }
 
     #[tokio::test]
-    async fn should_share_file_with_users() {
-        let path = Path::new("/test_file.txt").expect("valid path");
+    async fn test_should_not_mix_reencryption_and_key_rotation() {
         let caller = init();
-        let alias = Canister::request_file(caller, path).await.unwrap();
-        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-        //upload the file first
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        let res = Canister::upload_file(
+        let file_id = upload_test_file(caller, "/reencrypt.txt").await;
+        let request = ReencryptFileRequest {
             file_id,
-            file_content,
-            file_type.clone(),
-            owner_key,
-            num_chunks,
+            revoke: vec![],
+            num_chunks: 1,
+            owner_key: [1; OwnerKey::KEY_SIZE].into(),
+            shared_keys: vec![],
+        };
+        let chunk = UploadFileContinueRequest {
+            file_id,
+            chunk_id: 0,
+            contents: vec![9],
+        };
+        let old_key = Config::get_owner_public_key();
+        let new_key = PublicKey::try_from(vec![2; 32]).unwrap();
+
+        // no rotation while a re-encryption is staged
+        assert_eq!(
+            Canister::reencrypt_file(caller, request.clone()).await,
+            ReencryptFileResponse::Ok
+        );
+        assert_eq!(
+            Canister::start_key_rotation(caller, new_key),
+            StartKeyRotationResponse::ReencryptionInProgress
         );
-        assert!(res.is_ok());
-        // Now share the file with multiple users
 
-        let user_ids = vec![
-            Principal::from_slice(&[4, 5, 6, 7]),
-            Principal::from_slice(&[8, 9, 10, 11]),
-        ];
-        let file_key_encrypted_for_user = vec![
-            [2; OwnerKey::KEY_SIZE].into(),
-            [1; OwnerKey::KEY_SIZE].into(),
-        ];
-        Canister::share_file_with_users(
-            caller,
-            user_ids.clone(),
-            file_id,
-            file_key_encrypted_for_user,
-        )
-        .await;
-        for user_id in user_ids {
-            let shared_files = Canister::get_shared_files(caller, user_id);
-            assert_eq!(shared_files.len(), 1);
-            assert_eq!(shared_files[0].file_id, file_id);
-        }
+        // a re-encryption staged before the owner key changed is aborted
+        Config::set_owner_public_key(caller, new_key);
+        assert_eq!(
+            Canister::reencrypt_file_continue(caller, chunk.clone()),
+            ReencryptFileContinueResponse::OwnerKeyChanged
+        );
+        assert!(ReencryptionStorage::get(&file_id).is_none());
+        assert_eq!(
+            FileContentsStorage::get_file_contents(&file_id, &0),
+            Some(vec![1, 2, 3])
+        );
+
+        // and so is one completing while a rotation is in progress
+        Config::set_owner_public_key(caller, old_key);
+        assert_eq!(
+            Canister::reencrypt_file(caller, request).await,
+            ReencryptFileResponse::Ok
+        );
+        KeyRotationStorage::start(KeyRotation {
+            new_public_key: new_key,
+            started_at: 0,
+        });
+        assert_eq!(
+            Canister::reencrypt_file_continue(caller, chunk),
+            ReencryptFileContinueResponse::OwnerKeyChanged
+        );
+        assert!(ReencryptionStorage::get(&file_id).is_none());
     }
 
     #[tokio::test]
-    #[should_panic(expected = "Only the owner can share a file")]
-    async fn test_only_owner_should_share_file() {
-        init();
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_id = 1;
-        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
-        Canister::share_file(
+    #[should_panic]
+    async fn test_only_owner_should_reencrypt_file() {
+        let caller = init();
+        let file_id = upload_test_file(caller, "/reencrypt.txt").await;
+        Canister::reencrypt_file(
             Principal::anonymous(),
-            user_id,
-            file_id,
-            file_key_encrypted_for_user,
+            ReencryptFileRequest {
+                file_id,
+                revoke: vec![],
+                num_chunks: 1,
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                shared_keys: vec![],
+            },
         )
         .await;
     }
 
     #[tokio::test]
-    async fn test_should_revoke_file_sharing() {
-        let path = Path::new("/test_file.txt").expect("valid path");
+    async fn test_should_transfer_file() {
         let caller = init();
-        let alias = Canister::request_file(caller, path).await.unwrap();
-        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-        //upload the file first
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        let res = Canister::upload_file(
+        let alice = Principal::from_slice(&[4; 29]);
+        let bob = Principal::from_slice(&[5; 29]);
+        let file_id = upload_test_file(caller, "/transfer.txt").await;
+        Canister::share_file(
+            caller,
+            alice,
             file_id,
-            file_content,
-            file_type.clone(),
-            owner_key,
-            num_chunks,
+            [1; OwnerKey::KEY_SIZE].into(),
+            vec![],
+        )
+        .await;
+
+        let request = TransferFileRequest {
+            file_id,
+            recipient: bob,
+            owner_key: [2; OwnerKey::KEY_SIZE].into(),
+            keep_shares: true,
+        };
+        assert_eq!(
+            Canister::transfer_file(
+                caller,
+                TransferFileRequest {
+                    recipient: caller,
+                    ..request.clone()
+                }
+            )
+            .await,
+            TransferFileResponse::RecipientIsOwner
         );
-        assert!(res.is_ok());
-        // Now share the file with  user
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
-        Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
-        // Revoke sharing
-        Canister::revoke_file_sharing(caller, user_id, file_id).await;
-        // Check if the user can still access the shared files
-        let shared_files = Canister::get_shared_files(caller, user_id);
-        assert_eq!(shared_files.len(), 0);
-        // check if file has its sharing revoked
-        let file = FileDataStorage::get_file(&file_id).unwrap();
         assert_eq!(
-            file.content,
-            FileContent::Uploaded {
-                file_type: file_type.clone(),
-                owner_key,
-                shared_keys: BTreeMap::new(),
-                num_chunks,
-            }
+            Canister::transfer_file(
+                caller,
+                TransferFileRequest {
+                    file_id: 42,
+                    ..request.clone()
+                }
+            )
+            .await,
+            TransferFileResponse::FileNotFound
         );
+
+        // the file is kept until the recipient accepts the transfer
+        assert_eq!(
+            Canister::transfer_file(caller, request).await,
+            TransferFileResponse::Ok
+        );
+        assert!(FileDataStorage::get_file(&file_id).is_some());
+        assert!(FileSharesStorage::get_file_shares(&alice).is_some());
     }
 
     #[tokio::test]
-    async fn test_should_revoke_file_sharing_when_more_files_are_shared() {
+    #[should_panic]
+    async fn test_only_owner_should_transfer_file() {
         let caller = init();
-        let mut file_ids = vec![];
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
+        let file_id = upload_test_file(caller, "/transfer.txt").await;
+        Canister::transfer_file(
+            Principal::anonymous(),
+            TransferFileRequest {
+                file_id,
+                recipient: Principal::from_slice(&[5; 29]),
+                owner_key: [2; OwnerKey::KEY_SIZE].into(),
+                keep_shares: false,
+            },
+        )
+        .await;
+    }
 
-        for i in 0..5 {
-            let path = Path::new(format!("/test_file_{i}.txt")).expect("valid path");
-            let alias = Canister::request_file(caller, path).await.unwrap();
-            let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
-            //upload the file first
-            let file_content = vec![1, 2, 3];
+    #[tokio::test]
+    async fn test_should_reject_uploads_shares_and_transfers_while_suspended() {
+        let caller = init();
+        let file_id = upload_test_file(caller, "/shared.txt").await;
+        assert_eq!(
+            Canister::set_suspended(Principal::from_slice(&[5; 29]), true),
+            SetSuspendedResponse::Unauthorized
+        );
+        assert_eq!(
+            Canister::set_suspended(Config::get_orchestrator(), true),
+            SetSuspendedResponse::Ok
+        );
+        assert_eq!(
+            Canister::set_suspended(Config::get_orchestrator(), true),
+            SetSuspendedResponse::Unchanged
+        );
 
-            let res = Canister::upload_file(
+        let request = UploadFileAtomicRequest {
+            path: Path::new("/test_file.txt").expect("valid path"),
+            content: vec![1, 2, 3],
+            file_type: "text/plain".to_string(),
+            owner_key: [0; OwnerKey::KEY_SIZE].into(),
+            num_chunks: 2,
+            device_keys: None,
+        };
+        assert_eq!(
+            Canister::upload_file_atomic(caller, request.clone()),
+            UploadFileAtomicResponse::Suspended
+        );
+        assert_eq!(
+            Canister::upload_file_continue(UploadFileContinueRequest {
                 file_id,
-                file_content,
-                file_type.clone(),
-                owner_key,
-                num_chunks,
-            );
-            assert!(res.is_ok());
-            // Now share the file with  user
-            Canister::share_file(caller, user_id, file_id, file_key_encrypted_for_user).await;
-
-            file_ids.push(file_id);
-        }
+                chunk_id: 1,
+                contents: vec![4, 5, 6],
+            }),
+            UploadFileContinueResponse::Suspended
+        );
+        assert_eq!(
+            Canister::share_file(
+                caller,
+                Principal::from_slice(&[4, 5, 6, 7]),
+                file_id,
+                [0; OwnerKey::KEY_SIZE].into(),
+                vec![],
+            )
+            .await,
+            FileSharingResponse::Suspended
+        );
 
-        let file_id = file_ids[1];
-        // Revoke sharing
-        Canister::revoke_file_sharing(caller, user_id, file_id).await;
-        // Check if the user can still access the shared files
-        let shared_files = Canister::get_shared_files(caller, user_id);
-        assert_eq!(shared_files.len(), file_ids.len() - 1);
-        // check if file has its sharing revoked
-        let file = FileDataStorage::get_file(&file_id).unwrap();
         assert_eq!(
-            file.content,
-            FileContent::Uploaded {
-                file_type: file_type.clone(),
-                owner_key,
-                shared_keys: BTreeMap::new(),
-                num_chunks,
-            }
+            Canister::transfer_file(
+                caller,
+                TransferFileRequest {
+                    file_id,
+                    recipient: Principal::from_slice(&[4, 5, 6, 7]),
+                    owner_key: [2; OwnerKey::KEY_SIZE].into(),
+                    keep_shares: false,
+                }
+            )
+            .await,
+            TransferFileResponse::Suspended
+        );
+        assert_eq!(
+            Canister::receive_file_transfer(
+                Principal::from_slice(&[9; 29]),
+                ReceiveFileTransferRequest {
+                    owner: Principal::from_slice(&[8; 29]),
+                    file_id: 1,
+                    path: Path::new("/transferred.txt").expect("valid path"),
+                    file_type: "text/plain".to_string(),
+                    num_chunks: 1,
+                    owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                    shared_keys: vec![],
+                }
+            )
+            .await,
+            ReceiveFileTransferResponse::Suspended
         );
-    }
 
-    #[tokio::test]
-    #[should_panic(expected = "Only the owner can revoke file sharing")]
-    async fn test_only_owner_should_revoke_file_sharing() {
-        init();
-        let user_id = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_id = 1;
-        Canister::revoke_file_sharing(Principal::anonymous(), user_id, file_id).await;
+        assert_eq!(
+            Canister::set_suspended(Config::get_orchestrator(), false),
+            SetSuspendedResponse::Ok
+        );
+        assert!(matches!(
+            Canister::upload_file_atomic(caller, request),
+            UploadFileAtomicResponse::Ok(_)
+        ));
     }
 
     #[tokio::test]
-    async fn test_should_get_alias_info() {
-        let path = Path::new("/test_file.txt").expect("valid path");
+    async fn test_should_migrate_stable_memory_on_upgrade() {
         let caller = init();
-        let alias = Canister::request_file(caller, path.clone()).await.unwrap();
-        let alias_info = Canister::get_alias_info(alias.clone());
-        assert!(alias_info.is_ok());
-        let alias_info = alias_info.unwrap();
-        assert_eq!(alias_info.file_name, "test_file.txt".to_string());
-        assert_eq!(alias_info.file_path, path);
-    }
+        assert_eq!(Config::get_schema_version(), SCHEMA_VERSION);
+        upload_test_file(caller, "/uploaded.txt").await;
 
-    #[test]
-    fn test_should_get_alias_info_not_found() {
-        init();
-        let alias = "non_existent_alias".to_string();
-        let alias_info = Canister::get_alias_info(alias);
-        assert!(alias_info.is_err());
-        assert_eq!(alias_info.unwrap_err(), GetAliasInfoError::NotFound);
+        // the files are counted on a canister which was never migrated
+        Config::set_schema_version(0);
+        Canister::post_upgrade(UserCanisterInstallArgs::Upgrade);
+        assert_eq!(Config::get_schema_version(), SCHEMA_VERSION);
+        assert_eq!(
+            FileStatsStorage::get(),
+            FileStats {
+                stored_bytes: 3,
+                pending: 0,
+                partially_uploaded: 0,
+                uploaded: 1,
+                share_recipients: 0,
+                shares: 0,
+            }
+        );
+
+        // the shares are counted on a canister which counted the files only
+        FileSharesStorage::share(&Principal::from_slice(&[9; 29]), vec![1, 2]);
+        Config::set_schema_version(SHARE_STATS_SCHEMA_VERSION - 1);
+        Canister::post_upgrade(UserCanisterInstallArgs::Upgrade);
+        let stats = FileStatsStorage::get();
+        assert_eq!(stats.share_recipients, 1);
+        assert_eq!(stats.shares, 2);
+
+        // the files of a rotation in progress are counted too
+        KeyRotationStorage::start(KeyRotation {
+            new_public_key: PublicKey::try_from(vec![9; 32]).unwrap(),
+            started_at: 0,
+        });
+        Config::set_schema_version(KEY_ROTATION_PROGRESS_SCHEMA_VERSION - 1);
+        Canister::post_upgrade(UserCanisterInstallArgs::Upgrade);
+        assert_eq!(Config::get_schema_version(), SCHEMA_VERSION);
+        let status = Canister::key_rotation_status(caller).expect("rotation in progress");
+        assert_eq!(status.migrated, 0);
+        assert_eq!(status.remaining, 1);
     }
 
     #[tokio::test]
-    async fn test_should_delete_file() {
-        let user = init();
-
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 1;
-        let file_id = Canister::upload_file_atomic(
-            user,
-            UploadFileAtomicRequest {
-                path,
-                content: file_content,
-                file_type,
-                owner_key,
-                num_chunks,
-            },
-        )
-        .unwrap();
+    async fn test_should_serve_metrics() {
+        let caller = init();
+        upload_test_file(caller, "/uploaded.txt").await;
+        Canister::request_file(caller, Path::new("/pending.txt").expect("valid path"))
+            .await
+            .unwrap();
 
-        // share file with alice
-        let alice = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
-        let result = Canister::share_file(user, alice, file_id, file_key_encrypted_for_user).await;
-        assert_eq!(result, FileSharingResponse::Ok);
+        let request = |method: &str, url: &str| HttpRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: Default::default(),
+        };
 
-        // delete file
-        let resp = Canister::delete_file(user, file_id).await;
-        assert_eq!(resp, DeleteFileResponse::Ok);
+        let response = Canister::http_request(request("GET", "/metrics"));
+        assert_eq!(response.status_code, 200);
+        let metrics = String::from_utf8(response.body.into_vec()).unwrap();
+        assert!(metrics.contains("\nuser_canister_files{state=\"pending\"} 1\n"));
+        assert!(metrics.contains("\nuser_canister_files{state=\"uploaded\"} 1\n"));
+        assert!(metrics.contains("\nuser_canister_stored_bytes 3\n"));
+        assert!(metrics.contains("\nuser_canister_shares 0\n"));
 
-        // check if the file is deleted
-        let file = FileDataStorage::get_file(&file_id);
-        assert!(file.is_none());
-        // check if the file is deleted from the content storage
-        let file_content = FileContentsStorage::get_file_contents(&file_id, &0);
-        assert!(file_content.is_none());
-        // check if the file is deleted from the shares storage
-        let shares = FileSharesStorage::get_file_shares(&alice);
-        assert!(shares.is_none());
+        assert_eq!(
+            Canister::http_request(request("PUT", "/metrics")).status_code,
+            405
+        );
+        assert_eq!(
+            Canister::http_request(request("GET", "/index.html")).status_code,
+            404
+        );
     }
 
-    #[tokio::test]
-    async fn test_should_delete_file_when_pending() {
-        let user = init();
-
-        let path = Path::new("/test_file.txt").expect("valid path");
-        let file_content = vec![1, 2, 3];
-        let file_type = "text/plain".to_string();
-        let owner_key = [0; OwnerKey::KEY_SIZE].into();
-        let num_chunks = 4;
-        let request_id = Canister::request_file(user, path.clone()).await.unwrap();
-        // upload the file first
-        let file_id = FileAliasIndexStorage::get_file_id(&request_id).unwrap();
+    /// Request and upload a single chunk file at the given path.
+    pub(super) async fn upload_test_file(caller: Principal, path: &str) -> FileId {
+        let alias = Canister::request_file(caller, Path::new(path).expect("valid path"))
+            .await
+            .unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
         let res = Canister::upload_file(
+            caller,
             file_id,
-            file_content,
-            file_type.clone(),
-            owner_key,
-            num_chunks,
+            vec![1, 2, 3],
+            "text/plain".to_string(),
+            [0; OwnerKey::KEY_SIZE].into(),
+            1,
+            vec![],
         );
         assert!(res.is_ok());
 
-        // share file with alice
-        let alice = Principal::from_slice(&[4, 5, 6, 7]);
-        let file_key_encrypted_for_user = [0; OwnerKey::KEY_SIZE].into();
-        let result = Canister::share_file(user, alice, file_id, file_key_encrypted_for_user).await;
-        assert_eq!(result, FileSharingResponse::Ok);
-
-        // delete file
-        let resp = Canister::delete_file(user, file_id).await;
-        assert_eq!(resp, DeleteFileResponse::Ok);
-
-        // check if the file is deleted
-        let file = FileDataStorage::get_file(&file_id);
-        assert!(file.is_none());
-        // check if the file is deleted from the alias index
-        let resp_file_id = FileAliasIndexStorage::get_file_id(&request_id);
-        assert!(resp_file_id.is_none());
-        // check if the file is deleted from the content storage
-        let file_content = FileContentsStorage::get_file_contents(&file_id, &0);
-        assert!(file_content.is_none());
-        // check if the file is deleted from the shares storage
-        let shares = FileSharesStorage::get_file_shares(&alice);
-        assert!(shares.is_none());
-    }
-
-    #[tokio::test]
-    #[should_panic(expected = "Only the owner can delete files")]
-    async fn test_only_owner_should_delete_file() {
-        init();
-        let file_id = 1;
-        Canister::delete_file(Principal::anonymous(), file_id).await;
+        file_id
     }
 
-    fn init() -> Principal {
+    pub(super) fn init() -> Principal {
         let caller = Principal::from_slice(&[0, 1, 2, 3]);
         Canister::init(UserCanisterInstallArgs::Init(UserCanisterInitArgs {
             orchestrator: Principal::from_slice(&[0, 1, 2, 3]),
@@ -0,0 +1,133 @@
+use did::user_canister::{
+    KeyRotation, KeyRotationStatus, PublicKey, StartKeyRotationResponse,
+    SubmitKeyRotationBatchResponse, WrappedFileKey,
+};
+
+use crate::storage::config::Config;
+use crate::storage::files::{File, FileContent, FileDataStorage};
+use crate::storage::key_rotation::{KeyRotationProgress, KeyRotationStorage};
+use crate::storage::reencryption::ReencryptionStorage;
+use crate::utils::time;
+
+/// Canister owner key rotation logic.
+///
+/// While a rotation is in progress, the owner re-wraps the document key of every uploaded file for
+/// the new public key. A file is migrated once its metadata holds the new public key.
+pub struct CanisterKeyRotation;
+
+impl CanisterKeyRotation {
+    /// Start a rotation to the given public key.
+    ///
+    /// A rotation can't be started while files are being re-encrypted.
+    pub fn start(new_public_key: PublicKey) -> StartKeyRotationResponse {
+        if KeyRotationStorage::get().is_some() {
+            return StartKeyRotationResponse::RotationInProgress;
+        }
+        if new_public_key == Config::get_owner_public_key() {
+            return StartKeyRotationResponse::SameKey;
+        }
+        // a staged re-encryption wraps the new document key for the current public key
+        if !ReencryptionStorage::is_empty() {
+            return StartKeyRotationResponse::ReencryptionInProgress;
+        }
+
+        KeyRotationStorage::start(KeyRotation {
+            new_public_key,
+            started_at: time(),
+        });
+
+        StartKeyRotationResponse::Ok
+    }
+
+    /// Get the progress of the rotation in progress, if any.
+    pub fn status() -> Option<KeyRotationStatus> {
+        let rotation = KeyRotationStorage::get()?;
+
+        let KeyRotationProgress {
+            migrated,
+            remaining,
+        } = KeyRotationStorage::progress();
+
+        Some(KeyRotationStatus {
+            rotation,
+            migrated,
+            remaining,
+        })
+    }
+
+    /// Get up to `limit` document keys which must still be re-wrapped for the new public key.
+    ///
+    /// Since migrated files are not returned anymore, the next batch can be fetched once the
+    /// previous one has been submitted.
+    pub fn pending_keys(limit: u64) -> Vec<WrappedFileKey> {
+        let Some(rotation) = KeyRotationStorage::get() else {
+            return vec![];
+        };
+
+        FileDataStorage::find_files(limit as usize, |file| {
+            Self::needs_migration(file, &rotation.new_public_key)
+        })
+        .into_iter()
+        .filter_map(|(file_id, file)| {
+            file.content.owner_key().map(|owner_key| WrappedFileKey {
+                file_id,
+                owner_key: *owner_key,
+            })
+        })
+        .collect()
+    }
+
+    /// Replace the document keys of a batch of files with the keys re-wrapped for the new public key.
+    ///
+    /// The batch is validated first, so either all the keys are updated or none of them.
+    /// Submitting a key for an already migrated file replaces it again.
+    pub fn submit_batch(keys: Vec<WrappedFileKey>) -> SubmitKeyRotationBatchResponse {
+        let Some(rotation) = KeyRotationStorage::get() else {
+            return SubmitKeyRotationBatchResponse::NoRotationInProgress;
+        };
+
+        let mut files = Vec::with_capacity(keys.len());
+        for WrappedFileKey { file_id, owner_key } in keys {
+            let Some(mut file) = FileDataStorage::get_file(&file_id) else {
+                return SubmitKeyRotationBatchResponse::FileNotFound(file_id);
+            };
+            if !file.content.set_owner_key(owner_key) {
+                return SubmitKeyRotationBatchResponse::NotUploadedFile(file_id);
+            }
+            file.metadata.user_public_key = rotation.new_public_key;
+            files.push((file_id, file));
+        }
+
+        for (file_id, file) in files {
+            FileDataStorage::set_file(&file_id, file);
+        }
+
+        SubmitKeyRotationBatchResponse::Ok(Self::remaining())
+    }
+
+    /// Number of files which must still be migrated for the rotation in progress.
+    pub fn remaining() -> u64 {
+        KeyRotationStorage::progress().remaining
+    }
+
+    /// Complete the rotation, replacing the owner public key.
+    ///
+    /// Pending file requests are moved to the new public key, so that uploaders encrypt for it.
+    pub fn finalize(rotation: KeyRotation) {
+        let requests = FileDataStorage::find_files(usize::MAX, |file| {
+            matches!(file.content, FileContent::Pending { .. })
+                && file.metadata.user_public_key != rotation.new_public_key
+        });
+        for (file_id, mut file) in requests {
+            file.metadata.user_public_key = rotation.new_public_key;
+            FileDataStorage::set_file(&file_id, file);
+        }
+
+        KeyRotationStorage::clear();
+        Config::set_owner_public_key(Config::get_owner(), rotation.new_public_key);
+    }
+
+    fn needs_migration(file: &File, new_public_key: &PublicKey) -> bool {
+        file.content.owner_key().is_some() && file.metadata.user_public_key != *new_public_key
+    }
+}
@@ -0,0 +1,535 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use candid::Principal;
+use did::orchestrator::{RevokeShareFileResponse, ShareFileMetadata, ShareFileResponse};
+use did::user_canister::{
+    AuditAction, FileSharingResponse, OutboxEntry, OutboxEntryWithId, OutboxOperation, OwnerKey,
+};
+
+use super::Canister;
+use super::share::CanisterShareFile;
+use crate::client::OrchestratorClient;
+use crate::storage::config::Config;
+use crate::storage::files::FileId;
+use crate::storage::outbox::OutboxStorage;
+use crate::utils::time;
+
+/// Interval between two deliveries of the pending outbox entries.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+thread_local! {
+    /// IDs of the outbox entries being delivered to the orchestrator.
+    static IN_FLIGHT: RefCell<BTreeSet<u64>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Result of the delivery of an outbox entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// The orchestrator confirmed the operation.
+    ///
+    /// The users unknown to the orchestrator, or who blocked the owner, were dropped from a
+    /// partial share.
+    Delivered {
+        unknown_users: Vec<Principal>,
+        blocked_by: Vec<Principal>,
+    },
+    /// The orchestrator rejected the share, since the user is unknown; it was dropped.
+    NoSuchUser(Principal),
+    /// The orchestrator rejected the share, since the user blocked the owner; it was dropped.
+    Blocked(Principal),
+    /// The orchestrator rejected the operation, which was dropped.
+    Rejected(String),
+    /// The orchestrator could not be called; the operation will be retried.
+    Failed(String),
+    /// The entry was already delivered, or is being delivered.
+    Skipped,
+}
+
+/// Durable outbox of the operations on the orchestrator.
+///
+/// Operations are recorded in the same message as the local changes, then delivered until the
+/// orchestrator confirms them. Delivering an operation twice has no effect on the orchestrator,
+/// so an entry can be retried until it is confirmed.
+///
+/// - Revocations are applied on the canister right away; the orchestrator index follows.
+/// - Shares are applied on the canister once the orchestrator confirms them, since it may reject
+///   them (e.g. for unknown users).
+///
+/// Recording an operation for a user cancels the pending operation of the opposite kind on the
+/// same file for that user, so that retries can't reorder a share and a revocation.
+pub struct Outbox;
+
+impl Outbox {
+    /// Schedule the delivery of the pending entries at a regular interval.
+    pub fn schedule_retries() {
+        if cfg!(target_family = "wasm") {
+            ic_cdk_timers::set_timer_interval(RETRY_INTERVAL, || {
+                ic_cdk::futures::spawn(Self::flush())
+            });
+        }
+    }
+
+    /// Record the share of a file with the given users.
+    ///
+    /// If `partial` is set, the users unknown to the orchestrator are dropped from the share on
+    /// delivery, rather than rejecting it.
+    pub fn share(
+        file_id: FileId,
+        file_name: String,
+        users: Vec<(Principal, OwnerKey)>,
+        partial: bool,
+    ) -> u64 {
+        let shared = users.iter().map(|(user, _)| *user).collect::<BTreeSet<_>>();
+        Self::cancel_pending(file_id, false, |user| shared.contains(user));
+
+        Self::push(OutboxOperation::ShareFile {
+            file_id,
+            file_name,
+            users,
+            partial,
+        })
+    }
+
+    /// Record the revocation of the shares of a file, already removed from the canister.
+    pub fn revoke(file_id: FileId, users: Vec<Principal>) -> u64 {
+        let revoked = users.iter().copied().collect::<BTreeSet<_>>();
+        Self::cancel_pending(file_id, true, |user| revoked.contains(user));
+
+        Self::push(OutboxOperation::RevokeShare { file_id, users })
+    }
+
+    /// Record the revocation of the shares of files with a user, already removed from the canister.
+    pub fn revoke_user(user: Principal, file_ids: Vec<FileId>) -> u64 {
+        for file_id in &file_ids {
+            Self::cancel_pending(*file_id, true, |share_user| *share_user == user);
+        }
+
+        Self::push(OutboxOperation::RevokeUserShares { user, file_ids })
+    }
+
+    /// Cancel all the pending shares of a file, returning the users they were for.
+    pub fn cancel_shares(file_id: FileId) -> Vec<Principal> {
+        Self::cancel_pending(file_id, true, |_| true)
+    }
+
+    /// Cancel all the pending shares with a user, returning the files they were for.
+    pub fn cancel_user_shares(user: Principal) -> Vec<FileId> {
+        let file_ids = Self::pending_user_shares(user);
+        for file_id in &file_ids {
+            Self::cancel_pending(*file_id, true, |share_user| *share_user == user);
+        }
+
+        file_ids.into_iter().collect()
+    }
+
+    /// Get the users with a pending share of a file.
+    pub fn pending_shares(file_id: FileId) -> BTreeSet<Principal> {
+        OutboxStorage::entries()
+            .into_iter()
+            .filter_map(|OutboxEntryWithId { entry, .. }| match entry.operation {
+                OutboxOperation::ShareFile {
+                    file_id: id, users, ..
+                } if id == file_id => Some(users),
+                _ => None,
+            })
+            .flatten()
+            .map(|(user, _)| user)
+            .collect()
+    }
+
+    /// Get the files with a pending share with a user.
+    pub fn pending_user_shares(user: Principal) -> BTreeSet<FileId> {
+        OutboxStorage::entries()
+            .into_iter()
+            .filter_map(|OutboxEntryWithId { entry, .. }| match entry.operation {
+                OutboxOperation::ShareFile { file_id, users, .. }
+                    if users.iter().any(|(share_user, _)| *share_user == user) =>
+                {
+                    Some(file_id)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the pending entries, from the oldest to the newest.
+    pub fn entries() -> Vec<OutboxEntryWithId> {
+        OutboxStorage::entries()
+    }
+
+    /// Deliver the pending entries in order, stopping at the first failure.
+    pub async fn flush() {
+        for OutboxEntryWithId { id, .. } in OutboxStorage::entries() {
+            if let Delivery::Failed(_) = Self::deliver(id).await {
+                return;
+            }
+        }
+    }
+
+    /// Deliver an entry to the orchestrator.
+    ///
+    /// Once delivered, the entry is removed and, for shares, the shares are applied on the canister
+    /// for the users which were not revoked in the meantime.
+    ///
+    /// The users unknown to the orchestrator, or who blocked the owner, are dropped from partial
+    /// shares, which are delivered again to the remaining users.
+    pub async fn deliver(id: u64) -> Delivery {
+        let mut unknown_users = vec![];
+        let mut blocked_by = vec![];
+        loop {
+            let Some(entry) = OutboxStorage::get(id) else {
+                return Delivery::Skipped;
+            };
+            if !IN_FLIGHT.with_borrow_mut(|in_flight| in_flight.insert(id)) {
+                return Delivery::Skipped;
+            }
+
+            let delivery = Self::call(&entry.operation).await;
+            IN_FLIGHT.with_borrow_mut(|in_flight| in_flight.remove(&id));
+
+            // the entry may have been changed or cancelled while waiting for the orchestrator
+            let Some(mut entry) = OutboxStorage::get(id) else {
+                return delivery;
+            };
+            match delivery {
+                Delivery::Delivered { .. } => {
+                    OutboxStorage::remove(id);
+                    if let OutboxOperation::ShareFile { file_id, users, .. } = entry.operation {
+                        Self::apply_shares(file_id, users);
+                    }
+                    return Delivery::Delivered {
+                        unknown_users,
+                        blocked_by,
+                    };
+                }
+                Delivery::NoSuchUser(user) | Delivery::Blocked(user) => {
+                    let OutboxOperation::ShareFile {
+                        users,
+                        partial: true,
+                        ..
+                    } = &mut entry.operation
+                    else {
+                        OutboxStorage::remove(id);
+                        return delivery;
+                    };
+
+                    users.retain(|(share_user, _)| *share_user != user);
+                    if matches!(delivery, Delivery::Blocked(_)) {
+                        blocked_by.push(user);
+                    } else {
+                        unknown_users.push(user);
+                    }
+                    if users.is_empty() {
+                        OutboxStorage::remove(id);
+                        return Delivery::Delivered {
+                            unknown_users,
+                            blocked_by,
+                        };
+                    }
+                    OutboxStorage::set(id, entry);
+                }
+                Delivery::Rejected(err) => {
+                    OutboxStorage::remove(id);
+                    return Delivery::Rejected(err);
+                }
+                Delivery::Failed(err) => {
+                    entry.attempts += 1;
+                    entry.last_error = Some(err.clone());
+                    OutboxStorage::set(id, entry);
+                    return Delivery::Failed(err);
+                }
+                Delivery::Skipped => return Delivery::Skipped,
+            }
+        }
+    }
+
+    fn push(operation: OutboxOperation) -> u64 {
+        OutboxStorage::push(OutboxEntry {
+            operation,
+            created_at: time(),
+            attempts: 0,
+            last_error: None,
+        })
+    }
+
+    /// Remove the users matching `cancel` from the pending shares (or revocations) of a file,
+    /// returning the removed users.
+    fn cancel_pending<F>(file_id: FileId, shares: bool, cancel: F) -> Vec<Principal>
+    where
+        F: Fn(&Principal) -> bool,
+    {
+        let mut cancelled = vec![];
+        for OutboxEntryWithId { id, mut entry } in OutboxStorage::entries() {
+            if !entry.operation.file_ids().contains(&file_id) {
+                continue;
+            }
+            let remaining = match &mut entry.operation {
+                OutboxOperation::ShareFile { users, .. } if shares => {
+                    users.retain(|(user, _)| {
+                        let cancel = cancel(user);
+                        if cancel {
+                            cancelled.push(*user);
+                        }
+                        !cancel
+                    });
+                    users.len()
+                }
+                OutboxOperation::RevokeShare { users, .. } if !shares => {
+                    users.retain(|user| {
+                        let cancel = cancel(user);
+                        if cancel {
+                            cancelled.push(*user);
+                        }
+                        !cancel
+                    });
+                    users.len()
+                }
+                OutboxOperation::RevokeUserShares { user, file_ids } if !shares => {
+                    if cancel(user) {
+                        file_ids.retain(|id| *id != file_id);
+                        cancelled.push(*user);
+                    }
+                    file_ids.len()
+                }
+                _ => continue,
+            };
+
+            if remaining == 0 {
+                OutboxStorage::remove(id);
+            } else {
+                OutboxStorage::set(id, entry);
+            }
+        }
+
+        cancelled
+    }
+
+    /// Apply the shares confirmed by the orchestrator.
+    ///
+    /// If the file doesn't exist anymore, the shares are revoked on the orchestrator.
+    fn apply_shares(file_id: FileId, users: Vec<(Principal, OwnerKey)>) {
+        let owner = Config::get_owner();
+        let mut not_shared = vec![];
+        for (user, key) in users {
+            match CanisterShareFile::share_file(user, file_id, key) {
+                FileSharingResponse::Ok => {
+                    Canister::audit(owner, AuditAction::ShareFile { user }, file_id);
+                }
+                _ => not_shared.push(user),
+            }
+        }
+
+        if !not_shared.is_empty() {
+            Self::revoke(file_id, not_shared);
+        }
+    }
+
+    /// Call the orchestrator for the given operation.
+    async fn call(operation: &OutboxOperation) -> Delivery {
+        if !cfg!(target_family = "wasm") {
+            return Delivery::Delivered {
+                unknown_users: vec![],
+                blocked_by: vec![],
+            };
+        }
+
+        let client = OrchestratorClient::from(Config::get_orchestrator());
+        match operation {
+            OutboxOperation::ShareFile {
+                file_id,
+                file_name,
+                users,
+                ..
+            } => {
+                let users = users.iter().map(|(user, _)| *user).collect::<Vec<_>>();
+                let metadata = ShareFileMetadata {
+                    file_name: file_name.clone(),
+                };
+                match client
+                    .share_file_with_users(&users, *file_id, metadata)
+                    .await
+                {
+                    Ok(ShareFileResponse::Ok) => Delivery::Delivered {
+                        unknown_users: vec![],
+                        blocked_by: vec![],
+                    },
+                    Ok(ShareFileResponse::NoSuchUser(user)) => Delivery::NoSuchUser(user),
+                    Ok(ShareFileResponse::Blocked(user)) => Delivery::Blocked(user),
+                    Ok(err) => Delivery::Rejected(format!("{err:?}")),
+                    Err(err) => Delivery::Failed(err.to_string()),
+                }
+            }
+            OutboxOperation::RevokeShare { file_id, users } => {
+                match client.revoke_share_file_for_users(users, *file_id).await {
+                    Ok(RevokeShareFileResponse::Ok) => Delivery::Delivered {
+                        unknown_users: vec![],
+                        blocked_by: vec![],
+                    },
+                    Ok(err) => Delivery::Rejected(format!("{err:?}")),
+                    Err(err) => Delivery::Failed(err.to_string()),
+                }
+            }
+            OutboxOperation::RevokeUserShares { user, file_ids } => {
+                match client.revoke_user_shares(*user, file_ids).await {
+                    Ok(RevokeShareFileResponse::Ok) => Delivery::Delivered {
+                        unknown_users: vec![],
+                        blocked_by: vec![],
+                    },
+                    Ok(err) => Delivery::Rejected(format!("{err:?}")),
+                    Err(err) => Delivery::Failed(err.to_string()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::user_canister::{Path, UserCanisterInitArgs, UserCanisterInstallArgs};
+
+    use super::*;
+    use crate::storage::files::{FileAliasIndexStorage, FileSharesStorage};
+
+    #[test]
+    fn test_should_cancel_pending_shares_on_revoke() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let key = OwnerKey::from([1; OwnerKey::KEY_SIZE]);
+        let share = Outbox::share(
+            1,
+            "a.txt".to_string(),
+            vec![(alice, key), (bob, key)],
+            false,
+        );
+        Outbox::share(2, "b.txt".to_string(), vec![(alice, key)], false);
+
+        let revoke = Outbox::revoke(1, vec![alice]);
+        assert_eq!(
+            OutboxStorage::get(share).unwrap().operation,
+            OutboxOperation::ShareFile {
+                file_id: 1,
+                file_name: "a.txt".to_string(),
+                users: vec![(bob, key)],
+                partial: false,
+            }
+        );
+
+        assert_eq!(Outbox::pending_shares(1), BTreeSet::from([bob]));
+
+        // sharing again cancels the pending revocation
+        Outbox::share(1, "a.txt".to_string(), vec![(alice, key)], false);
+        assert!(OutboxStorage::get(revoke).is_none());
+
+        assert_eq!(Outbox::cancel_shares(1), vec![bob, alice]);
+        let entries = Outbox::entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].entry.operation.file_ids(), vec![2]);
+    }
+
+    #[test]
+    fn test_should_revoke_user_shares() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let key = OwnerKey::from([1; OwnerKey::KEY_SIZE]);
+        let share = Outbox::share(
+            1,
+            "a.txt".to_string(),
+            vec![(alice, key), (bob, key)],
+            false,
+        );
+        Outbox::share(2, "b.txt".to_string(), vec![(alice, key)], false);
+        assert_eq!(Outbox::pending_user_shares(alice), BTreeSet::from([1, 2]));
+        assert_eq!(Outbox::pending_user_shares(bob), BTreeSet::from([1]));
+
+        // revoking the user cancels the pending shares with them
+        let revoke = Outbox::revoke_user(alice, vec![1, 2, 3]);
+        assert!(Outbox::pending_user_shares(alice).is_empty());
+        assert_eq!(
+            OutboxStorage::get(share).unwrap().operation.file_ids(),
+            vec![1]
+        );
+        assert_eq!(Outbox::pending_shares(1), BTreeSet::from([bob]));
+
+        // sharing again cancels the file from the pending revocation
+        Outbox::share(3, "c.txt".to_string(), vec![(alice, key)], false);
+        assert_eq!(
+            OutboxStorage::get(revoke).unwrap().operation,
+            OutboxOperation::RevokeUserShares {
+                user: alice,
+                file_ids: vec![1, 2],
+            }
+        );
+        for file_id in [1, 2] {
+            Outbox::share(file_id, "a.txt".to_string(), vec![(alice, key)], false);
+        }
+        assert!(OutboxStorage::get(revoke).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_should_apply_shares_once_delivered() {
+        let owner = Principal::from_slice(&[0, 1, 2, 3]);
+        Canister::init(UserCanisterInstallArgs::Init(UserCanisterInitArgs {
+            orchestrator: owner,
+            owner,
+        }));
+        let alias = Canister::request_file(owner, Path::new("/a.txt").unwrap())
+            .await
+            .unwrap();
+        let file_id = FileAliasIndexStorage::get_file_id(&alias).unwrap();
+        let res = Canister::upload_file(
+            owner,
+            file_id,
+            vec![1],
+            "text/plain".to_string(),
+            [0; OwnerKey::KEY_SIZE].into(),
+            1,
+            vec![],
+        );
+        assert!(res.is_ok());
+
+        let alice = Principal::from_slice(&[1; 29]);
+        let key = OwnerKey::from([1; OwnerKey::KEY_SIZE]);
+        let id = Outbox::share(file_id, "a.txt".to_string(), vec![(alice, key)], false);
+        // not shared until the orchestrator confirms it
+        assert!(FileSharesStorage::get_users_with_file_shares(&file_id).is_empty());
+
+        assert_eq!(
+            Outbox::deliver(id).await,
+            Delivery::Delivered {
+                unknown_users: vec![],
+                blocked_by: vec![],
+            }
+        );
+        assert_eq!(Outbox::deliver(id).await, Delivery::Skipped);
+        assert!(Outbox::entries().is_empty());
+        assert_eq!(
+            FileSharesStorage::get_users_with_file_shares(&file_id),
+            vec![alice]
+        );
+
+        // shares of missing files are revoked back on the orchestrator
+        let id = Outbox::share(42, "b.txt".to_string(), vec![(alice, key)], true);
+        assert_eq!(
+            Outbox::deliver(id).await,
+            Delivery::Delivered {
+                unknown_users: vec![],
+                blocked_by: vec![],
+            }
+        );
+        let entries = Outbox::entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].entry.operation,
+            OutboxOperation::RevokeShare {
+                file_id: 42,
+                users: vec![alice],
+            }
+        );
+
+        Outbox::flush().await;
+        assert!(Outbox::entries().is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use candid::Principal;
+use did::orchestrator::{FileCursorPagination, UserCanisterSharesResponse};
+use did::user_canister::{
+    AuditAction, FileShare, OutboxEntryWithId, OutboxOperation, ReconcileSharesResponse,
+    ShareReconciliationReport,
+};
+
+use super::Canister;
+use super::outbox::Outbox;
+use super::share::CanisterShareFile;
+use crate::client::OrchestratorClient;
+use crate::storage::config::Config;
+use crate::storage::files::{FileId, FileSharesStorage};
+use crate::utils::time;
+
+/// Interval between two reconciliations of the shares with the orchestrator.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Number of shared files to fetch from the orchestrator at once.
+const SHARES_PAGE_SIZE: u64 = 128;
+
+thread_local! {
+    /// Whether a reconciliation is waiting for the orchestrator.
+    static IN_PROGRESS: Cell<bool> = const { Cell::new(false) };
+
+    /// Report of the last reconciliation; it is not kept across upgrades.
+    static LAST_REPORT: RefCell<Option<ShareReconciliationReport>> = const { RefCell::new(None) };
+}
+
+/// Reconciliation of the shares of the canister with the share index of the orchestrator.
+///
+/// The shares of the canister are compared with the ones held by the orchestrator, skipping the
+/// shares with a pending operation in the outbox:
+///
+/// - Shares held by the orchestrator only are revoked on the orchestrator through the outbox,
+///   since the canister holds no key for them.
+/// - Shares held by the canister only are dropped. Shares are applied on the canister once the
+///   orchestrator confirms them, so these shares were declined or lost by the orchestrator, and
+///   the user can't find them anymore.
+pub struct ShareReconciliation;
+
+impl ShareReconciliation {
+    /// Schedule the reconciliation of the shares at a regular interval.
+    pub fn schedule() {
+        if cfg!(target_family = "wasm") {
+            ic_cdk_timers::set_timer_interval(RECONCILE_INTERVAL, || {
+                ic_cdk::futures::spawn(async {
+                    Self::run().await;
+                })
+            });
+        }
+    }
+
+    /// Get the report of the last reconciliation, if any.
+    pub fn last_report() -> Option<ShareReconciliationReport> {
+        LAST_REPORT.with_borrow(|report| report.clone())
+    }
+
+    /// Reconcile the shares of the canister with the ones held by the orchestrator.
+    pub async fn run() -> ReconcileSharesResponse {
+        if IN_PROGRESS.replace(true) {
+            return ReconcileSharesResponse::InProgress;
+        }
+
+        let snapshot = Self::local_shares();
+        let remote = Self::remote_shares(&snapshot).await;
+        IN_PROGRESS.set(false);
+
+        match remote {
+            Ok(remote) => ReconcileSharesResponse::Ok(Self::reconcile(&snapshot, &remote)),
+            Err(err) => ReconcileSharesResponse::FailedToGetShares(err),
+        }
+    }
+
+    /// Repair the discrepancies between the shares of the canister and the `remote` shares held
+    /// by the orchestrator.
+    ///
+    /// `snapshot` holds the shares of the canister when the orchestrator was called; only the
+    /// shares which were already on the canister then can be dropped, since the shares applied
+    /// in the meantime may be missing from `remote`.
+    fn reconcile(
+        snapshot: &BTreeSet<FileShare>,
+        remote: &BTreeSet<FileShare>,
+    ) -> ShareReconciliationReport {
+        let current = Self::local_shares();
+        let pending = Self::pending_shares();
+
+        let revoked_on_orchestrator = remote
+            .iter()
+            .filter(|share| !current.contains(share) && !pending.contains(share))
+            .copied()
+            .collect::<Vec<_>>();
+        let dropped = snapshot
+            .iter()
+            .filter(|share| {
+                current.contains(share) && !remote.contains(share) && !pending.contains(share)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut revocations = BTreeMap::<FileId, Vec<Principal>>::new();
+        for FileShare { file_id, user } in &revoked_on_orchestrator {
+            revocations.entry(*file_id).or_default().push(*user);
+        }
+        for (file_id, users) in revocations {
+            Outbox::revoke(file_id, users);
+        }
+
+        // the orchestrator is recorded as the actor, since it doesn't hold the share anymore
+        let orchestrator = Config::get_orchestrator();
+        for FileShare { file_id, user } in &dropped {
+            CanisterShareFile::revoke_share(*user, *file_id);
+            Canister::audit(
+                orchestrator,
+                AuditAction::RevokeShare { user: *user },
+                *file_id,
+            );
+        }
+
+        let report = ShareReconciliationReport {
+            reconciled_at: time(),
+            revoked_on_orchestrator,
+            dropped,
+        };
+        LAST_REPORT.with_borrow_mut(|last_report| *last_report = Some(report.clone()));
+
+        report
+    }
+
+    /// Get the shares held by the canister.
+    fn local_shares() -> BTreeSet<FileShare> {
+        FileSharesStorage::get_file_shares_storage()
+            .into_iter()
+            .flat_map(|(user, file_ids)| {
+                file_ids
+                    .into_iter()
+                    .map(move |file_id| FileShare { file_id, user })
+            })
+            .collect()
+    }
+
+    /// Get the shares with a pending operation in the outbox.
+    fn pending_shares() -> BTreeSet<FileShare> {
+        Outbox::entries()
+            .into_iter()
+            .flat_map(|OutboxEntryWithId { entry, .. }| match entry.operation {
+                OutboxOperation::ShareFile { file_id, users, .. } => users
+                    .into_iter()
+                    .map(|(user, _)| FileShare { file_id, user })
+                    .collect::<Vec<_>>(),
+                OutboxOperation::RevokeShare { file_id, users } => users
+                    .into_iter()
+                    .map(|user| FileShare { file_id, user })
+                    .collect(),
+                OutboxOperation::RevokeUserShares { user, file_ids } => file_ids
+                    .into_iter()
+                    .map(|file_id| FileShare { file_id, user })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Get the shares held by the orchestrator for the canister, fetching them page by page.
+    async fn remote_shares(snapshot: &BTreeSet<FileShare>) -> Result<BTreeSet<FileShare>, String> {
+        if !cfg!(target_family = "wasm") {
+            return Ok(snapshot.clone());
+        }
+
+        let client = OrchestratorClient::from(Config::get_orchestrator());
+        let mut shares = BTreeSet::new();
+        let mut cursor = None;
+        loop {
+            let page = match client
+                .get_user_canister_shares(FileCursorPagination {
+                    cursor,
+                    limit: SHARES_PAGE_SIZE,
+                })
+                .await
+            {
+                Ok(UserCanisterSharesResponse::Shares(page)) => page,
+                Ok(err) => return Err(format!("{err:?}")),
+                Err(err) => return Err(err.to_string()),
+            };
+            shares.extend(page.files.into_iter().flat_map(|file| {
+                file.users.into_iter().map(move |user| FileShare {
+                    file_id: file.file_id,
+                    user,
+                })
+            }));
+
+            match page.next {
+                Some(next) => cursor = Some(next),
+                None => return Ok(shares),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::user_canister::OwnerKey;
+
+    use super::*;
+    use crate::canister::test::{init, upload_test_file};
+
+    #[tokio::test]
+    async fn test_should_reconcile_shares() {
+        let owner = init();
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let charlie = Principal::from_slice(&[3; 29]);
+        let key = OwnerKey::from([1; OwnerKey::KEY_SIZE]);
+        let file_id = upload_test_file(owner, "/a.txt").await;
+        let other_file_id = upload_test_file(owner, "/b.txt").await;
+        for user in [alice, bob] {
+            CanisterShareFile::share_file(user, file_id, key);
+        }
+        // bob's share is being revoked
+        Outbox::revoke(other_file_id, vec![bob]);
+
+        let snapshot = ShareReconciliation::local_shares();
+        let remote = BTreeSet::from([
+            FileShare { file_id, user: bob },
+            FileShare {
+                file_id,
+                user: charlie,
+            },
+            FileShare {
+                file_id: other_file_id,
+                user: bob,
+            },
+        ]);
+        let report = ShareReconciliation::reconcile(&snapshot, &remote);
+
+        assert_eq!(
+            report.revoked_on_orchestrator,
+            vec![FileShare {
+                file_id,
+                user: charlie,
+            }]
+        );
+        assert_eq!(
+            report.dropped,
+            vec![FileShare {
+                file_id,
+                user: alice,
+            }]
+        );
+        assert_eq!(
+            FileSharesStorage::get_users_with_file_shares(&file_id),
+            vec![bob]
+        );
+        assert_eq!(
+            Outbox::entries().last().unwrap().entry.operation,
+            OutboxOperation::RevokeShare {
+                file_id,
+                users: vec![charlie],
+            }
+        );
+        assert_eq!(ShareReconciliation::last_report(), Some(report));
+
+        // once reconciled, there is nothing left to repair
+        let report = ShareReconciliation::reconcile(&ShareReconciliation::local_shares(), &remote);
+        assert!(report.dropped.is_empty());
+        assert!(report.revoked_on_orchestrator.is_empty());
+    }
+}
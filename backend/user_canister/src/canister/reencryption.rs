@@ -0,0 +1,204 @@
+use std::collections::BTreeSet;
+
+use candid::Principal;
+use did::user_canister::{
+    ReencryptFileContinueResponse, ReencryptFileRequest, ReencryptFileResponse,
+    UploadFileContinueRequest,
+};
+
+use crate::storage::config::Config;
+use crate::storage::files::{
+    FileContent, FileContentsStorage, FileDataStorage, FileDeviceKeysStorage, FileId,
+    UploadedChunks,
+};
+use crate::storage::key_rotation::KeyRotationStorage;
+use crate::storage::reencryption::ReencryptionStorage;
+use crate::utils::time;
+
+/// Canister file re-encryption logic.
+///
+/// The re-encrypted chunks and the new keys are staged apart from the file, which keeps its
+/// current content until the last chunk is uploaded. The content is then swapped in a single
+/// message, so the file is never left with a mix of old and new chunks or keys.
+pub struct CanisterReencryption;
+
+impl CanisterReencryption {
+    /// Check a re-encryption request against the current state of the file.
+    ///
+    /// The new keys must cover exactly the current recipients, minus the revoked users.
+    ///
+    /// Returns the recipients of the file which are revoked by the request.
+    pub fn check(request: &ReencryptFileRequest) -> Result<Vec<Principal>, ReencryptFileResponse> {
+        if KeyRotationStorage::get().is_some() {
+            return Err(ReencryptFileResponse::KeyRotationInProgress);
+        }
+        let Some(file) = FileDataStorage::get_file(&request.file_id) else {
+            return Err(ReencryptFileResponse::FileNotFound);
+        };
+        let FileContent::Uploaded { shared_keys, .. } = &file.content else {
+            return Err(ReencryptFileResponse::NotUploadedFile);
+        };
+        if request.num_chunks == 0 {
+            return Err(ReencryptFileResponse::InvalidNumChunks);
+        }
+
+        let revoke = request.revoke.iter().collect::<BTreeSet<_>>();
+        let new_recipients = request
+            .shared_keys
+            .iter()
+            .map(|(user, _)| user)
+            .collect::<BTreeSet<_>>();
+        if let Some(user) = new_recipients
+            .iter()
+            .find(|user| !shared_keys.contains_key(user) || revoke.contains(*user))
+        {
+            return Err(ReencryptFileResponse::UnexpectedSharedKey(**user));
+        }
+        if let Some(user) = shared_keys
+            .keys()
+            .find(|user| !revoke.contains(user) && !new_recipients.contains(user))
+        {
+            return Err(ReencryptFileResponse::MissingSharedKey(*user));
+        }
+
+        Ok(shared_keys
+            .keys()
+            .filter(|user| revoke.contains(user))
+            .copied()
+            .collect())
+    }
+
+    /// Stage the new keys of the file, dropping any previous re-encryption of the file.
+    ///
+    /// The request must have been checked with [`CanisterReencryption::check`].
+    pub fn start(request: ReencryptFileRequest) {
+        let Some(file) = FileDataStorage::get_file(&request.file_id) else {
+            return;
+        };
+        let FileContent::Uploaded { file_type, .. } = file.content else {
+            return;
+        };
+
+        ReencryptionStorage::remove(&request.file_id);
+        ReencryptionStorage::set(
+            &request.file_id,
+            FileContent::PartiallyUploaded {
+                num_chunks: request.num_chunks,
+                uploaded_chunks: UploadedChunks::default(),
+                file_type,
+                owner_key: request.owner_key,
+                shared_keys: request.shared_keys.into_iter().collect(),
+            },
+        );
+    }
+
+    /// Stage a re-encrypted chunk, completing the re-encryption once all the chunks are staged.
+    pub fn upload_chunk(request: UploadFileContinueRequest) -> ReencryptFileContinueResponse {
+        let UploadFileContinueRequest {
+            file_id,
+            chunk_id,
+            contents,
+        } = request;
+        let Some(FileContent::PartiallyUploaded {
+            num_chunks,
+            mut uploaded_chunks,
+            file_type,
+            owner_key,
+            shared_keys,
+        }) = ReencryptionStorage::get(&file_id)
+        else {
+            return ReencryptFileContinueResponse::NoReencryptionInProgress;
+        };
+
+        if uploaded_chunks.contains(&chunk_id) {
+            return ReencryptFileContinueResponse::ChunkAlreadyUploaded;
+        }
+        if chunk_id >= num_chunks {
+            return ReencryptFileContinueResponse::ChunkOutOfBounds;
+        }
+
+        ReencryptionStorage::set_chunk(&file_id, &chunk_id, contents);
+        uploaded_chunks.insert(chunk_id);
+
+        if uploaded_chunks.len() == num_chunks as usize {
+            return Self::complete(
+                file_id,
+                FileContent::Uploaded {
+                    num_chunks,
+                    file_type,
+                    owner_key,
+                    shared_keys,
+                },
+            );
+        }
+
+        ReencryptionStorage::set(
+            &file_id,
+            FileContent::PartiallyUploaded {
+                num_chunks,
+                uploaded_chunks,
+                file_type,
+                owner_key,
+                shared_keys,
+            },
+        );
+
+        ReencryptFileContinueResponse::Ok
+    }
+
+    /// Swap the content of the file with the staged one.
+    ///
+    /// The re-encryption is aborted if the recipients of the file changed since it was started,
+    /// or if the owner public key is being rotated or changed: the staged owner key may then be
+    /// wrapped for another public key than the one of the file.
+    fn complete(file_id: FileId, content: FileContent) -> ReencryptFileContinueResponse {
+        let Some(mut file) = FileDataStorage::get_file(&file_id) else {
+            ReencryptionStorage::remove(&file_id);
+            return ReencryptFileContinueResponse::NoReencryptionInProgress;
+        };
+        if KeyRotationStorage::get().is_some()
+            || file.metadata.user_public_key != Config::get_owner_public_key()
+        {
+            ReencryptionStorage::remove(&file_id);
+            return ReencryptFileContinueResponse::OwnerKeyChanged;
+        }
+        let (
+            FileContent::Uploaded {
+                num_chunks: old_num_chunks,
+                shared_keys: current_keys,
+                ..
+            },
+            FileContent::Uploaded {
+                num_chunks,
+                shared_keys,
+                ..
+            },
+        ) = (&file.content, &content)
+        else {
+            ReencryptionStorage::remove(&file_id);
+            return ReencryptFileContinueResponse::NoReencryptionInProgress;
+        };
+        if !current_keys.keys().eq(shared_keys.keys()) {
+            ReencryptionStorage::remove(&file_id);
+            return ReencryptFileContinueResponse::RecipientsChanged;
+        }
+
+        for chunk_id in 0..*old_num_chunks {
+            FileContentsStorage::remove_file_contents(&file_id, &chunk_id);
+        }
+        for chunk_id in 0..*num_chunks {
+            if let Some(contents) = ReencryptionStorage::take_chunk(&file_id, &chunk_id) {
+                FileContentsStorage::set_file_contents(&file_id, &chunk_id, contents);
+            }
+        }
+        ReencryptionStorage::remove(&file_id);
+        // the device keys wrap the previous document key, and must be set again
+        FileDeviceKeysStorage::remove_file_keys(&file_id);
+
+        file.content = content;
+        file.metadata.uploaded_at = Some(time());
+        FileDataStorage::set_file(&file_id, file);
+
+        ReencryptFileContinueResponse::Completed
+    }
+}
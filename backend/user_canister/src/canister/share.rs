@@ -2,7 +2,9 @@ use candid::Principal;
 use did::orchestrator::FileId;
 use did::user_canister::{FileSharingResponse, OwnerKey};
 
-use crate::storage::files::{FileContent, FileDataStorage, FileSharesStorage};
+use crate::storage::files::{
+    FileContent, FileDataStorage, FileDeviceKeysStorage, FileSharesStorage,
+};
 
 /// Canister share file logic.
 pub struct CanisterShareFile;
@@ -81,4 +83,31 @@ impl CanisterShareFile {
 
         FileSharingResponse::Ok
     }
+
+    /// Remove the share of a file for a user from the canister storage.
+    ///
+    /// The user's decryption keys are dropped from the file shared keys and device keys.
+    pub fn revoke_share(user_id: Principal, file_id: FileId) -> FileSharingResponse {
+        let Some(mut file) = FileDataStorage::get_file(&file_id) else {
+            return FileSharingResponse::FileNotFound;
+        };
+
+        // remove user from file shares
+        match &mut file.content {
+            FileContent::Uploaded { shared_keys, .. }
+            | FileContent::PartiallyUploaded { shared_keys, .. } => {
+                shared_keys.remove(&user_id);
+            }
+            _ => {}
+        }
+
+        // persist file
+        FileDataStorage::set_file(&file_id, file);
+
+        // remove file from user shares
+        FileSharesStorage::revoke(&user_id, &file_id);
+        FileDeviceKeysStorage::remove_user_keys(&file_id, user_id);
+
+        FileSharingResponse::Ok
+    }
 }
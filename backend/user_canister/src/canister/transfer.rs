@@ -0,0 +1,865 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use candid::Principal;
+use did::orchestrator::GetUserCanisterResponse;
+use did::user_canister::{
+    AcceptFileTransferResponse, AuditAction, CompleteFileTransferResponse, IncomingFileTransfer,
+    MAX_INCOMING_TRANSFERS_PER_SENDER, MAX_TRANSFER_CHUNK_SIZE, MAX_TRANSFER_NUM_CHUNKS, Path,
+    ReceiveFileTransferChunkResponse, ReceiveFileTransferRequest, ReceiveFileTransferResponse,
+    ReleaseFileTransferResponse, TransferFileRequest, TransferFileResponse,
+    UploadFileContinueRequest,
+};
+use did::utils::trap;
+
+use super::Canister;
+use super::outbox::Outbox;
+use crate::client::{OrchestratorClient, UserCanisterClient};
+use crate::storage::config::Config;
+use crate::storage::files::{
+    File, FileContent, FileContentsStorage, FileCountStorage, FileDataStorage, FileId,
+    FileMetadata, OwnedFilesStorage, PathStorage, UploadedChunks,
+};
+use crate::storage::key_rotation::KeyRotationStorage;
+use crate::storage::transfer::{
+    IncomingTransfer, IncomingTransferStorage, OutgoingTransfer, OutgoingTransferStorage,
+};
+use crate::utils::time;
+
+/// Time after which a transfer not accepted by the recipient is dropped.
+const TRANSFER_EXPIRY: u64 = 7 * 24 * 60 * 60 * 1_000_000_000; // 7 days
+/// Interval between two checks for expired transfers.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+thread_local! {
+    /// IDs of the files being sent to another user canister.
+    static OUTGOING: RefCell<BTreeSet<FileId>> = const { RefCell::new(BTreeSet::new()) };
+
+    /// Transfers being accepted by the owner, by sending user canister and ID of the file there.
+    static ACCEPTING: RefCell<BTreeSet<(Principal, FileId)>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Transfer of the ownership of a file to another user.
+///
+/// The user canister of the owner locates the user canister of the recipient through the
+/// orchestrator, then sends the file metadata and streams the chunks, which the recipient stages
+/// apart from its files. Once all the chunks are sent, the transfer awaits the acceptance of the
+/// recipient. When they accept it, their user canister asks the user canister of the owner to
+/// release the file, which is deleted there, then creates the file.
+///
+/// If the transfer is interrupted, the original is kept and the transfer can be started again,
+/// replacing the chunks staged by the recipient. Transfers not accepted within
+/// [`TRANSFER_EXPIRY`] are dropped on both sides.
+pub struct CanisterFileTransfer;
+
+impl CanisterFileTransfer {
+    /// Schedule the removal of the expired transfers at a regular interval.
+    pub fn schedule_expiry() {
+        if cfg!(target_family = "wasm") {
+            ic_cdk_timers::set_timer_interval(EXPIRY_INTERVAL, Self::expire);
+        }
+    }
+
+    /// Drop the transfers started more than [`TRANSFER_EXPIRY`] ago.
+    pub fn expire() {
+        let expired_before = time().saturating_sub(TRANSFER_EXPIRY);
+        for (sender, file_id) in IncomingTransferStorage::started_before(expired_before) {
+            if !Self::is_accepting(sender, file_id) {
+                IncomingTransferStorage::remove(sender, &file_id);
+            }
+        }
+        for file_id in OutgoingTransferStorage::sent_before(expired_before) {
+            OutgoingTransferStorage::remove(&file_id);
+        }
+    }
+
+    /// Check a transfer request against the current state of the file.
+    ///
+    /// Returns the file to transfer.
+    pub fn check(request: &TransferFileRequest) -> Result<File, TransferFileResponse> {
+        if request.recipient == Config::get_owner() {
+            return Err(TransferFileResponse::RecipientIsOwner);
+        }
+        if KeyRotationStorage::get().is_some() {
+            return Err(TransferFileResponse::KeyRotationInProgress);
+        }
+        let Some(file) = FileDataStorage::get_file(&request.file_id) else {
+            return Err(TransferFileResponse::FileNotFound);
+        };
+        let FileContent::Uploaded { num_chunks, .. } = &file.content else {
+            return Err(TransferFileResponse::NotUploadedFile);
+        };
+        if *num_chunks > MAX_TRANSFER_NUM_CHUNKS {
+            return Err(TransferFileResponse::TooManyChunks);
+        }
+
+        Ok(file)
+    }
+
+    /// Send a checked file to the user canister of the recipient.
+    ///
+    /// The file is kept until the recipient accepts the transfer.
+    pub async fn send(
+        request: &TransferFileRequest,
+        file: &File,
+    ) -> Result<(), TransferFileResponse> {
+        if !OUTGOING.with_borrow_mut(|outgoing| outgoing.insert(request.file_id)) {
+            return Err(TransferFileResponse::TransferInProgress);
+        }
+
+        let result = Self::send_file(request, file).await;
+        OUTGOING.with_borrow_mut(|outgoing| outgoing.remove(&request.file_id));
+
+        result
+    }
+
+    /// Delete a file sent to the `caller` user canister, once the recipient accepted it.
+    ///
+    /// The file must be unchanged since it was sent; its shares are revoked through the outbox.
+    pub async fn release(caller: Principal, file_id: FileId) -> ReleaseFileTransferResponse {
+        let Some(transfer) = OutgoingTransferStorage::get(&file_id)
+            .filter(|transfer| transfer.user_canister == caller)
+        else {
+            return ReleaseFileTransferResponse::NoTransferInProgress;
+        };
+        OutgoingTransferStorage::remove(&file_id);
+        let Some(file) = FileDataStorage::get_file(&file_id).filter(|file| *file == transfer.file)
+        else {
+            return ReleaseFileTransferResponse::FileChanged;
+        };
+
+        Canister::audit(
+            Config::get_owner(),
+            AuditAction::TransferFile {
+                user: transfer.recipient,
+            },
+            file_id,
+        );
+        let users_with_access = Canister::remove_file(file_id, file);
+        if !users_with_access.is_empty() {
+            let id = Outbox::revoke(file_id, users_with_access);
+            Outbox::deliver(id).await;
+        }
+
+        ReleaseFileTransferResponse::Ok
+    }
+
+    /// Start the transfer of a file from the `sender` user canister.
+    ///
+    /// The sender must be the user canister of the owner of the file. Starting again drops the
+    /// chunks staged by a previous transfer of the file.
+    pub async fn receive(
+        sender: Principal,
+        request: ReceiveFileTransferRequest,
+    ) -> ReceiveFileTransferResponse {
+        if Config::is_suspended() {
+            return ReceiveFileTransferResponse::Suspended;
+        }
+        if request.num_chunks == 0 || request.num_chunks > MAX_TRANSFER_NUM_CHUNKS {
+            return ReceiveFileTransferResponse::InvalidNumChunks;
+        }
+        if PathStorage::exists(&request.path) {
+            return ReceiveFileTransferResponse::FileAlreadyExists;
+        }
+        if let Err(err) = Self::check_sender(sender, request.owner).await {
+            return err;
+        }
+        // checked after the sender, which may start other transfers in the meantime
+        if Self::is_accepting(sender, request.file_id) {
+            return ReceiveFileTransferResponse::AcceptInProgress;
+        }
+        Self::expire();
+        if IncomingTransferStorage::get(sender, &request.file_id).is_none()
+            && IncomingTransferStorage::count_from(sender) >= MAX_INCOMING_TRANSFERS_PER_SENDER
+        {
+            return ReceiveFileTransferResponse::TooManyTransfers;
+        }
+
+        IncomingTransferStorage::remove(sender, &request.file_id);
+        IncomingTransferStorage::set(
+            sender,
+            &request.file_id,
+            IncomingTransfer {
+                owner: request.owner,
+                started_at: time(),
+                ready: false,
+                path: request.path,
+                content: FileContent::PartiallyUploaded {
+                    num_chunks: request.num_chunks,
+                    uploaded_chunks: UploadedChunks::default(),
+                    file_type: request.file_type,
+                    owner_key: request.owner_key,
+                    shared_keys: request.shared_keys.into_iter().collect(),
+                },
+            },
+        );
+
+        ReceiveFileTransferResponse::Ok
+    }
+
+    /// Stage a chunk of a file being transferred from the `sender` user canister.
+    ///
+    /// Sending a chunk again replaces it, until the transfer is completed.
+    pub fn receive_chunk(
+        sender: Principal,
+        request: UploadFileContinueRequest,
+    ) -> ReceiveFileTransferChunkResponse {
+        let UploadFileContinueRequest {
+            file_id,
+            chunk_id,
+            contents,
+        } = request;
+        let Some(mut transfer) =
+            IncomingTransferStorage::get(sender, &file_id).filter(|transfer| !transfer.ready)
+        else {
+            return ReceiveFileTransferChunkResponse::NoTransferInProgress;
+        };
+        let FileContent::PartiallyUploaded {
+            num_chunks,
+            uploaded_chunks,
+            ..
+        } = &mut transfer.content
+        else {
+            return ReceiveFileTransferChunkResponse::NoTransferInProgress;
+        };
+        if chunk_id >= *num_chunks {
+            return ReceiveFileTransferChunkResponse::ChunkOutOfBounds;
+        }
+        if contents.len() > MAX_TRANSFER_CHUNK_SIZE {
+            return ReceiveFileTransferChunkResponse::ChunkTooLarge;
+        }
+
+        uploaded_chunks.insert(chunk_id);
+        IncomingTransferStorage::set_chunk(sender, &file_id, &chunk_id, contents);
+        IncomingTransferStorage::set(sender, &file_id, transfer);
+
+        ReceiveFileTransferChunkResponse::Ok
+    }
+
+    /// Complete the transfer of a file from the `sender` user canister, once all its chunks are
+    /// staged.
+    ///
+    /// The transfer then awaits the acceptance of the owner.
+    pub fn complete(sender: Principal, file_id: FileId) -> CompleteFileTransferResponse {
+        let Some(mut transfer) = IncomingTransferStorage::get(sender, &file_id) else {
+            return CompleteFileTransferResponse::NoTransferInProgress;
+        };
+        let FileContent::PartiallyUploaded {
+            num_chunks,
+            uploaded_chunks,
+            ..
+        } = &transfer.content
+        else {
+            return CompleteFileTransferResponse::NoTransferInProgress;
+        };
+        let missing = num_chunks.saturating_sub(uploaded_chunks.len() as u64);
+        if missing > 0 {
+            return CompleteFileTransferResponse::MissingChunks(missing);
+        }
+        if PathStorage::exists(&transfer.path) {
+            IncomingTransferStorage::remove(sender, &file_id);
+            return CompleteFileTransferResponse::FileAlreadyExists;
+        }
+
+        transfer.ready = true;
+        IncomingTransferStorage::set(sender, &file_id, transfer);
+
+        CompleteFileTransferResponse::Ok
+    }
+
+    /// Drop the transfer of a file from the `sender` user canister, if any.
+    ///
+    /// A transfer being accepted is kept.
+    pub fn abort(sender: Principal, file_id: FileId) {
+        if !Self::is_accepting(sender, file_id) {
+            IncomingTransferStorage::remove(sender, &file_id);
+        }
+    }
+
+    /// Get the transfers awaiting the acceptance of the owner.
+    pub fn incoming() -> Vec<IncomingFileTransfer> {
+        IncomingTransferStorage::ready()
+            .into_iter()
+            .filter_map(|(sender, file_id, transfer)| {
+                let FileContent::PartiallyUploaded {
+                    num_chunks,
+                    file_type,
+                    ..
+                } = transfer.content
+                else {
+                    return None;
+                };
+
+                Some(IncomingFileTransfer {
+                    sender,
+                    file_id,
+                    owner: transfer.owner,
+                    path: transfer.path,
+                    file_type,
+                    num_chunks,
+                    started_at: transfer.started_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Accept the transfer of a file from the `sender` user canister.
+    ///
+    /// The sender deletes the original first, then the file is created and shared again with the
+    /// users it was shared with through the outbox. If a file was created at the same path in the
+    /// meantime, the file name is suffixed with the ID of the new file.
+    pub async fn accept(sender: Principal, file_id: FileId) -> AcceptFileTransferResponse {
+        let Some(transfer) =
+            IncomingTransferStorage::get(sender, &file_id).filter(|transfer| transfer.ready)
+        else {
+            return AcceptFileTransferResponse::NoTransferInProgress;
+        };
+        if PathStorage::exists(&transfer.path) {
+            return AcceptFileTransferResponse::FileAlreadyExists;
+        }
+        if !ACCEPTING.with_borrow_mut(|accepting| accepting.insert((sender, file_id))) {
+            return AcceptFileTransferResponse::AcceptInProgress;
+        }
+
+        let result = Self::release_from(sender, file_id).await;
+        ACCEPTING.with_borrow_mut(|accepting| accepting.remove(&(sender, file_id)));
+        match result {
+            Ok(ReleaseFileTransferResponse::Ok) => {}
+            Ok(_) => {
+                IncomingTransferStorage::remove(sender, &file_id);
+                return AcceptFileTransferResponse::Withdrawn;
+            }
+            Err(err) => return AcceptFileTransferResponse::Failed(err),
+        }
+
+        AcceptFileTransferResponse::Ok(Self::create_file(sender, file_id, transfer).await)
+    }
+
+    /// Decline the transfer of a file from the `sender` user canister, dropping its chunks.
+    ///
+    /// The user canister of the owner drops the transfer once it expires.
+    pub fn decline(sender: Principal, file_id: FileId) {
+        Self::abort(sender, file_id);
+    }
+
+    /// Create the file of an accepted transfer from its staged chunks.
+    ///
+    /// Returns the ID of the new file.
+    async fn create_file(sender: Principal, file_id: FileId, transfer: IncomingTransfer) -> FileId {
+        let IncomingTransfer {
+            owner,
+            path,
+            content:
+                FileContent::PartiallyUploaded {
+                    num_chunks,
+                    file_type,
+                    owner_key,
+                    shared_keys,
+                    ..
+                },
+            ..
+        } = transfer
+        else {
+            trap("Incoming transfer without a partially uploaded content");
+        };
+
+        let new_file_id = FileCountStorage::generate_file_id();
+        for chunk_id in 0..num_chunks {
+            if let Some(contents) = IncomingTransferStorage::take_chunk(sender, &file_id, &chunk_id)
+            {
+                FileContentsStorage::set_file_contents(&new_file_id, &chunk_id, contents);
+            }
+        }
+        IncomingTransferStorage::remove(sender, &file_id);
+
+        let recipient = Config::get_owner();
+        let file = File {
+            metadata: FileMetadata {
+                user_public_key: Config::get_owner_public_key(),
+                requester_principal: recipient,
+                requested_at: time(),
+                uploaded_at: Some(time()),
+            },
+            content: FileContent::Uploaded {
+                num_chunks,
+                file_type,
+                owner_key,
+                shared_keys: BTreeMap::new(),
+            },
+        };
+        FileDataStorage::set_file(&new_file_id, file);
+        OwnedFilesStorage::add_owned_file(&new_file_id);
+        // the original is deleted already, so the file must be created even if the path was taken
+        let path = if PathStorage::exists(&path) {
+            Path::new(format!("{path} ({new_file_id})")).unwrap_or(path)
+        } else {
+            path
+        };
+        let file_name = path.file_name().unwrap_or_default().to_string();
+        PathStorage::create(new_file_id, path);
+
+        Canister::audit(owner, AuditAction::ReceiveFile { user: owner }, new_file_id);
+
+        // the shares are applied once the orchestrator confirms them
+        let shared_keys = shared_keys
+            .into_iter()
+            .filter(|(user, _)| *user != recipient)
+            .collect::<Vec<_>>();
+        if !shared_keys.is_empty() {
+            let id = Outbox::share(new_file_id, file_name, shared_keys, true);
+            Outbox::deliver(id).await;
+        }
+
+        new_file_id
+    }
+
+    fn is_accepting(sender: Principal, file_id: FileId) -> bool {
+        ACCEPTING.with_borrow(|accepting| accepting.contains(&(sender, file_id)))
+    }
+
+    /// Ask the `sender` user canister to release a file whose transfer was accepted.
+    async fn release_from(
+        sender: Principal,
+        file_id: FileId,
+    ) -> Result<ReleaseFileTransferResponse, String> {
+        if !cfg!(target_family = "wasm") {
+            return Ok(ReleaseFileTransferResponse::Ok);
+        }
+
+        UserCanisterClient::from(sender)
+            .release_file_transfer(file_id)
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    async fn send_file(
+        request: &TransferFileRequest,
+        file: &File,
+    ) -> Result<(), TransferFileResponse> {
+        if !cfg!(target_family = "wasm") {
+            return Ok(());
+        }
+
+        let file_id = request.file_id;
+        let FileContent::Uploaded {
+            num_chunks,
+            file_type,
+            shared_keys,
+            ..
+        } = &file.content
+        else {
+            return Err(TransferFileResponse::NotUploadedFile);
+        };
+        let Some(path) = PathStorage::read_link(&file_id) else {
+            return Err(TransferFileResponse::FileNotFound);
+        };
+        let shared_keys = if request.keep_shares {
+            shared_keys
+                .iter()
+                .filter(|(user, _)| **user != request.recipient)
+                .map(|(user, key)| (*user, *key))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let client = UserCanisterClient::from(Self::recipient_canister(request.recipient).await?);
+        let receive_request = ReceiveFileTransferRequest {
+            owner: Config::get_owner(),
+            file_id,
+            path,
+            file_type: file_type.clone(),
+            num_chunks: *num_chunks,
+            owner_key: request.owner_key,
+            shared_keys,
+        };
+        match client.receive_file_transfer(&receive_request).await {
+            Ok(ReceiveFileTransferResponse::Ok) => {}
+            Ok(ReceiveFileTransferResponse::FileAlreadyExists) => {
+                return Err(TransferFileResponse::FileAlreadyExists);
+            }
+            Ok(err) => return Err(TransferFileResponse::Failed(format!("{err:?}"))),
+            Err(err) => return Err(TransferFileResponse::Failed(err.to_string())),
+        }
+
+        for chunk_id in 0..*num_chunks {
+            let chunk = UploadFileContinueRequest {
+                file_id,
+                chunk_id,
+                contents: FileContentsStorage::get_file_contents(&file_id, &chunk_id)
+                    .unwrap_or_default(),
+            };
+            match client.receive_file_transfer_chunk(&chunk).await {
+                Ok(ReceiveFileTransferChunkResponse::Ok) => {}
+                Ok(err) => return Err(TransferFileResponse::Failed(format!("{err:?}"))),
+                Err(err) => return Err(TransferFileResponse::Failed(err.to_string())),
+            }
+        }
+
+        // the chunks sent must match the file, which may have been re-encrypted in the meantime
+        if FileDataStorage::get_file(&file_id).as_ref() != Some(file) {
+            // the staged chunks are replaced if the transfer is started again anyway
+            let _ = client.abort_file_transfer(file_id).await;
+            return Err(TransferFileResponse::FileChanged);
+        }
+
+        // recorded before completing the transfer, since the recipient may accept it right away
+        OutgoingTransferStorage::set(
+            &file_id,
+            OutgoingTransfer {
+                recipient: request.recipient,
+                user_canister: client.principal(),
+                sent_at: time(),
+                file: file.clone(),
+            },
+        );
+        let result = match client.complete_file_transfer(file_id).await {
+            Ok(CompleteFileTransferResponse::Ok) => Ok(()),
+            Ok(CompleteFileTransferResponse::FileAlreadyExists) => {
+                Err(TransferFileResponse::FileAlreadyExists)
+            }
+            Ok(err) => Err(TransferFileResponse::Failed(format!("{err:?}"))),
+            Err(err) => Err(TransferFileResponse::Failed(err.to_string())),
+        };
+        if result.is_err() {
+            OutgoingTransferStorage::remove(&file_id);
+        }
+
+        result
+    }
+
+    /// Get the user canister of the recipient from the orchestrator.
+    async fn recipient_canister(recipient: Principal) -> Result<Principal, TransferFileResponse> {
+        match OrchestratorClient::from(Config::get_orchestrator())
+            .get_user_canister(recipient)
+            .await
+        {
+            Ok(GetUserCanisterResponse::Ok(user_canister)) => Ok(user_canister),
+            Ok(GetUserCanisterResponse::NoSuchUser) => Err(TransferFileResponse::NoSuchUser),
+            Ok(err) => Err(TransferFileResponse::Failed(format!("{err:?}"))),
+            Err(err) => Err(TransferFileResponse::Failed(err.to_string())),
+        }
+    }
+
+    /// Check that `sender` is the user canister of `owner`.
+    async fn check_sender(
+        sender: Principal,
+        owner: Principal,
+    ) -> Result<(), ReceiveFileTransferResponse> {
+        if !cfg!(target_family = "wasm") {
+            return Ok(());
+        }
+
+        match OrchestratorClient::from(Config::get_orchestrator())
+            .get_user_canister(owner)
+            .await
+        {
+            Ok(GetUserCanisterResponse::Ok(user_canister)) if user_canister == sender => Ok(()),
+            Ok(_) => Err(ReceiveFileTransferResponse::Unauthorized),
+            Err(err) => Err(ReceiveFileTransferResponse::FailedToCheckSender(
+                err.to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::user_canister::{AuditLogFilter, OwnerKey};
+
+    use super::*;
+    use crate::canister::test::{init, upload_test_file};
+    use crate::storage::audit_log::AuditLogStorage;
+    use crate::storage::files::FileSharesStorage;
+
+    #[tokio::test]
+    async fn test_should_receive_file_transfer() {
+        let owner = init();
+        let sender = Principal::from_slice(&[9; 29]);
+        let previous_owner = Principal::from_slice(&[8; 29]);
+        let alice = Principal::from_slice(&[1; 29]);
+        upload_test_file(owner, "/existing.txt").await;
+
+        let request = ReceiveFileTransferRequest {
+            owner: previous_owner,
+            file_id: 7,
+            path: Path::new("/transferred.txt").unwrap(),
+            file_type: "text/plain".to_string(),
+            num_chunks: 2,
+            owner_key: OwnerKey::from([1; OwnerKey::KEY_SIZE]),
+            shared_keys: vec![
+                (alice, OwnerKey::from([2; OwnerKey::KEY_SIZE])),
+                (owner, OwnerKey::from([3; OwnerKey::KEY_SIZE])),
+            ],
+        };
+        assert_eq!(
+            CanisterFileTransfer::receive(
+                sender,
+                ReceiveFileTransferRequest {
+                    path: Path::new("/existing.txt").unwrap(),
+                    ..request.clone()
+                }
+            )
+            .await,
+            ReceiveFileTransferResponse::FileAlreadyExists
+        );
+        assert_eq!(
+            CanisterFileTransfer::receive(sender, request).await,
+            ReceiveFileTransferResponse::Ok
+        );
+
+        let chunk = |chunk_id| UploadFileContinueRequest {
+            file_id: 7,
+            chunk_id,
+            contents: vec![chunk_id as u8],
+        };
+        assert_eq!(
+            CanisterFileTransfer::receive_chunk(sender, chunk(2)),
+            ReceiveFileTransferChunkResponse::ChunkOutOfBounds
+        );
+        // only the sender can send the chunks
+        assert_eq!(
+            CanisterFileTransfer::receive_chunk(alice, chunk(0)),
+            ReceiveFileTransferChunkResponse::NoTransferInProgress
+        );
+        assert_eq!(
+            CanisterFileTransfer::receive_chunk(sender, chunk(0)),
+            ReceiveFileTransferChunkResponse::Ok
+        );
+        assert_eq!(
+            CanisterFileTransfer::complete(sender, 7),
+            CompleteFileTransferResponse::MissingChunks(1)
+        );
+        assert_eq!(
+            CanisterFileTransfer::receive_chunk(
+                sender,
+                UploadFileContinueRequest {
+                    contents: vec![0; MAX_TRANSFER_CHUNK_SIZE + 1],
+                    ..chunk(1)
+                }
+            ),
+            ReceiveFileTransferChunkResponse::ChunkTooLarge
+        );
+        assert_eq!(
+            CanisterFileTransfer::receive_chunk(sender, chunk(1)),
+            ReceiveFileTransferChunkResponse::Ok
+        );
+        assert_eq!(
+            CanisterFileTransfer::accept(sender, 7).await,
+            AcceptFileTransferResponse::NoTransferInProgress
+        );
+        assert_eq!(
+            CanisterFileTransfer::complete(sender, 7),
+            CompleteFileTransferResponse::Ok
+        );
+        // the chunks can't be changed once the transfer awaits the acceptance of the owner
+        assert_eq!(
+            CanisterFileTransfer::receive_chunk(sender, chunk(1)),
+            ReceiveFileTransferChunkResponse::NoTransferInProgress
+        );
+        let incoming = CanisterFileTransfer::incoming();
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].sender, sender);
+        assert_eq!(incoming[0].file_id, 7);
+        assert_eq!(incoming[0].owner, previous_owner);
+        assert_eq!(incoming[0].num_chunks, 2);
+
+        let AcceptFileTransferResponse::Ok(file_id) = CanisterFileTransfer::accept(sender, 7).await
+        else {
+            panic!("transfer not accepted");
+        };
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        assert_eq!(file.metadata.requester_principal, owner);
+        assert_eq!(
+            file.content.owner_key(),
+            Some(&OwnerKey::from([1; OwnerKey::KEY_SIZE]))
+        );
+        assert_eq!(
+            FileContentsStorage::get_file_contents(&file_id, &1),
+            Some(vec![1])
+        );
+        assert!(OwnedFilesStorage::get_owned_files().contains(&file_id));
+        assert_eq!(
+            PathStorage::read_link(&file_id),
+            Some(Path::new("/transferred.txt").unwrap())
+        );
+        assert!(IncomingTransferStorage::get(sender, &7).is_none());
+        assert!(
+            AuditLogStorage::get_entries(&AuditLogFilter::default(), 0, 128)
+                .0
+                .iter()
+                .any(|entry| entry.file_id == file_id
+                    && entry.action
+                        == AuditAction::ReceiveFile {
+                            user: previous_owner
+                        })
+        );
+        // the file is shared again with alice, but not with the new owner
+        assert_eq!(
+            FileSharesStorage::get_users_with_file_shares(&file_id),
+            vec![alice]
+        );
+
+        assert_eq!(
+            CanisterFileTransfer::accept(sender, 7).await,
+            AcceptFileTransferResponse::NoTransferInProgress
+        );
+        assert!(CanisterFileTransfer::incoming().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_should_limit_incoming_transfers() {
+        init();
+        let sender = Principal::from_slice(&[9; 29]);
+        let request = |file_id| ReceiveFileTransferRequest {
+            owner: Principal::from_slice(&[8; 29]),
+            file_id,
+            path: Path::new(format!("/transferred-{file_id}.txt")).unwrap(),
+            file_type: "text/plain".to_string(),
+            num_chunks: 1,
+            owner_key: OwnerKey::from([1; OwnerKey::KEY_SIZE]),
+            shared_keys: vec![],
+        };
+
+        assert_eq!(
+            CanisterFileTransfer::receive(
+                sender,
+                ReceiveFileTransferRequest {
+                    num_chunks: MAX_TRANSFER_NUM_CHUNKS + 1,
+                    ..request(0)
+                }
+            )
+            .await,
+            ReceiveFileTransferResponse::InvalidNumChunks
+        );
+        for file_id in 0..MAX_INCOMING_TRANSFERS_PER_SENDER as u64 {
+            assert_eq!(
+                CanisterFileTransfer::receive(sender, request(file_id)).await,
+                ReceiveFileTransferResponse::Ok
+            );
+        }
+        assert_eq!(
+            CanisterFileTransfer::receive(sender, request(100)).await,
+            ReceiveFileTransferResponse::TooManyTransfers
+        );
+        // starting a staged transfer again is allowed
+        assert_eq!(
+            CanisterFileTransfer::receive(sender, request(0)).await,
+            ReceiveFileTransferResponse::Ok
+        );
+        // other senders are not affected
+        assert_eq!(
+            CanisterFileTransfer::receive(Principal::from_slice(&[7; 29]), request(100)).await,
+            ReceiveFileTransferResponse::Ok
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_expire_and_decline_transfers() {
+        init();
+        let sender = Principal::from_slice(&[9; 29]);
+        let file =
+            FileDataStorage::get_file(&upload_test_file(Config::get_owner(), "/a.txt").await)
+                .unwrap();
+        let transfer = |path: &str, started_at| IncomingTransfer {
+            owner: Principal::from_slice(&[8; 29]),
+            started_at,
+            ready: true,
+            path: Path::new(path).unwrap(),
+            content: FileContent::PartiallyUploaded {
+                num_chunks: 1,
+                uploaded_chunks: UploadedChunks::default(),
+                file_type: "text/plain".to_string(),
+                owner_key: OwnerKey::from([1; OwnerKey::KEY_SIZE]),
+                shared_keys: BTreeMap::new(),
+            },
+        };
+        let expired = time() - TRANSFER_EXPIRY - 1;
+        IncomingTransferStorage::set(sender, &1, transfer("/expired.txt", expired));
+        IncomingTransferStorage::set(sender, &2, transfer("/declined.txt", time()));
+        IncomingTransferStorage::set(sender, &3, transfer("/pending.txt", time()));
+        for (file_id, sent_at) in [(1, expired), (2, time())] {
+            OutgoingTransferStorage::set(
+                &file_id,
+                OutgoingTransfer {
+                    recipient: Principal::from_slice(&[8; 29]),
+                    user_canister: sender,
+                    sent_at,
+                    file: file.clone(),
+                },
+            );
+        }
+
+        CanisterFileTransfer::expire();
+        CanisterFileTransfer::decline(sender, 2);
+        assert!(IncomingTransferStorage::get(sender, &1).is_none());
+        assert!(IncomingTransferStorage::get(sender, &2).is_none());
+        assert!(IncomingTransferStorage::get(sender, &3).is_some());
+        assert!(OutgoingTransferStorage::get(&1).is_none());
+        assert!(OutgoingTransferStorage::get(&2).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_should_release_transferred_file() {
+        let owner = init();
+        let recipient = Principal::from_slice(&[8; 29]);
+        let recipient_canister = Principal::from_slice(&[9; 29]);
+        let file_id = upload_test_file(owner, "/transfer.txt").await;
+        let file = FileDataStorage::get_file(&file_id).unwrap();
+        let outgoing = OutgoingTransfer {
+            recipient,
+            user_canister: recipient_canister,
+            sent_at: time(),
+            file: file.clone(),
+        };
+
+        // only the user canister of the recipient can release the file
+        OutgoingTransferStorage::set(&file_id, outgoing.clone());
+        assert_eq!(
+            CanisterFileTransfer::release(recipient, file_id).await,
+            ReleaseFileTransferResponse::NoTransferInProgress
+        );
+        assert!(FileDataStorage::get_file(&file_id).is_some());
+
+        // the file must be unchanged since it was sent
+        OutgoingTransferStorage::set(
+            &file_id,
+            OutgoingTransfer {
+                file: File {
+                    content: FileContent::Uploaded {
+                        num_chunks: 2,
+                        file_type: "text/plain".to_string(),
+                        owner_key: OwnerKey::from([1; OwnerKey::KEY_SIZE]),
+                        shared_keys: BTreeMap::new(),
+                    },
+                    ..file
+                },
+                ..outgoing.clone()
+            },
+        );
+        assert_eq!(
+            CanisterFileTransfer::release(recipient_canister, file_id).await,
+            ReleaseFileTransferResponse::FileChanged
+        );
+        assert!(OutgoingTransferStorage::get(&file_id).is_none());
+        assert!(FileDataStorage::get_file(&file_id).is_some());
+
+        OutgoingTransferStorage::set(&file_id, outgoing);
+        assert_eq!(
+            CanisterFileTransfer::release(recipient_canister, file_id).await,
+            ReleaseFileTransferResponse::Ok
+        );
+        assert!(FileDataStorage::get_file(&file_id).is_none());
+        assert!(OutgoingTransferStorage::get(&file_id).is_none());
+        assert_eq!(
+            AuditLogStorage::get_entries(&AuditLogFilter::default(), 0, 128)
+                .0
+                .last()
+                .unwrap()
+                .action,
+            AuditAction::TransferFile { user: recipient }
+        );
+    }
+}
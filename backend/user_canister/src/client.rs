@@ -1,3 +1,5 @@
 mod orchestrator;
+mod user_canister;
 
 pub use self::orchestrator::OrchestratorClient;
+pub use self::user_canister::UserCanisterClient;
@@ -1,5 +1,8 @@
 use candid::Principal;
-use did::orchestrator::{FileId, RevokeShareFileResponse, ShareFileMetadata, ShareFileResponse};
+use did::orchestrator::{
+    FileCursorPagination, FileId, GetUserCanisterResponse, PublicKey, RevokeShareFileResponse,
+    ShareFileMetadata, ShareFileResponse, UpdateUserPublicKeyResponse, UserCanisterSharesResponse,
+};
 use ic_cdk::call::{Call, CallResult, Error as CallError};
 
 /// Orchestrator canister client.
@@ -18,20 +21,26 @@ impl OrchestratorClient {
         Self { principal }
     }
 
-    /// Revoke share file from user.
-    ///
-    /// If successful, returns [`RevokeShareFileResponse`], which means that the call was successful, but it's not
-    /// guaranteed that the operation was successful and so it should be checked.
-    pub async fn revoke_share_file(
+    /// Get the user canister of a user.
+    pub async fn get_user_canister(&self, user: Principal) -> CallResult<GetUserCanisterResponse> {
+        Call::unbounded_wait(self.principal, "get_user_canister")
+            .with_arg(user)
+            .await
+            .map_err(CallError::from)?
+            .candid::<GetUserCanisterResponse>()
+            .map_err(CallError::CandidDecodeFailed)
+    }
+
+    /// Get a page of the shares held by the orchestrator for this canister.
+    pub async fn get_user_canister_shares(
         &self,
-        user: Principal,
-        file_id: FileId,
-    ) -> CallResult<RevokeShareFileResponse> {
-        Call::unbounded_wait(self.principal, "revoke_share_file")
-            .with_args(&(user, file_id))
+        pagination: FileCursorPagination,
+    ) -> CallResult<UserCanisterSharesResponse> {
+        Call::unbounded_wait(self.principal, "get_user_canister_shares")
+            .with_arg(pagination)
             .await
             .map_err(CallError::from)?
-            .candid::<RevokeShareFileResponse>()
+            .candid::<UserCanisterSharesResponse>()
             .map_err(CallError::CandidDecodeFailed)
     }
 
@@ -52,18 +61,20 @@ impl OrchestratorClient {
             .map_err(CallError::CandidDecodeFailed)
     }
 
-    /// Share file with user.
-    pub async fn share_file(
+    /// Revoke the shares of multiple files for a user.
+    ///
+    /// If successful, returns [`RevokeShareFileResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn revoke_user_shares(
         &self,
         user: Principal,
-        file_id: FileId,
-        metadata: ShareFileMetadata,
-    ) -> CallResult<ShareFileResponse> {
-        Call::unbounded_wait(self.principal, "share_file")
-            .with_args(&(user, file_id, metadata))
+        file_ids: &[FileId],
+    ) -> CallResult<RevokeShareFileResponse> {
+        Call::unbounded_wait(self.principal, "revoke_user_shares")
+            .with_args(&(user, file_ids))
             .await
             .map_err(CallError::from)?
-            .candid::<ShareFileResponse>()
+            .candid::<RevokeShareFileResponse>()
             .map_err(CallError::CandidDecodeFailed)
     }
 
@@ -81,4 +92,21 @@ impl OrchestratorClient {
             .candid::<ShareFileResponse>()
             .map_err(CallError::CandidDecodeFailed)
     }
+
+    /// Update the public key of the owner on the orchestrator.
+    ///
+    /// If successful, returns [`UpdateUserPublicKeyResponse`], which means that the call was successful, but it's not
+    /// guaranteed that the operation was successful and so it should be checked.
+    pub async fn update_user_public_key(
+        &self,
+        user: Principal,
+        public_key: PublicKey,
+    ) -> CallResult<UpdateUserPublicKeyResponse> {
+        Call::unbounded_wait(self.principal, "update_user_public_key")
+            .with_args(&(user, public_key))
+            .await
+            .map_err(CallError::from)?
+            .candid::<UpdateUserPublicKeyResponse>()
+            .map_err(CallError::CandidDecodeFailed)
+    }
 }
@@ -0,0 +1,87 @@
+use candid::Principal;
+use did::FileId;
+use did::user_canister::{
+    CompleteFileTransferResponse, ReceiveFileTransferChunkResponse, ReceiveFileTransferRequest,
+    ReceiveFileTransferResponse, ReleaseFileTransferResponse, UploadFileContinueRequest,
+};
+use ic_cdk::call::{Call, CallResult, Error as CallError};
+
+/// Client for the user canister of another user.
+pub struct UserCanisterClient {
+    principal: Principal,
+}
+
+impl From<Principal> for UserCanisterClient {
+    fn from(principal: Principal) -> Self {
+        Self { principal }
+    }
+}
+
+impl UserCanisterClient {
+    /// Get the principal of the user canister.
+    pub fn principal(&self) -> Principal {
+        self.principal
+    }
+
+    /// Start the transfer of a file to the user canister.
+    pub async fn receive_file_transfer(
+        &self,
+        request: &ReceiveFileTransferRequest,
+    ) -> CallResult<ReceiveFileTransferResponse> {
+        Call::unbounded_wait(self.principal, "receive_file_transfer")
+            .with_arg(request)
+            .await
+            .map_err(CallError::from)?
+            .candid::<ReceiveFileTransferResponse>()
+            .map_err(CallError::CandidDecodeFailed)
+    }
+
+    /// Send a chunk of a file being transferred to the user canister.
+    pub async fn receive_file_transfer_chunk(
+        &self,
+        request: &UploadFileContinueRequest,
+    ) -> CallResult<ReceiveFileTransferChunkResponse> {
+        Call::unbounded_wait(self.principal, "receive_file_transfer_chunk")
+            .with_arg(request)
+            .await
+            .map_err(CallError::from)?
+            .candid::<ReceiveFileTransferChunkResponse>()
+            .map_err(CallError::CandidDecodeFailed)
+    }
+
+    /// Complete the transfer of a file, once all its chunks were sent.
+    pub async fn complete_file_transfer(
+        &self,
+        file_id: FileId,
+    ) -> CallResult<CompleteFileTransferResponse> {
+        Call::unbounded_wait(self.principal, "complete_file_transfer")
+            .with_arg(file_id)
+            .await
+            .map_err(CallError::from)?
+            .candid::<CompleteFileTransferResponse>()
+            .map_err(CallError::CandidDecodeFailed)
+    }
+
+    /// Drop the transfer of a file, if any.
+    pub async fn abort_file_transfer(&self, file_id: FileId) -> CallResult<()> {
+        Call::unbounded_wait(self.principal, "abort_file_transfer")
+            .with_arg(file_id)
+            .await
+            .map_err(CallError::from)?
+            .candid::<()>()
+            .map_err(CallError::CandidDecodeFailed)
+    }
+
+    /// Ask the user canister to release a file whose transfer was accepted.
+    pub async fn release_file_transfer(
+        &self,
+        file_id: FileId,
+    ) -> CallResult<ReleaseFileTransferResponse> {
+        Call::unbounded_wait(self.principal, "release_file_transfer")
+            .with_arg(file_id)
+            .await
+            .map_err(CallError::from)?
+            .candid::<ReleaseFileTransferResponse>()
+            .map_err(CallError::CandidDecodeFailed)
+    }
+}
@@ -15,12 +15,55 @@ pub fn inspect() {
         | "share_file"
         | "share_file_with_users"
         | "revoke_file_sharing"
+        | "revoke_share"
+        | "revoke_share_for_users"
+        | "revoke_all_for_user"
         | "get_allowed_users"
-        | "get_shared_files" => {
-            if msg_caller() != Config::get_owner() {
+        | "get_audit_log"
+        | "get_file_access_log"
+        | "get_outbox"
+        | "get_share_reconciliation_report"
+        | "reconcile_shares"
+        | "get_shared_files"
+        | "reencrypt_file"
+        | "reencrypt_file_continue"
+        | "abort_file_reencryption"
+        | "start_key_rotation"
+        | "key_rotation_status"
+        | "get_key_rotation_files"
+        | "submit_key_rotation_batch"
+        | "complete_key_rotation"
+        | "transfer_file"
+        | "get_incoming_file_transfers"
+        | "accept_file_transfer"
+        | "decline_file_transfer"
+        | "set_device_keys"
+        | "set_public_key" => {
+            if !Config::is_owner(msg_caller()) {
                 trap("Only the owner can call this method");
             }
         }
+        // called by the user canister of the owner or of the recipient of a file being transferred
+        "receive_file_transfer"
+        | "receive_file_transfer_chunk"
+        | "complete_file_transfer"
+        | "abort_file_transfer"
+        | "release_file_transfer" => {
+            trap("Only user canisters can call this method");
+        }
+        // called by the orchestrator
+        "sync_public_key"
+        | "link_principal"
+        | "unlink_principal"
+        | "link_recipient_principal"
+        | "unlink_recipient_principal"
+        | "add_device_key"
+        | "remove_device_key"
+        | "remove_recipient_device_key"
+        | "revoke_deleted_user"
+        | "set_suspended" => {
+            trap("Only the orchestrator can call this method");
+        }
         _ => {}
     }
 
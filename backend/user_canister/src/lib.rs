@@ -7,14 +7,25 @@ mod utils;
 
 use candid::Principal;
 use did::FileId;
-use did::orchestrator::PublicKey;
+use did::http::{HttpRequest, HttpResponse};
+use did::orchestrator::{Pagination, PublicKey};
 use did::user_canister::{
-    AliasInfo, DeleteFileResponse, FileDownloadResponse, FileSharingResponse, GetAliasInfoError,
-    OwnerKey, Path, PublicFileMetadata, RequestFileResponse, UploadFileAtomicRequest,
+    AcceptFileTransferResponse, AliasInfo, AuditLog, AuditLogFilter, CompleteFileTransferResponse,
+    CompleteKeyRotationResponse, ConfirmDownloadResponse, DeleteFileResponse, DeviceId, DeviceKey,
+    DeviceOwnerKey, FileDownloadResponse, FileSharingResponse, GetAliasInfoError,
+    GetFileAccessLogResponse, IncomingFileTransfer, KeyRotationStatus, LinkPrincipalResponse,
+    OutboxEntryWithId, OwnerKey, Path, PublicFileMetadata, ReceiveFileTransferChunkResponse,
+    ReceiveFileTransferRequest, ReceiveFileTransferResponse, ReconcileSharesResponse,
+    ReencryptFileContinueResponse, ReencryptFileRequest, ReencryptFileResponse,
+    ReleaseFileTransferResponse, RequestFileResponse, RevokeAllForUserReport,
+    RevokeShareForUsersResponse, RevokeShareResponse, SetDeviceKeysResponse, SetPublicKeyResponse,
+    SetSuspendedResponse, ShareFileWithUsersResponse, ShareMode, ShareReconciliationReport,
+    StartKeyRotationResponse, SubmitKeyRotationBatchResponse, SyncDeviceKeyResponse,
+    SyncPublicKeyResponse, TransferFileRequest, TransferFileResponse, UploadFileAtomicRequest,
     UploadFileAtomicResponse, UploadFileContinueRequest, UploadFileContinueResponse,
-    UploadFileError, UploadFileRequest, UserCanisterInstallArgs,
+    UploadFileError, UploadFileRequest, UserCanisterInstallArgs, WrappedFileKey,
 };
-use ic_cdk_macros::{init, query, update};
+use ic_cdk_macros::{init, post_upgrade, query, update};
 use storage::config::Config;
 use utils::msg_caller;
 
@@ -25,14 +36,74 @@ pub fn init(args: UserCanisterInstallArgs) {
     Canister::init(args);
 }
 
+#[post_upgrade]
+pub fn post_upgrade(args: UserCanisterInstallArgs) {
+    Canister::post_upgrade(args);
+}
+
 #[query]
 fn public_key() -> PublicKey {
     Config::get_owner_public_key()
 }
 
+#[query]
+fn public_key_version() -> u64 {
+    Config::get_owner_public_key_version()
+}
+
+#[update]
+async fn set_public_key(public_key: PublicKey) -> SetPublicKeyResponse {
+    Canister::set_public_key(msg_caller(), public_key).await
+}
+
+#[update]
+fn sync_public_key(public_key: PublicKey, version: u64) -> SyncPublicKeyResponse {
+    Canister::sync_public_key(msg_caller(), public_key, version)
+}
+
+#[query]
+fn device_keys() -> Vec<DeviceKey> {
+    Config::get_owner_device_keys()
+}
+
+#[update]
+fn add_device_key(device_key: DeviceKey) -> SyncDeviceKeyResponse {
+    Canister::add_device_key(msg_caller(), device_key)
+}
+
+#[update]
+fn remove_device_key(device_id: DeviceId) -> SyncDeviceKeyResponse {
+    Canister::remove_device_key(msg_caller(), device_id)
+}
+
+#[update]
+fn remove_recipient_device_key(user: Principal, device_id: DeviceId) -> SyncDeviceKeyResponse {
+    Canister::remove_recipient_device_key(msg_caller(), user, device_id)
+}
+
+#[update]
+fn set_suspended(suspended: bool) -> SetSuspendedResponse {
+    Canister::set_suspended(msg_caller(), suspended)
+}
+
+#[update]
+fn link_principal(principal: Principal) -> LinkPrincipalResponse {
+    Canister::link_principal(msg_caller(), principal)
+}
+
+#[update]
+fn unlink_principal(principal: Principal) -> LinkPrincipalResponse {
+    Canister::unlink_principal(msg_caller(), principal)
+}
+
 #[update]
-fn set_public_key(public_key: PublicKey) {
-    Config::set_owner_public_key(msg_caller(), public_key);
+fn link_recipient_principal(account: Principal, principal: Principal) -> LinkPrincipalResponse {
+    Canister::link_recipient_principal(msg_caller(), account, principal)
+}
+
+#[update]
+fn unlink_recipient_principal(principal: Principal) -> LinkPrincipalResponse {
+    Canister::unlink_recipient_principal(msg_caller(), principal)
 }
 
 #[update]
@@ -40,11 +111,66 @@ async fn delete_file(file_id: FileId) -> DeleteFileResponse {
     Canister::delete_file(msg_caller(), file_id).await
 }
 
+#[update]
+async fn complete_key_rotation() -> CompleteKeyRotationResponse {
+    Canister::complete_key_rotation(msg_caller()).await
+}
+
+#[update]
+fn confirm_download(file_id: FileId) -> ConfirmDownloadResponse {
+    Canister::confirm_download(msg_caller(), file_id)
+}
+
+#[update]
+fn decline_share(user_id: Principal, file_id: FileId) -> FileSharingResponse {
+    Canister::decline_share(msg_caller(), user_id, file_id)
+}
+
+#[query]
+fn get_audit_log(filter: AuditLogFilter, pagination: Pagination) -> AuditLog {
+    Canister::get_audit_log(msg_caller(), filter, pagination)
+}
+
+#[query]
+fn get_outbox() -> Vec<OutboxEntryWithId> {
+    Canister::get_outbox(msg_caller())
+}
+
+#[query]
+fn get_share_reconciliation_report() -> Option<ShareReconciliationReport> {
+    Canister::get_share_reconciliation_report(msg_caller())
+}
+
+#[update]
+async fn reconcile_shares() -> ReconcileSharesResponse {
+    Canister::reconcile_shares(msg_caller()).await
+}
+
+#[query]
+fn get_file_access_log(file_id: FileId, pagination: Pagination) -> GetFileAccessLogResponse {
+    Canister::get_file_access_log(msg_caller(), file_id, pagination)
+}
+
+#[query]
+fn get_key_rotation_files(limit: u64) -> Vec<WrappedFileKey> {
+    Canister::get_key_rotation_files(msg_caller(), limit)
+}
+
 #[query]
 fn get_requests() -> Vec<PublicFileMetadata> {
     Canister::get_requests(msg_caller())
 }
 
+#[query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    Canister::http_request(request)
+}
+
+#[query]
+fn key_rotation_status() -> Option<KeyRotationStatus> {
+    Canister::key_rotation_status(msg_caller())
+}
+
 #[query]
 fn get_shared_files(user_id: Principal) -> Vec<PublicFileMetadata> {
     Canister::get_shared_files(msg_caller(), user_id)
@@ -58,11 +184,13 @@ fn get_alias_info(alias: String) -> Result<AliasInfo, GetAliasInfoError> {
 #[update]
 fn upload_file(request: UploadFileRequest) -> Result<(), UploadFileError> {
     Canister::upload_file(
+        msg_caller(),
         request.file_id,
         request.file_content,
         request.file_type,
         request.owner_key,
         request.num_chunks,
+        request.device_keys.unwrap_or_default(),
     )
 }
 
@@ -82,8 +210,12 @@ async fn request_file(path: Path) -> RequestFileResponse {
 }
 
 #[query]
-fn download_file(file_id: FileId, chunk_id: u64) -> FileDownloadResponse {
-    Canister::download_file(msg_caller(), file_id, chunk_id)
+fn download_file(
+    file_id: FileId,
+    chunk_id: u64,
+    device_id: Option<DeviceId>,
+) -> FileDownloadResponse {
+    Canister::download_file(msg_caller(), file_id, chunk_id, device_id)
 }
 
 #[update]
@@ -91,8 +223,16 @@ async fn share_file(
     user_id: Principal,
     file_id: FileId,
     file_key_encrypted_for_user: OwnerKey,
+    device_keys: Option<Vec<DeviceOwnerKey>>,
 ) -> FileSharingResponse {
-    Canister::share_file(msg_caller(), user_id, file_id, file_key_encrypted_for_user).await
+    Canister::share_file(
+        msg_caller(),
+        user_id,
+        file_id,
+        file_key_encrypted_for_user,
+        device_keys.unwrap_or_default(),
+    )
+    .await
 }
 
 #[update]
@@ -100,14 +240,122 @@ async fn share_file_with_users(
     user_id: Vec<Principal>,
     file_id: FileId,
     file_key_encrypted_for_user: Vec<OwnerKey>,
-) {
-    Canister::share_file_with_users(msg_caller(), user_id, file_id, file_key_encrypted_for_user)
-        .await
+    mode: ShareMode,
+    device_keys: Option<Vec<Vec<DeviceOwnerKey>>>,
+) -> ShareFileWithUsersResponse {
+    Canister::share_file_with_users(
+        msg_caller(),
+        user_id,
+        file_id,
+        file_key_encrypted_for_user,
+        mode,
+        device_keys.unwrap_or_default(),
+    )
+    .await
+}
+
+#[update]
+fn set_device_keys(
+    file_id: FileId,
+    user_id: Principal,
+    device_keys: Vec<DeviceOwnerKey>,
+) -> SetDeviceKeysResponse {
+    Canister::set_device_keys(msg_caller(), file_id, user_id, device_keys)
 }
 
 #[update]
-async fn revoke_share(user_id: Principal, file_id: FileId) {
+fn revoke_deleted_user(user_id: Principal) -> FileSharingResponse {
+    Canister::revoke_deleted_user(msg_caller(), user_id)
+}
+
+#[update]
+async fn revoke_share(user_id: Principal, file_id: FileId) -> RevokeShareResponse {
     Canister::revoke_file_sharing(msg_caller(), user_id, file_id).await
 }
 
+#[update]
+async fn revoke_share_for_users(
+    user_ids: Vec<Principal>,
+    file_id: FileId,
+) -> RevokeShareForUsersResponse {
+    Canister::revoke_share_for_users(msg_caller(), user_ids, file_id).await
+}
+
+#[update]
+async fn revoke_all_for_user(user_id: Principal) -> RevokeAllForUserReport {
+    Canister::revoke_all_for_user(msg_caller(), user_id).await
+}
+
+#[update]
+async fn reencrypt_file(request: ReencryptFileRequest) -> ReencryptFileResponse {
+    Canister::reencrypt_file(msg_caller(), request).await
+}
+
+#[update]
+fn reencrypt_file_continue(request: UploadFileContinueRequest) -> ReencryptFileContinueResponse {
+    Canister::reencrypt_file_continue(msg_caller(), request)
+}
+
+#[update]
+fn abort_file_reencryption(file_id: FileId) {
+    Canister::abort_file_reencryption(msg_caller(), file_id)
+}
+
+#[update]
+async fn transfer_file(request: TransferFileRequest) -> TransferFileResponse {
+    Canister::transfer_file(msg_caller(), request).await
+}
+
+#[update]
+async fn receive_file_transfer(request: ReceiveFileTransferRequest) -> ReceiveFileTransferResponse {
+    Canister::receive_file_transfer(msg_caller(), request).await
+}
+
+#[update]
+fn receive_file_transfer_chunk(
+    request: UploadFileContinueRequest,
+) -> ReceiveFileTransferChunkResponse {
+    Canister::receive_file_transfer_chunk(msg_caller(), request)
+}
+
+#[update]
+fn complete_file_transfer(file_id: FileId) -> CompleteFileTransferResponse {
+    Canister::complete_file_transfer(msg_caller(), file_id)
+}
+
+#[update]
+fn abort_file_transfer(file_id: FileId) {
+    Canister::abort_file_transfer(msg_caller(), file_id)
+}
+
+#[update]
+async fn release_file_transfer(file_id: FileId) -> ReleaseFileTransferResponse {
+    Canister::release_file_transfer(msg_caller(), file_id).await
+}
+
+#[query]
+fn get_incoming_file_transfers() -> Vec<IncomingFileTransfer> {
+    Canister::get_incoming_file_transfers(msg_caller())
+}
+
+#[update]
+async fn accept_file_transfer(sender: Principal, file_id: FileId) -> AcceptFileTransferResponse {
+    Canister::accept_file_transfer(msg_caller(), sender, file_id).await
+}
+
+#[update]
+fn decline_file_transfer(sender: Principal, file_id: FileId) {
+    Canister::decline_file_transfer(msg_caller(), sender, file_id)
+}
+
+#[update]
+fn start_key_rotation(new_public_key: PublicKey) -> StartKeyRotationResponse {
+    Canister::start_key_rotation(msg_caller(), new_public_key)
+}
+
+#[update]
+fn submit_key_rotation_batch(keys: Vec<WrappedFileKey>) -> SubmitKeyRotationBatchResponse {
+    Canister::submit_key_rotation_batch(msg_caller(), keys)
+}
+
 ic_cdk::export_candid!();
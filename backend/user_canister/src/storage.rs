@@ -1,4 +1,9 @@
+pub mod audit_log;
 pub mod config;
 pub mod files;
+pub mod key_rotation;
+pub mod outbox;
+pub mod reencryption;
+pub mod transfer;
 
 mod memory;
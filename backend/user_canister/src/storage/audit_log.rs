@@ -0,0 +1,116 @@
+use std::cell::RefCell;
+
+use did::user_canister::{AuditLogEntry, AuditLogFilter};
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+use super::memory::{AUDIT_LOG_MEMORY_ID, MEMORY_MANAGER};
+
+thread_local! {
+    /// Audit log. Append-only map between the sequence number of an entry and the entry.
+    static AUDIT_LOG: RefCell<StableBTreeMap<u64, AuditLogEntry, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(AUDIT_LOG_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the audit log of the canister.
+///
+/// Entries can only be appended; they are never updated nor removed.
+pub struct AuditLogStorage;
+
+impl AuditLogStorage {
+    /// Append an entry to the audit log.
+    pub fn append(entry: AuditLogEntry) {
+        AUDIT_LOG.with_borrow_mut(|audit_log| {
+            let next_id = audit_log
+                .last_key_value()
+                .map(|(id, _)| id + 1)
+                .unwrap_or_default();
+
+            audit_log.insert(next_id, entry);
+        });
+    }
+
+    /// Get up to `limit` entries matching the `filter`, skipping the first `offset` matches.
+    ///
+    /// Returns the entries along with the total number of entries matching the `filter`, which
+    /// are both computed in a single pass over the log.
+    pub fn get_entries(
+        filter: &AuditLogFilter,
+        offset: u64,
+        limit: u64,
+    ) -> (Vec<AuditLogEntry>, u64) {
+        AUDIT_LOG.with_borrow(|audit_log| {
+            let mut entries = vec![];
+            let mut total = 0;
+            for (_, entry) in audit_log.iter() {
+                if !filter.matches(&entry) {
+                    continue;
+                }
+                if total >= offset && total < offset.saturating_add(limit) {
+                    entries.push(entry);
+                }
+                total += 1;
+            }
+
+            (entries, total)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use candid::Principal;
+    use did::user_canister::{AuditAction, Path};
+
+    use super::*;
+
+    #[test]
+    fn test_should_append_and_get_entries() {
+        for file_id in 0..5 {
+            AuditLogStorage::append(entry(file_id));
+        }
+
+        let filter = AuditLogFilter::default();
+        assert_eq!(
+            AuditLogStorage::get_entries(&filter, 0, 10),
+            ((0..5).map(entry).collect::<Vec<_>>(), 5)
+        );
+        assert_eq!(
+            AuditLogStorage::get_entries(&filter, 3, 10),
+            (vec![entry(3), entry(4)], 5)
+        );
+        assert_eq!(
+            AuditLogStorage::get_entries(&filter, 1, 1),
+            (vec![entry(1)], 5)
+        );
+    }
+
+    #[test]
+    fn test_should_get_filtered_entries() {
+        for file_id in 0..5 {
+            AuditLogStorage::append(entry(file_id));
+        }
+
+        let filter = AuditLogFilter {
+            file_id: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            AuditLogStorage::get_entries(&filter, 0, 10),
+            (vec![entry(2)], 1)
+        );
+        assert_eq!(AuditLogStorage::get_entries(&filter, 1, 10), (vec![], 1));
+    }
+
+    fn entry(file_id: u64) -> AuditLogEntry {
+        AuditLogEntry {
+            actor: Principal::from_slice(&[1; 29]),
+            action: AuditAction::RequestFile,
+            file_id,
+            path: Path::new(format!("/file_{file_id}.txt")).unwrap(),
+            timestamp: file_id,
+        }
+    }
+}
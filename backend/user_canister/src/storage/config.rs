@@ -2,13 +2,17 @@ use std::cell::RefCell;
 
 use candid::Principal;
 use did::StorablePrincipal;
-use did::user_canister::PublicKey;
+use did::user_canister::{DeviceId, DeviceKey, PublicKey};
 use did::utils::trap;
 use ic_stable_structures::memory_manager::VirtualMemory;
-use ic_stable_structures::{DefaultMemoryImpl, StableCell};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 
+use super::key_rotation::KeyRotationStorage;
 use super::memory::{
-    MEMORY_MANAGER, ORCHESTRATOR_MEMORY_ID, OWNER_MEMORY_ID, OWNER_PUBLIC_KEY_MEMORY_ID,
+    LINKED_PRINCIPALS_MEMORY_ID, MEMORY_MANAGER, ORCHESTRATOR_MEMORY_ID,
+    OWNER_DEVICE_KEYS_MEMORY_ID, OWNER_MEMORY_ID, OWNER_PUBLIC_KEY_MEMORY_ID,
+    OWNER_PUBLIC_KEY_VERSION_MEMORY_ID, RECIPIENT_PRINCIPALS_MEMORY_ID, SCHEMA_VERSION_MEMORY_ID,
+    SUSPENDED_MEMORY_ID,
 };
 
 thread_local! {
@@ -17,14 +21,39 @@ thread_local! {
     static OWNER: RefCell<StableCell<StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(OWNER_MEMORY_ID)), Principal::anonymous().into()).unwrap()
     );
+    /// Principals linked to the account of the owner, such as the principals of their other devices
+    static LINKED_PRINCIPALS: RefCell<StableBTreeMap<StorablePrincipal, (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(LINKED_PRINCIPALS_MEMORY_ID)))
+    );
+    /// Map between a principal linked to the account of a user files are shared with and the
+    /// principal of the account
+    static RECIPIENT_PRINCIPALS: RefCell<StableBTreeMap<StorablePrincipal, StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(RECIPIENT_PRINCIPALS_MEMORY_ID)))
+    );
+    /// Public keys of the devices of the owner, as registered on the orchestrator
+    static OWNER_DEVICE_KEYS: RefCell<StableBTreeMap<DeviceId, DeviceKey, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(OWNER_DEVICE_KEYS_MEMORY_ID)))
+    );
     /// Owner public key
     static OWNER_PUBLIC_KEY: RefCell<StableCell<PublicKey, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(OWNER_PUBLIC_KEY_MEMORY_ID)), PublicKey::default()).unwrap()
     );
+    /// Version of the owner public key on the orchestrator
+    static OWNER_PUBLIC_KEY_VERSION: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(OWNER_PUBLIC_KEY_VERSION_MEMORY_ID)), 0).unwrap()
+    );
+    /// Whether the owner is suspended by the admins of the orchestrator
+    static SUSPENDED: RefCell<StableCell<bool, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(SUSPENDED_MEMORY_ID)), false).unwrap()
+    );
     /// Orchestrator
     static ORCHESTRATOR: RefCell<StableCell<StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(ORCHESTRATOR_MEMORY_ID)), Principal::anonymous().into()).unwrap()
     );
+    /// Version of the layout of the stable memory, up to which the migrations were run
+    static SCHEMA_VERSION: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(SCHEMA_VERSION_MEMORY_ID)), 0).unwrap()
+    );
 }
 
 /// Canister configuration
@@ -41,6 +70,64 @@ impl Config {
             ic_cdk::trap(format!("Failed to set owner: {:?}", err));
         }
     }
+
+    /// Get whether the [`Principal`] is the owner, or a principal linked to the account of the owner
+    pub fn is_owner(principal: Principal) -> bool {
+        principal == Self::get_owner()
+            || LINKED_PRINCIPALS.with_borrow(|linked| linked.contains_key(&principal.into()))
+    }
+
+    /// Link a [`Principal`] to the account of the owner
+    pub fn link_principal(principal: Principal) {
+        LINKED_PRINCIPALS.with_borrow_mut(|linked| linked.insert(principal.into(), ()));
+    }
+
+    /// Unlink a [`Principal`] from the account of the owner
+    pub fn unlink_principal(principal: Principal) {
+        LINKED_PRINCIPALS.with_borrow_mut(|linked| linked.remove(&principal.into()));
+    }
+
+    /// Link a [`Principal`] to the account of a user files may be shared with
+    pub fn link_recipient_principal(account: Principal, principal: Principal) {
+        RECIPIENT_PRINCIPALS
+            .with_borrow_mut(|linked| linked.insert(principal.into(), account.into()));
+    }
+
+    /// Unlink a [`Principal`] from the account of a user files may be shared with
+    pub fn unlink_recipient_principal(principal: Principal) {
+        RECIPIENT_PRINCIPALS.with_borrow_mut(|linked| linked.remove(&principal.into()));
+    }
+
+    /// Get the account of the user files are shared with on behalf of the [`Principal`], which is
+    /// the principal itself unless it is linked to another account
+    pub fn get_recipient_account(principal: Principal) -> Principal {
+        RECIPIENT_PRINCIPALS
+            .with_borrow(|linked| linked.get(&principal.into()))
+            .map_or(principal, |account| account.0)
+    }
+
+    /// Add a [`DeviceKey`] of the owner, replacing the key of the device if any
+    pub fn add_owner_device_key(device_key: DeviceKey) {
+        OWNER_DEVICE_KEYS.with_borrow_mut(|keys| keys.insert(device_key.device_id, device_key));
+    }
+
+    /// Remove a [`DeviceKey`] of the owner
+    ///
+    /// Returns `false` if the owner has no such device.
+    pub fn remove_owner_device_key(device_id: DeviceId) -> bool {
+        OWNER_DEVICE_KEYS.with_borrow_mut(|keys| keys.remove(&device_id).is_some())
+    }
+
+    /// Get whether the owner has a device with the given [`DeviceId`]
+    pub fn is_owner_device(device_id: DeviceId) -> bool {
+        OWNER_DEVICE_KEYS.with_borrow(|keys| keys.contains_key(&device_id))
+    }
+
+    /// Get the [`DeviceKey`]s of the owner, ordered by device ID
+    pub fn get_owner_device_keys() -> Vec<DeviceKey> {
+        OWNER_DEVICE_KEYS.with_borrow(|keys| keys.iter().map(|(_, key)| key).collect())
+    }
+
     /// Get the owner public key [`PublicKey`]
     pub fn get_owner_public_key() -> PublicKey {
         // OWNER_PUBLIC_KEY.with_borrow(|cell| cell.get())
@@ -48,15 +135,26 @@ impl Config {
     }
     /// Set the owner public key [`PublicKey`]
     pub fn set_owner_public_key(caller: Principal, public_key: PublicKey) {
-        let owner = Self::get_owner();
-
-        if owner != caller {
+        if !Self::is_owner(caller) {
             trap("Only the owner can set the public key");
         }
+        if KeyRotationStorage::get().is_some() {
+            trap("Cannot set the public key while a key rotation is in progress");
+        }
         if let Err(err) = OWNER_PUBLIC_KEY.with_borrow_mut(|cell| cell.set(public_key)) {
             ic_cdk::trap(format!("Failed to set owner public key: {:?}", err));
         }
     }
+    /// Get the version of the owner public key on the orchestrator
+    pub fn get_owner_public_key_version() -> u64 {
+        OWNER_PUBLIC_KEY_VERSION.with_borrow(|cell| *cell.get())
+    }
+    /// Set the version of the owner public key on the orchestrator
+    pub fn set_owner_public_key_version(version: u64) {
+        if let Err(err) = OWNER_PUBLIC_KEY_VERSION.with_borrow_mut(|cell| cell.set(version)) {
+            ic_cdk::trap(format!("Failed to set owner public key version: {:?}", err));
+        }
+    }
     /// Get the orchestrator [`Principal`]
     pub fn get_orchestrator() -> Principal {
         ORCHESTRATOR.with_borrow(|cell| cell.get().0)
@@ -67,6 +165,26 @@ impl Config {
             ic_cdk::trap(format!("Failed to set orchestrator: {:?}", err));
         }
     }
+    /// Get whether the owner is suspended
+    pub fn is_suspended() -> bool {
+        SUSPENDED.with_borrow(|cell| *cell.get())
+    }
+    /// Set whether the owner is suspended
+    pub fn set_suspended(suspended: bool) {
+        if let Err(err) = SUSPENDED.with_borrow_mut(|cell| cell.set(suspended)) {
+            ic_cdk::trap(format!("Failed to set suspended: {:?}", err));
+        }
+    }
+    /// Get the version of the layout of the stable memory; 0 if it was never set
+    pub fn get_schema_version() -> u64 {
+        SCHEMA_VERSION.with_borrow(|cell| *cell.get())
+    }
+    /// Set the version of the layout of the stable memory
+    pub fn set_schema_version(version: u64) {
+        if let Err(err) = SCHEMA_VERSION.with_borrow_mut(|cell| cell.set(version)) {
+            ic_cdk::trap(format!("Failed to set schema version: {:?}", err));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +201,65 @@ mod test {
         assert_eq!(Config::get_owner(), principal);
     }
 
+    #[test]
+    fn test_linked_principals() {
+        let owner = Principal::from_slice(&[2; 29]);
+        let device = Principal::from_slice(&[4; 29]);
+        Config::set_owner(owner);
+        assert!(Config::is_owner(owner));
+        assert!(!Config::is_owner(device));
+
+        Config::link_principal(device);
+        assert!(Config::is_owner(device));
+
+        Config::unlink_principal(device);
+        assert!(!Config::is_owner(device));
+        assert!(Config::is_owner(owner));
+    }
+
+    #[test]
+    fn test_recipient_principals() {
+        let bob = Principal::from_slice(&[2; 29]);
+        let bob_phone = Principal::from_slice(&[12; 29]);
+        assert_eq!(Config::get_recipient_account(bob_phone), bob_phone);
+
+        Config::link_recipient_principal(bob, bob_phone);
+        assert_eq!(Config::get_recipient_account(bob_phone), bob);
+        assert_eq!(Config::get_recipient_account(bob), bob);
+        assert!(!Config::is_owner(bob_phone));
+
+        Config::unlink_recipient_principal(bob_phone);
+        assert_eq!(Config::get_recipient_account(bob_phone), bob_phone);
+    }
+
+    #[test]
+    fn test_owner_device_keys() {
+        let device_key = DeviceKey {
+            device_id: 1,
+            name: "Laptop".to_string(),
+            public_key: vec![4; 32].try_into().unwrap(),
+        };
+        assert!(!Config::is_owner_device(1));
+
+        Config::add_owner_device_key(device_key.clone());
+        assert!(Config::is_owner_device(1));
+        assert_eq!(Config::get_owner_device_keys(), vec![device_key]);
+
+        assert!(Config::remove_owner_device_key(1));
+        assert!(!Config::remove_owner_device_key(1));
+        assert!(!Config::is_owner_device(1));
+        assert!(Config::get_owner_device_keys().is_empty());
+    }
+
+    #[test]
+    fn test_suspended() {
+        assert!(!Config::is_suspended());
+        Config::set_suspended(true);
+        assert!(Config::is_suspended());
+        Config::set_suspended(false);
+        assert!(!Config::is_suspended());
+    }
+
     #[test]
     fn test_orchestrator() {
         let principal = Principal::from_slice(&[3; 29]);
@@ -99,5 +276,8 @@ mod test {
         }));
         Config::set_owner_public_key(caller, public_key);
         assert_eq!(Config::get_owner_public_key(), public_key);
+
+        Config::set_owner_public_key_version(2);
+        assert_eq!(Config::get_owner_public_key_version(), 2);
     }
 }
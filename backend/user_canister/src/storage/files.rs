@@ -1,22 +1,27 @@
+mod access_log;
 mod create_state;
 mod data_storage;
+mod device_keys;
 mod file_alias_index;
 mod file_contents;
 mod file_count;
 mod owned_files;
 mod path_storage;
 mod shared_files;
+mod stats;
 
 use std::cell::RefCell;
 use std::collections::HashSet;
 
 use did::StorablePrincipal;
-use did::user_canister::Path;
+use did::user_canister::{DeviceId, FileAccessLogEntry, OwnerKey, Path};
 use ic_stable_structures::memory_manager::VirtualMemory;
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 
+pub use self::access_log::FileAccessLogStorage;
 pub use self::create_state::{ChunkId, File, FileContent, FileId, FileMetadata, UploadedChunks};
 pub use self::data_storage::FileDataStorage;
+pub use self::device_keys::FileDeviceKeysStorage;
 pub use self::file_alias_index::FileAliasIndexStorage;
 pub use self::file_contents::FileContentsStorage;
 pub use self::file_count::FileCountStorage;
@@ -24,13 +29,19 @@ pub use self::owned_files::OwnedFilesStorage;
 pub use self::path_storage::PathStorage;
 pub use self::shared_files::FileSharesStorage;
 use self::shared_files::SharedFiles;
+pub use self::stats::{FileStats, FileStatsStorage};
 use crate::storage::memory::{
-    FILE_ALIAS_INDEX_MEMORY_ID, FILE_CONTENTS_MEMORY_ID, FILE_COUNT_MEMORY_ID, FILE_DATA_MEMORY_ID,
-    FILE_ID_TO_PATH_MEMORY_ID, FILE_PATH_TO_ID_MEMORY_ID, FILE_SHARES_MEMORY_ID, MEMORY_MANAGER,
-    OWNED_FILES_MEMORY_ID,
+    FILE_ACCESS_LOG_MEMORY_ID, FILE_ALIAS_INDEX_MEMORY_ID, FILE_CONTENTS_MEMORY_ID,
+    FILE_COUNT_MEMORY_ID, FILE_DATA_MEMORY_ID, FILE_DEVICE_KEYS_MEMORY_ID,
+    FILE_ID_TO_PATH_MEMORY_ID, FILE_PATH_TO_ID_MEMORY_ID, FILE_SHARES_MEMORY_ID,
+    FILE_STATS_MEMORY_ID, MEMORY_MANAGER, OWNED_FILES_MEMORY_ID,
 };
 
 type ContentTuple = (FileId, ChunkId);
+/// Index of an entry in the access log of a file.
+type AccessLogIndex = u64;
+type AccessLogTuple = (FileId, AccessLogIndex);
+type DeviceKeyTuple = (FileId, StorablePrincipal, DeviceId);
 
 thread_local! {
     /// File count incrementer
@@ -76,6 +87,24 @@ thread_local! {
     static FILE_CONTENTS_STORAGE: RefCell<StableBTreeMap<ContentTuple, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(FILE_CONTENTS_MEMORY_ID)))
     );
+
+    /// File access log storage map
+    /// Mapping between a file ID and the index of the entry, and the download confirmed by a user.
+    static FILE_ACCESS_LOG_STORAGE: RefCell<StableBTreeMap<AccessLogTuple, FileAccessLogEntry, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(FILE_ACCESS_LOG_MEMORY_ID)))
+    );
+
+    /// File device keys storage map
+    /// Mapping between a file ID, a user and one of their devices, and the document key encrypted
+    /// for the device. The keys of the owner are stored under the owner principal.
+    static FILE_DEVICE_KEYS_STORAGE: RefCell<StableBTreeMap<DeviceKeyTuple, OwnerKey, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(FILE_DEVICE_KEYS_MEMORY_ID)))
+    );
+
+    /// Counters of the files by state, and of the bytes of their contents.
+    static FILE_STATS: RefCell<StableCell<FileStats, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(FILE_STATS_MEMORY_ID)), FileStats::default()).unwrap()
+    );
 }
 
 /// Accessor to the owned files storage
@@ -0,0 +1,143 @@
+use candid::Principal;
+use did::user_canister::FileAccessLogEntry;
+
+use super::{AccessLogIndex, FILE_ACCESS_LOG_STORAGE, FileId};
+
+// Public API for the file access log storage
+pub struct FileAccessLogStorage;
+
+impl FileAccessLogStorage {
+    /// Append an entry to the access log of a file
+    pub fn record(file_id: &FileId, entry: FileAccessLogEntry) {
+        FILE_ACCESS_LOG_STORAGE.with_borrow_mut(|access_log| {
+            let next_index = access_log
+                .range(Self::file_range(file_id))
+                .next_back()
+                .map(|((_, index), _)| index + 1)
+                .unwrap_or_default();
+
+            access_log.insert((*file_id, next_index), entry);
+        });
+    }
+
+    /// Get whether the access log of a file has an entry of the given user for the given version
+    /// of the file, recorded at or after `since`.
+    ///
+    /// Only the entries recorded since then are scanned, starting from the most recent one.
+    pub fn has_recent_entry(file_id: &FileId, user: Principal, version: u64, since: u64) -> bool {
+        FILE_ACCESS_LOG_STORAGE.with_borrow(|access_log| {
+            access_log
+                .range(Self::file_range(file_id))
+                .rev()
+                .take_while(|(_, entry)| entry.timestamp >= since)
+                .any(|(_, entry)| entry.user == user && entry.version == version)
+        })
+    }
+
+    /// Get up to `limit` entries of the access log of a file, starting from `offset`
+    pub fn get_entries(file_id: &FileId, offset: u64, limit: u64) -> Vec<FileAccessLogEntry> {
+        FILE_ACCESS_LOG_STORAGE.with_borrow(|access_log| {
+            access_log
+                .range(Self::file_range(file_id))
+                .skip(offset as usize)
+                .take(limit as usize)
+                .map(|(_, entry)| entry)
+                .collect()
+        })
+    }
+
+    /// Get the number of entries in the access log of a file
+    pub fn count(file_id: &FileId) -> u64 {
+        FILE_ACCESS_LOG_STORAGE.with_borrow(|access_log| {
+            access_log
+                .range(Self::file_range(file_id))
+                .next_back()
+                .map(|((_, index), _)| index + 1)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Remove the access log of a file
+    pub fn remove_file_access_log(file_id: &FileId) {
+        FILE_ACCESS_LOG_STORAGE.with_borrow_mut(|access_log| {
+            let keys = access_log
+                .range(Self::file_range(file_id))
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>();
+
+            for key in keys {
+                access_log.remove(&key);
+            }
+        });
+    }
+
+    /// Range of the keys of the access log of a file
+    fn file_range(file_id: &FileId) -> std::ops::RangeInclusive<(FileId, AccessLogIndex)> {
+        (*file_id, 0)..=(*file_id, AccessLogIndex::MAX)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_record_and_get_entries() {
+        let file_id = 1;
+        for i in 0..5 {
+            FileAccessLogStorage::record(&file_id, entry(i));
+        }
+        // another file
+        FileAccessLogStorage::record(&2, entry(10));
+
+        assert_eq!(FileAccessLogStorage::count(&file_id), 5);
+        assert_eq!(FileAccessLogStorage::count(&2), 1);
+        assert_eq!(FileAccessLogStorage::count(&3), 0);
+
+        assert_eq!(
+            FileAccessLogStorage::get_entries(&file_id, 0, 10),
+            (0..5).map(entry).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            FileAccessLogStorage::get_entries(&file_id, 1, 2),
+            vec![entry(1), entry(2)]
+        );
+        assert!(FileAccessLogStorage::get_entries(&file_id, 5, 2).is_empty());
+    }
+
+    #[test]
+    fn test_should_find_recent_entries() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        FileAccessLogStorage::record(&1, entry(10));
+        FileAccessLogStorage::record(&1, entry(20));
+
+        assert!(FileAccessLogStorage::has_recent_entry(&1, alice, 1, 15));
+        assert!(FileAccessLogStorage::has_recent_entry(&1, alice, 1, 20));
+        assert!(!FileAccessLogStorage::has_recent_entry(&1, alice, 1, 21));
+        assert!(!FileAccessLogStorage::has_recent_entry(&1, alice, 2, 0));
+        assert!(!FileAccessLogStorage::has_recent_entry(&1, bob, 1, 0));
+        assert!(!FileAccessLogStorage::has_recent_entry(&2, alice, 1, 0));
+    }
+
+    #[test]
+    fn test_should_remove_file_access_log() {
+        FileAccessLogStorage::record(&1, entry(0));
+        FileAccessLogStorage::record(&1, entry(1));
+        FileAccessLogStorage::record(&2, entry(2));
+
+        FileAccessLogStorage::remove_file_access_log(&1);
+
+        assert_eq!(FileAccessLogStorage::count(&1), 0);
+        assert_eq!(FileAccessLogStorage::get_entries(&2, 0, 10), vec![entry(2)]);
+    }
+
+    fn entry(timestamp: u64) -> FileAccessLogEntry {
+        FileAccessLogEntry {
+            user: Principal::from_slice(&[1; 29]),
+            timestamp,
+            version: 1,
+        }
+    }
+}
@@ -146,6 +146,29 @@ impl Storable for FileContent {
 }
 
 impl FileContent {
+    /// Get the document key encrypted for the owner, if the file has been uploaded.
+    pub fn owner_key(&self) -> Option<&OwnerKey> {
+        match self {
+            FileContent::Pending { .. } => None,
+            FileContent::Uploaded { owner_key, .. }
+            | FileContent::PartiallyUploaded { owner_key, .. } => Some(owner_key),
+        }
+    }
+
+    /// Replace the document key encrypted for the owner.
+    ///
+    /// Returns `false` if the file has not been uploaded yet.
+    pub fn set_owner_key(&mut self, key: OwnerKey) -> bool {
+        match self {
+            FileContent::Pending { .. } => false,
+            FileContent::Uploaded { owner_key, .. }
+            | FileContent::PartiallyUploaded { owner_key, .. } => {
+                *owner_key = key;
+                true
+            }
+        }
+    }
+
     // Decode Variant  for [`Pending::{alias}`]
     fn decode_pending(bytes: &[u8]) -> FileContent {
         let alias_len = bytes[0] as usize;
@@ -1,4 +1,5 @@
-use super::{FILE_DATA_STORAGE, File, FileId, with_file_data};
+use super::{FILE_DATA_STORAGE, File, FileId, FileStatsStorage, with_file_data};
+use crate::storage::key_rotation::KeyRotationStorage;
 
 // Public API for the file data storage
 pub struct FileDataStorage;
@@ -11,16 +12,47 @@ impl FileDataStorage {
 
     /// Set a file by its ID
     pub fn set_file(file_id: &FileId, file: File) {
-        FILE_DATA_STORAGE.with_borrow_mut(|file_data| {
-            file_data.insert(*file_id, file);
-        });
+        FileStatsStorage::add_file(&file.content);
+        KeyRotationStorage::add_file(&file);
+        let previous =
+            FILE_DATA_STORAGE.with_borrow_mut(|file_data| file_data.insert(*file_id, file));
+        if let Some(previous) = previous {
+            FileStatsStorage::remove_file(&previous.content);
+            KeyRotationStorage::remove_file(&previous);
+        }
+    }
+
+    /// Get up to `limit` files matching the `predicate`, in ID order.
+    pub fn find_files<F>(limit: usize, predicate: F) -> Vec<(FileId, File)>
+    where
+        F: Fn(&File) -> bool,
+    {
+        FILE_DATA_STORAGE.with_borrow(|file_data| {
+            file_data
+                .iter()
+                .filter(|(_, file)| predicate(file))
+                .take(limit)
+                .collect()
+        })
+    }
+
+    /// Count the files matching the `predicate`.
+    pub fn count_files<F>(predicate: F) -> u64
+    where
+        F: Fn(&File) -> bool,
+    {
+        FILE_DATA_STORAGE
+            .with_borrow(|file_data| file_data.iter().filter(|(_, file)| predicate(file)).count())
+            as u64
     }
 
     /// Remove a file by its ID
     pub fn remove_file(file_id: &FileId) {
-        FILE_DATA_STORAGE.with_borrow_mut(|file_data| {
-            file_data.remove(file_id);
-        });
+        let removed = FILE_DATA_STORAGE.with_borrow_mut(|file_data| file_data.remove(file_id));
+        if let Some(removed) = removed {
+            FileStatsStorage::remove_file(&removed.content);
+            KeyRotationStorage::remove_file(&removed);
+        }
     }
 }
 
@@ -28,6 +60,7 @@ impl FileDataStorage {
 mod test {
 
     use candid::Principal;
+    use did::user_canister::OwnerKey;
 
     use super::*;
     use crate::storage::files::{FileContent, FileMetadata};
@@ -72,4 +105,70 @@ mod test {
         FileDataStorage::remove_file(&file_id);
         assert!(FileDataStorage::get_file(&file_id).is_none());
     }
+
+    #[test]
+    fn test_find_and_count_files() {
+        for file_id in 0..4 {
+            let file = File {
+                metadata: FileMetadata {
+                    user_public_key: vec![0; 32].try_into().unwrap(),
+                    requester_principal: Principal::from_slice(&[1; 29]),
+                    requested_at: file_id,
+                    uploaded_at: None,
+                },
+                content: FileContent::Pending {
+                    alias: format!("alias_{file_id}"),
+                },
+            };
+            FileDataStorage::set_file(&file_id, file);
+        }
+
+        let even = |file: &File| file.metadata.requested_at % 2 == 0;
+        assert_eq!(FileDataStorage::count_files(even), 2);
+        let files = FileDataStorage::find_files(1, even);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, 0);
+        assert_eq!(FileDataStorage::find_files(10, even).len(), 2);
+    }
+
+    #[test]
+    fn test_should_count_files_by_state() {
+        let file = |content| File {
+            metadata: FileMetadata {
+                user_public_key: vec![0; 32].try_into().unwrap(),
+                requester_principal: Principal::from_slice(&[1; 29]),
+                requested_at: 0,
+                uploaded_at: None,
+            },
+            content,
+        };
+        let pending = || FileContent::Pending {
+            alias: "alias".to_string(),
+        };
+        FileDataStorage::set_file(&1, file(pending()));
+        FileDataStorage::set_file(&2, file(pending()));
+        FileDataStorage::set_file(
+            &2,
+            file(FileContent::PartiallyUploaded {
+                num_chunks: 2,
+                uploaded_chunks: Default::default(),
+                file_type: "text/plain".to_string(),
+                owner_key: [0; OwnerKey::KEY_SIZE].into(),
+                shared_keys: Default::default(),
+            }),
+        );
+        let stats = FileStatsStorage::get();
+        assert_eq!(
+            (stats.pending, stats.partially_uploaded, stats.uploaded),
+            (1, 1, 0)
+        );
+
+        FileDataStorage::remove_file(&1);
+        FileDataStorage::remove_file(&1);
+        let stats = FileStatsStorage::get();
+        assert_eq!(
+            (stats.pending, stats.partially_uploaded, stats.uploaded),
+            (0, 1, 0)
+        );
+    }
 }
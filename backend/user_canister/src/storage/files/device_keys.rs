@@ -0,0 +1,116 @@
+use candid::Principal;
+use did::StorablePrincipal;
+use did::user_canister::{DeviceId, DeviceOwnerKey, OwnerKey};
+
+use super::{DeviceKeyTuple, FILE_DEVICE_KEYS_STORAGE, FileId};
+
+// Public API for the file device keys storage
+pub struct FileDeviceKeysStorage;
+
+impl FileDeviceKeysStorage {
+    /// Set the keys of the devices of a user for a file, replacing the previous ones
+    pub fn set_keys(file_id: &FileId, user: Principal, keys: Vec<DeviceOwnerKey>) {
+        Self::remove_user_keys(file_id, user);
+        FILE_DEVICE_KEYS_STORAGE.with_borrow_mut(|device_keys| {
+            for DeviceOwnerKey { device_id, key } in keys {
+                device_keys.insert((*file_id, user.into(), device_id), key);
+            }
+        });
+    }
+
+    /// Get the key of a device of a user for a file
+    pub fn get_key(file_id: &FileId, user: Principal, device_id: DeviceId) -> Option<OwnerKey> {
+        FILE_DEVICE_KEYS_STORAGE
+            .with_borrow(|device_keys| device_keys.get(&(*file_id, user.into(), device_id)))
+    }
+
+    /// Remove the key of a device of a user for a file
+    pub fn remove_key(file_id: &FileId, user: Principal, device_id: DeviceId) {
+        FILE_DEVICE_KEYS_STORAGE.with_borrow_mut(|device_keys| {
+            device_keys.remove(&(*file_id, user.into(), device_id));
+        });
+    }
+
+    /// Remove the keys of the devices of a user for a file
+    pub fn remove_user_keys(file_id: &FileId, user: Principal) {
+        let user = StorablePrincipal::from(user);
+        Self::remove_range((*file_id, user, 0)..=(*file_id, user, DeviceId::MAX));
+    }
+
+    /// Remove the keys of all the devices for a file
+    pub fn remove_file_keys(file_id: &FileId) {
+        let first = StorablePrincipal::from(Principal::from_slice(&[]));
+        let keys = FILE_DEVICE_KEYS_STORAGE.with_borrow(|device_keys| {
+            device_keys
+                .range((*file_id, first, 0)..)
+                .take_while(|((id, _, _), _)| id == file_id)
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>()
+        });
+        FILE_DEVICE_KEYS_STORAGE.with_borrow_mut(|device_keys| {
+            for key in keys {
+                device_keys.remove(&key);
+            }
+        });
+    }
+
+    /// Remove the keys in the given range
+    fn remove_range(range: std::ops::RangeInclusive<DeviceKeyTuple>) {
+        FILE_DEVICE_KEYS_STORAGE.with_borrow_mut(|device_keys| {
+            let keys = device_keys
+                .range(range)
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>();
+
+            for key in keys {
+                device_keys.remove(&key);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_set_and_remove_device_keys() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        let key = |device_id, byte| DeviceOwnerKey {
+            device_id,
+            key: OwnerKey::new([byte; OwnerKey::KEY_SIZE]),
+        };
+
+        FileDeviceKeysStorage::set_keys(&1, alice, vec![key(0, 1), key(1, 2)]);
+        FileDeviceKeysStorage::set_keys(&1, bob, vec![key(0, 3)]);
+        FileDeviceKeysStorage::set_keys(&2, alice, vec![key(0, 4)]);
+        assert_eq!(
+            FileDeviceKeysStorage::get_key(&1, alice, 1),
+            Some(key(1, 2).key)
+        );
+        assert_eq!(FileDeviceKeysStorage::get_key(&1, bob, 1), None);
+
+        // keys are replaced
+        FileDeviceKeysStorage::set_keys(&1, alice, vec![key(1, 5)]);
+        assert_eq!(FileDeviceKeysStorage::get_key(&1, alice, 0), None);
+        assert_eq!(
+            FileDeviceKeysStorage::get_key(&1, alice, 1),
+            Some(key(1, 5).key)
+        );
+
+        FileDeviceKeysStorage::set_keys(&1, alice, vec![key(0, 6), key(1, 5)]);
+        FileDeviceKeysStorage::remove_key(&1, alice, 0);
+        assert_eq!(FileDeviceKeysStorage::get_key(&1, alice, 0), None);
+        assert!(FileDeviceKeysStorage::get_key(&1, alice, 1).is_some());
+
+        FileDeviceKeysStorage::remove_user_keys(&1, alice);
+        assert_eq!(FileDeviceKeysStorage::get_key(&1, alice, 1), None);
+        assert!(FileDeviceKeysStorage::get_key(&1, bob, 0).is_some());
+
+        FileDeviceKeysStorage::remove_file_keys(&1);
+        assert_eq!(FileDeviceKeysStorage::get_key(&1, bob, 0), None);
+        assert!(FileDeviceKeysStorage::get_key(&2, alice, 0).is_some());
+    }
+}
@@ -1,4 +1,4 @@
-use super::{ChunkId, FILE_CONTENTS_STORAGE, FileId, with_file_contents};
+use super::{ChunkId, FILE_CONTENTS_STORAGE, FileId, FileStatsStorage, with_file_contents};
 
 // Public API for the file contents storage
 pub struct FileContentsStorage;
@@ -11,16 +11,22 @@ impl FileContentsStorage {
 
     /// Set the contents of a file by its ID and chunk ID
     pub fn set_file_contents(file_id: &FileId, chunk_id: &ChunkId, contents: Vec<u8>) {
-        FILE_CONTENTS_STORAGE.with_borrow_mut(|file_contents| {
-            file_contents.insert((*file_id, *chunk_id), contents);
-        });
+        let bytes = contents.len() as u64;
+        let previous = FILE_CONTENTS_STORAGE
+            .with_borrow_mut(|file_contents| file_contents.insert((*file_id, *chunk_id), contents));
+        FileStatsStorage::replace_bytes(
+            previous.map_or(0, |previous| previous.len() as u64),
+            bytes,
+        );
     }
 
     /// Remove the contents of a file by its ID and chunk ID
     pub fn remove_file_contents(file_id: &FileId, chunk_id: &ChunkId) {
-        FILE_CONTENTS_STORAGE.with_borrow_mut(|file_contents| {
-            file_contents.remove(&(*file_id, *chunk_id));
-        });
+        let removed = FILE_CONTENTS_STORAGE
+            .with_borrow_mut(|file_contents| file_contents.remove(&(*file_id, *chunk_id)));
+        if let Some(removed) = removed {
+            FileStatsStorage::replace_bytes(removed.len() as u64, 0);
+        }
     }
 }
 
@@ -58,4 +64,20 @@ mod test {
             None
         );
     }
+
+    #[test]
+    fn test_stored_bytes() {
+        FileContentsStorage::set_file_contents(&1, &0, vec![0; 10]);
+        FileContentsStorage::set_file_contents(&1, &1, vec![0; 5]);
+        FileContentsStorage::set_file_contents(&2, &0, vec![0; 3]);
+        assert_eq!(FileStatsStorage::get().stored_bytes, 18);
+
+        // replaced contents are not counted anymore
+        FileContentsStorage::set_file_contents(&2, &0, vec![0; 4]);
+        assert_eq!(FileStatsStorage::get().stored_bytes, 19);
+
+        FileContentsStorage::remove_file_contents(&1, &1);
+        FileContentsStorage::remove_file_contents(&1, &1);
+        assert_eq!(FileStatsStorage::get().stored_bytes, 14);
+    }
 }
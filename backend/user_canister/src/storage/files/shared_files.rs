@@ -6,7 +6,7 @@ use candid::Principal;
 use did::StorablePrincipal;
 
 pub use self::storable::SharedFiles;
-use super::{FILE_SHARES_STORAGE, FileId, with_file_shares};
+use super::{FILE_SHARES_STORAGE, FileId, FileStatsStorage, with_file_shares};
 
 // Public API for the file shares storage
 pub struct FileSharesStorage;
@@ -54,10 +54,12 @@ impl FileSharesStorage {
 
         FILE_SHARES_STORAGE.with_borrow_mut(|file_shares| {
             let mut file_set = file_shares.get(&principal).unwrap_or_default();
+            let previous = file_set.len() as u64;
             for file_id in file_ids {
                 // Insert the file ID into the list of shares for the principal
                 file_set.insert(file_id);
             }
+            FileStatsStorage::replace_shares(previous, file_set.len() as u64);
             // Update the list of file IDs for the principal
             file_shares.insert(principal, file_set);
         });
@@ -69,9 +71,11 @@ impl FileSharesStorage {
 
         FILE_SHARES_STORAGE.with_borrow_mut(|file_shares| {
             let mut file_set = file_shares.get(&principal).unwrap_or_default();
+            let previous = file_set.len() as u64;
 
             // Remove the file ID from the list of shares for the principal
             file_set.remove(file_id);
+            FileStatsStorage::replace_shares(previous, file_set.len() as u64);
 
             if file_set.is_empty() {
                 // If the list is empty, remove the principal from the storage
@@ -0,0 +1,249 @@
+use std::borrow::Cow;
+
+use ic_stable_structures::Storable;
+use ic_stable_structures::storable::Bound;
+
+use super::{
+    FILE_CONTENTS_STORAGE, FILE_DATA_STORAGE, FILE_SHARES_STORAGE, FILE_STATS, FileContent,
+};
+
+/// Counters of the files, of their contents and of their shares.
+///
+/// They are updated as the files, their contents and their shares are stored and removed, so that
+/// they don't need to be counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileStats {
+    /// Total size of the stored file contents, in bytes
+    pub stored_bytes: u64,
+    /// Number of pending files
+    pub pending: u64,
+    /// Number of partially uploaded files
+    pub partially_uploaded: u64,
+    /// Number of uploaded files
+    pub uploaded: u64,
+    /// Number of users files are shared with
+    pub share_recipients: u64,
+    /// Number of files shared with users, counted once per user
+    pub shares: u64,
+}
+
+impl FileStats {
+    /// Get the counter of the files in the state of the given [`FileContent`].
+    fn files_mut(&mut self, content: &FileContent) -> &mut u64 {
+        match content {
+            FileContent::Pending { .. } => &mut self.pending,
+            FileContent::PartiallyUploaded { .. } => &mut self.partially_uploaded,
+            FileContent::Uploaded { .. } => &mut self.uploaded,
+        }
+    }
+}
+
+impl Storable for FileStats {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 48,
+        is_fixed_size: false,
+    };
+
+    /// Strategy [stored_bytes: u64 | pending: u64 | partially_uploaded: u64 | uploaded: u64 |
+    /// share_recipients: u64 | shares: u64]
+    ///
+    /// The share counters are missing from the stats stored before the shares were counted; they
+    /// are decoded as zero until the shares are counted again.
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(48);
+        for counter in [
+            self.stored_bytes,
+            self.pending,
+            self.partially_uploaded,
+            self.uploaded,
+            self.share_recipients,
+            self.shares,
+        ] {
+            bytes.extend_from_slice(&counter.to_le_bytes());
+        }
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let counter = |index: usize| {
+            bytes.get(index * 8..(index + 1) * 8).map_or(0, |counter| {
+                u64::from_le_bytes(counter.try_into().expect("Failed to decode FileStats"))
+            })
+        };
+
+        Self {
+            stored_bytes: counter(0),
+            pending: counter(1),
+            partially_uploaded: counter(2),
+            uploaded: counter(3),
+            share_recipients: counter(4),
+            shares: counter(5),
+        }
+    }
+}
+
+// Public API for the file stats
+pub struct FileStatsStorage;
+
+impl FileStatsStorage {
+    /// Get the [`FileStats`]
+    pub fn get() -> FileStats {
+        FILE_STATS.with_borrow(|stats| *stats.get())
+    }
+
+    /// Count the files, their contents and their shares again, replacing the [`FileStats`].
+    ///
+    /// Every file, chunk and share is read, so this is only meant to initialize the counters of
+    /// the files stored before they were kept.
+    pub fn recount() {
+        let mut stats = FileStats::default();
+        FILE_DATA_STORAGE.with_borrow(|file_data| {
+            for (_, file) in file_data.iter() {
+                *stats.files_mut(&file.content) += 1;
+            }
+        });
+        stats.stored_bytes = FILE_CONTENTS_STORAGE.with_borrow(|file_contents| {
+            file_contents
+                .iter()
+                .map(|(_, contents)| contents.len() as u64)
+                .sum()
+        });
+        FILE_SHARES_STORAGE.with_borrow(|file_shares| {
+            for (_, file_ids) in file_shares
+                .iter()
+                .filter(|(_, file_ids)| !file_ids.is_empty())
+            {
+                stats.share_recipients += 1;
+                stats.shares += file_ids.len() as u64;
+            }
+        });
+
+        Self::set(stats);
+    }
+
+    /// Count a file stored with the given [`FileContent`]
+    pub(super) fn add_file(content: &FileContent) {
+        Self::update(|stats| *stats.files_mut(content) += 1);
+    }
+
+    /// Uncount a file removed with the given [`FileContent`]
+    pub(super) fn remove_file(content: &FileContent) {
+        Self::update(|stats| {
+            let files = stats.files_mut(content);
+            *files = files.saturating_sub(1);
+        });
+    }
+
+    /// Count the bytes of stored contents, replacing `previous` bytes
+    pub(super) fn replace_bytes(previous: u64, bytes: u64) {
+        Self::update(|stats| {
+            stats.stored_bytes = stats.stored_bytes.saturating_sub(previous) + bytes;
+        });
+    }
+
+    /// Count the shares of the files of a user, replacing the `previous` shares.
+    ///
+    /// The user is counted as a recipient while files are shared with them.
+    pub(super) fn replace_shares(previous: u64, shares: u64) {
+        Self::update(|stats| {
+            if previous == 0 && shares > 0 {
+                stats.share_recipients += 1;
+            } else if previous > 0 && shares == 0 {
+                stats.share_recipients = stats.share_recipients.saturating_sub(1);
+            }
+            stats.shares = stats.shares.saturating_sub(previous) + shares;
+        });
+    }
+
+    fn update(f: impl FnOnce(&mut FileStats)) {
+        let mut stats = Self::get();
+        f(&mut stats);
+        Self::set(stats);
+    }
+
+    fn set(stats: FileStats) {
+        if let Err(err) = FILE_STATS.with_borrow_mut(|cell| cell.set(stats)) {
+            ic_cdk::trap(format!("Failed to set file stats: {:?}", err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use candid::Principal;
+
+    use super::*;
+    use crate::storage::files::{FileSharesStorage, SharedFiles};
+
+    #[test]
+    fn test_should_recount_file_stats() {
+        FILE_CONTENTS_STORAGE.with_borrow_mut(|file_contents| {
+            file_contents.insert((1, 0), vec![0; 10]);
+            file_contents.insert((1, 1), vec![0; 5]);
+        });
+        FILE_SHARES_STORAGE.with_borrow_mut(|file_shares| {
+            let mut file_ids = SharedFiles::default();
+            file_ids.insert(1);
+            file_ids.insert(2);
+            file_shares.insert(Principal::from_slice(&[1; 29]).into(), file_ids);
+            file_shares.insert(
+                Principal::from_slice(&[2; 29]).into(),
+                SharedFiles::default(),
+            );
+        });
+        assert_eq!(FileStatsStorage::get(), FileStats::default());
+
+        FileStatsStorage::recount();
+        let stats = FileStatsStorage::get();
+        assert_eq!(stats.stored_bytes, 15);
+        assert_eq!(stats.share_recipients, 1);
+        assert_eq!(stats.shares, 2);
+    }
+
+    #[test]
+    fn test_should_count_shares() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+
+        FileSharesStorage::share(&alice, vec![1, 2]);
+        FileSharesStorage::share(&alice, vec![2, 3]);
+        FileSharesStorage::share(&bob, vec![1]);
+        let stats = FileStatsStorage::get();
+        assert_eq!(stats.share_recipients, 2);
+        assert_eq!(stats.shares, 4);
+
+        FileSharesStorage::revoke(&bob, &1);
+        FileSharesStorage::revoke(&bob, &1);
+        FileSharesStorage::revoke(&alice, &2);
+        let stats = FileStatsStorage::get();
+        assert_eq!(stats.share_recipients, 1);
+        assert_eq!(stats.shares, 2);
+    }
+
+    #[test]
+    fn test_file_stats_storable() {
+        let stats = FileStats {
+            stored_bytes: 1 << 40,
+            pending: 1,
+            partially_uploaded: 2,
+            uploaded: 3,
+            share_recipients: 4,
+            shares: 5,
+        };
+        let bytes = stats.to_bytes();
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(FileStats::from_bytes(bytes.clone()), stats);
+
+        // the stats stored before the shares were counted
+        assert_eq!(
+            FileStats::from_bytes(bytes[..32].to_vec().into()),
+            FileStats {
+                share_recipients: 0,
+                shares: 0,
+                ..stats
+            }
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use did::user_canister::{KeyRotation, PublicKey};
+use did::utils::trap;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableCell, Storable};
+
+use super::files::{File, FileDataStorage};
+use super::memory::{KEY_ROTATION_MEMORY_ID, KEY_ROTATION_PROGRESS_MEMORY_ID, MEMORY_MANAGER};
+
+thread_local! {
+    /// Rotation of the owner public key in progress, if any
+    static KEY_ROTATION: RefCell<StableCell<Option<KeyRotation>, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(KEY_ROTATION_MEMORY_ID)), None).unwrap()
+    );
+
+    /// Progress of the rotation in progress
+    static KEY_ROTATION_PROGRESS: RefCell<StableCell<KeyRotationProgress, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(KEY_ROTATION_PROGRESS_MEMORY_ID)), KeyRotationProgress::default()).unwrap()
+    );
+}
+
+/// Counters of the files migrated by the rotation in progress.
+///
+/// They are updated as the files are stored and removed, so that the files don't need to be
+/// counted on each batch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRotationProgress {
+    /// Number of files whose key is already wrapped for the new public key
+    pub migrated: u64,
+    /// Number of files whose key must still be re-wrapped
+    pub remaining: u64,
+}
+
+impl KeyRotationProgress {
+    /// Get the counter of the given file for a rotation to `new_public_key`, if it has a key.
+    fn files_mut(&mut self, file: &File, new_public_key: &PublicKey) -> Option<&mut u64> {
+        file.content.owner_key()?;
+
+        if file.metadata.user_public_key == *new_public_key {
+            Some(&mut self.migrated)
+        } else {
+            Some(&mut self.remaining)
+        }
+    }
+}
+
+impl Storable for KeyRotationProgress {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 16,
+        is_fixed_size: true,
+    };
+
+    /// Strategy [migrated: u64 | remaining: u64]
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(16);
+        bytes.extend_from_slice(&self.migrated.to_le_bytes());
+        bytes.extend_from_slice(&self.remaining.to_le_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let counter = |index: usize| {
+            u64::from_le_bytes(
+                bytes[index * 8..(index + 1) * 8]
+                    .try_into()
+                    .expect("Failed to decode KeyRotationProgress"),
+            )
+        };
+
+        Self {
+            migrated: counter(0),
+            remaining: counter(1),
+        }
+    }
+}
+
+/// Accessor for the rotation of the owner public key.
+pub struct KeyRotationStorage;
+
+impl KeyRotationStorage {
+    /// Get the rotation in progress, if any.
+    pub fn get() -> Option<KeyRotation> {
+        KEY_ROTATION.with_borrow(|cell| *cell.get())
+    }
+
+    /// Get the [`KeyRotationProgress`] of the rotation in progress.
+    pub fn progress() -> KeyRotationProgress {
+        KEY_ROTATION_PROGRESS.with_borrow(|cell| *cell.get())
+    }
+
+    /// Set the rotation in progress, counting the files to migrate.
+    pub fn start(rotation: KeyRotation) {
+        Self::set(Some(rotation));
+        Self::recount();
+    }
+
+    /// Clear the rotation in progress.
+    pub fn clear() {
+        Self::set(None);
+        Self::set_progress(KeyRotationProgress::default());
+    }
+
+    /// Count the files of the rotation in progress again, replacing the [`KeyRotationProgress`].
+    ///
+    /// Every file is read, so this is only meant to initialize the counters.
+    pub fn recount() {
+        let mut progress = KeyRotationProgress::default();
+        if let Some(rotation) = Self::get() {
+            let new_public_key = rotation.new_public_key;
+            progress.migrated = FileDataStorage::count_files(|file| {
+                file.content.owner_key().is_some()
+                    && file.metadata.user_public_key == new_public_key
+            });
+            progress.remaining = FileDataStorage::count_files(|file| {
+                file.content.owner_key().is_some()
+                    && file.metadata.user_public_key != new_public_key
+            });
+        }
+
+        Self::set_progress(progress);
+    }
+
+    /// Count a stored file in the progress of the rotation in progress, if any.
+    pub(crate) fn add_file(file: &File) {
+        Self::update_progress(file, |files| *files += 1);
+    }
+
+    /// Uncount a removed file from the progress of the rotation in progress, if any.
+    pub(crate) fn remove_file(file: &File) {
+        Self::update_progress(file, |files| *files = files.saturating_sub(1));
+    }
+
+    fn update_progress(file: &File, f: impl FnOnce(&mut u64)) {
+        let Some(rotation) = Self::get() else {
+            return;
+        };
+
+        let mut progress = Self::progress();
+        if let Some(files) = progress.files_mut(file, &rotation.new_public_key) {
+            f(files);
+            Self::set_progress(progress);
+        }
+    }
+
+    fn set(rotation: Option<KeyRotation>) {
+        if let Err(err) = KEY_ROTATION.with_borrow_mut(|cell| cell.set(rotation)) {
+            trap(format!("Failed to set key rotation: {:?}", err));
+        }
+    }
+
+    fn set_progress(progress: KeyRotationProgress) {
+        if let Err(err) = KEY_ROTATION_PROGRESS.with_borrow_mut(|cell| cell.set(progress)) {
+            trap(format!("Failed to set key rotation progress: {:?}", err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use candid::Principal;
+    use did::user_canister::{OwnerKey, PublicKey};
+
+    use super::*;
+    use crate::storage::files::{FileContent, FileMetadata};
+
+    #[test]
+    fn test_should_start_and_clear_rotation() {
+        assert!(KeyRotationStorage::get().is_none());
+
+        let rotation = KeyRotation {
+            new_public_key: PublicKey::try_from(vec![1; 32]).unwrap(),
+            started_at: 1,
+        };
+        KeyRotationStorage::start(rotation);
+        assert_eq!(KeyRotationStorage::get(), Some(rotation));
+
+        KeyRotationStorage::clear();
+        assert!(KeyRotationStorage::get().is_none());
+    }
+
+    #[test]
+    fn test_should_count_rotation_progress() {
+        let old_key = PublicKey::try_from(vec![0; 32]).unwrap();
+        let new_key = PublicKey::try_from(vec![1; 32]).unwrap();
+        let file = |user_public_key: PublicKey, content: FileContent| File {
+            metadata: FileMetadata {
+                user_public_key,
+                requester_principal: Principal::from_slice(&[1; 29]),
+                requested_at: 0,
+                uploaded_at: None,
+            },
+            content,
+        };
+        let uploaded = || FileContent::Uploaded {
+            num_chunks: 1,
+            file_type: "txt".to_string(),
+            owner_key: [0; OwnerKey::KEY_SIZE].into(),
+            shared_keys: Default::default(),
+        };
+
+        FileDataStorage::set_file(&1, file(old_key, uploaded()));
+        FileDataStorage::set_file(&2, file(old_key, uploaded()));
+        FileDataStorage::set_file(
+            &3,
+            file(
+                old_key,
+                FileContent::Pending {
+                    alias: "alias".to_string(),
+                },
+            ),
+        );
+        assert_eq!(
+            KeyRotationStorage::progress(),
+            KeyRotationProgress::default()
+        );
+
+        KeyRotationStorage::start(KeyRotation {
+            new_public_key: new_key,
+            started_at: 1,
+        });
+        assert_eq!(
+            KeyRotationStorage::progress(),
+            KeyRotationProgress {
+                migrated: 0,
+                remaining: 2,
+            }
+        );
+
+        // migrating a file moves it to the migrated files
+        FileDataStorage::set_file(&1, file(new_key, uploaded()));
+        assert_eq!(
+            KeyRotationStorage::progress(),
+            KeyRotationProgress {
+                migrated: 1,
+                remaining: 1,
+            }
+        );
+
+        // a file uploaded for the old key must be migrated too
+        FileDataStorage::set_file(&3, file(old_key, uploaded()));
+        FileDataStorage::remove_file(&2);
+        assert_eq!(
+            KeyRotationStorage::progress(),
+            KeyRotationProgress {
+                migrated: 1,
+                remaining: 1,
+            }
+        );
+
+        KeyRotationStorage::recount();
+        assert_eq!(
+            KeyRotationStorage::progress(),
+            KeyRotationProgress {
+                migrated: 1,
+                remaining: 1,
+            }
+        );
+
+        KeyRotationStorage::clear();
+        assert_eq!(
+            KeyRotationStorage::progress(),
+            KeyRotationProgress::default()
+        );
+    }
+}
@@ -4,6 +4,12 @@ use ic_stable_structures::memory_manager::{MemoryId, MemoryManager as IcMemoryMa
 pub const OWNER_MEMORY_ID: MemoryId = MemoryId::new(1);
 pub const ORCHESTRATOR_MEMORY_ID: MemoryId = MemoryId::new(2);
 pub const OWNER_PUBLIC_KEY_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub const KEY_ROTATION_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub const OWNER_PUBLIC_KEY_VERSION_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub const LINKED_PRINCIPALS_MEMORY_ID: MemoryId = MemoryId::new(6);
+pub const OWNER_DEVICE_KEYS_MEMORY_ID: MemoryId = MemoryId::new(7);
+pub const SUSPENDED_MEMORY_ID: MemoryId = MemoryId::new(8);
+pub const RECIPIENT_PRINCIPALS_MEMORY_ID: MemoryId = MemoryId::new(9);
 
 pub const FILE_COUNT_MEMORY_ID: MemoryId = MemoryId::new(10);
 pub const FILE_ID_TO_PATH_MEMORY_ID: MemoryId = MemoryId::new(11);
@@ -13,6 +19,25 @@ pub const FILE_DATA_MEMORY_ID: MemoryId = MemoryId::new(14);
 pub const FILE_ALIAS_INDEX_MEMORY_ID: MemoryId = MemoryId::new(15);
 pub const FILE_SHARES_MEMORY_ID: MemoryId = MemoryId::new(16);
 pub const FILE_CONTENTS_MEMORY_ID: MemoryId = MemoryId::new(17);
+pub const FILE_ACCESS_LOG_MEMORY_ID: MemoryId = MemoryId::new(18);
+pub const FILE_DEVICE_KEYS_MEMORY_ID: MemoryId = MemoryId::new(19);
+
+pub const AUDIT_LOG_MEMORY_ID: MemoryId = MemoryId::new(20);
+
+pub const REENCRYPTION_MEMORY_ID: MemoryId = MemoryId::new(30);
+pub const REENCRYPTION_CONTENTS_MEMORY_ID: MemoryId = MemoryId::new(31);
+
+pub const OUTBOX_MEMORY_ID: MemoryId = MemoryId::new(40);
+pub const OUTBOX_ID_MEMORY_ID: MemoryId = MemoryId::new(41);
+
+pub const INCOMING_TRANSFERS_MEMORY_ID: MemoryId = MemoryId::new(50);
+pub const INCOMING_TRANSFER_CONTENTS_MEMORY_ID: MemoryId = MemoryId::new(51);
+pub const OUTGOING_TRANSFERS_MEMORY_ID: MemoryId = MemoryId::new(52);
+
+pub const FILE_STATS_MEMORY_ID: MemoryId = MemoryId::new(60);
+pub const KEY_ROTATION_PROGRESS_MEMORY_ID: MemoryId = MemoryId::new(61);
+
+pub const SCHEMA_VERSION_MEMORY_ID: MemoryId = MemoryId::new(70);
 
 thread_local! {
   /// Memory manager
@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+
+use did::user_canister::{OutboxEntry, OutboxEntryWithId};
+use did::utils::trap;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
+
+use super::memory::{MEMORY_MANAGER, OUTBOX_ID_MEMORY_ID, OUTBOX_MEMORY_ID};
+
+thread_local! {
+    /// Outbox of the operations to deliver to the orchestrator.
+    /// Mapping between the ID of an entry and the entry; entries are delivered in ascending order.
+    static OUTBOX: RefCell<StableBTreeMap<u64, OutboxEntry, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(OUTBOX_MEMORY_ID)))
+    );
+
+    /// ID of the next outbox entry; IDs are never reused.
+    static OUTBOX_NEXT_ID: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(OUTBOX_ID_MEMORY_ID)), 0).unwrap()
+    );
+}
+
+/// Accessor for the outbox of the operations to deliver to the orchestrator.
+pub struct OutboxStorage;
+
+impl OutboxStorage {
+    /// Push an entry to the outbox, returning its ID.
+    pub fn push(entry: OutboxEntry) -> u64 {
+        let id = OUTBOX_NEXT_ID.with_borrow(|next_id| *next_id.get());
+        if let Err(err) = OUTBOX_NEXT_ID.with_borrow_mut(|next_id| next_id.set(id + 1)) {
+            trap(format!("Failed to set next outbox ID: {:?}", err));
+        }
+        OUTBOX.with_borrow_mut(|outbox| {
+            outbox.insert(id, entry);
+        });
+
+        id
+    }
+
+    /// Get an entry by its ID.
+    pub fn get(id: u64) -> Option<OutboxEntry> {
+        OUTBOX.with_borrow(|outbox| outbox.get(&id))
+    }
+
+    /// Replace an entry.
+    pub fn set(id: u64, entry: OutboxEntry) {
+        OUTBOX.with_borrow_mut(|outbox| {
+            outbox.insert(id, entry);
+        });
+    }
+
+    /// Remove an entry, once delivered.
+    pub fn remove(id: u64) {
+        OUTBOX.with_borrow_mut(|outbox| {
+            outbox.remove(&id);
+        });
+    }
+
+    /// Get all the entries, from the oldest to the newest.
+    pub fn entries() -> Vec<OutboxEntryWithId> {
+        OUTBOX.with_borrow(|outbox| {
+            outbox
+                .iter()
+                .map(|(id, entry)| OutboxEntryWithId { id, entry })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use candid::Principal;
+    use did::user_canister::OutboxOperation;
+
+    use super::*;
+
+    #[test]
+    fn test_should_push_and_remove_entries() {
+        let entry = OutboxEntry {
+            operation: OutboxOperation::RevokeShare {
+                file_id: 1,
+                users: vec![Principal::from_slice(&[1; 29])],
+            },
+            created_at: 1,
+            attempts: 0,
+            last_error: None,
+        };
+        assert_eq!(OutboxStorage::push(entry.clone()), 0);
+        assert_eq!(OutboxStorage::push(entry.clone()), 1);
+        assert_eq!(OutboxStorage::get(1), Some(entry.clone()));
+
+        OutboxStorage::remove(0);
+        let entries = OutboxStorage::entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, 1);
+
+        // IDs are never reused
+        OutboxStorage::remove(1);
+        assert_eq!(OutboxStorage::push(entry), 2);
+    }
+}
@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+use super::files::{ChunkId, FileContent, FileId};
+use super::memory::{MEMORY_MANAGER, REENCRYPTION_CONTENTS_MEMORY_ID, REENCRYPTION_MEMORY_ID};
+
+type ContentTuple = (FileId, ChunkId);
+
+thread_local! {
+    /// Re-encryptions in progress.
+    /// Mapping between a file ID and the staged content of the file, as
+    /// [`FileContent::PartiallyUploaded`], holding the new keys.
+    static REENCRYPTIONS: RefCell<StableBTreeMap<FileId, FileContent, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(REENCRYPTION_MEMORY_ID)))
+    );
+
+    /// The re-encrypted chunks staged until the re-encryption is completed.
+    static REENCRYPTION_CONTENTS: RefCell<StableBTreeMap<ContentTuple, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(REENCRYPTION_CONTENTS_MEMORY_ID)))
+    );
+}
+
+/// Accessor for the staged re-encryptions of files.
+///
+/// The staged chunks are kept apart from the file contents, so that the file can be downloaded
+/// with its current key until the re-encryption is completed.
+pub struct ReencryptionStorage;
+
+impl ReencryptionStorage {
+    /// Get the staged content of the file being re-encrypted, if any.
+    pub fn get(file_id: &FileId) -> Option<FileContent> {
+        REENCRYPTIONS.with_borrow(|reencryptions| reencryptions.get(file_id))
+    }
+
+    /// Set the staged content of the file being re-encrypted.
+    pub fn set(file_id: &FileId, content: FileContent) {
+        REENCRYPTIONS.with_borrow_mut(|reencryptions| {
+            reencryptions.insert(*file_id, content);
+        });
+    }
+
+    /// Stage a re-encrypted chunk.
+    pub fn set_chunk(file_id: &FileId, chunk_id: &ChunkId, contents: Vec<u8>) {
+        REENCRYPTION_CONTENTS.with_borrow_mut(|staged| {
+            staged.insert((*file_id, *chunk_id), contents);
+        });
+    }
+
+    /// Take a staged chunk out of the storage.
+    pub fn take_chunk(file_id: &FileId, chunk_id: &ChunkId) -> Option<Vec<u8>> {
+        REENCRYPTION_CONTENTS.with_borrow_mut(|staged| staged.remove(&(*file_id, *chunk_id)))
+    }
+
+    /// Get whether no file is being re-encrypted.
+    pub fn is_empty() -> bool {
+        REENCRYPTIONS.with_borrow(|reencryptions| reencryptions.is_empty())
+    }
+
+    /// Drop the re-encryption of the file and its staged chunks, if any.
+    pub fn remove(file_id: &FileId) {
+        REENCRYPTIONS.with_borrow_mut(|reencryptions| {
+            reencryptions.remove(file_id);
+        });
+        REENCRYPTION_CONTENTS.with_borrow_mut(|staged| {
+            let chunks = staged
+                .range((*file_id, 0)..=(*file_id, ChunkId::MAX))
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>();
+            for key in chunks {
+                staged.remove(&key);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::BTreeMap;
+
+    use did::user_canister::OwnerKey;
+
+    use super::*;
+    use crate::storage::files::UploadedChunks;
+
+    #[test]
+    fn test_should_stage_and_remove_reencryption() {
+        let content = FileContent::PartiallyUploaded {
+            num_chunks: 2,
+            uploaded_chunks: UploadedChunks::default(),
+            file_type: "txt".to_string(),
+            owner_key: OwnerKey::new([1; OwnerKey::KEY_SIZE]),
+            shared_keys: BTreeMap::new(),
+        };
+        ReencryptionStorage::set(&1, content.clone());
+        ReencryptionStorage::set_chunk(&1, &0, vec![1, 2, 3]);
+        ReencryptionStorage::set_chunk(&1, &1, vec![4, 5, 6]);
+        ReencryptionStorage::set_chunk(&2, &0, vec![7]);
+        assert_eq!(ReencryptionStorage::get(&1), Some(content));
+
+        assert_eq!(ReencryptionStorage::take_chunk(&1, &0), Some(vec![1, 2, 3]));
+        assert_eq!(ReencryptionStorage::take_chunk(&1, &0), None);
+
+        ReencryptionStorage::remove(&1);
+        assert!(ReencryptionStorage::get(&1).is_none());
+        assert_eq!(ReencryptionStorage::take_chunk(&1, &1), None);
+        assert_eq!(ReencryptionStorage::take_chunk(&2, &0), Some(vec![7]));
+    }
+}
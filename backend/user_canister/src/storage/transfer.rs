@@ -0,0 +1,397 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use candid::Principal;
+use did::StorablePrincipal;
+use did::user_canister::Path;
+use did::utils::trap;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, Storable};
+
+use super::files::{ChunkId, File, FileContent, FileId};
+use super::memory::{
+    INCOMING_TRANSFER_CONTENTS_MEMORY_ID, INCOMING_TRANSFERS_MEMORY_ID, MEMORY_MANAGER,
+    OUTGOING_TRANSFERS_MEMORY_ID,
+};
+
+/// A transfer is identified by the user canister sending the file and the ID of the file there.
+type TransferKey = (StorablePrincipal, FileId);
+type ContentTuple = (TransferKey, ChunkId);
+
+thread_local! {
+    /// Files being transferred to the canister.
+    /// Mapping between the sending user canister and the ID of the file there, and the transfer.
+    static INCOMING_TRANSFERS: RefCell<StableBTreeMap<TransferKey, IncomingTransfer, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(INCOMING_TRANSFERS_MEMORY_ID)))
+    );
+
+    /// The chunks of the files being transferred, staged until the transfer is completed.
+    static INCOMING_TRANSFER_CONTENTS: RefCell<StableBTreeMap<ContentTuple, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(INCOMING_TRANSFER_CONTENTS_MEMORY_ID)))
+    );
+
+    /// Files sent to another user canister, awaiting the acceptance of the recipient.
+    /// Mapping between the ID of the file and the transfer.
+    static OUTGOING_TRANSFERS: RefCell<StableBTreeMap<FileId, OutgoingTransfer, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(OUTGOING_TRANSFERS_MEMORY_ID)))
+    );
+}
+
+/// A file being transferred to the canister.
+///
+/// ## Encoding
+///
+/// - 1 byte: length of the owner principal.
+/// - N bytes: owner principal.
+/// - 8 bytes: start timestamp.
+/// - 1 byte: whether all the chunks were sent.
+/// - 2 bytes: length of the path.
+/// - N bytes: path.
+/// - Remaining bytes: content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingTransfer {
+    /// The owner of the file before the transfer
+    pub owner: Principal,
+    /// When the transfer was started
+    pub started_at: u64,
+    /// Whether all the chunks were sent, so that the transfer awaits the acceptance of the owner
+    pub ready: bool,
+    /// The path of the file
+    pub path: Path,
+    /// The content of the file, as [`FileContent::PartiallyUploaded`], holding the keys
+    pub content: FileContent,
+}
+
+impl Storable for IncomingTransfer {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = vec![];
+        let owner = self.owner.as_slice();
+        bytes.push(owner.len() as u8);
+        bytes.extend_from_slice(owner);
+        bytes.extend_from_slice(&self.started_at.to_le_bytes());
+        bytes.push(self.ready as u8);
+        let path = self.path.to_bytes();
+        bytes.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&path);
+        bytes.extend_from_slice(&self.content.to_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        if bytes.is_empty() {
+            trap("Failed to decode IncomingTransfer: empty bytes");
+        }
+        let owner_len = bytes[0] as usize;
+        let mut offset = 1;
+        if bytes.len() < offset + owner_len + 11 {
+            trap("Failed to decode IncomingTransfer: not enough bytes for owner");
+        }
+        let owner = Principal::from_slice(&bytes[offset..offset + owner_len]);
+        offset += owner_len;
+
+        let started_at = u64::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("Invalid start timestamp"),
+        );
+        offset += 8;
+        let ready = bytes[offset] != 0;
+        offset += 1;
+
+        let path_len = u16::from_le_bytes(
+            bytes[offset..offset + 2]
+                .try_into()
+                .expect("Invalid path length"),
+        ) as usize;
+        offset += 2;
+        if bytes.len() < offset + path_len {
+            trap("Failed to decode IncomingTransfer: not enough bytes for path");
+        }
+        let path = Path::from_bytes(Cow::Borrowed(&bytes[offset..offset + path_len]));
+        offset += path_len;
+
+        let content = FileContent::from_bytes(Cow::Borrowed(&bytes[offset..]));
+
+        Self {
+            owner,
+            started_at,
+            ready,
+            path,
+            content,
+        }
+    }
+}
+
+/// A file sent to another user canister, awaiting the acceptance of the recipient.
+///
+/// ## Encoding
+///
+/// - 1 byte: length of the recipient principal.
+/// - N bytes: recipient principal.
+/// - 1 byte: length of the user canister principal.
+/// - N bytes: user canister principal.
+/// - 8 bytes: sent timestamp.
+/// - Remaining bytes: file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingTransfer {
+    /// The user who becomes the owner of the file
+    pub recipient: Principal,
+    /// The user canister of the recipient
+    pub user_canister: Principal,
+    /// When the file was sent
+    pub sent_at: u64,
+    /// The file as it was sent, which must be unchanged once the recipient accepts it
+    pub file: File,
+}
+
+impl Storable for OutgoingTransfer {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = vec![];
+        for principal in [self.recipient, self.user_canister] {
+            let principal = principal.as_slice();
+            bytes.push(principal.len() as u8);
+            bytes.extend_from_slice(principal);
+        }
+        bytes.extend_from_slice(&self.sent_at.to_le_bytes());
+        bytes.extend_from_slice(&self.file.to_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut offset = 0;
+        let mut principals = [Principal::anonymous(); 2];
+        for principal in &mut principals {
+            let Some(len) = bytes.get(offset).map(|len| *len as usize) else {
+                trap("Failed to decode OutgoingTransfer: not enough bytes for principal");
+            };
+            offset += 1;
+            if bytes.len() < offset + len {
+                trap("Failed to decode OutgoingTransfer: not enough bytes for principal");
+            }
+            *principal = Principal::from_slice(&bytes[offset..offset + len]);
+            offset += len;
+        }
+        if bytes.len() < offset + 8 {
+            trap("Failed to decode OutgoingTransfer: not enough bytes for sent timestamp");
+        }
+        let sent_at = u64::from_le_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("Invalid sent timestamp"),
+        );
+        offset += 8;
+
+        let file = File::from_bytes(Cow::Borrowed(&bytes[offset..]));
+        let [recipient, user_canister] = principals;
+
+        Self {
+            recipient,
+            user_canister,
+            sent_at,
+            file,
+        }
+    }
+}
+
+/// Accessor for the files being transferred to the canister.
+///
+/// The chunks are staged apart from the files, so that the file only appears once all of them
+/// are received.
+pub struct IncomingTransferStorage;
+
+impl IncomingTransferStorage {
+    /// Get the transfer of a file from the given user canister, if any.
+    pub fn get(sender: Principal, file_id: &FileId) -> Option<IncomingTransfer> {
+        INCOMING_TRANSFERS.with_borrow(|transfers| transfers.get(&(sender.into(), *file_id)))
+    }
+
+    /// Get the transfers awaiting the acceptance of the owner.
+    pub fn ready() -> Vec<(Principal, FileId, IncomingTransfer)> {
+        INCOMING_TRANSFERS.with_borrow(|transfers| {
+            transfers
+                .iter()
+                .filter(|(_, transfer)| transfer.ready)
+                .map(|((sender, file_id), transfer)| (sender.0, file_id, transfer))
+                .collect()
+        })
+    }
+
+    /// Count the transfers staged from the given user canister.
+    pub fn count_from(sender: Principal) -> usize {
+        let sender = StorablePrincipal::from(sender);
+        INCOMING_TRANSFERS
+            .with_borrow(|transfers| transfers.range((sender, 0)..=(sender, FileId::MAX)).count())
+    }
+
+    /// Get the transfers started before the given timestamp.
+    pub fn started_before(timestamp: u64) -> Vec<(Principal, FileId)> {
+        INCOMING_TRANSFERS.with_borrow(|transfers| {
+            transfers
+                .iter()
+                .filter(|(_, transfer)| transfer.started_at < timestamp)
+                .map(|((sender, file_id), _)| (sender.0, file_id))
+                .collect()
+        })
+    }
+
+    /// Set the transfer of a file from the given user canister.
+    pub fn set(sender: Principal, file_id: &FileId, transfer: IncomingTransfer) {
+        INCOMING_TRANSFERS.with_borrow_mut(|transfers| {
+            transfers.insert((sender.into(), *file_id), transfer);
+        });
+    }
+
+    /// Stage a chunk of a file being transferred.
+    pub fn set_chunk(sender: Principal, file_id: &FileId, chunk_id: &ChunkId, contents: Vec<u8>) {
+        INCOMING_TRANSFER_CONTENTS.with_borrow_mut(|staged| {
+            staged.insert(((sender.into(), *file_id), *chunk_id), contents);
+        });
+    }
+
+    /// Take a staged chunk out of the storage.
+    pub fn take_chunk(sender: Principal, file_id: &FileId, chunk_id: &ChunkId) -> Option<Vec<u8>> {
+        INCOMING_TRANSFER_CONTENTS
+            .with_borrow_mut(|staged| staged.remove(&((sender.into(), *file_id), *chunk_id)))
+    }
+
+    /// Drop the transfer of a file and its staged chunks, if any.
+    pub fn remove(sender: Principal, file_id: &FileId) {
+        let key = (StorablePrincipal::from(sender), *file_id);
+        INCOMING_TRANSFERS.with_borrow_mut(|transfers| {
+            transfers.remove(&key);
+        });
+        INCOMING_TRANSFER_CONTENTS.with_borrow_mut(|staged| {
+            let chunks = staged
+                .range((key, 0)..=(key, ChunkId::MAX))
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>();
+            for key in chunks {
+                staged.remove(&key);
+            }
+        });
+    }
+}
+
+/// Accessor for the files sent to other user canisters, awaiting the acceptance of the recipient.
+pub struct OutgoingTransferStorage;
+
+impl OutgoingTransferStorage {
+    /// Get the transfer of a file, if any.
+    pub fn get(file_id: &FileId) -> Option<OutgoingTransfer> {
+        OUTGOING_TRANSFERS.with_borrow(|transfers| transfers.get(file_id))
+    }
+
+    /// Set the transfer of a file, replacing the previous one.
+    pub fn set(file_id: &FileId, transfer: OutgoingTransfer) {
+        OUTGOING_TRANSFERS.with_borrow_mut(|transfers| {
+            transfers.insert(*file_id, transfer);
+        });
+    }
+
+    /// Drop the transfer of a file, if any.
+    pub fn remove(file_id: &FileId) {
+        OUTGOING_TRANSFERS.with_borrow_mut(|transfers| {
+            transfers.remove(file_id);
+        });
+    }
+
+    /// Get the files sent before the given timestamp.
+    pub fn sent_before(timestamp: u64) -> Vec<FileId> {
+        OUTGOING_TRANSFERS.with_borrow(|transfers| {
+            transfers
+                .iter()
+                .filter(|(_, transfer)| transfer.sent_at < timestamp)
+                .map(|(file_id, _)| file_id)
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::BTreeMap;
+
+    use did::user_canister::{OwnerKey, PublicKey};
+
+    use super::*;
+    use crate::storage::files::{FileMetadata, UploadedChunks};
+
+    #[test]
+    fn test_incoming_transfer_roundtrip() {
+        let transfer = IncomingTransfer {
+            owner: Principal::from_slice(&[1; 29]),
+            started_at: 42,
+            ready: true,
+            path: Path::new("/contracts/x.pdf").unwrap(),
+            content: FileContent::PartiallyUploaded {
+                num_chunks: 2,
+                uploaded_chunks: UploadedChunks::default(),
+                file_type: "application/pdf".to_string(),
+                owner_key: OwnerKey::new([1; OwnerKey::KEY_SIZE]),
+                shared_keys: BTreeMap::from([(
+                    Principal::from_slice(&[2; 29]),
+                    OwnerKey::new([2; OwnerKey::KEY_SIZE]),
+                )]),
+            },
+        };
+
+        let decoded = IncomingTransfer::from_bytes(transfer.to_bytes());
+        assert_eq!(transfer, decoded);
+    }
+
+    #[test]
+    fn test_outgoing_transfer_roundtrip() {
+        let transfer = OutgoingTransfer {
+            recipient: Principal::from_slice(&[1; 29]),
+            user_canister: Principal::from_slice(&[2; 10]),
+            sent_at: 42,
+            file: File {
+                metadata: FileMetadata {
+                    user_public_key: PublicKey::default(),
+                    requester_principal: Principal::from_slice(&[3; 29]),
+                    requested_at: 1,
+                    uploaded_at: Some(2),
+                },
+                content: FileContent::Uploaded {
+                    num_chunks: 2,
+                    file_type: "application/pdf".to_string(),
+                    owner_key: OwnerKey::new([1; OwnerKey::KEY_SIZE]),
+                    shared_keys: BTreeMap::new(),
+                },
+            },
+        };
+
+        let decoded = OutgoingTransfer::from_bytes(transfer.to_bytes());
+        assert_eq!(transfer, decoded);
+    }
+
+    #[test]
+    fn test_should_stage_and_remove_incoming_transfer() {
+        let sender = Principal::from_slice(&[1; 29]);
+        let other_sender = Principal::from_slice(&[2; 29]);
+        IncomingTransferStorage::set_chunk(sender, &1, &0, vec![1, 2, 3]);
+        IncomingTransferStorage::set_chunk(sender, &1, &1, vec![4, 5, 6]);
+        IncomingTransferStorage::set_chunk(other_sender, &1, &0, vec![7]);
+
+        assert_eq!(
+            IncomingTransferStorage::take_chunk(sender, &1, &0),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(IncomingTransferStorage::take_chunk(sender, &1, &0), None);
+
+        IncomingTransferStorage::remove(sender, &1);
+        assert_eq!(IncomingTransferStorage::take_chunk(sender, &1, &1), None);
+        assert_eq!(
+            IncomingTransferStorage::take_chunk(other_sender, &1, &0),
+            Some(vec![7])
+        );
+    }
+}
@@ -215,7 +215,8 @@ impl PocketIcTestEnv {
 
         let init_arg = Encode!(&OrchestratorInstallArgs::Init(OrchestratorInitArgs {
             orbit_station,
-            orbit_station_admin
+            orbit_station_admin,
+            admins: vec![admin()],
         }))
         .expect("Failed to encode init arg");
 
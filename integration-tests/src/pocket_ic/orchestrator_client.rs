@@ -1,9 +1,22 @@
 use std::time::{Duration, Instant};
 
 use candid::Principal;
+use did::FileId;
+use did::http::{HttpRequest, HttpResponse};
 use did::orchestrator::{
-    GetUsersResponse, Pagination, PublicKey, SetUserResponse, SharedFilesResponse,
-    UserCanisterResponse, WhoamiResponse,
+    AddContactRequest, AddContactResponse, AddDeviceKeyRequest, AddDeviceKeyResponse,
+    AdminFailedUserCanistersResponse, AdminReleaseUsernameResponse, AdminReserveUsernameResponse,
+    AdminReservedUsernamesResponse, AdminRetryUserCanisterCreationResponse, AdminStatsResponse,
+    AdminSuspendUserResponse, AdminUnsuspendUserResponse, AdminUsersResponse,
+    ApprovePrincipalLinkResponse, BlockResponse, ContactsResponse, CreateInviteRequest,
+    CreateInviteResponse, CursorPagination, DataCertificate, DeclineShareResponse,
+    DeleteAccountRequest, DeleteAccountResponse, DeviceId, DeviceKey, GetBlockedResponse,
+    GetBlocksRequest, GetBlocksResponse, GetUsersResponse, InvitesResponse,
+    LinkedPrincipalsResponse, Pagination, PublicKey, PublicUser, RemoveContactResponse,
+    RemoveDeviceKeyResponse, RequestPrincipalLinkResponse, RevokeInviteResponse,
+    SearchUsersResponse, SetUserResponse, ShareStateResponse, SharedByMeResponse,
+    SharedFilesResponse, UnblockResponse, UnlinkPrincipalResponse, UpdateProfileRequest,
+    UpdateProfileResponse, UpdatePublicKeyResponse, UserCanisterResponse, WhoamiResponse,
 };
 
 use super::PocketIcTestEnv;
@@ -28,6 +41,322 @@ impl OrchestratorClient<'_> {
             .expect("Failed to get orbit station")
     }
 
+    pub async fn accept_share(
+        &self,
+        caller: Principal,
+        user_canister: Principal,
+        file_id: FileId,
+    ) -> ShareStateResponse {
+        let payload = candid::encode_args((user_canister, file_id)).unwrap();
+        self.pic
+            .update::<ShareStateResponse>(self.pic.orchestrator(), caller, "accept_share", payload)
+            .await
+            .expect("Failed to accept share")
+    }
+
+    pub async fn add_contact(
+        &self,
+        caller: Principal,
+        request: AddContactRequest,
+    ) -> AddContactResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .update::<AddContactResponse>(self.pic.orchestrator(), caller, "add_contact", payload)
+            .await
+            .expect("Failed to add contact")
+    }
+
+    pub async fn add_device_key(
+        &self,
+        caller: Principal,
+        request: AddDeviceKeyRequest,
+    ) -> AddDeviceKeyResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .update::<AddDeviceKeyResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "add_device_key",
+                payload,
+            )
+            .await
+            .expect("Failed to add device key")
+    }
+
+    pub async fn admin_failed_user_canisters(
+        &self,
+        caller: Principal,
+        pagination: CursorPagination,
+    ) -> AdminFailedUserCanistersResponse {
+        let payload = candid::encode_args((pagination,)).unwrap();
+        self.pic
+            .query::<AdminFailedUserCanistersResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "admin_failed_user_canisters",
+                payload,
+            )
+            .await
+            .expect("Failed to get failed user canisters")
+    }
+
+    pub async fn admin_release_username(
+        &self,
+        caller: Principal,
+        username: String,
+    ) -> AdminReleaseUsernameResponse {
+        let payload = candid::encode_args((username,)).unwrap();
+        self.pic
+            .update::<AdminReleaseUsernameResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "admin_release_username",
+                payload,
+            )
+            .await
+            .expect("Failed to release username")
+    }
+
+    pub async fn admin_reserve_username(
+        &self,
+        caller: Principal,
+        username: String,
+    ) -> AdminReserveUsernameResponse {
+        let payload = candid::encode_args((username,)).unwrap();
+        self.pic
+            .update::<AdminReserveUsernameResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "admin_reserve_username",
+                payload,
+            )
+            .await
+            .expect("Failed to reserve username")
+    }
+
+    pub async fn admin_reserved_usernames(
+        &self,
+        caller: Principal,
+    ) -> AdminReservedUsernamesResponse {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<AdminReservedUsernamesResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "admin_reserved_usernames",
+                payload,
+            )
+            .await
+            .expect("Failed to get reserved usernames")
+    }
+
+    pub async fn admin_retry_user_canister_creation(
+        &self,
+        caller: Principal,
+        user: Principal,
+    ) -> AdminRetryUserCanisterCreationResponse {
+        let payload = candid::encode_args((user,)).unwrap();
+        self.pic
+            .update::<AdminRetryUserCanisterCreationResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "admin_retry_user_canister_creation",
+                payload,
+            )
+            .await
+            .expect("Failed to retry user canister creation")
+    }
+
+    pub async fn admin_stats(&self, caller: Principal) -> AdminStatsResponse {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<AdminStatsResponse>(self.pic.orchestrator(), caller, "admin_stats", payload)
+            .await
+            .expect("Failed to get stats")
+    }
+
+    pub async fn admin_suspend_user(
+        &self,
+        caller: Principal,
+        user: Principal,
+    ) -> AdminSuspendUserResponse {
+        let payload = candid::encode_args((user,)).unwrap();
+        self.pic
+            .update::<AdminSuspendUserResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "admin_suspend_user",
+                payload,
+            )
+            .await
+            .expect("Failed to suspend user")
+    }
+
+    pub async fn admin_unsuspend_user(
+        &self,
+        caller: Principal,
+        user: Principal,
+    ) -> AdminUnsuspendUserResponse {
+        let payload = candid::encode_args((user,)).unwrap();
+        self.pic
+            .update::<AdminUnsuspendUserResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "admin_unsuspend_user",
+                payload,
+            )
+            .await
+            .expect("Failed to unsuspend user")
+    }
+
+    pub async fn admin_users(
+        &self,
+        caller: Principal,
+        pagination: CursorPagination,
+    ) -> AdminUsersResponse {
+        let payload = candid::encode_args((pagination,)).unwrap();
+        self.pic
+            .query::<AdminUsersResponse>(self.pic.orchestrator(), caller, "admin_users", payload)
+            .await
+            .expect("Failed to get admin users")
+    }
+
+    pub async fn approve_principal_link(
+        &self,
+        caller: Principal,
+        principal: Principal,
+    ) -> ApprovePrincipalLinkResponse {
+        let payload = candid::encode_args((principal,)).unwrap();
+        self.pic
+            .update::<ApprovePrincipalLinkResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "approve_principal_link",
+                payload,
+            )
+            .await
+            .expect("Failed to approve principal link")
+    }
+
+    pub async fn block(&self, caller: Principal, principal: Principal) -> BlockResponse {
+        let payload = candid::encode_args((principal,)).unwrap();
+        self.pic
+            .update::<BlockResponse>(self.pic.orchestrator(), caller, "block", payload)
+            .await
+            .expect("Failed to block")
+    }
+
+    pub async fn contacts(&self, caller: Principal, pagination: Pagination) -> ContactsResponse {
+        let payload = candid::encode_args((pagination,)).unwrap();
+        self.pic
+            .query::<ContactsResponse>(self.pic.orchestrator(), caller, "contacts", payload)
+            .await
+            .expect("Failed to get contacts")
+    }
+
+    pub async fn create_invite(
+        &self,
+        caller: Principal,
+        request: CreateInviteRequest,
+    ) -> CreateInviteResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .update::<CreateInviteResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "create_invite",
+                payload,
+            )
+            .await
+            .expect("Failed to create invite")
+    }
+
+    pub async fn decline_share(
+        &self,
+        caller: Principal,
+        user_canister: Principal,
+        file_id: FileId,
+    ) -> DeclineShareResponse {
+        let payload = candid::encode_args((user_canister, file_id)).unwrap();
+        self.pic
+            .update::<DeclineShareResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "decline_share",
+                payload,
+            )
+            .await
+            .expect("Failed to decline share")
+    }
+
+    pub async fn delete_account(&self, caller: Principal, archive: bool) -> DeleteAccountResponse {
+        let payload = candid::encode_args((DeleteAccountRequest { archive },)).unwrap();
+        self.pic
+            .update::<DeleteAccountResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "delete_account",
+                payload,
+            )
+            .await
+            .expect("Failed to delete account")
+    }
+
+    pub async fn get_blocked(&self, caller: Principal) -> GetBlockedResponse {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<GetBlockedResponse>(self.pic.orchestrator(), caller, "get_blocked", payload)
+            .await
+            .expect("Failed to get blocked principals")
+    }
+
+    pub async fn get_blocks(&self, start: u64, length: u64) -> GetBlocksResponse {
+        let payload = candid::encode_args((GetBlocksRequest { start, length },)).unwrap();
+        self.pic
+            .query::<GetBlocksResponse>(
+                self.pic.orchestrator(),
+                Principal::anonymous(),
+                "get_blocks",
+                payload,
+            )
+            .await
+            .expect("Failed to get blocks")
+    }
+
+    pub async fn get_device_keys(&self, user: Principal) -> Vec<DeviceKey> {
+        let payload = candid::encode_args((user,)).unwrap();
+        self.pic
+            .query::<Vec<DeviceKey>>(self.pic.orchestrator(), admin(), "get_device_keys", payload)
+            .await
+            .expect("Failed to get device keys")
+    }
+
+    pub async fn get_tip_certificate(&self) -> Option<DataCertificate> {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<Option<DataCertificate>>(
+                self.pic.orchestrator(),
+                Principal::anonymous(),
+                "get_tip_certificate",
+                payload,
+            )
+            .await
+            .expect("Failed to get tip certificate")
+    }
+
+    pub async fn hide_share(
+        &self,
+        caller: Principal,
+        user_canister: Principal,
+        file_id: FileId,
+    ) -> ShareStateResponse {
+        let payload = candid::encode_args((user_canister, file_id)).unwrap();
+        self.pic
+            .update::<ShareStateResponse>(self.pic.orchestrator(), caller, "hide_share", payload)
+            .await
+            .expect("Failed to hide share")
+    }
+
     pub async fn get_users(&self, caller: Principal, pagination: Pagination) -> GetUsersResponse {
         let payload = candid::encode_args((&pagination,)).unwrap();
         self.pic
@@ -36,6 +365,65 @@ impl OrchestratorClient<'_> {
             .expect("Failed to get users")
     }
 
+    pub async fn search_users(
+        &self,
+        caller: Principal,
+        pagination: CursorPagination,
+        query: Option<String>,
+    ) -> SearchUsersResponse {
+        let payload = candid::encode_args((pagination, query)).unwrap();
+        self.pic
+            .query::<SearchUsersResponse>(self.pic.orchestrator(), caller, "search_users", payload)
+            .await
+            .expect("Failed to search users")
+    }
+
+    pub async fn http_request(&self, request: HttpRequest, caller: Principal) -> HttpResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .query::<HttpResponse>(self.pic.orchestrator(), caller, "http_request", payload)
+            .await
+            .expect("Failed to send http request")
+    }
+
+    pub async fn invites(&self, caller: Principal) -> InvitesResponse {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<InvitesResponse>(self.pic.orchestrator(), caller, "invites", payload)
+            .await
+            .expect("Failed to get invites")
+    }
+
+    pub async fn linked_principals(&self, caller: Principal) -> LinkedPrincipalsResponse {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<LinkedPrincipalsResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "linked_principals",
+                payload,
+            )
+            .await
+            .expect("Failed to get linked principals")
+    }
+
+    pub async fn request_principal_link(
+        &self,
+        caller: Principal,
+        account: Principal,
+    ) -> RequestPrincipalLinkResponse {
+        let payload = candid::encode_args((account,)).unwrap();
+        self.pic
+            .update::<RequestPrincipalLinkResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "request_principal_link",
+                payload,
+            )
+            .await
+            .expect("Failed to request principal link")
+    }
+
     pub async fn set_user(
         &self,
         caller: Principal,
@@ -49,6 +437,32 @@ impl OrchestratorClient<'_> {
             .expect("Failed to set user")
     }
 
+    pub async fn set_user_with_invite(
+        &self,
+        caller: Principal,
+        username: String,
+        public_key: PublicKey,
+        invite: String,
+    ) -> SetUserResponse {
+        let payload = candid::encode_args((username, public_key, Some(invite))).unwrap();
+        self.pic
+            .update::<SetUserResponse>(self.pic.orchestrator(), caller, "set_user", payload)
+            .await
+            .expect("Failed to set user with invite")
+    }
+
+    pub async fn shared_by_me(
+        &self,
+        caller: Principal,
+        pagination: Pagination,
+    ) -> SharedByMeResponse {
+        let payload = candid::encode_args((pagination,)).unwrap();
+        self.pic
+            .query::<SharedByMeResponse>(self.pic.orchestrator(), caller, "shared_by_me", payload)
+            .await
+            .expect("Failed to get files shared by me")
+    }
+
     pub async fn shared_files(&self, caller: Principal) -> SharedFilesResponse {
         let payload = candid::encode_args(()).unwrap();
         self.pic
@@ -57,6 +471,130 @@ impl OrchestratorClient<'_> {
             .expect("Failed to get shared files")
     }
 
+    pub async fn unblock(&self, caller: Principal, principal: Principal) -> UnblockResponse {
+        let payload = candid::encode_args((principal,)).unwrap();
+        self.pic
+            .update::<UnblockResponse>(self.pic.orchestrator(), caller, "unblock", payload)
+            .await
+            .expect("Failed to unblock")
+    }
+
+    pub async fn unlink_principal(
+        &self,
+        caller: Principal,
+        principal: Principal,
+    ) -> UnlinkPrincipalResponse {
+        let payload = candid::encode_args((principal,)).unwrap();
+        self.pic
+            .update::<UnlinkPrincipalResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "unlink_principal",
+                payload,
+            )
+            .await
+            .expect("Failed to unlink principal")
+    }
+
+    pub async fn update_profile(
+        &self,
+        caller: Principal,
+        request: UpdateProfileRequest,
+    ) -> UpdateProfileResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .update::<UpdateProfileResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "update_profile",
+                payload,
+            )
+            .await
+            .expect("Failed to update profile")
+    }
+
+    pub async fn update_public_key(
+        &self,
+        caller: Principal,
+        public_key: PublicKey,
+        version: u64,
+    ) -> UpdatePublicKeyResponse {
+        let payload = candid::encode_args((public_key, version)).unwrap();
+        self.pic
+            .update::<UpdatePublicKeyResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "update_public_key",
+                payload,
+            )
+            .await
+            .expect("Failed to update public key")
+    }
+
+    pub async fn remove_contact(
+        &self,
+        caller: Principal,
+        user: Principal,
+    ) -> RemoveContactResponse {
+        let payload = candid::encode_args((user,)).unwrap();
+        self.pic
+            .update::<RemoveContactResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "remove_contact",
+                payload,
+            )
+            .await
+            .expect("Failed to remove contact")
+    }
+
+    pub async fn remove_device_key(
+        &self,
+        caller: Principal,
+        device_id: DeviceId,
+    ) -> RemoveDeviceKeyResponse {
+        let payload = candid::encode_args((device_id,)).unwrap();
+        self.pic
+            .update::<RemoveDeviceKeyResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "remove_device_key",
+                payload,
+            )
+            .await
+            .expect("Failed to remove device key")
+    }
+
+    pub async fn revoke_invite(&self, caller: Principal, code: String) -> RevokeInviteResponse {
+        let payload = candid::encode_args((code,)).unwrap();
+        self.pic
+            .update::<RevokeInviteResponse>(
+                self.pic.orchestrator(),
+                caller,
+                "revoke_invite",
+                payload,
+            )
+            .await
+            .expect("Failed to revoke invite")
+    }
+
+    pub async fn resolve_username(
+        &self,
+        caller: Principal,
+        username: String,
+    ) -> Option<PublicUser> {
+        let payload = candid::encode_args((username,)).unwrap();
+        self.pic
+            .query::<Option<PublicUser>>(
+                self.pic.orchestrator(),
+                caller,
+                "resolve_username",
+                payload,
+            )
+            .await
+            .expect("Failed to resolve username")
+    }
+
     pub async fn who_am_i(&self, caller: Principal) -> WhoamiResponse {
         let payload = candid::encode_args(()).unwrap();
         self.pic
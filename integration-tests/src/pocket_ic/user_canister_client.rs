@@ -1,11 +1,19 @@
 use candid::Principal;
 use did::FileId;
-use did::orchestrator::PublicKey;
+use did::http::{HttpRequest, HttpResponse};
+use did::orchestrator::{Pagination, PublicKey};
 use did::user_canister::{
-    AliasInfo, DeleteFileResponse, FileDownloadResponse, FileSharingResponse, GetAliasInfoError,
-    OwnerKey, Path, PublicFileMetadata, RequestFileResponse, UploadFileAtomicRequest,
-    UploadFileAtomicResponse, UploadFileContinueRequest, UploadFileContinueResponse,
-    UploadFileError, UploadFileRequest,
+    AcceptFileTransferResponse, AliasInfo, AuditLog, AuditLogFilter, CompleteKeyRotationResponse,
+    ConfirmDownloadResponse, DeleteFileResponse, DeviceId, DeviceKey, DeviceOwnerKey,
+    FileDownloadResponse, FileSharingResponse, GetAliasInfoError, GetFileAccessLogResponse,
+    IncomingFileTransfer, OutboxEntryWithId, OwnerKey, Path, PublicFileMetadata,
+    ReconcileSharesResponse, ReencryptFileContinueResponse, ReencryptFileRequest,
+    ReencryptFileResponse, RequestFileResponse, RevokeAllForUserReport,
+    RevokeShareForUsersResponse, RevokeShareResponse, SetDeviceKeysResponse, SetPublicKeyResponse,
+    ShareFileWithUsersResponse, ShareMode, ShareReconciliationReport, StartKeyRotationResponse,
+    SubmitKeyRotationBatchResponse, TransferFileRequest, TransferFileResponse,
+    UploadFileAtomicRequest, UploadFileAtomicResponse, UploadFileContinueRequest,
+    UploadFileContinueResponse, UploadFileError, UploadFileRequest, WrappedFileKey,
 };
 
 use super::PocketIcTestEnv;
@@ -30,14 +38,177 @@ impl UserCanisterClient<'_> {
             .expect("Failed to get public key")
     }
 
-    pub async fn set_public_key(&self, public_key: PublicKey) {
+    pub async fn public_key_version(&self, caller: Principal) -> u64 {
+        self.pic
+            .query::<u64>(
+                self.pic.user_canister(),
+                caller,
+                "public_key_version",
+                vec![],
+            )
+            .await
+            .expect("Failed to get public key version")
+    }
+
+    pub async fn set_public_key(&self, public_key: PublicKey) -> SetPublicKeyResponse {
         let payload = candid::encode_args((public_key,)).unwrap();
         self.pic
-            .update::<()>(self.pic.user_canister(), admin(), "set_public_key", payload)
+            .update::<SetPublicKeyResponse>(
+                self.pic.user_canister(),
+                admin(),
+                "set_public_key",
+                payload,
+            )
             .await
             .expect("Failed to set public key")
     }
 
+    pub async fn start_key_rotation(
+        &self,
+        caller: Principal,
+        new_public_key: PublicKey,
+    ) -> StartKeyRotationResponse {
+        let payload = candid::encode_args((new_public_key,)).unwrap();
+        self.pic
+            .update::<StartKeyRotationResponse>(
+                self.pic.user_canister(),
+                caller,
+                "start_key_rotation",
+                payload,
+            )
+            .await
+            .expect("Failed to start key rotation")
+    }
+
+    pub async fn get_key_rotation_files(
+        &self,
+        caller: Principal,
+        limit: u64,
+    ) -> Vec<WrappedFileKey> {
+        let payload = candid::encode_args((limit,)).unwrap();
+        self.pic
+            .query::<Vec<WrappedFileKey>>(
+                self.pic.user_canister(),
+                caller,
+                "get_key_rotation_files",
+                payload,
+            )
+            .await
+            .expect("Failed to get key rotation files")
+    }
+
+    pub async fn submit_key_rotation_batch(
+        &self,
+        caller: Principal,
+        keys: Vec<WrappedFileKey>,
+    ) -> SubmitKeyRotationBatchResponse {
+        let payload = candid::encode_args((keys,)).unwrap();
+        self.pic
+            .update::<SubmitKeyRotationBatchResponse>(
+                self.pic.user_canister(),
+                caller,
+                "submit_key_rotation_batch",
+                payload,
+            )
+            .await
+            .expect("Failed to submit key rotation batch")
+    }
+
+    pub async fn complete_key_rotation(&self, caller: Principal) -> CompleteKeyRotationResponse {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .update::<CompleteKeyRotationResponse>(
+                self.pic.user_canister(),
+                caller,
+                "complete_key_rotation",
+                payload,
+            )
+            .await
+            .expect("Failed to complete key rotation")
+    }
+
+    pub async fn confirm_download(
+        &self,
+        caller: Principal,
+        file_id: FileId,
+    ) -> ConfirmDownloadResponse {
+        let payload = candid::encode_args((file_id,)).unwrap();
+        self.pic
+            .update::<ConfirmDownloadResponse>(
+                self.pic.user_canister(),
+                caller,
+                "confirm_download",
+                payload,
+            )
+            .await
+            .expect("Failed to confirm download")
+    }
+
+    pub async fn get_audit_log(
+        &self,
+        caller: Principal,
+        filter: AuditLogFilter,
+        pagination: Pagination,
+    ) -> AuditLog {
+        let payload = candid::encode_args((filter, pagination)).unwrap();
+        self.pic
+            .query::<AuditLog>(self.pic.user_canister(), caller, "get_audit_log", payload)
+            .await
+            .expect("Failed to get audit log")
+    }
+
+    pub async fn get_outbox(&self, caller: Principal) -> Vec<OutboxEntryWithId> {
+        self.pic
+            .query::<Vec<OutboxEntryWithId>>(self.pic.user_canister(), caller, "get_outbox", vec![])
+            .await
+            .expect("Failed to get outbox")
+    }
+
+    pub async fn get_share_reconciliation_report(
+        &self,
+        caller: Principal,
+    ) -> Option<ShareReconciliationReport> {
+        self.pic
+            .query::<Option<ShareReconciliationReport>>(
+                self.pic.user_canister(),
+                caller,
+                "get_share_reconciliation_report",
+                vec![],
+            )
+            .await
+            .expect("Failed to get share reconciliation report")
+    }
+
+    pub async fn reconcile_shares(&self, caller: Principal) -> ReconcileSharesResponse {
+        self.pic
+            .update::<ReconcileSharesResponse>(
+                self.pic.user_canister(),
+                caller,
+                "reconcile_shares",
+                candid::encode_args(()).unwrap(),
+            )
+            .await
+            .expect("Failed to reconcile shares")
+    }
+
+    pub async fn get_file_access_log(
+        &self,
+        caller: Principal,
+        file_id: FileId,
+        pagination: Pagination,
+    ) -> GetFileAccessLogResponse {
+        let payload = candid::encode_args((file_id, pagination)).unwrap();
+        self.pic
+            .query::<GetFileAccessLogResponse>(
+                self.pic.user_canister(),
+                caller,
+                "get_file_access_log",
+                payload,
+            )
+            .await
+            .expect("Failed to get file access log")
+    }
+
     pub async fn get_requests(&self, caller: Principal) -> Vec<PublicFileMetadata> {
         let payload = candid::encode_args(()).unwrap();
         self.pic
@@ -51,6 +222,19 @@ impl UserCanisterClient<'_> {
             .expect("Failed to get requests")
     }
 
+    /// Get the requests of the owner of another user canister.
+    pub async fn get_requests_of(
+        &self,
+        user_canister: Principal,
+        caller: Principal,
+    ) -> Vec<PublicFileMetadata> {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<Vec<PublicFileMetadata>>(user_canister, caller, "get_requests", payload)
+            .await
+            .expect("Failed to get requests")
+    }
+
     pub async fn get_shared_files(
         &self,
         caller: Principal,
@@ -68,6 +252,14 @@ impl UserCanisterClient<'_> {
             .expect("Failed to get shared files")
     }
 
+    pub async fn http_request(&self, request: HttpRequest, caller: Principal) -> HttpResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .query::<HttpResponse>(self.pic.user_canister(), caller, "http_request", payload)
+            .await
+            .expect("Failed to send http request")
+    }
+
     pub async fn get_alias_info(
         &self,
         alias: String,
@@ -136,6 +328,93 @@ impl UserCanisterClient<'_> {
             .expect("Failed to continue file upload")
     }
 
+    pub async fn transfer_file(
+        &self,
+        caller: Principal,
+        request: TransferFileRequest,
+    ) -> TransferFileResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .update::<TransferFileResponse>(
+                self.pic.user_canister(),
+                caller,
+                "transfer_file",
+                payload,
+            )
+            .await
+            .expect("Failed to transfer file")
+    }
+
+    pub async fn get_incoming_file_transfers_of(
+        &self,
+        user_canister: Principal,
+        caller: Principal,
+    ) -> Vec<IncomingFileTransfer> {
+        let payload = candid::encode_args(()).unwrap();
+        self.pic
+            .query::<Vec<IncomingFileTransfer>>(
+                user_canister,
+                caller,
+                "get_incoming_file_transfers",
+                payload,
+            )
+            .await
+            .expect("Failed to get incoming file transfers")
+    }
+
+    pub async fn accept_file_transfer_of(
+        &self,
+        user_canister: Principal,
+        caller: Principal,
+        sender: Principal,
+        file_id: FileId,
+    ) -> AcceptFileTransferResponse {
+        let payload = candid::encode_args((sender, file_id)).unwrap();
+        self.pic
+            .update::<AcceptFileTransferResponse>(
+                user_canister,
+                caller,
+                "accept_file_transfer",
+                payload,
+            )
+            .await
+            .expect("Failed to accept file transfer")
+    }
+
+    pub async fn reencrypt_file(
+        &self,
+        caller: Principal,
+        request: ReencryptFileRequest,
+    ) -> ReencryptFileResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .update::<ReencryptFileResponse>(
+                self.pic.user_canister(),
+                caller,
+                "reencrypt_file",
+                payload,
+            )
+            .await
+            .expect("Failed to re-encrypt file")
+    }
+
+    pub async fn reencrypt_file_continue(
+        &self,
+        caller: Principal,
+        request: UploadFileContinueRequest,
+    ) -> ReencryptFileContinueResponse {
+        let payload = candid::encode_args((request,)).unwrap();
+        self.pic
+            .update::<ReencryptFileContinueResponse>(
+                self.pic.user_canister(),
+                caller,
+                "reencrypt_file_continue",
+                payload,
+            )
+            .await
+            .expect("Failed to continue file re-encryption")
+    }
+
     pub async fn request_file(&self, path: Path, caller: Principal) -> RequestFileResponse {
         let payload = candid::encode_args((path,)).unwrap();
         self.pic
@@ -155,7 +434,18 @@ impl UserCanisterClient<'_> {
         chunk_id: u64,
         caller: Principal,
     ) -> FileDownloadResponse {
-        let payload = candid::encode_args((file_id, chunk_id)).unwrap();
+        self.download_file_for_device(file_id, chunk_id, None, caller)
+            .await
+    }
+
+    pub async fn download_file_for_device(
+        &self,
+        file_id: FileId,
+        chunk_id: u64,
+        device_id: Option<DeviceId>,
+        caller: Principal,
+    ) -> FileDownloadResponse {
+        let payload = candid::encode_args((file_id, chunk_id, device_id)).unwrap();
         self.pic
             .query::<FileDownloadResponse>(
                 self.pic.user_canister(),
@@ -174,7 +464,13 @@ impl UserCanisterClient<'_> {
         user_id: Principal,
         file_key_encrypted_for_user: OwnerKey,
     ) -> FileSharingResponse {
-        let payload = candid::encode_args((user_id, file_id, file_key_encrypted_for_user)).unwrap();
+        let payload = candid::encode_args((
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            None::<Vec<DeviceOwnerKey>>,
+        ))
+        .unwrap();
         self.pic
             .update::<FileSharingResponse>(self.pic.user_canister(), caller, "share_file", payload)
             .await
@@ -186,11 +482,19 @@ impl UserCanisterClient<'_> {
         user_id: Vec<Principal>,
         file_id: FileId,
         file_key_encrypted_for_user: Vec<OwnerKey>,
+        mode: ShareMode,
         caller: Principal,
-    ) {
-        let payload = candid::encode_args((user_id, file_id, file_key_encrypted_for_user)).unwrap();
+    ) -> ShareFileWithUsersResponse {
+        let payload = candid::encode_args((
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            mode,
+            None::<Vec<Vec<DeviceOwnerKey>>>,
+        ))
+        .unwrap();
         self.pic
-            .update::<()>(
+            .update::<ShareFileWithUsersResponse>(
                 self.pic.user_canister(),
                 caller,
                 "share_file_with_users",
@@ -200,14 +504,85 @@ impl UserCanisterClient<'_> {
             .expect("Failed to share file with users")
     }
 
-    pub async fn revoke_share(&self, user_id: Principal, file_id: FileId, caller: Principal) {
+    pub async fn set_device_keys(
+        &self,
+        caller: Principal,
+        file_id: FileId,
+        user_id: Principal,
+        device_keys: Vec<DeviceOwnerKey>,
+    ) -> SetDeviceKeysResponse {
+        let payload = candid::encode_args((file_id, user_id, device_keys)).unwrap();
+        self.pic
+            .update::<SetDeviceKeysResponse>(
+                self.pic.user_canister(),
+                caller,
+                "set_device_keys",
+                payload,
+            )
+            .await
+            .expect("Failed to set device keys")
+    }
+
+    pub async fn device_keys(&self, caller: Principal) -> Vec<DeviceKey> {
+        self.pic
+            .query::<Vec<DeviceKey>>(self.pic.user_canister(), caller, "device_keys", vec![])
+            .await
+            .expect("Failed to get device keys")
+    }
+
+    pub async fn revoke_share(
+        &self,
+        user_id: Principal,
+        file_id: FileId,
+        caller: Principal,
+    ) -> RevokeShareResponse {
         let payload = candid::encode_args((user_id, file_id)).unwrap();
         self.pic
-            .update::<()>(self.pic.user_canister(), caller, "revoke_share", payload)
+            .update::<RevokeShareResponse>(
+                self.pic.user_canister(),
+                caller,
+                "revoke_share",
+                payload,
+            )
             .await
             .expect("Failed to revoke share")
     }
 
+    pub async fn revoke_share_for_users(
+        &self,
+        user_ids: Vec<Principal>,
+        file_id: FileId,
+        caller: Principal,
+    ) -> RevokeShareForUsersResponse {
+        let payload = candid::encode_args((user_ids, file_id)).unwrap();
+        self.pic
+            .update::<RevokeShareForUsersResponse>(
+                self.pic.user_canister(),
+                caller,
+                "revoke_share_for_users",
+                payload,
+            )
+            .await
+            .expect("Failed to revoke shares")
+    }
+
+    pub async fn revoke_all_for_user(
+        &self,
+        user_id: Principal,
+        caller: Principal,
+    ) -> RevokeAllForUserReport {
+        let payload = candid::encode_args((user_id,)).unwrap();
+        self.pic
+            .update::<RevokeAllForUserReport>(
+                self.pic.user_canister(),
+                caller,
+                "revoke_all_for_user",
+                payload,
+            )
+            .await
+            .expect("Failed to revoke all shares for user")
+    }
+
     pub async fn delete_file(&self, caller: Principal, file_id: FileId) -> DeleteFileResponse {
         let payload = candid::encode_args((file_id,)).unwrap();
         self.pic
@@ -1,10 +1,22 @@
 use candid::Principal;
+use did::http::HttpRequest;
 use did::orchestrator::{
-    GetUsersResponse, Pagination, PublicKey, PublicUser, SetUserResponse, SharedFilesResponse,
-    WhoamiResponse,
+    AddContactRequest, AddContactResponse, AdminReleaseUsernameResponse,
+    AdminReserveUsernameResponse, AdminRetryUserCanisterCreationResponse, AdminStatsResponse,
+    AdminSuspendUserResponse, AdminUnsuspendUserResponse, AdminUserCanisterState,
+    AdminUsersResponse, ApprovePrincipalLinkResponse, ContactsResponse, CreateInviteRequest,
+    CreateInviteResponse, CursorPagination, DeclineShareResponse, DeleteAccountResponse,
+    GetUsersResponse, InviteState, InvitesResponse, LinkedPrincipals, LinkedPrincipalsResponse,
+    Pagination, PublicKey, PublicUser, RemoveContactResponse, RequestPrincipalLinkResponse,
+    RevokeInviteResponse, SearchUsersResponse, SetUserResponse, ShareFileMetadata, ShareOperation,
+    ShareState, SharedByMeResponse, SharedFilesResponse, UnlinkPrincipalResponse,
+    UpdateProfileRequest, UpdateProfileResponse, WhoamiResponse,
 };
-use did::user_canister::{FileSharingResponse, OwnerKey, UploadFileAtomicRequest};
-use integration_tests::actor::{admin, alice};
+use did::user_canister::{
+    FileSharingResponse, OwnerKey, PublicFileMetadata, RevokeShareResponse,
+    UploadFileAtomicRequest, UploadFileAtomicResponse,
+};
+use integration_tests::actor::{admin, alice, bob};
 use integration_tests::{OrchestratorClient, TestEnv, UserCanisterClient};
 
 #[pocket_test::test]
@@ -41,10 +53,65 @@ async fn test_should_register_user(env: PocketIcTestEnv) {
             username,
             public_key,
             ic_principal: me,
+            display_name: None,
+            key_version: 0,
         })
     );
 }
 
+#[pocket_test::test]
+async fn test_should_update_profile(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+
+    let me = alice();
+    let response = client
+        .set_user(me, "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    let response = client
+        .update_profile(
+            me,
+            UpdateProfileRequest {
+                username: "alice_new".to_string(),
+                display_name: Some("Alice".to_string()),
+            },
+        )
+        .await;
+    assert_eq!(response, UpdateProfileResponse::Ok);
+
+    let WhoamiResponse::KnownUser(user) = client.who_am_i(me).await else {
+        panic!("Expected KnownUser");
+    };
+    assert_eq!(user.username, "alice_new");
+    assert_eq!(user.display_name.as_deref(), Some("Alice"));
+
+    // the previous username is reserved and redirects to alice
+    assert!(client.username_exists("alice".to_string()).await);
+    let resolved = client.resolve_username(admin(), "alice".to_string()).await;
+    assert_eq!(resolved.map(|user| user.ic_principal), Some(me));
+}
+
+#[pocket_test::test]
+async fn test_should_compare_usernames_regardless_of_case(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+
+    let response = client
+        .set_user(alice(), "Alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+    assert!(client.username_exists("ALICE".to_string()).await);
+
+    let response = client
+        .set_user(bob(), "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::UsernameExists);
+    let response = client
+        .set_user(bob(), "b\u{200B}ob".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::InvalidUsername);
+}
+
 #[pocket_test::test]
 async fn test_should_not_register_user_if_anonymous(env: PocketIcTestEnv) {
     let client = OrchestratorClient::from(&env);
@@ -73,6 +140,267 @@ async fn test_should_not_get_users_if_anonymous(env: PocketIcTestEnv) {
     assert_eq!(users, GetUsersResponse::PermissionError);
 }
 
+#[pocket_test::test]
+async fn test_should_call_admin_methods_as_admin(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+    let pagination = CursorPagination {
+        cursor: None,
+        limit: 10,
+    };
+
+    assert_eq!(
+        client.admin_users(alice(), pagination.clone()).await,
+        AdminUsersResponse::Unauthorized
+    );
+    assert_eq!(
+        client.admin_stats(alice()).await,
+        AdminStatsResponse::Unauthorized
+    );
+
+    let AdminUsersResponse::Users(page) = client.admin_users(admin(), pagination).await else {
+        panic!("Expected users");
+    };
+    let admin_user = page
+        .users
+        .iter()
+        .find(|user| user.user.ic_principal == admin())
+        .expect("admin user not found");
+    assert_eq!(
+        admin_user.user_canister,
+        AdminUserCanisterState::Created(env.user_canister())
+    );
+    assert_eq!(
+        client
+            .admin_retry_user_canister_creation(admin(), admin())
+            .await,
+        AdminRetryUserCanisterCreationResponse::Created(env.user_canister())
+    );
+
+    let AdminStatsResponse::Stats(stats) = client.admin_stats(admin()).await else {
+        panic!("Expected stats");
+    };
+    assert_eq!(stats.users, page.total);
+    assert_eq!(stats.user_canisters, 1);
+}
+
+#[pocket_test::test]
+async fn test_should_serve_metrics(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/metrics".to_string(),
+        headers: vec![],
+        body: Default::default(),
+    };
+
+    let response = client.http_request(request, Principal::anonymous()).await;
+    assert_eq!(response.status_code, 200);
+    let metrics = String::from_utf8(response.body.into_vec()).unwrap();
+    assert!(metrics.contains("\norchestrator_user_canisters{state=\"created\"} 1\n"));
+    assert!(!metrics.contains("\norchestrator_stable_memory_pages 0\n"));
+    assert!(!metrics.contains("\norchestrator_cycle_balance 0\n"));
+}
+
+#[pocket_test::test]
+async fn test_should_suspend_user_as_admin(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+    let user_canister_client = UserCanisterClient::from(&env);
+    let request = UploadFileAtomicRequest {
+        path: "/suspended.txt".to_string().try_into().unwrap(),
+        content: vec![1, 2, 3],
+        owner_key: [1; OwnerKey::KEY_SIZE].into(),
+        file_type: "text/plain".to_string(),
+        num_chunks: 1,
+        device_keys: None,
+    };
+
+    assert_eq!(
+        client.admin_suspend_user(alice(), admin()).await,
+        AdminSuspendUserResponse::Unauthorized
+    );
+    assert_eq!(
+        client.admin_suspend_user(admin(), admin()).await,
+        AdminSuspendUserResponse::Ok
+    );
+
+    // the user canister rejects uploads, and the orchestrator the calls of the user
+    assert_eq!(
+        user_canister_client
+            .upload_file_atomic(request.clone(), admin())
+            .await,
+        UploadFileAtomicResponse::Suspended
+    );
+    assert_eq!(
+        client.shared_files(admin()).await,
+        SharedFilesResponse::Suspended
+    );
+
+    assert_eq!(
+        client.admin_unsuspend_user(admin(), admin()).await,
+        AdminUnsuspendUserResponse::Ok
+    );
+    assert!(matches!(
+        user_canister_client
+            .upload_file_atomic(request, admin())
+            .await,
+        UploadFileAtomicResponse::Ok(_)
+    ));
+}
+
+#[pocket_test::test]
+async fn test_should_reserve_username_as_admin(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+
+    assert_eq!(
+        client
+            .admin_reserve_username(admin(), "support".to_string())
+            .await,
+        AdminReserveUsernameResponse::Ok
+    );
+    assert_eq!(
+        client
+            .set_user(alice(), "Support".to_string(), PublicKey::default())
+            .await,
+        SetUserResponse::UsernameReserved
+    );
+
+    assert_eq!(
+        client
+            .admin_release_username(admin(), "support".to_string())
+            .await,
+        AdminReleaseUsernameResponse::Ok
+    );
+    assert_eq!(
+        client
+            .set_user(alice(), "Support".to_string(), PublicKey::default())
+            .await,
+        SetUserResponse::Ok
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_share_file_with_invited_user(env: PocketIcTestEnv) {
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let user_canister_client = UserCanisterClient::from(&env);
+
+    let file_id = user_canister_client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: "/invite.txt".to_string().try_into().unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            admin(),
+        )
+        .await
+        .unwrap();
+    let CreateInviteResponse::Ok(code) = orchestrator_client
+        .create_invite(
+            admin(),
+            CreateInviteRequest {
+                file_id,
+                metadata: ShareFileMetadata {
+                    file_name: "invite.txt".to_string(),
+                },
+            },
+        )
+        .await
+    else {
+        panic!("Expected invite code");
+    };
+
+    // alice signs up with the invite
+    assert_eq!(
+        orchestrator_client
+            .set_user_with_invite(
+                alice(),
+                "alice".to_string(),
+                PublicKey::default(),
+                code.clone()
+            )
+            .await,
+        SetUserResponse::Ok
+    );
+    // the invite can't be used twice
+    assert_eq!(
+        orchestrator_client
+            .set_user_with_invite(bob(), "bob".to_string(), PublicKey::default(), code.clone())
+            .await,
+        SetUserResponse::InvalidInvite
+    );
+
+    let InvitesResponse::Invites(invites) = orchestrator_client.invites(admin()).await else {
+        panic!("Expected invites");
+    };
+    assert_eq!(invites.len(), 1);
+    assert_eq!(invites[0].state, InviteState::Redeemed { user: alice() });
+
+    // the owner wraps the key for alice, completing the invite
+    assert_eq!(
+        user_canister_client
+            .share_file(admin(), file_id, alice(), [1; OwnerKey::KEY_SIZE].into())
+            .await,
+        FileSharingResponse::Ok
+    );
+    assert_eq!(
+        orchestrator_client.invites(admin()).await,
+        InvitesResponse::Invites(vec![])
+    );
+    assert_eq!(
+        orchestrator_client.revoke_invite(admin(), code).await,
+        RevokeInviteResponse::NoSuchInvite
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_search_users(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+
+    for (caller, username) in [(alice(), "alice_search"), (bob(), "bob_search")] {
+        let response = client
+            .set_user(caller, username.to_string(), PublicKey::default())
+            .await;
+        assert_eq!(response, SetUserResponse::Ok);
+    }
+
+    let SearchUsersResponse::Users(page) = client
+        .search_users(
+            alice(),
+            CursorPagination {
+                cursor: None,
+                limit: 1,
+            },
+            Some("_search".to_string()),
+        )
+        .await
+    else {
+        panic!("Expected users");
+    };
+    assert_eq!(page.total, 2);
+    assert_eq!(page.users.len(), 1);
+    assert!(page.next.is_some());
+
+    let SearchUsersResponse::Users(next_page) = client
+        .search_users(
+            alice(),
+            CursorPagination {
+                cursor: page.next,
+                limit: 1,
+            },
+            Some("_search".to_string()),
+        )
+        .await
+    else {
+        panic!("Expected users");
+    };
+    assert_eq!(next_page.users.len(), 1);
+    assert_ne!(next_page.users[0], page.users[0]);
+    assert_eq!(next_page.next, None);
+}
+
 #[pocket_test::test]
 async fn test_should_create_user_canister(env: PocketIcTestEnv) {
     let client = OrchestratorClient::from(&env);
@@ -121,6 +449,7 @@ async fn test_should_return_shared_files(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 1,
+                device_keys: None,
             },
             owner,
         )
@@ -153,3 +482,339 @@ async fn test_should_return_shared_files(env: PocketIcTestEnv) {
     assert_eq!(shared.shared_with.len(), 1);
     assert_eq!(shared.shared_with[0].ic_principal, shared_with);
 }
+
+#[pocket_test::test]
+async fn test_should_return_files_shared_by_me(env: PocketIcTestEnv) {
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+    let shared_with = alice();
+
+    let response = orchestrator_client
+        .set_user(shared_with, "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    let user_canister_client = UserCanisterClient::from(&env);
+    let file_id = user_canister_client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: "/test.txt".to_string().try_into().unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        user_canister_client
+            .share_file(owner, file_id, shared_with, [1; OwnerKey::KEY_SIZE].into())
+            .await,
+        FileSharingResponse::Ok
+    );
+
+    let pagination = Pagination {
+        offset: 0,
+        limit: 10,
+    };
+    let response = orchestrator_client
+        .shared_by_me(owner, pagination.clone())
+        .await;
+    let SharedByMeResponse::Files(page) = response else {
+        panic!("Expected Files, got: {:?}", response);
+    };
+    assert_eq!(page.total, 1);
+    assert_eq!(page.next, None);
+    assert_eq!(page.files[0].file_id, file_id);
+    assert_eq!(page.files[0].file_name, "test.txt");
+    assert_eq!(page.files[0].shared_with.len(), 1);
+    assert_eq!(page.files[0].shared_with[0].ic_principal, shared_with);
+
+    // unknown users have no user canister
+    assert_eq!(
+        orchestrator_client
+            .shared_by_me(Principal::from_slice(&[42; 29]), pagination)
+            .await,
+        SharedByMeResponse::NoUserCanister
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_add_recipients_to_contacts(env: PocketIcTestEnv) {
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+    let shared_with = alice();
+
+    let response = orchestrator_client
+        .set_user(shared_with, "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    let user_canister_client = UserCanisterClient::from(&env);
+    let file_id = user_canister_client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: "/test.txt".to_string().try_into().unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        user_canister_client
+            .share_file(owner, file_id, shared_with, [1; OwnerKey::KEY_SIZE].into())
+            .await,
+        FileSharingResponse::Ok
+    );
+
+    let pagination = Pagination {
+        offset: 0,
+        limit: 10,
+    };
+    let response = orchestrator_client
+        .contacts(owner, pagination.clone())
+        .await;
+    let ContactsResponse::Contacts(page) = response else {
+        panic!("Expected Contacts, got: {:?}", response);
+    };
+    assert_eq!(page.total, 1);
+    assert_eq!(page.contacts[0].user.ic_principal, shared_with);
+    assert!(page.contacts[0].last_shared_at.is_some());
+
+    // contacts are private
+    let response = orchestrator_client.contacts(shared_with, pagination).await;
+    let ContactsResponse::Contacts(page) = response else {
+        panic!("Expected Contacts, got: {:?}", response);
+    };
+    assert_eq!(page.total, 0);
+
+    assert_eq!(
+        orchestrator_client
+            .add_contact(
+                shared_with,
+                AddContactRequest {
+                    user: owner,
+                    nickname: Some("Admin".to_string()),
+                },
+            )
+            .await,
+        AddContactResponse::Ok
+    );
+    assert_eq!(
+        orchestrator_client.remove_contact(owner, shared_with).await,
+        RemoveContactResponse::Ok
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_decline_shared_file(env: PocketIcTestEnv) {
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+    let shared_with = alice();
+
+    // register alice on orchestrator
+    let response = orchestrator_client
+        .set_user(shared_with, "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    // admin creates a file and shares it with alice
+    let user_canister_client = UserCanisterClient::from(&env);
+    let path = "/test.txt".to_string().try_into().unwrap();
+    let file_id = user_canister_client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path,
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        user_canister_client
+            .share_file(owner, file_id, shared_with, [1; OwnerKey::KEY_SIZE].into())
+            .await,
+        FileSharingResponse::Ok
+    );
+
+    let SharedFilesResponse::SharedFiles(files) =
+        orchestrator_client.shared_files(shared_with).await
+    else {
+        panic!("Expected SharedFiles");
+    };
+    assert_eq!(
+        files[&env.user_canister()][0].share_state,
+        ShareState::Pending
+    );
+
+    // decline
+    assert_eq!(
+        orchestrator_client
+            .decline_share(shared_with, env.user_canister(), file_id)
+            .await,
+        DeclineShareResponse::Ok
+    );
+
+    // the share is gone on both the orchestrator and the user canister
+    let SharedFilesResponse::SharedFiles(files) =
+        orchestrator_client.shared_files(shared_with).await
+    else {
+        panic!("Expected SharedFiles");
+    };
+    assert!(
+        files
+            .get(&env.user_canister())
+            .is_none_or(|files| files.is_empty())
+    );
+    assert!(
+        user_canister_client
+            .get_shared_files(owner, shared_with)
+            .await
+            .is_empty()
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_record_share_log(env: PocketIcTestEnv) {
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+    let shared_with = alice();
+
+    assert!(orchestrator_client.get_tip_certificate().await.is_none());
+
+    // register alice on orchestrator
+    let response = orchestrator_client
+        .set_user(shared_with, "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    // admin creates a file, shares it with alice and revokes it
+    let user_canister_client = UserCanisterClient::from(&env);
+    let path = "/test.txt".to_string().try_into().unwrap();
+    let file_id = user_canister_client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path,
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        user_canister_client
+            .share_file(owner, file_id, shared_with, [1; OwnerKey::KEY_SIZE].into())
+            .await,
+        FileSharingResponse::Ok
+    );
+    assert_eq!(
+        user_canister_client
+            .revoke_share(shared_with, file_id, owner)
+            .await,
+        RevokeShareResponse::Ok
+    );
+
+    let response = orchestrator_client.get_blocks(0, 10).await;
+    assert_eq!(response.log_length, 2);
+    let share = &response.blocks[0].block;
+    assert_eq!(share.operation, ShareOperation::Share);
+    assert_eq!(share.user_canister, env.user_canister());
+    assert_eq!(share.file_id, file_id);
+    assert_eq!(share.user, shared_with);
+    let revoke = &response.blocks[1].block;
+    assert_eq!(revoke.operation, ShareOperation::Revoke);
+    assert_eq!(
+        revoke.parent_hash.as_ref().map(|hash| hash.as_slice()),
+        Some(share.hash().as_slice())
+    );
+
+    let certificate = orchestrator_client
+        .get_tip_certificate()
+        .await
+        .expect("Expected a tip certificate");
+    assert!(!certificate.certificate.is_empty());
+    assert!(!certificate.hash_tree.is_empty());
+}
+
+#[pocket_test::test]
+async fn test_should_start_account_deletion(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+
+    assert_eq!(
+        client.delete_account(alice(), false).await,
+        DeleteAccountResponse::NoSuchUser
+    );
+
+    assert_eq!(
+        client.delete_account(admin(), false).await,
+        DeleteAccountResponse::Ok
+    );
+    assert_eq!(
+        client.delete_account(admin(), false).await,
+        DeleteAccountResponse::DeletionPending
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_link_principal_to_account(env: PocketIcTestEnv) {
+    let client = OrchestratorClient::from(&env);
+    let user_canister_client = UserCanisterClient::from(&env);
+    // alice is another principal of admin, such as their principal on another device
+    let device = alice();
+
+    assert_eq!(
+        client.approve_principal_link(admin(), device).await,
+        ApprovePrincipalLinkResponse::NoSuchLinkRequest
+    );
+    assert_eq!(
+        client.request_principal_link(device, admin()).await,
+        RequestPrincipalLinkResponse::Ok
+    );
+    assert_eq!(
+        client.approve_principal_link(admin(), device).await,
+        ApprovePrincipalLinkResponse::Ok
+    );
+    assert_eq!(
+        client.linked_principals(device).await,
+        LinkedPrincipalsResponse::LinkedPrincipals(LinkedPrincipals {
+            account: admin(),
+            linked: vec![device],
+            requests: vec![],
+        })
+    );
+    let WhoamiResponse::KnownUser(user) = client.who_am_i(device).await else {
+        panic!("Expected a known user");
+    };
+    assert_eq!(user.ic_principal, admin());
+
+    // the linked principal acts as the owner of the user canister
+    assert!(user_canister_client.get_requests(device).await.is_empty());
+
+    assert_eq!(
+        client.unlink_principal(device, device).await,
+        UnlinkPrincipalResponse::Ok
+    );
+    let payload = candid::encode_args(()).unwrap();
+    assert!(
+        env.query::<Vec<PublicFileMetadata>>(env.user_canister(), device, "get_requests", payload)
+            .await
+            .is_err()
+    );
+}
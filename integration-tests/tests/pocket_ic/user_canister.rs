@@ -1,24 +1,66 @@
 use candid::Principal;
-use did::orchestrator::{PublicKey, SetUserResponse};
+use did::http::HttpRequest;
+use did::orchestrator::Pagination;
+use did::orchestrator::{
+    AddDeviceKeyRequest, AddDeviceKeyResponse, BlockResponse, GetBlockedResponse, PublicKey,
+    RemoveDeviceKeyResponse, SetUserResponse, UnblockResponse, UpdatePublicKeyResponse,
+    WhoamiResponse,
+};
 use did::user_canister::{
-    FileStatus, OwnerKey, Path, UploadFileAtomicRequest, UploadFileContinueRequest,
-    UploadFileRequest,
+    AcceptFileTransferResponse, CompleteKeyRotationResponse, ConfirmDownloadResponse,
+    DeviceOwnerKey, FileDownloadResponse, FileStatus, GetFileAccessLogResponse, OwnerKey, Path,
+    ReconcileSharesResponse, ReencryptFileContinueResponse, ReencryptFileRequest,
+    ReencryptFileResponse, RevokeOutcome, RevokeResult, RevokeShareForUsersResponse, RevokedShare,
+    SetPublicKeyResponse, ShareFileWithUsersResponse, ShareMode, ShareOutcome, ShareResult,
+    StartKeyRotationResponse, SubmitKeyRotationBatchResponse, TransferFileRequest,
+    TransferFileResponse, UploadFileAtomicRequest, UploadFileContinueRequest, UploadFileRequest,
+    WrappedFileKey,
 };
-use integration_tests::actor::{admin, alice};
-use integration_tests::{OrchestratorClient, UserCanisterClient};
+use integration_tests::actor::{admin, alice, bob};
+use integration_tests::{OrchestratorClient, TestEnv, UserCanisterClient};
 
 #[pocket_test::test]
 async fn test_should_set_and_get_public_key(env: PocketIcTestEnv) {
     let client = UserCanisterClient::from(&env);
     let me = Principal::from_slice(&[1; 29]);
 
-    let new_public_key = PublicKey::default();
+    let new_public_key = PublicKey::try_from(vec![3; 32]).unwrap();
     // set public key (only owner_can set it)
-    client.set_public_key(new_public_key).await;
+    let SetPublicKeyResponse::Ok(version) = client.set_public_key(new_public_key).await else {
+        panic!("Expected Ok");
+    };
     // get public key
     let public_key = client.public_key(me).await;
 
     assert_eq!(new_public_key, public_key);
+    assert_eq!(client.public_key_version(me).await, version);
+}
+
+#[pocket_test::test]
+async fn test_should_sync_public_key_from_orchestrator(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    let version = client.public_key_version(owner).await;
+    let new_public_key = PublicKey::try_from(vec![4; 32]).unwrap();
+    assert_eq!(
+        orchestrator_client
+            .update_public_key(owner, new_public_key, version)
+            .await,
+        UpdatePublicKeyResponse::Ok(version + 1)
+    );
+    assert_eq!(client.public_key(owner).await, new_public_key);
+    assert_eq!(client.public_key_version(owner).await, version + 1);
+
+    // stale updates are rejected
+    assert_eq!(
+        orchestrator_client
+            .update_public_key(owner, PublicKey::default(), version)
+            .await,
+        UpdatePublicKeyResponse::StaleVersion(version + 1)
+    );
+    assert_eq!(client.public_key(owner).await, new_public_key);
 }
 
 #[pocket_test::test]
@@ -66,6 +108,7 @@ async fn test_should_upload_file(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 1,
+                device_keys: None,
             },
             owner,
         )
@@ -120,6 +163,7 @@ async fn test_should_upload_file_atomic(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 1,
+                device_keys: None,
             },
             owner,
         )
@@ -137,6 +181,39 @@ async fn test_should_upload_file_atomic(env: PocketIcTestEnv) {
     assert_eq!(public_metadata.file_id, file_id);
 }
 
+#[pocket_test::test]
+async fn test_should_serve_metrics(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let owner = admin();
+    client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/metrics".to_string(),
+        headers: vec![],
+        body: Default::default(),
+    };
+    let response = client.http_request(request, Principal::anonymous()).await;
+    assert_eq!(response.status_code, 200);
+    let metrics = String::from_utf8(response.body.into_vec()).unwrap();
+    assert!(metrics.contains("\nuser_canister_files{state=\"uploaded\"} 1\n"));
+    assert!(metrics.contains("\nuser_canister_stored_bytes 3\n"));
+    assert!(!metrics.contains("\nuser_canister_cycle_balance 0\n"));
+}
+
 #[pocket_test::test]
 async fn test_should_upload_file_continue(env: PocketIcTestEnv) {
     let client = UserCanisterClient::from(&env);
@@ -151,6 +228,7 @@ async fn test_should_upload_file_continue(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 3,
+                device_keys: None,
             },
             owner,
         )
@@ -209,6 +287,7 @@ async fn test_should_download_file(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 3,
+                device_keys: None,
             },
             owner,
         )
@@ -254,6 +333,72 @@ async fn test_should_download_file(env: PocketIcTestEnv) {
     }
 }
 
+#[pocket_test::test]
+async fn test_should_download_file_with_device_key(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    let AddDeviceKeyResponse::Ok(device_id) = orchestrator_client
+        .add_device_key(
+            owner,
+            AddDeviceKeyRequest {
+                name: "Laptop".to_string(),
+                public_key: PublicKey::try_from(vec![3; 32]).unwrap(),
+            },
+        )
+        .await
+    else {
+        panic!("failed to add device key");
+    };
+    assert_eq!(
+        orchestrator_client.get_device_keys(owner).await,
+        client.device_keys(owner).await
+    );
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: Some(vec![DeviceOwnerKey {
+                    device_id,
+                    key: [2; OwnerKey::KEY_SIZE].into(),
+                }]),
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    match client
+        .download_file_for_device(file_id, 0, Some(device_id), owner)
+        .await
+    {
+        FileDownloadResponse::FoundFile(file_data) => {
+            assert_eq!(file_data.owner_key, [2; OwnerKey::KEY_SIZE].into());
+        }
+        response => panic!("unexpected response: {response:?}"),
+    }
+
+    // the removed device can't download the file anymore
+    assert_eq!(
+        orchestrator_client
+            .remove_device_key(owner, device_id)
+            .await,
+        RemoveDeviceKeyResponse::Ok
+    );
+    assert!(client.device_keys(owner).await.is_empty());
+    assert_eq!(
+        client
+            .download_file_for_device(file_id, 0, Some(device_id), owner)
+            .await,
+        FileDownloadResponse::DeviceKeyNotFound
+    );
+}
+
 #[pocket_test::test]
 async fn test_should_get_shared_files(env: PocketIcTestEnv) {
     let client = UserCanisterClient::from(&env);
@@ -276,6 +421,7 @@ async fn test_should_get_shared_files(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 1,
+                device_keys: None,
             },
             owner,
         )
@@ -316,6 +462,7 @@ async fn test_should_delete_file(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 1,
+                device_keys: None,
             },
             owner,
         )
@@ -356,6 +503,7 @@ async fn test_should_delete_shared_file(env: PocketIcTestEnv) {
                 file_type: "txt".to_string(),
                 owner_key: [1; OwnerKey::KEY_SIZE].into(),
                 num_chunks: 1,
+                device_keys: None,
             },
             owner,
         )
@@ -387,3 +535,558 @@ async fn test_should_delete_shared_file(env: PocketIcTestEnv) {
         "file should be deleted"
     );
 }
+
+#[pocket_test::test]
+async fn test_should_record_confirmed_downloads(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let owner = admin();
+    let path = Path::new("/test.txt").unwrap();
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path,
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        client.confirm_download(owner, file_id).await,
+        ConfirmDownloadResponse::Ok
+    );
+    assert_eq!(
+        client.confirm_download(alice(), file_id).await,
+        ConfirmDownloadResponse::PermissionError
+    );
+
+    let GetFileAccessLogResponse::Ok(access_log) = client
+        .get_file_access_log(
+            owner,
+            file_id,
+            Pagination {
+                offset: 0,
+                limit: 10,
+            },
+        )
+        .await
+    else {
+        panic!("Expected access log");
+    };
+    assert_eq!(access_log.total, 1);
+    assert_eq!(access_log.entries[0].user, owner);
+}
+
+#[pocket_test::test]
+async fn test_should_rotate_owner_key(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+
+    let new_public_key = PublicKey::try_from(vec![7; 32]).unwrap();
+    assert_eq!(
+        client.start_key_rotation(owner, new_public_key).await,
+        StartKeyRotationResponse::Ok
+    );
+
+    let keys = client.get_key_rotation_files(owner, 10).await;
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].file_id, file_id);
+    assert_eq!(
+        client
+            .submit_key_rotation_batch(
+                owner,
+                vec![WrappedFileKey {
+                    file_id,
+                    owner_key: [2; OwnerKey::KEY_SIZE].into(),
+                }],
+            )
+            .await,
+        SubmitKeyRotationBatchResponse::Ok(0)
+    );
+    assert_eq!(
+        client.complete_key_rotation(owner).await,
+        CompleteKeyRotationResponse::Ok
+    );
+
+    // the key is updated on both the user canister and the orchestrator
+    assert_eq!(client.public_key(owner).await, new_public_key);
+    let WhoamiResponse::KnownUser(user) = orchestrator_client.who_am_i(owner).await else {
+        panic!("Expected KnownUser");
+    };
+    assert_eq!(user.public_key, new_public_key);
+}
+
+#[pocket_test::test]
+async fn test_should_revoke_and_reencrypt_file(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let external_user = alice();
+    let owner = admin();
+
+    // register alice on orchestrator
+    let response = orchestrator_client
+        .set_user(external_user, "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        client
+            .share_file(
+                owner,
+                file_id,
+                external_user,
+                [1; OwnerKey::KEY_SIZE].into()
+            )
+            .await,
+        did::user_canister::FileSharingResponse::Ok
+    );
+
+    // revoke alice and re-encrypt the file
+    assert_eq!(
+        client
+            .reencrypt_file(
+                owner,
+                ReencryptFileRequest {
+                    file_id,
+                    revoke: vec![external_user],
+                    num_chunks: 2,
+                    owner_key: [2; OwnerKey::KEY_SIZE].into(),
+                    shared_keys: vec![],
+                },
+            )
+            .await,
+        ReencryptFileResponse::Ok
+    );
+    assert_eq!(
+        client.download_file(file_id, 0, external_user).await,
+        FileDownloadResponse::PermissionError
+    );
+
+    for (chunk_id, contents) in [(0, vec![4, 5]), (1, vec![6])] {
+        let response = client
+            .reencrypt_file_continue(
+                owner,
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id,
+                    contents,
+                },
+            )
+            .await;
+        let expected = if chunk_id == 1 {
+            ReencryptFileContinueResponse::Completed
+        } else {
+            ReencryptFileContinueResponse::Ok
+        };
+        assert_eq!(response, expected);
+    }
+
+    let FileDownloadResponse::FoundFile(file_data) = client.download_file(file_id, 1, owner).await
+    else {
+        panic!("File not found");
+    };
+    assert_eq!(file_data.contents, vec![6]);
+    assert_eq!(file_data.owner_key, [2; OwnerKey::KEY_SIZE].into());
+    assert_eq!(file_data.num_chunks, 2);
+}
+
+#[pocket_test::test]
+async fn test_should_reject_share_with_unknown_user(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let owner = admin();
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+
+    // alice is not registered on the orchestrator
+    let response = client
+        .share_file(owner, file_id, alice(), [1; OwnerKey::KEY_SIZE].into())
+        .await;
+    assert!(matches!(
+        response,
+        did::user_canister::FileSharingResponse::Rejected(_)
+    ));
+
+    // the share is dropped from the outbox and not applied
+    assert!(client.get_outbox(owner).await.is_empty());
+    assert!(client.get_shared_files(owner, alice()).await.is_empty());
+}
+
+#[pocket_test::test]
+async fn test_should_reconcile_shares(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    let response = orchestrator_client
+        .set_user(alice(), "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        client
+            .share_file(owner, file_id, alice(), [1; OwnerKey::KEY_SIZE].into())
+            .await,
+        did::user_canister::FileSharingResponse::Ok
+    );
+    assert!(
+        client
+            .get_share_reconciliation_report(owner)
+            .await
+            .is_none()
+    );
+
+    // both indexes agree, so there is nothing to repair
+    let ReconcileSharesResponse::Ok(report) = client.reconcile_shares(owner).await else {
+        panic!("Failed to reconcile shares");
+    };
+    assert!(report.revoked_on_orchestrator.is_empty());
+    assert!(report.dropped.is_empty());
+    assert_eq!(
+        client.get_share_reconciliation_report(owner).await,
+        Some(report)
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_not_share_file_with_user_who_blocked_owner(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    for (user, username) in [(alice(), "alice"), (bob(), "bob")] {
+        let response = orchestrator_client
+            .set_user(user, username.to_string(), PublicKey::default())
+            .await;
+        assert_eq!(response, SetUserResponse::Ok);
+    }
+    assert_eq!(
+        orchestrator_client.block(bob(), owner).await,
+        BlockResponse::Ok
+    );
+    assert_eq!(
+        orchestrator_client.get_blocked(bob()).await,
+        GetBlockedResponse::Blocked(vec![owner])
+    );
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    let response = client
+        .share_file_with_users(
+            vec![bob(), alice()],
+            file_id,
+            vec![
+                [1; OwnerKey::KEY_SIZE].into(),
+                [2; OwnerKey::KEY_SIZE].into(),
+            ],
+            ShareMode::Partial,
+            owner,
+        )
+        .await;
+    assert_eq!(
+        response,
+        ShareFileWithUsersResponse::Ok(vec![
+            ShareResult {
+                user: bob(),
+                outcome: ShareOutcome::Blocked,
+            },
+            ShareResult {
+                user: alice(),
+                outcome: ShareOutcome::Ok,
+            },
+        ])
+    );
+    assert!(client.get_shared_files(owner, bob()).await.is_empty());
+
+    assert_eq!(
+        orchestrator_client.unblock(bob(), owner).await,
+        UnblockResponse::Ok
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_share_file_with_known_users_in_partial_mode(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    // bob is not registered on the orchestrator
+    let response = orchestrator_client
+        .set_user(alice(), "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/test.txt").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "txt".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+    let keys = vec![
+        [1; OwnerKey::KEY_SIZE].into(),
+        [2; OwnerKey::KEY_SIZE].into(),
+    ];
+
+    // nobody is shared with in all-or-nothing mode
+    let response = client
+        .share_file_with_users(
+            vec![bob(), alice()],
+            file_id,
+            keys.clone(),
+            ShareMode::AllOrNothing,
+            owner,
+        )
+        .await;
+    assert_eq!(
+        response,
+        ShareFileWithUsersResponse::Ok(vec![
+            ShareResult {
+                user: bob(),
+                outcome: ShareOutcome::NoSuchUser,
+            },
+            ShareResult {
+                user: alice(),
+                outcome: ShareOutcome::NotShared,
+            },
+        ])
+    );
+    assert!(client.get_shared_files(owner, alice()).await.is_empty());
+
+    // alice is shared with in partial mode
+    let response = client
+        .share_file_with_users(
+            vec![bob(), alice()],
+            file_id,
+            keys,
+            ShareMode::Partial,
+            owner,
+        )
+        .await;
+    assert_eq!(
+        response,
+        ShareFileWithUsersResponse::Ok(vec![
+            ShareResult {
+                user: bob(),
+                outcome: ShareOutcome::NoSuchUser,
+            },
+            ShareResult {
+                user: alice(),
+                outcome: ShareOutcome::Ok,
+            },
+        ])
+    );
+    assert_eq!(client.get_shared_files(owner, alice()).await.len(), 1);
+    assert!(client.get_outbox(owner).await.is_empty());
+
+    assert_eq!(
+        client
+            .revoke_share_for_users(vec![alice(), bob()], file_id, owner)
+            .await,
+        RevokeShareForUsersResponse::Ok(vec![
+            RevokeResult {
+                user: alice(),
+                outcome: RevokeOutcome::Ok,
+            },
+            RevokeResult {
+                user: bob(),
+                outcome: RevokeOutcome::NotShared,
+            },
+        ])
+    );
+}
+
+#[pocket_test::test]
+async fn test_should_revoke_all_for_user(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    let response = orchestrator_client
+        .set_user(alice(), "alice".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+
+    let mut revoked = vec![];
+    for path in ["/a.txt", "/b.txt"] {
+        let path = Path::new(path).unwrap();
+        let file_id = client
+            .upload_file_atomic(
+                UploadFileAtomicRequest {
+                    path: path.clone(),
+                    content: vec![1, 2, 3],
+                    file_type: "txt".to_string(),
+                    owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                    num_chunks: 1,
+                    device_keys: None,
+                },
+                owner,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            client
+                .share_file(owner, file_id, alice(), [1; OwnerKey::KEY_SIZE].into())
+                .await,
+            did::user_canister::FileSharingResponse::Ok
+        );
+        revoked.push(RevokedShare { file_id, path });
+    }
+    assert_eq!(client.get_shared_files(owner, alice()).await.len(), 2);
+
+    let report = client.revoke_all_for_user(alice(), owner).await;
+    assert_eq!(report.user, alice());
+    assert_eq!(report.revoked, revoked);
+    assert!(report.cancelled.is_empty());
+    assert!(!report.pending);
+    assert!(client.get_shared_files(owner, alice()).await.is_empty());
+    assert!(client.get_outbox(owner).await.is_empty());
+}
+
+#[pocket_test::test]
+async fn test_should_transfer_file(env: PocketIcTestEnv) {
+    let client = UserCanisterClient::from(&env);
+    let orchestrator_client = OrchestratorClient::from(&env);
+    let owner = admin();
+
+    let response = orchestrator_client
+        .set_user(bob(), "bob".to_string(), PublicKey::default())
+        .await;
+    assert_eq!(response, SetUserResponse::Ok);
+    let bob_canister = orchestrator_client.wait_for_user_canister(bob()).await;
+
+    let file_id = client
+        .upload_file_atomic(
+            UploadFileAtomicRequest {
+                path: Path::new("/case/contract.pdf").unwrap(),
+                content: vec![1, 2, 3],
+                file_type: "application/pdf".to_string(),
+                owner_key: [1; OwnerKey::KEY_SIZE].into(),
+                num_chunks: 1,
+                device_keys: None,
+            },
+            owner,
+        )
+        .await
+        .unwrap();
+
+    let response = client
+        .transfer_file(
+            owner,
+            TransferFileRequest {
+                file_id,
+                recipient: bob(),
+                owner_key: [2; OwnerKey::KEY_SIZE].into(),
+                keep_shares: false,
+            },
+        )
+        .await;
+    assert_eq!(response, TransferFileResponse::Ok);
+
+    // the original is kept until the recipient accepts the transfer
+    assert_eq!(client.get_requests(owner).await.len(), 1);
+    let incoming = client
+        .get_incoming_file_transfers_of(bob_canister, bob())
+        .await;
+    assert_eq!(incoming.len(), 1);
+    assert_eq!(incoming[0].sender, env.user_canister());
+    assert_eq!(incoming[0].file_id, file_id);
+    assert_eq!(incoming[0].owner, owner);
+
+    let response = client
+        .accept_file_transfer_of(bob_canister, bob(), env.user_canister(), file_id)
+        .await;
+    let AcceptFileTransferResponse::Ok(new_file_id) = response else {
+        panic!("Failed to accept file transfer: {response:?}");
+    };
+
+    // the original is deleted once the recipient accepted the transfer
+    assert!(client.get_requests(owner).await.is_empty());
+    let requests = client.get_requests_of(bob_canister, bob()).await;
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].file_id, new_file_id);
+    assert_eq!(
+        requests[0].file_path,
+        Path::new("/case/contract.pdf").unwrap()
+    );
+}